@@ -0,0 +1,181 @@
+//! Synthetic models and palettes shared by the benchmarks, generated rather than loaded so that every size can be
+//! measured without checking large assets into the repository.
+
+#![allow(dead_code)]
+
+use bevy::{color::Color, math::UVec3};
+use bevy_vox_scene::{Voxel, VoxelData, VoxelElement, VoxelPalette, SDF};
+
+/// The widths of the synthetic models, in voxels, from a small prop to the largest model a `.vox` file can hold
+pub const SIZES: [u32; 3] = [32, 96, 256];
+
+/// The palette index of the voxel at `point` of a synthetic terrain `size` voxels wide, or 0 where it is empty.
+///
+/// The terrain has rolling hills, so that most of the voxels are hidden below a smooth surface, with caves carved out
+/// of it and a band of color every eighth of its height, so that the greedy mesher can't merge every face.
+pub fn terrain(point: UVec3, size: u32) -> u8 {
+    let p = point.as_vec3() / size as f32 * 12.0;
+    let height = (0.55 + 0.2 * (p.x * 0.7).sin() * (p.z * 0.5).cos()) * size as f32;
+    if point.y as f32 > height {
+        return 0;
+    }
+    let cave = p.x.sin() * p.y.cos() + p.y.sin() * p.z.cos() + p.z.sin() * p.x.cos();
+    if cave > 0.9 {
+        0
+    } else {
+        1 + (point.y * 8 / size) as u8
+    }
+}
+
+/// Calls `f` with every filled voxel of the synthetic terrain `size` voxels wide, and its palette index
+fn for_each_voxel(size: u32, mut f: impl FnMut(UVec3, u8)) {
+    for z in 0..size {
+        for y in 0..size {
+            for x in 0..size {
+                let point = UVec3::new(x, y, z);
+                let index = terrain(point, size);
+                if index != 0 {
+                    f(point, index);
+                }
+            }
+        }
+    }
+}
+
+/// The synthetic terrain `size` voxels wide, as it is held in memory once loaded
+pub fn terrain_data(size: u32) -> VoxelData {
+    let mut data = VoxelData::new(UVec3::splat(size), true, 1.0);
+    for_each_voxel(size, |point, index| data.set_voxel(Voxel(index), point));
+    data
+}
+
+/// A porous model `size` voxels wide, with a visible face on most voxels, unlike the terrain which hides most of its
+/// voxels below its surface
+pub fn gyroid_data(size: u32) -> VoxelData {
+    SDF::new(|point| {
+        let p = point * 0.2;
+        (p.x.sin() * p.y.cos() + p.y.sin() * p.z.cos() + p.z.sin() * p.x.cos()).abs() - 0.4
+    })
+    .map_to_voxels(UVec3::splat(size), 1.0, |distance, point| {
+        if distance >= 0.0 {
+            Voxel::EMPTY
+        } else if point.y > 0.0 {
+            Voxel(1)
+        } else {
+            Voxel(2)
+        }
+    })
+}
+
+/// The synthetic terrain `size` voxels wide, encoded as a `.vox` file.
+///
+/// The file holds a single model named `terrain`, placed by a minimal scene graph, and no palette, so it is loaded with
+/// Magica Voxel's default palette.
+pub fn terrain_vox_bytes(size: u32) -> Vec<u8> {
+    let mut voxels = Vec::new();
+    // Magica Voxel is z-up, so the terrain's y axis is written as z
+    for_each_voxel(size, |point, index| {
+        voxels.extend_from_slice(&[point.x as u8, point.z as u8, point.y as u8, index]);
+    });
+    let mut children = Vec::new();
+    write_chunk(
+        &mut children,
+        b"SIZE",
+        &[size, size, size].map(u32::to_le_bytes).concat(),
+    );
+    let count = (voxels.len() as u32 / 4).to_le_bytes();
+    write_chunk(&mut children, b"XYZI", &[&count[..], &voxels[..]].concat());
+    // the root transform, holding a group, holding the transform and shape of the model
+    write_chunk(&mut children, b"nTRN", &transform_node(0, 1, &[]));
+    let mut group = Vec::new();
+    write_i32(&mut group, 1);
+    write_dict(&mut group, &[]);
+    write_i32(&mut group, 1);
+    write_i32(&mut group, 2);
+    write_chunk(&mut children, b"nGRP", &group);
+    write_chunk(
+        &mut children,
+        b"nTRN",
+        &transform_node(2, 3, &[("_name", "terrain")]),
+    );
+    let mut shape = Vec::new();
+    write_i32(&mut shape, 3);
+    write_dict(&mut shape, &[]);
+    write_i32(&mut shape, 1);
+    write_i32(&mut shape, 0);
+    write_dict(&mut shape, &[]);
+    write_chunk(&mut children, b"nSHP", &shape);
+    let mut bytes = b"VOX ".to_vec();
+    bytes.extend_from_slice(&150_u32.to_le_bytes());
+    bytes.extend_from_slice(b"MAIN");
+    bytes.extend_from_slice(&0_u32.to_le_bytes());
+    bytes.extend_from_slice(&(children.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&children);
+    bytes
+}
+
+/// The content of an `nTRN` chunk with a single frame at the origin
+fn transform_node(id: i32, child: i32, attributes: &[(&str, &str)]) -> Vec<u8> {
+    let mut content = Vec::new();
+    write_i32(&mut content, id);
+    write_dict(&mut content, attributes);
+    write_i32(&mut content, child);
+    // reserved, then the layer, which is -1 for no layer
+    write_i32(&mut content, -1);
+    write_i32(&mut content, -1);
+    write_i32(&mut content, 1);
+    write_dict(&mut content, &[]);
+    content
+}
+
+fn write_i32(bytes: &mut Vec<u8>, value: i32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_dict(bytes: &mut Vec<u8>, entries: &[(&str, &str)]) {
+    write_i32(bytes, entries.len() as i32);
+    for text in entries.iter().flat_map(|(key, value)| [key, value]) {
+        write_i32(bytes, text.len() as i32);
+        bytes.extend_from_slice(text.as_bytes());
+    }
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+    bytes.extend_from_slice(id);
+    bytes.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&0_u32.to_le_bytes());
+    bytes.extend_from_slice(content);
+}
+
+/// A palette of `elements` diffuse colors, which bakes only the color texture
+pub fn diffuse_palette(elements: usize) -> VoxelPalette {
+    VoxelPalette::new(
+        (0..elements)
+            .map(|index| VoxelElement::new(hue(index, elements)))
+            .collect(),
+    )
+}
+
+/// A palette of `elements` colors whose emission, roughness, metalness and translucency all vary, which bakes every
+/// texture
+pub fn varied_palette(elements: usize) -> VoxelPalette {
+    VoxelPalette::new(
+        (0..elements)
+            .map(|index| {
+                let t = index as f32 / elements as f32;
+                VoxelElement {
+                    color: hue(index, elements),
+                    emission: if index % 16 == 0 { 2.0 } else { 0.0 },
+                    roughness: t,
+                    metalness: 1.0 - t,
+                    translucency: if index % 8 == 0 { 0.8 } else { 0.0 },
+                    refraction_index: 1.5,
+                }
+            })
+            .collect(),
+    )
+}
+
+fn hue(index: usize, elements: usize) -> Color {
+    Color::hsl(index as f32 / elements as f32 * 360.0, 0.6, 0.5)
+}
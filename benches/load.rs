@@ -0,0 +1,92 @@
+//! Measures loading synthetic `.vox` files of several sizes through the asset server, which runs the whole asset
+//! loader: streaming the file, building the scene graph, palette and materials, and meshing every model.
+//!
+//! Run with `cargo bench --bench load --features benchmarks`.
+
+mod common;
+
+use std::path::PathBuf;
+
+use bevy::{
+    asset::io::{
+        memory::{Dir, MemoryAssetReader},
+        AssetSource, AssetSourceId,
+    },
+    prelude::*,
+    tasks::block_on,
+};
+use bevy_vox_scene::{VoxLoaderSettings, VoxScenePlugin};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+
+/// An app that loads `.vox` files from `dir` with the `settings`, through the `bench://` asset source
+fn loader_app(dir: Dir, settings: VoxLoaderSettings) -> App {
+    let mut app = App::new();
+    app.register_asset_source(
+        AssetSourceId::from("bench"),
+        AssetSource::build().with_reader(move || Box::new(MemoryAssetReader { root: dir.clone() })),
+    )
+    .add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        ImagePlugin::default(),
+        bevy::scene::ScenePlugin,
+        VoxScenePlugin {
+            global_settings: Some(settings),
+        },
+    ))
+    .init_asset::<StandardMaterial>()
+    .init_asset::<Mesh>();
+    app
+}
+
+fn load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("load");
+    group.sample_size(10);
+    for (name, settings) in [
+        ("default", VoxLoaderSettings::default()),
+        (
+            "optimized",
+            VoxLoaderSettings {
+                optimize_meshes: true,
+                ..Default::default()
+            },
+        ),
+    ] {
+        let dir = Dir::default();
+        let mut app = loader_app(dir.clone(), settings);
+        let asset_server = app.world().resource::<AssetServer>().clone();
+        for size in common::SIZES {
+            let bytes = common::terrain_vox_bytes(size);
+            group.throughput(Throughput::Bytes(bytes.len() as u64));
+            let mut iteration = 0;
+            group.bench_function(BenchmarkId::new(name, size), |b| {
+                b.iter_batched(
+                    || {
+                        // drops the assets loaded by the last iteration
+                        app.update();
+                        // every iteration loads a new file, as the asset server only loads each path once
+                        iteration += 1;
+                        let path = PathBuf::from(format!("terrain-{size}-{iteration}.vox"));
+                        dir.insert_asset(&path, bytes.clone());
+                        path
+                    },
+                    |path| {
+                        block_on(
+                            asset_server.load_untyped_async(format!("bench://{}", path.display())),
+                        )
+                        .expect("load")
+                    },
+                    BatchSize::PerIteration,
+                )
+            });
+            app.update();
+            for iteration in 1..=iteration {
+                dir.remove_asset(&PathBuf::from(format!("terrain-{size}-{iteration}.vox")));
+            }
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
@@ -0,0 +1,80 @@
+//! Compares the bitmask face visibility pass and greedy mesher used by the mesher with `block_mesh`, which tests one
+//! voxel at a time, and measures the whole mesher, on 256³ models.
+//!
+//! Run with `cargo bench --bench meshing --features benchmarks`.
+
+mod common;
+
+use bevy_vox_scene::{
+    test_utils::{
+        count_greedy_quads, count_greedy_quads_scalar, count_visible_faces,
+        count_visible_faces_scalar, mesh_voxel_data,
+    },
+    VoxelData,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// The width of the models, the largest model a `.vox` file can hold
+const SIZE: u32 = 256;
+
+fn models() -> Vec<(&'static str, VoxelData)> {
+    vec![
+        // a smooth surface, with large runs of hidden voxels inside
+        ("terrain", common::terrain_data(SIZE)),
+        // a porous surface, with a visible face on most voxels
+        ("gyroid", common::gyroid_data(SIZE)),
+    ]
+}
+
+fn visible_faces(c: &mut Criterion) {
+    let palette = common::diffuse_palette(255);
+    let mut group = c.benchmark_group("visible_faces");
+    group.sample_size(10);
+    for (name, data) in models() {
+        assert_eq!(
+            count_visible_faces(&data, &palette),
+            count_visible_faces_scalar(&data, &palette)
+        );
+        group.bench_with_input(BenchmarkId::new("scalar", name), &data, |b, data| {
+            b.iter(|| count_visible_faces_scalar(black_box(data), &palette))
+        });
+        group.bench_with_input(BenchmarkId::new("bitmask", name), &data, |b, data| {
+            b.iter(|| count_visible_faces(black_box(data), &palette))
+        });
+    }
+    group.finish();
+}
+
+fn greedy_quads(c: &mut Criterion) {
+    let palette = common::diffuse_palette(255);
+    let mut group = c.benchmark_group("greedy_quads");
+    group.sample_size(10);
+    for (name, data) in models() {
+        assert_eq!(
+            count_greedy_quads(&data, &palette),
+            count_greedy_quads_scalar(&data, &palette)
+        );
+        group.bench_with_input(BenchmarkId::new("block_mesh", name), &data, |b, data| {
+            b.iter(|| count_greedy_quads_scalar(black_box(data), &palette))
+        });
+        group.bench_with_input(BenchmarkId::new("bitmask", name), &data, |b, data| {
+            b.iter(|| count_greedy_quads(black_box(data), &palette))
+        });
+    }
+    group.finish();
+}
+
+fn remesh(c: &mut Criterion) {
+    let palette = common::diffuse_palette(255);
+    let mut group = c.benchmark_group("remesh");
+    group.sample_size(10);
+    for (name, data) in models() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &data, |b, data| {
+            b.iter(|| mesh_voxel_data(black_box(data), &palette))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, visible_faces, greedy_quads, remesh);
+criterion_main!(benches);
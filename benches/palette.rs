@@ -0,0 +1,34 @@
+//! Measures baking palettes of several sizes into the textures of their material.
+//!
+//! Run with `cargo bench --bench palette --features benchmarks`.
+
+mod common;
+
+use bevy::{asset::Assets, render::texture::Image};
+use bevy_vox_scene::test_utils::bake_palette;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+fn palette_bake(c: &mut Criterion) {
+    let mut group = c.benchmark_group("palette_bake");
+    for elements in [16, 64, 255] {
+        for (name, palette) in [
+            ("diffuse", common::diffuse_palette(elements)),
+            ("varied", common::varied_palette(elements)),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, elements), &palette, |b, palette| {
+                b.iter_batched(
+                    Assets::<Image>::default,
+                    |mut images| {
+                        bake_palette(palette, &mut images);
+                        images
+                    },
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, palette_bake);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+//! Measures meshing synthetic models of several sizes, and remeshing them after a single voxel has been edited, as
+//! happens on every edit made with `ModifyVoxelCommandsExt`.
+//!
+//! Run with `cargo bench --bench remesh --features benchmarks`.
+
+mod common;
+
+use bevy::math::UVec3;
+use bevy_vox_scene::{test_utils::mesh_voxel_data, Voxel};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+fn mesh(c: &mut Criterion) {
+    let palette = common::diffuse_palette(255);
+    let mut group = c.benchmark_group("mesh");
+    group.sample_size(10);
+    for size in common::SIZES {
+        let data = common::terrain_data(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| mesh_voxel_data(black_box(data), &palette))
+        });
+    }
+    group.finish();
+}
+
+fn remesh_after_edit(c: &mut Criterion) {
+    let palette = common::diffuse_palette(255);
+    let mut group = c.benchmark_group("remesh_after_edit");
+    group.sample_size(10);
+    for size in common::SIZES {
+        let data = common::terrain_data(size);
+        // a voxel in the middle of the model, near the surface of the terrain
+        let point = UVec3::new(size / 2, size / 2, size / 2);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter_batched(
+                || data.clone(),
+                |mut data| {
+                    data.set_voxel(Voxel(1), point);
+                    mesh_voxel_data(&data, &palette)
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, mesh, remesh_after_edit);
+criterion_main!(benches);
@@ -1,6 +1,8 @@
 use bevy::{core_pipeline::bloom::BloomSettings, prelude::*};
-use bevy_vox_scene::VoxScenePlugin;
-use utilities::{PanOrbitCamera, PanOrbitCameraPlugin};
+use bevy_vox_scene::{
+    utilities::{PanOrbitCamera, PanOrbitCameraPlugin},
+    VoxScenePlugin,
+};
 
 fn main() {
     App::new()
@@ -8,10 +8,12 @@ use bevy::{
     input::keyboard::KeyboardInput,
     prelude::*,
 };
-use bevy_vox_scene::VoxScenePlugin;
+use bevy_vox_scene::{
+    utilities::{PanOrbitCamera, PanOrbitCameraPlugin},
+    VoxScenePlugin,
+};
 use rand::Rng;
 use std::f32::consts::PI;
-use utilities::{PanOrbitCamera, PanOrbitCameraPlugin};
 
 /// Uses an observer triggered by `VoxelModelInstance` being added to add extra components into the scene graph.
 /// Press any key to toggle the fish tank black-light on and off
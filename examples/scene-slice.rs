@@ -7,8 +7,10 @@ use bevy::{
     },
     prelude::*,
 };
-use bevy_vox_scene::VoxScenePlugin;
-use utilities::{PanOrbitCamera, PanOrbitCameraPlugin};
+use bevy_vox_scene::{
+    utilities::{PanOrbitCamera, PanOrbitCameraPlugin},
+    VoxScenePlugin,
+};
 
 /// Asset labels aren't just for loading individual models within a scene, they can load any named group within a scene, a "slice" of the scene
 /// Here, just the workstation is loaded from the example scene
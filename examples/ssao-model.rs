@@ -7,8 +7,10 @@ use bevy::{
     pbr::ScreenSpaceAmbientOcclusionBundle,
     prelude::*,
 };
-use bevy_vox_scene::VoxScenePlugin;
-use utilities::{PanOrbitCamera, PanOrbitCameraPlugin};
+use bevy_vox_scene::{
+    utilities::{PanOrbitCamera, PanOrbitCameraPlugin},
+    VoxScenePlugin,
+};
 
 /// Press any key to toggle Screen Space Ambient Occlusion
 fn main() {
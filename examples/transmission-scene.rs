@@ -8,8 +8,10 @@ use bevy::{
     pbr::{VolumetricFogSettings, VolumetricLight},
     prelude::*,
 };
-use bevy_vox_scene::{VoxLoaderSettings, VoxScenePlugin};
-use utilities::{PanOrbitCamera, PanOrbitCameraPlugin};
+use bevy_vox_scene::{
+    utilities::{PanOrbitCamera, PanOrbitCameraPlugin},
+    VoxLoaderSettings, VoxScenePlugin,
+};
 
 fn main() {
     let mut app = App::new();
@@ -17,12 +19,10 @@ fn main() {
     app.add_plugins((
         DefaultPlugins,
         PanOrbitCameraPlugin,
-        VoxScenePlugin {
-            global_settings: Some(VoxLoaderSettings {
-                voxel_size: 0.05,
-                ..default()
-            }),
-        },
+        VoxScenePlugin::with_settings(VoxLoaderSettings {
+            voxel_size: 0.05,
+            ..default()
+        }),
     ))
     .add_systems(Startup, setup);
 
@@ -6,15 +6,15 @@ use bevy::{
         dof::{DepthOfFieldMode, DepthOfFieldSettings},
         tonemapping::Tonemapping,
     },
+    math::bounding::BoundingSphere,
     prelude::*,
     time::common_conditions::on_timer,
 };
 use bevy_vox_scene::{
+    utilities::{PanOrbitCamera, PanOrbitCameraPlugin},
     ModifyVoxelCommandsExt, VoxScenePlugin, Voxel, VoxelModel, VoxelModelInstance, VoxelQueryable,
-    VoxelRegion, VoxelRegionMode,
 };
 use rand::Rng;
-use utilities::{PanOrbitCamera, PanOrbitCameraPlugin};
 
 #[derive(States, Debug, Clone, Default, Hash, Eq, PartialEq)]
 enum AppState {
@@ -177,18 +177,14 @@ fn update_snow(
             if voxel == Voxel::EMPTY {
                 continue;
             };
-            let flake_radius = 2;
-            let radius_squared = flake_radius * flake_radius;
-            let flake_region = VoxelRegion {
-                origin: vox_pos - IVec3::splat(flake_radius),
-                size: IVec3::splat(1 + (flake_radius * 2)),
-            };
-            commands.modify_voxel_model(
+            let flake_region = BoundingSphere::new(snowflake_xform.translation, 2.0);
+            commands.modify_voxel_model_in_world(
                 item_instance.clone(),
-                VoxelRegionMode::Box(flake_region),
+                item_xform,
+                flake_region,
                 move |pos, voxel, model| {
-                    // a signed distance field for a sphere, but _only_ drawing it on empty cells directly above solid voxels
-                    if *voxel == Voxel::EMPTY && pos.distance_squared(vox_pos) <= radius_squared {
+                    // draw snow within the sphere, but _only_ on empty cells directly above solid voxels
+                    if *voxel == Voxel::EMPTY {
                         if let Ok(voxel_below) = model.get_voxel_at_point(pos - IVec3::Y) {
                             if voxel_below != Voxel::EMPTY {
                                 // draw our snow material
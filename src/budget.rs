@@ -0,0 +1,192 @@
+use bevy::{
+    asset::{AssetEvent, AssetId, Assets},
+    ecs::{
+        event::EventReader,
+        query::With,
+        system::{Query, Res, ResMut, Resource},
+    },
+    math::Vec3A,
+    render::{camera::Camera, mesh::Mesh},
+    transform::components::GlobalTransform,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{VoxelContext, VoxelModel, VoxelModelInstance, VoxelWorldIndex};
+
+/// Resource that tracks the memory used by voxel models, and optionally evicts the meshes of models that are far away
+/// from every camera.
+///
+/// Evicted models keep their voxel data, so they can still be queried and modified, and are remeshed as soon as one of
+/// their instances comes back within range of a camera. The memory used by each model is only measured again when the
+/// model or its mesh changes.
+#[derive(Resource, Default, Debug)]
+pub struct VoxelMemoryBudget {
+    /// The meshes of models with no instance within this distance of a camera are removed from [`Assets<Mesh>`].
+    /// Defaults to `None`, which disables eviction.
+    pub eviction_distance: Option<f32>,
+    voxel_bytes: usize,
+    mesh_bytes: usize,
+    evicted: HashSet<AssetId<VoxelModel>>,
+    /// The memory used by each model, as of the last change to the model or its mesh
+    usage: HashMap<AssetId<VoxelModel>, ModelMemoryUsage>,
+    /// The model that each tracked mesh belongs to
+    mesh_models: HashMap<AssetId<Mesh>, AssetId<VoxelModel>>,
+}
+
+#[derive(Debug)]
+struct ModelMemoryUsage {
+    mesh: AssetId<Mesh>,
+    voxel_bytes: usize,
+    mesh_bytes: usize,
+}
+
+impl VoxelMemoryBudget {
+    /// Creates a budget that evicts the meshes of models further than `distance` from every camera
+    pub fn with_eviction_distance(distance: f32) -> Self {
+        Self {
+            eviction_distance: Some(distance),
+            ..Default::default()
+        }
+    }
+
+    /// The total size of the voxel data of every loaded model, in bytes, as of the last update
+    pub fn voxel_bytes(&self) -> usize {
+        self.voxel_bytes
+    }
+
+    /// The approximate size of the vertex and index buffers of every model mesh that is currently resident, in bytes,
+    /// as of the last update
+    pub fn mesh_bytes(&self) -> usize {
+        self.mesh_bytes
+    }
+
+    /// Returns true if the mesh of the model is currently evicted
+    pub fn is_evicted(&self, model: AssetId<VoxelModel>) -> bool {
+        self.evicted.contains(&model)
+    }
+
+    /// Measures the memory used by the model again, or stops tracking it if it has been removed
+    fn track_model(
+        &mut self,
+        id: AssetId<VoxelModel>,
+        model: Option<&VoxelModel>,
+        meshes: &Assets<Mesh>,
+    ) {
+        if let Some(usage) = self.usage.remove(&id) {
+            self.voxel_bytes -= usage.voxel_bytes;
+            self.mesh_bytes -= usage.mesh_bytes;
+            self.mesh_models.remove(&usage.mesh);
+        }
+        let Some(model) = model else {
+            self.evicted.remove(&id);
+            return;
+        };
+        let usage = ModelMemoryUsage {
+            mesh: model.mesh.id(),
+            voxel_bytes: model.memory_usage(),
+            mesh_bytes: meshes.get(&model.mesh).map_or(0, mesh_memory_usage),
+        };
+        self.voxel_bytes += usage.voxel_bytes;
+        self.mesh_bytes += usage.mesh_bytes;
+        self.mesh_models.insert(usage.mesh, id);
+        self.usage.insert(id, usage);
+    }
+
+    /// Measures the mesh again, if it belongs to a tracked model
+    fn track_mesh(&mut self, id: AssetId<Mesh>, meshes: &Assets<Mesh>) {
+        let Some(usage) = self
+            .mesh_models
+            .get(&id)
+            .and_then(|model| self.usage.get_mut(model))
+        else {
+            return;
+        };
+        self.mesh_bytes -= usage.mesh_bytes;
+        usage.mesh_bytes = meshes.get(id).map_or(0, mesh_memory_usage);
+        self.mesh_bytes += usage.mesh_bytes;
+    }
+}
+
+fn mesh_memory_usage(mesh: &Mesh) -> usize {
+    mesh.count_vertices() * mesh.get_vertex_size() as usize
+        + mesh
+            .indices()
+            .map_or(0, |indices| indices.len() * std::mem::size_of::<u32>())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_voxel_memory_budget(
+    mut budget: ResMut<VoxelMemoryBudget>,
+    mut model_events: EventReader<AssetEvent<VoxelModel>>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    index: Res<VoxelWorldIndex>,
+    cameras: Query<&GlobalTransform, With<Camera>>,
+    instances: Query<&VoxelModelInstance>,
+    models: Res<Assets<VoxelModel>>,
+    contexts: Res<Assets<VoxelContext>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for event in model_events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } | AssetEvent::Removed { id } => {
+                budget.track_model(*id, models.get(*id), &meshes);
+            }
+            _ => {}
+        }
+    }
+    for event in mesh_events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } | AssetEvent::Removed { id } => {
+                budget.track_mesh(*id, &meshes);
+            }
+            _ => {}
+        }
+    }
+    let mut restored: Vec<AssetId<VoxelModel>> = Vec::new();
+    if let Some(eviction_distance) = budget.eviction_distance {
+        let camera_positions: Vec<Vec3A> = cameras
+            .iter()
+            .map(|xform| xform.translation_vec3a())
+            .collect();
+        let mut in_range: HashMap<AssetId<VoxelModel>, bool> = HashMap::new();
+        for (_, entry) in index.iter() {
+            let near_camera = camera_positions.iter().any(|camera| {
+                let closest = camera.clamp(entry.aabb.min.into(), entry.aabb.max.into());
+                closest.distance(*camera) <= eviction_distance
+            });
+            *in_range.entry(entry.model.id()).or_default() |= near_camera;
+        }
+        for (id, near_camera) in in_range {
+            let is_evicted = budget.evicted.contains(&id);
+            if near_camera && is_evicted {
+                restored.push(id);
+            } else if !near_camera && !is_evicted {
+                let Some(model) = models.get(id) else {
+                    continue;
+                };
+                meshes.remove(&model.mesh);
+                budget.track_mesh(model.mesh.id(), &meshes);
+                budget.evicted.insert(id);
+            }
+        }
+    } else {
+        // eviction is disabled, so restore every evicted mesh
+        restored.extend(budget.evicted.iter().copied());
+    }
+    for id in restored {
+        // models don't store their context, so find it via one of their instances
+        let Some(context) = instances
+            .iter()
+            .find(|instance| instance.model.id() == id)
+            .and_then(|instance| contexts.get(&instance.context))
+        else {
+            continue;
+        };
+        if let Some(model) = models.get(id) {
+            let (mesh, _) = model.data.remesh(&context.palette);
+            meshes.insert(&model.mesh, mesh);
+            budget.track_mesh(model.mesh.id(), &meshes);
+        }
+        budget.evicted.remove(&id);
+    }
+}
@@ -0,0 +1,137 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        system::{Commands, Query, Res},
+        world::{Command, World},
+    },
+    hierarchy::Children,
+    math::Vec3,
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    time::Time,
+    transform::components::Transform,
+};
+
+/// Extension to [`Commands`] for animating scenes apart, to show how they are assembled
+pub trait ExplodeVoxelSceneCommandsExt {
+    /// Animates the descendants of `root` apart and back together, as an exploded view of the scene hierarchy.
+    ///
+    /// Every descendant with a [`Transform`] moves away from its parent along its local offset, so the nested groups of
+    /// a `.vox` file spread out level by level, and the scene is back in its original pose after `duration` seconds.
+    /// Exploding a scene that is still animating restarts the animation from its original pose.
+    ///
+    /// ### Arguments
+    /// * `root` - the entity at the top of the hierarchy, such as the entity holding a [`bevy::scene::SceneBundle`]
+    /// * `spacing` - the furthest each descendant moves from its original position, in the space of its parent
+    /// * `duration` - the number of seconds for the whole animation, apart and back
+    fn explode_voxel_scene(&mut self, root: Entity, spacing: f32, duration: f32) -> &mut Self;
+}
+
+impl ExplodeVoxelSceneCommandsExt for Commands<'_, '_> {
+    fn explode_voxel_scene(&mut self, root: Entity, spacing: f32, duration: f32) -> &mut Self {
+        self.add(ExplodeVoxelScene {
+            root,
+            spacing,
+            duration,
+        });
+        self
+    }
+}
+
+/// An exploded view in progress, added to the root entity by
+/// [`ExplodeVoxelSceneCommandsExt::explode_voxel_scene`] and removed when the animation ends
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VoxelExplodedView {
+    /// The furthest each descendant moves from its original position
+    pub spacing: f32,
+    /// The number of seconds for the whole animation
+    pub duration: f32,
+    /// The number of seconds since the animation started
+    pub elapsed: f32,
+    #[reflect(ignore)]
+    rest: Vec<(Entity, Vec3)>,
+}
+
+impl VoxelExplodedView {
+    /// How far apart the scene is, from 0 in its original pose to 1 at the midpoint of the animation
+    pub fn separation(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 0.0;
+        }
+        let progress = (self.elapsed / self.duration).clamp(0.0, 1.0);
+        (1.0 - (progress * TAU).cos()) * 0.5
+    }
+}
+
+struct ExplodeVoxelScene {
+    root: Entity,
+    spacing: f32,
+    duration: f32,
+}
+
+impl Command for ExplodeVoxelScene {
+    fn apply(self, world: &mut World) {
+        let Some(root) = world.get_entity(self.root) else {
+            return;
+        };
+        // keep the original pose of a scene that is already exploding
+        let rest = match root.get::<VoxelExplodedView>() {
+            Some(view) => view.rest.clone(),
+            None => {
+                let mut rest = Vec::new();
+                let mut stack: Vec<Entity> = root
+                    .get::<Children>()
+                    .map(|children| children.to_vec())
+                    .unwrap_or_default();
+                while let Some(entity) = stack.pop() {
+                    let Some(entity_ref) = world.get_entity(entity) else {
+                        continue;
+                    };
+                    if let Some(transform) = entity_ref.get::<Transform>() {
+                        rest.push((entity, transform.translation));
+                    }
+                    if let Some(children) = entity_ref.get::<Children>() {
+                        stack.extend(children.iter().copied());
+                    }
+                }
+                rest
+            }
+        };
+        world.entity_mut(self.root).insert(VoxelExplodedView {
+            spacing: self.spacing,
+            duration: self.duration,
+            elapsed: 0.0,
+            rest,
+        });
+    }
+}
+
+pub(crate) fn update_exploded_views(
+    mut commands: Commands,
+    mut views: Query<(Entity, &mut VoxelExplodedView)>,
+    mut transforms: Query<&mut Transform>,
+    time: Res<Time>,
+) {
+    for (root, mut view) in views.iter_mut() {
+        view.elapsed += time.delta_seconds();
+        let finished = view.elapsed >= view.duration;
+        let distance = if finished {
+            0.0
+        } else {
+            view.spacing * view.separation()
+        };
+        for (entity, rest) in view.rest.iter() {
+            let Ok(mut transform) = transforms.get_mut(*entity) else {
+                continue;
+            };
+            transform.translation = *rest + rest.normalize_or_zero() * distance;
+        }
+        if finished {
+            commands.entity(root).remove::<VoxelExplodedView>();
+        }
+    }
+}
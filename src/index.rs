@@ -0,0 +1,136 @@
+use bevy::{
+    asset::{AssetEvent, AssetId, Assets, Handle},
+    ecs::{
+        entity::Entity,
+        event::EventReader,
+        query::{Changed, Or},
+        removal_detection::RemovedComponents,
+        system::{Query, Res, ResMut, Resource},
+    },
+    math::{
+        bounding::{Aabb3d, IntersectsVolume},
+        Vec3, Vec3A,
+    },
+    transform::components::GlobalTransform,
+    utils::HashMap,
+};
+
+use crate::{VoxelModel, VoxelModelInstance};
+
+/// An entry in the [`VoxelWorldIndex`]
+#[derive(Clone, Debug)]
+pub struct VoxelIndexEntry {
+    /// Handle to the model that the entity is an instance of
+    pub model: Handle<VoxelModel>,
+    /// The bounds of the model in world space
+    pub aabb: Aabb3d,
+}
+
+/// Resource mapping every entity with a [`VoxelModelInstance`] to its model and its world-space bounds.
+///
+/// The index is kept up to date by the [`crate::VoxScenePlugin`] in [`bevy::app::PostUpdate`], after transforms have
+/// been propagated, so that global queries such as "every voxel instance intersecting this box" don't need to iterate
+/// every entity in the world.
+#[derive(Resource, Default, Debug)]
+pub struct VoxelWorldIndex {
+    entries: HashMap<Entity, VoxelIndexEntry>,
+}
+
+impl VoxelWorldIndex {
+    /// Returns the entry for the entity, if it is an indexed voxel instance
+    pub fn get(&self, entity: Entity) -> Option<&VoxelIndexEntry> {
+        self.entries.get(&entity)
+    }
+
+    /// Iterates over every indexed voxel instance
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &VoxelIndexEntry)> {
+        self.entries.iter().map(|(entity, entry)| (*entity, entry))
+    }
+
+    /// The number of indexed voxel instances
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there are no indexed voxel instances
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over every voxel instance whose world-space bounds intersect `aabb`
+    pub fn intersecting<'a>(&'a self, aabb: &'a Aabb3d) -> impl Iterator<Item = Entity> + 'a {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.aabb.intersects(aabb))
+            .map(|(entity, _)| *entity)
+    }
+
+    /// Iterates over every instance of the model
+    pub fn instances_of(&self, model: AssetId<VoxelModel>) -> impl Iterator<Item = Entity> + '_ {
+        self.entries
+            .iter()
+            .filter(move |(_, entry)| entry.model.id() == model)
+            .map(|(entity, _)| *entity)
+    }
+
+    /// Removes the entry of an entity straight away, rather than when the index is next updated
+    pub(crate) fn remove(&mut self, entity: Entity) {
+        self.entries.remove(&entity);
+    }
+}
+
+/// Computes the world-space bounds of a model with the supplied transform
+pub(crate) fn world_aabb(model: &VoxelModel, transform: &GlobalTransform) -> Aabb3d {
+    let affine = transform.affine();
+    let half_extents = Vec3A::from(model.data._size().as_vec3() * model.data.voxel_size * 0.5);
+    let world_half_extents = affine.matrix3.x_axis.abs() * half_extents.x
+        + affine.matrix3.y_axis.abs() * half_extents.y
+        + affine.matrix3.z_axis.abs() * half_extents.z;
+    Aabb3d::new(
+        Vec3::from(affine.translation),
+        Vec3::from(world_half_extents),
+    )
+}
+
+pub(crate) fn update_voxel_world_index(
+    mut index: ResMut<VoxelWorldIndex>,
+    changed: Query<
+        (Entity, &VoxelModelInstance, &GlobalTransform),
+        Or<(Changed<GlobalTransform>, Changed<VoxelModelInstance>)>,
+    >,
+    all: Query<(Entity, &VoxelModelInstance, &GlobalTransform)>,
+    mut removed: RemovedComponents<VoxelModelInstance>,
+    mut model_events: EventReader<AssetEvent<VoxelModel>>,
+    models: Res<Assets<VoxelModel>>,
+) {
+    for entity in removed.read() {
+        index.entries.remove(&entity);
+    }
+    let mut update = |entity: Entity, instance: &VoxelModelInstance, xform: &GlobalTransform| {
+        if let Some(model) = models.get(&instance.model) {
+            index.entries.insert(
+                entity,
+                VoxelIndexEntry {
+                    model: instance.model.clone(),
+                    aabb: world_aabb(model, xform),
+                },
+            );
+        }
+    };
+    for (entity, instance, xform) in changed.iter() {
+        update(entity, instance, xform);
+    }
+    for event in model_events.read() {
+        let (AssetEvent::Added { id }
+        | AssetEvent::Modified { id }
+        | AssetEvent::LoadedWithDependencies { id }) = event
+        else {
+            continue;
+        };
+        for (entity, instance, xform) in all.iter() {
+            if instance.model.id() == *id {
+                update(entity, instance, xform);
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use bevy::{
+    asset::{AssetServer, Handle, LoadState},
+    ecs::{
+        entity::Entity,
+        event::{Event, EventWriter},
+        query::With,
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    hierarchy::{Children, DespawnRecursiveExt, HierarchyQueryExt},
+    log::warn,
+    render::view::Visibility,
+    scene::{Scene, SceneBundle},
+    time::Time,
+};
+
+use crate::{
+    DespawnVoxelSceneCommandsExt, VoxelInstanceMaterialParams, VoxelLoadProgress,
+    VoxelModelInstance, VoxelSceneInstance,
+};
+
+/// Resource switching between `.vox` scenes used as levels, such as the rooms of a game.
+///
+/// Call [`VoxelLevelManager::load_level`] to request the next level. It is loaded in the background and spawned hidden,
+/// and only once it has finished spawning is it shown in place of the current level, which is then despawned with
+/// [`DespawnVoxelSceneCommandsExt::despawn_voxel_scene`]. A [`VoxelLevelProgress`] event is sent every frame of a
+/// transition, for loading screens.
+///
+/// With a [`VoxelLevelManager::fade_duration`], the levels crossfade by dissolving the outgoing level's instances
+/// away while the incoming level's instances dissolve in, using [`VoxelInstanceMaterialParams::dissolve`]. This
+/// requires the [`crate::VoxelInstanceMaterialPlugin`], otherwise both levels are shown until the end of the fade.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_vox_scene::VoxelLevelManager;
+/// fn enter_study(mut levels: ResMut<VoxelLevelManager>) {
+///     levels.load_level("study.vox");
+/// }
+/// ```
+#[derive(Resource, Debug, Default)]
+pub struct VoxelLevelManager {
+    /// The number of seconds the levels crossfade for. Defaults to 0, which swaps the levels straight away.
+    pub fade_duration: f32,
+    /// Whether the plugin's handles to the assets of the outgoing level are released when it is despawned, as with
+    /// [`DespawnVoxelSceneCommandsExt::despawn_voxel_scene`]. Defaults to false.
+    pub release_assets: bool,
+    requested: Option<String>,
+    current: Option<VoxelLevel>,
+    next: Option<PendingVoxelLevel>,
+}
+
+#[derive(Debug)]
+struct VoxelLevel {
+    path: String,
+    root: Entity,
+}
+
+#[derive(Debug)]
+struct PendingVoxelLevel {
+    level: VoxelLevel,
+    scene: Handle<Scene>,
+    /// The seconds since the crossfade started, once the level has finished spawning
+    fade_elapsed: Option<f32>,
+    /// The instances that were given a [`VoxelInstanceMaterialParams`] for the crossfade
+    faded: Vec<Entity>,
+}
+
+impl VoxelLevelManager {
+    /// Creates a manager that crossfades between levels for `fade_duration` seconds
+    pub fn with_fade(fade_duration: f32) -> Self {
+        Self {
+            fade_duration,
+            ..Default::default()
+        }
+    }
+
+    /// Requests the `.vox` scene at `path`, such as `"study.vox"` or `"study.vox#tank"`, to be loaded and shown in place
+    /// of the current level. A level that is still loading is replaced, whereas one that is already crossfading
+    /// finishes first.
+    pub fn load_level(&mut self, path: impl Into<String>) {
+        self.requested = Some(path.into());
+    }
+
+    /// The root entity of the level being shown
+    pub fn current(&self) -> Option<Entity> {
+        self.current.as_ref().map(|level| level.root)
+    }
+
+    /// The asset path of the level being shown
+    pub fn current_path(&self) -> Option<&str> {
+        self.current.as_ref().map(|level| level.path.as_str())
+    }
+
+    /// Whether a level is being loaded, or crossfaded to
+    pub fn is_transitioning(&self) -> bool {
+        self.requested.is_some() || self.next.is_some()
+    }
+}
+
+/// The stage of a transition between levels, reported by [`VoxelLevelProgress`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoxelLevelStage {
+    /// The level's assets are loading
+    Loading,
+    /// The level has loaded, and is being spawned hidden
+    Spawning,
+    /// The levels are crossfading
+    Fading,
+    /// The level is being shown, and the previous level has been despawned
+    Ready,
+    /// The level's assets failed to load, so the current level is kept
+    Failed,
+}
+
+/// Event sent by the [`VoxelLevelManager`] every frame of a transition between levels
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct VoxelLevelProgress {
+    /// The asset path of the incoming level
+    pub path: String,
+    /// The stage of the transition
+    pub stage: VoxelLevelStage,
+    /// The fraction of the whole transition that is complete, from 0 to 1. Loading takes the first half, following the
+    /// [`VoxelLoadProgress`] of the level's file, and spawning and crossfading the second.
+    pub progress: f32,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_voxel_level(
+    mut commands: Commands,
+    mut manager: ResMut<VoxelLevelManager>,
+    mut progress: EventWriter<VoxelLevelProgress>,
+    assets: Res<AssetServer>,
+    load_progress: Res<VoxelLoadProgress>,
+    time: Res<Time>,
+    spawned: Query<(), With<VoxelSceneInstance>>,
+    children: Query<&Children>,
+    mut instances: Query<Option<&mut VoxelInstanceMaterialParams>, With<VoxelModelInstance>>,
+) {
+    let manager = manager.as_mut();
+    // a level that is crossfading finishes before the next one starts
+    let is_fading = manager
+        .next
+        .as_ref()
+        .is_some_and(|next| next.fade_elapsed.is_some());
+    if !is_fading {
+        if let Some(path) = manager.requested.take() {
+            if let Some(next) = manager.next.take() {
+                commands.entity(next.level.root).despawn_recursive();
+            }
+            let scene = assets.load(path.clone());
+            let root = commands
+                .spawn(SceneBundle {
+                    scene: scene.clone(),
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                })
+                .id();
+            manager.next = Some(PendingVoxelLevel {
+                level: VoxelLevel { path, root },
+                scene,
+                fade_elapsed: None,
+                faded: Vec::new(),
+            });
+        }
+    }
+    let Some(next) = manager.next.as_mut() else {
+        return;
+    };
+    let root = next.level.root;
+    let (stage, fraction) = if let Some(elapsed) = next.fade_elapsed {
+        let elapsed = elapsed + time.delta_seconds();
+        next.fade_elapsed = Some(elapsed);
+        let fraction = if manager.fade_duration > 0.0 {
+            (elapsed / manager.fade_duration).min(1.0)
+        } else {
+            1.0
+        };
+        set_dissolve(
+            &mut commands,
+            &children,
+            &mut instances,
+            root,
+            1.0 - fraction,
+        );
+        if let Some(current) = manager.current.as_ref() {
+            set_dissolve(
+                &mut commands,
+                &children,
+                &mut instances,
+                current.root,
+                fraction,
+            );
+        }
+        if fraction < 1.0 {
+            (VoxelLevelStage::Fading, 0.5 + fraction * 0.5)
+        } else {
+            (VoxelLevelStage::Ready, 1.0)
+        }
+    } else if matches!(assets.load_state(&next.scene), LoadState::Failed(_)) {
+        (VoxelLevelStage::Failed, 0.0)
+    } else if !assets.is_loaded_with_dependencies(&next.scene) {
+        let loaded = load_progress
+            .get_for(&next.scene)
+            .map_or(0.0, |progress| progress.fraction());
+        (VoxelLevelStage::Loading, loaded * 0.5)
+    } else if !spawned.contains(root) {
+        (VoxelLevelStage::Spawning, 0.5)
+    } else {
+        commands.entity(root).insert(Visibility::Inherited);
+        if manager.fade_duration > 0.0 {
+            next.faded = set_dissolve(&mut commands, &children, &mut instances, root, 1.0);
+            next.fade_elapsed = Some(0.0);
+            if let Some(current) = manager.current.as_ref() {
+                set_dissolve(&mut commands, &children, &mut instances, current.root, 0.0);
+            }
+            (VoxelLevelStage::Fading, 0.5)
+        } else {
+            (VoxelLevelStage::Ready, 1.0)
+        }
+    };
+    let path = next.level.path.clone();
+    match stage {
+        VoxelLevelStage::Failed => {
+            warn!("Failed to load level {path}");
+            let next = manager.next.take().expect("pending level");
+            commands.entity(next.level.root).despawn_recursive();
+        }
+        VoxelLevelStage::Ready => {
+            let next = manager.next.take().expect("pending level");
+            if let Some(current) = manager.current.take() {
+                commands.despawn_voxel_scene(current.root, manager.release_assets);
+            }
+            for entity in next.faded {
+                commands
+                    .entity(entity)
+                    .remove::<VoxelInstanceMaterialParams>();
+            }
+            manager.current = Some(next.level);
+        }
+        _ => {}
+    }
+    progress.send(VoxelLevelProgress {
+        path,
+        stage,
+        progress: fraction,
+    });
+}
+
+/// Sets the dissolve of every instance below `root`, adding [`VoxelInstanceMaterialParams`] to those without, and
+/// returns the instances that were given them
+fn set_dissolve(
+    commands: &mut Commands,
+    children: &Query<&Children>,
+    instances: &mut Query<Option<&mut VoxelInstanceMaterialParams>, With<VoxelModelInstance>>,
+    root: Entity,
+    dissolve: f32,
+) -> Vec<Entity> {
+    let mut added = Vec::new();
+    for entity in children.iter_descendants(root) {
+        match instances.get_mut(entity) {
+            Ok(Some(mut params)) => params.dissolve = dissolve,
+            Ok(None) => {
+                commands.entity(entity).insert(VoxelInstanceMaterialParams {
+                    dissolve,
+                    ..Default::default()
+                });
+                added.push(entity);
+            }
+            Err(_) => {}
+        }
+    }
+    added
+}
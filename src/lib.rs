@@ -47,47 +47,290 @@
 //!```
 
 use bevy::{
-    app::{App, Plugin},
+    app::{App, Plugin, PostUpdate},
     asset::AssetApp,
+    ecs::schedule::IntoSystemConfigs,
+    transform::TransformSystem,
 };
 
+#[cfg(feature = "modify_voxels")]
+use bevy::{
+    app::FixedPostUpdate,
+    render::{
+        render_asset::prepare_assets, texture::GpuImage, view::VisibilitySystems, ExtractSchedule,
+        Render, RenderApp, RenderSet,
+    },
+};
+
+mod budget;
+mod explode;
+mod hash;
+mod index;
+mod level;
 mod load;
 mod model;
+mod rng;
+#[cfg(feature = "modify_voxels")]
+mod server;
+#[cfg(any(test, feature = "test_utils"))]
+pub mod test_utils;
+mod unload;
+#[cfg(feature = "utilities")]
+pub mod utilities;
 
 #[cfg(test)]
 mod tests;
 
+pub use budget::VoxelMemoryBudget;
+pub use explode::{ExplodeVoxelSceneCommandsExt, VoxelExplodedView};
+pub use index::{VoxelIndexEntry, VoxelWorldIndex};
+pub use level::{VoxelLevelManager, VoxelLevelProgress, VoxelLevelStage};
+#[cfg(feature = "point_cloud")]
+pub use load::VoxPointCloudSettings;
+pub use load::{
+    validate_vox_bytes, DuplicateNamePolicy, PlatformProfile, VoxLoaderError, VoxLoaderOverrides,
+    VoxLoaderSettings, VoxSceneGlobalSettings, VoxelCatalog, VoxelCatalogEntry, VoxelCatalogId,
+    VoxelFileIndex, VoxelFileLoadProgress, VoxelFileModel, VoxelJoint, VoxelJointKind, VoxelLayer,
+    VoxelLintIssue, VoxelLoadProgress, VoxelModelInfo, VoxelModelInstance, VoxelNodeTags,
+    VoxelReflectionProbe, VoxelRenderObjects, VoxelSceneInstance, VoxelShapeFrame,
+    VoxelShapeFrames, VoxelSocket,
+};
 #[doc(inline)]
-use load::VoxSceneLoader;
-pub use load::{VoxLoaderSettings, VoxelLayer, VoxelModelInstance};
-#[cfg(feature = "generate_voxels")]
-pub use model::sdf::SDF;
+use load::{VoxCatalogLoader, VoxFileIndexLoader, VoxSceneLoader};
+#[cfg(feature = "raymarch")]
+pub use model::raymarch::{VoxelRaymarchMaterial, VoxelRaymarchPlugin, VoxelRaymarched};
 #[cfg(feature = "modify_voxels")]
 pub use model::{
-    modify::{ModifyVoxelCommandsExt, VoxelRegion, VoxelRegionMode},
-    queryable::VoxelQueryable,
+    blueprint::{StampBlueprintCommandsExt, VoxelBlueprint},
+    brush::{VoxelBrush, VoxelBrushBlend, VoxelBrushFalloff},
+    clipboard::{VoxelClipboard, VoxelClipboardCommandsExt},
+    cursor::VoxelCursor,
+    ghost::VoxelGhost,
+    gravity::{VoxelGravity, VoxelGravityBudget},
+    harvest::{HarvestVoxelCommandsExt, VoxelsHarvested},
+    integrity::{VoxelIntegrity, VoxelIntegrityThresholdCrossed},
+    interaction::{
+        VoxelInteraction, VoxelInteractionKind, VoxelInteractionPlugin, VoxelInteractor,
+        VoxelTarget,
+    },
+    modify::{ModifyVoxelCommandsExt, VoxelRegion, VoxelRegionMode, VoxelWorldRegion},
+    morphology::{MorphologyCommandsExt, VoxelSmoothKernel},
+    neighbors::VoxelNeighbors,
+    outline::VoxelOutline,
+    palette_texels::SetVoxelElementCommandsExt,
+    queryable::{VoxelQueryable, VoxelRayHit},
+    queue::{QueueVoxelEditCommandsExt, VoxelEditQueue},
+    resample::VoxelResampleFilter,
+    timeline::VoxelTimeline,
+};
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+pub use model::{
+    clipmap::{VoxelClipmap, VoxelClipmapTile},
+    debris::{
+        ShatterVoxelCommandsExt, VoxelDebris, VoxelDebrisBudget, VoxelDebrisParticle,
+        VoxelDebrisSettings,
+    },
+    lighting::VoxelWorldLighting,
+    region::VoxelRegionFiles,
+    streaming::{VoxelChunkLoaded, VoxelChunkUnloaded, VoxelWorldStreaming, VoxelWorldViewer},
+    tileset::{VoxelTile, VoxelTileset},
+    water::{VoxelSubmersion, VoxelWater, VoxelWaterSurface},
+    world::{VoxelWorld, VoxelWorldChunk},
 };
-pub use model::{Voxel, VoxelContext, VoxelData, VoxelElement, VoxelModel, VoxelPalette};
+#[cfg(feature = "generate_voxels")]
+pub use model::{
+    generate::{GenerateVoxelModelCommandsExt, VoxelModelGenerated},
+    sdf::SDF,
+};
+pub use model::{
+    instance::VoxelModelInstanceBuilder,
+    instance_material::{
+        VoxelInstanceExtension, VoxelInstanceMaterial, VoxelInstanceMaterialParams,
+        VoxelInstanceMaterialPlugin,
+    },
+    lod::VoxelLod,
+    swap::SwapVoxelModelCommandsExt,
+    ColliderFilter, ColorMetric, DirectionalOcclusion, MaterialProperty, MeshAttributeConfig,
+    PaletteLayout, PalettePrecision, Voxel, VoxelAir, VoxelAirMap, VoxelAudioMaterials,
+    VoxelBrickHit, VoxelBrickMap, VoxelCharacterController, VoxelChunkOcclusion, VoxelColliderBox,
+    VoxelContext, VoxelData, VoxelEditMask, VoxelElement, VoxelElementData, VoxelElementDataPlugin,
+    VoxelFacing, VoxelFracture, VoxelGrid, VoxelModel, VoxelMoveResult, VoxelPalette,
+    VoxelPaletteSummary, VoxelSampler, VoxelShape, VoxelShapes, VoxelShard, VoxelShards, VoxelTint,
+    ATTRIBUTE_DIRECTIONAL_OCCLUSION, ATTRIBUTE_FACE_ID, ATTRIBUTE_PALETTE_INDEX,
+};
+pub use rng::VoxelRng;
+#[cfg(feature = "modify_voxels")]
+pub use server::{VoxelChange, VoxelModelId, VoxelWorldServer};
+pub use unload::DespawnVoxelSceneCommandsExt;
 
 /// Plugin adding functionality for loading `.vox` files.
 ///
 /// Registers an [`bevy::asset::AssetLoader`] capable of loading `.vox` files as spawnable [`VoxelScene`]s.
 #[derive(Default)]
 pub struct VoxScenePlugin {
-    /// Inject global settings. This is a workaround for `load_with_settings` currently being broken.
-    /// See: https://github.com/bevyengine/bevy/issues/12320
-    /// and: https://github.com/bevyengine/bevy/issues/11111
+    /// The initial value of the [`VoxSceneGlobalSettings`] resource, used for every field that an asset's
+    /// [`VoxLoaderOverrides`] doesn't set. Defaults to [`VoxLoaderSettings::default`].
     pub global_settings: Option<VoxLoaderSettings>,
 }
 
+impl VoxScenePlugin {
+    /// Creates the plugin with the supplied global settings
+    pub fn with_settings(settings: VoxLoaderSettings) -> Self {
+        Self {
+            global_settings: Some(settings),
+        }
+    }
+}
+
 impl Plugin for VoxScenePlugin {
     fn build(&self, app: &mut App) {
+        let global_settings =
+            VoxSceneGlobalSettings::new(self.global_settings.clone().unwrap_or_default());
+        let load_progress = VoxelLoadProgress::default();
         app.init_asset::<VoxelModel>()
             .init_asset::<VoxelContext>()
+            .init_asset::<VoxelFileIndex>()
+            .init_asset::<VoxelCatalog>()
+            .init_asset::<VoxelShards>()
+            .init_asset::<VoxelBrickMap>()
+            .register_type::<VoxelElement>()
+            .register_type::<VoxelExplodedView>()
+            .register_type::<VoxelJoint>()
             .register_type::<VoxelLayer>()
+            .register_type::<VoxelLod>()
+            .register_type::<VoxelModelInfo>()
             .register_type::<VoxelModelInstance>()
+            .register_type::<VoxelPalette>()
+            .register_type::<VoxelReflectionProbe>()
+            .register_type::<VoxelRenderObjects>()
+            .register_type::<VoxelSceneInstance>()
+            .register_type::<VoxelShapeFrames>()
+            .register_type::<VoxelSocket>()
+            .insert_resource(global_settings.clone())
+            .insert_resource(load_progress.clone())
+            .init_resource::<VoxelRng>()
+            .init_resource::<VoxelWorldIndex>()
+            .init_resource::<VoxelMemoryBudget>()
+            .init_resource::<model::lod::VoxelLodMeshes>()
+            .init_resource::<VoxelNodeTags>()
+            .init_resource::<VoxelLevelManager>()
+            .add_event::<VoxelLevelProgress>()
+            .add_systems(
+                PostUpdate,
+                (
+                    index::update_voxel_world_index.after(TransformSystem::TransformPropagate),
+                    budget::update_voxel_memory_budget.after(index::update_voxel_world_index),
+                    load::tags::tag_scene_nodes.after(load::spawn::populate_scene_instances),
+                    load::spawn::populate_scene_instances,
+                    load::spawn::mesh_pending_models,
+                    load::spawn::update_voxel_model_info.after(load::spawn::mesh_pending_models),
+                    load::spawn::connect_voxel_joints,
+                    load::spawn::spawn_reflection_probes
+                        .before(TransformSystem::TransformPropagate),
+                    model::lod::update_voxel_lods.after(TransformSystem::TransformPropagate),
+                    explode::update_exploded_views.before(TransformSystem::TransformPropagate),
+                    level::update_voxel_level
+                        .after(load::spawn::populate_scene_instances)
+                        .before(model::instance_material::update_instance_materials),
+                ),
+            )
+            // registered first, so that untyped loads of `.vox` files use the scene loader
+            .register_asset_loader(VoxFileIndexLoader)
+            .register_asset_loader(VoxCatalogLoader {
+                global_settings: global_settings.clone(),
+            })
             .register_asset_loader(VoxSceneLoader {
-                global_settings: self.global_settings.clone(),
+                global_settings: global_settings.clone(),
+                progress: load_progress,
             });
+        #[cfg(feature = "point_cloud")]
+        app.register_asset_loader(load::VoxPointCloudLoader { global_settings });
+        #[cfg(feature = "generate_voxels")]
+        app.init_resource::<model::generate::PendingVoxelGenerations>()
+            .add_event::<VoxelModelGenerated>()
+            .add_systems(PostUpdate, model::generate::finish_voxel_generations);
+        #[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+        app.register_type::<VoxelWorldChunk>()
+            .register_type::<VoxelWorldViewer>()
+            .register_type::<VoxelClipmapTile>()
+            .register_type::<VoxelDebris>()
+            .register_type::<VoxelDebrisParticle>()
+            .init_resource::<VoxelDebrisBudget>()
+            .init_resource::<model::debris::VoxelDebrisParticleAssets>()
+            .register_type::<VoxelWaterSurface>()
+            .register_type::<VoxelSubmersion>()
+            .add_event::<VoxelChunkLoaded>()
+            .add_event::<VoxelChunkUnloaded>()
+            .add_systems(
+                PostUpdate,
+                (
+                    model::streaming::stream_voxel_world
+                        .after(TransformSystem::TransformPropagate)
+                        .before(model::world::update_voxel_world),
+                    model::region::update_voxel_world_regions
+                        .after(model::streaming::stream_voxel_world)
+                        .before(model::world::update_voxel_world),
+                    model::world::update_voxel_world,
+                    model::water::update_voxel_submersion
+                        .after(TransformSystem::TransformPropagate),
+                    model::clipmap::update_voxel_clipmaps
+                        .after(TransformSystem::TransformPropagate),
+                    model::debris::update_voxel_debris.before(TransformSystem::TransformPropagate),
+                ),
+            );
+        #[cfg(feature = "modify_voxels")]
+        app.init_asset::<VoxelBlueprint>()
+            .init_resource::<VoxelEditQueue>()
+            .init_resource::<VoxelGravityBudget>()
+            .register_type::<VoxelCursor>()
+            .register_type::<VoxelGhost>()
+            .register_type::<VoxelGravity>()
+            .register_type::<VoxelIntegrity>()
+            .register_type::<VoxelNeighbors>()
+            .register_type::<VoxelOutline>()
+            .register_type::<VoxelRegion>()
+            .register_type::<VoxelRegionMode>()
+            .register_type::<VoxelTimeline>()
+            .add_event::<VoxelIntegrityThresholdCrossed>()
+            .add_event::<VoxelsHarvested>()
+            .add_systems(
+                FixedPostUpdate,
+                (
+                    model::queue::apply_voxel_edit_queue,
+                    model::gravity::apply_voxel_gravity.after(model::queue::apply_voxel_edit_queue),
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    model::modify::update_instance_aabbs
+                        .after(VisibilitySystems::CalculateBounds)
+                        .before(VisibilitySystems::CheckVisibility),
+                    model::cursor::update_voxel_cursors.after(TransformSystem::TransformPropagate),
+                    model::ghost::update_voxel_ghosts.before(TransformSystem::TransformPropagate),
+                    model::integrity::update_voxel_integrity,
+                    model::neighbors::update_voxel_neighbors
+                        .after(TransformSystem::TransformPropagate),
+                    model::outline::update_voxel_outlines
+                        .before(TransformSystem::TransformPropagate),
+                    model::timeline::update_voxel_timelines,
+                ),
+            )
+            .init_resource::<model::palette_texels::PendingPaletteTexelWrites>();
+        #[cfg(feature = "modify_voxels")]
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<model::palette_texels::PendingPaletteTexelWrites>()
+                .add_systems(
+                    ExtractSchedule,
+                    model::palette_texels::extract_palette_texel_writes,
+                )
+                .add_systems(
+                    Render,
+                    model::palette_texels::write_palette_texels
+                        .in_set(RenderSet::PrepareResources)
+                        .after(prepare_assets::<GpuImage>),
+                );
+        }
     }
 }
@@ -0,0 +1,288 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use bevy::{
+    asset::{io::Reader, Asset, AssetLoader, AsyncReadExt, Handle, LoadContext},
+    log::info,
+    pbr::StandardMaterial,
+    reflect::{Reflect, TypePath},
+    tasks::futures_lite::io::Cursor,
+    utils::HashMap,
+};
+use dot_vox::DotVoxData;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    add_model_assets, chunks, model_names,
+    stream::{read_streamed, StreamedFile},
+    validate, VoxLoaderError, VoxLoaderOverrides, VoxSceneGlobalSettings,
+};
+use crate::{
+    hash::StableHasher,
+    model::{VoxelAudioMaterials, VoxelModel, VoxelPalette},
+    VoxelContext, VoxelModelInstance,
+};
+
+/// A stable identifier for an entry in a [`VoxelCatalog`], derived from the entry's name, so that it stays the same as
+/// files are added to or removed from the manifest. Suitable for save games and network messages.
+#[derive(Reflect, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VoxelCatalogId(pub u64);
+
+impl VoxelCatalogId {
+    /// The id of the entry with the supplied name
+    pub fn from_name(name: &str) -> Self {
+        let mut hasher = StableHasher::default();
+        hasher.write(name.as_bytes());
+        Self(hasher.finish())
+    }
+}
+
+/// A model in a [`VoxelCatalog`]
+#[derive(Clone, Debug)]
+pub struct VoxelCatalogEntry {
+    /// The stable id of the entry
+    pub id: VoxelCatalogId,
+    /// The name of the entry. This is the stem of the file for files with a single model, such as `chair` for
+    /// `props/chair.vox`, or the stem followed by the name of the model, such as `kitchen/sink`, for files with more.
+    pub name: String,
+    /// The path of the `.vox` file holding the model, as written in the manifest
+    pub file: String,
+    /// The model
+    pub model: Handle<VoxelModel>,
+    /// The context of the model, which is shared by every entry whose file has the same palette
+    pub context: Handle<VoxelContext>,
+}
+
+impl VoxelCatalogEntry {
+    /// A [`VoxelModelInstance`] of the entry, to spawn alongside a [`crate::VoxelModelInstanceBuilder`] or as part of
+    /// a bundle
+    pub fn instance(&self) -> VoxelModelInstance {
+        VoxelModelInstance {
+            model: self.model.clone(),
+            context: self.context.clone(),
+        }
+    }
+}
+
+/// A collection of the models of many `.vox` files, loaded as a single asset, for instance to give a game one handle
+/// for all of its props.
+///
+/// The catalog is loaded from a RON manifest with the extension `.voxcat.ron`, listing the `.vox` files relative to
+/// the manifest:
+/// ```ron
+/// (
+///     files: ["props/chair.vox", "props/table.vox", "kitchen.vox"],
+/// )
+/// ```
+/// Every model is meshed with the [`crate::VoxLoaderOverrides`] the catalog is loaded with. Files with identical
+/// palettes share a single [`VoxelContext`], and their opaque models all use the context's opaque material, so that
+/// they can be batched together, unless the models are meshed lazily with [`crate::VoxLoaderSettings::lazy_meshing`].
+/// Translucent models each have their own material, holding their index of refraction and thickness. The models are
+/// labelled sub-assets of the catalog, so `props.voxcat.ron#chair@model` loads a single model.
+#[derive(Asset, TypePath, Clone, Debug, Default)]
+pub struct VoxelCatalog {
+    entries: Vec<VoxelCatalogEntry>,
+    contexts: Vec<Handle<VoxelContext>>,
+}
+
+impl VoxelCatalog {
+    /// The entry with the supplied name
+    pub fn get(&self, name: &str) -> Option<&VoxelCatalogEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// The entry with the supplied id
+    pub fn get_by_id(&self, id: VoxelCatalogId) -> Option<&VoxelCatalogEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    /// Every entry, in the order the files are listed in the manifest
+    pub fn entries(&self) -> &[VoxelCatalogEntry] {
+        &self.entries
+    }
+
+    /// The distinct contexts of the entries, one for each distinct palette
+    pub fn contexts(&self) -> &[Handle<VoxelContext>] {
+        &self.contexts
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct VoxelCatalogManifest {
+    files: Vec<String>,
+}
+
+/// The palette and materials of a file, to find files that can share a context
+#[derive(PartialEq)]
+struct PaletteKey {
+    colors: Vec<u8>,
+    materials: Vec<(u32, Vec<(String, String)>)>,
+}
+
+impl PaletteKey {
+    fn new(file: &DotVoxData) -> Self {
+        let mut materials: Vec<(u32, Vec<(String, String)>)> = file
+            .materials
+            .iter()
+            .map(|material| {
+                let mut properties: Vec<(String, String)> = material
+                    .properties
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                properties.sort();
+                (material.id, properties)
+            })
+            .collect();
+        materials.sort();
+        Self {
+            colors: file
+                .palette
+                .iter()
+                .flat_map(|color| [color.r, color.g, color.b, color.a])
+                .collect(),
+            materials,
+        }
+    }
+}
+
+/// Loads a [`VoxelCatalog`] from a manifest of `.vox` files
+pub(crate) struct VoxCatalogLoader {
+    pub(crate) global_settings: VoxSceneGlobalSettings,
+}
+
+impl AssetLoader for VoxCatalogLoader {
+    type Asset = VoxelCatalog;
+    type Settings = VoxLoaderOverrides;
+    type Error = VoxLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        settings: &'a VoxLoaderOverrides,
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(|e| VoxLoaderError::InvalidAsset(anyhow!(e)))?;
+        let manifest: VoxelCatalogManifest =
+            ron::de::from_bytes(&bytes).map_err(|error| anyhow!(error))?;
+        let settings = self.global_settings.resolve(settings);
+        let directory = load_context
+            .path()
+            .parent()
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        info!(
+            "Loading catalog {} of {} files",
+            load_context.asset_path(),
+            manifest.files.len()
+        );
+
+        let mut catalog = VoxelCatalog::default();
+        let mut palettes: Vec<(
+            PaletteKey,
+            VoxelPalette,
+            StandardMaterial,
+            Handle<StandardMaterial>,
+        )> = Vec::new();
+        let mut names: HashMap<VoxelCatalogId, String> = HashMap::new();
+        for path in manifest.files {
+            let bytes = load_context
+                .read_asset_bytes(directory.join(&path))
+                .await
+                .map_err(|error| anyhow!("{path}: {error}"))?;
+            // the voxels of each model are only parsed once the model is meshed, rather than every model at once
+            let mut reader = Cursor::new(bytes.as_slice());
+            let StreamedFile { file, models, .. } = read_streamed(&mut reader, |_| {}).await?;
+            validate::validate_file(&file, &path)?;
+
+            let key = PaletteKey::new(&file);
+            let context_index = match palettes.iter().position(|(other, _, _, _)| *other == key) {
+                Some(index) => index,
+                None => {
+                    let index = palettes.len();
+                    let label = format!("palette-{index}");
+                    let palette = settings
+                        .create_palette(&file)
+                        .with_notes(chunks::palette_notes(&bytes))
+                        .with_display_order(chunks::palette_index_map(&bytes));
+                    let translucent_material = palette
+                        .create_material_with_label_prefix(load_context, &format!("{label}/"));
+                    let opaque_material =
+                        load_context.labeled_asset_scope(format!("{label}/material"), |_| {
+                            let mut opaque_material = translucent_material.clone();
+                            opaque_material.specular_transmission_texture = None;
+                            opaque_material.specular_transmission = 0.0;
+                            opaque_material
+                        });
+                    let transmissive_material = load_context.add_labeled_asset(
+                        format!("{label}/material-transmissive"),
+                        translucent_material.clone(),
+                    );
+                    let context = load_context.add_labeled_asset(
+                        label,
+                        VoxelContext {
+                            palette: palette.clone(),
+                            audio_materials: VoxelAudioMaterials::from_data(&file),
+                            opaque_material: opaque_material.clone(),
+                            transmissive_material,
+                        },
+                    );
+                    catalog.contexts.push(context);
+                    palettes.push((key, palette, translucent_material, opaque_material));
+                    index
+                }
+            };
+            let (_, palette, translucent_material, opaque_material) = &palettes[context_index];
+            let context_label = format!("palette-{context_index}");
+
+            let stem = PathBuf::from(&path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or(path.clone());
+            for (index, model_name) in model_names(&file, settings.duplicate_names)
+                .iter()
+                .enumerate()
+            {
+                let name = if file.models.len() == 1 {
+                    stem.clone()
+                } else {
+                    format!("{stem}/{model_name}")
+                };
+                let id = VoxelCatalogId::from_name(&name);
+                if let Some(existing) = names.insert(id, name.clone()) {
+                    return Err(VoxLoaderError::InvalidAsset(anyhow!(
+                        "{}: the name {name} is used by more than one model, or has the same id as {existing}",
+                        load_context.asset_path()
+                    )));
+                }
+                let model = models.model(&mut reader, &file, index, &path).await?;
+                let model = add_model_assets(
+                    load_context,
+                    name.clone(),
+                    &model,
+                    &settings,
+                    palette,
+                    translucent_material,
+                    Some(opaque_material),
+                    &context_label,
+                );
+                catalog.entries.push(VoxelCatalogEntry {
+                    id,
+                    name,
+                    file: path.clone(),
+                    model,
+                    context: catalog.contexts[context_index].clone(),
+                });
+            }
+        }
+        Ok(catalog)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["voxcat.ron"]
+    }
+}
@@ -0,0 +1,130 @@
+use std::ops::Range;
+
+use bevy::utils::HashMap;
+
+/// Reads a little-endian `u32` from `bytes` at `offset`
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> Option<usize> {
+    let bytes: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+    Some(u32::from_le_bytes(bytes) as usize)
+}
+
+/// Reads a length-prefixed string from `bytes` at `offset`, returning the string and the offset following it
+fn read_string(bytes: &[u8], offset: usize) -> Option<(String, usize)> {
+    let length = read_u32(bytes, offset)?;
+    let string = bytes.get(offset + 4..offset + 4 + length)?;
+    Some((
+        String::from_utf8_lossy(string).to_string(),
+        offset + 4 + length,
+    ))
+}
+
+/// Returns the id and content of every chunk in a `.vox` file, in the order they appear
+pub(crate) fn chunks(bytes: &[u8]) -> Vec<(String, &[u8])> {
+    chunk_ranges(bytes)
+        .into_iter()
+        .map(|chunk| (chunk.id, &bytes[chunk.content]))
+        .collect()
+}
+
+/// The position of a chunk within the bytes of a `.vox` file
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ChunkRange {
+    /// The id of the chunk, eg `XYZI`
+    pub(crate) id: String,
+    /// The range of the chunk's content
+    pub(crate) content: Range<usize>,
+    /// The range of the whole chunk, from its header to the end of its children
+    pub(crate) chunk: Range<usize>,
+}
+
+/// Returns the position of every chunk in a `.vox` file, in the order they appear
+pub(crate) fn chunk_ranges(bytes: &[u8]) -> Vec<ChunkRange> {
+    let mut chunks = Vec::new();
+    // the header is followed by the MAIN chunk, whose children are the chunks of the file
+    let (Some(main_content), Some(main_children)) = (read_u32(bytes, 12), read_u32(bytes, 16))
+    else {
+        return chunks;
+    };
+    let mut offset = 20 + main_content;
+    let end = offset.saturating_add(main_children).min(bytes.len());
+    while offset + 12 <= end {
+        let (Some(content), Some(children)) =
+            (read_u32(bytes, offset + 4), read_u32(bytes, offset + 8))
+        else {
+            break;
+        };
+        let id = String::from_utf8_lossy(&bytes[offset..offset + 4]).to_string();
+        let content_start = offset + 12;
+        let content_end = content_start.saturating_add(content).min(end);
+        let next = offset.saturating_add(12 + content).saturating_add(children);
+        chunks.push(ChunkRange {
+            id,
+            content: content_start..content_end,
+            chunk: offset..next.min(end),
+        });
+        offset = next;
+    }
+    chunks
+}
+
+/// Reads the palette notes from the `NOTE` chunk of a `.vox` file, which name the rows of the palette. `dot_vox`
+/// skips this chunk, so it is read from the raw bytes. Returns an empty list if the file has no notes.
+pub(crate) fn palette_notes(bytes: &[u8]) -> Vec<String> {
+    let Some((_, content)) = chunks(bytes).into_iter().find(|(id, _)| id == "NOTE") else {
+        return Vec::new();
+    };
+    let mut notes = Vec::new();
+    let Some(count) = read_u32(content, 0) else {
+        return notes;
+    };
+    let mut offset = 4;
+    for _ in 0..count {
+        let Some((note, next)) = read_string(content, offset) else {
+            break;
+        };
+        notes.push(note);
+        offset = next;
+    }
+    notes
+}
+
+/// Reads the `IMAP` chunk saved by Magica Voxel 0.99.7 and later, which records the order the palette is displayed in
+/// after colors are rearranged in the editor. Entry `n` is the index of the voxel shown in slot `n` of the palette.
+/// Returns an empty list for files that keep the default order.
+pub(crate) fn palette_index_map(bytes: &[u8]) -> Vec<u8> {
+    chunks(bytes)
+        .into_iter()
+        .find(|(id, _)| id == "IMAP")
+        .map(|(_, content)| content.to_vec())
+        .unwrap_or_default()
+}
+
+/// Reads the properties of every render object (`rOBJ`) chunk, which hold the editor's render settings such as the
+/// environment, bounces and post-processing
+pub(crate) fn render_objects(bytes: &[u8]) -> Vec<HashMap<String, String>> {
+    chunks(bytes)
+        .into_iter()
+        .filter(|(id, _)| id == "rOBJ")
+        .map(|(_, content)| read_dict(content))
+        .collect()
+}
+
+/// Reads a dictionary of string keys and values
+fn read_dict(bytes: &[u8]) -> HashMap<String, String> {
+    let mut dict = HashMap::new();
+    let Some(count) = read_u32(bytes, 0) else {
+        return dict;
+    };
+    let mut offset = 4;
+    for _ in 0..count {
+        let Some((key, next)) = read_string(bytes, offset) else {
+            break;
+        };
+        let Some((value, next)) = read_string(bytes, next) else {
+            break;
+        };
+        dict.insert(key, value);
+        offset = next;
+    }
+    dict
+}
@@ -1,4 +1,11 @@
-use bevy::{asset::Handle, ecs::component::Component, prelude::ReflectComponent, reflect::Reflect};
+use bevy::{
+    asset::Handle,
+    ecs::{component::Component, entity::Entity},
+    math::UVec3,
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    utils::HashMap,
+};
 
 use crate::{VoxelContext, VoxelModel};
 
@@ -11,7 +18,7 @@ pub(crate) struct LayerInfo {
 /// Component wrapping the handle to the [`VoxelModel`]
 ///
 /// When the scene is spawned this component gets added to entities with a voxel mesh.
-#[derive(Component, Clone, Reflect)]
+#[derive(Component, Clone, Debug, Reflect)]
 #[reflect(Component)]
 pub struct VoxelModelInstance {
     /// Handle to the model
@@ -32,3 +39,191 @@ pub struct VoxelLayer {
     /// An optional name for the Layer, assignable in Magica Voxel layer editor.
     pub name: Option<String>,
 }
+
+/// A component marking an attachment point authored in Magica Voxel, for parenting props such as weapons to the model.
+///
+/// It is added to nodes whose name begins with `socket:` (in which case the prefix is stripped from the socket name),
+/// and to named nodes that contain an empty group.
+#[derive(Component, Clone, Debug, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct VoxelSocket(pub String);
+
+/// A component marking a reflection probe authored in Magica Voxel.
+///
+/// It is added to nodes whose name begins with `probe:`, with the prefix stripped from the probe name. When the scene
+/// is spawned, the model in the node is replaced by a [`bevy::pbr::LightProbe`] covering the bounds of the model, so
+/// that probes can be placed and sized in the editor. Insert an [`bevy::pbr::environment_map::EnvironmentMapLight`]
+/// on the entity to supply the image that it reflects.
+#[derive(Component, Clone, Debug, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct VoxelReflectionProbe(pub String);
+
+/// A component describing a physics joint authored in Magica Voxel, for building contraptions such as doors and wheels.
+///
+/// It is added to nodes whose name begins with `joint:`, followed by the kind of joint, eg `joint:hinge`. When the scene is
+/// spawned, the joint is connected to the two sibling models closest to it, which can be read with
+/// [`VoxelJoint::bodies`].
+///
+/// This crate doesn't depend on a physics engine, so the component is only data: no joint is ever simulated. Create the
+/// joint with the physics engine of your choice in a system that watches for `Changed<VoxelJoint>`, as the bodies are
+/// connected in [`bevy::app::PostUpdate`] of the frame the scene is spawned. The node's [`bevy::transform::components::Transform`]
+/// is the anchor of the joint, and its local X axis is the axis of hinge and prismatic joints.
+#[derive(Component, Clone, Debug, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct VoxelJoint {
+    /// The kind of joint, parsed from the node name
+    pub kind: VoxelJointKind,
+    pub(crate) bodies: Option<[Entity; 2]>,
+}
+
+impl VoxelJoint {
+    /// Creates an unconnected joint of the supplied kind
+    pub fn new(kind: VoxelJointKind) -> Self {
+        Self { kind, bodies: None }
+    }
+
+    /// The two model entities the joint connects, or `None` if the joint isn't connected yet, or has fewer than two
+    /// sibling models
+    pub fn bodies(&self) -> Option<[Entity; 2]> {
+        self.bodies
+    }
+}
+
+/// The kind of a [`VoxelJoint`]
+#[derive(Clone, Debug, PartialEq, Eq, Reflect)]
+pub enum VoxelJointKind {
+    /// `joint:fixed`, locking the bodies together
+    Fixed,
+    /// `joint:hinge` or `joint:revolute`, allowing rotation around the joint's local X axis
+    Hinge,
+    /// `joint:prismatic` or `joint:slider`, allowing translation along the joint's local X axis
+    Prismatic,
+    /// `joint:ball` or `joint:spherical`, allowing rotation around every axis
+    Spherical,
+    /// Any other kind, holding the text following the `joint:` prefix
+    Other(String),
+}
+
+impl VoxelJointKind {
+    pub(crate) fn parse(kind: &str) -> Self {
+        match kind {
+            "fixed" => Self::Fixed,
+            "hinge" | "revolute" => Self::Hinge,
+            "prismatic" | "slider" => Self::Prismatic,
+            "ball" | "spherical" => Self::Spherical,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+/// Statistics about the model displayed by a [`VoxelModelInstance`], added to every instance and kept up to date as the
+/// model is edited, so that voxel scenes can be debugged in a reflection-based inspector.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_vox_scene::VoxelModelInfo;
+/// // log the models that have grown too large
+/// fn report_large_models(instances: Query<&VoxelModelInfo, Changed<VoxelModelInfo>>) {
+///     for info in instances.iter().filter(|info| info.voxel_count > 100_000) {
+///         warn!("{} has {} voxels", info.name, info.voxel_count);
+///     }
+/// }
+/// # App::new().add_systems(Update, report_large_models);
+/// ```
+#[derive(Component, Clone, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct VoxelModelInfo {
+    /// The name of the model
+    pub name: String,
+    /// The size of the model in voxels
+    pub size: UVec3,
+    /// The number of solid voxels in the model
+    pub voxel_count: usize,
+    /// The number of bytes of voxel data held in memory by the model
+    pub memory_usage: usize,
+}
+
+/// A component holding the keyframes of a shape node authored with several models in Magica Voxel's animation
+/// timeline.
+///
+/// It is added alongside the [`VoxelModelInstance`], which displays the model of the first keyframe. To play the
+/// animation, look up the model for the current frame with [`VoxelShapeFrames::model_at`] and display it with
+/// [`crate::SwapVoxelModelCommandsExt::swap_voxel_model`].
+#[derive(Component, Clone, Debug, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct VoxelShapeFrames {
+    /// The keyframes, in ascending order of frame index
+    pub frames: Vec<VoxelShapeFrame>,
+}
+
+/// A single keyframe of a [`VoxelShapeFrames`]
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub struct VoxelShapeFrame {
+    /// The index of the frame at which the model is first shown
+    pub frame_index: u32,
+    /// The model shown from this frame until the next keyframe
+    pub model: Handle<VoxelModel>,
+}
+
+impl VoxelShapeFrames {
+    /// The model shown at `frame`, which is the model of the last keyframe at or before `frame`. Frames before the first
+    /// keyframe show the first model.
+    pub fn model_at(&self, frame: u32) -> Option<&Handle<VoxelModel>> {
+        self.frames
+            .iter()
+            .rev()
+            .find(|keyframe| keyframe.frame_index <= frame)
+            .or(self.frames.first())
+            .map(|keyframe| &keyframe.model)
+    }
+
+    /// The index of the last keyframe, after which the animation loops
+    pub fn last_frame_index(&self) -> u32 {
+        self.frames
+            .last()
+            .map_or(0, |keyframe| keyframe.frame_index)
+    }
+}
+
+/// A component holding the properties of the render objects (`rOBJ` chunks) saved by Magica Voxel 0.99.7 and later,
+/// which configure its renderer, such as the environment, bounces, fog and post-processing, so that a scene can be lit
+/// to match the editor.
+///
+/// It is added to the root entity of the scene loaded from a whole `.vox` file, if the file has any render objects.
+/// The same properties are listed in [`crate::VoxelFileIndex::render_objects`].
+#[derive(Component, Clone, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct VoxelRenderObjects(pub Vec<HashMap<String, String>>);
+
+impl VoxelRenderObjects {
+    /// Returns the properties of the render object of the supplied type, eg `"_env"` or `"_fog_uni"`
+    pub fn get(&self, kind: &str) -> Option<&HashMap<String, String>> {
+        self.0
+            .iter()
+            .find(|object| object.get("_type").is_some_and(|value| value == kind))
+    }
+}
+
+/// A component added to the root entity of a spawned `.vox` scene, mapping the path of every named node to its entity.
+///
+/// It is inserted once the scene has finished spawning, so you can look up nodes by the name assigned to them in
+/// Magica Voxel, rather than matching on [`bevy::core::Name`]s in observers.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct VoxelSceneInstance {
+    pub(crate) nodes: HashMap<String, Entity>,
+}
+
+impl VoxelSceneInstance {
+    /// Returns the entity spawned for the node at `path`, eg `"workstation/computer"`
+    pub fn get_node(&self, path: &str) -> Option<Entity> {
+        self.nodes.get(path).copied()
+    }
+
+    /// Iterates over the path and entity of every named node in the scene
+    pub fn nodes(&self) -> impl Iterator<Item = (&str, Entity)> {
+        self.nodes
+            .iter()
+            .map(|(path, entity)| (path.as_str(), *entity))
+    }
+}
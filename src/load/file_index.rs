@@ -0,0 +1,198 @@
+use bevy::{
+    asset::{io::Reader, Asset, AssetLoader, LoadContext},
+    math::UVec3,
+    reflect::TypePath,
+    utils::HashMap,
+};
+use dot_vox::{DotVoxData, SceneNode};
+
+use super::{
+    chunks::{palette_index_map, render_objects},
+    model_names,
+    names::DuplicateNamePolicy,
+    parse_scene::get_accumulated_and_node_name,
+    stream::{read_streamed, StreamedFile},
+    validate::{unsupported_chunks, validate_file},
+    VoxLoaderError,
+};
+
+/// A lightweight summary of the contents of a `.vox` file, listing every model, node and layer without generating any
+/// meshes or materials, so that launchers and editors can present a picker before deciding what to fully load.
+///
+/// Load the index on its own with `asset_server.load::<VoxelFileIndex>("study.vox")`, or alongside the scene by
+/// appending `#index` to the asset path.
+#[derive(Asset, TypePath, Clone, Debug, Default)]
+pub struct VoxelFileIndex {
+    /// Every model in the file, in the order the models appear in the file
+    pub models: Vec<VoxelFileModel>,
+    /// The path of every named node in the scene graph, in the format used by the asset labels
+    pub nodes: Vec<String>,
+    /// The name of every layer in the file, or `None` for unnamed layers
+    pub layers: Vec<Option<String>>,
+    /// The ids of the chunks in the file that the loader didn't recognise and skipped, which may hold data from a newer
+    /// version of Magica Voxel that isn't part of the loaded scene
+    pub unsupported_features: Vec<String>,
+    /// The original and final path of every node renamed by [`crate::DuplicateNamePolicy::Suffix`], so that the labels
+    /// of duplicate nodes can be looked up
+    pub renamed_nodes: Vec<(String, String)>,
+    /// The indices of the voxels in the order they are displayed in the palette of Magica Voxel, from the `IMAP` chunk
+    /// saved by Magica Voxel 0.99.7 and later. Empty for files that keep the default order.
+    pub palette_order: Vec<u8>,
+    /// The properties of each render object (`rOBJ`) in the file, such as the environment, bounces and post-processing
+    /// settings used by Magica Voxel's renderer
+    pub render_objects: Vec<HashMap<String, String>>,
+    /// A hash of the bytes of the file, which is the same on every platform and in every release of the crate, for
+    /// keying caches of the scene, negotiating assets over the network or invalidating preprocessed assets. Any edit
+    /// saved in Magica Voxel changes it, including changes to the camera or render settings. Use
+    /// [`crate::VoxelModel::content_hash`] to key off the voxels of a single model instead.
+    pub content_hash: u64,
+}
+
+/// An entry in the [`VoxelFileIndex`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelFileModel {
+    /// The name of the model, as used in the asset label `#{name}@model`
+    pub name: String,
+    /// The size of the model in voxels, in bevy's Y-up space
+    pub size: UVec3,
+    /// The number of solid voxels in the model
+    pub voxel_count: usize,
+    /// The paths of the nodes that instance this model
+    pub node_paths: Vec<String>,
+    /// The names of the layers of the nodes that instance this model
+    pub layers: Vec<String>,
+}
+
+impl VoxelFileIndex {
+    pub(crate) fn from_file(file: &DotVoxData, policy: DuplicateNamePolicy) -> Self {
+        let mut index = Self {
+            models: model_names(file, policy)
+                .into_iter()
+                .zip(file.models.iter())
+                .map(|(name, model)| VoxelFileModel {
+                    name,
+                    size: UVec3::new(model.size.x, model.size.z, model.size.y),
+                    voxel_count: model.voxels.len(),
+                    node_paths: Vec::new(),
+                    layers: Vec::new(),
+                })
+                .collect(),
+            nodes: Vec::new(),
+            layers: file.layers.iter().map(|layer| layer.name()).collect(),
+            unsupported_features: Vec::new(),
+            renamed_nodes: Vec::new(),
+            palette_order: Vec::new(),
+            render_objects: Vec::new(),
+            content_hash: 0,
+        };
+        if let Some(root) = file.scenes.first() {
+            index.index_node(&file.scenes, root, None, None);
+        }
+        index
+    }
+
+    /// Fills in the parts of the index read from chunks that `dot_vox` skips, from the `bytes` of the file, which may
+    /// leave out the voxels of its models, and the `content_hash` of the whole file
+    pub(crate) fn read_chunks(&mut self, bytes: &[u8], content_hash: u64) {
+        self.unsupported_features = unsupported_chunks(bytes);
+        self.palette_order = palette_index_map(bytes);
+        self.render_objects = render_objects(bytes);
+        self.content_hash = content_hash;
+    }
+
+    /// Returns the entry for the model with the supplied name
+    pub fn model(&self, name: &str) -> Option<&VoxelFileModel> {
+        self.models.iter().find(|model| model.name == name)
+    }
+
+    fn index_node(
+        &mut self,
+        graph: &[SceneNode],
+        scene_node: &SceneNode,
+        parent_name: Option<&String>,
+        layer: Option<&String>,
+    ) {
+        match scene_node {
+            SceneNode::Transform {
+                attributes,
+                child,
+                layer_id,
+                ..
+            } => {
+                let (accumulated, node_name) =
+                    get_accumulated_and_node_name(parent_name, attributes.get("_name"));
+                if let Some(node_name) = node_name {
+                    self.nodes.push(node_name);
+                }
+                let layer = self
+                    .layers
+                    .get(*layer_id as usize)
+                    .cloned()
+                    .flatten()
+                    .or(layer.cloned());
+                self.index_node(
+                    graph,
+                    &graph[*child as usize],
+                    accumulated.as_ref(),
+                    layer.as_ref(),
+                );
+            }
+            SceneNode::Group { children, .. } => {
+                for child in children {
+                    self.index_node(graph, &graph[*child as usize], parent_name, layer);
+                }
+            }
+            SceneNode::Shape { models, .. } => {
+                let Some(entry) = models
+                    .first()
+                    .and_then(|model| self.models.get_mut(model.model_id as usize))
+                else {
+                    return;
+                };
+                if let Some(path) = parent_name {
+                    entry.node_paths.push(path.clone());
+                }
+                if let Some(layer) = layer {
+                    if !entry.layers.contains(layer) {
+                        entry.layers.push(layer.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Loads a [`VoxelFileIndex`] from a `.vox` file without meshing any of its models. The file is streamed, reading past
+/// the voxels of its models rather than parsing them, as the index only needs their sizes and counts.
+pub(crate) struct VoxFileIndexLoader;
+
+impl AssetLoader for VoxFileIndexLoader {
+    type Asset = VoxelFileIndex;
+    type Settings = ();
+    type Error = VoxLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let StreamedFile {
+            file,
+            skeleton,
+            models,
+            content_hash,
+        } = read_streamed(reader, |_| {}).await?;
+        validate_file(&file, &load_context.asset_path().to_string())?;
+        let mut index = VoxelFileIndex::from_file(&file, DuplicateNamePolicy::default());
+        index.read_chunks(&skeleton, content_hash);
+        for (model, voxel_count) in index.models.iter_mut().zip(models.voxel_counts()) {
+            model.voxel_count = voxel_count;
+        }
+        Ok(index)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["vox"]
+    }
+}
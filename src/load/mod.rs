@@ -1,26 +1,74 @@
+mod catalog;
+pub(crate) mod chunks;
 mod components;
+mod file_index;
+pub(crate) mod names;
 mod parse_model;
-mod parse_scene;
+pub(crate) mod parse_scene;
+#[cfg(feature = "point_cloud")]
+pub(crate) mod point_cloud;
+mod progress;
+pub(crate) mod spawn;
+pub(crate) mod stream;
+pub(crate) mod tags;
+pub(crate) mod validate;
+
+use std::{
+    io::SeekFrom,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
 
 use anyhow::anyhow;
 use bevy::{
-    asset::{io::Reader, AssetLoader, AsyncReadExt, Handle, LoadContext},
+    asset::{io::Reader, AssetLoader, Handle, LoadContext},
     color::LinearRgba,
-    log::info,
+    ecs::{entity::Entity, query::Without, system::Resource},
+    hierarchy::Parent,
+    log::{info, info_span, warn},
     pbr::StandardMaterial,
+    render::{
+        mesh::Mesh, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology,
+        texture::ImageSampler,
+    },
     scene::Scene,
+    tasks::futures_lite::io::AsyncSeekExt,
     utils::HashSet,
 };
+pub(crate) use catalog::VoxCatalogLoader;
+pub use catalog::{VoxelCatalog, VoxelCatalogEntry, VoxelCatalogId};
 use components::LayerInfo;
-pub use components::{VoxelLayer, VoxelModelInstance};
+pub use components::{
+    VoxelJoint, VoxelJointKind, VoxelLayer, VoxelModelInfo, VoxelModelInstance,
+    VoxelReflectionProbe, VoxelRenderObjects, VoxelSceneInstance, VoxelShapeFrame,
+    VoxelShapeFrames, VoxelSocket,
+};
+use dot_vox::{DotVoxData, Model};
+pub(crate) use file_index::VoxFileIndexLoader;
+pub use file_index::{VoxelFileIndex, VoxelFileModel};
+pub use names::DuplicateNamePolicy;
 use parse_scene::{find_model_names, parse_scene_graph};
+#[cfg(feature = "point_cloud")]
+pub(crate) use point_cloud::VoxPointCloudLoader;
+#[cfg(feature = "point_cloud")]
+pub use point_cloud::VoxPointCloudSettings;
+pub use progress::{VoxelFileLoadProgress, VoxelLoadProgress};
 use serde::{Deserialize, Serialize};
+use stream::StreamedFile;
+pub use tags::VoxelNodeTags;
 use thiserror::Error;
+pub use validate::{validate_vox_bytes, VoxelLintIssue};
 
 use crate::{
-    model::{MaterialProperty, VoxelModel, VoxelPalette},
+    model::{
+        ColliderFilter, DirectionalOcclusion, MaterialProperty, MeshAttributeConfig, PaletteLayout,
+        PalettePrecision, VoxelAudioMaterials, VoxelBrickMap, VoxelFracture, VoxelModel,
+        VoxelPalette, VoxelShapes, VoxelShards,
+    },
     VoxelContext, VoxelData, VoxelQueryable,
 };
+#[cfg(feature = "modify_voxels")]
+use crate::{VoxelBlueprint, VoxelClipboard, VoxelRegionMode};
 
 /// An asset loader capable of loading models in `.vox` files as [`bevy::scene::Scene`]s.
 ///
@@ -29,11 +77,56 @@ use crate::{
 /// You can load multiple models from the same `.vox` file by appending `#{name}` to the asset loading path, where `{name}` corresponds to the object's name in the Magical Voxel world editor.
 /// You can load unnamed models by appending `#model{no}` to the asset loading path, where `{no}` corresponds to the model index in the file. Note that this index is subject to change if you delete models in the Magica Voxel file.
 pub(super) struct VoxSceneLoader {
-    pub(super) global_settings: Option<VoxLoaderSettings>,
+    pub(super) global_settings: VoxSceneGlobalSettings,
+    pub(super) progress: VoxelLoadProgress,
+}
+
+/// Resource holding the [`VoxLoaderSettings`] used for every `.vox` file, so that projects don't need to supply
+/// `.meta` files or `load_with_settings` for every asset. Fields set in an asset's [`VoxLoaderOverrides`] take
+/// precedence.
+///
+/// The initial value can be set with [`crate::VoxScenePlugin::with_settings`]. Changing the settings only affects assets
+/// loaded afterwards.
+#[derive(Resource, Clone, Default)]
+pub struct VoxSceneGlobalSettings {
+    settings: Arc<RwLock<VoxLoaderSettings>>,
+}
+
+impl VoxSceneGlobalSettings {
+    /// Creates a new resource holding the `settings`
+    pub fn new(settings: VoxLoaderSettings) -> Self {
+        Self {
+            settings: Arc::new(RwLock::new(settings)),
+        }
+    }
+
+    /// Returns a copy of the current global settings
+    pub fn get(&self) -> VoxLoaderSettings {
+        self.settings
+            .read()
+            .map(|settings| settings.clone())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the global settings
+    pub fn set(&self, settings: VoxLoaderSettings) {
+        if let Ok(mut current) = self.settings.write() {
+            *current = settings;
+        }
+    }
+
+    /// The global settings with the fields set in the per-load `overrides` replaced
+    pub(crate) fn resolve(&self, overrides: &VoxLoaderOverrides) -> VoxLoaderSettings {
+        overrides.apply(self.get())
+    }
 }
 
 /// Settings for the VoxSceneLoader.
+///
+/// Every asset is loaded with the [`VoxSceneGlobalSettings`], with any fields set in its [`VoxLoaderOverrides`]
+/// replaced.
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct VoxLoaderSettings {
     /// The length of each side of a single voxel. Defaults to 1.0.
     pub voxel_size: f32,
@@ -46,6 +139,266 @@ pub struct VoxLoaderSettings {
     pub uses_srgb: bool,
     /// Magica Voxel doesn't let you adjust the roughness for the default "diffuse" block type, so it can be adjusted with this setting. Defaults to 0.8.
     pub diffuse_roughness: f32,
+    /// How the palette is laid out in the generated material textures. Defaults to [`PaletteLayout::Grid`].
+    /// Use [`PaletteLayout::Strip`] if you are sampling the palette textures with filtering or mip-maps enabled.
+    pub palette_layout: PaletteLayout,
+    /// The sampler used by the generated material textures. Defaults to nearest-neighbour sampling, which avoids colors
+    /// bleeding between neighbouring palette entries.
+    pub palette_sampler: ImageSampler,
+    /// The precision of the generated material textures. Defaults to [`PalettePrecision::Full`]. Use [`PalettePrecision::Reduced`]
+    /// to cut the VRAM used by the palette on mobile and WebGL targets.
+    pub palette_precision: PalettePrecision,
+    /// The platform the assets will be rendered on. Defaults to [`PlatformProfile::Desktop`]. Other profiles override
+    /// settings that the platform doesn't support, so that the same assets can run everywhere.
+    pub platform_profile: PlatformProfile,
+    /// Whether a [`crate::VoxelBlueprint`] should be created for each model, loadable by appending `#{name}@blueprint`
+    /// to the asset path. Defaults to false.
+    pub create_blueprints: bool,
+    /// Whether the generated meshes should include flat per-face tangents, which are required by normal-mapped
+    /// materials. Defaults to false.
+    pub generate_tangents: bool,
+    /// Whether identical vertices are welded after meshing, with the triangles and vertices reordered so that the GPU
+    /// can reuse and fetch vertices efficiently. Defaults to false. This reduces the vertex count of dense models, at
+    /// the cost of slower meshing. Enable the `meshopt` feature to also optimize the triangle order for the vertex cache.
+    pub optimize_meshes: bool,
+    /// The primitives that voxels of each palette index are drawn as, such as ramps and slabs for smoother walkable
+    /// terrain. Defaults to drawing every voxel as a cube.
+    pub voxel_shapes: VoxelShapes,
+    /// Which voxels generate colliders, and their collision groups, when colliders are generated from the models with
+    /// [`crate::VoxelModel::collider_boxes`]. Defaults to every solid voxel, in collision group 1.
+    pub collider_filter: ColliderFilter,
+    /// Which vertex attributes are generated for each mesh. By default only the attributes used by the generated
+    /// materials are included.
+    pub mesh_attributes: MeshAttributeConfig,
+    /// Whether meshing should be deferred until the first instance of each model is spawned. Defaults to false. Enable
+    /// this for files containing many models of which only a few are used, to reduce loading times and memory usage.
+    pub lazy_meshing: bool,
+    /// Whether a [`crate::VoxelBrickMap`] should be created for each model, loadable by appending `#{name}@bricks` to
+    /// the asset path, to accelerate raycasts and region queries against large models. Defaults to false.
+    pub brick_maps: bool,
+    /// The light directions for which a static occlusion term is baked into each mesh as
+    /// [`crate::ATTRIBUTE_DIRECTIONAL_OCCLUSION`]. Defaults to no directions, in which case nothing is baked.
+    pub directional_occlusion: DirectionalOcclusion,
+    /// Whether files with any of the problems reported by [`crate::validate_vox_bytes`] fail to load, rather than
+    /// loading as well as possible. Defaults to false.
+    pub strict: bool,
+    /// How nodes sharing a path are labelled. Defaults to [`DuplicateNamePolicy::Shared`].
+    pub duplicate_names: DuplicateNamePolicy,
+    /// Whether to load a RON sidecar of gameplay properties for the palette, with the same name as the `.vox` file but
+    /// the extension `.elements.ron`, eg `study.elements.ron` for `study.vox`. The sidecar is parsed into a
+    /// [`crate::VoxelElementData`] by a [`crate::VoxelElementDataPlugin`]. Defaults to false.
+    pub element_data: bool,
+    /// A directory in which meshes are cached between runs, keyed by the voxels of each model, the meshing settings and
+    /// the palette, so that repeated loads of large unchanged files skip meshing. Defaults to `None`, which disables
+    /// the cache. Intended for development, with a path such as `target/vox_mesh_cache`; the cache is never cleaned
+    /// up, and isn't available on the web.
+    pub mesh_cache: Option<PathBuf>,
+    /// The number of shards each model is split into along 3D Voronoi cells, for pre-fractured destruction. Defaults
+    /// to 0, which disables fracturing. The shards are loadable as a [`crate::VoxelShards`] by appending
+    /// `#{name}@shards` to the asset path, and each shard's model by appending `#{name}/shard-{index}@model`. The
+    /// cells are placed deterministically, so a model is always split the same way.
+    pub fracture_shards: u32,
+}
+
+/// The [`VoxLoaderSettings`] supplied for a single asset, either in a `.meta` file or with
+/// [`bevy::asset::AssetServer::load_with_settings`]. Each field that is set overrides the same field of the
+/// [`VoxSceneGlobalSettings`], even if it is set to the default value, and the other fields keep their global values.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_vox_scene::VoxLoaderOverrides;
+/// # fn setup(mut commands: Commands, assets: Res<AssetServer>) {
+/// commands.spawn(SceneBundle {
+///     scene: assets.load_with_settings("study.vox", |settings: &mut VoxLoaderOverrides| {
+///         settings.voxel_size = Some(0.1);
+///         settings.emission_strength = Some(4.0);
+///     }),
+///     ..default()
+/// });
+/// # }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct VoxLoaderOverrides {
+    /// Overrides [`VoxLoaderSettings::voxel_size`]
+    pub voxel_size: Option<f32>,
+    /// Overrides [`VoxLoaderSettings::mesh_outer_faces`]
+    pub mesh_outer_faces: Option<bool>,
+    /// Overrides [`VoxLoaderSettings::emission_strength`]
+    pub emission_strength: Option<f32>,
+    /// Overrides [`VoxLoaderSettings::uses_srgb`]
+    pub uses_srgb: Option<bool>,
+    /// Overrides [`VoxLoaderSettings::diffuse_roughness`]
+    pub diffuse_roughness: Option<f32>,
+    /// Overrides [`VoxLoaderSettings::palette_layout`]
+    pub palette_layout: Option<PaletteLayout>,
+    /// Overrides [`VoxLoaderSettings::palette_sampler`]
+    pub palette_sampler: Option<ImageSampler>,
+    /// Overrides [`VoxLoaderSettings::palette_precision`]
+    pub palette_precision: Option<PalettePrecision>,
+    /// Overrides [`VoxLoaderSettings::platform_profile`]
+    pub platform_profile: Option<PlatformProfile>,
+    /// Overrides [`VoxLoaderSettings::create_blueprints`]
+    pub create_blueprints: Option<bool>,
+    /// Overrides [`VoxLoaderSettings::generate_tangents`]
+    pub generate_tangents: Option<bool>,
+    /// Overrides [`VoxLoaderSettings::optimize_meshes`]
+    pub optimize_meshes: Option<bool>,
+    /// Overrides [`VoxLoaderSettings::voxel_shapes`]
+    pub voxel_shapes: Option<VoxelShapes>,
+    /// Overrides [`VoxLoaderSettings::collider_filter`]
+    pub collider_filter: Option<ColliderFilter>,
+    /// Overrides [`VoxLoaderSettings::mesh_attributes`]
+    pub mesh_attributes: Option<MeshAttributeConfig>,
+    /// Overrides [`VoxLoaderSettings::lazy_meshing`]
+    pub lazy_meshing: Option<bool>,
+    /// Overrides [`VoxLoaderSettings::brick_maps`]
+    pub brick_maps: Option<bool>,
+    /// Overrides [`VoxLoaderSettings::directional_occlusion`]
+    pub directional_occlusion: Option<DirectionalOcclusion>,
+    /// Overrides [`VoxLoaderSettings::strict`]
+    pub strict: Option<bool>,
+    /// Overrides [`VoxLoaderSettings::duplicate_names`]
+    pub duplicate_names: Option<DuplicateNamePolicy>,
+    /// Overrides [`VoxLoaderSettings::element_data`]
+    pub element_data: Option<bool>,
+    /// Overrides [`VoxLoaderSettings::mesh_cache`]. Set it to `Some(None)` to disable a cache set in the global settings.
+    pub mesh_cache: Option<Option<PathBuf>>,
+    /// Overrides [`VoxLoaderSettings::fracture_shards`]
+    pub fracture_shards: Option<u32>,
+}
+
+impl VoxLoaderOverrides {
+    /// Returns the `settings` with every field that is set in the overrides replaced
+    pub fn apply(&self, mut settings: VoxLoaderSettings) -> VoxLoaderSettings {
+        if let Some(voxel_size) = self.voxel_size {
+            settings.voxel_size = voxel_size;
+        }
+        if let Some(mesh_outer_faces) = self.mesh_outer_faces {
+            settings.mesh_outer_faces = mesh_outer_faces;
+        }
+        if let Some(emission_strength) = self.emission_strength {
+            settings.emission_strength = emission_strength;
+        }
+        if let Some(uses_srgb) = self.uses_srgb {
+            settings.uses_srgb = uses_srgb;
+        }
+        if let Some(diffuse_roughness) = self.diffuse_roughness {
+            settings.diffuse_roughness = diffuse_roughness;
+        }
+        if let Some(palette_layout) = self.palette_layout {
+            settings.palette_layout = palette_layout;
+        }
+        if let Some(palette_sampler) = &self.palette_sampler {
+            settings.palette_sampler = palette_sampler.clone();
+        }
+        if let Some(palette_precision) = self.palette_precision {
+            settings.palette_precision = palette_precision;
+        }
+        if let Some(platform_profile) = self.platform_profile {
+            settings.platform_profile = platform_profile;
+        }
+        if let Some(create_blueprints) = self.create_blueprints {
+            settings.create_blueprints = create_blueprints;
+        }
+        if let Some(generate_tangents) = self.generate_tangents {
+            settings.generate_tangents = generate_tangents;
+        }
+        if let Some(optimize_meshes) = self.optimize_meshes {
+            settings.optimize_meshes = optimize_meshes;
+        }
+        if let Some(voxel_shapes) = &self.voxel_shapes {
+            settings.voxel_shapes = voxel_shapes.clone();
+        }
+        if let Some(collider_filter) = &self.collider_filter {
+            settings.collider_filter = collider_filter.clone();
+        }
+        if let Some(mesh_attributes) = self.mesh_attributes {
+            settings.mesh_attributes = mesh_attributes;
+        }
+        if let Some(lazy_meshing) = self.lazy_meshing {
+            settings.lazy_meshing = lazy_meshing;
+        }
+        if let Some(brick_maps) = self.brick_maps {
+            settings.brick_maps = brick_maps;
+        }
+        if let Some(directional_occlusion) = &self.directional_occlusion {
+            settings.directional_occlusion = directional_occlusion.clone();
+        }
+        if let Some(strict) = self.strict {
+            settings.strict = strict;
+        }
+        if let Some(duplicate_names) = self.duplicate_names {
+            settings.duplicate_names = duplicate_names;
+        }
+        if let Some(element_data) = self.element_data {
+            settings.element_data = element_data;
+        }
+        if let Some(mesh_cache) = &self.mesh_cache {
+            settings.mesh_cache = mesh_cache.clone();
+        }
+        if let Some(fracture_shards) = self.fracture_shards {
+            settings.fracture_shards = fracture_shards;
+        }
+        settings
+    }
+}
+
+impl From<VoxLoaderSettings> for VoxLoaderOverrides {
+    /// Overrides every field with the `settings`
+    fn from(settings: VoxLoaderSettings) -> Self {
+        Self {
+            voxel_size: Some(settings.voxel_size),
+            mesh_outer_faces: Some(settings.mesh_outer_faces),
+            emission_strength: Some(settings.emission_strength),
+            uses_srgb: Some(settings.uses_srgb),
+            diffuse_roughness: Some(settings.diffuse_roughness),
+            palette_layout: Some(settings.palette_layout),
+            palette_sampler: Some(settings.palette_sampler),
+            palette_precision: Some(settings.palette_precision),
+            platform_profile: Some(settings.platform_profile),
+            create_blueprints: Some(settings.create_blueprints),
+            generate_tangents: Some(settings.generate_tangents),
+            optimize_meshes: Some(settings.optimize_meshes),
+            voxel_shapes: Some(settings.voxel_shapes),
+            collider_filter: Some(settings.collider_filter),
+            mesh_attributes: Some(settings.mesh_attributes),
+            lazy_meshing: Some(settings.lazy_meshing),
+            brick_maps: Some(settings.brick_maps),
+            directional_occlusion: Some(settings.directional_occlusion),
+            strict: Some(settings.strict),
+            duplicate_names: Some(settings.duplicate_names),
+            element_data: Some(settings.element_data),
+            mesh_cache: Some(settings.mesh_cache),
+            fracture_shards: Some(settings.fracture_shards),
+        }
+    }
+}
+
+/// The rendering capabilities of the platform that the scene will be loaded on.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlatformProfile {
+    /// All settings are honored as supplied.
+    #[default]
+    Desktop,
+    /// Forces [`PalettePrecision::Reduced`], as WebGL2 can't filter `Rgba32Float` textures and doesn't support
+    /// `R16Unorm` or `Rgba16Unorm` textures, and renders glass voxels as opaque, as WebGL2 doesn't support specular transmission.
+    WebGL2,
+    /// Forces [`PalettePrecision::Reduced`] to reduce VRAM usage.
+    Mobile,
+}
+
+impl PlatformProfile {
+    fn palette_precision(&self, requested: PalettePrecision) -> PalettePrecision {
+        match self {
+            PlatformProfile::Desktop => requested,
+            PlatformProfile::WebGL2 | PlatformProfile::Mobile => PalettePrecision::Reduced,
+        }
+    }
+
+    fn supports_transmission(&self) -> bool {
+        *self != PlatformProfile::WebGL2
+    }
 }
 
 impl Default for VoxLoaderSettings {
@@ -56,33 +409,207 @@ impl Default for VoxLoaderSettings {
             emission_strength: 10.0,
             uses_srgb: true,
             diffuse_roughness: 0.8,
+            palette_layout: PaletteLayout::default(),
+            palette_sampler: ImageSampler::nearest(),
+            palette_precision: PalettePrecision::default(),
+            platform_profile: PlatformProfile::default(),
+            create_blueprints: false,
+            generate_tangents: false,
+            optimize_meshes: false,
+            voxel_shapes: VoxelShapes::default(),
+            collider_filter: ColliderFilter::default(),
+            mesh_attributes: MeshAttributeConfig::default(),
+            lazy_meshing: false,
+            brick_maps: false,
+            directional_occlusion: DirectionalOcclusion::default(),
+            strict: false,
+            duplicate_names: DuplicateNamePolicy::default(),
+            element_data: false,
+            mesh_cache: None,
+            fracture_shards: 0,
         }
     }
 }
 
+impl PartialEq for VoxLoaderSettings {
+    fn eq(&self, other: &Self) -> bool {
+        self.voxel_size == other.voxel_size
+            && self.mesh_outer_faces == other.mesh_outer_faces
+            && self.emission_strength == other.emission_strength
+            && self.uses_srgb == other.uses_srgb
+            && self.diffuse_roughness == other.diffuse_roughness
+            && self.palette_layout == other.palette_layout
+            // ImageSampler doesn't implement PartialEq
+            && format!("{:?}", self.palette_sampler) == format!("{:?}", other.palette_sampler)
+            && self.palette_precision == other.palette_precision
+            && self.platform_profile == other.platform_profile
+            && self.create_blueprints == other.create_blueprints
+            && self.generate_tangents == other.generate_tangents
+            && self.optimize_meshes == other.optimize_meshes
+            && self.voxel_shapes == other.voxel_shapes
+            && self.collider_filter == other.collider_filter
+            && self.mesh_attributes == other.mesh_attributes
+            && self.lazy_meshing == other.lazy_meshing
+            && self.brick_maps == other.brick_maps
+            && self.directional_occlusion == other.directional_occlusion
+            && self.strict == other.strict
+            && self.duplicate_names == other.duplicate_names
+            && self.element_data == other.element_data
+            && self.mesh_cache == other.mesh_cache
+            && self.fracture_shards == other.fracture_shards
+    }
+}
+
+impl VoxLoaderSettings {
+    /// Creates the palette for the `file`, honoring the palette and platform settings
+    pub(crate) fn create_palette(&self, file: &DotVoxData) -> VoxelPalette {
+        self.configure_palette(VoxelPalette::from_data(
+            file,
+            self.diffuse_roughness,
+            self.emission_strength,
+        ))
+    }
+
+    /// Applies the palette and platform settings to a `palette` created from any source
+    pub(crate) fn configure_palette(&self, mut palette: VoxelPalette) -> VoxelPalette {
+        if !self.platform_profile.supports_transmission() {
+            palette = palette.without_transmission();
+        }
+        palette
+            .with_layout(self.palette_layout)
+            .with_sampler(self.palette_sampler.clone())
+            .with_precision(
+                self.platform_profile
+                    .palette_precision(self.palette_precision),
+            )
+    }
+
+    /// Converts the `model` to voxel data, honoring the meshing settings
+    pub(crate) fn create_data(&self, model: &Model) -> VoxelData {
+        self.configure_data(VoxelData::from_model(
+            model,
+            self.mesh_outer_faces,
+            self.voxel_size,
+        ))
+    }
+
+    /// Applies the meshing settings to `data` created from any source
+    pub(crate) fn configure_data(&self, data: VoxelData) -> VoxelData {
+        data.with_tangents(self.generate_tangents)
+            .with_mesh_optimization(self.optimize_meshes)
+            .with_shapes(self.voxel_shapes.clone())
+            .with_collider_filter(self.collider_filter.clone())
+            .with_attributes(self.mesh_attributes)
+            .with_directional_occlusion(self.directional_occlusion.clone())
+    }
+}
+
+/// The names the loader gives to each model in the `file` under the `policy`, in the order the models appear in the
+/// file
+pub(crate) fn model_names(file: &DotVoxData, policy: DuplicateNamePolicy) -> Vec<String> {
+    let mut model_names: Vec<Option<String>> = vec![None; file.models.len()];
+    if let Some(root) = file.scenes.first() {
+        find_model_names(&mut model_names, &file.scenes, root, None);
+    }
+    names::dedupe_model_names(&mut model_names, policy);
+    model_names
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| name.unwrap_or(format!("model-{}", index)))
+        .collect()
+}
+
+/// An error encountered while loading a `.vox` file
 #[derive(Error, Debug)]
 pub enum VoxLoaderError {
+    /// The file could not be read or parsed
     #[error(transparent)]
     InvalidAsset(#[from] anyhow::Error),
+    /// The file contains a chunk that the loader doesn't support
+    #[error("{path}: unsupported chunk `{chunk}`")]
+    UnsupportedChunk {
+        /// The path of the asset being loaded
+        path: String,
+        /// The id of the chunk, eg `nNOT`
+        chunk: String,
+    },
+    /// A shape node references a model that isn't in the file
+    #[error("{path}: node `{node}` references missing model {model_id}")]
+    MissingModel {
+        /// The path of the asset being loaded
+        path: String,
+        /// The path of the node in the scene graph
+        node: String,
+        /// The index of the missing model
+        model_id: u32,
+    },
+    /// A model contains a voxel whose color index is outside of the palette
+    #[error("{path}: model {model} contains a voxel with out-of-range palette index {index}")]
+    PaletteOutOfRange {
+        /// The path of the asset being loaded
+        path: String,
+        /// The index of the model in the file
+        model: usize,
+        /// The palette index of the voxel
+        index: u8,
+    },
+    /// The file has problems reported by [`crate::validate_vox_bytes`], and was loaded with
+    /// [`VoxLoaderSettings::strict`]
+    #[error("{path}: {}", .issues.iter().map(|issue| issue.to_string()).collect::<Vec<String>>().join("; "))]
+    Lint {
+        /// The path of the asset being loaded
+        path: String,
+        /// The problems found in the file
+        issues: Vec<VoxelLintIssue>,
+    },
+    /// Several nodes share a path, and the file was loaded with [`DuplicateNamePolicy::Error`]
+    #[error("{path}: several nodes are named `{name}`")]
+    DuplicateName {
+        /// The path of the asset being loaded
+        path: String,
+        /// The path of the nodes
+        name: String,
+    },
+    /// A node of the scene graph is its own ancestor
+    #[error("{path}: the scene graph contains a cycle at node `{node}`")]
+    SceneGraphCycle {
+        /// The path of the asset being loaded
+        path: String,
+        /// The path of the node in the scene graph
+        node: String,
+    },
 }
 
 impl AssetLoader for VoxSceneLoader {
     type Asset = Scene;
-    type Settings = VoxLoaderSettings;
+    type Settings = VoxLoaderOverrides;
     type Error = VoxLoaderError;
 
     async fn load<'a>(
         &'a self,
         reader: &'a mut Reader<'_>,
-        _settings: &'a VoxLoaderSettings,
-        _load_context: &'a mut LoadContext<'_>,
+        settings: &'a VoxLoaderOverrides,
+        load_context: &'a mut LoadContext<'_>,
     ) -> Result<Self::Asset, Self::Error> {
-        let mut bytes = Vec::new();
-        reader
-            .read_to_end(&mut bytes)
-            .await
-            .map_err(|e| VoxLoaderError::InvalidAsset(anyhow!(e)))?;
-        self.process_vox_file(&bytes, _load_context, _settings)
+        let file_path = load_context.path().to_path_buf();
+        self.progress.start(&file_path);
+        let element_data = if self.global_settings.resolve(settings).element_data {
+            let sidecar = load_context.path().with_extension("elements.ron");
+            match load_context.read_asset_bytes(sidecar.clone()).await {
+                Ok(bytes) => Some(Arc::from(String::from_utf8_lossy(&bytes).as_ref())),
+                Err(error) => {
+                    warn!("Failed to read element data {}: {error}", sidecar.display());
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let scene = self
+            .process_vox_file(reader, load_context, settings, element_data)
+            .await;
+        self.progress.finish(&file_path);
+        scene
     }
 
     fn extensions(&self) -> &[&str] {
@@ -91,25 +618,72 @@ impl AssetLoader for VoxSceneLoader {
 }
 
 impl VoxSceneLoader {
-    fn process_vox_file<'a>(
+    async fn process_vox_file<'a>(
         &self,
-        bytes: &'a [u8],
-        mut load_context: &'a mut LoadContext,
-        settings: &'a VoxLoaderSettings,
+        reader: &'a mut Reader<'_>,
+        mut load_context: &'a mut LoadContext<'_>,
+        settings: &'a VoxLoaderOverrides,
+        element_data: Option<Arc<str>>,
     ) -> Result<Scene, VoxLoaderError> {
-        let file = match dot_vox::load_bytes(bytes) {
-            Ok(data) => data,
-            Err(error) => return Err(VoxLoaderError::InvalidAsset(anyhow!(error))),
-        };
+        let file_path = load_context.path().to_path_buf();
+        // readers that can seek know the length of the file, so the bytes read can be shown as a fraction of it
+        if let Ok(length) = reader.seek(SeekFrom::End(0)).await {
+            reader
+                .seek(SeekFrom::Start(0))
+                .await
+                .map_err(|error| anyhow!(error))?;
+            self.progress.update(&file_path, |progress| {
+                progress.total_bytes = Some(length as usize)
+            });
+        }
+        // the file is read chunk by chunk, skipping the voxels of each model, which are only read back from the file
+        // once the model is meshed, so that neither the whole file nor the voxels of every model are held at once
+        let StreamedFile {
+            mut file,
+            skeleton,
+            models,
+            content_hash,
+        } = stream::read_streamed(reader, |bytes_read| {
+            self.progress
+                .update(&file_path, |progress| progress.bytes_read = bytes_read)
+        })
+        .await?;
+        let bytes = skeleton.as_slice();
+        let voxel_counts = models.voxel_counts();
         info!("Loading {}", load_context.asset_path());
-        let settings = self.global_settings.clone().unwrap_or(settings.clone());
+        let path = load_context.asset_path().to_string();
+        // the span is only entered between reads of the file, as it can't be held across them
+        let span = info_span!("vox_load", path = %path);
+        let guard = span.enter();
+        let settings = self.global_settings.resolve(settings);
+        validate::validate_file(&file, &path)?;
+        if file.scenes.is_empty() {
+            return Err(VoxLoaderError::InvalidAsset(anyhow!(
+                "{path}: the file has no scene graph"
+            )));
+        }
+        if settings.strict {
+            let issues = validate::lint_streamed_file(&file, bytes, &voxel_counts);
+            if let Some(VoxelLintIssue::UnsupportedChunk { chunk }) = issues.first() {
+                return Err(VoxLoaderError::UnsupportedChunk {
+                    path,
+                    chunk: chunk.clone(),
+                });
+            }
+            if !issues.is_empty() {
+                return Err(VoxLoaderError::Lint { path, issues });
+            }
+        }
+        validate::warn_ignored_features(&file, &path);
+        let renamed_nodes =
+            names::resolve_duplicate_names(&mut file, settings.duplicate_names, &path)?;
 
         // Palette
-        let palette = VoxelPalette::from_data(
-            &file,
-            settings.diffuse_roughness,
-            settings.emission_strength,
-        );
+        let mut palette = settings
+            .create_palette(&file)
+            .with_notes(chunks::palette_notes(bytes))
+            .with_display_order(chunks::palette_index_map(bytes));
+        palette.element_data_source = element_data;
         let translucent_material = palette.create_material_in_load_context(load_context);
         let opaque_material = load_context.labeled_asset_scope("material".to_string(), |_| {
             let mut opaque_material = translucent_material.clone();
@@ -125,7 +699,6 @@ impl VoxSceneLoader {
                 non_emissive
             });
         }
-        let indices_of_refraction = palette.indices_of_refraction.clone();
 
         // Scene graph
         let layers: Vec<LayerInfo> = file
@@ -141,7 +714,9 @@ impl VoxSceneLoader {
         let mut subassets: HashSet<String> = HashSet::new();
         let mut model_names: Vec<Option<String>> = vec![None; model_count];
         find_model_names(&mut model_names, &file.scenes, &file.scenes[0], None);
-        let scene = parse_scene_graph(
+        names::dedupe_model_names(&mut model_names, settings.duplicate_names);
+        let scene_span = info_span!("vox_parse_scene", nodes = file.scenes.len()).entered();
+        let mut scene = parse_scene_graph(
             &mut load_context,
             &file.scenes,
             &file.scenes[0],
@@ -152,51 +727,65 @@ impl VoxSceneLoader {
             settings.voxel_size,
         );
 
-        // Models
+        let mut index = VoxelFileIndex::from_file(&file, settings.duplicate_names);
+        index.read_chunks(bytes, content_hash);
+        index.renamed_nodes = renamed_nodes;
+        for (model, voxel_count) in index.models.iter_mut().zip(voxel_counts) {
+            model.voxel_count = voxel_count;
+        }
+        if !index.unsupported_features.is_empty() {
+            warn!(
+                "{path}: skipped unsupported chunks {}",
+                index.unsupported_features.join(", ")
+            );
+        }
+        if !index.render_objects.is_empty() {
+            let root = scene
+                .world
+                .query_filtered::<Entity, Without<Parent>>()
+                .iter(&scene.world)
+                .next();
+            if let Some(root) = root {
+                scene
+                    .world
+                    .entity_mut(root)
+                    .insert(VoxelRenderObjects(index.render_objects.clone()));
+            }
+        }
+        load_context.add_labeled_asset("index".to_string(), index);
+        drop(scene_span);
 
-        model_names
-            .iter()
-            .zip(file.models)
-            .enumerate()
-            .for_each(|(index, (maybe_name, model))| {
-                let name = maybe_name.clone().unwrap_or(format!("model-{}", index));
-                let data =
-                    VoxelData::from_model(&model, settings.mesh_outer_faces, settings.voxel_size);
-                let (visible_voxels, ior) = data.visible_voxels(&indices_of_refraction);
-                let mesh = load_context.labeled_asset_scope(format!("{}@mesh", name), |_| {
-                    crate::model::mesh::mesh_model(&visible_voxels, &data)
-                });
+        // Models
 
-                let material: Handle<StandardMaterial> = if let Some(ior) = ior {
-                    load_context.labeled_asset_scope(format!("{}@material", name), |_| {
-                        let mut material = translucent_material.clone();
-                        material.ior = ior;
-                        material.thickness = data.size().min_element() as f32;
-                        material
-                    })
-                } else {
-                    load_context.labeled_asset_scope(format!("{}@material", name), |_| {
-                        let mut opaque_material = translucent_material.clone();
-                        opaque_material.specular_transmission_texture = None;
-                        opaque_material.specular_transmission = 0.0;
-                        opaque_material
-                    })
-                };
-                load_context.labeled_asset_scope(format!("{}@model", name), |_| VoxelModel {
-                    name,
-                    data,
-                    mesh,
-                    material,
-                    has_translucency: ior.is_some(),
-                });
-            });
+        self.progress
+            .update(&file_path, |progress| progress.total_models = model_count);
+        drop(guard);
+        for (index, maybe_name) in model_names.iter().enumerate() {
+            let name = maybe_name.clone().unwrap_or(format!("model-{}", index));
+            let model = models.model(reader, &file, index, &path).await?;
+            let _guard = span.enter();
+            add_model_assets(
+                load_context,
+                name,
+                &model,
+                &settings,
+                &palette,
+                &translucent_material,
+                None,
+                "voxel-context",
+            );
+            self.progress
+                .update(&file_path, |progress| progress.models_meshed = index + 1);
+        }
 
+        let _guard = span.enter();
         let transmissive_material = load_context
             .add_labeled_asset("material-transmissive".to_string(), translucent_material);
         load_context.add_labeled_asset(
             "voxel-context".to_string(),
             VoxelContext {
                 palette,
+                audio_materials: VoxelAudioMaterials::from_data(&file),
                 opaque_material,
                 transmissive_material,
             },
@@ -204,3 +793,132 @@ impl VoxSceneLoader {
         Ok(scene)
     }
 }
+
+/// Adds the mesh, material and [`VoxelModel`] of the `model` to the load context, labelled `{name}@mesh`,
+/// `{name}@material` and `{name}@model`, for a palette whose [`VoxelContext`] is labelled `context_label`, along with
+/// its blueprint, brick map and shards if the `settings` ask for them. Opaque models use the `shared_opaque_material`
+/// rather than a material of their own, if there is one.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn add_model_assets(
+    load_context: &mut LoadContext,
+    name: String,
+    model: &Model,
+    settings: &VoxLoaderSettings,
+    palette: &VoxelPalette,
+    translucent_material: &StandardMaterial,
+    shared_opaque_material: Option<&Handle<StandardMaterial>>,
+    context_label: &str,
+) -> Handle<VoxelModel> {
+    let _span = info_span!(
+        "vox_load_model",
+        name = %name,
+        voxels = model.voxels.len()
+    )
+    .entered();
+    let data = settings.create_data(model);
+    #[cfg(feature = "modify_voxels")]
+    if settings.create_blueprints {
+        load_context.labeled_asset_scope(format!("{}@blueprint", name), |context| {
+            VoxelBlueprint::from_clipboard(VoxelClipboard::copy(
+                &data,
+                context.get_label_handle::<VoxelContext>(context_label).id(),
+                palette,
+                VoxelRegionMode::All,
+            ))
+        });
+    }
+    if settings.fracture_shards > 0 {
+        let shards = data.fracture(&VoxelFracture::Count {
+            count: settings.fracture_shards,
+            seed: data.content_hash(),
+        });
+        let shards = shards
+            .into_iter()
+            .enumerate()
+            .map(|(index, shard)| {
+                let model = add_data_assets(
+                    load_context,
+                    format!("{}/shard-{}", name, index),
+                    shard.data,
+                    settings,
+                    palette,
+                    translucent_material,
+                    shared_opaque_material,
+                    None,
+                );
+                (model, shard.offset)
+            })
+            .collect();
+        load_context.add_labeled_asset(format!("{}@shards", name), VoxelShards { shards });
+    }
+    let brick_map = settings.brick_maps.then(|| {
+        load_context.add_labeled_asset(format!("{}@bricks", name), VoxelBrickMap::from_data(&data))
+    });
+    add_data_assets(
+        load_context,
+        name,
+        data,
+        settings,
+        palette,
+        translucent_material,
+        shared_opaque_material,
+        brick_map,
+    )
+}
+
+/// Adds the mesh, material and [`VoxelModel`] of the `data` to the load context, labelled `{name}@mesh`,
+/// `{name}@material` and `{name}@model`. The material is only added if the model is translucent or there is no
+/// `shared_opaque_material`. With [`VoxLoaderSettings::lazy_meshing`], whether the model is translucent isn't known
+/// until it is meshed, so every model gets a material of its own.
+#[allow(clippy::too_many_arguments)]
+fn add_data_assets(
+    load_context: &mut LoadContext,
+    name: String,
+    data: VoxelData,
+    settings: &VoxLoaderSettings,
+    palette: &VoxelPalette,
+    translucent_material: &StandardMaterial,
+    shared_opaque_material: Option<&Handle<StandardMaterial>>,
+    brick_map: Option<Handle<VoxelBrickMap>>,
+) -> Handle<VoxelModel> {
+    let (mesh, ior) = if settings.lazy_meshing {
+        (
+            Mesh::new(
+                PrimitiveTopology::TriangleList,
+                RenderAssetUsages::default(),
+            ),
+            None,
+        )
+    } else {
+        data.remesh_cached(palette, settings.mesh_cache.as_deref())
+    };
+    let mesh = load_context.add_labeled_asset(format!("{}@mesh", name), mesh);
+
+    let material: Handle<StandardMaterial> = if let Some(ior) = ior {
+        load_context.labeled_asset_scope(format!("{}@material", name), |_| {
+            let mut material = translucent_material.clone();
+            material.ior = ior;
+            material.thickness = data.size().min_element() as f32;
+            material
+        })
+    } else if let Some(opaque_material) = shared_opaque_material.filter(|_| !settings.lazy_meshing)
+    {
+        opaque_material.clone()
+    } else {
+        load_context.labeled_asset_scope(format!("{}@material", name), |_| {
+            let mut opaque_material = translucent_material.clone();
+            opaque_material.specular_transmission_texture = None;
+            opaque_material.specular_transmission = 0.0;
+            opaque_material
+        })
+    };
+    load_context.labeled_asset_scope(format!("{}@model", name), |_| VoxelModel {
+        name,
+        data,
+        mesh,
+        material,
+        has_translucency: ior.is_some(),
+        mesh_pending: settings.lazy_meshing,
+        brick_map,
+    })
+}
@@ -0,0 +1,104 @@
+use bevy::utils::HashSet;
+use dot_vox::{DotVoxData, SceneNode};
+use serde::{Deserialize, Serialize};
+
+use super::{parse_scene::get_accumulated_and_node_name, VoxLoaderError};
+
+/// How the loader handles several nodes with the same path, whose sub-asset labels would otherwise collide.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateNamePolicy {
+    /// Nodes and models keep their names even when they collide, labelling the assets of existing files just as before
+    /// this setting was added. Only one of the colliding assets can be loaded by its label.
+    #[default]
+    Shared,
+    /// Only the first node with each path can be loaded by its label. Later models sharing a name with an earlier model
+    /// are labelled by their index instead, eg `model-3`.
+    KeepFirst,
+    /// Later nodes with a path that has already been used are renamed by appending `-1`, `-2` and so on to the node's
+    /// name. The renamed nodes are listed in [`crate::VoxelFileIndex::renamed_nodes`].
+    Suffix,
+    /// Files containing duplicate paths fail to load with [`VoxLoaderError::DuplicateName`].
+    Error,
+}
+
+/// Applies the `policy` to the node names in the `file`, returning the original and final path of each renamed node
+pub(crate) fn resolve_duplicate_names(
+    file: &mut DotVoxData,
+    policy: DuplicateNamePolicy,
+    path: &str,
+) -> Result<Vec<(String, String)>, VoxLoaderError> {
+    let mut renamed: Vec<(String, String)> = Vec::new();
+    if matches!(
+        policy,
+        DuplicateNamePolicy::Shared | DuplicateNamePolicy::KeepFirst
+    ) || file.scenes.is_empty()
+    {
+        return Ok(renamed);
+    }
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut stack: Vec<(u32, Option<String>)> = vec![(0, None)];
+    while let Some((index, parent_name)) = stack.pop() {
+        let Some(scene_node) = file.scenes.get_mut(index as usize) else {
+            continue;
+        };
+        match scene_node {
+            SceneNode::Transform {
+                attributes, child, ..
+            } => {
+                let (mut accumulated, node_name) =
+                    get_accumulated_and_node_name(parent_name.as_ref(), attributes.get("_name"));
+                if let (Some(node_name), Some(name)) = (node_name, attributes.get("_name").cloned())
+                {
+                    if !seen.insert(node_name.clone()) {
+                        if policy == DuplicateNamePolicy::Error {
+                            return Err(VoxLoaderError::DuplicateName {
+                                path: path.to_string(),
+                                name: node_name,
+                            });
+                        }
+                        let (unique_name, unique_path) = (1..)
+                            .map(|suffix| {
+                                let unique_name = format!("{}-{}", name, suffix);
+                                let (_, unique_path) = get_accumulated_and_node_name(
+                                    parent_name.as_ref(),
+                                    Some(&unique_name),
+                                );
+                                (unique_name, unique_path.unwrap_or_default())
+                            })
+                            .find(|(_, unique_path)| !seen.contains(unique_path))
+                            .expect("a free suffix");
+                        seen.insert(unique_path.clone());
+                        attributes.insert("_name".to_string(), unique_name);
+                        renamed.push((node_name, unique_path.clone()));
+                        accumulated = Some(unique_path);
+                    }
+                }
+                stack.push((*child, accumulated));
+            }
+            SceneNode::Group { children, .. } => {
+                // visit the children in order, so that the first node keeps its name
+                for child in children.iter().rev() {
+                    stack.push((*child, parent_name.clone()));
+                }
+            }
+            SceneNode::Shape { .. } => {}
+        }
+    }
+    Ok(renamed)
+}
+
+/// Clears the names of models that share a name with an earlier model, so that they are labelled by their index. Only
+/// [`DuplicateNamePolicy::KeepFirst`] clears any names.
+pub(crate) fn dedupe_model_names(model_names: &mut [Option<String>], policy: DuplicateNamePolicy) {
+    if policy != DuplicateNamePolicy::KeepFirst {
+        return;
+    }
+    let mut seen: HashSet<String> = HashSet::new();
+    for name in model_names.iter_mut() {
+        if let Some(unique) = name {
+            if !seen.insert(unique.clone()) {
+                *name = None;
+            }
+        }
+    }
+}
@@ -5,7 +5,7 @@ use crate::model::{RawVoxel, VoxelData};
 
 impl VoxelData {
     /// Ingest Magica Voxel data and perform coordinate conversion from MV's left-handed Z-up to bevy's right-handed Y-up
-    pub(super) fn from_model(model: &Model, mesh_outer_faces: bool, voxel_size: f32) -> VoxelData {
+    pub(crate) fn from_model(model: &Model, mesh_outer_faces: bool, voxel_size: f32) -> VoxelData {
         let mut data = VoxelData::new(
             UVec3::new(model.size.x, model.size.z, model.size.y),
             mesh_outer_faces,
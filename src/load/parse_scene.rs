@@ -13,7 +13,10 @@ use bevy::{
 };
 use dot_vox::{Frame, SceneNode};
 
-use crate::{VoxelLayer, VoxelModelInstance};
+use crate::{
+    VoxelJoint, VoxelJointKind, VoxelLayer, VoxelModelInstance, VoxelReflectionProbe,
+    VoxelShapeFrame, VoxelShapeFrames, VoxelSocket,
+};
 
 use super::components::LayerInfo;
 
@@ -113,6 +116,15 @@ pub(super) fn parse_scene_graph(
                 Visibility::Inherited
             };
             node.insert(visibility);
+            if let Some(socket) = socket_name(attributes.get("_name"), &graph[*child as usize]) {
+                node.insert(VoxelSocket(socket));
+            }
+            if let Some(probe) = probe_name(attributes.get("_name")) {
+                node.insert(VoxelReflectionProbe(probe));
+            }
+            if let Some(kind) = joint_kind(attributes.get("_name")) {
+                node.insert(VoxelJoint::new(kind));
+            }
             if let Some(node_name) = node_name.clone() {
                 node.insert(Name::new(node_name.clone()));
             }
@@ -174,6 +186,15 @@ fn load_xform_node(
                 Visibility::Inherited
             };
             node.insert(visibility);
+            if let Some(socket) = socket_name(attributes.get("_name"), &graph[*child as usize]) {
+                node.insert(VoxelSocket(socket));
+            }
+            if let Some(probe) = probe_name(attributes.get("_name")) {
+                node.insert(VoxelReflectionProbe(probe));
+            }
+            if let Some(kind) = joint_kind(attributes.get("_name")) {
+                node.insert(VoxelJoint::new(kind));
+            }
             if let Some(node_name) = node_name.clone() {
                 node.insert(Name::new(node_name.clone()));
                 // create sub-asset
@@ -265,10 +286,38 @@ fn load_xform_child(
             attributes: _,
             models,
         } => {
-            let model_id = models[0].model_id as usize;
-            let model_name = model_names[model_id]
-                .clone()
-                .unwrap_or(format!("model-{}", model_id));
+            let model_name = |model_id: u32| {
+                model_names[model_id as usize]
+                    .clone()
+                    .unwrap_or(format!("model-{}", model_id))
+            };
+            // each model of an animated shape is a keyframe, starting at the frame index in its `_f` attribute
+            let mut keyframes: Vec<(u32, u32)> = models
+                .iter()
+                .map(|model| {
+                    let frame_index = model
+                        .attributes
+                        .get("_f")
+                        .and_then(|frame| frame.parse().ok())
+                        .unwrap_or(0);
+                    (frame_index, model.model_id)
+                })
+                .collect();
+            keyframes.sort_by_key(|(frame_index, _)| *frame_index);
+            if keyframes.len() > 1 {
+                let frames = keyframes
+                    .iter()
+                    .map(|(frame_index, model_id)| VoxelShapeFrame {
+                        frame_index: *frame_index,
+                        model: context.get_label_handle(format!("{}@model", model_name(*model_id))),
+                    })
+                    .collect();
+                node.insert(VoxelShapeFrames { frames });
+            }
+            let Some((_, model_id)) = keyframes.first() else {
+                return;
+            };
+            let model_name = model_name(*model_id);
             node.insert((
                 PbrBundle {
                     mesh: context.get_label_handle(format!("{}@mesh", model_name)),
@@ -284,7 +333,7 @@ fn load_xform_child(
     }
 }
 
-fn get_accumulated_and_node_name(
+pub(super) fn get_accumulated_and_node_name(
     parent_name: Option<&String>,
     node_name: Option<&String>,
 ) -> (Option<String>, Option<String>) {
@@ -299,6 +348,39 @@ fn get_accumulated_and_node_name(
     }
 }
 
+const SOCKET_PREFIX: &str = "socket:";
+
+/// Nodes named with the `socket:` prefix, or named nodes containing an empty group, are attachment points
+pub(crate) fn socket_name(node_name: Option<&String>, child: &SceneNode) -> Option<String> {
+    let node_name = node_name?;
+    if let Some(socket) = node_name.strip_prefix(SOCKET_PREFIX) {
+        return Some(socket.to_string());
+    }
+    match child {
+        SceneNode::Group { children, .. } if children.is_empty() => Some(node_name.clone()),
+        _ => None,
+    }
+}
+
+const PROBE_PREFIX: &str = "probe:";
+
+/// Nodes named with the `probe:` prefix are reflection probes
+pub(crate) fn probe_name(node_name: Option<&String>) -> Option<String> {
+    node_name?
+        .strip_prefix(PROBE_PREFIX)
+        .map(|probe| probe.to_string())
+}
+
+const JOINT_PREFIX: &str = "joint:";
+
+/// Nodes named with the `joint:` prefix are physics joints. Anything following a second `:` is ignored, so that joints
+/// can be given unique names, eg `joint:hinge:front-door`
+pub(crate) fn joint_kind(node_name: Option<&String>) -> Option<VoxelJointKind> {
+    let kind = node_name?.strip_prefix(JOINT_PREFIX)?;
+    let kind = kind.split(':').next().unwrap_or(kind);
+    Some(VoxelJointKind::parse(kind))
+}
+
 fn parse_bool(value: Option<String>) -> bool {
     match value.as_deref() {
         Some("1") => true,
@@ -0,0 +1,539 @@
+use anyhow::{anyhow, bail, Context};
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    color::{Color, ColorToComponents, LinearRgba, Srgba},
+    core::Name,
+    ecs::world::World,
+    log::{info, info_span},
+    math::{DVec3, IVec3, Vec3, Vec4},
+    pbr::PbrBundle,
+    prelude::default,
+    scene::Scene,
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    add_data_assets, VoxLoaderError, VoxLoaderOverrides, VoxLoaderSettings, VoxSceneGlobalSettings,
+};
+use crate::{
+    model::{
+        ColorMetric, RawVoxel, VoxelAudioMaterials, VoxelBrickMap, VoxelData, VoxelElement,
+        VoxelPalette,
+    },
+    VoxelContext, VoxelModelInstance,
+};
+
+/// Settings for loading point clouds as voxel models.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_vox_scene::VoxPointCloudSettings;
+/// # fn setup(mut commands: Commands, assets: Res<AssetServer>) {
+/// commands.spawn(SceneBundle {
+///     scene: assets.load_with_settings("scan.ply", |settings: &mut VoxPointCloudSettings| {
+///         settings.resolution = 0.05;
+///         settings.density_threshold = 3;
+///     }),
+///     ..default()
+/// });
+/// # }
+/// ```
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct VoxPointCloudSettings {
+    /// The length of the side of each voxel, in the units of the point cloud. Defaults to 1.0.
+    pub resolution: f32,
+    /// The number of points that must fall inside a voxel for it to be filled. Defaults to 1. Raise it to drop the
+    /// stray points of noisy scans.
+    pub density_threshold: u32,
+    /// The maximum number of colors in the palette quantized from the colors of the points. Defaults to 255.
+    pub max_colors: u8,
+    /// How the difference between colors is measured when quantizing the palette. Defaults to [`ColorMetric::Oklab`].
+    pub color_metric: ColorMetric,
+    /// Whether the point cloud is Z-up, as is common for scans, rather than Y-up. Defaults to false.
+    pub z_up: bool,
+    /// The settings used to create the palette and mesh of the model, such as the size of each voxel in the scene.
+    /// Fields that aren't set keep the values of the [`VoxSceneGlobalSettings`].
+    pub voxel_settings: VoxLoaderOverrides,
+}
+
+impl Default for VoxPointCloudSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 1.0,
+            density_threshold: 1,
+            max_colors: 255,
+            color_metric: ColorMetric::default(),
+            z_up: false,
+            voxel_settings: VoxLoaderOverrides::default(),
+        }
+    }
+}
+
+/// The positions and colors of the points of a point cloud
+#[derive(Debug, Default)]
+pub(crate) struct PointCloud {
+    pub(crate) positions: Vec<Vec3>,
+    pub(crate) colors: Vec<Color>,
+}
+
+impl PointCloud {
+    /// Parses an `.xyz` file, with one point per line written as `x y z`, optionally followed by `r g b`. Colors
+    /// written as integers range from 0 to 255, and colors written as decimals from 0 to 1.
+    pub(crate) fn from_xyz(text: &str) -> anyhow::Result<Self> {
+        let mut cloud = PointCloud::default();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 3 {
+                bail!("line {} has fewer than 3 coordinates", line_number + 1);
+            }
+            let number = |field: &str| -> anyhow::Result<f32> {
+                field
+                    .parse()
+                    .with_context(|| format!("line {}: invalid number `{field}`", line_number + 1))
+            };
+            cloud.positions.push(Vec3::new(
+                number(fields[0])?,
+                number(fields[1])?,
+                number(fields[2])?,
+            ));
+            let color = if fields.len() >= 6 {
+                let scale = if fields[3..6].iter().any(|field| field.contains('.')) {
+                    1.0
+                } else {
+                    255.0
+                };
+                Color::srgb(
+                    number(fields[3])? / scale,
+                    number(fields[4])? / scale,
+                    number(fields[5])? / scale,
+                )
+            } else {
+                Color::WHITE
+            };
+            cloud.colors.push(color);
+        }
+        Ok(cloud)
+    }
+
+    /// Parses the vertices of a `.ply` file in the ascii or either of the binary formats, along with their `red`,
+    /// `green`, `blue` and `alpha` properties if present
+    pub(crate) fn from_ply(bytes: &[u8]) -> anyhow::Result<Self> {
+        let header_end = bytes
+            .windows(b"end_header".len())
+            .position(|window| window == b"end_header")
+            .context("missing `end_header`")?;
+        let body_start = bytes[header_end..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map_or(bytes.len(), |offset| header_end + offset + 1);
+        let header = String::from_utf8_lossy(&bytes[..header_end]);
+        let mut lines = header.lines().map(str::trim);
+        if lines.next() != Some("ply") {
+            bail!("missing `ply` magic number");
+        }
+        let mut format = None;
+        let mut elements: Vec<PlyElement> = Vec::new();
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["format", name, _] => format = Some(name.to_string()),
+                ["element", name, count] => elements.push(PlyElement {
+                    name: name.to_string(),
+                    count: count
+                        .parse()
+                        .with_context(|| format!("invalid count `{count}`"))?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", count, item, name] => elements
+                    .last_mut()
+                    .with_context(|| format!("property `{name}` outside of an element"))?
+                    .properties
+                    .push(PlyProperty {
+                        name: name.to_string(),
+                        list_count: Some(PlyScalar::parse(count)?),
+                        scalar: PlyScalar::parse(item)?,
+                    }),
+                ["property", scalar, name] => elements
+                    .last_mut()
+                    .with_context(|| format!("property `{name}` outside of an element"))?
+                    .properties
+                    .push(PlyProperty {
+                        name: name.to_string(),
+                        list_count: None,
+                        scalar: PlyScalar::parse(scalar)?,
+                    }),
+                _ => {}
+            }
+        }
+        let body = &bytes[body_start.min(bytes.len())..];
+        let mut reader = match format.as_deref() {
+            Some("ascii") => PlyReader::Ascii(std::str::from_utf8(body)?.split_ascii_whitespace()),
+            Some("binary_little_endian") => PlyReader::Binary {
+                bytes: body,
+                big_endian: false,
+            },
+            Some("binary_big_endian") => PlyReader::Binary {
+                bytes: body,
+                big_endian: true,
+            },
+            Some(format) => bail!("unsupported format `{format}`"),
+            None => bail!("missing format"),
+        };
+        let mut cloud = PointCloud::default();
+        for element in elements.iter() {
+            let is_vertex = element.name == "vertex";
+            let index_of = |names: &[&str]| {
+                element
+                    .properties
+                    .iter()
+                    .position(|property| names.contains(&property.name.as_str()))
+            };
+            let position = [index_of(&["x"]), index_of(&["y"]), index_of(&["z"])];
+            let color = [
+                index_of(&["red", "r", "diffuse_red"]),
+                index_of(&["green", "g", "diffuse_green"]),
+                index_of(&["blue", "b", "diffuse_blue"]),
+                index_of(&["alpha", "a"]),
+            ];
+            let mut values = vec![0.0; element.properties.len()];
+            for _ in 0..element.count {
+                for (value, property) in values.iter_mut().zip(element.properties.iter()) {
+                    *value = property.read(&mut reader)?;
+                }
+                if !is_vertex {
+                    continue;
+                }
+                let [Some(x), Some(y), Some(z)] = position else {
+                    bail!("vertices are missing coordinates");
+                };
+                cloud
+                    .positions
+                    .push(DVec3::new(values[x], values[y], values[z]).as_vec3());
+                let channel = |index: Option<usize>| {
+                    index.map_or(1.0, |index| {
+                        (values[index] / element.properties[index].scalar.color_scale()) as f32
+                    })
+                };
+                cloud.colors.push(Color::Srgba(Srgba::new(
+                    channel(color[0]),
+                    channel(color[1]),
+                    channel(color[2]),
+                    channel(color[3]),
+                )));
+            }
+            if is_vertex {
+                break;
+            }
+        }
+        Ok(cloud)
+    }
+}
+
+/// An element declared in the header of a `.ply` file
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+/// A property of a [`PlyElement`], which is a list if it has a `list_count`
+struct PlyProperty {
+    name: String,
+    list_count: Option<PlyScalar>,
+    scalar: PlyScalar,
+}
+
+impl PlyProperty {
+    /// Reads the value of the property, or 0 for lists, which are skipped
+    fn read(&self, reader: &mut PlyReader) -> anyhow::Result<f64> {
+        let Some(list_count) = self.list_count else {
+            return reader.read(self.scalar);
+        };
+        let count = reader.read(list_count)? as usize;
+        for _ in 0..count {
+            reader.read(self.scalar)?;
+        }
+        Ok(0.0)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PlyScalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyScalar {
+    fn parse(name: &str) -> anyhow::Result<Self> {
+        Ok(match name {
+            "char" | "int8" => PlyScalar::I8,
+            "uchar" | "uint8" => PlyScalar::U8,
+            "short" | "int16" => PlyScalar::I16,
+            "ushort" | "uint16" => PlyScalar::U16,
+            "int" | "int32" => PlyScalar::I32,
+            "uint" | "uint32" => PlyScalar::U32,
+            "float" | "float32" => PlyScalar::F32,
+            "double" | "float64" => PlyScalar::F64,
+            _ => bail!("unsupported property type `{name}`"),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            PlyScalar::I8 | PlyScalar::U8 => 1,
+            PlyScalar::I16 | PlyScalar::U16 => 2,
+            PlyScalar::I32 | PlyScalar::U32 | PlyScalar::F32 => 4,
+            PlyScalar::F64 => 8,
+        }
+    }
+
+    /// The value that a color channel of this type has at full intensity
+    fn color_scale(&self) -> f64 {
+        match self {
+            PlyScalar::I8 => i8::MAX as f64,
+            PlyScalar::U8 => u8::MAX as f64,
+            PlyScalar::I16 => i16::MAX as f64,
+            PlyScalar::U16 => u16::MAX as f64,
+            PlyScalar::I32 => i32::MAX as f64,
+            PlyScalar::U32 => u32::MAX as f64,
+            PlyScalar::F32 | PlyScalar::F64 => 1.0,
+        }
+    }
+}
+
+/// Reads the values of the body of a `.ply` file one at a time
+enum PlyReader<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl PlyReader<'_> {
+    fn read(&mut self, scalar: PlyScalar) -> anyhow::Result<f64> {
+        match self {
+            PlyReader::Ascii(words) => {
+                let word = words.next().context("unexpected end of file")?;
+                word.parse()
+                    .with_context(|| format!("invalid number `{word}`"))
+            }
+            PlyReader::Binary { bytes, big_endian } => {
+                if bytes.len() < scalar.size() {
+                    bail!("unexpected end of file");
+                }
+                let (value, rest) = std::mem::take(bytes).split_at(scalar.size());
+                *bytes = rest;
+                let mut buffer = [0; 8];
+                buffer[..value.len()].copy_from_slice(value);
+                if *big_endian {
+                    buffer[..value.len()].reverse();
+                }
+                Ok(match scalar {
+                    PlyScalar::I8 => buffer[0] as i8 as f64,
+                    PlyScalar::U8 => buffer[0] as f64,
+                    PlyScalar::I16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    PlyScalar::U16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    PlyScalar::I32 => {
+                        i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+                    }
+                    PlyScalar::U32 => {
+                        u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+                    }
+                    PlyScalar::F32 => {
+                        f32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+                    }
+                    PlyScalar::F64 => f64::from_le_bytes(buffer),
+                })
+            }
+        }
+    }
+}
+
+impl VoxPointCloudSettings {
+    /// Bins the points of the `cloud` into voxels of the [`VoxPointCloudSettings::resolution`], keeping those holding
+    /// at least [`VoxPointCloudSettings::density_threshold`] points, and colors each with the voxel of a quantized
+    /// palette closest to the average color of its points. Returns `None` if no voxel is dense enough.
+    pub(crate) fn voxelize(
+        &self,
+        cloud: &PointCloud,
+        voxel_settings: &VoxLoaderSettings,
+    ) -> Option<(VoxelData, VoxelPalette)> {
+        let resolution = self.resolution.max(f32::EPSILON);
+        let positions: Vec<Vec3> = cloud
+            .positions
+            .iter()
+            .map(|position| {
+                if self.z_up {
+                    Vec3::new(position.x, position.z, -position.y)
+                } else {
+                    *position
+                }
+            })
+            .collect();
+        let min = positions
+            .iter()
+            .fold(Vec3::INFINITY, |min, position| min.min(*position));
+        let mut cells: HashMap<IVec3, (u32, Vec4)> = HashMap::new();
+        for (position, color) in positions.iter().zip(cloud.colors.iter()) {
+            let cell = ((*position - min) / resolution).floor().as_ivec3();
+            let (count, sum) = cells.entry(cell).or_insert((0, Vec4::ZERO));
+            *count += 1;
+            *sum += Vec4::from_array(color.to_linear().to_f32_array());
+        }
+        let mut cells: Vec<(IVec3, Color)> = cells
+            .into_iter()
+            .filter(|(_, (count, _))| *count >= self.density_threshold.max(1))
+            .map(|(cell, (count, sum))| {
+                (
+                    cell,
+                    Color::LinearRgba(LinearRgba::from_vec4(sum / count as f32)),
+                )
+            })
+            .collect();
+        if cells.is_empty() {
+            return None;
+        }
+        cells.sort_by_key(|(cell, _)| cell.to_array());
+        let (min_cell, max_cell) = cells
+            .iter()
+            .fold((IVec3::MAX, IVec3::MIN), |(min, max), (cell, _)| {
+                (min.min(*cell), max.max(*cell))
+            });
+        let colors: Vec<Color> = cells.iter().map(|(_, color)| *color).collect();
+        let quantized = VoxelPalette::quantized(&colors, self.max_colors, self.color_metric);
+        let voxels = quantized.fit_colors(&colors, self.color_metric);
+        let palette = voxel_settings.configure_palette(VoxelPalette::new(
+            quantized
+                .elements
+                .iter()
+                .map(|element| {
+                    VoxelElement::new(element.color)
+                        .with_roughness(voxel_settings.diffuse_roughness)
+                })
+                .collect(),
+        ));
+        let mut data = voxel_settings.configure_data(VoxelData::new(
+            (max_cell - min_cell + IVec3::ONE).as_uvec3(),
+            voxel_settings.mesh_outer_faces,
+            voxel_settings.voxel_size,
+        ));
+        for ((cell, _), voxel) in cells.iter().zip(voxels) {
+            data.write_raw(*cell - min_cell, RawVoxel::from(voxel));
+        }
+        Some((data, palette))
+    }
+}
+
+/// An asset loader capable of loading `.ply` and `.xyz` point clouds as [`bevy::scene::Scene`]s holding a single
+/// voxel model, configured with [`VoxPointCloudSettings`].
+///
+/// The model, its mesh and its material are labelled with the stem of the file, eg `scan.ply#scan@model`, and its
+/// palette is labelled `voxel-context`, as in `.vox` files.
+pub(crate) struct VoxPointCloudLoader {
+    pub(crate) global_settings: VoxSceneGlobalSettings,
+}
+
+impl AssetLoader for VoxPointCloudLoader {
+    type Asset = Scene;
+    type Settings = VoxPointCloudSettings;
+    type Error = VoxLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        settings: &'a VoxPointCloudSettings,
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(|e| VoxLoaderError::InvalidAsset(anyhow!(e)))?;
+        info!("Loading {}", load_context.asset_path());
+        let path = load_context.asset_path().to_string();
+        let _span = info_span!("vox_load_point_cloud", path = %path).entered();
+        let is_ply = load_context
+            .path()
+            .extension()
+            .is_some_and(|extension| extension == "ply");
+        let parsed = if is_ply {
+            PointCloud::from_ply(&bytes)
+        } else {
+            PointCloud::from_xyz(&String::from_utf8_lossy(&bytes))
+        };
+        let cloud =
+            parsed.map_err(|error| VoxLoaderError::InvalidAsset(anyhow!("{path}: {error}")))?;
+        let voxel_settings = self.global_settings.resolve(&settings.voxel_settings);
+        let Some((data, palette)) = settings.voxelize(&cloud, &voxel_settings) else {
+            return Err(VoxLoaderError::InvalidAsset(anyhow!(
+                "{path}: no voxel holds {} or more of the {} points",
+                settings.density_threshold,
+                cloud.positions.len()
+            )));
+        };
+
+        let name = load_context
+            .path()
+            .file_stem()
+            .map_or("model".to_string(), |stem| {
+                stem.to_string_lossy().to_string()
+            });
+        let translucent_material = palette.create_material_in_load_context(load_context);
+        let opaque_material = load_context.labeled_asset_scope("material".to_string(), |_| {
+            let mut opaque_material = translucent_material.clone();
+            opaque_material.specular_transmission_texture = None;
+            opaque_material.specular_transmission = 0.0;
+            opaque_material
+        });
+        let brick_map = voxel_settings.brick_maps.then(|| {
+            load_context
+                .add_labeled_asset(format!("{}@bricks", name), VoxelBrickMap::from_data(&data))
+        });
+        let model = add_data_assets(
+            load_context,
+            name.clone(),
+            data,
+            &voxel_settings,
+            &palette,
+            &translucent_material,
+            brick_map,
+        );
+        let transmissive_material = load_context
+            .add_labeled_asset("material-transmissive".to_string(), translucent_material);
+        let context = load_context.add_labeled_asset(
+            "voxel-context".to_string(),
+            VoxelContext {
+                palette,
+                audio_materials: VoxelAudioMaterials::default(),
+                opaque_material,
+                transmissive_material,
+            },
+        );
+
+        let mut world = World::default();
+        world.spawn((
+            Name::new(name.clone()),
+            PbrBundle {
+                mesh: load_context.get_label_handle(format!("{}@mesh", name)),
+                material: load_context.get_label_handle(format!("{}@material", name)),
+                ..default()
+            },
+            VoxelModelInstance { model, context },
+        ));
+        Ok(Scene::new(world))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ply", "xyz"]
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use bevy::{
+    asset::{Asset, Handle},
+    ecs::system::Resource,
+    utils::HashMap,
+};
+
+/// The progress of loading a single `.vox` file, reported by [`VoxelLoadProgress`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VoxelFileLoadProgress {
+    /// The number of bytes of the file read so far
+    pub bytes_read: usize,
+    /// The size of the file in bytes, if the asset reader can tell
+    pub total_bytes: Option<usize>,
+    /// The number of models meshed so far
+    pub models_meshed: usize,
+    /// The number of models in the file, or 0 until the file has been parsed
+    pub total_models: usize,
+    /// Whether the loader has finished with the file, successfully or not
+    pub finished: bool,
+}
+
+impl VoxelFileLoadProgress {
+    /// The fraction of the models that have been meshed, from 0 to 1, or 1 once the file has finished loading
+    pub fn fraction(&self) -> f32 {
+        if self.finished {
+            1.0
+        } else if self.total_models == 0 {
+            0.0
+        } else {
+            self.models_meshed as f32 / self.total_models as f32
+        }
+    }
+}
+
+/// Resource reporting the progress of the `.vox` files being loaded, so that loading screens can show meaningful
+/// progress for large files.
+///
+/// The loader updates the progress from its own task while it reads, parses and meshes a file, so the values can
+/// change at any time. The progress of a file is kept once it has finished loading, until 64 more files have finished
+/// after it, and is reset when it is reloaded.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_vox_scene::VoxelLoadProgress;
+/// #[derive(Resource)]
+/// struct Level(Handle<Scene>);
+///
+/// fn show_progress(level: Res<Level>, progress: Res<VoxelLoadProgress>) {
+///     if let Some(progress) = progress.get_for(&level.0) {
+///         info!("{:.0}% meshed", progress.fraction() * 100.0);
+///     }
+/// }
+/// ```
+#[derive(Resource, Clone, Default, Debug)]
+pub struct VoxelLoadProgress {
+    files: Arc<RwLock<LoadProgressFiles>>,
+}
+
+/// The number of finished files whose progress is kept, so that a game loading many files doesn't accumulate their
+/// progress forever
+const MAX_FINISHED_FILES: usize = 64;
+
+#[derive(Default, Debug)]
+struct LoadProgressFiles {
+    progress: HashMap<PathBuf, VoxelFileLoadProgress>,
+    /// The files that have finished loading, oldest first
+    finished: VecDeque<PathBuf>,
+}
+
+impl VoxelLoadProgress {
+    /// Returns the progress of the file at `path`, relative to the asset folder, eg `"study.vox"`
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<VoxelFileLoadProgress> {
+        self.files
+            .read()
+            .ok()
+            .and_then(|files| files.progress.get(path.as_ref()).copied())
+    }
+
+    /// Returns the progress of the file that the asset behind `handle` is loaded from, such as a [`bevy::scene::Scene`]
+    /// or any of the labelled assets of the file
+    pub fn get_for<A: Asset>(&self, handle: &Handle<A>) -> Option<VoxelFileLoadProgress> {
+        handle.path().and_then(|path| self.get(path.path()))
+    }
+
+    /// Updates the progress of the file at `path`
+    pub(crate) fn update(&self, path: &Path, update: impl FnOnce(&mut VoxelFileLoadProgress)) {
+        if let Ok(mut files) = self.files.write() {
+            update(files.progress.entry(path.to_path_buf()).or_default());
+        }
+    }
+
+    /// Starts reporting the progress of the file at `path` afresh
+    pub(crate) fn start(&self, path: &Path) {
+        if let Ok(mut files) = self.files.write() {
+            files.finished.retain(|finished| finished != path);
+            files
+                .progress
+                .insert(path.to_path_buf(), VoxelFileLoadProgress::default());
+        }
+    }
+
+    /// Marks the file at `path` as finished, forgetting the progress of the files that finished longest ago once more
+    /// than [`MAX_FINISHED_FILES`] have
+    pub(crate) fn finish(&self, path: &Path) {
+        if let Ok(mut files) = self.files.write() {
+            files
+                .progress
+                .entry(path.to_path_buf())
+                .or_default()
+                .finished = true;
+            files.finished.retain(|finished| finished != path);
+            files.finished.push_back(path.to_path_buf());
+            while files.finished.len() > MAX_FINISHED_FILES {
+                if let Some(oldest) = files.finished.pop_front() {
+                    files.progress.remove(&oldest);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use bevy::{
+    asset::{AssetEvent, AssetId, Assets, Handle},
+    core::Name,
+    ecs::{
+        change_detection::DetectChanges,
+        entity::Entity,
+        event::EventReader,
+        query::{Added, With, Without},
+        system::{Commands, Query, Res, ResMut},
+        world::Ref,
+    },
+    hierarchy::{Children, Parent},
+    log::warn,
+    pbr::{LightProbe, StandardMaterial},
+    render::mesh::Mesh,
+    scene::{Scene, SceneInstance, SceneSpawner},
+    transform::components::Transform,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{VoxelContext, VoxelModel};
+
+use super::{
+    VoxelJoint, VoxelModelInfo, VoxelModelInstance, VoxelReflectionProbe, VoxelSceneInstance,
+};
+
+/// Inserts a [`VoxelSceneInstance`] on the root of every `.vox` scene that has finished spawning
+pub(crate) fn populate_scene_instances(
+    mut commands: Commands,
+    roots: Query<(Entity, &SceneInstance, &Handle<Scene>), Without<VoxelSceneInstance>>,
+    names: Query<&Name>,
+    scene_spawner: Res<SceneSpawner>,
+) {
+    for (root, instance, scene) in roots.iter() {
+        let is_vox_scene = scene
+            .path()
+            .is_some_and(|path| path.path().extension().is_some_and(|ext| ext == "vox"));
+        if !is_vox_scene || !scene_spawner.instance_is_ready(**instance) {
+            continue;
+        }
+        let nodes = scene_spawner
+            .iter_instance_entities(**instance)
+            .filter_map(|entity| {
+                names
+                    .get(entity)
+                    .ok()
+                    .map(|name| (name.as_str().to_string(), entity))
+            })
+            .collect();
+        commands.entity(root).insert(VoxelSceneInstance { nodes });
+    }
+}
+
+/// Meshes models loaded with [`super::VoxLoaderSettings::lazy_meshing`] when their first instance is spawned
+pub(crate) fn mesh_pending_models(
+    mut commands: Commands,
+    instances: Query<
+        (
+            Entity,
+            &VoxelModelInstance,
+            Option<&Handle<StandardMaterial>>,
+        ),
+        Added<VoxelModelInstance>,
+    >,
+    mut models: ResMut<Assets<VoxelModel>>,
+    contexts: Res<Assets<VoxelContext>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // the material each translucent model was given in place of the opaque material it was loaded with
+    let mut replaced: HashMap<
+        AssetId<VoxelModel>,
+        (Handle<StandardMaterial>, Handle<StandardMaterial>),
+    > = HashMap::new();
+    for (_, instance, _) in instances.iter() {
+        if !models
+            .get(&instance.model)
+            .is_some_and(|model| model.mesh_pending)
+        {
+            continue;
+        }
+        let (Some(model), Some(context)) = (
+            models.get_mut(&instance.model),
+            contexts.get(&instance.context),
+        ) else {
+            continue;
+        };
+        let (mesh, average_ior) = model.data.remesh(&context.palette);
+        meshes.insert(&model.mesh, mesh);
+        if let Some(ior) = average_ior {
+            if let Some(mut material) = materials.get(&context.transmissive_material).cloned() {
+                material.ior = ior;
+                material.thickness = model.data._size().min_element() as f32;
+                // the opaque material may be shared with other models, so the model is given a new material rather
+                // than modifying it
+                let material = materials.add(material);
+                let opaque_material = std::mem::replace(&mut model.material, material.clone());
+                replaced.insert(instance.model.id(), (opaque_material, material));
+            }
+        }
+        model.has_translucency = average_ior.is_some();
+        model.mesh_pending = false;
+    }
+    for (entity, instance, material) in instances.iter() {
+        let Some((opaque_material, translucent_material)) = replaced.get(&instance.model.id())
+        else {
+            continue;
+        };
+        if material.is_some_and(|material| material == opaque_material) {
+            commands.entity(entity).insert(translucent_material.clone());
+        }
+    }
+}
+
+/// Replaces the model of each [`VoxelReflectionProbe`] node with a [`LightProbe`] covering the model's bounds
+pub(crate) fn spawn_reflection_probes(
+    mut commands: Commands,
+    mut probes: Query<(Entity, &VoxelModelInstance, &mut Transform), Added<VoxelReflectionProbe>>,
+    models: Res<Assets<VoxelModel>>,
+) {
+    for (entity, instance, mut transform) in probes.iter_mut() {
+        let Some(model) = models.get(&instance.model) else {
+            continue;
+        };
+        // the light probe covers a unit cube, centered on the origin like the model
+        transform.scale *= model.data._size().as_vec3() * model.data.voxel_size;
+        commands
+            .entity(entity)
+            .remove::<(Handle<Mesh>, Handle<StandardMaterial>, VoxelModelInstance)>()
+            .insert(LightProbe);
+    }
+}
+
+/// Connects each newly spawned [`VoxelJoint`] to the two sibling models closest to it
+pub(crate) fn connect_voxel_joints(
+    mut joints: Query<(Entity, &mut VoxelJoint, &Parent, &Transform), Added<VoxelJoint>>,
+    children: Query<&Children>,
+    bodies: Query<&Transform, With<VoxelModelInstance>>,
+) {
+    for (entity, mut joint, parent, transform) in joints.iter_mut() {
+        let Ok(siblings) = children.get(parent.get()) else {
+            continue;
+        };
+        let mut candidates: Vec<(Entity, f32)> = siblings
+            .iter()
+            .filter(|sibling| **sibling != entity)
+            .filter_map(|sibling| {
+                bodies.get(*sibling).ok().map(|body| {
+                    (
+                        *sibling,
+                        body.translation.distance_squared(transform.translation),
+                    )
+                })
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.1.total_cmp(&b.1));
+        match candidates.as_slice() {
+            [first, second, ..] => joint.bodies = Some([first.0, second.0]),
+            _ => warn!("Joint {:?} has fewer than two sibling models", joint.kind),
+        }
+    }
+}
+
+/// Keeps the [`VoxelModelInfo`] of every instance up to date with its model
+pub(crate) fn update_voxel_model_info(
+    mut commands: Commands,
+    mut model_events: EventReader<AssetEvent<VoxelModel>>,
+    instances: Query<(Entity, Ref<VoxelModelInstance>, Option<&VoxelModelInfo>)>,
+    models: Res<Assets<VoxelModel>>,
+) {
+    let changed: HashSet<AssetId<VoxelModel>> = model_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Added { id }
+            | AssetEvent::Modified { id }
+            | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (entity, instance, info) in instances.iter() {
+        if info.is_some() && !instance.is_changed() && !changed.contains(&instance.model.id()) {
+            continue;
+        }
+        let Some(model) = models.get(&instance.model) else {
+            continue;
+        };
+        let updated = VoxelModelInfo {
+            name: model.name.clone(),
+            size: model.data._size().as_uvec3(),
+            voxel_count: model.count_voxels(),
+            memory_usage: model.memory_usage(),
+        };
+        if info != Some(&updated) {
+            commands.entity(entity).insert(updated);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::{io::SeekFrom, ops::Range};
+
+use anyhow::anyhow;
+use bevy::tasks::futures_lite::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+use dot_vox::{DotVoxData, Model, Size, Voxel};
+
+use super::{chunks::read_u32, VoxLoaderError};
+use crate::hash::StableHasher;
+
+/// The number of bytes read at once while reading past the voxels of a model
+const SKIP_BUFFER_SIZE: u64 = 1 << 16;
+
+/// The voxels of the models of a `.vox` file, left in the file until each model is needed.
+///
+/// `dot_vox` parses every model of a file at once from the bytes of the whole file, so loading a large file holds both
+/// its bytes and all of its parsed voxels. Instead, [`read_streamed`] reads the file chunk by chunk, keeping the
+/// palette, materials, scene graph and the other small chunks while reading past the voxels of each model, which are
+/// read back from the file one model at a time with [`StreamedModels::model`].
+pub(crate) struct StreamedModels {
+    /// The range of the voxels of each model within the file
+    voxels: Vec<Range<u64>>,
+}
+
+/// A `.vox` file read by [`read_streamed`]
+pub(crate) struct StreamedFile {
+    /// The file parsed without the voxels of its models. Every model is empty, but has its size.
+    pub(crate) file: DotVoxData,
+    /// The bytes of the file with the voxels of its models left out, for reading the chunks that `dot_vox` skips
+    pub(crate) skeleton: Vec<u8>,
+    pub(crate) models: StreamedModels,
+    /// The hash of every byte of the file, voxels included
+    pub(crate) content_hash: u64,
+}
+
+/// Reads a file from its start, keeping count of the bytes read and hashing them
+struct HashingReader<'a, R: ?Sized> {
+    reader: &'a mut R,
+    position: u64,
+    hasher: StableHasher,
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> HashingReader<'_, R> {
+    /// Reads up to `length` bytes onto the end of `bytes`, returning false if the file ends first
+    async fn append(&mut self, bytes: &mut Vec<u8>, length: u64) -> Result<bool, VoxLoaderError> {
+        let start = bytes.len();
+        (&mut *self.reader)
+            .take(length)
+            .read_to_end(bytes)
+            .await
+            .map_err(|error| anyhow!(error))?;
+        self.hasher.write(&bytes[start..]);
+        let read = (bytes.len() - start) as u64;
+        self.position += read;
+        Ok(read == length)
+    }
+
+    /// Reads past up to `length` bytes without keeping them, returning false if the file ends first
+    async fn skip(&mut self, length: u64) -> Result<bool, VoxLoaderError> {
+        let mut buffer = Vec::with_capacity(SKIP_BUFFER_SIZE.min(length) as usize);
+        let mut remaining = length;
+        while remaining > 0 {
+            let step = remaining.min(SKIP_BUFFER_SIZE);
+            buffer.clear();
+            if !self.append(&mut buffer, step).await? {
+                return Ok(false);
+            }
+            remaining -= step;
+        }
+        Ok(true)
+    }
+}
+
+/// Reads the `.vox` file from `reader` without keeping the voxels of its models, which are returned as
+/// [`StreamedModels`]. `on_read` is called with the number of bytes read so far after each chunk of the file.
+pub(crate) async fn read_streamed<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    mut on_read: impl FnMut(usize),
+) -> Result<StreamedFile, VoxLoaderError> {
+    let mut source = HashingReader {
+        reader,
+        position: 0,
+        hasher: StableHasher::default(),
+    };
+    // the header is followed by the MAIN chunk, whose children are the chunks of the file
+    let mut skeleton = Vec::new();
+    if !source.append(&mut skeleton, 20).await? {
+        return Err(VoxLoaderError::InvalidAsset(anyhow!(
+            "the file is too short to hold a MAIN chunk"
+        )));
+    }
+    let main_children = read_u32(&skeleton, 16).unwrap_or(0) as u64;
+    let main_content = read_u32(&skeleton, 12).unwrap_or(0) as u64;
+    source.append(&mut skeleton, main_content).await?;
+    let header_length = skeleton.len();
+    let mut voxels = Vec::new();
+    let mut remaining = main_children;
+    // a truncated chunk ends the file, leaving dot_vox to report what is missing
+    while remaining >= 12 {
+        let mut header = Vec::with_capacity(12);
+        if !source.append(&mut header, 12).await? {
+            break;
+        }
+        let content = read_u32(&header, 4).unwrap_or(0) as u64;
+        let children = read_u32(&header, 8).unwrap_or(0) as u64;
+        remaining = remaining.saturating_sub(12 + content + children);
+        if &header[..4] == b"XYZI" {
+            let mut count = Vec::with_capacity(4);
+            if content < 4 || !source.append(&mut count, 4).await? {
+                break;
+            }
+            let start = source.position;
+            let length = (read_u32(&count, 0).unwrap_or(0) as u64 * 4).min(content - 4);
+            voxels.push(start..start + length);
+            // an XYZI chunk holding no voxels
+            skeleton.extend_from_slice(b"XYZI");
+            skeleton.extend_from_slice(&4u32.to_le_bytes());
+            skeleton.extend_from_slice(&0u32.to_le_bytes());
+            skeleton.extend_from_slice(&0u32.to_le_bytes());
+            if !source.skip(content - 4 + children).await? {
+                break;
+            }
+        } else {
+            skeleton.extend_from_slice(&header);
+            if !source.append(&mut skeleton, content + children).await? {
+                break;
+            }
+        }
+        on_read(source.position as usize);
+    }
+    // anything following the MAIN chunk is part of the file's content too
+    source.skip(u64::MAX).await?;
+    on_read(source.position as usize);
+    let children = (skeleton.len() - header_length) as u32;
+    skeleton[16..20].copy_from_slice(&children.to_le_bytes());
+    let file = dot_vox::load_bytes(&skeleton).map_err(|error| anyhow!(error))?;
+    if file.models.len() != voxels.len() {
+        return Err(VoxLoaderError::InvalidAsset(anyhow!(
+            "the file has {} models but {} voxel chunks",
+            file.models.len(),
+            voxels.len()
+        )));
+    }
+    Ok(StreamedFile {
+        file,
+        skeleton,
+        models: StreamedModels { voxels },
+        content_hash: source.hasher.finish(),
+    })
+}
+
+impl StreamedModels {
+    /// The number of voxels of each model
+    pub(crate) fn voxel_counts(&self) -> Vec<usize> {
+        self.voxels
+            .iter()
+            .map(|range| ((range.end - range.start) / 4) as usize)
+            .collect()
+    }
+
+    /// Reads the voxels of the model at `index` of the `file` back from the `reader` that the models were streamed
+    /// from, checking that they reference colors within the palette
+    pub(crate) async fn model<R: AsyncRead + AsyncSeek + Unpin + ?Sized>(
+        &self,
+        reader: &mut R,
+        file: &DotVoxData,
+        index: usize,
+        path: &str,
+    ) -> Result<Model, VoxLoaderError> {
+        let size = &file.models[index].size;
+        let mut bytes = Vec::new();
+        if let Some(range) = self.voxels.get(index) {
+            reader
+                .seek(SeekFrom::Start(range.start))
+                .await
+                .map_err(|error| anyhow!(error))?;
+            (&mut *reader)
+                .take(range.end - range.start)
+                .read_to_end(&mut bytes)
+                .await
+                .map_err(|error| anyhow!(error))?;
+        }
+        let voxels: Vec<Voxel> = bytes
+            .chunks_exact(4)
+            .map(|voxel| Voxel {
+                x: voxel[0],
+                y: voxel[1],
+                z: voxel[2],
+                // palette indices are stored from 1, as 0 is the empty voxel
+                i: voxel[3].wrapping_sub(1),
+            })
+            .collect();
+        if let Some(voxel) = voxels
+            .iter()
+            .find(|voxel| voxel.i == u8::MAX || voxel.i as usize >= file.palette.len())
+        {
+            return Err(VoxLoaderError::PaletteOutOfRange {
+                path: path.to_string(),
+                model: index,
+                index: voxel.i,
+            });
+        }
+        Ok(Model {
+            size: Size {
+                x: size.x,
+                y: size.y,
+                z: size.z,
+            },
+            voxels,
+        })
+    }
+}
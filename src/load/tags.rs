@@ -0,0 +1,97 @@
+use bevy::{
+    ecs::{
+        bundle::Bundle,
+        query::Added,
+        system::{Commands, EntityCommands, Query, Res, Resource},
+    },
+    utils::tracing::warn,
+};
+
+use super::VoxelSceneInstance;
+
+type Tagger = Box<dyn Fn(&mut EntityCommands) + Send + Sync + 'static>;
+
+/// Resource mapping node-name patterns to components that are inserted into matching nodes when a `.vox` scene is
+/// spawned, so that gameplay markup can live in data rather than in hand-written observers.
+///
+/// Patterns are matched against the full path of the node (eg `"workstation/computer"`), and may contain `*`
+/// wildcards, which match any sequence of characters.
+///
+/// ### Example
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_vox_scene::{VoxScenePlugin, VoxelNodeTags};
+/// #[derive(Component, Clone)]
+/// struct Door;
+///
+/// App::new()
+///     .add_plugins((DefaultPlugins, VoxScenePlugin::default()))
+///     .insert_resource(VoxelNodeTags::default().with("*door*", Door));
+/// ```
+#[derive(Resource, Default)]
+pub struct VoxelNodeTags {
+    rules: Vec<(String, Tagger)>,
+}
+
+impl VoxelNodeTags {
+    /// Inserts a clone of `bundle` into every node matching `pattern`
+    pub fn with<B: Bundle + Clone>(self, pattern: &str, bundle: B) -> Self {
+        self.with_fn(pattern, move |entity| {
+            entity.insert(bundle.clone());
+        })
+    }
+
+    /// Runs `tag` against every node matching `pattern`
+    pub fn with_fn<F: Fn(&mut EntityCommands) + Send + Sync + 'static>(
+        mut self,
+        pattern: &str,
+        tag: F,
+    ) -> Self {
+        if pattern.is_empty() {
+            warn!("Ignoring empty voxel node tag pattern");
+            return self;
+        }
+        self.rules.push((pattern.to_string(), Box::new(tag)));
+        self
+    }
+}
+
+pub(crate) fn tag_scene_nodes(
+    mut commands: Commands,
+    scenes: Query<&VoxelSceneInstance, Added<VoxelSceneInstance>>,
+    tags: Res<VoxelNodeTags>,
+) {
+    if tags.rules.is_empty() {
+        return;
+    }
+    for scene in scenes.iter() {
+        for (path, entity) in scene.nodes() {
+            for (pattern, tag) in tags.rules.iter() {
+                if matches_pattern(pattern, path) {
+                    tag(&mut commands.entity(entity));
+                }
+            }
+        }
+    }
+}
+
+/// Matches `text` against a `pattern` in which `*` matches any sequence of characters
+pub(crate) fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // no wildcards in the pattern
+        return remaining.is_empty();
+    };
+    for part in middle {
+        let Some(index) = remaining.find(part) else {
+            return false;
+        };
+        remaining = &remaining[index + part.len()..];
+    }
+    remaining.len() >= last.len() && remaining.ends_with(last)
+}
@@ -0,0 +1,248 @@
+use anyhow::anyhow;
+use bevy::{log::warn, math::UVec3, utils::HashSet};
+use dot_vox::{DotVoxData, SceneNode};
+use thiserror::Error;
+
+use super::{
+    chunks::chunks,
+    names::DuplicateNamePolicy,
+    parse_scene::{find_model_names, get_accumulated_and_node_name},
+    VoxLoaderError, VoxelFileIndex,
+};
+
+/// The largest size of a model along each axis that Magica Voxel can edit
+const MAX_MODEL_SIZE: u32 = 256;
+
+/// A problem found in a `.vox` file by [`validate_vox_bytes`]. None of these prevent the file from loading, unless
+/// [`crate::VoxLoaderSettings::strict`] is enabled, but they usually point to a mistake in the file.
+#[derive(Error, Clone, Debug, PartialEq)]
+pub enum VoxelLintIssue {
+    /// A model is larger than Magica Voxel can edit along at least one axis
+    #[error("model `{model}` is {size} voxels, larger than the {MAX_MODEL_SIZE} voxel limit")]
+    ModelTooLarge {
+        /// The name of the model
+        model: String,
+        /// The size of the model, in bevy's Y-up space
+        size: UVec3,
+    },
+    /// A model contains no voxels
+    #[error("model `{model}` is empty")]
+    EmptyModel {
+        /// The name of the model
+        model: String,
+    },
+    /// Several nodes share a path, so only the first can be loaded by its label
+    #[error("several nodes are named `{path}`")]
+    DuplicateNodeName {
+        /// The path of the nodes
+        path: String,
+    },
+    /// Several models share a name, so only one of them can be loaded by its label
+    #[error("several models are named `{name}`")]
+    DuplicateModelName {
+        /// The name of the models
+        name: String,
+    },
+    /// The file contains a chunk that the loader doesn't recognise
+    #[error("unsupported chunk `{chunk}`")]
+    UnsupportedChunk {
+        /// The id of the chunk
+        chunk: String,
+    },
+}
+
+/// Checks the `.vox` file `bytes` for problems without loading it, for instance to lint assets in a CI pipeline.
+///
+/// ### Returns
+/// Every [`VoxelLintIssue`] found in the file, or a [`VoxLoaderError`] if the file is too malformed to be loaded at
+/// all.
+pub fn validate_vox_bytes(bytes: &[u8]) -> Result<Vec<VoxelLintIssue>, VoxLoaderError> {
+    let file = dot_vox::load_bytes(bytes).map_err(|error| anyhow!(error))?;
+    validate_file(&file, "<bytes>")?;
+    Ok(lint_file(&file, bytes))
+}
+
+/// Lists the problems in a file that has passed [`validate_file`]
+pub(crate) fn lint_file(file: &DotVoxData, bytes: &[u8]) -> Vec<VoxelLintIssue> {
+    let voxel_counts: Vec<usize> = file.models.iter().map(|model| model.voxels.len()).collect();
+    lint_streamed_file(file, bytes, &voxel_counts)
+}
+
+/// Lists the problems in a file whose models are read separately, with `voxel_counts` holding the number of voxels of
+/// each model
+pub(crate) fn lint_streamed_file(
+    file: &DotVoxData,
+    bytes: &[u8],
+    voxel_counts: &[usize],
+) -> Vec<VoxelLintIssue> {
+    let mut issues: Vec<VoxelLintIssue> = unsupported_chunks(bytes)
+        .into_iter()
+        .map(|chunk| VoxelLintIssue::UnsupportedChunk { chunk })
+        .collect();
+    // the names before duplicates are relabelled by index
+    let mut named: Vec<Option<String>> = vec![None; file.models.len()];
+    if let Some(root) = file.scenes.first() {
+        find_model_names(&mut named, &file.scenes, root, None);
+    }
+    let names: Vec<String> = named
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| name.unwrap_or(format!("model-{}", index)))
+        .collect();
+    for ((name, model), voxel_count) in names.iter().zip(file.models.iter()).zip(voxel_counts) {
+        let size = UVec3::new(model.size.x, model.size.z, model.size.y);
+        if size.cmpgt(UVec3::splat(MAX_MODEL_SIZE)).any() {
+            issues.push(VoxelLintIssue::ModelTooLarge {
+                model: name.clone(),
+                size,
+            });
+        }
+        if *voxel_count == 0 {
+            issues.push(VoxelLintIssue::EmptyModel {
+                model: name.clone(),
+            });
+        }
+    }
+    let mut seen: HashSet<&String> = HashSet::new();
+    let mut reported: HashSet<&String> = HashSet::new();
+    for name in names.iter() {
+        if !seen.insert(name) && reported.insert(name) {
+            issues.push(VoxelLintIssue::DuplicateModelName { name: name.clone() });
+        }
+    }
+    let index = VoxelFileIndex::from_file(file, DuplicateNamePolicy::default());
+    let mut seen: HashSet<&String> = HashSet::new();
+    let mut reported: HashSet<&String> = HashSet::new();
+    for path in index.nodes.iter() {
+        if !seen.insert(path) && reported.insert(path) {
+            issues.push(VoxelLintIssue::DuplicateNodeName { path: path.clone() });
+        }
+    }
+    issues
+}
+
+/// Checks the parts of a parsed `.vox` file that the loader indexes into, so that a malformed file is reported as an
+/// error rather than panicking or overflowing the stack while the scene is built.
+pub(crate) fn validate_file(file: &DotVoxData, path: &str) -> Result<(), VoxLoaderError> {
+    if !file.scenes.is_empty() {
+        let mut visiting = vec![false; file.scenes.len()];
+        validate_node(file, path, 0, None, &mut visiting)?;
+    }
+    for (index, model) in file.models.iter().enumerate() {
+        if let Some(voxel) = model
+            .voxels
+            .iter()
+            .find(|voxel| voxel.i == u8::MAX || voxel.i as usize >= file.palette.len())
+        {
+            return Err(VoxLoaderError::PaletteOutOfRange {
+                path: path.to_string(),
+                model: index,
+                index: voxel.i,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn validate_node(
+    file: &DotVoxData,
+    path: &str,
+    node: u32,
+    parent_name: Option<&String>,
+    visiting: &mut Vec<bool>,
+) -> Result<(), VoxLoaderError> {
+    let Some(scene_node) = file.scenes.get(node as usize) else {
+        return Err(VoxLoaderError::InvalidAsset(anyhow!(
+            "{path}: node {} references missing scene node {node}",
+            parent_name.map_or("<root>", |name| name.as_str())
+        )));
+    };
+    if visiting[node as usize] {
+        return Err(VoxLoaderError::SceneGraphCycle {
+            path: path.to_string(),
+            node: node_path(parent_name),
+        });
+    }
+    visiting[node as usize] = true;
+    match scene_node {
+        SceneNode::Transform {
+            attributes, child, ..
+        } => {
+            let (accumulated, _) =
+                get_accumulated_and_node_name(parent_name, attributes.get("_name"));
+            validate_node(file, path, *child, accumulated.as_ref(), visiting)?;
+        }
+        SceneNode::Group { children, .. } => {
+            for child in children {
+                validate_node(file, path, *child, parent_name, visiting)?;
+            }
+        }
+        SceneNode::Shape { models, .. } => {
+            if models.is_empty() {
+                return Err(VoxLoaderError::InvalidAsset(anyhow!(
+                    "{path}: shape node {} has no models",
+                    node_path(parent_name)
+                )));
+            }
+            if let Some(model) = models
+                .iter()
+                .find(|model| model.model_id as usize >= file.models.len())
+            {
+                return Err(VoxLoaderError::MissingModel {
+                    path: path.to_string(),
+                    node: node_path(parent_name),
+                    model_id: model.model_id,
+                });
+            }
+        }
+    }
+    visiting[node as usize] = false;
+    Ok(())
+}
+
+fn node_path(name: Option<&String>) -> String {
+    name.cloned().unwrap_or("<unnamed>".to_string())
+}
+
+/// Logs a warning for each feature of the file that the loader doesn't support
+pub(crate) fn warn_ignored_features(file: &DotVoxData, path: &str) {
+    let animated = file
+        .scenes
+        .iter()
+        .filter(|node| matches!(node, SceneNode::Transform { frames, .. } if frames.len() > 1))
+        .count();
+    if animated > 0 {
+        warn!("{path}: only the first frame of {animated} animated transform node(s) is used");
+    }
+    let media = file
+        .materials
+        .iter()
+        .filter(|material| {
+            material
+                .material_type()
+                .is_some_and(|kind| kind.trim_start_matches('_') == "media")
+        })
+        .count();
+    if media > 0 {
+        warn!("{path}: {media} cloud (media) material(s) are rendered as diffuse");
+    }
+}
+
+/// The chunks of the `.vox` format known to the loader. Editor settings such as render objects (`rOBJ`) and cameras
+/// (`rCAM`) are known, even though they aren't loaded, as they don't affect the scene.
+const KNOWN_CHUNKS: [&str; 14] = [
+    "PACK", "SIZE", "XYZI", "RGBA", "MATL", "MATT", "nTRN", "nGRP", "nSHP", "LAYR", "rOBJ", "rCAM",
+    "NOTE", "IMAP",
+];
+
+/// Lists the ids of the chunks in a `.vox` file that the loader doesn't recognise, such as chunks added by newer
+/// versions of Magica Voxel, without duplicates and in the order they first appear
+pub(crate) fn unsupported_chunks(bytes: &[u8]) -> Vec<String> {
+    let mut unsupported: Vec<String> = Vec::new();
+    for (id, _) in chunks(bytes) {
+        if !KNOWN_CHUNKS.contains(&id.as_str()) && !unsupported.contains(&id) {
+            unsupported.push(id);
+        }
+    }
+    unsupported
+}
@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+
+use bevy::math::{IVec3, UVec3};
+use ndshape::{RuntimeShape, Shape};
+
+use super::{RawVoxel, VoxelData, VoxelModel, SIDES};
+
+/// The classification of a single voxel in a [`VoxelAirMap`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VoxelAir {
+    /// The voxel is solid
+    Solid,
+    /// The voxel is empty, and connected to the bounds of the model
+    Exterior,
+    /// The voxel is empty, and sealed off from the bounds of the model by solid voxels. Voxels in the same cavity share
+    /// the index, which counts up from 0.
+    Cavity(u32),
+}
+
+/// The classification of every voxel in a model as solid, outside air, or part of an enclosed cavity, returned by
+/// [`VoxelModel::classify_air`].
+///
+/// Empty voxels are connected through their faces, so a diagonal gap between two solid voxels doesn't unseal a
+/// cavity.
+#[derive(Clone, Debug)]
+pub struct VoxelAirMap {
+    shape: RuntimeShape<u32, 3>,
+    cells: Vec<VoxelAir>,
+    cavity_sizes: Vec<usize>,
+}
+
+impl VoxelAirMap {
+    /// The classification of the voxel at `position`, in voxel space. Returns `None` outside the model.
+    pub fn get(&self, position: IVec3) -> Option<VoxelAir> {
+        let size = IVec3::from(self.shape.as_array().map(|axis| axis as i32));
+        if position.cmplt(IVec3::ZERO).any() || position.cmpge(size).any() {
+            return None;
+        }
+        Some(self.cells[self.shape.linearize(position.as_uvec3().into()) as usize])
+    }
+
+    /// The number of enclosed cavities in the model
+    pub fn cavity_count(&self) -> usize {
+        self.cavity_sizes.len()
+    }
+
+    /// The number of voxels in the cavity with the supplied index, for instance to work out how much water floods
+    /// a sealed room
+    pub fn cavity_size(&self, cavity: u32) -> usize {
+        self.cavity_sizes.get(cavity as usize).copied().unwrap_or(0)
+    }
+
+    /// Iterates over the positions of the voxels in the cavity with the supplied index, in voxel space
+    pub fn cavity_voxels(&self, cavity: u32) -> impl Iterator<Item = IVec3> + '_ {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(move |(_, cell)| **cell == VoxelAir::Cavity(cavity))
+            .map(|(index, _)| UVec3::from(self.shape.delinearize(index as u32)).as_ivec3())
+    }
+}
+
+impl VoxelData {
+    /// Classifies every voxel of the model as solid, outside air, or part of an enclosed cavity, by flood filling the
+    /// empty voxels from the bounds of the model. See [`VoxelAirMap`].
+    pub fn classify_air(&self) -> VoxelAirMap {
+        let size = self._size();
+        let shape = RuntimeShape::<u32, 3>::new(size.as_uvec3().into());
+        let leading_padding = UVec3::splat(self.padding() / 2);
+        let mut cells: Vec<VoxelAir> = (0..shape.size())
+            .map(|index| {
+                let position = UVec3::from(shape.delinearize(index)) + leading_padding;
+                if *self.voxel_at_index(self.shape.linearize(position.into()) as usize)
+                    == RawVoxel::EMPTY
+                {
+                    // unvisited air is marked as a cavity until a flood fill reaches it
+                    VoxelAir::Cavity(u32::MAX)
+                } else {
+                    VoxelAir::Solid
+                }
+            })
+            .collect();
+        let unvisited = VoxelAir::Cavity(u32::MAX);
+        let flood = |cells: &mut [VoxelAir], start: u32, fill: VoxelAir| -> usize {
+            let mut filled = 1;
+            cells[start as usize] = fill;
+            let mut frontier = VecDeque::from([start]);
+            while let Some(index) = frontier.pop_front() {
+                let position = UVec3::from(shape.delinearize(index)).as_ivec3();
+                for offset in SIDES {
+                    let neighbor = position + offset;
+                    if neighbor.cmplt(IVec3::ZERO).any() || neighbor.cmpge(size).any() {
+                        continue;
+                    }
+                    let neighbor = shape.linearize(neighbor.as_uvec3().into());
+                    if cells[neighbor as usize] == unvisited {
+                        cells[neighbor as usize] = fill;
+                        filled += 1;
+                        frontier.push_back(neighbor);
+                    }
+                }
+            }
+            filled
+        };
+        for index in 0..shape.size() {
+            let position = UVec3::from(shape.delinearize(index)).as_ivec3();
+            let on_bounds = position.cmpeq(IVec3::ZERO).any() || position.cmpeq(size - 1).any();
+            if on_bounds && cells[index as usize] == unvisited {
+                flood(&mut cells, index, VoxelAir::Exterior);
+            }
+        }
+        let mut cavity_sizes = Vec::new();
+        for index in 0..shape.size() {
+            if cells[index as usize] == unvisited {
+                let cavity = VoxelAir::Cavity(cavity_sizes.len() as u32);
+                cavity_sizes.push(flood(&mut cells, index, cavity));
+            }
+        }
+        VoxelAirMap {
+            shape,
+            cells,
+            cavity_sizes,
+        }
+    }
+}
+
+impl VoxelModel {
+    /// Classifies every voxel of the model as solid, outside air, or part of an enclosed cavity, for gameplay such as
+    /// flooding sealed rooms or checking that a ship is pressurized. See [`VoxelData::classify_air`].
+    pub fn classify_air(&self) -> VoxelAirMap {
+        self.data.classify_air()
+    }
+}
@@ -0,0 +1,55 @@
+use bevy::utils::HashMap;
+use dot_vox::DotVoxData;
+
+use super::{RawVoxel, Voxel};
+
+/// Maps voxels to sound tags, such as `"metal"` or `"wood"`, so that games can play the right footstep and impact sounds
+/// on voxel surfaces.
+///
+/// Every [`super::VoxelContext`] holds a map. When a `.vox` file is loaded, each voxel is tagged with the name of its
+/// Magica Voxel material type, eg `"diffuse"`, `"metal"`, `"glass"` or `"emit"`. Override the tags with
+/// [`VoxelAudioMaterials::with_tag`] or [`VoxelAudioMaterials::set_tag`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VoxelAudioMaterials {
+    tags: HashMap<Voxel, String>,
+}
+
+impl VoxelAudioMaterials {
+    /// Tags the voxel with the sound tag
+    pub fn with_tag(mut self, voxel: Voxel, tag: impl Into<String>) -> Self {
+        self.set_tag(voxel, tag);
+        self
+    }
+
+    /// Tags the voxel with the sound tag, replacing any existing tag
+    pub fn set_tag(&mut self, voxel: Voxel, tag: impl Into<String>) {
+        if voxel != Voxel::EMPTY {
+            self.tags.insert(voxel, tag.into());
+        }
+    }
+
+    /// Removes the tag from the voxel
+    pub fn remove_tag(&mut self, voxel: &Voxel) {
+        self.tags.remove(voxel);
+    }
+
+    /// Returns the sound tag of the voxel, if it has one
+    pub fn tag(&self, voxel: &Voxel) -> Option<&str> {
+        self.tags.get(voxel).map(|tag| tag.as_str())
+    }
+
+    pub(crate) fn from_data(data: &DotVoxData) -> Self {
+        let tags = data
+            .materials
+            .iter()
+            .take(RawVoxel::EMPTY.0 as usize)
+            .enumerate()
+            .filter_map(|(index, material)| {
+                let tag = material.material_type()?.trim_start_matches('_');
+                let voxel: Voxel = RawVoxel(index as u8).into();
+                Some((voxel, tag.to_string()))
+            })
+            .collect();
+        Self { tags }
+    }
+}
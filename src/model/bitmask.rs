@@ -0,0 +1,316 @@
+use block_mesh::{UnitQuadBuffer, UnorientedQuad, UnorientedUnitQuad, VoxelVisibility};
+use ndshape::{RuntimeShape, Shape};
+
+use super::voxel::VisibleVoxel;
+
+/// The step to the neighbor checked for each face, in the order of [`block_mesh::RIGHT_HANDED_Y_UP_CONFIG`] and
+/// [`super::mesh::ATTRIBUTE_FACE_ID`]: `-X, -Y, -Z, +X, +Y, +Z`
+const FACE_STEPS: [[i32; 3]; 6] = [
+    [-1, 0, 0],
+    [0, -1, 0],
+    [0, 0, -1],
+    [1, 0, 0],
+    [0, 1, 0],
+    [0, 0, 1],
+];
+
+/// The axes of each face, in the order of [`FACE_STEPS`], as the axis of its normal followed by the axes along which
+/// the width and height of its quads are measured, as in [`block_mesh::RIGHT_HANDED_Y_UP_CONFIG`]
+const FACE_AXES: [[usize; 3]; 6] = [
+    [0, 2, 1],
+    [1, 2, 0],
+    [2, 0, 1],
+    [0, 2, 1],
+    [1, 2, 0],
+    [2, 0, 1],
+];
+
+/// The bits of the word at `word` of a row covering voxels with `x` from `start` up to but not including `end`
+fn bits_between(word: usize, start: u32, end: u32) -> u64 {
+    let first = word as u32 * 64;
+    let start = start.saturating_sub(first);
+    let end = end.saturating_sub(first).min(64);
+    if end <= start {
+        return 0;
+    }
+    let high = if end == 64 { u64::MAX } else { (1 << end) - 1 };
+    high & !((1_u64 << start) - 1)
+}
+
+/// The opaque and translucent voxels of a model packed into bitmasks, one bit per voxel, so that the visibility of
+/// 64 faces is tested with a handful of integer operations rather than one voxel at a time.
+///
+/// Each row of voxels running along x is stored as consecutive `u64` words, with the voxel at `x` in bit `x % 64` of
+/// word `x / 64`. Neighbors along y and z are the same bits of another row, and neighbors along x are found by
+/// shifting the row by one bit.
+pub(crate) struct VoxelOccupancy {
+    size: [u32; 3],
+    words_per_row: usize,
+    opaque: Vec<u64>,
+    translucent: Vec<u64>,
+    /// For each face, in the order of [`FACE_STEPS`], the opaque and translucent voxels that aren't cubes but cover the
+    /// whole side of their cell that the face would be against. Empty if there are no such voxels.
+    covering: Vec<[Vec<u64>; 2]>,
+}
+
+impl VoxelOccupancy {
+    /// Packs the `voxels` laid out in `shape`
+    pub(crate) fn new(voxels: &[VisibleVoxel], shape: &RuntimeShape<u32, 3>) -> Self {
+        let size = shape.as_array();
+        let words_per_row = (size[0] as usize).div_ceil(64);
+        let rows = (size[1] * size[2]) as usize;
+        let mut opaque = vec![0; rows * words_per_row];
+        let mut translucent = vec![0; rows * words_per_row];
+        for z in 0..size[2] {
+            for y in 0..size[1] {
+                let start = shape.linearize([0, y, z]) as usize;
+                let row = (y + size[1] * z) as usize * words_per_row;
+                for (word, chunk) in voxels[start..start + size[0] as usize]
+                    .chunks(64)
+                    .enumerate()
+                {
+                    let (mut o, mut t) = (0_u64, 0_u64);
+                    for (bit, voxel) in chunk.iter().enumerate() {
+                        match voxel.visibility {
+                            VoxelVisibility::Opaque => o |= 1 << bit,
+                            VoxelVisibility::Translucent => t |= 1 << bit,
+                            VoxelVisibility::Empty => {}
+                        }
+                    }
+                    opaque[row + word] = o;
+                    translucent[row + word] = t;
+                }
+            }
+        }
+        Self {
+            size,
+            words_per_row,
+            opaque,
+            translucent,
+            covering: Vec::new(),
+        }
+    }
+
+    /// Marks the voxel at `cell`, which is empty to the meshers as it isn't a cube, as covering the whole side of its
+    /// cell at index `side` of [`FACE_STEPS`], so that the face of the neighboring cube against that side is hidden like
+    /// it would be by a cube
+    pub(crate) fn cover(&mut self, cell: [u32; 3], side: usize, translucent: bool) {
+        if self.covering.is_empty() {
+            self.covering = vec![[vec![0; self.opaque.len()], vec![0; self.opaque.len()]]; 6];
+        }
+        // the neighbor's face against this side points the opposite way
+        let face = (side + 3) % 6;
+        let (word, bit) = self.bit(cell);
+        self.covering[face][translucent as usize][word] |= bit;
+    }
+
+    fn row(&self, y: u32, z: u32) -> usize {
+        (y + self.size[1] * z) as usize * self.words_per_row
+    }
+
+    /// The word of `mask` holding the neighbors one step along x from the voxels of `word` in the row starting at
+    /// `row`, in the direction of `step`
+    fn shifted(&self, mask: &[u64], row: usize, word: usize, step: i32) -> u64 {
+        let words = &mask[row..row + self.words_per_row];
+        match step {
+            -1 => {
+                let carry = if word > 0 { words[word - 1] >> 63 } else { 0 };
+                (words[word] << 1) | carry
+            }
+            1 => {
+                let carry = words.get(word + 1).map_or(0, |next| next << 63);
+                (words[word] >> 1) | carry
+            }
+            _ => words[word],
+        }
+    }
+
+    /// The bits of `word` covering voxels with `x` from 1 to `size.x - 2`, as the meshers skip the outermost voxels
+    fn interior_bits(&self, word: usize) -> u64 {
+        bits_between(word, 1, self.size[0].saturating_sub(1))
+    }
+
+    /// The faces in the direction of `step` that aren't covered by their neighbor, as one bit per voxel laid out like
+    /// the occupancy: an opaque voxel's face is visible unless its neighbor is opaque, and a translucent voxel's face
+    /// is visible only if its neighbor is empty. Like the meshers, the outermost voxels are left out.
+    fn visible_mask(&self, face: usize) -> Vec<u64> {
+        let step = FACE_STEPS[face];
+        let [_, size_y, size_z] = self.size;
+        let mut visible = vec![0; self.opaque.len()];
+        for z in 1..size_z.saturating_sub(1) {
+            for y in 1..size_y.saturating_sub(1) {
+                let row = self.row(y, z);
+                let neighbor_row = self.row(
+                    y.wrapping_add_signed(step[1]),
+                    z.wrapping_add_signed(step[2]),
+                );
+                for word in 0..self.words_per_row {
+                    let opaque = self.opaque[row + word];
+                    let translucent = self.translucent[row + word];
+                    if (opaque | translucent) == 0 {
+                        continue;
+                    }
+                    let mut neighbor_opaque =
+                        self.shifted(&self.opaque, neighbor_row, word, step[0]);
+                    let mut neighbor_translucent =
+                        self.shifted(&self.translucent, neighbor_row, word, step[0]);
+                    if let Some([opaque, translucent]) = self.covering.get(face) {
+                        neighbor_opaque |= self.shifted(opaque, neighbor_row, word, step[0]);
+                        neighbor_translucent |=
+                            self.shifted(translucent, neighbor_row, word, step[0]);
+                    }
+                    visible[row + word] = ((opaque & !neighbor_opaque)
+                        | (translucent & !(neighbor_opaque | neighbor_translucent)))
+                        & self.interior_bits(word);
+                }
+            }
+        }
+        visible
+    }
+
+    /// The index of the word of a mask holding the voxel at `cell`, and the bit of the voxel within it
+    fn bit(&self, cell: [u32; 3]) -> (usize, u64) {
+        (
+            self.row(cell[1], cell[2]) + cell[0] as usize / 64,
+            1 << (cell[0] % 64),
+        )
+    }
+
+    /// Adds a unit quad to `output` for every face of a non-empty voxel that isn't covered by its neighbor. This
+    /// produces exactly the same quads, in the same order, as [`block_mesh::visible_block_faces`] over the whole
+    /// shape.
+    pub(crate) fn visible_faces(&self, output: &mut UnitQuadBuffer) {
+        for (face, group) in output.groups.iter_mut().enumerate() {
+            let visible = self.visible_mask(face);
+            for z in 0..self.size[2] {
+                for y in 0..self.size[1] {
+                    let row = self.row(y, z);
+                    for word in 0..self.words_per_row {
+                        let mut bits = visible[row + word];
+                        while bits != 0 {
+                            let x = word as u32 * 64 + bits.trailing_zeros();
+                            group.push(UnorientedUnitQuad { minimum: [x, y, z] });
+                            bits &= bits - 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Merges the faces of the `voxels` that aren't covered by their neighbors into quads of identical voxels. This
+    /// produces exactly the same quads, in the same order, as [`block_mesh::greedy_quads`] over the whole shape, but
+    /// the visibility of each face is read from a bitmask, and runs of hidden faces are skipped 64 voxels at a time.
+    pub(crate) fn greedy_quads(
+        &self,
+        voxels: &[VisibleVoxel],
+        shape: &RuntimeShape<u32, 3>,
+    ) -> [Vec<UnorientedQuad>; 6] {
+        let Some((min, max)) = self.bounds() else {
+            return Default::default();
+        };
+        std::array::from_fn(|face| {
+            let [n, u, v] = FACE_AXES[face];
+            // faces are cleared from the mask once they are part of a quad
+            let mut remaining = self.visible_mask(face);
+            let mut quads = Vec::new();
+            // like block_mesh, each slice along the normal is scanned with x varying fastest, then y, then z
+            for slice in min[n]..=max[n] {
+                let (mut low, mut high) = (min, max);
+                low[n] = slice;
+                high[n] = slice;
+                for z in low[2]..=high[2] {
+                    for y in low[1]..=high[1] {
+                        let row = self.row(y, z);
+                        for word in low[0] as usize / 64..=high[0] as usize / 64 {
+                            let span = bits_between(word, low[0], high[0] + 1);
+                            loop {
+                                let bits = remaining[row + word] & span;
+                                if bits == 0 {
+                                    break;
+                                }
+                                let minimum = [word as u32 * 64 + bits.trailing_zeros(), y, z];
+                                let quad =
+                                    self.grow_quad(voxels, shape, &mut remaining, minimum, [u, v]);
+                                quads.push(quad);
+                            }
+                        }
+                    }
+                }
+            }
+            quads
+        })
+    }
+
+    /// The largest quad of faces still in `remaining` that are identical to the face of the voxel at `minimum`,
+    /// growing first along the axis `axes[0]`, then along `axes[1]`, as [`block_mesh::greedy_quads`] does. The faces
+    /// of the quad are cleared from `remaining`.
+    fn grow_quad(
+        &self,
+        voxels: &[VisibleVoxel],
+        shape: &RuntimeShape<u32, 3>,
+        remaining: &mut [u64],
+        minimum: [u32; 3],
+        axes: [usize; 2],
+    ) -> UnorientedQuad {
+        let voxel = voxels[shape.linearize(minimum) as usize];
+        let mergeable = |remaining: &[u64], cell: [u32; 3]| {
+            let (word, bit) = self.bit(cell);
+            remaining[word] & bit != 0 && voxels[shape.linearize(cell) as usize] == voxel
+        };
+        let along = |cell: [u32; 3], axis: usize, distance: u32| {
+            let mut cell = cell;
+            cell[axis] += distance;
+            cell
+        };
+        // the outermost voxels have no visible faces, so quads stop before them
+        let max_width = self.size[axes[0]] - 1 - minimum[axes[0]];
+        let max_height = self.size[axes[1]] - 1 - minimum[axes[1]];
+        let width = (0..max_width)
+            .take_while(|&step| mergeable(remaining, along(minimum, axes[0], step)))
+            .count() as u32;
+        let height = (0..max_height)
+            .take_while(|&row| {
+                let start = along(minimum, axes[1], row);
+                (0..width).all(|step| mergeable(remaining, along(start, axes[0], step)))
+            })
+            .count() as u32;
+        for row in 0..height {
+            for step in 0..width {
+                let (word, bit) = self.bit(along(along(minimum, axes[1], row), axes[0], step));
+                remaining[word] &= !bit;
+            }
+        }
+        UnorientedQuad {
+            minimum,
+            width,
+            height,
+        }
+    }
+
+    /// The smallest box containing every non-empty voxel, as its inclusive minimum and maximum corners, or `None` if
+    /// every voxel is empty
+    pub(crate) fn bounds(&self) -> Option<([u32; 3], [u32; 3])> {
+        let mut min = [u32::MAX; 3];
+        let mut max = [0; 3];
+        for z in 0..self.size[2] {
+            for y in 0..self.size[1] {
+                let row = self.row(y, z);
+                for word in 0..self.words_per_row {
+                    let occupied = self.opaque[row + word] | self.translucent[row + word];
+                    if occupied == 0 {
+                        continue;
+                    }
+                    let first = word as u32 * 64;
+                    min[0] = min[0].min(first + occupied.trailing_zeros());
+                    max[0] = max[0].max(first + 63 - occupied.leading_zeros());
+                    min[1] = min[1].min(y);
+                    max[1] = max[1].max(y);
+                    min[2] = min[2].min(z);
+                    max[2] = max[2].max(z);
+                }
+            }
+        }
+        (min[0] != u32::MAX).then_some((min, max))
+    }
+}
@@ -0,0 +1,134 @@
+use bevy::{
+    asset::{Asset, Assets, Handle},
+    ecs::{
+        system::Commands,
+        world::{Command, World},
+    },
+    math::{IVec3, Quat},
+    reflect::TypePath,
+};
+
+use crate::VoxelModelInstance;
+
+use super::{
+    clipboard::{PasteVoxels, VoxelClipboard},
+    Voxel, VoxelQueryable,
+};
+
+/// A reusable arrangement of voxels that can be stamped into models, for instance a building piece in a construction game.
+///
+/// Blueprints can be loaded from `.vox` files by setting [`crate::VoxLoaderSettings::create_blueprints`] and appending
+/// `#{name}@blueprint` to the asset path, or created from a [`VoxelClipboard`] with [`VoxelBlueprint::from_clipboard`].
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct VoxelBlueprint {
+    /// The voxels and the palette elements they refer to
+    pub voxels: VoxelClipboard,
+    /// The point within the blueprint, in its voxel space, that is placed at the position passed to
+    /// [`StampBlueprintCommandsExt::stamp_blueprint`]
+    pub anchor: IVec3,
+}
+
+impl VoxelBlueprint {
+    /// Creates a blueprint from the `clipboard`, anchored at the center of its bottom face
+    pub fn from_clipboard(clipboard: VoxelClipboard) -> Self {
+        let size = clipboard.size();
+        Self {
+            anchor: IVec3::new(size.x / 2, 0, size.z / 2),
+            voxels: clipboard,
+        }
+    }
+
+    /// Sets the anchor of the blueprint
+    pub fn with_anchor(mut self, anchor: IVec3) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    /// The size of the blueprint, in voxels
+    pub fn size(&self) -> IVec3 {
+        self.voxels.size()
+    }
+
+    /// Returns true if the blueprint, stamped with its anchor at `position` and the supplied `rotation`, lies entirely
+    /// within the bounds of the `model` without overlapping any of its solid voxels.
+    pub fn fits_at(&self, model: &impl VoxelQueryable, position: IVec3, rotation: Quat) -> bool {
+        let origin = self.origin_at(position, rotation);
+        self.voxels
+            .rotated(rotation)
+            .iter()
+            .filter(|(_, voxel)| **voxel != Voxel::EMPTY)
+            .all(|(offset, _)| model.get_voxel_at_point(origin + offset) == Ok(Voxel::EMPTY))
+    }
+
+    /// The voxel-space position in the target model of the lower-back-left corner of the blueprint, when it is stamped
+    /// with its anchor at `position` and the supplied `rotation`
+    pub fn origin_at(&self, position: IVec3, rotation: Quat) -> IVec3 {
+        position - self.voxels.rotate_point(self.anchor, rotation)
+    }
+}
+
+/// Command for stamping a [`VoxelBlueprint`] into a model
+pub trait StampBlueprintCommandsExt {
+    /// Writes the `blueprint` into the `model`
+    ///
+    /// ### Arguments
+    /// * `model` - the model to stamp the blueprint into
+    /// * `blueprint` - handle to the blueprint
+    /// * `position` - the position in the model's voxel space that the blueprint's anchor will be placed at
+    /// * `rotation` - the rotation of the blueprint around its anchor, snapped to multiples of 90 degrees
+    ///
+    /// ### Notes
+    /// As with [`crate::VoxelClipboardCommandsExt::paste_voxels`], empty voxels in the blueprint don't overwrite the
+    /// model, and the blueprint's voxels are remapped to the model's palette if necessary.
+    fn stamp_blueprint(
+        &mut self,
+        model: VoxelModelInstance,
+        blueprint: Handle<VoxelBlueprint>,
+        position: IVec3,
+        rotation: Quat,
+    ) -> &mut Self;
+}
+
+impl StampBlueprintCommandsExt for Commands<'_, '_> {
+    fn stamp_blueprint(
+        &mut self,
+        model: VoxelModelInstance,
+        blueprint: Handle<VoxelBlueprint>,
+        position: IVec3,
+        rotation: Quat,
+    ) -> &mut Self {
+        self.add(StampBlueprint {
+            instance: model,
+            blueprint,
+            position,
+            rotation,
+        });
+        self
+    }
+}
+
+struct StampBlueprint {
+    instance: VoxelModelInstance,
+    blueprint: Handle<VoxelBlueprint>,
+    position: IVec3,
+    rotation: Quat,
+}
+
+impl Command for StampBlueprint {
+    fn apply(self, world: &mut World) {
+        let Some(blueprint) = world
+            .resource::<Assets<VoxelBlueprint>>()
+            .get(self.blueprint.id())
+            .cloned()
+        else {
+            return;
+        };
+        PasteVoxels {
+            instance: self.instance,
+            offset: blueprint.origin_at(self.position, self.rotation),
+            clipboard: Some(blueprint.voxels),
+            rotation: self.rotation,
+        }
+        .apply(world);
+    }
+}
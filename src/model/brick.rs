@@ -0,0 +1,330 @@
+use bevy::{
+    asset::Asset,
+    math::{IVec3, Ray3d, UVec3, Vec3},
+    reflect::TypePath,
+    transform::components::GlobalTransform,
+};
+use ndshape::Shape;
+
+use super::{RawVoxel, VoxelData};
+
+/// The number of voxels along each edge of a brick
+const BRICK_SIZE: i32 = 64;
+const WORDS_PER_BRICK: usize = (BRICK_SIZE * BRICK_SIZE) as usize;
+
+/// A coarse acceleration structure over the voxels of a model, splitting it into bricks of 64³ voxels,
+/// each holding an occupancy bitmask, or nothing at all if the brick is empty.
+///
+/// Raycasts and region queries against the brick map skip empty bricks entirely, and only test one bit per voxel in
+/// occupied ones, which is much faster than querying the voxels of a large, sparse model directly. Brick maps are
+/// created by the loader when [`crate::VoxLoaderSettings::brick_maps`] is enabled, and can be loaded by appending
+/// `#{name}@bricks` to the asset path. They are kept up to date as the model is modified, rebuilding only the bricks
+/// touched by each edit.
+#[derive(Asset, TypePath, Clone, Debug, Default)]
+pub struct VoxelBrickMap {
+    size: IVec3,
+    voxel_size: f32,
+    bricks_size: IVec3,
+    /// One row of bits along the x axis per word, indexed by y then z
+    bricks: Vec<Option<Box<[u64]>>>,
+}
+
+/// The result of a successful [`VoxelBrickMap::raycast`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelBrickHit {
+    /// The coordinate of the voxel that was hit, in voxel space
+    pub voxel_coord: IVec3,
+    /// The normal of the face of the voxel that the ray entered through, in voxel space. This is zero if the ray
+    /// started inside the voxel.
+    pub normal: IVec3,
+    /// The point where the ray entered the voxel, in global space
+    pub point: Vec3,
+    /// The distance along the ray to [`VoxelBrickHit::point`]
+    pub distance: f32,
+}
+
+impl VoxelBrickMap {
+    /// Builds a brick map of the solid voxels in `data`
+    pub fn from_data(data: &VoxelData) -> Self {
+        let size = data._size();
+        let bricks_size = (size + IVec3::splat(BRICK_SIZE - 1)) / BRICK_SIZE;
+        let mut brick_map = Self {
+            size,
+            voxel_size: data.voxel_size,
+            bricks_size,
+            bricks: vec![None; (bricks_size.x * bricks_size.y * bricks_size.z).max(0) as usize],
+        };
+        brick_map.update_region(data, IVec3::ZERO, size);
+        brick_map
+    }
+
+    /// The size of the model in voxels
+    pub fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    /// The number of bricks along each axis
+    pub fn bricks_size(&self) -> IVec3 {
+        self.bricks_size
+    }
+
+    /// The number of bricks containing at least one solid voxel
+    pub fn occupied_bricks(&self) -> usize {
+        self.bricks.iter().filter(|brick| brick.is_some()).count()
+    }
+
+    /// Whether the brick at the `brick` coordinate contains no solid voxels. Bricks outside the map are empty.
+    pub fn is_brick_empty(&self, brick: IVec3) -> bool {
+        self.brick_index(brick)
+            .map_or(true, |index| self.bricks[index].is_none())
+    }
+
+    /// Whether the voxel at the `position`, in voxel space, is solid
+    pub fn is_solid(&self, position: IVec3) -> bool {
+        if position.cmplt(IVec3::ZERO).any() || position.cmpge(self.size).any() {
+            return false;
+        }
+        let Some(Some(words)) = self
+            .brick_index(position / BRICK_SIZE)
+            .map(|index| &self.bricks[index])
+        else {
+            return false;
+        };
+        let local = position % BRICK_SIZE;
+        words[Self::word_index(local)] & (1 << local.x) != 0
+    }
+
+    /// Whether any voxel in the box from `min` to `max` (exclusive), in voxel space, is solid
+    pub fn any_solid(&self, min: IVec3, max: IVec3) -> bool {
+        self.rows(min, max)
+            .any(|(words, word, mask)| words[word] & mask != 0)
+    }
+
+    /// The number of solid voxels in the box from `min` to `max` (exclusive), in voxel space
+    pub fn count_solid(&self, min: IVec3, max: IVec3) -> usize {
+        self.rows(min, max)
+            .map(|(words, word, mask)| (words[word] & mask).count_ones() as usize)
+            .sum()
+    }
+
+    /// Casts a ray through the brick map, returning the first solid voxel it hits
+    ///
+    /// ### Arguments
+    /// * `ray` - the ray in global space
+    /// * `global_xform` - the [`bevy::transform::components::GlobalTransform`] of the entity that owns the
+    ///   [`crate::VoxelModelInstance`] of the model
+    /// * `max_distance` - the furthest distance along the ray to search, in global space
+    ///
+    /// ### Returns
+    /// the voxel that was hit, or `None` if the ray misses every solid voxel.
+    pub fn raycast(
+        &self,
+        ray: Ray3d,
+        global_xform: &GlobalTransform,
+        max_distance: f32,
+    ) -> Option<VoxelBrickHit> {
+        if self.size.cmple(IVec3::ZERO).any() {
+            return None;
+        }
+        let inverse = global_xform.affine().inverse();
+        // in voxel space each voxel is a unit cube, and the distance along the ray is unchanged
+        let origin =
+            inverse.transform_point3(ray.origin) / self.voxel_size + self.size.as_vec3() * 0.5;
+        let direction = inverse.transform_vector3(*ray.direction) / self.voxel_size;
+        let (mut distance, exit, mut normal) =
+            clip_ray(origin, direction, IVec3::ZERO, self.size, 0.0, max_distance)?;
+        let step = direction.signum().as_ivec3();
+        // the distance along the ray at which it crosses the next boundary on each axis
+        let boundary_distance = |boundary: IVec3, axis: usize| {
+            if direction[axis] == 0.0 {
+                f32::INFINITY
+            } else {
+                (boundary[axis] as f32 - origin[axis]) / direction[axis]
+            }
+        };
+        let mut coord = (origin + direction * distance)
+            .floor()
+            .as_ivec3()
+            .clamp(IVec3::ZERO, self.size - IVec3::ONE);
+        while distance <= exit && coord.cmpge(IVec3::ZERO).all() && coord.cmplt(self.size).all() {
+            let brick = coord / BRICK_SIZE;
+            let brick_min = brick * BRICK_SIZE;
+            let brick_max = brick_min + IVec3::splat(BRICK_SIZE);
+            let far_face = IVec3::select(step.cmpgt(IVec3::ZERO), brick_max, brick_min);
+            if self.is_brick_empty(brick) {
+                // skip the empty brick, entering the next one through the face the ray leaves by
+                let leaving =
+                    Vec3::from_array([0, 1, 2].map(|axis| boundary_distance(far_face, axis)));
+                let axis = min_axis(leaving);
+                distance = leaving[axis];
+                coord = (origin + direction * distance)
+                    .floor()
+                    .as_ivec3()
+                    .clamp(brick_min, brick_max - IVec3::ONE);
+                coord[axis] = if step[axis] > 0 {
+                    brick_max[axis]
+                } else {
+                    brick_min[axis] - 1
+                };
+                normal = IVec3::ZERO;
+                normal[axis] = -step[axis];
+                continue;
+            }
+            // march the voxels of the occupied brick
+            let mut next_boundary = Vec3::from_array([0, 1, 2].map(|axis| {
+                boundary_distance(
+                    coord + IVec3::select(step.cmpgt(IVec3::ZERO), IVec3::ONE, IVec3::ZERO),
+                    axis,
+                )
+            }));
+            let boundary_step = direction.abs().recip();
+            while coord.cmpge(brick_min).all() && coord.cmplt(brick_max).all() {
+                if distance > exit || coord.cmpge(self.size).any() {
+                    return None;
+                }
+                if self.is_solid(coord) {
+                    return Some(VoxelBrickHit {
+                        voxel_coord: coord,
+                        normal,
+                        point: ray.get_point(distance),
+                        distance,
+                    });
+                }
+                let axis = min_axis(next_boundary);
+                distance = next_boundary[axis];
+                next_boundary[axis] += boundary_step[axis];
+                coord[axis] += step[axis];
+                normal = IVec3::ZERO;
+                normal[axis] = -step[axis];
+            }
+        }
+        None
+    }
+
+    /// Rebuilds the bricks overlapping the box from `min` to `max` (exclusive), after the voxels within it were written
+    pub(crate) fn update_region(&mut self, data: &VoxelData, min: IVec3, max: IVec3) {
+        let first = min.clamp(IVec3::ZERO, self.size) / BRICK_SIZE;
+        let last = (max.clamp(IVec3::ZERO, self.size) + IVec3::splat(BRICK_SIZE - 1)) / BRICK_SIZE;
+        let leading_padding = UVec3::splat(data.padding() / 2);
+        for bz in first.z..last.z {
+            for by in first.y..last.y {
+                for bx in first.x..last.x {
+                    let brick = IVec3::new(bx, by, bz);
+                    let Some(index) = self.brick_index(brick) else {
+                        continue;
+                    };
+                    let origin = brick * BRICK_SIZE;
+                    let end = (origin + IVec3::splat(BRICK_SIZE)).min(self.size);
+                    let mut words = vec![0_u64; WORDS_PER_BRICK];
+                    let mut occupied = false;
+                    for z in origin.z..end.z {
+                        for y in origin.y..end.y {
+                            let word = Self::word_index(IVec3::new(0, y, z) - origin);
+                            for x in origin.x..end.x {
+                                let point = IVec3::new(x, y, z).as_uvec3() + leading_padding;
+                                let voxel = data
+                                    .voxel_at_index(data.shape.linearize(point.into()) as usize);
+                                if *voxel != RawVoxel::EMPTY {
+                                    words[word] |= 1 << (x - origin.x);
+                                    occupied = true;
+                                }
+                            }
+                        }
+                    }
+                    self.bricks[index] = occupied.then(|| words.into_boxed_slice());
+                }
+            }
+        }
+    }
+
+    fn brick_index(&self, brick: IVec3) -> Option<usize> {
+        if brick.cmplt(IVec3::ZERO).any() || brick.cmpge(self.bricks_size).any() {
+            return None;
+        }
+        Some((brick.x + self.bricks_size.x * (brick.y + self.bricks_size.y * brick.z)) as usize)
+    }
+
+    fn word_index(local: IVec3) -> usize {
+        (local.y + BRICK_SIZE * local.z) as usize
+    }
+
+    /// Iterates over the occupied rows intersecting the box, as the words of the brick, the index of the row and a mask
+    /// of the bits within the box
+    fn rows(&self, min: IVec3, max: IVec3) -> impl Iterator<Item = (&[u64], usize, u64)> + '_ {
+        let min = min.clamp(IVec3::ZERO, self.size);
+        let max = max.clamp(IVec3::ZERO, self.size);
+        let first = min / BRICK_SIZE;
+        let last = if max.cmpgt(min).all() {
+            (max + IVec3::splat(BRICK_SIZE - 1)) / BRICK_SIZE
+        } else {
+            first
+        };
+        (first.z..last.z).flat_map(move |bz| {
+            (first.y..last.y).flat_map(move |by| {
+                (first.x..last.x).flat_map(move |bx| {
+                    let brick = IVec3::new(bx, by, bz);
+                    let words = self
+                        .brick_index(brick)
+                        .and_then(|index| self.bricks[index].as_deref());
+                    let origin = brick * BRICK_SIZE;
+                    let start = min.max(origin) - origin;
+                    let end = max.min(origin + IVec3::splat(BRICK_SIZE)) - origin;
+                    let width = (end.x - start.x) as u32;
+                    let mask = if width >= 64 {
+                        u64::MAX
+                    } else {
+                        ((1_u64 << width) - 1) << start.x
+                    };
+                    words.into_iter().flat_map(move |words| {
+                        (start.z..end.z).flat_map(move |z| {
+                            (start.y..end.y)
+                                .map(move |y| (words, Self::word_index(IVec3::new(0, y, z)), mask))
+                        })
+                    })
+                })
+            })
+        })
+    }
+}
+
+/// The axis with the smallest component
+fn min_axis(value: Vec3) -> usize {
+    if value.x < value.y && value.x < value.z {
+        0
+    } else if value.y < value.z {
+        1
+    } else {
+        2
+    }
+}
+
+/// Clips the ray to the box from `min` to `max`, returning the distances at which it enters and leaves the box, and the
+/// normal of the face it enters through
+fn clip_ray(
+    origin: Vec3,
+    direction: Vec3,
+    min: IVec3,
+    max: IVec3,
+    mut entry: f32,
+    mut exit: f32,
+) -> Option<(f32, f32, IVec3)> {
+    let mut normal = IVec3::ZERO;
+    for axis in 0..3 {
+        if direction[axis] == 0.0 {
+            if origin[axis] < min[axis] as f32 || origin[axis] > max[axis] as f32 {
+                return None;
+            }
+            continue;
+        }
+        let near = (min[axis] as f32 - origin[axis]) / direction[axis];
+        let far = (max[axis] as f32 - origin[axis]) / direction[axis];
+        let (near, far) = (near.min(far), near.max(far));
+        if near > entry {
+            entry = near;
+            normal = IVec3::ZERO;
+            normal[axis] = -direction[axis].signum() as i32;
+        }
+        exit = exit.min(far);
+    }
+    (entry <= exit).then_some((entry, exit, normal))
+}
@@ -0,0 +1,144 @@
+use bevy::math::IVec3;
+
+use crate::VoxelRng;
+
+use super::Voxel;
+
+/// How the strength of a [`VoxelBrush`] fades from the center of the edited region towards its edges.
+///
+/// Weaker parts of the brush place voxels probabilistically, so that edits feather into their surroundings.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum VoxelBrushFalloff {
+    /// Every voxel within the region is edited
+    #[default]
+    None,
+    /// The chance of editing a voxel falls linearly from 1 at the center to 0 at the edge
+    Linear,
+    /// The chance of editing a voxel follows a smoothstep curve from 1 at the center to 0 at the edge
+    Smooth,
+    /// Every voxel within this fraction of the distance to the edge is edited, with the chance of editing falling
+    /// linearly to 0 beyond it
+    Hardness(f32),
+}
+
+impl VoxelBrushFalloff {
+    /// The chance of editing a voxel at the normalized `distance` from the center of the region, where 1 is the edge
+    pub fn weight(&self, distance: f32) -> f32 {
+        let distance = distance.clamp(0.0, 1.0);
+        match self {
+            VoxelBrushFalloff::None => 1.0,
+            VoxelBrushFalloff::Linear => 1.0 - distance,
+            VoxelBrushFalloff::Smooth => {
+                let t = 1.0 - distance;
+                t * t * (3.0 - 2.0 * t)
+            }
+            VoxelBrushFalloff::Hardness(hardness) => {
+                let hardness = hardness.clamp(0.0, 1.0);
+                if distance <= hardness || hardness >= 1.0 {
+                    1.0
+                } else {
+                    1.0 - (distance - hardness) / (1.0 - hardness)
+                }
+            }
+        }
+    }
+}
+
+/// Which existing voxels a [`VoxelBrush`] is allowed to overwrite
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VoxelBrushBlend {
+    /// Overwrite every voxel
+    #[default]
+    Replace,
+    /// Only fill empty cells, leaving solid voxels untouched
+    FillEmpty,
+    /// Only overwrite solid voxels, leaving empty cells untouched. Combine with [`Voxel::EMPTY`] to erode terrain.
+    ErodeSolid,
+}
+
+/// A brush for sculpting voxels with [`crate::ModifyVoxelCommandsExt::paint_voxels`], combining the voxel to paint, a
+/// falloff curve, and a blend mode.
+///
+/// ### Example
+/// ```
+/// # use bevy_vox_scene::{Voxel, VoxelBrush, VoxelBrushBlend, VoxelBrushFalloff};
+/// // add soft-edged mounds of dirt, without overwriting anything already there
+/// let brush = VoxelBrush::new(Voxel(12))
+///     .with_falloff(VoxelBrushFalloff::Smooth)
+///     .with_blend(VoxelBrushBlend::FillEmpty);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelBrush {
+    /// The voxel that the brush paints
+    pub voxel: Voxel,
+    /// How the brush fades towards the edge of the region
+    pub falloff: VoxelBrushFalloff,
+    /// Which existing voxels the brush overwrites
+    pub blend: VoxelBrushBlend,
+    /// Seeds the placement of voxels where the brush is weaker than full strength. The same seed always produces the
+    /// same pattern.
+    pub seed: u64,
+}
+
+impl VoxelBrush {
+    /// Creates a brush that replaces every voxel in the region with `voxel`
+    pub fn new(voxel: Voxel) -> Self {
+        Self {
+            voxel,
+            falloff: VoxelBrushFalloff::default(),
+            blend: VoxelBrushBlend::default(),
+            seed: 0,
+        }
+    }
+
+    /// Creates a brush that removes solid voxels
+    pub fn eraser() -> Self {
+        Self::new(Voxel::EMPTY).with_blend(VoxelBrushBlend::ErodeSolid)
+    }
+
+    /// Sets the falloff of the brush
+    pub fn with_falloff(mut self, falloff: VoxelBrushFalloff) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    /// Sets the blend mode of the brush
+    pub fn with_blend(mut self, blend: VoxelBrushBlend) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Sets the seed used for probabilistic placement
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Returns the voxel after applying the brush to the `current` voxel at `position`, where `distance` is the
+    /// normalized distance from the center of the region, 1 being the edge.
+    pub fn paint(&self, position: IVec3, current: &Voxel, distance: f32) -> Voxel {
+        let allowed = match self.blend {
+            VoxelBrushBlend::Replace => true,
+            VoxelBrushBlend::FillEmpty => *current == Voxel::EMPTY,
+            VoxelBrushBlend::ErodeSolid => *current != Voxel::EMPTY,
+        };
+        if !allowed {
+            return current.clone();
+        }
+        let weight = self.falloff.weight(distance);
+        // seeded by position, so that the pattern doesn't depend on the order voxels are visited in
+        let chance = if weight >= 1.0 {
+            0.0
+        } else {
+            let hash = (position.x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+                ^ (position.y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+                ^ (position.z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+            VoxelRng::from_seed(self.seed ^ hash).next_f32()
+        };
+        if chance < weight {
+            self.voxel.clone()
+        } else {
+            current.clone()
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use bevy::{
+    asset::{AssetId, Assets},
+    ecs::{
+        system::{Commands, Resource},
+        world::{Command, World},
+    },
+    math::{IVec3, Quat, Vec3},
+};
+
+use crate::VoxelModelInstance;
+
+use super::{
+    modify::{ModifyVoxelModel, VoxelRegion, VoxelRegionMode},
+    RawVoxel, Voxel, VoxelContext, VoxelData, VoxelElement, VoxelModel, VoxelPalette,
+    VoxelQueryable,
+};
+
+/// A box of voxels copied from a model, along with the palette elements they refer to, so that they can be pasted into
+/// models that use a different [`VoxelPalette`].
+///
+/// The most recent copy made with [`VoxelClipboardCommandsExt::copy_voxel_region`] is stored as a resource.
+#[derive(Resource, Clone, Debug)]
+pub struct VoxelClipboard {
+    size: IVec3,
+    voxels: Vec<Voxel>,
+    elements: Vec<VoxelElement>,
+    context: AssetId<VoxelContext>,
+}
+
+impl VoxelClipboard {
+    /// Copies the voxels within the `region` of the `model`
+    ///
+    /// ### Arguments
+    /// * `model` - the model to copy from, either a [`VoxelModel`] or its [`crate::VoxelData`]
+    /// * `context` - the id of the model's [`VoxelContext`], used to decide whether palette indices need remapping on paste
+    /// * `palette` - the model's palette
+    /// * `region` - the region of the model to copy
+    pub fn copy(
+        model: &impl VoxelQueryable,
+        context: AssetId<VoxelContext>,
+        palette: &VoxelPalette,
+        region: VoxelRegionMode,
+    ) -> Self {
+        let region = region.clamped(model.size());
+        let mut voxels = Vec::with_capacity(region.size.element_product().max(0) as usize);
+        for z in 0..region.size.z {
+            for y in 0..region.size.y {
+                for x in 0..region.size.x {
+                    let voxel = model
+                        .get_voxel_at_point(region.origin + IVec3::new(x, y, z))
+                        .unwrap_or(Voxel::EMPTY);
+                    voxels.push(voxel);
+                }
+            }
+        }
+        Self {
+            size: region.size,
+            voxels,
+            elements: palette.elements.clone(),
+            context,
+        }
+    }
+
+    /// The size of the copied region, in voxels
+    pub fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    /// Returns the [`Voxel`] at the `position` within the clipboard, or `None` if it is out of bounds
+    pub fn get_voxel(&self, position: IVec3) -> Option<&Voxel> {
+        if position.cmplt(IVec3::ZERO).any() || position.cmpge(self.size).any() {
+            return None;
+        }
+        self.voxels.get(self.linearize(position))
+    }
+
+    /// Iterates over the position and value of every voxel in the clipboard
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, &Voxel)> {
+        let size = self.size;
+        self.voxels.iter().enumerate().map(move |(index, voxel)| {
+            let index = index as i32;
+            let position = IVec3::new(
+                index % size.x,
+                (index / size.x) % size.y,
+                index / (size.x * size.y),
+            );
+            (position, voxel)
+        })
+    }
+
+    /// Converts the clipboard into [`VoxelData`] that can be used to create a new [`VoxelModel`]
+    pub fn to_data(&self, voxel_size: f32) -> VoxelData {
+        let mut data = VoxelData::new(self.size.max(IVec3::ZERO).as_uvec3(), true, voxel_size);
+        for (position, voxel) in self.iter() {
+            if *voxel != Voxel::EMPTY {
+                data.set_voxel(voxel.clone(), position.as_uvec3());
+            }
+        }
+        data
+    }
+
+    fn linearize(&self, position: IVec3) -> usize {
+        (position.x + self.size.x * (position.y + self.size.y * position.z)) as usize
+    }
+
+    fn rotated_size(&self, rotation: Quat) -> IVec3 {
+        (rotation * self.size.as_vec3()).round().abs().as_ivec3()
+    }
+
+    /// Returns where the `point` within the clipboard ends up once the clipboard has been [`VoxelClipboard::rotated`]
+    pub fn rotate_point(&self, point: IVec3, rotation: Quat) -> IVec3 {
+        let size = self.rotated_size(rotation);
+        let centered = point.as_vec3() + Vec3::splat(0.5) - self.size.as_vec3() * 0.5;
+        ((rotation * centered) + size.as_vec3() * 0.5 - Vec3::splat(0.5))
+            .round()
+            .as_ivec3()
+    }
+
+    /// Returns a copy of the clipboard rotated by `rotation` around its center. The rotation is snapped to the voxel
+    /// grid, so only multiples of 90 degrees around each axis are meaningful.
+    pub fn rotated(&self, rotation: Quat) -> Self {
+        let size = self.rotated_size(rotation);
+        let mut voxels = vec![Voxel::EMPTY; size.element_product().max(0) as usize];
+        let mut rotated = Self {
+            size,
+            voxels: Vec::new(),
+            elements: self.elements.clone(),
+            context: self.context,
+        };
+        for z in 0..self.size.z {
+            for y in 0..self.size.y {
+                for x in 0..self.size.x {
+                    let source = IVec3::new(x, y, z);
+                    let destination = self
+                        .rotate_point(source, rotation)
+                        .clamp(IVec3::ZERO, size - IVec3::ONE);
+                    voxels[rotated.linearize(destination)] =
+                        self.voxels[self.linearize(source)].clone();
+                }
+            }
+        }
+        rotated.voxels = voxels;
+        rotated
+    }
+
+    /// Returns a copy of the clipboard with every voxel mapped to the closest matching element in `palette`.
+    pub fn remapped(&self, context: AssetId<VoxelContext>, palette: &VoxelPalette) -> Self {
+        if context == self.context {
+            return self.clone();
+        }
+        let mut mapping: Vec<Option<Voxel>> = vec![None; self.elements.len()];
+        let voxels = self
+            .voxels
+            .iter()
+            .map(|voxel| {
+                if *voxel == Voxel::EMPTY {
+                    return Voxel::EMPTY;
+                }
+                let raw_index = RawVoxel::from(voxel.clone()).0 as usize;
+                mapping[raw_index]
+                    .get_or_insert_with(|| palette.closest_voxel(&self.elements[raw_index]))
+                    .clone()
+            })
+            .collect();
+        Self {
+            size: self.size,
+            voxels,
+            elements: palette.elements.clone(),
+            context,
+        }
+    }
+}
+
+/// Commands for copying and pasting regions of voxels between models
+pub trait VoxelClipboardCommandsExt {
+    /// Copies the `region` of the `model` into the [`VoxelClipboard`] resource.
+    fn copy_voxel_region(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+    ) -> &mut Self;
+
+    /// Copies the `region` of the `model` into the [`VoxelClipboard`] resource, and then empties the region.
+    fn cut_voxel_region(&mut self, model: VoxelModelInstance, region: VoxelRegionMode)
+        -> &mut Self;
+
+    /// Pastes the contents of the [`VoxelClipboard`] resource into the `model`.
+    ///
+    /// ### Arguments
+    /// * `model` - the model to paste into
+    /// * `offset` - the position in the model's voxel space of the lower-back-left corner of the pasted region
+    /// * `rotation` - the rotation applied to the clipboard around its center, snapped to multiples of 90 degrees
+    ///
+    /// ### Notes
+    /// Empty voxels in the clipboard do not overwrite the model. If the model uses a different [`VoxelContext`] to the
+    /// one the voxels were copied from, each voxel is mapped to the closest matching element in the model's palette.
+    fn paste_voxel_clipboard(
+        &mut self,
+        model: VoxelModelInstance,
+        offset: IVec3,
+        rotation: Quat,
+    ) -> &mut Self;
+
+    /// Pastes the supplied `clipboard` into the `model`. See [`VoxelClipboardCommandsExt::paste_voxel_clipboard`].
+    fn paste_voxels(
+        &mut self,
+        model: VoxelModelInstance,
+        clipboard: VoxelClipboard,
+        offset: IVec3,
+        rotation: Quat,
+    ) -> &mut Self;
+}
+
+impl VoxelClipboardCommandsExt for Commands<'_, '_> {
+    fn copy_voxel_region(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+    ) -> &mut Self {
+        self.add(CopyVoxelRegion {
+            instance: model,
+            region,
+            cut: false,
+        });
+        self
+    }
+
+    fn cut_voxel_region(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+    ) -> &mut Self {
+        self.add(CopyVoxelRegion {
+            instance: model,
+            region,
+            cut: true,
+        });
+        self
+    }
+
+    fn paste_voxel_clipboard(
+        &mut self,
+        model: VoxelModelInstance,
+        offset: IVec3,
+        rotation: Quat,
+    ) -> &mut Self {
+        self.add(PasteVoxels {
+            instance: model,
+            clipboard: None,
+            offset,
+            rotation,
+        });
+        self
+    }
+
+    fn paste_voxels(
+        &mut self,
+        model: VoxelModelInstance,
+        clipboard: VoxelClipboard,
+        offset: IVec3,
+        rotation: Quat,
+    ) -> &mut Self {
+        self.add(PasteVoxels {
+            instance: model,
+            clipboard: Some(clipboard),
+            offset,
+            rotation,
+        });
+        self
+    }
+}
+
+struct CopyVoxelRegion {
+    instance: VoxelModelInstance,
+    region: VoxelRegionMode,
+    cut: bool,
+}
+
+impl Command for CopyVoxelRegion {
+    fn apply(self, world: &mut World) {
+        let Some(clipboard) = ({
+            let models = world.resource::<Assets<VoxelModel>>();
+            let contexts = world.resource::<Assets<VoxelContext>>();
+            models
+                .get(self.instance.model.id())
+                .zip(contexts.get(self.instance.context.id()))
+                .map(|(model, context)| {
+                    VoxelClipboard::copy(
+                        model,
+                        self.instance.context.id(),
+                        &context.palette,
+                        self.region,
+                    )
+                })
+        }) else {
+            return;
+        };
+        world.insert_resource(clipboard);
+        if self.cut {
+            ModifyVoxelModel {
+                instance: self.instance,
+                region: self.region,
+                modify: Box::new(|_, _, _| Voxel::EMPTY),
+            }
+            .apply(world);
+        }
+    }
+}
+
+pub(super) struct PasteVoxels {
+    pub(super) instance: VoxelModelInstance,
+    pub(super) clipboard: Option<VoxelClipboard>,
+    pub(super) offset: IVec3,
+    pub(super) rotation: Quat,
+}
+
+impl Command for PasteVoxels {
+    fn apply(self, world: &mut World) {
+        let Some(clipboard) = self
+            .clipboard
+            .or_else(|| world.get_resource::<VoxelClipboard>().cloned())
+        else {
+            return;
+        };
+        let Some(context) = world
+            .resource::<Assets<VoxelContext>>()
+            .get(self.instance.context.id())
+        else {
+            return;
+        };
+        let clipboard = clipboard
+            .rotated(self.rotation)
+            .remapped(self.instance.context.id(), &context.palette);
+        let offset = self.offset;
+        ModifyVoxelModel {
+            instance: self.instance,
+            region: VoxelRegionMode::Box(VoxelRegion {
+                origin: offset,
+                size: clipboard.size(),
+            }),
+            modify: Box::new(move |position, voxel, _| {
+                match clipboard.get_voxel(position - offset) {
+                    Some(pasted) if *pasted != Voxel::EMPTY => pasted.clone(),
+                    _ => voxel.clone(),
+                }
+            }),
+        }
+        .apply(world);
+    }
+}
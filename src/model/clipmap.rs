@@ -0,0 +1,559 @@
+use std::sync::Arc;
+
+use bevy::{
+    asset::{Assets, Handle},
+    core::Name,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::With,
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    hierarchy::{BuildChildren, DespawnRecursiveExt},
+    math::{IVec2, IVec3, UVec3, Vec2, Vec3},
+    pbr::StandardMaterial,
+    prelude::{ReflectComponent, SpatialBundle},
+    reflect::Reflect,
+    render::{camera::Camera, mesh::Mesh},
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+    transform::components::{GlobalTransform, Transform},
+    utils::{HashMap, HashSet},
+};
+
+use crate::{VoxelModelInstance, VoxelQueryable};
+
+use super::{Voxel, VoxelContext, VoxelData, VoxelModel};
+
+/// The height of a column of a heightfield in voxels, and the voxel that the column is made of
+type ColumnSampler = Arc<dyn Fn(IVec2) -> (i32, Voxel) + Send + Sync>;
+
+/// Renders a voxel heightfield with a level of detail that decreases with the distance from the camera, so that
+/// kilometer-scale landscapes stay renderable. Add it to an entity with a [`bevy::prelude::SpatialBundle`].
+///
+/// The terrain is divided into square tiles of `tile_size` × `tile_size` columns. Level 0 tiles sample every column of
+/// the heightfield, and each further level doubles the width of its tiles and of their voxels. The tiles form a
+/// quadtree: the `rings` of coarsest tiles around each camera are split into four finer tiles while the camera is
+/// within `detail` tile widths of them, down to level 0, so that the terrain is drawn as rings of decreasing
+/// resolution. Tiles are meshed on the [`AsyncComputeTaskPool`], and the previous tiles are kept until the new tiles
+/// covering the same area are ready, so that the terrain never has holes, while areas whose tiles are ready don't wait
+/// for the rest. Where tiles of different levels meet, the edges of both tiles are meshed as walls reaching below the
+/// surface of their neighbors, hiding the seams.
+///
+/// Tiles are spawned as children of the entity, with a [`VoxelClipmapTile`] component. They aren't part of a
+/// [`crate::VoxelWorld`], so aren't editable, and the heightfield is only sampled as tiles are built.
+#[derive(Component, Clone)]
+pub struct VoxelClipmap {
+    /// The number of levels of detail. Defaults to 6.
+    pub levels: u32,
+    /// The number of columns along each side of a tile. Defaults to 32.
+    pub tile_size: u32,
+    /// The number of coarsest tiles on each side of the camera's tile, so that the terrain extends
+    /// `(rings + 0.5) × tile_size × 2^(levels - 1)` voxels from the camera. Defaults to 2.
+    pub rings: u32,
+    /// How close the camera has to be to a tile, in widths of the tile, for it to be split into finer tiles. Defaults
+    /// to 1.5.
+    pub detail: f32,
+    /// The maximum number of tiles being meshed at once. Defaults to 8.
+    pub max_tasks: usize,
+    context: Handle<VoxelContext>,
+    voxel_size: f32,
+    sampler: ColumnSampler,
+}
+
+impl VoxelClipmap {
+    /// Creates a clipmap of the heightfield sampled by `sampler`, which returns the height in voxels of the column at
+    /// each position and the voxel that the column is made of. Voxels are `voxel_size` wide at level 0.
+    pub fn new<F: Fn(IVec2) -> (i32, Voxel) + Send + Sync + 'static>(
+        context: Handle<VoxelContext>,
+        voxel_size: f32,
+        sampler: F,
+    ) -> Self {
+        Self {
+            levels: 6,
+            tile_size: 32,
+            rings: 2,
+            detail: 1.5,
+            max_tasks: 8,
+            context,
+            voxel_size,
+            sampler: Arc::new(sampler),
+        }
+    }
+
+    /// Creates a clipmap of the top surface of `data`, such as a terrain loaded from a `.vox` file. Columns beyond the
+    /// edges of the data repeat the columns at the edges.
+    pub fn from_data(context: Handle<VoxelContext>, data: &VoxelData) -> Self {
+        let size = data.size().max(IVec3::ONE);
+        let columns: Vec<(i32, Voxel)> = (0..size.z)
+            .flat_map(|z| (0..size.x).map(move |x| (x, z)))
+            .map(|(x, z)| {
+                (0..size.y)
+                    .rev()
+                    .find_map(|y| {
+                        data.get_voxel_at_point(IVec3::new(x, y, z))
+                            .ok()
+                            .filter(|voxel| *voxel != Voxel::EMPTY)
+                            .map(|voxel| (y + 1, voxel))
+                    })
+                    .unwrap_or((0, Voxel::EMPTY))
+            })
+            .collect();
+        let width = size.x;
+        let depth = size.z;
+        Self::new(context, data.voxel_size, move |column| {
+            let x = column.x.clamp(0, width - 1);
+            let z = column.y.clamp(0, depth - 1);
+            columns[(z * width + x) as usize].clone()
+        })
+    }
+
+    /// Sets the number of levels of detail
+    pub fn with_levels(mut self, levels: u32) -> Self {
+        self.levels = levels.max(1);
+        self
+    }
+
+    /// Sets the number of columns along each side of a tile
+    pub fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size.max(1);
+        self
+    }
+
+    /// Sets the number of coarsest tiles on each side of the camera's tile
+    pub fn with_rings(mut self, rings: u32) -> Self {
+        self.rings = rings;
+        self
+    }
+
+    /// Sets how close the camera has to be to a tile, in widths of the tile, for it to be split into finer tiles
+    pub fn with_detail(mut self, detail: f32) -> Self {
+        self.detail = detail;
+        self
+    }
+
+    /// The width in columns of the tiles of `level`
+    fn tile_width(&self, level: u32) -> i32 {
+        (self.tile_size << level) as i32
+    }
+
+    /// The minimum and exclusive maximum columns of a tile
+    fn tile_bounds(&self, level: u32, coord: IVec2) -> (IVec2, IVec2) {
+        let min = coord * self.tile_width(level);
+        (min, min + IVec2::splat(self.tile_width(level)))
+    }
+
+    /// For each side of a tile, in the order of [`NEIGHBORS`], a mask of the levels of the other `selected` tiles that
+    /// the side touches
+    fn skirts(&self, selected: &HashSet<(u32, IVec2)>, level: u32, coord: IVec2) -> [u32; 4] {
+        let (min, max) = self.tile_bounds(level, coord);
+        let mut skirts = [0; 4];
+        for (other_level, other_coord) in selected.iter().filter(|(other, _)| *other != level) {
+            let (other_min, other_max) = self.tile_bounds(*other_level, *other_coord);
+            let overlaps_x = other_min.x < max.x && other_max.x > min.x;
+            let overlaps_z = other_min.y < max.y && other_max.y > min.y;
+            let touching = [
+                overlaps_z && other_max.x == min.x,
+                overlaps_z && other_min.x == max.x,
+                overlaps_x && other_max.y == min.y,
+                overlaps_x && other_min.y == max.y,
+            ];
+            for (skirt, touching) in skirts.iter_mut().zip(touching) {
+                if touching {
+                    *skirt |= 1 << other_level;
+                }
+            }
+        }
+        skirts
+    }
+
+    /// The tiles to draw for cameras at the supplied positions, in columns relative to the clipmap
+    fn select_tiles(&self, cameras: &[Vec2]) -> HashSet<(u32, IVec2)> {
+        let top = self.levels.max(1) - 1;
+        let top_width = self.tile_width(top);
+        let mut pending: Vec<(u32, IVec2)> = Vec::new();
+        for camera in cameras {
+            let center = (*camera / top_width as f32).floor().as_ivec2();
+            let rings = self.rings as i32;
+            for z in -rings..=rings {
+                for x in -rings..=rings {
+                    pending.push((top, center + IVec2::new(x, z)));
+                }
+            }
+        }
+        let mut selected: HashSet<(u32, IVec2)> = HashSet::new();
+        while let Some((level, coord)) = pending.pop() {
+            if selected.contains(&(level, coord)) {
+                continue;
+            }
+            let width = self.tile_width(level) as f32;
+            let min = coord.as_vec2() * width;
+            let near = cameras.iter().any(|camera| {
+                camera
+                    .clamp(min, min + Vec2::splat(width))
+                    .distance(*camera)
+                    < self.detail * width
+            });
+            if level > 0 && near {
+                for child in [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE] {
+                    pending.push((level - 1, coord * 2 + child));
+                }
+            } else {
+                selected.insert((level, coord));
+            }
+        }
+        selected
+    }
+}
+
+/// A tile of a [`VoxelClipmap`], spawned as a child of the clipmap's entity
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct VoxelClipmapTile {
+    /// The level of detail of the tile, from 0 for the finest tiles
+    pub level: u32,
+    /// The coordinate of the tile among the tiles of its level
+    pub coord: IVec2,
+}
+
+/// A tile, and which of its neighbors of the same level are drawn, in the order -X, +X, -Z, +Z
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct TileKey {
+    level: u32,
+    coord: IVec2,
+    neighbors: [bool; 4],
+    /// For each side, a mask of the levels of the tiles of other levels along the side, whose surface the wall on that
+    /// side has to reach below
+    skirts: [u32; 4],
+}
+
+impl TileKey {
+    /// Whether the tile covers the `other` tile, which is either the same tile or one of its descendants in the
+    /// quadtree
+    fn covers(&self, other: &TileKey) -> bool {
+        self.level >= other.level && other.coord >> (self.level - other.level) == self.coord
+    }
+}
+
+const NEIGHBORS: [IVec2; 4] = [IVec2::NEG_X, IVec2::X, IVec2::NEG_Y, IVec2::Y];
+
+/// The voxels and mesh of a tile, and its translation relative to the clipmap
+type TileTask = Task<(VoxelData, Mesh, Option<f32>, Vec3)>;
+
+/// The tiles of each clipmap that are drawn or being meshed
+#[derive(Default)]
+pub(crate) struct ClipmapTiles {
+    spawned: HashMap<(Entity, TileKey), Option<Entity>>,
+    tasks: HashMap<(Entity, TileKey), TileTask>,
+    ready: HashMap<(Entity, TileKey), (VoxelData, Mesh, Option<f32>, Vec3)>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_voxel_clipmaps(
+    mut commands: Commands,
+    mut tiles: Local<ClipmapTiles>,
+    clipmaps: Query<(Entity, &VoxelClipmap, &GlobalTransform)>,
+    cameras: Query<&GlobalTransform, With<Camera>>,
+    mut models: ResMut<Assets<VoxelModel>>,
+    contexts: Res<Assets<VoxelContext>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let ClipmapTiles {
+        spawned,
+        tasks,
+        ready,
+    } = &mut *tiles;
+
+    // collect the tiles that have been meshed
+    tasks.retain(|key, task| match block_on(future::poll_once(task)) {
+        Some(result) => {
+            ready.insert(*key, result);
+            false
+        }
+        None => true,
+    });
+
+    // forget the tiles of despawned clipmaps
+    let live: HashSet<Entity> = clipmaps.iter().map(|(entity, _, _)| entity).collect();
+    tasks.retain(|(clipmap, _), _| live.contains(clipmap));
+    ready.retain(|(clipmap, _), _| live.contains(clipmap));
+    spawned.retain(|(clipmap, _), _| live.contains(clipmap));
+
+    for (entity, clipmap, xform) in clipmaps.iter() {
+        let Some(context) = contexts.get(&clipmap.context) else {
+            continue;
+        };
+        let inverse = xform.affine().inverse();
+        let camera_columns: Vec<Vec2> = cameras
+            .iter()
+            .map(|camera| {
+                let local = inverse.transform_point3(camera.translation()) / clipmap.voxel_size;
+                Vec2::new(local.x, local.z)
+            })
+            .collect();
+        if camera_columns.is_empty() {
+            continue;
+        }
+        let selected = clipmap.select_tiles(&camera_columns);
+        let wanted: HashSet<TileKey> = selected
+            .iter()
+            .map(|(level, coord)| TileKey {
+                level: *level,
+                coord: *coord,
+                neighbors: NEIGHBORS.map(|offset| selected.contains(&(*level, *coord + offset))),
+                skirts: clipmap.skirts(&selected, *level, *coord),
+            })
+            .collect();
+        // tiles that are no longer wanted are abandoned, unless they are drawn until their replacements are ready
+        tasks.retain(|(owner, key), _| *owner != entity || wanted.contains(key));
+        ready.retain(|(owner, key), _| *owner != entity || wanted.contains(key));
+
+        // mesh the missing tiles, nearest first
+        let mut missing: Vec<(f32, TileKey)> = wanted
+            .iter()
+            .filter(|key| {
+                let id = (entity, **key);
+                !spawned.contains_key(&id) && !tasks.contains_key(&id) && !ready.contains_key(&id)
+            })
+            .map(|key| {
+                let width = clipmap.tile_width(key.level) as f32;
+                let center = (key.coord.as_vec2() + 0.5) * width;
+                let distance = camera_columns
+                    .iter()
+                    .map(|camera| camera.distance(center))
+                    .fold(f32::MAX, f32::min);
+                (distance, *key)
+            })
+            .collect();
+        missing.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let in_flight = tasks.keys().filter(|(owner, _)| *owner == entity).count();
+        for (_, key) in missing
+            .into_iter()
+            .take(clipmap.max_tasks.saturating_sub(in_flight))
+        {
+            let sampler = clipmap.sampler.clone();
+            let palette = context.palette.clone();
+            let (tile_size, voxel_size) = (clipmap.tile_size, clipmap.voxel_size);
+            let task = AsyncComputeTaskPool::get().spawn(async move {
+                let (data, translation) = tile_data(&sampler, key, tile_size, voxel_size);
+                let (mesh, average_ior) = data.remesh(&palette);
+                (data, mesh, average_ior, translation)
+            });
+            tasks.insert((entity, key), task);
+        }
+
+        // swap the old tiles of each area for the new ones once all of the new ones are ready, so that the terrain
+        // never has holes. As the tiles form a quadtree, the old and new tiles overlapping each other all lie within
+        // the largest of them, which the area is named after.
+        let old: Vec<TileKey> = spawned
+            .keys()
+            .filter(|(owner, key)| *owner == entity && !wanted.contains(key))
+            .map(|(_, key)| *key)
+            .collect();
+        let new: Vec<TileKey> = wanted
+            .iter()
+            .filter(|key| !spawned.contains_key(&(entity, **key)))
+            .copied()
+            .collect();
+        let area = |key: &TileKey| {
+            old.iter()
+                .chain(new.iter())
+                .filter(|other| other.covers(key))
+                .map(|other| (other.level, other.coord))
+                .max_by_key(|(level, _)| *level)
+                .unwrap_or((key.level, key.coord))
+        };
+        let mut areas: HashMap<(u32, IVec2), (Vec<TileKey>, Vec<TileKey>)> = HashMap::new();
+        for key in old.iter() {
+            areas.entry(area(key)).or_default().0.push(*key);
+        }
+        for key in new.iter() {
+            areas.entry(area(key)).or_default().1.push(*key);
+        }
+        for (old_tiles, new_tiles) in areas.into_values() {
+            if !new_tiles
+                .iter()
+                .all(|key| ready.contains_key(&(entity, *key)))
+            {
+                continue;
+            }
+            for key in new_tiles {
+                let Some((data, mesh, average_ior, translation)) = ready.remove(&(entity, key))
+                else {
+                    continue;
+                };
+                let tile = (data.count_voxels() > 0)
+                    .then(|| {
+                        spawn_tile(
+                            &mut commands,
+                            entity,
+                            key,
+                            data,
+                            mesh,
+                            average_ior,
+                            translation,
+                            (context, &clipmap.context),
+                            (&mut models, &mut meshes, &mut materials),
+                        )
+                    })
+                    .flatten();
+                spawned.insert((entity, key), tile);
+            }
+            for key in old_tiles {
+                if let Some(Some(tile)) = spawned.remove(&(entity, key)) {
+                    commands.entity(tile).despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+/// Samples the columns of the tile into voxel data, returning the data and the translation of its center relative to
+/// the clipmap.
+///
+/// The data has a border of one column sampled from the neighboring tiles, which isn't meshed, so that the faces
+/// between tiles of the same level are culled. The border is left empty next to tiles of other levels, so that the
+/// edge of the tile is meshed as a wall reaching below the surface of the neighbors' edge columns, hiding the seam
+/// between them.
+fn tile_data(
+    sampler: &ColumnSampler,
+    key: TileKey,
+    tile_size: u32,
+    voxel_size: f32,
+) -> (VoxelData, Vec3) {
+    let scale = 1 << key.level;
+    let size = tile_size as i32 + 2;
+    let origin = key.coord * tile_size as i32 * scale - IVec2::splat(scale);
+    let columns: Vec<(i32, Voxel)> = (0..size)
+        .flat_map(|z| (0..size).map(move |x| IVec2::new(x, z)))
+        .map(|column| {
+            let (height, voxel) = sampler(origin + column * scale + IVec2::splat(scale / 2));
+            (height.div_euclid(scale), voxel)
+        })
+        .collect();
+    let is_drawn = |column: IVec2| {
+        let interior = column.cmpge(IVec2::ONE).all() && column.cmplt(IVec2::splat(size - 1)).all();
+        let side = match (column.x, column.y) {
+            (0, z) if z > 0 && z < size - 1 => Some(0),
+            (x, 0) if x > 0 && x < size - 1 => Some(2),
+            (x, z) if x == size - 1 && z > 0 && z < size - 1 => Some(1),
+            (x, z) if z == size - 1 && x > 0 && x < size - 1 => Some(3),
+            _ => None,
+        };
+        interior || side.is_some_and(|side| key.neighbors[side])
+    };
+    // the walls next to tiles of other levels reach below the columns of those tiles along the edge, which are sampled
+    // at their own scale, so may be lower than any column of this tile
+    let min = key.coord * tile_size as i32 * scale;
+    let max = min + IVec2::splat(tile_size as i32 * scale);
+    let skirt = (0..4)
+        .flat_map(|side| {
+            (0..u32::BITS)
+                .filter(move |level| key.skirts[side] & (1 << level) != 0)
+                .flat_map(move |level| edge_columns(min, max, side, 1 << level))
+        })
+        .map(|column| sampler(column).0.div_euclid(scale));
+    // one layer below the lowest column, which isn't meshed, and one layer of air above the highest
+    let bottom = columns
+        .iter()
+        .map(|(height, _)| *height)
+        .chain(skirt)
+        .min()
+        .unwrap_or(0)
+        - 2;
+    let top = columns.iter().map(|(height, _)| *height).max().unwrap_or(0) + 1;
+    let mut data = VoxelData::new(
+        UVec3::new(size as u32, (top - bottom) as u32, size as u32),
+        false,
+        voxel_size * scale as f32,
+    );
+    for z in 0..size {
+        for x in 0..size {
+            let column = IVec2::new(x, z);
+            let (height, voxel) = &columns[(z * size + x) as usize];
+            if !is_drawn(column) || *voxel == Voxel::EMPTY {
+                continue;
+            }
+            for y in 0..(height - bottom) {
+                data.set_voxel(voxel.clone(), UVec3::new(x as u32, y as u32, z as u32));
+            }
+        }
+    }
+    let extent = Vec3::new(size as f32, (top - bottom) as f32, size as f32) * 0.5;
+    let translation = (Vec3::new(origin.x as f32, (bottom * scale) as f32, origin.y as f32)
+        + extent * scale as f32)
+        * voxel_size;
+    (data, translation)
+}
+
+/// The columns sampled by the tiles of `scale` along the outside of `side` of the area from `min` to `max`, in the order
+/// of [`NEIGHBORS`]
+fn edge_columns(min: IVec2, max: IVec2, side: usize, scale: i32) -> impl Iterator<Item = IVec2> {
+    let (along_min, along_max) = if side < 2 {
+        (min.y, max.y)
+    } else {
+        (min.x, max.x)
+    };
+    let across = match side {
+        0 => min.x.div_euclid(scale) - 1,
+        1 => max.x.div_euclid(scale),
+        2 => min.y.div_euclid(scale) - 1,
+        _ => max.y.div_euclid(scale),
+    };
+    (along_min.div_euclid(scale)..=(along_max - 1).div_euclid(scale)).map(move |along| {
+        let cell = if side < 2 {
+            IVec2::new(across, along)
+        } else {
+            IVec2::new(along, across)
+        };
+        cell * scale + IVec2::splat(scale / 2)
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_tile(
+    commands: &mut Commands,
+    clipmap: Entity,
+    key: TileKey,
+    data: VoxelData,
+    mesh: Mesh,
+    average_ior: Option<f32>,
+    translation: Vec3,
+    (context, context_handle): (&VoxelContext, &Handle<VoxelContext>),
+    (models, meshes, materials): (
+        &mut Assets<VoxelModel>,
+        &mut Assets<Mesh>,
+        &mut Assets<StandardMaterial>,
+    ),
+) -> Option<Entity> {
+    let material = context.material_for(average_ior, &data, materials)?;
+    let name = format!("clipmap tile {} {} {}", key.level, key.coord.x, key.coord.y);
+    let mesh = meshes.add(mesh);
+    let model = models.add(VoxelModel {
+        name: name.clone(),
+        data,
+        mesh: mesh.clone(),
+        material: material.clone(),
+        has_translucency: average_ior.is_some(),
+        mesh_pending: false,
+        brick_map: None,
+    });
+    let tile = commands
+        .spawn((
+            VoxelModelInstance {
+                model,
+                context: context_handle.clone(),
+            },
+            VoxelClipmapTile {
+                level: key.level,
+                coord: key.coord,
+            },
+            Name::new(name),
+            mesh,
+            material,
+            SpatialBundle::from_transform(Transform::from_translation(translation)),
+        ))
+        .id();
+    commands.entity(clipmap).add_child(tile);
+    Some(tile)
+}
@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use bevy::math::{bounding::Aabb3d, IVec3, UVec3, Vec3};
+use ndshape::Shape;
+use serde::{Deserialize, Serialize};
+
+use super::{RawVoxel, Voxel, VoxelData, VoxelGrid, VoxelModel};
+
+/// Chooses which voxels generate colliders, and the collision groups of their colliders, by palette index, so that
+/// foliage, decals and other decoration don't block movement even though they are part of a model.
+///
+/// Voxels of excluded indices generate no colliders. The others belong to the collision groups assigned to their
+/// index, or to `default_groups`. The groups are a bit mask, to be passed on to the physics engine.
+/// ```
+/// # use bevy_vox_scene::{ColliderFilter, Voxel};
+/// let filter = ColliderFilter::default()
+///     .with_excluded(Voxel(12))
+///     .with_groups(Voxel(30), 0b10);
+/// assert!(!filter.collides(&Voxel(12)));
+/// assert_eq!(filter.groups(&Voxel(30)), Some(0b10));
+/// assert_eq!(filter.groups(&Voxel(1)), Some(1));
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct ColliderFilter {
+    /// The palette indices whose voxels generate no colliders
+    pub excluded: BTreeSet<u8>,
+    /// The collision groups of the voxels of each palette index
+    pub groups: BTreeMap<u8, u32>,
+    /// The collision groups of the voxels of indices without groups of their own. Defaults to 1.
+    pub default_groups: u32,
+}
+
+impl Default for ColliderFilter {
+    fn default() -> Self {
+        Self {
+            excluded: BTreeSet::new(),
+            groups: BTreeMap::new(),
+            default_groups: 1,
+        }
+    }
+}
+
+impl ColliderFilter {
+    /// Stops the voxels of the palette index of `voxel` from generating colliders
+    pub fn with_excluded(mut self, voxel: Voxel) -> Self {
+        self.excluded.insert(voxel.0);
+        self
+    }
+
+    /// Assigns the voxels of the palette index of `voxel` to the collision `groups`
+    pub fn with_groups(mut self, voxel: Voxel, groups: u32) -> Self {
+        self.groups.insert(voxel.0, groups);
+        self
+    }
+
+    /// Whether `voxel` generates colliders
+    pub fn collides(&self, voxel: &Voxel) -> bool {
+        *voxel != Voxel::EMPTY && !self.excluded.contains(&voxel.0)
+    }
+
+    /// The collision groups of `voxel`, or `None` if it generates no colliders
+    pub fn groups(&self, voxel: &Voxel) -> Option<u32> {
+        self.collides(voxel).then(|| {
+            self.groups
+                .get(&voxel.0)
+                .copied()
+                .unwrap_or(self.default_groups)
+        })
+    }
+}
+
+/// A box of voxels of a model that share the same collision groups, from which a physics engine collider can be built
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoxelColliderBox {
+    /// The bounds of the box, in the local space of the model
+    pub aabb: Aabb3d,
+    /// The collision groups of the voxels in the box, from the model's [`ColliderFilter`]
+    pub groups: u32,
+}
+
+impl VoxelData {
+    /// Sets the [`ColliderFilter`] used when colliders are generated from this data
+    pub fn with_collider_filter(mut self, collider_filter: ColliderFilter) -> Self {
+        self.collider_filter = collider_filter;
+        self
+    }
+
+    /// Whether the voxel at `position`, in voxel space, generates colliders. Positions outside of the model don't.
+    pub fn collides_at(&self, position: IVec3) -> bool {
+        self.collider_groups_at(position).is_some()
+    }
+
+    fn collider_groups_at(&self, position: IVec3) -> Option<u32> {
+        if position.cmplt(IVec3::ZERO).any() || position.cmpge(self._size()).any() {
+            return None;
+        }
+        let padded = position.as_uvec3() + UVec3::splat(self.padding() / 2);
+        let raw = self.voxel_at_index(self.shape.linearize(padded.into()) as usize);
+        // water is drawn as a surface rather than as voxels, and is never solid
+        if *raw == RawVoxel::EMPTY || self.water.as_ref() == Some(raw) {
+            return None;
+        }
+        self.collider_filter.groups(&raw.clone().into())
+    }
+
+    /// Covers the voxels that generate colliders with as few boxes as possible, each of which contains voxels of a
+    /// single set of collision groups, in the local space of the model. Build a compound collider for the physics engine
+    /// of your choice from the boxes, and add it with [`crate::VoxelModelInstanceBuilder::with_collider`].
+    pub fn collider_boxes(&self) -> Vec<VoxelColliderBox> {
+        let size = self._size();
+        let grid = VoxelGrid::new(self.voxel_size, size.as_vec3() * self.voxel_size * -0.5);
+        let index =
+            |position: IVec3| (position.x + size.x * (position.y + size.y * position.z)) as usize;
+        let mut covered = vec![false; size.element_product().max(0) as usize];
+        let mut boxes = Vec::new();
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let min = IVec3::new(x, y, z);
+                    if covered[index(min)] {
+                        continue;
+                    }
+                    let Some(groups) = self.collider_groups_at(min) else {
+                        continue;
+                    };
+                    let fits = |position: IVec3| {
+                        !covered[index(position)]
+                            && self.collider_groups_at(position) == Some(groups)
+                    };
+                    // grow the box along x, then y, then z, for as long as every voxel it gains fits
+                    let mut max = min + IVec3::ONE;
+                    while max.x < size.x && fits(IVec3::new(max.x, y, z)) {
+                        max.x += 1;
+                    }
+                    while max.y < size.y && (min.x..max.x).all(|x| fits(IVec3::new(x, max.y, z))) {
+                        max.y += 1;
+                    }
+                    while max.z < size.z
+                        && (min.y..max.y)
+                            .all(|y| (min.x..max.x).all(|x| fits(IVec3::new(x, y, max.z))))
+                    {
+                        max.z += 1;
+                    }
+                    for cz in min.z..max.z {
+                        for cy in min.y..max.y {
+                            for cx in min.x..max.x {
+                                covered[index(IVec3::new(cx, cy, cz))] = true;
+                            }
+                        }
+                    }
+                    let lower: Vec3 = grid.cell_min_world(min);
+                    let upper: Vec3 = grid.cell_min_world(max);
+                    boxes.push(VoxelColliderBox {
+                        aabb: Aabb3d::new((lower + upper) * 0.5, (upper - lower) * 0.5),
+                        groups,
+                    });
+                }
+            }
+        }
+        boxes
+    }
+}
+
+impl VoxelModel {
+    /// Boxes covering the voxels of the model that generate colliders. See [`VoxelData::collider_boxes`].
+    pub fn collider_boxes(&self) -> Vec<VoxelColliderBox> {
+        self.data.collider_boxes()
+    }
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+impl super::world::VoxelWorld {
+    /// Whether the voxel at the global `position` blocks movement, meaning that it is solid and not excluded by the
+    /// world's [`ColliderFilter`]
+    pub fn collides(&self, position: IVec3) -> bool {
+        self.is_solid(position) && self.collider_filter.collides(&self.get_voxel(position))
+    }
+}
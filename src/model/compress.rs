@@ -0,0 +1,214 @@
+use std::borrow::Cow;
+
+use bevy::math::{IVec3, UVec3};
+use ndshape::Shape;
+
+use super::{RawVoxel, Voxel, VoxelData, VoxelModel};
+
+/// Run-length encodes `values` as the length of each run and the value repeated along it
+pub(super) fn encode_runs<T: Clone + PartialEq>(values: &[T]) -> Vec<(u32, T)> {
+    let mut runs: Vec<(u32, T)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((length, run)) if run == value => *length += 1,
+            _ => runs.push((1, value.clone())),
+        }
+    }
+    runs
+}
+
+/// Expands the runs made by [`encode_runs`]
+pub(super) fn decode_runs<T: Clone>(runs: &[(u32, T)]) -> Vec<T> {
+    let mut values = Vec::with_capacity(runs.iter().map(|(length, _)| *length as usize).sum());
+    for (length, value) in runs {
+        values.extend(std::iter::repeat(value.clone()).take(*length as usize));
+    }
+    values
+}
+
+/// The voxels of a [`VoxelData`] while it is compressed, as runs of identical voxels
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CompressedVoxels {
+    /// The length of each run, and the voxel repeated along it
+    runs: Vec<(u32, RawVoxel)>,
+    /// The exclusive end index of each run, for finding the run that holds a voxel
+    ends: Vec<u32>,
+}
+
+impl CompressedVoxels {
+    fn encode(voxels: &[RawVoxel]) -> Self {
+        let mut runs = encode_runs(voxels);
+        runs.shrink_to_fit();
+        let ends = runs
+            .iter()
+            .scan(0, |end, (length, _)| {
+                *end += length;
+                Some(*end)
+            })
+            .collect();
+        Self { runs, ends }
+    }
+
+    fn decode(&self) -> Vec<RawVoxel> {
+        decode_runs(&self.runs)
+    }
+
+    fn get(&self, index: usize) -> Option<&RawVoxel> {
+        let run = self.ends.partition_point(|end| *end as usize <= index);
+        self.runs.get(run).map(|(_, voxel)| voxel)
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.runs.capacity() * std::mem::size_of::<(u32, RawVoxel)>()
+            + self.ends.capacity() * std::mem::size_of::<u32>()
+    }
+}
+
+/// Reads compressed voxels at increasing indices by stepping through the runs, rather than searching them for each
+/// voxel
+struct RunCursor<'a> {
+    voxels: &'a CompressedVoxels,
+    run: usize,
+}
+
+impl<'a> RunCursor<'a> {
+    fn get(&mut self, index: usize) -> &'a RawVoxel {
+        while self
+            .voxels
+            .ends
+            .get(self.run)
+            .is_some_and(|end| *end as usize <= index)
+        {
+            self.run += 1;
+        }
+        self.voxels
+            .runs
+            .get(self.run)
+            .map_or(&RawVoxel::EMPTY, |(_, voxel)| voxel)
+    }
+}
+
+impl VoxelData {
+    /// Run-length encodes the voxels, freeing the dense array
+    pub(crate) fn compress(&mut self) {
+        if self.compressed.is_none() {
+            self.compressed = Some(CompressedVoxels::encode(&self.voxels));
+            self.voxels = Vec::new();
+        }
+    }
+
+    /// Restores the dense array of voxels, if they are compressed. Must be called before writing to `voxels`.
+    pub(crate) fn decompress(&mut self) {
+        if let Some(compressed) = self.compressed.take() {
+            self.voxels = compressed.decode();
+        }
+    }
+
+    /// A copy of the data with its voxels run-length encoded, made without copying the dense array of voxels. The data
+    /// itself is left unchanged.
+    pub(crate) fn compressed_copy(&mut self) -> VoxelData {
+        if self.compressed.is_some() {
+            return self.clone();
+        }
+        let voxels = std::mem::take(&mut self.voxels);
+        let mut copy = self.clone();
+        copy.compressed = Some(CompressedVoxels::encode(&voxels));
+        self.voxels = voxels;
+        copy
+    }
+
+    /// Whether the voxels are run-length encoded
+    pub(crate) fn is_compressed(&self) -> bool {
+        self.compressed.is_some()
+    }
+
+    /// The voxel at an index into the padded shape, whether or not the voxels are compressed
+    pub(crate) fn raw_voxel(&self, index: usize) -> Option<&RawVoxel> {
+        match &self.compressed {
+            Some(compressed) => compressed.get(index),
+            None => self.voxels.get(index),
+        }
+    }
+
+    /// The voxel at an index into the padded shape, whether or not the voxels are compressed. Panics if the index is
+    /// outside of the shape, like indexing the dense array does.
+    pub(crate) fn voxel_at_index(&self, index: usize) -> &RawVoxel {
+        self.raw_voxel(index)
+            .expect("voxel index within the padded shape")
+    }
+
+    /// Every voxel of the padded shape, decoded into a temporary array if the voxels are compressed
+    pub(crate) fn dense_voxels(&self) -> Cow<[RawVoxel]> {
+        match &self.compressed {
+            Some(compressed) => Cow::Owned(compressed.decode()),
+            None => Cow::Borrowed(&self.voxels),
+        }
+    }
+
+    /// The number of bytes used by the voxels while they are compressed
+    pub(crate) fn compressed_memory_usage(&self) -> usize {
+        self.compressed
+            .as_ref()
+            .map_or(0, |compressed| compressed.memory_usage())
+    }
+
+    /// Calls `f` with the index into the padded shape of every voxel of the model, with x varying fastest, then y,
+    /// then z, so that the indices increase
+    fn for_each_index(&self, mut f: impl FnMut(usize)) {
+        let size = self._size().max(IVec3::ZERO).as_uvec3();
+        let leading_padding = UVec3::splat(self.padding() / 2);
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let point = UVec3::new(x, y, z) + leading_padding;
+                    f(self.shape.linearize(point.into()) as usize);
+                }
+            }
+        }
+    }
+}
+
+impl VoxelModel {
+    /// Run-length encodes the model's voxels in memory, which shrinks large models with big areas of empty or uniform
+    /// voxels many times over, at the cost of slower reads.
+    ///
+    /// A compressed model can still be drawn, remeshed, hashed, counted and read by every API of the crate. Edits made
+    /// through the crate decompress the model first. Use [`VoxelModel::decompress_into`] to read every voxel in hot
+    /// loops without decompressing the model.
+    pub fn compress(&mut self) {
+        self.data.compress();
+    }
+
+    /// Restores the model's voxels to a dense array, after [`VoxelModel::compress`]
+    pub fn decompress(&mut self) {
+        self.data.decompress();
+    }
+
+    /// Whether the model's voxels are compressed with [`VoxelModel::compress`]
+    pub fn is_compressed(&self) -> bool {
+        self.data.is_compressed()
+    }
+
+    /// Copies every voxel of the model into `buffer`, which is cleared first, whether or not the model is compressed.
+    /// The voxels are ordered with x varying fastest, then y, then z, so the voxel at `position` is at index
+    /// `position.x + size.x * (position.y + size.y * position.z)`, where `size` is [`crate::VoxelQueryable::size`].
+    /// Compressed voxels are read straight from their runs, and reusing the same buffer avoids an allocation per call.
+    pub fn decompress_into(&self, buffer: &mut Vec<Voxel>) {
+        buffer.clear();
+        let size = self.data._size().max(IVec3::ZERO).as_uvec3();
+        buffer.reserve((size.x * size.y * size.z) as usize);
+        match &self.data.compressed {
+            Some(compressed) => {
+                let mut cursor = RunCursor {
+                    voxels: compressed,
+                    run: 0,
+                };
+                self.data
+                    .for_each_index(|index| buffer.push(cursor.get(index).clone().into()));
+            }
+            None => self
+                .data
+                .for_each_index(|index| buffer.push(self.data.voxels[index].clone().into())),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use bevy::math::{bounding::Aabb3d, IVec3, Vec3};
+
+use super::VoxelGrid;
+
+/// Moves box-shaped kinematic characters through solid voxels, without a physics engine.
+///
+/// The motion is resolved one axis at a time, vertical first, so that a character stops at the first solid voxel in
+/// its way along each axis and slides along walls and floors with the rest of its motion. When a character standing
+/// on the ground is blocked horizontally by a ledge no higher than `step_height`, it steps up onto the ledge instead.
+///
+/// Use [`crate::VoxelWorld::move_and_slide`] for a world, or [`VoxelCharacterController::move_and_slide`] with a grid
+/// and a solidity test for anything else, such as a model in its local space:
+/// ```ignore
+/// let grid = model.grid();
+/// let result = controller.move_and_slide(&grid, |cell| {
+///     model.get_voxel_at_point(cell).is_ok_and(|voxel| voxel != Voxel::EMPTY)
+/// }, aabb, velocity * time.delta_seconds());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VoxelCharacterController {
+    /// The tallest ledge that the character steps up onto, in the units of the grid. Defaults to 0, which disables
+    /// stepping.
+    pub step_height: f32,
+}
+
+/// The outcome of moving a character with a [`VoxelCharacterController`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VoxelMoveResult {
+    /// The motion that was applied, which is shorter than the requested motion along any blocked axis. Add it to the
+    /// character's translation.
+    pub motion: Vec3,
+    /// True if the character was stopped by the ground while moving down
+    pub grounded: bool,
+    /// True if the character was stopped by a ceiling while moving up
+    pub hit_ceiling: bool,
+    /// True if the character was stopped by a wall while moving horizontally
+    pub hit_wall: bool,
+    /// True if the character stepped up onto a ledge
+    pub stepped: bool,
+}
+
+/// Tolerance in voxels, so that boxes resting against a voxel face aren't treated as overlapping it
+const EPSILON: f32 = 1e-4;
+
+impl VoxelCharacterController {
+    /// Creates a controller that steps up onto ledges up to `step_height` high
+    pub fn new(step_height: f32) -> Self {
+        Self { step_height }
+    }
+
+    /// Moves the box `aabb` by `motion` through the cells of the `grid` for which `is_solid` returns true
+    pub fn move_and_slide(
+        &self,
+        grid: &VoxelGrid,
+        is_solid: impl Fn(IVec3) -> bool,
+        aabb: Aabb3d,
+        motion: Vec3,
+    ) -> VoxelMoveResult {
+        if grid.voxel_size <= 0.0 {
+            return VoxelMoveResult::default();
+        }
+        // work in voxel units, with cell `c` spanning from `c` to `c + 1`
+        let min = (Vec3::from(aabb.min) - grid.origin) / grid.voxel_size;
+        let max = (Vec3::from(aabb.max) - grid.origin) / grid.voxel_size;
+        let motion = motion / grid.voxel_size;
+        let step_height = self.step_height / grid.voxel_size;
+
+        let mut result = VoxelMoveResult::default();
+        let vertical = sweep_axis(&is_solid, min, max, 1, motion.y);
+        result.grounded = motion.y < 0.0 && vertical > motion.y;
+        result.hit_ceiling = motion.y > 0.0 && vertical < motion.y;
+        let offset = Vec3::new(0.0, vertical, 0.0);
+        let (horizontal, blocked) = sweep_horizontal(&is_solid, min + offset, max + offset, motion);
+        let mut applied = offset + horizontal;
+        result.hit_wall = blocked;
+
+        if blocked && result.grounded && step_height > 0.0 {
+            // try stepping up, moving across, and dropping back down onto the ledge
+            let rise = sweep_axis(&is_solid, min + offset, max + offset, 1, step_height);
+            let raised = offset + Vec3::new(0.0, rise, 0.0);
+            let (stepped_horizontal, stepped_blocked) =
+                sweep_horizontal(&is_solid, min + raised, max + raised, motion);
+            let across = raised + stepped_horizontal;
+            let drop = sweep_axis(&is_solid, min + across, max + across, 1, -rise);
+            if stepped_horizontal.length_squared() > horizontal.length_squared() + EPSILON {
+                applied = across + Vec3::new(0.0, drop, 0.0);
+                result.hit_wall = stepped_blocked;
+                result.stepped = applied.y > offset.y + EPSILON;
+            }
+        }
+        result.motion = applied * grid.voxel_size;
+        result
+    }
+}
+
+/// Moves the box along x, then along z, returning the motion and whether either axis was blocked
+fn sweep_horizontal(
+    is_solid: &impl Fn(IVec3) -> bool,
+    min: Vec3,
+    max: Vec3,
+    motion: Vec3,
+) -> (Vec3, bool) {
+    let x = sweep_axis(is_solid, min, max, 0, motion.x);
+    let offset = Vec3::new(x, 0.0, 0.0);
+    let z = sweep_axis(is_solid, min + offset, max + offset, 2, motion.z);
+    (Vec3::new(x, 0.0, z), x != motion.x || z != motion.z)
+}
+
+/// The furthest the box from `min` to `max` can move by `distance` along `axis` before touching a solid cell. Cells
+/// that the box already overlaps don't block it, so that boxes stuck inside voxels can move out of them.
+fn sweep_axis(
+    is_solid: &impl Fn(IVec3) -> bool,
+    min: Vec3,
+    max: Vec3,
+    axis: usize,
+    distance: f32,
+) -> f32 {
+    if distance == 0.0 {
+        return 0.0;
+    }
+    let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+    let lo = (min + EPSILON).floor().as_ivec3();
+    let hi = (max - EPSILON).ceil().as_ivec3() - IVec3::ONE;
+    let slab_is_solid = |layer: i32| {
+        (lo[a]..=hi[a]).any(|i| {
+            (lo[b]..=hi[b]).any(|j| {
+                let mut cell = IVec3::ZERO;
+                cell[axis] = layer;
+                cell[a] = i;
+                cell[b] = j;
+                is_solid(cell)
+            })
+        })
+    };
+    if distance > 0.0 {
+        let mut layer = (max[axis] - EPSILON).ceil() as i32;
+        while (layer as f32) < max[axis] + distance {
+            if slab_is_solid(layer) {
+                return (layer as f32 - max[axis]).clamp(0.0, distance);
+            }
+            layer += 1;
+        }
+    } else {
+        let mut layer = (min[axis] + EPSILON).floor() as i32 - 1;
+        while (layer + 1) as f32 > min[axis] + distance {
+            if slab_is_solid(layer) {
+                return ((layer + 1) as f32 - min[axis]).clamp(distance, 0.0);
+            }
+            layer -= 1;
+        }
+    }
+    distance
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+impl super::world::VoxelWorld {
+    /// Moves the box `aabb`, in global space, by `motion` through the solid voxels of the world with the `controller`.
+    /// Voxels excluded by the world's [`crate::ColliderFilter`] don't block the box. See [`VoxelCharacterController`].
+    pub fn move_and_slide(
+        &self,
+        controller: &VoxelCharacterController,
+        aabb: Aabb3d,
+        motion: Vec3,
+    ) -> VoxelMoveResult {
+        controller.move_and_slide(
+            &VoxelGrid::new(self.voxel_size(), Vec3::ZERO),
+            |cell| self.collides(cell),
+            aabb,
+            motion,
+        )
+    }
+}
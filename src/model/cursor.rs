@@ -0,0 +1,143 @@
+use bevy::{
+    asset::Assets,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::With,
+        system::{Query, Res},
+    },
+    math::{Ray3d, Vec2},
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    render::camera::{Camera, NormalizedRenderTarget},
+    transform::components::GlobalTransform,
+    window::{PrimaryWindow, Window},
+};
+
+use crate::VoxelModelInstance;
+
+use super::{interaction::VoxelTarget, VoxelModel, VoxelQueryable};
+
+/// Finds the voxel under the mouse pointer every frame, for editing tools and debug readouts.
+///
+/// Add it to any entity, naming the `camera` that the pointer is over. Each update, a ray is cast from the pointer
+/// through the camera's viewport, and the nearest voxel it hits of the `instances`, or of every
+/// [`VoxelModelInstance`] if `instances` is empty, becomes the [`VoxelCursor::target`]. The target is `None` while the
+/// pointer is outside of the camera's window or viewport, or isn't over a voxel.
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_vox_scene::VoxelCursor;
+/// fn show_cursor(cursors: Query<&VoxelCursor>) {
+///     for cursor in cursors.iter() {
+///         if let Some(target) = cursor.target.as_ref() {
+///             info!("{:?} at {}", target.voxel, target.voxel_coord);
+///         }
+///     }
+/// }
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VoxelCursor {
+    /// The camera whose viewport the pointer is over
+    pub camera: Entity,
+    /// The entities holding the [`VoxelModelInstance`]s that can be targeted. If empty, which is the default, every
+    /// instance can be.
+    pub instances: Vec<Entity>,
+    /// The furthest distance from the camera at which voxels can be targeted, in global space. Defaults to 1000.
+    pub max_distance: f32,
+    /// The position of the pointer within the camera's viewport, in logical pixels, updated every frame
+    pub pointer_position: Option<Vec2>,
+    /// The voxel under the pointer, updated every frame
+    pub target: Option<VoxelTarget>,
+}
+
+impl VoxelCursor {
+    /// Creates a cursor for the pointer over the viewport of `camera`, targeting every instance
+    pub fn new(camera: Entity) -> Self {
+        Self {
+            camera,
+            instances: Vec::new(),
+            max_distance: 1000.0,
+            pointer_position: None,
+            target: None,
+        }
+    }
+
+    /// Restricts the cursor to the supplied entities holding [`VoxelModelInstance`]s
+    pub fn with_instances(mut self, instances: impl IntoIterator<Item = Entity>) -> Self {
+        self.instances = instances.into_iter().collect();
+        self
+    }
+
+    /// Sets the furthest distance from the camera at which voxels can be targeted
+    pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    /// Targets the nearest voxel hit by the global-space `ray` of any of the `instances` that the cursor accepts
+    pub(crate) fn retarget<'a>(
+        &mut self,
+        ray: Option<Ray3d>,
+        instances: impl Iterator<Item = (Entity, &'a VoxelModelInstance, &'a GlobalTransform)>,
+        models: &Assets<VoxelModel>,
+    ) {
+        let Some(ray) = ray else {
+            self.target = None;
+            return;
+        };
+        self.target = instances
+            .filter(|(entity, _, _)| self.instances.is_empty() || self.instances.contains(entity))
+            .filter_map(|(entity, instance, global_xform)| {
+                let hit =
+                    models
+                        .get(&instance.model)?
+                        .raycast(ray, global_xform, self.max_distance)?;
+                Some((entity, hit))
+            })
+            .min_by(|a, b| a.1.distance.total_cmp(&b.1.distance))
+            .map(|(entity, hit)| VoxelTarget {
+                entity,
+                voxel_coord: hit.voxel_coord,
+                voxel: hit.voxel,
+                normal: hit.normal,
+                point: hit.point,
+            });
+    }
+}
+
+pub(crate) fn update_voxel_cursors(
+    mut cursors: Query<&mut VoxelCursor>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    windows: Query<&Window>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    instances: Query<(Entity, &VoxelModelInstance, &GlobalTransform)>,
+    models: Res<Assets<VoxelModel>>,
+) {
+    let primary_window = primary_window.get_single().ok();
+    for mut cursor in cursors.iter_mut() {
+        let Ok((camera, camera_xform)) = cameras.get(cursor.camera) else {
+            cursor.pointer_position = None;
+            cursor.target = None;
+            continue;
+        };
+        let window = match camera.target.normalize(primary_window) {
+            Some(NormalizedRenderTarget::Window(window)) => windows.get(window.entity()).ok(),
+            _ => None,
+        };
+        // the pointer position relative to the viewport, if it lies within it
+        let pointer_position =
+            window
+                .and_then(|window| window.cursor_position())
+                .and_then(|position| {
+                    let viewport = camera.logical_viewport_rect()?;
+                    viewport
+                        .contains(position)
+                        .then_some(position - viewport.min)
+                });
+        let ray =
+            pointer_position.and_then(|position| camera.viewport_to_world(camera_xform, position));
+        cursor.pointer_position = pointer_position;
+        cursor.retarget(ray, instances.iter(), &models);
+    }
+}
@@ -1,4 +1,5 @@
 use bevy::{
+    log::info_span,
     math::{IVec3, UVec3},
     render::mesh::Mesh,
 };
@@ -6,15 +7,47 @@ use block_mesh::VoxelVisibility;
 use ndshape::{RuntimeShape, Shape};
 use std::fmt::Debug;
 
-use super::{voxel::VisibleVoxel, RawVoxel};
+use crate::hash::StableHasher;
+
+use super::{
+    collider::ColliderFilter,
+    compress::CompressedVoxels,
+    light::VoxelLightLevels,
+    mask::VoxelEditMask,
+    mesh::MeshAttributeConfig,
+    occlusion::DirectionalOcclusion,
+    shape::{VoxelShape, VoxelShapes},
+    tint::VoxelTint,
+    voxel::VisibleVoxel,
+    RawVoxel, VoxelPalette,
+};
 
 /// The voxel data used to create a mesh and a material.
 #[derive(Clone)]
 pub struct VoxelData {
     pub(crate) shape: RuntimeShape<u32, 3>,
     pub(crate) voxels: Vec<RawVoxel>,
+    /// The voxels while they are run-length encoded, in which case `voxels` is empty
+    pub(crate) compressed: Option<CompressedVoxels>,
     pub(crate) mesh_outer_faces: bool,
     pub(crate) voxel_size: f32,
+    pub(crate) generate_tangents: bool,
+    pub(crate) optimize_mesh: bool,
+    pub(crate) attributes: MeshAttributeConfig,
+    pub(crate) directional_occlusion: DirectionalOcclusion,
+    /// The number of voxels of each palette index, kept up to date as voxels are written
+    pub(crate) histogram: Vec<u32>,
+    pub(crate) edit_mask: Option<VoxelEditMask>,
+    /// The bounds of the voxels written since the model's [`crate::VoxelBrickMap`] was last updated, as a minimum and
+    /// exclusive maximum in voxel space
+    pub(crate) dirty_region: Option<(IVec3, IVec3)>,
+    pub(crate) tint: Option<VoxelTint>,
+    /// Light levels baked into the vertex colors, set by [`crate::VoxelWorld`] when it is lit
+    pub(crate) light: Option<VoxelLightLevels>,
+    /// Voxels that are left out of the meshes, as they are drawn as water by [`crate::VoxelWorld`]
+    pub(crate) water: Option<RawVoxel>,
+    pub(crate) shapes: VoxelShapes,
+    pub(crate) collider_filter: ColliderFilter,
 }
 
 impl Default for VoxelData {
@@ -22,8 +55,21 @@ impl Default for VoxelData {
         Self {
             shape: RuntimeShape::<u32, 3>::new([0, 0, 0]),
             voxels: Default::default(),
+            compressed: None,
             mesh_outer_faces: true,
             voxel_size: 1.0,
+            generate_tangents: false,
+            optimize_mesh: false,
+            attributes: MeshAttributeConfig::default(),
+            directional_occlusion: DirectionalOcclusion::default(),
+            histogram: vec![0; RawVoxel::EMPTY.0 as usize],
+            edit_mask: None,
+            dirty_region: None,
+            tint: None,
+            light: None,
+            water: None,
+            shapes: VoxelShapes::default(),
+            collider_filter: ColliderFilter::default(),
         }
     }
 }
@@ -33,7 +79,14 @@ impl Debug for VoxelData {
         f.debug_struct("VoxelData")
             .field("shape", &self.shape.as_array())
             .field("voxels", &self.voxels.len())
+            .field("compressed", &self.is_compressed())
             .field("mesh_outer_faces", &self.mesh_outer_faces)
+            .field("generate_tangents", &self.generate_tangents)
+            .field("optimize_mesh", &self.optimize_mesh)
+            .field("attributes", &self.attributes)
+            .field("directional_occlusion", &self.directional_occlusion)
+            .field("tint", &self.tint)
+            .field("shapes", &self.shapes)
             .finish()
     }
 }
@@ -51,10 +104,75 @@ impl VoxelData {
         Self {
             shape,
             voxels: vec![RawVoxel::EMPTY; size],
+            compressed: None,
             mesh_outer_faces,
             voxel_size,
+            generate_tangents: false,
+            optimize_mesh: false,
+            attributes: MeshAttributeConfig::default(),
+            directional_occlusion: DirectionalOcclusion::default(),
+            histogram: vec![0; RawVoxel::EMPTY.0 as usize],
+            edit_mask: None,
+            dirty_region: None,
+            tint: None,
+            light: None,
+            water: None,
+            shapes: VoxelShapes::default(),
+            collider_filter: ColliderFilter::default(),
         }
     }
+
+    /// Sets whether meshes generated from this data include flat per-face tangents, which are required by normal-mapped
+    /// materials. Defaults to false.
+    pub fn with_tangents(mut self, generate_tangents: bool) -> Self {
+        self.generate_tangents = generate_tangents;
+        self
+    }
+
+    /// Sets whether identical vertices are welded in meshes generated from this data, with the triangles and vertices
+    /// reordered for the GPU's caches. Defaults to false. See [`crate::VoxLoaderSettings::optimize_meshes`].
+    pub fn with_mesh_optimization(mut self, optimize_mesh: bool) -> Self {
+        self.optimize_mesh = optimize_mesh;
+        self
+    }
+
+    /// Sets which vertex attributes are included in meshes generated from this data
+    pub fn with_attributes(mut self, attributes: MeshAttributeConfig) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Sets a [`VoxelTint`] that the color of each vertex is multiplied by when meshes are generated from this data
+    pub fn with_tint(mut self, tint: VoxelTint) -> Self {
+        self.tint = Some(tint);
+        self
+    }
+
+    /// Sets the [`VoxelShapes`] that voxels are drawn as in meshes generated from this data
+    pub fn with_shapes(mut self, shapes: VoxelShapes) -> Self {
+        self.shapes = shapes;
+        self
+    }
+
+    /// Sets the light directions for which a static occlusion term is baked into meshes generated from this data
+    pub fn with_directional_occlusion(
+        mut self,
+        directional_occlusion: DirectionalOcclusion,
+    ) -> Self {
+        self.directional_occlusion = directional_occlusion;
+        self
+    }
+
+    /// Copies the meshing settings of `other`, for data derived from it, such as its LODs, shards and resampled copies
+    pub(crate) fn with_settings_of(self, other: &VoxelData) -> Self {
+        self.with_tangents(other.generate_tangents)
+            .with_mesh_optimization(other.optimize_mesh)
+            .with_shapes(other.shapes.clone())
+            .with_collider_filter(other.collider_filter.clone())
+            .with_attributes(other.attributes)
+            .with_directional_occlusion(other.directional_occlusion.clone())
+    }
+
     /// The size of the voxel model, not including the padding that may have been added if the outer faces are being meshed.
     pub(crate) fn _size(&self) -> IVec3 {
         let raw_size: UVec3 = self.shape.as_array().into();
@@ -71,9 +189,95 @@ impl VoxelData {
         }
     }
 
-    pub(crate) fn remesh(&self, ior_for_voxel: &[Option<f32>]) -> (Mesh, Option<f32>) {
-        let (visible_voxels, average_ior) = self.visible_voxels(ior_for_voxel);
-        (super::mesh::mesh_model(&visible_voxels, self), average_ior)
+    /// Grows the dirty region to include the box from `min` to `max` (exclusive)
+    pub(crate) fn mark_dirty(&mut self, min: IVec3, max: IVec3) {
+        self.dirty_region = Some(match self.dirty_region {
+            Some((dirty_min, dirty_max)) => (dirty_min.min(min), dirty_max.max(max)),
+            None => (min, max),
+        });
+    }
+
+    /// Writes `voxel` at `position`, in voxel space, keeping the histogram up to date
+    pub(crate) fn write_raw(&mut self, position: IVec3, voxel: RawVoxel) {
+        let leading_padding = UVec3::splat(self.padding() / 2);
+        let index = self
+            .shape
+            .linearize((position.as_uvec3() + leading_padding).into()) as usize;
+        self.decompress();
+        VoxelData::record_change(&mut self.histogram, &self.voxels[index], &voxel);
+        self.voxels[index] = voxel;
+    }
+
+    /// The number of bytes used to store the voxels, including any padding
+    pub fn memory_usage(&self) -> usize {
+        self.voxels.capacity() * std::mem::size_of::<RawVoxel>() + self.compressed_memory_usage()
+    }
+
+    /// A hash of the size of the model, its voxel size and the palette index of every voxel, which is independent of
+    /// the padding and the meshing settings
+    pub(crate) fn content_hash(&self) -> u64 {
+        let mut hasher = StableHasher::default();
+        let size = self._size();
+        for component in size.to_array() {
+            hasher.write_u32(component as u32);
+        }
+        hasher.write_f32(self.voxel_size);
+        let leading_padding = UVec3::splat(self.padding() / 2);
+        let voxels = self.dense_voxels();
+        for z in 0..size.z as u32 {
+            for y in 0..size.y as u32 {
+                for x in 0..size.x as u32 {
+                    let index = self
+                        .shape
+                        .linearize((UVec3::new(x, y, z) + leading_padding).into())
+                        as usize;
+                    hasher.write(&[voxels[index].0]);
+                }
+            }
+        }
+        hasher.finish()
+    }
+
+    pub(crate) fn remesh(&self, palette: &VoxelPalette) -> (Mesh, Option<f32>) {
+        let _span = info_span!(
+            "voxel_mesh",
+            voxels = self.count_voxels(),
+            size = ?self._size()
+        )
+        .entered();
+        if self.is_compressed() {
+            // shaped voxels and occlusion read the dense voxels directly
+            let mut dense = self.clone();
+            dense.decompress();
+            return dense.remesh(palette);
+        }
+        let (visible_voxels, average_ior) = self.visible_voxels(&palette.indices_of_refraction);
+        (
+            super::mesh::mesh_model(&visible_voxels, self, palette),
+            average_ior,
+        )
+    }
+
+    /// Returns the [`VoxelVisibility`] of a single voxel to the cube mesher, given the [`VoxelShapes::raw_shapes`]
+    pub(crate) fn visible_voxel(
+        &self,
+        voxel: &RawVoxel,
+        shapes: &[VoxelShape],
+        ior_for_voxel: &[Option<f32>],
+    ) -> VisibleVoxel {
+        VisibleVoxel {
+            index: voxel.0,
+            visibility: if *voxel == RawVoxel::EMPTY
+                || self.water.as_ref() == Some(voxel)
+                || shapes[voxel.0 as usize] != VoxelShape::Cube
+            {
+                VoxelVisibility::Empty
+            } else if ior_for_voxel[voxel.0 as usize].is_some() {
+                VoxelVisibility::Translucent
+            } else {
+                VoxelVisibility::Opaque
+            },
+        }
     }
 
     /// Returns the [`VoxelVisibility`] of each Voxel, and, if the model contains
@@ -83,19 +287,23 @@ impl VoxelData {
         ior_for_voxel: &[Option<f32>],
     ) -> (Vec<VisibleVoxel>, Option<f32>) {
         let mut refraction_indices: Vec<f32> = Vec::new();
+        // shaped voxels are meshed separately
+        let shapes = self.shapes.raw_shapes();
+        // every voxel with the same palette index is equally visible, so each index is only classified once
+        let mut ior_for_voxel = ior_for_voxel.to_vec();
+        ior_for_voxel.resize(256, None);
+        let table: [VisibleVoxel; 256] = std::array::from_fn(|index| {
+            self.visible_voxel(&RawVoxel(index as u8), &shapes, &ior_for_voxel)
+        });
         let voxels: Vec<VisibleVoxel> = self
-            .voxels
+            .dense_voxels()
             .iter()
-            .map(|v| VisibleVoxel {
-                index: v.0,
-                visibility: if *v == RawVoxel::EMPTY {
-                    VoxelVisibility::Empty
-                } else if let Some(ior) = ior_for_voxel[v.0 as usize] {
-                    refraction_indices.push(ior);
-                    VoxelVisibility::Translucent
-                } else {
-                    VoxelVisibility::Opaque
-                },
+            .map(|v| {
+                let visible = table[v.0 as usize];
+                if visible.visibility == VoxelVisibility::Translucent {
+                    refraction_indices.extend(ior_for_voxel[v.0 as usize]);
+                }
+                visible
             })
             .collect();
         let average_ior: Option<f32> = if refraction_indices.is_empty() {
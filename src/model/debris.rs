@@ -0,0 +1,464 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::{
+    asset::{Assets, Handle},
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::With,
+        system::{Commands, Query, Res, ResMut, Resource},
+        world::{Command, Mut, World},
+    },
+    hierarchy::DespawnRecursiveExt,
+    math::{primitives::Rectangle, IVec3, UVec3, Vec3},
+    pbr::{PbrBundle, StandardMaterial},
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    render::{camera::Camera, mesh::Mesh, prelude::SpatialBundle},
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
+    utils::HashMap,
+};
+
+use crate::VoxelModelInstance;
+
+use super::{
+    modify::{ModifyVoxelModelInWorld, VoxelWorldRegion},
+    RawVoxel, Voxel, VoxelContext, VoxelData, VoxelModel, VoxelQueryable,
+};
+
+/// Configures the debris spawned by [`ShatterVoxelCommandsExt::shatter_voxels`], trading the fidelity of the
+/// destruction against the cost of simulating it.
+///
+/// Each fragment is a small model that your physics engine can simulate as a rigid body. Fragments of at most
+/// `merge_below` voxels are merged into particle billboards once they are `merge_after` seconds old, when they have
+/// usually come to rest, and the particles shrink away over `particle_lifetime` seconds.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct VoxelDebrisSettings {
+    /// The edge length of the cubes of voxels that the shattered region is broken into, in voxels. Defaults to 2.
+    pub fragment_size: u32,
+    /// The most fragments spawned by one command. The smallest fragments beyond it become particles straight away.
+    /// Defaults to 64.
+    pub max_fragments: usize,
+    /// The age in seconds at which small fragments are merged into particles. Defaults to 3.
+    pub merge_after: f32,
+    /// The largest number of voxels in a fragment that is merged into a particle once it is old enough. Defaults to 4.
+    pub merge_below: usize,
+    /// The number of seconds that particles take to shrink away. Defaults to 1.5.
+    pub particle_lifetime: f32,
+    /// The speed at which fragments fly away from the center of the shattered region. Defaults to 4.
+    pub speed: f32,
+}
+
+impl Default for VoxelDebrisSettings {
+    fn default() -> Self {
+        Self {
+            fragment_size: 2,
+            max_fragments: 64,
+            merge_after: 3.0,
+            merge_below: 4,
+            particle_lifetime: 1.5,
+            speed: 4.0,
+        }
+    }
+}
+
+impl VoxelDebrisSettings {
+    /// Sets the edge length of the fragments, in voxels
+    pub fn with_fragment_size(mut self, fragment_size: u32) -> Self {
+        self.fragment_size = fragment_size.max(1);
+        self
+    }
+
+    /// Sets the most fragments spawned by one command
+    pub fn with_max_fragments(mut self, max_fragments: usize) -> Self {
+        self.max_fragments = max_fragments;
+        self
+    }
+
+    /// Merges fragments of at most `merge_below` voxels into particles once they are `merge_after` seconds old
+    pub fn with_merging(mut self, merge_after: f32, merge_below: usize) -> Self {
+        self.merge_after = merge_after;
+        self.merge_below = merge_below;
+        self
+    }
+
+    /// Sets the number of seconds that particles take to shrink away
+    pub fn with_particle_lifetime(mut self, particle_lifetime: f32) -> Self {
+        self.particle_lifetime = particle_lifetime;
+        self
+    }
+
+    /// Sets the speed at which fragments fly away from the center of the shattered region
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+}
+
+/// A fragment of a model spawned by [`ShatterVoxelCommandsExt::shatter_voxels`].
+///
+/// Add a rigid body and collider of the physics engine of your choice to entities when this component is added, setting
+/// off the body with `velocity`. The fragment, and with it the body, is despawned when it is merged into a
+/// [`VoxelDebrisParticle`].
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VoxelDebris {
+    /// The initial velocity of the fragment, in world space
+    pub velocity: Vec3,
+    /// The number of voxels in the fragment
+    pub voxel_count: usize,
+    /// The number of seconds since the fragment was spawned
+    pub age: f32,
+    /// The settings of the command that spawned the fragment
+    pub settings: VoxelDebrisSettings,
+    color: Color,
+    particle_size: f32,
+}
+
+impl VoxelDebris {
+    fn is_due(&self) -> bool {
+        self.age >= self.settings.merge_after && self.voxel_count <= self.settings.merge_below
+    }
+}
+
+/// A camera-facing billboard that a [`VoxelDebris`] fragment has been merged into, which shrinks away and is despawned
+/// at the end of its lifetime
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VoxelDebrisParticle {
+    /// The number of seconds that the particle takes to shrink away
+    pub lifetime: f32,
+    /// The number of seconds since the particle was spawned
+    pub age: f32,
+    size: f32,
+}
+
+/// Caps the debris alive at once across every shattered model, to protect the frame rate when a lot is destroyed at
+/// once.
+///
+/// When there are more fragments than `max_fragments`, the oldest are merged into particles regardless of their size,
+/// and when there are more particles than `max_particles`, the oldest are despawned.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct VoxelDebrisBudget {
+    /// Defaults to 256
+    pub max_fragments: usize,
+    /// Defaults to 2048
+    pub max_particles: usize,
+}
+
+impl Default for VoxelDebrisBudget {
+    fn default() -> Self {
+        Self {
+            max_fragments: 256,
+            max_particles: 2048,
+        }
+    }
+}
+
+/// Extension to [`Commands`] for breaking models into debris
+pub trait ShatterVoxelCommandsExt {
+    /// Remove every voxel of the `model` whose center lies within a world-space `region`, and spawn the removed voxels
+    /// as [`VoxelDebris`] fragments flying away from the center of the region.
+    ///
+    /// ### Arguments
+    /// * `model` - the [`VoxelModelInstance`] to be shattered.
+    /// * `global_xform` - the [`GlobalTransform`] of the entity that owns the `model`.
+    /// * `region` - the area to shatter in world space, either an [`bevy::math::bounding::Aabb3d`] or a [`bevy::math::bounding::BoundingSphere`].
+    /// * `settings` - the [`VoxelDebrisSettings`] controlling how many fragments are spawned and when they are merged.
+    fn shatter_voxels(
+        &mut self,
+        model: VoxelModelInstance,
+        global_xform: &GlobalTransform,
+        region: impl Into<VoxelWorldRegion>,
+        settings: VoxelDebrisSettings,
+    ) -> &mut Self;
+}
+
+impl ShatterVoxelCommandsExt for Commands<'_, '_> {
+    fn shatter_voxels(
+        &mut self,
+        model: VoxelModelInstance,
+        global_xform: &GlobalTransform,
+        region: impl Into<VoxelWorldRegion>,
+        settings: VoxelDebrisSettings,
+    ) -> &mut Self {
+        self.add(ShatterVoxels {
+            instance: model,
+            xform: *global_xform,
+            region: region.into(),
+            settings,
+        });
+        self
+    }
+}
+
+struct ShatterVoxels {
+    instance: VoxelModelInstance,
+    xform: GlobalTransform,
+    region: VoxelWorldRegion,
+    settings: VoxelDebrisSettings,
+}
+
+struct Fragment {
+    origin: IVec3,
+    voxels: Vec<(IVec3, Voxel)>,
+}
+
+impl Command for ShatterVoxels {
+    fn apply(self, world: &mut World) {
+        let removed: Arc<Mutex<Vec<(IVec3, Voxel)>>> = Arc::default();
+        let recorded = removed.clone();
+        ModifyVoxelModelInWorld {
+            instance: self.instance.clone(),
+            xform: self.xform,
+            region: self.region,
+            modify: Box::new(move |position, voxel, _| {
+                if *voxel == Voxel::EMPTY {
+                    return voxel.clone();
+                }
+                if let Ok(mut removed) = recorded.lock() {
+                    removed.push((position, voxel.clone()));
+                }
+                Voxel::EMPTY
+            }),
+        }
+        .apply(world);
+        let removed = removed
+            .lock()
+            .map(|removed| removed.clone())
+            .unwrap_or_default();
+        if removed.is_empty() {
+            return;
+        }
+        let Some((name, grid)) = world
+            .resource::<Assets<VoxelModel>>()
+            .get(&self.instance.model)
+            .map(|model| (model.name.clone(), model.grid()))
+        else {
+            return;
+        };
+        let Some(palette) = world
+            .resource::<Assets<VoxelContext>>()
+            .get(&self.instance.context)
+            .map(|context| context.palette.clone())
+        else {
+            return;
+        };
+        let fragment_size = self.settings.fragment_size.max(1) as i32;
+        let mut fragments: HashMap<IVec3, Fragment> = HashMap::new();
+        for (position, voxel) in removed {
+            let key = position.div_euclid(IVec3::splat(fragment_size));
+            fragments
+                .entry(key)
+                .or_insert_with(|| Fragment {
+                    origin: key * fragment_size,
+                    voxels: Vec::new(),
+                })
+                .voxels
+                .push((position, voxel));
+        }
+        let mut fragments: Vec<Fragment> = fragments.into_values().collect();
+        // the largest fragments are simulated, and the crumbs beyond the cap become particles straight away
+        fragments.sort_by(|a, b| {
+            b.voxels
+                .len()
+                .cmp(&a.voxels.len())
+                .then(a.origin.to_array().cmp(&b.origin.to_array()))
+        });
+        let center = Vec3::from(self.region.aabb().center());
+        let extent = Vec3::splat(fragment_size as f32 * grid.voxel_size);
+        for (index, fragment) in fragments.into_iter().enumerate() {
+            let translation = self
+                .xform
+                .transform_point(grid.cell_min_world(fragment.origin) + extent * 0.5);
+            let mut counts: HashMap<u8, usize> = HashMap::new();
+            for (_, voxel) in fragment.voxels.iter() {
+                *counts.entry(voxel.0).or_default() += 1;
+            }
+            let dominant = counts
+                .into_iter()
+                .max_by_key(|(index, count)| (*count, *index))
+                .map(|(index, _)| RawVoxel::from(Voxel(index)))
+                .unwrap_or(RawVoxel::EMPTY);
+            let color = palette
+                .elements
+                .get(dominant.0 as usize)
+                .map(|element| element.color)
+                .unwrap_or(Color::WHITE);
+            let voxel_count = fragment.voxels.len();
+            let particle_size = (voxel_count as f32).cbrt() * grid.voxel_size;
+            if index >= self.settings.max_fragments {
+                spawn_particle(
+                    world,
+                    translation,
+                    color,
+                    particle_size,
+                    self.settings.particle_lifetime,
+                );
+                continue;
+            }
+            let mut data =
+                VoxelData::new(UVec3::splat(fragment_size as u32), true, grid.voxel_size);
+            for (position, voxel) in fragment.voxels {
+                data.set_voxel(voxel, (position - fragment.origin).as_uvec3());
+            }
+            let Some((model, model_asset)) = VoxelModel::new(
+                world,
+                data,
+                format!("{name} debris {index}"),
+                self.instance.context.clone(),
+            ) else {
+                continue;
+            };
+            let mut transform = self.xform.compute_transform();
+            transform.translation = translation;
+            world.spawn((
+                VoxelModelInstance {
+                    model,
+                    context: self.instance.context.clone(),
+                },
+                VoxelDebris {
+                    velocity: (translation - center).normalize_or_zero() * self.settings.speed,
+                    voxel_count,
+                    age: 0.0,
+                    settings: self.settings,
+                    color,
+                    particle_size,
+                },
+                model_asset.mesh,
+                model_asset.material,
+                SpatialBundle::from_transform(transform),
+            ));
+        }
+    }
+}
+
+/// The mesh and materials shared by every [`VoxelDebrisParticle`]
+#[derive(Resource, Default)]
+pub(crate) struct VoxelDebrisParticleAssets {
+    mesh: Option<Handle<Mesh>>,
+    materials: HashMap<[u8; 4], Handle<StandardMaterial>>,
+}
+
+impl VoxelDebrisParticleAssets {
+    fn bundle(
+        &mut self,
+        translation: Vec3,
+        color: Color,
+        size: f32,
+        lifetime: f32,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> (VoxelDebrisParticle, PbrBundle) {
+        let mesh = self
+            .mesh
+            .get_or_insert_with(|| meshes.add(Rectangle::new(1.0, 1.0)))
+            .clone();
+        let material = self
+            .materials
+            .entry(color.to_srgba().to_u8_array())
+            .or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: color,
+                    unlit: true,
+                    cull_mode: None,
+                    ..Default::default()
+                })
+            })
+            .clone();
+        (
+            VoxelDebrisParticle {
+                lifetime,
+                age: 0.0,
+                size,
+            },
+            PbrBundle {
+                mesh,
+                material,
+                transform: Transform::from_translation(translation).with_scale(Vec3::splat(size)),
+                ..Default::default()
+            },
+        )
+    }
+}
+
+fn spawn_particle(world: &mut World, translation: Vec3, color: Color, size: f32, lifetime: f32) {
+    world.resource_scope(|world, mut assets: Mut<VoxelDebrisParticleAssets>| {
+        world.resource_scope(|world, mut meshes: Mut<Assets<Mesh>>| {
+            let bundle = assets.bundle(
+                translation,
+                color,
+                size,
+                lifetime,
+                &mut meshes,
+                &mut world.resource_mut::<Assets<StandardMaterial>>(),
+            );
+            world.spawn(bundle);
+        });
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_voxel_debris(
+    mut commands: Commands,
+    time: Res<Time>,
+    budget: Res<VoxelDebrisBudget>,
+    mut particle_assets: ResMut<VoxelDebrisParticleAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut fragments: Query<(Entity, &mut VoxelDebris, &GlobalTransform)>,
+    mut particles: Query<(Entity, &mut VoxelDebrisParticle, &mut Transform)>,
+    cameras: Query<&GlobalTransform, With<Camera>>,
+) {
+    let delta = time.delta_seconds();
+    for (_, mut debris, _) in fragments.iter_mut() {
+        debris.age += delta;
+    }
+    let mut live: Vec<(Entity, f32)> = fragments
+        .iter()
+        .map(|(entity, debris, _)| (entity, debris.age))
+        .collect();
+    live.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let excess = live.len().saturating_sub(budget.max_fragments);
+    for (index, (entity, _)) in live.into_iter().enumerate() {
+        let Ok((_, debris, xform)) = fragments.get(entity) else {
+            continue;
+        };
+        if index >= excess && !debris.is_due() {
+            continue;
+        }
+        commands.spawn(particle_assets.bundle(
+            xform.translation(),
+            debris.color,
+            debris.particle_size,
+            debris.settings.particle_lifetime,
+            &mut meshes,
+            &mut materials,
+        ));
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let camera = cameras.iter().next().map(|xform| xform.translation());
+    let mut alive: Vec<(Entity, f32)> = Vec::new();
+    for (entity, mut particle, mut transform) in particles.iter_mut() {
+        particle.age += delta;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        alive.push((entity, particle.age));
+        transform.scale = Vec3::splat(particle.size * (1.0 - particle.age / particle.lifetime));
+        if let Some(camera) = camera {
+            if camera != transform.translation {
+                transform.look_at(camera, Vec3::Y);
+            }
+        }
+    }
+    alive.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let excess = alive.len().saturating_sub(budget.max_particles);
+    for (entity, _) in alive.into_iter().take(excess) {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,152 @@
+use std::{any::TypeId, marker::PhantomData, sync::Arc};
+
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    asset::{AssetEvent, Assets},
+    ecs::{event::EventReader, system::ResMut},
+    log::warn,
+    reflect::Reflect,
+    utils::HashMap,
+};
+use serde::de::DeserializeOwned;
+
+use super::{Voxel, VoxelContext, VoxelPalette};
+
+/// A side table of gameplay properties for the elements of a [`VoxelPalette`], such as mining hardness, friction or
+/// flammability, keyed by [`Voxel`].
+///
+/// Attach a table to a palette with [`VoxelPalette::with_element_data`], or author one as a RON sidecar next to a
+/// `.vox` file and load it with [`crate::VoxLoaderSettings::element_data`] and a [`VoxelElementDataPlugin`]. The
+/// sidecar is a map from palette index (1-255) to the properties of that index:
+///
+/// ```ron
+/// {
+///     1: (hardness: 3.0, friction: 0.6),
+///     2: (hardness: 0.5, friction: 0.9),
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct VoxelElementData<T> {
+    elements: HashMap<Voxel, T>,
+}
+
+impl<T> Default for VoxelElementData<T> {
+    fn default() -> Self {
+        Self {
+            elements: HashMap::new(),
+        }
+    }
+}
+
+impl<T> VoxelElementData<T> {
+    /// Sets the properties of `voxel`
+    pub fn with(mut self, voxel: Voxel, data: T) -> Self {
+        self.insert(voxel, data);
+        self
+    }
+
+    /// Sets the properties of `voxel`, replacing any existing properties. Has no effect for [`Voxel::EMPTY`].
+    pub fn insert(&mut self, voxel: Voxel, data: T) {
+        if voxel != Voxel::EMPTY {
+            self.elements.insert(voxel, data);
+        }
+    }
+
+    /// Returns the properties of `voxel`, if it has any
+    pub fn get(&self, voxel: &Voxel) -> Option<&T> {
+        self.elements.get(voxel)
+    }
+
+    /// Iterates over every voxel with properties, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&Voxel, &T)> {
+        self.elements.iter()
+    }
+}
+
+impl<T: DeserializeOwned> VoxelElementData<T> {
+    /// Parses a table from RON, in the format described in [`VoxelElementData`]. Entries for index 0, which is
+    /// [`Voxel::EMPTY`], are ignored.
+    pub fn from_ron(ron: &str) -> Result<Self, ron::error::SpannedError> {
+        let elements: HashMap<u8, T> = ron::from_str(ron)?;
+        Ok(Self {
+            elements: elements
+                .into_iter()
+                .filter(|(index, _)| *index != 0)
+                .map(|(index, data)| (Voxel(index), data))
+                .collect(),
+        })
+    }
+}
+
+impl VoxelPalette {
+    /// Attaches a table of gameplay properties to the palette, replacing any existing table of the same type, so that
+    /// the properties travel with the palette. See [`VoxelElementData`].
+    pub fn with_element_data<T: Reflect>(mut self, data: VoxelElementData<T>) -> Self {
+        self.element_data.insert(TypeId::of::<T>(), Arc::new(data));
+        self
+    }
+
+    /// Returns the properties of type `T` for `voxel`, if the palette has a table of that type with an entry for the
+    /// voxel
+    pub fn element_data<T: Reflect>(&self, voxel: &Voxel) -> Option<&T> {
+        self.element_data_table::<T>()?.get(voxel)
+    }
+
+    /// Returns the table of gameplay properties of type `T` attached to the palette
+    pub fn element_data_table<T: Reflect>(&self) -> Option<&VoxelElementData<T>> {
+        self.element_data
+            .get(&TypeId::of::<T>())?
+            .downcast_ref::<VoxelElementData<T>>()
+    }
+}
+
+/// Parses the RON sidecars loaded with [`crate::VoxLoaderSettings::element_data`] into a [`VoxelElementData<T>`]
+/// attached to the palette of each loaded [`VoxelContext`].
+///
+/// Add one plugin for the type of the properties stored in the sidecars.
+pub struct VoxelElementDataPlugin<T>(PhantomData<T>);
+
+impl<T> Default for VoxelElementDataPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Reflect + DeserializeOwned> Plugin for VoxelElementDataPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostUpdate, parse_element_data::<T>);
+    }
+}
+
+fn parse_element_data<T: Reflect + DeserializeOwned>(
+    mut events: EventReader<AssetEvent<VoxelContext>>,
+    mut contexts: ResMut<Assets<VoxelContext>>,
+) {
+    for event in events.read() {
+        let (AssetEvent::Added { id } | AssetEvent::LoadedWithDependencies { id }) = event else {
+            continue;
+        };
+        let Some(source) = contexts
+            .get(*id)
+            .filter(|context| context.palette.element_data_table::<T>().is_none())
+            .and_then(|context| context.palette.element_data_source.clone())
+        else {
+            continue;
+        };
+        let Some(context) = contexts.get_mut(*id) else {
+            continue;
+        };
+        match VoxelElementData::<T>::from_ron(&source) {
+            Ok(data) => {
+                context
+                    .palette
+                    .element_data
+                    .insert(TypeId::of::<T>(), Arc::new(data));
+            }
+            Err(error) => warn!(
+                "Failed to parse element data as {}: {error}",
+                std::any::type_name::<T>()
+            ),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use bevy::{
+    asset::{Asset, Handle},
+    math::{IVec3, UVec3, Vec3},
+    reflect::TypePath,
+};
+use ndshape::Shape;
+
+use crate::VoxelRng;
+
+use super::{RawVoxel, VoxelData, VoxelModel};
+
+/// Where the Voronoi cells that [`VoxelModel::fracture`] splits a model along are centered
+#[derive(Clone, Debug, PartialEq)]
+pub enum VoxelFracture {
+    /// One cell around each point, in voxel space
+    Points(Vec<IVec3>),
+    /// `count` cells around distinct solid voxels of the model, picked with a [`VoxelRng`] seeded with `seed`, so the
+    /// same model is always split into the same shards
+    Count {
+        /// The number of cells
+        count: u32,
+        /// The seed of the generator picking the centers of the cells
+        seed: u64,
+    },
+}
+
+/// A piece of a model split by [`VoxelModel::fracture`]
+#[derive(Clone, Debug)]
+pub struct VoxelShard {
+    /// The voxels of the shard, cropped to their bounds
+    pub data: VoxelData,
+    /// The center of the shard in the local space of the model it was split from. Placing each shard at its offset from
+    /// the model reassembles the model.
+    pub offset: Vec3,
+}
+
+/// The shards that a model was split into when it was loaded with [`crate::VoxLoaderSettings::fracture_shards`],
+/// loadable by appending `#{name}@shards` to the asset path
+#[derive(Asset, TypePath, Clone, Debug, Default)]
+pub struct VoxelShards {
+    /// Each shard's model, which shares the palette of the file it was loaded from, and its offset in the local space
+    /// of the intact model. See [`VoxelShard::offset`].
+    pub shards: Vec<(Handle<VoxelModel>, Vec3)>,
+}
+
+impl VoxelData {
+    /// Splits the model into shards along 3D Voronoi cells, each voxel going to the cell whose center is nearest, for
+    /// pre-fractured destruction assets. Shards keep the palette indices, voxel size and settings of the model, so
+    /// models created from them with [`VoxelModel::new`] share the model's [`super::VoxelContext`]. Cells without any
+    /// solid voxels produce no shard.
+    pub fn fracture(&self, fracture: &VoxelFracture) -> Vec<VoxelShard> {
+        let size = self._size();
+        let leading_padding = UVec3::splat(self.padding() / 2);
+        let index = |position: IVec3| {
+            self.shape
+                .linearize((position.as_uvec3() + leading_padding).into()) as usize
+        };
+        let mut solid: Vec<IVec3> = Vec::new();
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let position = IVec3::new(x, y, z);
+                    if *self.voxel_at_index(index(position)) != RawVoxel::EMPTY {
+                        solid.push(position);
+                    }
+                }
+            }
+        }
+        let seeds = match fracture {
+            VoxelFracture::Points(points) => points.clone(),
+            VoxelFracture::Count { count, seed } => {
+                // a partial shuffle picks distinct voxels
+                let mut rng = VoxelRng::from_seed(*seed);
+                let count = (*count as usize).min(solid.len());
+                let mut candidates = solid.clone();
+                for i in 0..count {
+                    let j = i + rng.below((candidates.len() - i) as u32) as usize;
+                    candidates.swap(i, j);
+                }
+                candidates.truncate(count);
+                candidates
+            }
+        };
+        if seeds.is_empty() {
+            return Vec::new();
+        }
+        let mut cells: Vec<Vec<IVec3>> = vec![Vec::new(); seeds.len()];
+        for position in solid {
+            let nearest = seeds
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, seed)| seed.distance_squared(position))
+                .map_or(0, |(cell, _)| cell);
+            cells[nearest].push(position);
+        }
+        let half_size = size.as_vec3() * 0.5;
+        cells
+            .into_iter()
+            .filter(|cell| !cell.is_empty())
+            .map(|cell| {
+                let (min, max) = cell
+                    .iter()
+                    .fold((IVec3::MAX, IVec3::MIN), |(min, max), position| {
+                        (min.min(*position), max.max(*position))
+                    });
+                let shard_size = max - min + IVec3::ONE;
+                let mut data = VoxelData::new(
+                    shard_size.as_uvec3(),
+                    self.mesh_outer_faces,
+                    self.voxel_size,
+                )
+                .with_settings_of(self);
+                let shard_padding = UVec3::splat(data.padding() / 2);
+                for position in cell {
+                    let raw = self.voxel_at_index(index(position)).clone();
+                    let target = data
+                        .shape
+                        .linearize(((position - min).as_uvec3() + shard_padding).into())
+                        as usize;
+                    VoxelData::record_change(&mut data.histogram, &data.voxels[target], &raw);
+                    data.voxels[target] = raw;
+                }
+                VoxelShard {
+                    data,
+                    offset: (min.as_vec3() + shard_size.as_vec3() * 0.5 - half_size)
+                        * self.voxel_size,
+                }
+            })
+            .collect()
+    }
+}
+
+impl VoxelModel {
+    /// Splits the voxel data of the model into shards along 3D Voronoi cells. Create a model from each shard with
+    /// [`VoxelModel::new`], passing the model's [`super::VoxelContext`]. See [`VoxelData::fracture`].
+    pub fn fracture(&self, fracture: &VoxelFracture) -> Vec<VoxelShard> {
+        self.data.fracture(fracture)
+    }
+}
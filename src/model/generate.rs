@@ -0,0 +1,168 @@
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{
+        event::{Event, EventWriter},
+        system::{Commands, Res, ResMut, Resource},
+        world::{Command, World},
+    },
+    log::{info_span, warn},
+    math::UVec3,
+    pbr::StandardMaterial,
+    render::mesh::Mesh,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+
+use super::{Voxel, VoxelContext, VoxelData, VoxelModel};
+
+/// Extension to [`Commands`] for generating voxel models in the background
+pub trait GenerateVoxelModelCommandsExt {
+    /// Generates a model by evaluating `generator` for every voxel, then meshes it, on the
+    /// [`AsyncComputeTaskPool`], so that procedurally creating a large model doesn't stall the frame. A
+    /// [`VoxelModelGenerated`] event is sent with the handle to the new model once it is ready.
+    ///
+    /// ### Arguments
+    /// * `name` - the name of the new model, which is included in the [`VoxelModelGenerated`] event
+    /// * `context` - the [`VoxelContext`] whose palette the model uses. This must already be loaded.
+    /// * `size` - the size of the model in voxels
+    /// * `voxel_size` - the size of each voxel in the local space of the model
+    /// * `generator` - a function returning the voxel at each position in voxel space
+    fn generate_voxel_model_async<F: Fn(UVec3) -> Voxel + Send + Sync + 'static>(
+        &mut self,
+        name: impl Into<String>,
+        context: Handle<VoxelContext>,
+        size: UVec3,
+        voxel_size: f32,
+        generator: F,
+    ) -> &mut Self;
+}
+
+impl GenerateVoxelModelCommandsExt for Commands<'_, '_> {
+    fn generate_voxel_model_async<F: Fn(UVec3) -> Voxel + Send + Sync + 'static>(
+        &mut self,
+        name: impl Into<String>,
+        context: Handle<VoxelContext>,
+        size: UVec3,
+        voxel_size: f32,
+        generator: F,
+    ) -> &mut Self {
+        self.add(GenerateVoxelModel {
+            name: name.into(),
+            context,
+            size,
+            voxel_size,
+            generator: Box::new(generator),
+        });
+        self
+    }
+}
+
+/// Sent when a model requested with [`GenerateVoxelModelCommandsExt::generate_voxel_model_async`] is ready
+#[derive(Event, Clone, Debug)]
+pub struct VoxelModelGenerated {
+    /// The name of the model, as passed to [`GenerateVoxelModelCommandsExt::generate_voxel_model_async`]
+    pub name: String,
+    /// Handle to the new model
+    pub model: Handle<VoxelModel>,
+    /// Handle to the context of the model
+    pub context: Handle<VoxelContext>,
+}
+
+/// The voxel generation tasks that are still running
+#[derive(Resource, Default)]
+pub(crate) struct PendingVoxelGenerations {
+    tasks: Vec<PendingVoxelGeneration>,
+}
+
+struct PendingVoxelGeneration {
+    name: String,
+    context: Handle<VoxelContext>,
+    task: Task<(VoxelData, Mesh, Option<f32>)>,
+}
+
+struct GenerateVoxelModel {
+    name: String,
+    context: Handle<VoxelContext>,
+    size: UVec3,
+    voxel_size: f32,
+    generator: Box<dyn Fn(UVec3) -> Voxel + Send + Sync>,
+}
+
+impl Command for GenerateVoxelModel {
+    fn apply(self, world: &mut World) {
+        let Some(palette) = world
+            .resource::<Assets<VoxelContext>>()
+            .get(&self.context)
+            .map(|context| context.palette.clone())
+        else {
+            warn!(
+                "Can't generate {} with a context that hasn't loaded",
+                self.name
+            );
+            return;
+        };
+        let (size, voxel_size, generator) = (self.size, self.voxel_size, self.generator);
+        let name = self.name.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            let _span = info_span!("voxel_generate", name = %name).entered();
+            let mut data = VoxelData::new(size, true, voxel_size);
+            for z in 0..size.z {
+                for y in 0..size.y {
+                    for x in 0..size.x {
+                        let position = UVec3::new(x, y, z);
+                        let voxel = generator(position);
+                        if voxel != Voxel::EMPTY {
+                            data.set_voxel(voxel, position);
+                        }
+                    }
+                }
+            }
+            let (mesh, average_ior) = data.remesh(&palette);
+            (data, mesh, average_ior)
+        });
+        world
+            .resource_mut::<PendingVoxelGenerations>()
+            .tasks
+            .push(PendingVoxelGeneration {
+                name: self.name,
+                context: self.context,
+                task,
+            });
+    }
+}
+
+pub(crate) fn finish_voxel_generations(
+    mut pending: ResMut<PendingVoxelGenerations>,
+    mut events: EventWriter<VoxelModelGenerated>,
+    mut models: ResMut<Assets<VoxelModel>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    contexts: Res<Assets<VoxelContext>>,
+) {
+    pending.tasks.retain_mut(|generation| {
+        let Some((data, mesh, average_ior)) = block_on(future::poll_once(&mut generation.task))
+        else {
+            return true;
+        };
+        let Some(context) = contexts.get(&generation.context) else {
+            return false;
+        };
+        let Some(material) = context.material_for(average_ior, &data, &mut materials) else {
+            return false;
+        };
+        let model = models.add(VoxelModel {
+            name: generation.name.clone(),
+            data,
+            mesh: meshes.add(mesh),
+            material,
+            has_translucency: average_ior.is_some(),
+            mesh_pending: false,
+            brick_map: None,
+        });
+        events.send(VoxelModelGenerated {
+            name: generation.name.clone(),
+            model,
+            context: generation.context.clone(),
+        });
+        false
+    });
+}
@@ -0,0 +1,156 @@
+use bevy::{
+    asset::{AssetEvent, AssetId, Assets, Handle},
+    color::{palettes::css, Alpha, Color},
+    ecs::{
+        change_detection::{DetectChanges, DetectChangesMut},
+        component::Component,
+        entity::Entity,
+        event::EventReader,
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::{IVec3, Quat},
+    pbr::StandardMaterial,
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    render::{alpha::AlphaMode, mesh::Mesh},
+    transform::components::Transform,
+    utils::HashSet,
+};
+
+use crate::VoxelModelInstance;
+
+use super::{blueprint::VoxelBlueprint, VoxelContext, VoxelModel, VoxelQueryable};
+
+/// Previews where a [`VoxelBlueprint`] would be stamped into a model, before committing it with
+/// [`crate::StampBlueprintCommandsExt::stamp_blueprint`].
+///
+/// Spawn the ghost with a [`bevy::prelude::SpatialBundle`] as a child of the entity holding the `target`
+/// [`VoxelModelInstance`]. The plugin will generate a semi-transparent mesh for the blueprint and position it within the
+/// target, tinted with `valid_color` if the blueprint fits at that position, or `invalid_color` if it overlaps solid
+/// voxels or extends beyond the bounds of the target.
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VoxelGhost {
+    /// The blueprint being previewed
+    pub blueprint: Handle<VoxelBlueprint>,
+    /// The model the blueprint would be stamped into
+    pub target: VoxelModelInstance,
+    /// The position in the target's voxel space of the blueprint's anchor
+    pub position: IVec3,
+    /// The rotation of the blueprint, snapped to multiples of 90 degrees
+    pub rotation: Quat,
+    /// The tint applied when the blueprint fits. Defaults to translucent green.
+    pub valid_color: Color,
+    /// The tint applied when the blueprint doesn't fit. Defaults to translucent red.
+    pub invalid_color: Color,
+    fits: bool,
+}
+
+impl VoxelGhost {
+    /// Create a new ghost previewing the `blueprint` within the `target` model
+    pub fn new(
+        blueprint: Handle<VoxelBlueprint>,
+        target: VoxelModelInstance,
+        position: IVec3,
+        rotation: Quat,
+    ) -> Self {
+        Self {
+            blueprint,
+            target,
+            position,
+            rotation,
+            valid_color: css::LIME.with_alpha(0.5).into(),
+            invalid_color: css::RED.with_alpha(0.5).into(),
+            fits: false,
+        }
+    }
+
+    /// Whether the blueprint fits at the ghost's current position, as of the last time the ghost was updated.
+    pub fn fits(&self) -> bool {
+        self.fits
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_voxel_ghosts(
+    mut commands: Commands,
+    mut ghosts: Query<(
+        Entity,
+        &mut VoxelGhost,
+        Option<&Handle<Mesh>>,
+        Option<&Handle<StandardMaterial>>,
+    )>,
+    mut model_events: EventReader<AssetEvent<VoxelModel>>,
+    mut blueprint_events: EventReader<AssetEvent<VoxelBlueprint>>,
+    blueprints: Res<Assets<VoxelBlueprint>>,
+    models: Res<Assets<VoxelModel>>,
+    contexts: Res<Assets<VoxelContext>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let modified_models: HashSet<AssetId<VoxelModel>> = model_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    let modified_blueprints: HashSet<AssetId<VoxelBlueprint>> = blueprint_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (entity, mut ghost, mesh_handle, material_handle) in ghosts.iter_mut() {
+        if !ghost.is_changed()
+            && !modified_models.contains(&ghost.target.model.id())
+            && !modified_blueprints.contains(&ghost.blueprint.id())
+        {
+            continue;
+        }
+        let (Some(blueprint), Some(model), Some(context)) = (
+            blueprints.get(&ghost.blueprint),
+            models.get(&ghost.target.model),
+            contexts.get(&ghost.target.context),
+        ) else {
+            continue;
+        };
+        let fits = blueprint.fits_at(model, ghost.position, ghost.rotation);
+        ghost.bypass_change_detection().fits = fits;
+
+        let voxels = blueprint
+            .voxels
+            .rotated(ghost.rotation)
+            .remapped(ghost.target.context.id(), &context.palette);
+        let data = voxels.to_data(model.data.voxel_size);
+        let (mesh, _) = data.remesh(&context.palette);
+        let Some(mut material) = materials.get(&context.opaque_material).cloned() else {
+            continue;
+        };
+        material.base_color = if fits {
+            ghost.valid_color
+        } else {
+            ghost.invalid_color
+        };
+        material.alpha_mode = AlphaMode::Blend;
+        let origin = blueprint.origin_at(ghost.position, ghost.rotation);
+        let translation =
+            model.voxel_coord_to_local_space(origin) - data.voxel_coord_to_local_space(IVec3::ZERO);
+        let mut entity = commands.entity(entity);
+        entity.insert(Transform::from_translation(translation));
+        // the ghost's mesh and material are replaced in place, rather than leaving a new asset behind on every change
+        match mesh_handle.filter(|handle| meshes.contains(*handle)) {
+            Some(handle) => meshes.insert(handle, mesh),
+            None => {
+                entity.insert(meshes.add(mesh));
+            }
+        }
+        match material_handle.filter(|handle| materials.contains(*handle)) {
+            Some(handle) => materials.insert(handle, material),
+            None => {
+                entity.insert(materials.add(material));
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use bevy::{
+    asset::{AssetEvent, AssetId, Assets},
+    ecs::{
+        component::Component,
+        event::EventReader,
+        system::{Query, Res, ResMut, Resource},
+    },
+    math::{IVec3, UVec3},
+    pbr::StandardMaterial,
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    render::mesh::Mesh,
+    utils::HashSet,
+};
+use ndshape::Shape;
+
+use crate::VoxelModelInstance;
+
+use super::{
+    brick::VoxelBrickMap, modify::update_model_mesh, RawVoxel, Voxel, VoxelContext, VoxelData,
+    VoxelModel,
+};
+
+/// Makes the unsupported voxels of a [`VoxelModelInstance`] fall, for sand and gravel that settle after the ground
+/// beneath them is dug away.
+///
+/// Every fixed timestep, each falling voxel with an empty voxel beneath it moves down by one voxel, up to
+/// `cells_per_tick` times. Models are remeshed once per tick while voxels are moving, and the number of voxels moved
+/// per tick across every model is capped by the [`VoxelGravityBudget`]. As instances share the voxel data of their
+/// model, every instance of the model settles.
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VoxelGravity {
+    /// The number of voxels that falling voxels move down each tick. Defaults to 1.
+    pub cells_per_tick: u32,
+    /// The voxels that fall. If empty, which is the default, every voxel falls.
+    pub materials: Vec<Voxel>,
+    settled: bool,
+}
+
+impl Default for VoxelGravity {
+    fn default() -> Self {
+        Self {
+            cells_per_tick: 1,
+            materials: Vec::new(),
+            settled: false,
+        }
+    }
+}
+
+impl VoxelGravity {
+    /// Creates a component making only the voxels in `materials` fall, or every voxel if it is empty
+    pub fn new(materials: impl IntoIterator<Item = Voxel>) -> Self {
+        Self {
+            materials: materials.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the number of voxels that falling voxels move down each tick
+    pub fn with_cells_per_tick(mut self, cells_per_tick: u32) -> Self {
+        self.cells_per_tick = cells_per_tick;
+        self
+    }
+
+    /// Whether every falling voxel has come to rest. Falling resumes when the model is edited.
+    pub fn is_settled(&self) -> bool {
+        self.settled
+    }
+}
+
+/// The maximum number of voxels moved by [`VoxelGravity`] per fixed timestep, across every model.
+///
+/// Once the budget is spent, the remaining models continue falling on the next tick.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct VoxelGravityBudget {
+    /// Defaults to 16384
+    pub max_moves_per_tick: usize,
+}
+
+impl Default for VoxelGravityBudget {
+    fn default() -> Self {
+        Self {
+            max_moves_per_tick: 16384,
+        }
+    }
+}
+
+impl VoxelData {
+    /// Moves each voxel accepted by `falls` that has an empty voxel beneath it down by one voxel, scanning from the
+    /// bottom up so that whole columns fall together. Stops once `budget` voxels have moved, and returns the number
+    /// moved.
+    pub(crate) fn fall(&mut self, falls: &dyn Fn(&RawVoxel) -> bool, budget: usize) -> usize {
+        self.decompress();
+        let pairs: Vec<(usize, usize)> = self.vertical_pairs().collect();
+        let mut moved = 0;
+        for (above, below) in pairs {
+            if moved >= budget {
+                break;
+            }
+            if self.can_fall(above, below, falls) {
+                self.voxels.swap(above, below);
+                moved += 1;
+            }
+        }
+        if moved > 0 {
+            self.mark_dirty(IVec3::ZERO, self._size());
+        }
+        moved
+    }
+
+    /// Whether none of the voxels accepted by `falls` has an empty voxel beneath it
+    pub(crate) fn is_settled(&self, falls: &dyn Fn(&RawVoxel) -> bool) -> bool {
+        !self
+            .vertical_pairs()
+            .any(|(above, below)| self.can_fall(above, below, falls))
+    }
+
+    fn can_fall(&self, above: usize, below: usize, falls: &dyn Fn(&RawVoxel) -> bool) -> bool {
+        *self.voxel_at_index(below) == RawVoxel::EMPTY
+            && *self.voxel_at_index(above) != RawVoxel::EMPTY
+            && falls(self.voxel_at_index(above))
+            && !self.is_protected_index(above)
+            && !self.is_protected_index(below)
+    }
+
+    /// The indices of every voxel and the voxel beneath it, from the bottom of the model up
+    fn vertical_pairs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let size = self._size().as_uvec3();
+        let leading_padding = UVec3::splat(self.padding() / 2);
+        let index = move |x: u32, y: u32, z: u32| {
+            self.shape
+                .linearize((UVec3::new(x, y, z) + leading_padding).into()) as usize
+        };
+        (1..size.y).flat_map(move |y| {
+            (0..size.z)
+                .flat_map(move |z| (0..size.x).map(move |x| (index(x, y, z), index(x, y - 1, z))))
+        })
+    }
+}
+
+pub(crate) fn apply_voxel_gravity(
+    mut instances: Query<(&VoxelModelInstance, &mut VoxelGravity)>,
+    mut model_events: EventReader<AssetEvent<VoxelModel>>,
+    budget: Res<VoxelGravityBudget>,
+    mut models: ResMut<Assets<VoxelModel>>,
+    contexts: Res<Assets<VoxelContext>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut brick_maps: ResMut<Assets<VoxelBrickMap>>,
+) {
+    let mut remaining = budget.max_moves_per_tick;
+    let mut visited: HashSet<AssetId<VoxelModel>> = HashSet::new();
+    let modified: HashSet<AssetId<VoxelModel>> = model_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (instance, mut gravity) in instances.iter_mut() {
+        if modified.contains(&instance.model.id()) {
+            gravity.settled = false;
+        }
+        if remaining == 0 || gravity.settled || !visited.insert(instance.model.id()) {
+            continue;
+        }
+        let falling: Vec<RawVoxel> = gravity
+            .materials
+            .iter()
+            .map(|voxel| RawVoxel::from(voxel.clone()))
+            .collect();
+        let falls = |voxel: &RawVoxel| falling.is_empty() || falling.contains(voxel);
+        // check before borrowing the model mutably, so that settled models aren't flagged as modified
+        let Some(is_settled) = models
+            .get(&instance.model)
+            .map(|model| model.data.is_settled(&falls))
+        else {
+            continue;
+        };
+        if is_settled {
+            gravity.settled = true;
+            continue;
+        }
+        let (Some(model), Some(context)) = (
+            models.get_mut(&instance.model),
+            contexts.get(&instance.context),
+        ) else {
+            continue;
+        };
+        let mut moved = 0;
+        for _ in 0..gravity.cells_per_tick {
+            let step = model.data.fall(&falls, remaining - moved);
+            moved += step;
+            if step == 0 || moved >= remaining {
+                break;
+            }
+        }
+        remaining -= moved;
+        update_model_mesh(
+            model,
+            &mut meshes,
+            &mut materials,
+            &mut brick_maps,
+            context.opaque_material.clone(),
+            context.transmissive_material.clone(),
+            &context.palette,
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use bevy::math::{bounding::Aabb3d, IVec3, Vec3};
+
+/// A regular grid of cubic voxels, used to convert between points and the cells containing them.
+///
+/// Every model has a grid in its local space, returned by [`crate::VoxelQueryable::grid`], with its origin at the
+/// model's minimum corner. A grid can also be created directly to snap objects to a voxel-aligned placement grid in
+/// world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoxelGrid {
+    /// The length of each side of a cell
+    pub voxel_size: f32,
+    /// The minimum corner of the cell at `IVec3::ZERO`
+    pub origin: Vec3,
+}
+
+impl Default for VoxelGrid {
+    fn default() -> Self {
+        Self {
+            voxel_size: 1.0,
+            origin: Vec3::ZERO,
+        }
+    }
+}
+
+impl VoxelGrid {
+    /// Creates a grid of cells `voxel_size` long, with the minimum corner of cell zero at `origin`
+    pub fn new(voxel_size: f32, origin: Vec3) -> Self {
+        Self { voxel_size, origin }
+    }
+
+    /// The cell containing `point`. Points on the boundary between two cells belong to the cell with the higher
+    /// coordinate.
+    pub fn world_to_cell(&self, point: Vec3) -> IVec3 {
+        ((point - self.origin) / self.voxel_size).floor().as_ivec3()
+    }
+
+    /// The minimum corner of `cell`
+    pub fn cell_min_world(&self, cell: IVec3) -> Vec3 {
+        self.origin + cell.as_vec3() * self.voxel_size
+    }
+
+    /// The center of `cell`
+    pub fn cell_center_world(&self, cell: IVec3) -> Vec3 {
+        self.origin + (cell.as_vec3() + 0.5) * self.voxel_size
+    }
+
+    /// The bounds of `cell`
+    pub fn cell_aabb(&self, cell: IVec3) -> Aabb3d {
+        let min = self.cell_min_world(cell);
+        Aabb3d::new(
+            min + self.voxel_size * 0.5,
+            Vec3::splat(self.voxel_size * 0.5),
+        )
+    }
+
+    /// Snaps `point` to the nearest corner of the grid
+    pub fn snap_world_point(&self, point: Vec3) -> Vec3 {
+        self.origin + ((point - self.origin) / self.voxel_size).round() * self.voxel_size
+    }
+
+    /// Snaps `point` to the center of the cell containing it
+    pub fn snap_to_cell_center(&self, point: Vec3) -> Vec3 {
+        self.cell_center_world(self.world_to_cell(point))
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::{
+    ecs::{
+        event::Event,
+        system::Commands,
+        world::{Command, World},
+    },
+    utils::HashMap,
+};
+
+use crate::{VoxelModelInstance, VoxelRegionMode};
+
+use super::{modify::ModifyVoxelModel, Voxel};
+
+/// Sent when voxels are removed by [`HarvestVoxelCommandsExt::harvest_voxels`]
+#[derive(Event, Clone)]
+pub struct VoxelsHarvested {
+    /// The model instance the voxels were removed from
+    pub instance: VoxelModelInstance,
+    /// The number of voxels removed of each type. Types of which no voxels were removed are omitted.
+    pub counts: HashMap<Voxel, u32>,
+}
+
+impl VoxelsHarvested {
+    /// The total number of voxels removed
+    pub fn total(&self) -> u32 {
+        self.counts.values().sum()
+    }
+}
+
+/// Extension to [`Commands`] for mining voxels out of a model
+pub trait HarvestVoxelCommandsExt {
+    /// Remove every voxel within the `region` of the `model` for which `filter` returns true, and send a
+    /// [`VoxelsHarvested`] event reporting how many voxels of each type were removed.
+    ///
+    /// ### Arguments
+    /// * `model` - the [`VoxelModelInstance`] to be mined.
+    /// * `region` - a [`VoxelRegionMode`] defining the area of the voxel model to mine.
+    /// * `filter` - a closure deciding whether a solid voxel is removed, for instance to only mine ore out of a rock.
+    ///
+    /// ### Notes
+    /// An event is sent even if no voxels were removed, so that a mining action can always be resolved.
+    fn harvest_voxels<F: Fn(&Voxel) -> bool + Send + Sync + 'static>(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+        filter: F,
+    ) -> &mut Self;
+}
+
+impl HarvestVoxelCommandsExt for Commands<'_, '_> {
+    fn harvest_voxels<F: Fn(&Voxel) -> bool + Send + Sync + 'static>(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+        filter: F,
+    ) -> &mut Self {
+        self.add(HarvestVoxels {
+            instance: model,
+            region,
+            filter: Box::new(filter),
+        });
+        self
+    }
+}
+
+struct HarvestVoxels {
+    instance: VoxelModelInstance,
+    region: VoxelRegionMode,
+    filter: Box<dyn Fn(&Voxel) -> bool + Send + Sync + 'static>,
+}
+
+impl Command for HarvestVoxels {
+    fn apply(self, world: &mut World) {
+        let counts: Arc<Mutex<HashMap<Voxel, u32>>> = Arc::default();
+        let (filter, harvested) = (self.filter, counts.clone());
+        ModifyVoxelModel {
+            instance: self.instance.clone(),
+            region: self.region,
+            modify: Box::new(move |_, voxel, _| {
+                if *voxel == Voxel::EMPTY || !filter(voxel) {
+                    return voxel.clone();
+                }
+                if let Ok(mut counts) = harvested.lock() {
+                    *counts.entry(voxel.clone()).or_default() += 1;
+                }
+                Voxel::EMPTY
+            }),
+        }
+        .apply(world);
+        let counts = counts
+            .lock()
+            .map(|counts| counts.clone())
+            .unwrap_or_default();
+        world.send_event(VoxelsHarvested {
+            instance: self.instance,
+            counts,
+        });
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+
+use bevy::math::{IVec3, UVec3};
+use ndshape::Shape;
+
+use super::{RawVoxel, VoxelData, VoxelModel, SIDES};
+
+impl VoxelData {
+    /// Returns a copy of the model with the solid voxels further than `thickness` voxels from any empty voxel removed,
+    /// leaving a shell. Voxels beyond the bounds of the model count as empty, and distances are measured in steps
+    /// between face-adjacent voxels. A `thickness` of 0 is treated as 1.
+    ///
+    /// This is an edit rather than an optimization: the voxels are stored densely whether or not they are empty, so
+    /// the copy uses as much memory as the model, and its mesh gains the faces on the inside of the shell.
+    pub fn hollow(&self, thickness: u32) -> VoxelData {
+        let size = self._size();
+        let mut hollowed = self.clone();
+        if size.cmple(IVec3::ZERO).any() {
+            return hollowed;
+        }
+        let thickness = thickness.max(1);
+        let cells = self.cells();
+        let mut depths = vec![u32::MAX; cells.len()];
+        let mut queue: VecDeque<IVec3> = VecDeque::new();
+        for position in positions(size) {
+            let index = cell_index(size, position);
+            if cells[index] == RawVoxel::EMPTY {
+                depths[index] = 0;
+                queue.push_back(position);
+            } else if SIDES
+                .iter()
+                .any(|offset| !contains(size, position + *offset))
+            {
+                depths[index] = 1;
+                queue.push_back(position);
+            }
+        }
+        // breadth-first from the empty voxels, so each solid voxel learns its distance to the nearest one
+        while let Some(position) = queue.pop_front() {
+            let depth = depths[cell_index(size, position)];
+            if depth >= thickness {
+                continue;
+            }
+            for offset in SIDES {
+                let neighbor = position + offset;
+                if !contains(size, neighbor) {
+                    continue;
+                }
+                let index = cell_index(size, neighbor);
+                if depths[index] > depth + 1 {
+                    depths[index] = depth + 1;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        for position in positions(size) {
+            if depths[cell_index(size, position)] > thickness {
+                hollowed.write_raw(position, RawVoxel::EMPTY);
+            }
+        }
+        hollowed.mark_dirty(IVec3::ZERO, size);
+        hollowed
+    }
+
+    /// Returns a copy of the model with every enclosed cavity filled, the inverse of [`VoxelData::hollow`]. Empty voxels
+    /// that can't be reached from the bounds of the model through other empty face-adjacent voxels are filled with the
+    /// nearest voxel of the surrounding shell.
+    pub fn solidify(&self) -> VoxelData {
+        let size = self._size();
+        let mut solidified = self.clone();
+        if size.cmple(IVec3::ZERO).any() {
+            return solidified;
+        }
+        let mut cells = self.cells();
+        // flood the empty voxels reachable from outside of the model
+        let mut outside = vec![false; cells.len()];
+        let mut queue: VecDeque<IVec3> = positions(size)
+            .filter(|position| {
+                cells[cell_index(size, *position)] == RawVoxel::EMPTY
+                    && SIDES
+                        .iter()
+                        .any(|offset| !contains(size, *position + *offset))
+            })
+            .collect();
+        for position in queue.iter() {
+            outside[cell_index(size, *position)] = true;
+        }
+        while let Some(position) = queue.pop_front() {
+            for offset in SIDES {
+                let neighbor = position + offset;
+                if !contains(size, neighbor) {
+                    continue;
+                }
+                let index = cell_index(size, neighbor);
+                if !outside[index] && cells[index] == RawVoxel::EMPTY {
+                    outside[index] = true;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        // grow the shell into the cavities, so each enclosed voxel takes the nearest solid voxel
+        let mut queue: VecDeque<IVec3> = positions(size)
+            .filter(|position| cells[cell_index(size, *position)] != RawVoxel::EMPTY)
+            .collect();
+        while let Some(position) = queue.pop_front() {
+            let voxel = cells[cell_index(size, position)].clone();
+            for offset in SIDES {
+                let neighbor = position + offset;
+                if !contains(size, neighbor) {
+                    continue;
+                }
+                let index = cell_index(size, neighbor);
+                if !outside[index] && cells[index] == RawVoxel::EMPTY {
+                    cells[index] = voxel.clone();
+                    solidified.write_raw(neighbor, voxel.clone());
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        solidified.mark_dirty(IVec3::ZERO, size);
+        solidified
+    }
+
+    /// The voxels of the model without padding, indexed with [`cell_index`]
+    fn cells(&self) -> Vec<RawVoxel> {
+        let size = self._size();
+        let leading_padding = UVec3::splat(self.padding() / 2);
+        positions(size)
+            .map(|position| {
+                self.voxel_at_index(
+                    self.shape
+                        .linearize((position.as_uvec3() + leading_padding).into())
+                        as usize,
+                )
+                .clone()
+            })
+            .collect()
+    }
+}
+
+impl VoxelModel {
+    /// Returns a copy of the voxel data of the model hollowed out to a shell `thickness` voxels thick. Create a new model
+    /// from the data with [`VoxelModel::new`]. See [`VoxelData::hollow`].
+    pub fn hollow(&self, thickness: u32) -> VoxelData {
+        self.data.hollow(thickness)
+    }
+
+    /// Returns a copy of the voxel data of the model with its enclosed cavities filled. Create a new model from the data
+    /// with [`VoxelModel::new`]. See [`VoxelData::solidify`].
+    pub fn solidify(&self) -> VoxelData {
+        self.data.solidify()
+    }
+}
+
+/// Every position within a model of `size`, in the order of [`cell_index`]
+fn positions(size: IVec3) -> impl Iterator<Item = IVec3> {
+    (0..size.z).flat_map(move |z| {
+        (0..size.y).flat_map(move |y| (0..size.x).map(move |x| IVec3::new(x, y, z)))
+    })
+}
+
+fn cell_index(size: IVec3, position: IVec3) -> usize {
+    (position.x + size.x * (position.y + size.y * position.z)) as usize
+}
+
+fn contains(size: IVec3, position: IVec3) -> bool {
+    position.cmpge(IVec3::ZERO).all() && position.cmplt(size).all()
+}
@@ -0,0 +1,93 @@
+use std::borrow::Cow;
+
+use bevy::{
+    core::Name,
+    ecs::{
+        bundle::Bundle,
+        entity::Entity,
+        system::{Commands, EntityCommands},
+    },
+    render::{prelude::SpatialBundle, view::Visibility},
+    transform::components::Transform,
+};
+
+use crate::{VoxelLayer, VoxelModelInstance};
+
+use super::swap::SwapVoxelModelCommandsExt;
+
+impl VoxelModelInstance {
+    /// Spawns an entity displaying the model, with the mesh, material, transform and visibility components needed to
+    /// render it, returning a [`VoxelModelInstanceBuilder`] for configuring the entity further.
+    ///
+    /// The model must already be loaded, which is always the case for models created with [`crate::VoxelModel::new`].
+    ///
+    /// ```no_run
+    /// # use bevy::prelude::*;
+    /// # use bevy_vox_scene::{VoxelLayer, VoxelModelInstance};
+    /// # fn spawn(mut commands: Commands, instance: VoxelModelInstance) {
+    /// instance
+    ///     .spawn(&mut commands)
+    ///     .with_name("boulder")
+    ///     .with_layer(VoxelLayer { id: 1, name: None })
+    ///     .at(Transform::from_xyz(0.0, 4.0, 0.0));
+    /// # }
+    /// ```
+    pub fn spawn<'a>(self, commands: &'a mut Commands) -> VoxelModelInstanceBuilder<'a> {
+        let model = self.model.clone();
+        let entity = commands.spawn((self, SpatialBundle::default())).id();
+        // fills in the mesh and material of the model
+        commands.swap_voxel_model(entity, model);
+        VoxelModelInstanceBuilder {
+            entity: commands.entity(entity),
+        }
+    }
+}
+
+/// Configures an entity spawned with [`VoxelModelInstance::spawn`]
+pub struct VoxelModelInstanceBuilder<'a> {
+    entity: EntityCommands<'a>,
+}
+
+impl<'a> VoxelModelInstanceBuilder<'a> {
+    /// Places the instance with the supplied transform
+    pub fn at(mut self, transform: Transform) -> Self {
+        // the spatial bundle already holds the global transform, which is propagated from this
+        self.entity.insert(transform);
+        self
+    }
+
+    /// Names the instance
+    pub fn with_name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.entity.insert(Name::new(name));
+        self
+    }
+
+    /// Assigns the instance to a layer, as instances spawned from `.vox` files are
+    pub fn with_layer(mut self, layer: VoxelLayer) -> Self {
+        self.entity.insert(layer);
+        self
+    }
+
+    /// Sets whether the instance is visible. Instances are visible by default.
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.entity.insert(visibility);
+        self
+    }
+
+    /// Adds the collider of the physics engine of your choice to the instance, for instance one built from the model's
+    /// mesh
+    pub fn with_collider(mut self, collider: impl Bundle) -> Self {
+        self.entity.insert(collider);
+        self
+    }
+
+    /// The entity of the instance
+    pub fn id(&self) -> Entity {
+        self.entity.id()
+    }
+
+    /// The [`EntityCommands`] of the instance, for inserting any other components
+    pub fn entity_commands(&mut self) -> &mut EntityCommands<'a> {
+        &mut self.entity
+    }
+}
@@ -0,0 +1,239 @@
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    asset::{load_internal_asset, Asset, AssetEvent, AssetId, Assets, Handle},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::EventReader,
+        query::With,
+        removal_detection::RemovedComponents,
+        system::{Commands, Query, Res, ResMut},
+        world::Ref,
+    },
+    math::Vec4,
+    pbr::{ExtendedMaterial, MaterialExtension, MaterialPlugin, StandardMaterial},
+    prelude::{AlphaMode, ReflectComponent},
+    reflect::{Reflect, TypePath},
+    render::render_resource::{AsBindGroup, Shader, ShaderRef},
+    utils::HashSet,
+};
+
+use crate::VoxelModelInstance;
+
+use super::VoxelModel;
+
+const INSTANCE_MATERIAL_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x8a41_c6f2_93d0_4e5b_b718_2c94_e06f_5a3d);
+const INSTANCE_PREPASS_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x3c5e_81a7_d24f_49b6_9e03_7fb1_c8d2_6e14);
+
+/// The material drawing instances with [`VoxelInstanceMaterialParams`]: the model's [`StandardMaterial`], extended with
+/// the parameters of the instance
+pub type VoxelInstanceMaterial = ExtendedMaterial<StandardMaterial, VoxelInstanceExtension>;
+
+/// Plugin letting gameplay state change how individual [`VoxelModelInstance`]s are drawn without remeshing them. Add it
+/// alongside [`crate::VoxScenePlugin`], then add a [`VoxelInstanceMaterialParams`] to any instance.
+pub struct VoxelInstanceMaterialPlugin;
+
+impl Plugin for VoxelInstanceMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            INSTANCE_MATERIAL_SHADER_HANDLE,
+            "instance_material.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            INSTANCE_PREPASS_SHADER_HANDLE,
+            "instance_prepass.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(MaterialPlugin::<VoxelInstanceMaterial>::default())
+            .register_type::<VoxelInstanceMaterialParams>()
+            .add_systems(PostUpdate, update_instance_materials);
+        #[cfg(feature = "modify_voxels")]
+        {
+            use bevy::ecs::schedule::IntoSystemConfigs;
+            app.add_systems(
+                PostUpdate,
+                damage_from_integrity
+                    .after(super::integrity::update_voxel_integrity)
+                    .before(update_instance_materials),
+            );
+        }
+    }
+}
+
+/// Scalars that modulate the material of a single [`VoxelModelInstance`], set from gameplay code every frame if need
+/// be. Requires the [`VoxelInstanceMaterialPlugin`].
+///
+/// Each instance with the component is drawn with its own [`VoxelInstanceMaterial`], built from the model's material
+/// and rebuilt whenever that material changes. Changes to the parameters only update the instance's material, so the
+/// mesh is never rebuilt. The cracks and the dissolve pattern are computed from world positions, so they suit
+/// instances that stay still, such as buildings. Dissolving instances are drawn alpha masked, so that the prepasses and
+/// shadows are dissolved too. Removing the component restores the model's material.
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct VoxelInstanceMaterialParams {
+    /// How damaged the instance looks, from 0 to 1, darkening the voxels and overlaying cracks
+    pub damage: f32,
+    /// How wet the instance looks, from 0 to 1, darkening the voxels and making them glossier
+    pub wetness: f32,
+    /// The fraction of the instance that has dissolved away, from 0 to 1, discarding the surface in a noisy pattern
+    pub dissolve: f32,
+    /// The number of crack and dissolve cells per world unit. Defaults to 4.
+    pub pattern_scale: f32,
+    /// When true, `damage` follows the [`crate::VoxelIntegrity::destroyed_fraction`] of the instance, so that models
+    /// look more damaged as voxels are removed from them. Defaults to false.
+    pub damage_from_integrity: bool,
+}
+
+impl Default for VoxelInstanceMaterialParams {
+    fn default() -> Self {
+        Self {
+            damage: 0.0,
+            wetness: 0.0,
+            dissolve: 0.0,
+            pattern_scale: 4.0,
+            damage_from_integrity: false,
+        }
+    }
+}
+
+impl VoxelInstanceMaterialParams {
+    fn uniform(&self) -> Vec4 {
+        Vec4::new(
+            self.damage.clamp(0.0, 1.0),
+            self.wetness.clamp(0.0, 1.0),
+            self.dissolve.clamp(0.0, 1.0),
+            self.pattern_scale,
+        )
+    }
+}
+
+/// The extension to [`StandardMaterial`] holding the [`VoxelInstanceMaterialParams`] of an instance
+#[derive(Asset, TypePath, AsBindGroup, Clone, Debug, Default)]
+pub struct VoxelInstanceExtension {
+    /// The damage, wetness, dissolve and pattern scale of the instance
+    #[uniform(100)]
+    pub params: Vec4,
+}
+
+impl MaterialExtension for VoxelInstanceExtension {
+    fn fragment_shader() -> ShaderRef {
+        INSTANCE_MATERIAL_SHADER_HANDLE.into()
+    }
+
+    fn prepass_fragment_shader() -> ShaderRef {
+        INSTANCE_PREPASS_SHADER_HANDLE.into()
+    }
+
+    fn deferred_fragment_shader() -> ShaderRef {
+        INSTANCE_MATERIAL_SHADER_HANDLE.into()
+    }
+}
+
+/// The `base` material of a model, extended with the parameters of an instance. Dissolving opaque materials are alpha
+/// masked, as the prepasses, including the shadow pass, only run the fragment shader that discards the dissolved
+/// surface for materials that may discard.
+fn instance_material(
+    base: &StandardMaterial,
+    params: &VoxelInstanceMaterialParams,
+) -> VoxelInstanceMaterial {
+    let mut base = base.clone();
+    if params.dissolve > 0.0 && base.alpha_mode == AlphaMode::Opaque {
+        base.alpha_mode = AlphaMode::Mask(0.5);
+    }
+    VoxelInstanceMaterial {
+        base,
+        extension: VoxelInstanceExtension {
+            params: params.uniform(),
+        },
+    }
+}
+
+pub(crate) fn update_instance_materials(
+    mut commands: Commands,
+    instances: Query<(
+        Entity,
+        Ref<VoxelModelInstance>,
+        Ref<VoxelInstanceMaterialParams>,
+        Option<&Handle<VoxelInstanceMaterial>>,
+    )>,
+    restored: Query<&VoxelModelInstance, With<Handle<VoxelInstanceMaterial>>>,
+    mut removed: RemovedComponents<VoxelInstanceMaterialParams>,
+    mut model_events: EventReader<AssetEvent<VoxelModel>>,
+    mut material_events: EventReader<AssetEvent<StandardMaterial>>,
+    models: Res<Assets<VoxelModel>>,
+    standard_materials: Res<Assets<StandardMaterial>>,
+    mut materials: ResMut<Assets<VoxelInstanceMaterial>>,
+) {
+    // the instances' materials are copies of their models' materials, so are rebuilt when those change
+    let modified_models: HashSet<AssetId<VoxelModel>> = model_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    let modified_materials: HashSet<AssetId<StandardMaterial>> = material_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (entity, instance, params, handle) in instances.iter() {
+        let Some(model) = models.get(&instance.model) else {
+            continue;
+        };
+        let handle = handle.filter(|handle| materials.contains(*handle));
+        let stale = instance.is_changed()
+            || modified_models.contains(&instance.model.id())
+            || modified_materials.contains(&model.material.id());
+        if handle.is_some() && !stale && !params.is_changed() {
+            continue;
+        }
+        let Some(base) = standard_materials.get(&model.material) else {
+            continue;
+        };
+        let material = instance_material(base, &params);
+        match handle.and_then(|handle| materials.get_mut(handle)) {
+            Some(existing) => *existing = material,
+            None => {
+                let material = materials.add(material);
+                commands
+                    .entity(entity)
+                    .remove::<Handle<StandardMaterial>>()
+                    .insert(material);
+            }
+        }
+    }
+    for entity in removed.read() {
+        let Ok(instance) = restored.get(entity) else {
+            continue;
+        };
+        let Some(model) = models.get(&instance.model) else {
+            continue;
+        };
+        commands
+            .entity(entity)
+            .remove::<Handle<VoxelInstanceMaterial>>()
+            .insert(model.material.clone());
+    }
+}
+
+#[cfg(feature = "modify_voxels")]
+pub(crate) fn damage_from_integrity(
+    mut instances: Query<(
+        &super::integrity::VoxelIntegrity,
+        &mut VoxelInstanceMaterialParams,
+    )>,
+) {
+    for (integrity, mut params) in instances.iter_mut() {
+        if params.damage_from_integrity && params.damage != integrity.destroyed_fraction {
+            params.damage = integrity.destroyed_fraction;
+        }
+    }
+}
@@ -0,0 +1,92 @@
+#import bevy_pbr::{
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::alpha_discard,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::{
+    prepass_io::{VertexOutput, FragmentOutput},
+    pbr_deferred_functions::deferred_output,
+}
+#else
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+#endif
+
+struct VoxelInstanceExtension {
+    // x: damage, y: wetness, z: dissolve, w: number of pattern cells per world unit
+    params: vec4<f32>,
+}
+
+@group(2) @binding(100) var<uniform> extension: VoxelInstanceExtension;
+
+fn hash(cell: vec3<f32>) -> f32 {
+    return fract(sin(dot(cell, vec3<f32>(127.1, 311.7, 74.7))) * 43758.5453);
+}
+
+// The distance to the nearest border between the cells of a 3D Voronoi pattern, used to draw cracks
+fn crack_distance(position: vec3<f32>) -> f32 {
+    let cell = floor(position);
+    var nearest = 8.0;
+    var second = 8.0;
+    for (var z = -1; z <= 1; z++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                let neighbor = cell + vec3<f32>(f32(x), f32(y), f32(z));
+                let seed = neighbor + vec3<f32>(
+                    hash(neighbor),
+                    hash(neighbor + 17.0),
+                    hash(neighbor + 43.0),
+                );
+                let distance = length(position - seed);
+                if distance < nearest {
+                    second = nearest;
+                    nearest = distance;
+                } else if distance < second {
+                    second = distance;
+                }
+            }
+        }
+    }
+    return second - nearest;
+}
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    let damage = extension.params.x;
+    let wetness = extension.params.y;
+    let dissolve = extension.params.z;
+    let position = in.world_position.xyz * extension.params.w;
+
+    if dissolve > 0.0 && hash(floor(position)) < dissolve {
+        discard;
+    }
+
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    // wet surfaces are darker and glossier
+    let wet_darkening = mix(1.0, 0.6, wetness);
+    pbr_input.material.perceptual_roughness = mix(pbr_input.material.perceptual_roughness, 0.15, wetness);
+
+    // damaged surfaces are darker, with cracks that widen as the damage grows
+    let crack = 1.0 - smoothstep(0.0, 0.12 * damage, crack_distance(position));
+    let damage_darkening = (1.0 - 0.35 * damage) * (1.0 - 0.8 * crack * step(0.001, damage));
+
+    let color = pbr_input.material.base_color.rgb * wet_darkening * damage_darkening;
+    pbr_input.material.base_color = vec4<f32>(color, pbr_input.material.base_color.a);
+
+#ifdef PREPASS_PIPELINE
+    let out = deferred_output(in, pbr_input);
+#else
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+#endif
+    return out;
+}
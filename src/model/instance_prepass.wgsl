@@ -0,0 +1,63 @@
+#import bevy_pbr::{
+    prepass_io::VertexOutput,
+    pbr_prepass_functions,
+}
+
+#ifdef PREPASS_FRAGMENT
+#import bevy_pbr::prepass_io::FragmentOutput
+#endif
+
+struct VoxelInstanceExtension {
+    // x: damage, y: wetness, z: dissolve, w: number of pattern cells per world unit
+    params: vec4<f32>,
+}
+
+@group(2) @binding(100) var<uniform> extension: VoxelInstanceExtension;
+
+// the same hash as in instance_material.wgsl, so that the prepasses discard the same cells as the main pass
+fn hash(cell: vec3<f32>) -> f32 {
+    return fract(sin(dot(cell, vec3<f32>(127.1, 311.7, 74.7))) * 43758.5453);
+}
+
+fn dissolve_discard(in: VertexOutput) {
+    let dissolve = extension.params.z;
+    let position = in.world_position.xyz * extension.params.w;
+    if dissolve > 0.0 && hash(floor(position)) < dissolve {
+        discard;
+    }
+    pbr_prepass_functions::prepass_alpha_discard(in);
+}
+
+#ifdef PREPASS_FRAGMENT
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    dissolve_discard(in);
+    var out: FragmentOutput;
+
+#ifdef DEPTH_CLAMP_ORTHO
+    out.frag_depth = in.clip_position_unclamped.z;
+#endif
+
+#ifdef NORMAL_PREPASS
+    // the faces of voxels are flat, so the interpolated normal is the normal of the face
+    out.normal = vec4(normalize(in.world_normal) * 0.5 + vec3(0.5), 1.0);
+#endif
+
+#ifdef MOTION_VECTOR_PREPASS
+    out.motion_vector = pbr_prepass_functions::calculate_motion_vector(
+        in.world_position,
+        in.previous_world_position,
+    );
+#endif
+
+    return out;
+}
+#else
+@fragment
+fn fragment(in: VertexOutput) {
+    dissolve_discard(in);
+}
+#endif
@@ -0,0 +1,102 @@
+use bevy::{
+    asset::{AssetEvent, AssetId, Assets},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventReader, EventWriter},
+        system::{Query, Res},
+    },
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    utils::HashSet,
+};
+
+use crate::VoxelModelInstance;
+
+use super::VoxelModel;
+
+/// Tracks how much of a [`VoxelModelInstance`] has been destroyed by edits, for destruction-based health systems.
+///
+/// Add it to an entity holding a [`VoxelModelInstance`]. The number of solid voxels in the model when the component is
+/// first updated is taken as the intact state, and whenever the model is modified, `destroyed_fraction` is updated and a
+/// [`VoxelIntegrityThresholdCrossed`] event is sent for each threshold it rises past.
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VoxelIntegrity {
+    /// The fraction of the original solid voxels that have been removed, from 0 (intact) to 1 (destroyed)
+    pub destroyed_fraction: f32,
+    /// The destroyed fractions at which events are sent, for instance `[0.25, 0.5, 0.9]` for successive damage states
+    pub thresholds: Vec<f32>,
+    original: Option<usize>,
+}
+
+impl VoxelIntegrity {
+    /// Creates an intact integrity component that sends events at the supplied `thresholds`
+    pub fn new(thresholds: impl IntoIterator<Item = f32>) -> Self {
+        Self {
+            destroyed_fraction: 0.0,
+            thresholds: thresholds.into_iter().collect(),
+            original: None,
+        }
+    }
+
+    /// The number of solid voxels in the intact model, once the component has been updated
+    pub fn original_voxel_count(&self) -> Option<usize> {
+        self.original
+    }
+}
+
+/// Sent when the [`VoxelIntegrity::destroyed_fraction`] of an instance rises past one of its thresholds
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct VoxelIntegrityThresholdCrossed {
+    /// The entity holding the [`VoxelIntegrity`]
+    pub entity: Entity,
+    /// The threshold that was crossed
+    pub threshold: f32,
+    /// The destroyed fraction after the edit
+    pub destroyed_fraction: f32,
+}
+
+pub(crate) fn update_voxel_integrity(
+    mut model_events: EventReader<AssetEvent<VoxelModel>>,
+    mut crossed: EventWriter<VoxelIntegrityThresholdCrossed>,
+    mut instances: Query<(Entity, &VoxelModelInstance, &mut VoxelIntegrity)>,
+    models: Res<Assets<VoxelModel>>,
+) {
+    let modified: HashSet<AssetId<VoxelModel>> = model_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (entity, instance, mut integrity) in instances.iter_mut() {
+        if integrity.original.is_some() && !modified.contains(&instance.model.id()) {
+            continue;
+        }
+        let Some(model) = models.get(&instance.model) else {
+            continue;
+        };
+        let count = model.count_voxels();
+        let Some(original) = integrity.original else {
+            integrity.original = Some(count);
+            continue;
+        };
+        let destroyed_fraction = if original == 0 {
+            0.0
+        } else {
+            (1.0 - count as f32 / original as f32).clamp(0.0, 1.0)
+        };
+        let previous = integrity.destroyed_fraction;
+        for threshold in integrity.thresholds.iter() {
+            if previous < *threshold && destroyed_fraction >= *threshold {
+                crossed.send(VoxelIntegrityThresholdCrossed {
+                    entity,
+                    threshold: *threshold,
+                    destroyed_fraction,
+                });
+            }
+        }
+        integrity.destroyed_fraction = destroyed_fraction;
+    }
+}
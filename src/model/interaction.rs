@@ -0,0 +1,311 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    color::{palettes::css, Alpha, Color},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventWriter},
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput},
+    math::{primitives::Cuboid, IVec3, Quat, Ray3d, Vec3},
+    pbr::{PbrBundle, StandardMaterial},
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    render::{alpha::AlphaMode, mesh::Mesh, view::Visibility},
+    transform::components::{GlobalTransform, Transform},
+};
+
+use crate::VoxelModelInstance;
+
+use super::{
+    modify::{ModifyVoxelCommandsExt, VoxelRegion, VoxelRegionMode},
+    Voxel, VoxelModel, VoxelQueryable,
+};
+
+/// Plugin adding ready-made block placement and removal for prototyping block games. Add it alongside
+/// [`crate::VoxScenePlugin`], then add a [`VoxelInteractor`] to the camera.
+///
+/// Each update, a ray is cast along the forward axis of every interactor, and the nearest voxel of any
+/// [`VoxelModelInstance`] within reach becomes its [`VoxelInteractor::target`], with the face it hits highlighted.
+/// Pressing the remove button clears the targeted voxel, and pressing the place button writes the selected voxel into
+/// the empty cell in front of the targeted face. The chunks of a [`crate::VoxelWorld`] are edited through the world,
+/// so placed voxels can cross into neighboring chunks. A [`VoxelInteraction`] event is sent for each edit.
+pub struct VoxelInteractionPlugin;
+
+impl Plugin for VoxelInteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<VoxelInteractor>()
+            .add_event::<VoxelInteraction>()
+            .add_systems(Update, update_voxel_interactors);
+    }
+}
+
+/// Lets the player target, place and remove voxels by looking at them. See [`VoxelInteractionPlugin`].
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VoxelInteractor {
+    /// The furthest distance at which voxels can be targeted, in global space. Defaults to 8.
+    pub reach: f32,
+    /// The voxels that can be placed. Defaults to the first 9 palette indices.
+    pub palette: Vec<Voxel>,
+    /// The index within [`VoxelInteractor::palette`] of the voxel that is placed
+    pub selected: usize,
+    /// Whether the digit keys 1 to 9 select from the palette. Defaults to true.
+    pub select_with_digits: bool,
+    /// The mouse button that places the selected voxel. Defaults to [`MouseButton::Right`].
+    pub place_button: MouseButton,
+    /// The mouse button that removes the targeted voxel. Defaults to [`MouseButton::Left`].
+    pub remove_button: MouseButton,
+    /// The color of the highlight drawn over the targeted face. Defaults to translucent white.
+    pub highlight_color: Color,
+    /// The voxel being looked at, updated every frame
+    pub target: Option<VoxelTarget>,
+    #[reflect(ignore)]
+    highlight: Option<Entity>,
+}
+
+impl Default for VoxelInteractor {
+    fn default() -> Self {
+        Self {
+            reach: 8.0,
+            palette: (1..=9).map(Voxel).collect(),
+            selected: 0,
+            select_with_digits: true,
+            place_button: MouseButton::Right,
+            remove_button: MouseButton::Left,
+            highlight_color: Color::from(css::WHITE).with_alpha(0.4),
+            target: None,
+            highlight: None,
+        }
+    }
+}
+
+impl VoxelInteractor {
+    /// Creates an interactor with the supplied reach
+    pub fn new(reach: f32) -> Self {
+        Self {
+            reach,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the voxels that can be placed
+    pub fn with_palette(mut self, palette: Vec<Voxel>) -> Self {
+        self.palette = palette;
+        self.selected = 0;
+        self
+    }
+
+    /// The voxel that is placed, or `None` if the palette is empty
+    pub fn selected_voxel(&self) -> Option<Voxel> {
+        self.palette.get(self.selected).cloned()
+    }
+}
+
+/// The voxel targeted by a [`VoxelInteractor`]
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub struct VoxelTarget {
+    /// The entity holding the [`VoxelModelInstance`] that was hit
+    pub entity: Entity,
+    /// The coordinate of the targeted voxel, in the voxel space of the model
+    pub voxel_coord: IVec3,
+    /// The targeted voxel
+    pub voxel: Voxel,
+    /// The normal of the targeted face, in the voxel space of the model. This is zero if the interactor is inside the
+    /// voxel.
+    pub normal: IVec3,
+    /// The point where the ray hit the voxel, in global space
+    pub point: Vec3,
+}
+
+impl VoxelTarget {
+    /// The coordinate of the cell in front of the targeted face, where voxels are placed
+    pub fn adjacent_coord(&self) -> IVec3 {
+        self.voxel_coord + self.normal
+    }
+}
+
+/// What a [`VoxelInteraction`] did
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoxelInteractionKind {
+    /// A voxel was placed
+    Place,
+    /// A voxel was removed
+    Remove,
+}
+
+/// Sent when a [`VoxelInteractor`] places or removes a voxel
+#[derive(Event, Clone, Debug)]
+pub struct VoxelInteraction {
+    /// The interactor that made the edit
+    pub interactor: Entity,
+    /// The entity holding the [`VoxelModelInstance`] that was edited
+    pub entity: Entity,
+    /// The coordinate of the edited voxel, in the voxel space of the model
+    pub voxel_coord: IVec3,
+    /// The voxel that was placed, or that was removed
+    pub voxel: Voxel,
+    /// Whether the voxel was placed or removed
+    pub kind: VoxelInteractionKind,
+}
+
+const DIGITS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+#[allow(clippy::too_many_arguments)]
+fn update_voxel_interactors(
+    mut commands: Commands,
+    mut interactors: Query<(Entity, &GlobalTransform, &mut VoxelInteractor)>,
+    instances: Query<(Entity, &VoxelModelInstance, &GlobalTransform)>,
+    mut highlights: Query<(&mut Transform, &mut Visibility)>,
+    models: Res<Assets<VoxelModel>>,
+    mouse: Option<Res<ButtonInput<MouseButton>>>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut events: EventWriter<VoxelInteraction>,
+    #[cfg(feature = "generate_voxels")] mut world: Option<ResMut<super::world::VoxelWorld>>,
+    #[cfg(feature = "generate_voxels")] chunks: Query<&super::world::VoxelWorldChunk>,
+) {
+    for (interactor_entity, interactor_xform, mut interactor) in interactors.iter_mut() {
+        if interactor.select_with_digits {
+            if let Some(keys) = keys.as_ref() {
+                if let Some(digit) = DIGITS.iter().position(|key| keys.just_pressed(*key)) {
+                    if digit < interactor.palette.len() {
+                        interactor.selected = digit;
+                    }
+                }
+            }
+        }
+
+        let ray = Ray3d::new(interactor_xform.translation(), interactor_xform.forward());
+        let target = instances
+            .iter()
+            .filter_map(|(entity, instance, global_xform)| {
+                let model = models.get(&instance.model)?;
+                let hit = model.raycast(ray, global_xform, interactor.reach)?;
+                Some((entity, model, global_xform, hit))
+            })
+            .min_by(|a, b| a.3.distance.total_cmp(&b.3.distance));
+
+        // highlight the targeted face
+        if interactor.highlight.is_none() {
+            let highlight = commands
+                .spawn(PbrBundle {
+                    mesh: meshes.add(Cuboid::new(1.0, 1.0, 0.02)),
+                    material: materials.add(StandardMaterial {
+                        base_color: interactor.highlight_color,
+                        alpha_mode: AlphaMode::Blend,
+                        unlit: true,
+                        ..Default::default()
+                    }),
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                })
+                .id();
+            interactor.highlight = Some(highlight);
+        }
+        if let Some((mut transform, mut visibility)) = interactor
+            .highlight
+            .and_then(|highlight| highlights.get_mut(highlight).ok())
+        {
+            match target.as_ref() {
+                Some((_, model, global_xform, hit)) if hit.normal != IVec3::ZERO => {
+                    let grid = model.grid();
+                    let voxel_size = grid.voxel_size;
+                    let center = grid.cell_center_world(hit.voxel_coord)
+                        + hit.normal.as_vec3() * 0.51 * voxel_size;
+                    let local = Transform::from_translation(center)
+                        .with_rotation(Quat::from_rotation_arc(Vec3::Z, hit.normal.as_vec3()))
+                        .with_scale(Vec3::splat(voxel_size));
+                    *transform = global_xform.mul_transform(local).compute_transform();
+                    *visibility = Visibility::Visible;
+                }
+                _ => *visibility = Visibility::Hidden,
+            }
+        }
+
+        interactor.target = target.as_ref().map(|(entity, _, _, hit)| VoxelTarget {
+            entity: *entity,
+            voxel_coord: hit.voxel_coord,
+            voxel: hit.voxel.clone(),
+            normal: hit.normal,
+            point: hit.point,
+        });
+
+        // edit the targeted voxel
+        let Some(mouse) = mouse.as_ref() else {
+            continue;
+        };
+        let Some(target) = interactor.target.clone() else {
+            continue;
+        };
+        let (coord, voxel, kind) = if mouse.just_pressed(interactor.remove_button) {
+            (
+                target.voxel_coord,
+                target.voxel.clone(),
+                VoxelInteractionKind::Remove,
+            )
+        } else if mouse.just_pressed(interactor.place_button) && target.normal != IVec3::ZERO {
+            let Some(voxel) = interactor.selected_voxel() else {
+                continue;
+            };
+            (target.adjacent_coord(), voxel, VoxelInteractionKind::Place)
+        } else {
+            continue;
+        };
+        let written = match kind {
+            VoxelInteractionKind::Place => voxel.clone(),
+            VoxelInteractionKind::Remove => Voxel::EMPTY,
+        };
+
+        #[cfg(feature = "generate_voxels")]
+        if let (Some(world), Ok(chunk)) = (world.as_mut(), chunks.get(target.entity)) {
+            world.set_voxel(chunk.coord * world.chunk_size().as_ivec3() + coord, written);
+            events.send(VoxelInteraction {
+                interactor: interactor_entity,
+                entity: target.entity,
+                voxel_coord: coord,
+                voxel,
+                kind,
+            });
+            continue;
+        }
+
+        let Ok((_, instance, _)) = instances.get(target.entity) else {
+            continue;
+        };
+        let in_bounds = models
+            .get(&instance.model)
+            .is_some_and(|model| model.point_in_model(coord).is_ok());
+        if !in_bounds {
+            continue;
+        }
+        commands.modify_voxel_model(
+            instance.clone(),
+            VoxelRegionMode::Box(VoxelRegion {
+                origin: coord,
+                size: IVec3::ONE,
+            }),
+            move |_, _, _| written.clone(),
+        );
+        events.send(VoxelInteraction {
+            interactor: interactor_entity,
+            entity: target.entity,
+            voxel_coord: coord,
+            voxel,
+            kind,
+        });
+    }
+}
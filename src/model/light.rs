@@ -0,0 +1,49 @@
+use bevy::math::{IVec3, Vec3};
+use ndshape::Shape;
+
+use super::VoxelData;
+
+/// The brightest light level, given to voxels open to the sky and to fully emissive voxels
+pub(crate) const MAX_LIGHT_LEVEL: u8 = 15;
+
+/// The fraction of brightness kept with each step away from a light source
+const LIGHT_FALLOFF: f32 = 0.8;
+
+/// Light levels from 0 to [`MAX_LIGHT_LEVEL`] for every voxel of a [`VoxelData`], including its padding, that are
+/// baked into the vertex colors of its meshes
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct VoxelLightLevels {
+    pub(crate) levels: Vec<u8>,
+    /// The brightness of faces in complete darkness
+    pub(crate) ambient: f32,
+}
+
+impl VoxelLightLevels {
+    /// The brightness of a face with the voxel at `position` in front of it, in the padded space of the `data`
+    pub(crate) fn brightness(&self, data: &VoxelData, position: IVec3) -> f32 {
+        let size = IVec3::from(data.shape.as_array().map(|x| x as i32));
+        if position.cmplt(IVec3::ZERO).any() || position.cmpge(size).any() {
+            return 1.0;
+        }
+        let level = self
+            .levels
+            .get(data.shape.linearize(position.as_uvec3().into()) as usize)
+            .copied()
+            .unwrap_or(MAX_LIGHT_LEVEL)
+            .min(MAX_LIGHT_LEVEL);
+        let lit = LIGHT_FALLOFF.powi((MAX_LIGHT_LEVEL - level) as i32);
+        lit + self.ambient * (1.0 - lit)
+    }
+
+    /// The brightness of a quad of a mesh generated from the `data`, given the padded voxel position of its `minimum`
+    /// corner and its `normal`
+    pub(crate) fn face_brightness(
+        &self,
+        data: &VoxelData,
+        minimum: [u32; 3],
+        normal: [f32; 3],
+    ) -> f32 {
+        let normal = Vec3::from(normal).round().as_ivec3();
+        self.brightness(data, IVec3::from(minimum.map(|x| x as i32)) + normal)
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::VecDeque;
+
+use bevy::{math::IVec3, utils::HashMap};
+use ndshape::Shape;
+
+use super::{
+    light::{VoxelLightLevels, MAX_LIGHT_LEVEL},
+    world::VoxelWorld,
+    RawVoxel, VoxelPalette, SIDES,
+};
+
+/// Settings for baking classic blocky lighting into the vertex colors of the chunks of a [`VoxelWorld`]. Add them with
+/// [`VoxelWorld::with_lighting`].
+///
+/// Each voxel is given a sky light level and a block light level from 0 to 15. Voxels open to the sky have full sky
+/// light, which shines straight down until it hits an opaque voxel. Emissive voxels give off block light in proportion
+/// to their [`crate::VoxelElement::emission`]. Both spread into the neighboring empty and translucent voxels, losing
+/// one level with each step, so light crosses chunk borders and reaches at most 15 voxels from its source. Each face
+/// is shaded by the brighter of the two levels of the voxel in front of it.
+///
+/// When voxels are written, only the chunks within reach of the change are relit and remeshed. As each face is shaded
+/// individually, the faces of lit chunks aren't merged by the greedy mesher, so their meshes have more vertices.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelWorldLighting {
+    /// Whether voxels open to the sky are lit. Defaults to true. Disable it for caves and interiors lit only by
+    /// emissive voxels.
+    pub sky_light: bool,
+    /// The brightness of faces in complete darkness, from 0 to 1. Defaults to 0.05.
+    pub ambient: f32,
+}
+
+impl Default for VoxelWorldLighting {
+    fn default() -> Self {
+        Self {
+            sky_light: true,
+            ambient: 0.05,
+        }
+    }
+}
+
+impl VoxelWorld {
+    /// Bakes light into the chunks. See [`VoxelWorldLighting`].
+    pub fn with_lighting(mut self, lighting: VoxelWorldLighting) -> Self {
+        self.lighting = Some(lighting);
+        self
+    }
+
+    /// Recomputes the light of every chunk within reach of a dirty chunk, and marks them dirty so that they are
+    /// remeshed
+    pub(super) fn relight(&mut self, palette: &VoxelPalette) {
+        let Some(lighting) = self.lighting.clone() else {
+            return;
+        };
+        let reach = MAX_LIGHT_LEVEL as i32;
+        let chunk_size = self.chunk_size;
+        // group nearby dirty chunks, so that each group is lit in a single pass
+        let mut groups: Vec<(IVec3, IVec3)> = Vec::new();
+        for coord in self
+            .chunks
+            .iter()
+            .filter(|(_, state)| state.dirty)
+            .map(|(coord, _)| *coord)
+        {
+            let min = (coord * chunk_size - IVec3::splat(reach)).div_euclid(chunk_size);
+            let max = ((coord + IVec3::ONE) * chunk_size + IVec3::splat(reach - 1))
+                .div_euclid(chunk_size);
+            match groups.iter_mut().find(|(group_min, group_max)| {
+                (min - IVec3::ONE).cmple(*group_max).all()
+                    && (max + IVec3::ONE).cmpge(*group_min).all()
+            }) {
+                Some((group_min, group_max)) => {
+                    *group_min = group_min.min(min);
+                    *group_max = group_max.max(max);
+                }
+                None => groups.push((min, max)),
+            }
+        }
+        for (min, max) in groups {
+            self.relight_chunks(palette, &lighting, min, max);
+        }
+    }
+
+    /// Relights the chunks from `min_chunk` to `max_chunk` (inclusive)
+    fn relight_chunks(
+        &mut self,
+        palette: &VoxelPalette,
+        lighting: &VoxelWorldLighting,
+        min_chunk: IVec3,
+        max_chunk: IVec3,
+    ) {
+        let reach = MAX_LIGHT_LEVEL as i32;
+        let chunk_size = self.chunk_size;
+        let affected: Vec<IVec3> = self
+            .chunks
+            .keys()
+            .filter(|coord| coord.cmpge(min_chunk).all() && coord.cmple(max_chunk).all())
+            .copied()
+            .collect();
+        if affected.is_empty() {
+            return;
+        }
+        // every voxel that can light the affected chunks, including their padding
+        let min = min_chunk * chunk_size - IVec3::splat(reach + 1);
+        let max = (max_chunk + IVec3::ONE) * chunk_size + IVec3::splat(reach + 1);
+        let volume = LightVolume::new(min, max);
+        let mut opaque = vec![false; volume.len()];
+        let mut block = vec![0_u8; volume.len()];
+        let mut queue: VecDeque<IVec3> = VecDeque::new();
+        let emission: Vec<u8> = palette
+            .elements
+            .iter()
+            .map(|element| (element.emission.clamp(0.0, 1.0) * MAX_LIGHT_LEVEL as f32).ceil() as u8)
+            .collect();
+        for (coord, state) in self.chunks.iter() {
+            let origin = *coord * chunk_size;
+            let from = origin.max(min);
+            let to = (origin + chunk_size).min(max);
+            if from.cmpge(to).any() {
+                continue;
+            }
+            for z in from.z..to.z {
+                for y in from.y..to.y {
+                    for x in from.x..to.x {
+                        let position = IVec3::new(x, y, z);
+                        let local = (position - origin + IVec3::ONE).as_uvec3();
+                        let raw = state
+                            .data
+                            .voxel_at_index(state.data.shape.linearize(local.into()) as usize);
+                        if *raw == RawVoxel::EMPTY {
+                            continue;
+                        }
+                        let index = volume.index(position);
+                        opaque[index] = !palette
+                            .indices_of_refraction
+                            .get(raw.0 as usize)
+                            .is_some_and(|ior| ior.is_some());
+                        let level = emission.get(raw.0 as usize).copied().unwrap_or(0);
+                        if level > 0 {
+                            block[index] = level;
+                            queue.push_back(position);
+                        }
+                    }
+                }
+            }
+        }
+        propagate(&volume, &opaque, &mut block, queue);
+
+        let mut sky = vec![0_u8; volume.len()];
+        if lighting.sky_light {
+            let mut queue: VecDeque<IVec3> = VecDeque::new();
+            // the chunks above the volume, which may block the sky
+            let mut above: HashMap<(i32, i32), Vec<IVec3>> = HashMap::new();
+            for coord in self.chunks.keys() {
+                if (*coord + IVec3::ONE).y * chunk_size.y > max.y {
+                    above.entry((coord.x, coord.z)).or_default().push(*coord);
+                }
+            }
+            for z in min.z..max.z {
+                for x in min.x..max.x {
+                    let column = IVec3::new(x, 0, z).div_euclid(chunk_size);
+                    let covered = above.get(&(column.x, column.z)).is_some_and(|coords| {
+                        coords
+                            .iter()
+                            .any(|coord| self.column_blocked(palette, *coord, x, z, max.y))
+                    });
+                    if covered {
+                        continue;
+                    }
+                    for y in (min.y..max.y).rev() {
+                        let position = IVec3::new(x, y, z);
+                        let index = volume.index(position);
+                        if opaque[index] {
+                            break;
+                        }
+                        sky[index] = MAX_LIGHT_LEVEL;
+                        queue.push_back(position);
+                    }
+                }
+            }
+            propagate(&volume, &opaque, &mut sky, queue);
+        }
+
+        let padded = chunk_size + IVec3::splat(2);
+        for coord in affected {
+            let Some(state) = self.chunks.get_mut(&coord) else {
+                continue;
+            };
+            let origin = coord * chunk_size - IVec3::ONE;
+            let mut levels = vec![0_u8; state.data.shape.size() as usize];
+            for z in 0..padded.z {
+                for y in 0..padded.y {
+                    for x in 0..padded.x {
+                        let local = IVec3::new(x, y, z);
+                        let index = volume.index(origin + local);
+                        levels[state.data.shape.linearize(local.as_uvec3().into()) as usize] =
+                            sky[index].max(block[index]);
+                    }
+                }
+            }
+            state.data.light = Some(VoxelLightLevels {
+                levels,
+                ambient: lighting.ambient,
+            });
+            state.dirty = true;
+        }
+    }
+
+    /// Whether the chunk at `coord` has an opaque voxel in the column at `x`, `z`, at or above the global height
+    /// `above`
+    fn column_blocked(
+        &self,
+        palette: &VoxelPalette,
+        coord: IVec3,
+        x: i32,
+        z: i32,
+        above: i32,
+    ) -> bool {
+        let Some(state) = self.chunks.get(&coord) else {
+            return false;
+        };
+        let origin = coord * self.chunk_size;
+        let from = (above - origin.y).max(0);
+        (from..self.chunk_size.y).any(|y| {
+            let local = (IVec3::new(x, origin.y + y, z) - origin + IVec3::ONE).as_uvec3();
+            let raw = state
+                .data
+                .voxel_at_index(state.data.shape.linearize(local.into()) as usize);
+            *raw != RawVoxel::EMPTY
+                && !palette
+                    .indices_of_refraction
+                    .get(raw.0 as usize)
+                    .is_some_and(|ior| ior.is_some())
+        })
+    }
+}
+
+/// A box of global voxel positions that light is propagated through
+struct LightVolume {
+    min: IVec3,
+    size: IVec3,
+}
+
+impl LightVolume {
+    fn new(min: IVec3, max: IVec3) -> Self {
+        Self {
+            min,
+            size: (max - min).max(IVec3::ZERO),
+        }
+    }
+
+    fn len(&self) -> usize {
+        (self.size.x * self.size.y * self.size.z) as usize
+    }
+
+    fn contains(&self, position: IVec3) -> bool {
+        let local = position - self.min;
+        local.cmpge(IVec3::ZERO).all() && local.cmplt(self.size).all()
+    }
+
+    fn index(&self, position: IVec3) -> usize {
+        let local = position - self.min;
+        (local.x + local.y * self.size.x + local.z * self.size.x * self.size.y) as usize
+    }
+}
+
+/// Spreads the light from the voxels in the `queue` through the volume, losing a level with each step
+fn propagate(volume: &LightVolume, opaque: &[bool], levels: &mut [u8], mut queue: VecDeque<IVec3>) {
+    while let Some(position) = queue.pop_front() {
+        let level = levels[volume.index(position)];
+        if level <= 1 {
+            continue;
+        }
+        for offset in SIDES {
+            let neighbor = position + offset;
+            if !volume.contains(neighbor) {
+                continue;
+            }
+            let index = volume.index(neighbor);
+            if opaque[index] || levels[index] >= level - 1 {
+                continue;
+            }
+            levels[index] = level - 1;
+            queue.push_back(neighbor);
+        }
+    }
+}
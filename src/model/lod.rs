@@ -0,0 +1,199 @@
+use bevy::{
+    asset::{AssetEvent, AssetId, Assets, Handle},
+    ecs::{
+        component::Component,
+        event::EventReader,
+        query::With,
+        system::{Query, Res, ResMut, Resource},
+    },
+    math::{UVec3, Vec3A},
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    render::{camera::Camera, mesh::Mesh},
+    transform::components::GlobalTransform,
+    utils::{HashMap, HashSet},
+};
+use ndshape::Shape;
+
+use crate::{index::world_aabb, VoxelModelInstance};
+
+use super::{RawVoxel, Voxel, VoxelContext, VoxelData, VoxelModel, VoxelPalette};
+
+impl VoxelData {
+    /// Returns a simplified copy of the data in which each `factor`³ block of voxels is replaced by a single voxel
+    /// `factor` times larger, taking the most common solid voxel in the block. Blocks containing no solid voxels are
+    /// left empty.
+    pub fn downsampled(&self, factor: u32) -> VoxelData {
+        let factor = factor.max(1);
+        let size = self._size().as_uvec3();
+        let downsampled_size = (size + UVec3::splat(factor - 1)) / factor;
+        let mut data = VoxelData::new(
+            downsampled_size,
+            self.mesh_outer_faces,
+            self.voxel_size * factor as f32,
+        )
+        .with_settings_of(self);
+        let leading_padding = UVec3::splat(self.padding() / 2);
+        let mut counts: HashMap<u8, u32> = HashMap::new();
+        for z in 0..downsampled_size.z {
+            for y in 0..downsampled_size.y {
+                for x in 0..downsampled_size.x {
+                    counts.clear();
+                    let block_min = UVec3::new(x, y, z) * factor;
+                    let block_max = (block_min + UVec3::splat(factor)).min(size);
+                    for bz in block_min.z..block_max.z {
+                        for by in block_min.y..block_max.y {
+                            for bx in block_min.x..block_max.x {
+                                let position = UVec3::new(bx, by, bz) + leading_padding;
+                                let raw = self
+                                    .voxel_at_index(self.shape.linearize(position.into()) as usize);
+                                if *raw != RawVoxel::EMPTY {
+                                    *counts.entry(raw.0).or_default() += 1;
+                                }
+                            }
+                        }
+                    }
+                    // ties are broken by the lowest index, so that the result is deterministic
+                    if let Some((index, _)) =
+                        counts.iter().max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+                    {
+                        let voxel: Voxel = RawVoxel(*index).into();
+                        data.set_voxel(voxel, UVec3::new(x, y, z));
+                    }
+                }
+            }
+        }
+        data
+    }
+}
+
+/// The mesh of the `data` downsampled by `factor`, centered on the mesh of the `data`. The blocks of the downsampled
+/// data overhang the far sides of models whose size isn't a multiple of `factor`, so the mesh is shifted back by half
+/// the overhang.
+pub(crate) fn impostor_mesh(data: &VoxelData, factor: u32, palette: &VoxelPalette) -> Mesh {
+    let downsampled = data.downsampled(factor);
+    let (mesh, _) = downsampled.remesh(palette);
+    let overhang = downsampled._size().as_vec3() * downsampled.voxel_size
+        - data._size().as_vec3() * data.voxel_size;
+    mesh.translated_by(overhang * -0.5)
+}
+
+/// Swaps the mesh of a [`VoxelModelInstance`] for a drastically simplified "impostor" mesh when it is far away from
+/// every camera, so that huge worlds stay renderable.
+///
+/// The impostor is generated from [`VoxelData::downsampled`] the first time it is needed, shared between every instance
+/// of the model, and regenerated if the model is modified.
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VoxelLod {
+    /// Instances further than this distance from every camera use the impostor mesh
+    pub distance: f32,
+    /// The number of voxels along each side of the block that becomes a single voxel in the impostor. Defaults to 8.
+    pub factor: u32,
+    is_far: Option<bool>,
+}
+
+impl VoxelLod {
+    /// Creates a LOD that switches to the impostor mesh beyond `distance`
+    pub fn new(distance: f32) -> Self {
+        Self {
+            distance,
+            factor: 8,
+            is_far: None,
+        }
+    }
+
+    /// Sets the downsampling factor of the impostor mesh
+    pub fn with_factor(mut self, factor: u32) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Whether the instance is currently using the impostor mesh
+    pub fn is_far(&self) -> bool {
+        self.is_far.unwrap_or(false)
+    }
+
+    /// Forces the mesh to be reselected on the next update, after the instance's model has changed
+    pub(crate) fn invalidate(&mut self) {
+        self.is_far = None;
+    }
+}
+
+/// The impostor meshes generated for each model and downsampling factor
+#[derive(Resource, Default)]
+pub(crate) struct VoxelLodMeshes {
+    meshes: HashMap<(AssetId<VoxelModel>, u32), Handle<Mesh>>,
+}
+
+impl VoxelLodMeshes {
+    /// Drops the impostor meshes of the models, once no instance uses them
+    pub(crate) fn release(&mut self, models: &HashSet<AssetId<VoxelModel>>) {
+        self.meshes.retain(|(model, _), _| !models.contains(model));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_voxel_lods(
+    mut impostors: ResMut<VoxelLodMeshes>,
+    mut model_events: EventReader<AssetEvent<VoxelModel>>,
+    cameras: Query<&GlobalTransform, With<Camera>>,
+    mut instances: Query<(
+        &VoxelModelInstance,
+        &GlobalTransform,
+        &mut VoxelLod,
+        &mut Handle<Mesh>,
+    )>,
+    models: Res<Assets<VoxelModel>>,
+    contexts: Res<Assets<VoxelContext>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let modified: HashSet<AssetId<VoxelModel>> = model_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } | AssetEvent::Removed { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    if !modified.is_empty() {
+        impostors
+            .meshes
+            .retain(|(model, _), _| !modified.contains(model));
+    }
+    let camera_positions: Vec<Vec3A> = cameras
+        .iter()
+        .map(|xform| xform.translation_vec3a())
+        .collect();
+    for (instance, xform, mut lod, mut mesh) in instances.iter_mut() {
+        let Some(model) = models.get(&instance.model) else {
+            continue;
+        };
+        let aabb = world_aabb(model, xform);
+        let is_far = !camera_positions.iter().any(|camera| {
+            let closest = camera.clamp(aabb.min.into(), aabb.max.into());
+            closest.distance(*camera) <= lod.distance
+        });
+        if lod.is_far == Some(is_far) && !modified.contains(&instance.model.id()) {
+            continue;
+        }
+        let target = if is_far {
+            let key = (instance.model.id(), lod.factor);
+            if let Some(impostor) = impostors.meshes.get(&key) {
+                impostor.clone()
+            } else {
+                let Some(context) = contexts.get(&instance.context) else {
+                    continue;
+                };
+                let impostor = meshes.add(impostor_mesh(&model.data, lod.factor, &context.palette));
+                impostors.meshes.insert(key, impostor.clone());
+                impostor
+            }
+        } else {
+            model.mesh.clone()
+        };
+        if *mesh != target {
+            *mesh = target;
+        }
+        lod.is_far = Some(is_far);
+    }
+}
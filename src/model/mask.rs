@@ -0,0 +1,127 @@
+use bevy::{math::IVec3, utils::HashSet};
+use ndshape::Shape;
+
+use super::{Voxel, VoxelData, VoxelModel};
+
+/// Protects voxels of a model from being modified, such as quest-critical structures or an indestructible bedrock
+/// layer.
+///
+/// Voxels can be protected by position, with a bit for every voxel of the model, or by palette index. Protected voxels
+/// are skipped by [`crate::ModifyVoxelCommandsExt::modify_voxel_model`] and every tool built on it, such as brushes,
+/// pasting, blueprints and morphology, as well as by [`crate::VoxelGravity`] and
+/// [`crate::VoxelWorldServer::modify_voxels`]. Writing a voxel directly with [`VoxelData::set_voxel`] ignores the mask.
+///
+/// Attach a mask to a model with [`VoxelModel::set_edit_mask`] or [`VoxelData::with_edit_mask`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelEditMask {
+    size: IVec3,
+    cells: Vec<u64>,
+    voxels: HashSet<Voxel>,
+}
+
+impl VoxelEditMask {
+    /// Create a mask for a model of the supplied size, with nothing protected
+    pub fn new(size: IVec3) -> Self {
+        let size = size.max(IVec3::ZERO);
+        let volume = size.x as usize * size.y as usize * size.z as usize;
+        Self {
+            size,
+            cells: vec![0; volume.div_ceil(64)],
+            voxels: HashSet::new(),
+        }
+    }
+
+    /// Protects the voxel at `position`, in voxel space. Positions outside the model are ignored.
+    pub fn protect_cell(&mut self, position: IVec3) {
+        if let Some(index) = self.index(position) {
+            self.cells[index / 64] |= 1 << (index % 64);
+        }
+    }
+
+    /// Protects every voxel in the box with the lower-back-left corner `origin` and the supplied `size`, in voxel space
+    pub fn protect_region(&mut self, origin: IVec3, size: IVec3) {
+        let start = origin.max(IVec3::ZERO);
+        let end = (origin + size).min(self.size);
+        for z in start.z..end.z {
+            for y in start.y..end.y {
+                for x in start.x..end.x {
+                    self.protect_cell(IVec3::new(x, y, z));
+                }
+            }
+        }
+    }
+
+    /// Protects every voxel with the palette index of `voxel`, wherever it is in the model
+    pub fn protect_voxel(&mut self, voxel: Voxel) {
+        self.voxels.insert(voxel);
+    }
+
+    /// Protects every voxel with the palette index of `voxel`
+    pub fn with_protected_voxel(mut self, voxel: Voxel) -> Self {
+        self.protect_voxel(voxel);
+        self
+    }
+
+    /// Protects every voxel in the box with the lower-back-left corner `origin` and the supplied `size`
+    pub fn with_protected_region(mut self, origin: IVec3, size: IVec3) -> Self {
+        self.protect_region(origin, size);
+        self
+    }
+
+    /// Whether the `voxel` at `position`, in voxel space, is protected from modification
+    pub fn is_protected(&self, position: IVec3, voxel: &Voxel) -> bool {
+        self.voxels.contains(voxel)
+            || self
+                .index(position)
+                .is_some_and(|index| self.cells[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    fn index(&self, position: IVec3) -> Option<usize> {
+        if position.cmplt(IVec3::ZERO).any() || position.cmpge(self.size).any() {
+            return None;
+        }
+        let position = position.as_uvec3();
+        let size = self.size.as_uvec3();
+        Some((position.x + size.x * (position.y + size.y * position.z)) as usize)
+    }
+}
+
+impl VoxelData {
+    /// Attaches a [`VoxelEditMask`] protecting voxels of the model from modification
+    pub fn with_edit_mask(mut self, mask: VoxelEditMask) -> Self {
+        self.edit_mask = Some(mask);
+        self
+    }
+
+    /// Whether the voxel at `position`, in voxel space, is protected by the model's [`VoxelEditMask`]
+    pub(crate) fn is_protected(&self, position: IVec3, voxel: &Voxel) -> bool {
+        self.edit_mask
+            .as_ref()
+            .is_some_and(|mask| mask.is_protected(position, voxel))
+    }
+
+    /// Whether the voxel at the `index` in the padded voxel array is protected by the model's [`VoxelEditMask`]
+    pub(crate) fn is_protected_index(&self, index: usize) -> bool {
+        let Some(mask) = &self.edit_mask else {
+            return false;
+        };
+        let leading_padding = IVec3::splat(self.padding() as i32 / 2);
+        let position = IVec3::from(self.shape.delinearize(index as u32).map(|axis| axis as i32));
+        mask.is_protected(
+            position - leading_padding,
+            &self.voxel_at_index(index).clone().into(),
+        )
+    }
+}
+
+impl VoxelModel {
+    /// Attaches a [`VoxelEditMask`] protecting voxels of the model from modification, or removes the mask if `None`
+    pub fn set_edit_mask(&mut self, mask: Option<VoxelEditMask>) {
+        self.data.edit_mask = mask;
+    }
+
+    /// The [`VoxelEditMask`] protecting voxels of the model from modification, if it has one
+    pub fn edit_mask(&self) -> Option<&VoxelEditMask> {
+        self.data.edit_mask.as_ref()
+    }
+}
@@ -1,66 +1,240 @@
 use bevy::{
-    math::Vec3,
+    color::ColorToComponents,
+    log::warn,
+    math::{IVec3, Vec2, Vec3},
     render::{
-        mesh::{Indices, Mesh, VertexAttributeValues},
+        mesh::{Indices, Mesh, MeshVertexAttribute, VertexAttributeValues},
         render_asset::RenderAssetUsages,
-        render_resource::PrimitiveTopology,
+        render_resource::{PrimitiveTopology, VertexFormat},
     },
 };
-use block_mesh::{greedy_quads, GreedyQuadsBuffer, RIGHT_HANDED_Y_UP_CONFIG};
+use block_mesh::{UnitQuadBuffer, UnorientedQuad, VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG};
 use ndshape::Shape;
+use serde::{Deserialize, Serialize};
 
-use super::{voxel::VisibleVoxel, VoxelData};
+use super::{
+    bitmask::VoxelOccupancy,
+    optimize::{optimize_vertices, select_vertices, VertexKeys},
+    shape::VoxelShape,
+    voxel::VisibleVoxel,
+    RawVoxel, Voxel, VoxelData, VoxelPalette, SIDES,
+};
+
+/// A `Uint32` vertex attribute holding the index of the direction the face points in, in the order
+/// `-X, -Y, -Z, +X, +Y, +Z`. Generated when [`MeshAttributeConfig::face_id`] is set.
+pub const ATTRIBUTE_FACE_ID: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_VoxelFaceId", 908_122_001, VertexFormat::Uint32);
+
+/// A `Uint32` vertex attribute holding the index of each voxel's palette element, matching the index of its [`Voxel`]
+/// (1-255), so that custom shaders can branch per voxel type. Generated when [`MeshAttributeConfig::palette_index`] is
+/// set.
+pub const ATTRIBUTE_PALETTE_INDEX: MeshVertexAttribute = MeshVertexAttribute::new(
+    "Vertex_VoxelPaletteIndex",
+    908_122_002,
+    VertexFormat::Uint32,
+);
 
-pub(crate) fn mesh_model(voxels: &[VisibleVoxel], data: &VoxelData) -> Mesh {
-    let mut greedy_quads_buffer = GreedyQuadsBuffer::new(data.shape.size() as usize);
+/// A `Float32` vertex attribute holding the fraction of the [`crate::DirectionalOcclusion::directions`] from which
+/// each vertex is lit. Generated when directional occlusion is enabled on the [`VoxelData`].
+pub const ATTRIBUTE_DIRECTIONAL_OCCLUSION: MeshVertexAttribute = MeshVertexAttribute::new(
+    "Vertex_VoxelDirectionalOcclusion",
+    908_122_003,
+    VertexFormat::Float32,
+);
+
+/// Selects which vertex attributes are generated when a model is meshed, so that custom shaders can get exactly the
+/// data they need. Positions, normals and indices are always generated.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct MeshAttributeConfig {
+    /// [`Mesh::ATTRIBUTE_UV_0`], used to look up each voxel's properties in the palette textures. Defaults to true.
+    /// The materials generated by this crate won't render correctly without it.
+    pub uv0: bool,
+    /// [`Mesh::ATTRIBUTE_UV_1`], with coordinates running from 0 to the size of each face in voxels, for instance to
+    /// render lightmaps or ambient occlusion. Defaults to false.
+    pub uv1: bool,
+    /// [`Mesh::ATTRIBUTE_COLOR`], holding the linear color of each voxel's palette element. Defaults to false.
+    /// Note that [`bevy::pbr::StandardMaterial`] multiplies its base color by the vertex color, so this should only be
+    /// enabled for custom materials.
+    pub color: bool,
+    /// [`ATTRIBUTE_FACE_ID`], holding the direction of each face. Defaults to false.
+    pub face_id: bool,
+    /// [`ATTRIBUTE_PALETTE_INDEX`], holding the palette index of each voxel. Defaults to false.
+    pub palette_index: bool,
+    /// When set, [`Mesh::ATTRIBUTE_UV_1`] is generated as a lightmap atlas for a square texture with this many texels
+    /// per side, instead of per-face coordinates. Every face is given its own region of the atlas, surrounded by a
+    /// one texel gutter so that filtering doesn't bleed between faces. Defaults to `None`.
+    ///
+    /// Bake a lightmap with this resolution for the model, and add it to its instances with a
+    /// [`bevy::pbr::Lightmap`] component whose `uv_rect` covers the whole texture.
+    pub lightmap_resolution: Option<u32>,
+}
+
+impl Default for MeshAttributeConfig {
+    fn default() -> Self {
+        Self {
+            uv0: true,
+            uv1: false,
+            color: false,
+            face_id: false,
+            palette_index: false,
+            lightmap_resolution: None,
+        }
+    }
+}
+
+pub(crate) fn mesh_model(
+    voxels: &[VisibleVoxel],
+    data: &VoxelData,
+    palette: &VoxelPalette,
+) -> Mesh {
+    let attributes = data.attributes;
     let quads_config = RIGHT_HANDED_Y_UP_CONFIG;
-    greedy_quads(
-        voxels,
-        &data.shape,
-        [0; 3],
-        data.shape.as_array().map(|x| x - 1),
-        &quads_config.faces,
-        &mut greedy_quads_buffer,
-    );
+    let groups = mesh_quads(voxels, data, &palette.indices_of_refraction);
     let leading_padding = (data.padding() / 2) as f32 * data.voxel_size; // corrects the 1 offset introduced by the meshing.
     let position_offset = Vec3::splat(leading_padding);
 
-    let num_indices = greedy_quads_buffer.quads.num_quads() * 6;
-    let num_vertices = greedy_quads_buffer.quads.num_quads() * 4;
+    let mut quads: Vec<MeshQuad> = Vec::with_capacity(groups.iter().map(Vec::len).sum());
+    for (face_id, (group, face)) in groups.iter().zip(quads_config.faces.as_ref()).enumerate() {
+        for quad in group.iter() {
+            quads.push(MeshQuad {
+                positions: face.quad_mesh_positions(quad, data.voxel_size),
+                indices: face.quad_mesh_indices(0),
+                normal: face.quad_mesh_normals()[0],
+                lightmap_uvs: face.tex_coords(quads_config.u_flip_face, true, quad),
+                palette_index: voxels[data.shape.linearize(quad.minimum) as usize].index,
+                face_id: face_id as u32,
+                cell: quad.minimum,
+            });
+        }
+    }
+    if !data.shapes.is_empty() {
+        shape_quads(voxels, data, &mut quads);
+    }
+
+    let num_indices = quads.len() * 6;
+    let num_vertices = quads.len() * 4;
 
     let mut indices = Vec::with_capacity(num_indices);
     let mut positions = Vec::with_capacity(num_vertices);
     let mut normals = Vec::with_capacity(num_vertices);
     let mut uvs = Vec::with_capacity(num_vertices);
+    let capacity = |enabled: bool| if enabled { num_vertices } else { 0 };
+    let mut tangents = Vec::with_capacity(capacity(data.generate_tangents));
+    let generate_uv1 = attributes.uv1 || attributes.lightmap_resolution.is_some();
+    let mut lightmap_uvs = Vec::with_capacity(capacity(generate_uv1));
+    let generate_colors = attributes.color || data.tint.is_some() || data.light.is_some();
+    let mut colors = Vec::with_capacity(capacity(generate_colors));
+    let mut face_ids = Vec::with_capacity(capacity(attributes.face_id));
+    let mut palette_indices = Vec::with_capacity(capacity(attributes.palette_index));
 
     let mut render_mesh = Mesh::new(
         PrimitiveTopology::TriangleList,
         RenderAssetUsages::default(),
     );
 
-    for (group, face) in greedy_quads_buffer
-        .quads
-        .groups
-        .iter()
-        .zip(quads_config.faces.as_ref())
-    {
-        for quad in group.iter() {
-            let palette_index = voxels[data.shape.linearize(quad.minimum) as usize].index;
-            indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
-            positions.extend_from_slice(&face.quad_mesh_positions(quad, data.voxel_size).map(
-                |position| {
+    for quad in quads {
+        let palette_index = quad.palette_index;
+        let start = positions.len() as u32;
+        indices.extend(quad.indices.map(|index| start + index));
+        positions.extend_from_slice(&quad.positions.map(|position| {
+            [
+                position[0] - position_offset.x,
+                position[1] - position_offset.y,
+                position[2] - position_offset.z,
+            ]
+        }));
+        if attributes.uv0 {
+            let uv = palette.layout.uv(palette_index);
+            uvs.extend_from_slice(&[uv, uv, uv, uv]);
+        }
+        if generate_uv1 {
+            lightmap_uvs.extend_from_slice(&quad.lightmap_uvs);
+        }
+        if generate_colors {
+            let color = palette
+                .elements
+                .get(palette_index as usize)
+                .filter(|_| attributes.color)
+                .map_or([1.0; 4], |element| element.color.to_linear().to_f32_array());
+            let color = match data.light.as_ref() {
+                Some(light) => {
+                    let brightness =
+                        light.face_brightness(data, quad.cell, FACE_NORMALS[quad.face_id as usize]);
                     [
-                        position[0] - position_offset.x,
-                        position[1] - position_offset.y,
-                        position[2] - position_offset.z,
+                        color[0] * brightness,
+                        color[1] * brightness,
+                        color[2] * brightness,
+                        color[3],
                     ]
-                },
-            ));
-            let u = ((palette_index % 16) as f32 + 0.5) / 16.0;
-            let v = ((palette_index / 16) as f32 + 0.5) / 16.0;
-            uvs.extend_from_slice(&[[u, v], [u, v], [u, v], [u, v]]);
-            normals.extend_from_slice(&face.quad_mesh_normals());
+                }
+                None => color,
+            };
+            let quad_positions = &positions[positions.len() - 4..];
+            colors.extend(quad_positions.iter().map(|position| {
+                let Some(tint) = data.tint.as_ref() else {
+                    return color;
+                };
+                let tint = tint.evaluate(Vec3::from(*position), RawVoxel(palette_index).into());
+                [
+                    color[0] * tint[0],
+                    color[1] * tint[1],
+                    color[2] * tint[2],
+                    color[3] * tint[3],
+                ]
+            }));
+        }
+        if attributes.face_id {
+            face_ids.extend_from_slice(&[quad.face_id; 4]);
+        }
+        if attributes.palette_index {
+            let voxel: Voxel = RawVoxel(palette_index).into();
+            palette_indices.extend_from_slice(&[voxel.0 as u32; 4]);
         }
+        normals.extend_from_slice(&[quad.normal; 4]);
+        if data.generate_tangents {
+            tangents.extend_from_slice(&[face_tangent(quad.normal); 4]);
+        }
+    }
+
+    let mut occlusion = if data.directional_occlusion.is_enabled() {
+        data.directional_occlusion.bake(data, &positions, &normals)
+    } else {
+        Vec::new()
+    };
+    // the atlas is packed one quad at a time, so this has to happen before vertices are welded
+    if let Some(resolution) = attributes.lightmap_resolution {
+        pack_lightmap(&mut lightmap_uvs, resolution);
+    }
+
+    if data.optimize_mesh {
+        let mut keys = VertexKeys::new(positions.len());
+        keys.push_floats(&positions);
+        keys.push_floats(&normals);
+        keys.push_floats(&uvs);
+        keys.push_floats(&tangents);
+        keys.push_floats(&lightmap_uvs);
+        keys.push_floats(&colors);
+        keys.push_uint(&face_ids);
+        keys.push_uint(&palette_indices);
+        keys.push_float(&occlusion);
+        let kept = optimize_vertices(keys, &mut indices);
+        positions = select_vertices(positions, &kept);
+        normals = select_vertices(normals, &kept);
+        uvs = select_vertices(uvs, &kept);
+        tangents = select_vertices(tangents, &kept);
+        lightmap_uvs = select_vertices(lightmap_uvs, &kept);
+        colors = select_vertices(colors, &kept);
+        face_ids = select_vertices(face_ids, &kept);
+        palette_indices = select_vertices(palette_indices, &kept);
+        occlusion = select_vertices(occlusion, &kept);
+    }
+
+    if data.directional_occlusion.is_enabled() {
+        render_mesh.insert_attribute(
+            ATTRIBUTE_DIRECTIONAL_OCCLUSION,
+            VertexAttributeValues::Float32(occlusion),
+        );
     }
 
     render_mesh.insert_attribute(
@@ -72,9 +246,255 @@ pub(crate) fn mesh_model(voxels: &[VisibleVoxel], data: &VoxelData) -> Mesh {
         Mesh::ATTRIBUTE_NORMAL,
         VertexAttributeValues::Float32x3(normals),
     );
-    render_mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, VertexAttributeValues::Float32x2(uvs));
+    if attributes.uv0 {
+        render_mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, VertexAttributeValues::Float32x2(uvs));
+    }
+    if generate_uv1 {
+        render_mesh.insert_attribute(
+            Mesh::ATTRIBUTE_UV_1,
+            VertexAttributeValues::Float32x2(lightmap_uvs),
+        );
+    }
+    if generate_colors {
+        render_mesh.insert_attribute(
+            Mesh::ATTRIBUTE_COLOR,
+            VertexAttributeValues::Float32x4(colors),
+        );
+    }
+    if attributes.face_id {
+        render_mesh.insert_attribute(ATTRIBUTE_FACE_ID, VertexAttributeValues::Uint32(face_ids));
+    }
+    if attributes.palette_index {
+        render_mesh.insert_attribute(
+            ATTRIBUTE_PALETTE_INDEX,
+            VertexAttributeValues::Uint32(palette_indices),
+        );
+    }
+
+    if data.generate_tangents {
+        render_mesh.insert_attribute(
+            Mesh::ATTRIBUTE_TANGENT,
+            VertexAttributeValues::Float32x4(tangents),
+        );
+    }
 
     render_mesh.insert_indices(Indices::U32(indices.clone()));
 
     render_mesh
 }
+
+/// Generates the quads of the cube voxels, grouped by the direction they face in the order of [`ATTRIBUTE_FACE_ID`]
+pub(crate) fn mesh_quads(
+    voxels: &[VisibleVoxel],
+    data: &VoxelData,
+    ior_for_voxel: &[Option<f32>],
+) -> [Vec<UnorientedQuad>; 6] {
+    let mut occupancy = VoxelOccupancy::new(voxels, &data.shape);
+    if !data.shapes.is_empty() {
+        cover_with_shapes(&mut occupancy, data, ior_for_voxel);
+    }
+    // baked light differs from face to face, so faces are only merged when there is no light
+    if data.light.is_some() {
+        let mut unit_quads_buffer = UnitQuadBuffer::new();
+        occupancy.visible_faces(&mut unit_quads_buffer);
+        return unit_quads_buffer
+            .groups
+            .map(|group| group.into_iter().map(UnorientedQuad::from).collect());
+    }
+    occupancy.greedy_quads(voxels, &data.shape)
+}
+
+/// The unit normals of the faces of a voxel, in the order of [`ATTRIBUTE_FACE_ID`]
+const FACE_NORMALS: [[f32; 3]; 6] = [
+    [-1.0, 0.0, 0.0],
+    [0.0, -1.0, 0.0],
+    [0.0, 0.0, -1.0],
+    [1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, 0.0, 1.0],
+];
+
+/// A quad to be written to the mesh, in the padded voxel space of the data scaled by the voxel size
+struct MeshQuad {
+    positions: [[f32; 3]; 4],
+    /// The two triangles of the quad, indexing into `positions`
+    indices: [u32; 6],
+    normal: [f32; 3],
+    lightmap_uvs: [[f32; 2]; 4],
+    palette_index: u8,
+    /// The index of the side of the voxel that the quad faces, or is closest to facing
+    face_id: u32,
+    /// The voxel that the quad belongs to
+    cell: [u32; 3],
+}
+
+/// Marks the shaped voxels in the `occupancy` as covering the sides where their shape has a full face, so that the faces
+/// of cubes against those sides are hidden, as shaped voxels are otherwise empty to the cube mesher
+fn cover_with_shapes(
+    occupancy: &mut VoxelOccupancy,
+    data: &VoxelData,
+    ior_for_voxel: &[Option<f32>],
+) {
+    let shapes = data.shapes.raw_shapes();
+    let size = data.shape.as_array();
+    for z in 0..size[2] {
+        for y in 0..size[1] {
+            for x in 0..size[0] {
+                let raw = data.voxel_at_index(data.shape.linearize([x, y, z]) as usize);
+                let shape = shapes[raw.0 as usize];
+                if *raw == RawVoxel::EMPTY
+                    || data.water.as_ref() == Some(raw)
+                    || shape == VoxelShape::Cube
+                {
+                    continue;
+                }
+                let translucent = ior_for_voxel
+                    .get(raw.0 as usize)
+                    .copied()
+                    .flatten()
+                    .is_some();
+                for (side, side_vector) in SIDES.iter().enumerate() {
+                    if shape.covers(*side_vector) {
+                        occupancy.cover([x, y, z], side, translucent);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Adds the faces of the voxels that have a [`VoxelShape`] other than a cube, leaving out those that are covered by
+/// their neighbors
+fn shape_quads(voxels: &[VisibleVoxel], data: &VoxelData, quads: &mut Vec<MeshQuad>) {
+    let shapes = data.shapes.raw_shapes();
+    let size = data.shape.as_array().map(|x| x as i32);
+    // like the cube mesher, only voxels with a neighbor on every side are meshed
+    for z in 1..size[2] - 1 {
+        for y in 1..size[1] - 1 {
+            for x in 1..size[0] - 1 {
+                let cell = IVec3::new(x, y, z);
+                let raw =
+                    data.voxel_at_index(data.shape.linearize(cell.as_uvec3().into()) as usize);
+                if *raw == RawVoxel::EMPTY || data.water.as_ref() == Some(raw) {
+                    continue;
+                }
+                let shape = shapes[raw.0 as usize];
+                for face in shape.faces() {
+                    let covered = face.side.is_some_and(|side| {
+                        let index = data.shape.linearize((cell + side).as_uvec3().into()) as usize;
+                        let neighbor = data.voxel_at_index(index);
+                        voxels[index].visibility == VoxelVisibility::Opaque
+                            || (face.full
+                                && *neighbor != RawVoxel::EMPTY
+                                && data.water.as_ref() != Some(neighbor)
+                                && shapes[neighbor.0 as usize] != VoxelShape::Cube
+                                && shapes[neighbor.0 as usize].covers(-side))
+                    });
+                    if covered {
+                        continue;
+                    }
+                    let normal = face.normal();
+                    // sloped faces are treated as facing up, or otherwise along their steepest axis
+                    let axis = if normal.y.abs() >= normal.x.abs().max(normal.z.abs()) {
+                        1
+                    } else if normal.x.abs() >= normal.z.abs() {
+                        0
+                    } else {
+                        2
+                    };
+                    let face_id = axis + if normal[axis] > 0.0 { 3 } else { 0 };
+                    let (u, v) = [(2, 1), (0, 2), (0, 1)][axis];
+                    quads.push(MeshQuad {
+                        positions: face
+                            .corners
+                            .map(|corner| ((cell.as_vec3() + corner) * data.voxel_size).into()),
+                        indices: [0, 1, 2, 0, 2, 3],
+                        normal: normal.into(),
+                        lightmap_uvs: face.corners.map(|corner| [corner[u], corner[v]]),
+                        palette_index: raw.0,
+                        face_id: face_id as u32,
+                        cell: cell.as_uvec3().into(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+const LIGHTMAP_GUTTER: f32 = 1.0;
+
+/// Packs each quad of per-face `uvs` into a square lightmap atlas with `resolution` texels per side, scaling the faces
+/// uniformly so that the texel density is the same across the whole model
+fn pack_lightmap(uvs: &mut [[f32; 2]], resolution: u32) {
+    let size = resolution as f32;
+    let quads: Vec<Vec2> = uvs
+        .chunks(4)
+        .map(|quad| {
+            quad.iter()
+                .fold(Vec2::ZERO, |extent, uv| extent.max(Vec2::from(*uv)))
+        })
+        .collect();
+    let face_area: f32 = quads.iter().map(|quad| quad.x * quad.y).sum();
+    let max_side = quads
+        .iter()
+        .fold(0.0_f32, |max, quad| max.max(quad.max_element()));
+    if quads.is_empty() || face_area <= 0.0 {
+        return;
+    }
+    // tallest faces first, so that each shelf wastes as little space as possible
+    let mut order: Vec<usize> = (0..quads.len()).collect();
+    order.sort_by(|a, b| quads[*b].y.total_cmp(&quads[*a].y));
+    let mut scale = (size * size / face_area)
+        .sqrt()
+        .min((size - LIGHTMAP_GUTTER * 2.0) / max_side);
+    while scale > f32::EPSILON {
+        if let Some(offsets) = shelf_pack(&quads, &order, scale, size) {
+            for (quad, offset) in uvs.chunks_mut(4).zip(offsets) {
+                for uv in quad {
+                    *uv = ((offset + Vec2::from(*uv) * scale) / size).into();
+                }
+            }
+            return;
+        }
+        scale *= 0.9;
+    }
+    warn!(
+        "A lightmap resolution of {} is too small to fit {} faces",
+        resolution,
+        quads.len()
+    );
+}
+
+/// Places the `quads`, scaled by `scale`, in rows along the atlas, returning the offset of each quad in texels, or
+/// `None` if they don't fit
+fn shelf_pack(quads: &[Vec2], order: &[usize], scale: f32, size: f32) -> Option<Vec<Vec2>> {
+    let mut offsets = vec![Vec2::ZERO; quads.len()];
+    let mut cursor = Vec2::ZERO;
+    let mut shelf_height: f32 = 0.0;
+    for index in order {
+        let extent = (quads[*index] * scale).ceil() + Vec2::splat(LIGHTMAP_GUTTER * 2.0);
+        if cursor.x + extent.x > size {
+            cursor = Vec2::new(0.0, cursor.y + shelf_height);
+            shelf_height = 0.0;
+        }
+        if cursor.x + extent.x > size || cursor.y + extent.y > size {
+            return None;
+        }
+        offsets[*index] = cursor + Vec2::splat(LIGHTMAP_GUTTER);
+        cursor.x += extent.x;
+        shelf_height = shelf_height.max(extent.y);
+    }
+    Some(offsets)
+}
+
+/// A tangent lying in the plane of the face. As every vertex of a quad shares the same UV, any direction in the plane is
+/// valid, so the tangent is chosen to be consistent between faces with the same normal.
+fn face_tangent(normal: [f32; 3]) -> [f32; 4] {
+    if normal[0] != 0.0 {
+        [0.0, 0.0, -normal[0].signum(), 1.0]
+    } else if normal[1] != 0.0 {
+        [1.0, 0.0, 0.0, 1.0]
+    } else {
+        [normal[2], 0.0, 0.0, 1.0]
+    }
+}
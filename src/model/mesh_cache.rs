@@ -0,0 +1,320 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    color::ColorToComponents,
+    log::{debug, warn},
+    render::{
+        mesh::{Indices, Mesh, MeshVertexAttribute, VertexAttributeValues},
+        render_asset::RenderAssetUsages,
+        render_resource::{PrimitiveTopology, VertexFormat},
+    },
+};
+
+use super::{
+    mesh::{ATTRIBUTE_DIRECTIONAL_OCCLUSION, ATTRIBUTE_FACE_ID, ATTRIBUTE_PALETTE_INDEX},
+    palette::PaletteLayout,
+    shape::VoxelShape,
+    VoxelData, VoxelPalette,
+};
+use crate::hash::StableHasher;
+
+const MAGIC: &[u8; 4] = b"VXMC";
+const FORMAT_VERSION: u32 = 1;
+
+/// Every attribute that the mesher can generate, in the order they are stored in the cache
+const ATTRIBUTES: [MeshVertexAttribute; 9] = [
+    Mesh::ATTRIBUTE_POSITION,
+    Mesh::ATTRIBUTE_NORMAL,
+    Mesh::ATTRIBUTE_UV_0,
+    Mesh::ATTRIBUTE_UV_1,
+    Mesh::ATTRIBUTE_COLOR,
+    Mesh::ATTRIBUTE_TANGENT,
+    ATTRIBUTE_FACE_ID,
+    ATTRIBUTE_PALETTE_INDEX,
+    ATTRIBUTE_DIRECTIONAL_OCCLUSION,
+];
+
+/// The key of the mesh of `data` in the cache, covering the voxels, the meshing settings, the parts of the palette that
+/// affect meshing and the version of the crate, so that stale meshes are never read back. Every field is written
+/// explicitly in a fixed byte order, so keys stay stable across platforms and don't depend on debug output.
+fn cache_key(data: &VoxelData, palette: &VoxelPalette) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+    for component in data.shape.as_array() {
+        hasher.write_u32(component);
+    }
+    hasher.write(&[
+        data.mesh_outer_faces as u8,
+        data.generate_tangents as u8,
+        data.optimize_mesh as u8,
+    ]);
+    hasher.write_f32(data.voxel_size);
+    let voxels = data.dense_voxels();
+    hasher.write_u32(voxels.len() as u32);
+    hasher.write(&voxels.iter().map(|voxel| voxel.0).collect::<Vec<u8>>());
+
+    let attributes = &data.attributes;
+    hasher.write(&[
+        attributes.uv0 as u8,
+        attributes.uv1 as u8,
+        attributes.color as u8,
+        attributes.face_id as u8,
+        attributes.palette_index as u8,
+    ]);
+    write_option(
+        &mut hasher,
+        attributes.lightmap_resolution,
+        |hasher, resolution| hasher.write_u32(resolution),
+    );
+    let occlusion = &data.directional_occlusion;
+    hasher.write_u32(occlusion.directions.len() as u32);
+    for direction in occlusion.directions.iter() {
+        direction
+            .iter()
+            .for_each(|component| hasher.write_f32(*component));
+    }
+    hasher.write_f32(occlusion.max_distance);
+    write_option(&mut hasher, data.light.as_ref(), |hasher, light| {
+        hasher.write_u32(light.levels.len() as u32);
+        hasher.write(&light.levels);
+        hasher.write_f32(light.ambient);
+    });
+    write_option(&mut hasher, data.water.as_ref(), |hasher, water| {
+        hasher.write(&[water.0])
+    });
+    for shape in data.shapes.raw_shapes() {
+        let (kind, turns) = match shape {
+            VoxelShape::Cube => (0, 0),
+            VoxelShape::Slab => (1, 0),
+            VoxelShape::Ramp(facing) => (2, facing.quarter_turns()),
+            VoxelShape::Stairs(facing) => (3, facing.quarter_turns()),
+        };
+        hasher.write(&[kind, turns as u8]);
+    }
+    let filter = &data.collider_filter;
+    hasher.write_u32(filter.excluded.len() as u32);
+    hasher.write(&filter.excluded.iter().copied().collect::<Vec<u8>>());
+    hasher.write_u32(filter.groups.len() as u32);
+    for (index, groups) in filter.groups.iter() {
+        hasher.write(&[*index]);
+        hasher.write_u32(*groups);
+    }
+    hasher.write_u32(filter.default_groups);
+
+    hasher.write_u32(palette.elements.len() as u32);
+    for element in palette.elements.iter() {
+        for component in element.color.to_linear().to_f32_array() {
+            hasher.write_f32(component);
+        }
+        hasher.write_f32(element.emission);
+        hasher.write_f32(element.roughness);
+        hasher.write_f32(element.metalness);
+        hasher.write_f32(element.translucency);
+        hasher.write_f32(element.refraction_index);
+    }
+    hasher.write_u32(palette.indices_of_refraction.len() as u32);
+    for ior in palette.indices_of_refraction.iter() {
+        write_option(&mut hasher, *ior, StableHasher::write_f32);
+    }
+    hasher.write(&[match palette.layout {
+        PaletteLayout::Grid => 0,
+        PaletteLayout::Strip => 1,
+    }]);
+    hasher.finish()
+}
+
+/// Writes whether `value` is set, followed by the value itself if it is
+fn write_option<T>(
+    hasher: &mut StableHasher,
+    value: Option<T>,
+    write: impl FnOnce(&mut StableHasher, T),
+) {
+    hasher.write(&[value.is_some() as u8]);
+    if let Some(value) = value {
+        write(hasher, value);
+    }
+}
+
+fn cache_path(directory: &Path, key: u64) -> PathBuf {
+    directory.join(format!("{key:016x}.voxmesh"))
+}
+
+impl VoxelData {
+    /// Meshes the data, or reads the mesh back from `cache` if it was meshed with the same settings and palette before.
+    /// Meshes are written to the cache after meshing. Failing to read or write the cache only logs a warning.
+    pub(crate) fn remesh_cached(
+        &self,
+        palette: &VoxelPalette,
+        cache: Option<&Path>,
+    ) -> (Mesh, Option<f32>) {
+        let Some(directory) = cache else {
+            return self.remesh(palette);
+        };
+        // meshes with baked light or water are edited at runtime, so are never loaded through the cache, and a tint is a
+        // function that can't be part of the key
+        if self.light.is_some() || self.water.is_some() || self.tint.is_some() {
+            return self.remesh(palette);
+        }
+        let path = cache_path(directory, cache_key(self, palette));
+        match fs::read(&path) {
+            Ok(bytes) => match decode(&bytes) {
+                Ok(cached) => {
+                    debug!("Read cached mesh {}", path.display());
+                    return cached;
+                }
+                Err(error) => warn!("Ignoring cached mesh {}: {error}", path.display()),
+            },
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => warn!("Failed to read cached mesh {}: {error}", path.display()),
+        }
+        let (mesh, average_ior) = self.remesh(palette);
+        if let Err(error) = write(&path, &encode(&mesh, average_ior)) {
+            warn!("Failed to write cached mesh {}: {error}", path.display());
+        }
+        (mesh, average_ior)
+    }
+}
+
+fn write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    // write to a temporary file first, so that concurrent loads never read a partial mesh
+    let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temporary, bytes)?;
+    fs::rename(&temporary, path)
+}
+
+fn encode(mesh: &Mesh, average_ior: Option<f32>) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(FORMAT_VERSION.to_le_bytes());
+    bytes.push(average_ior.is_some() as u8);
+    bytes.extend(average_ior.unwrap_or_default().to_le_bytes());
+    for (index, attribute) in ATTRIBUTES.iter().enumerate() {
+        let Some(values) = mesh.attribute(attribute.clone()) else {
+            continue;
+        };
+        bytes.push(index as u8);
+        let attribute_bytes: Vec<u8> = match values {
+            VertexAttributeValues::Float32(values) => values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            VertexAttributeValues::Float32x2(values) => floats(values),
+            VertexAttributeValues::Float32x3(values) => floats(values),
+            VertexAttributeValues::Float32x4(values) => floats(values),
+            VertexAttributeValues::Uint32(values) => values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            _ => unreachable!("the mesher only generates 32 bit attributes"),
+        };
+        bytes.extend((values.len() as u32).to_le_bytes());
+        bytes.extend(attribute_bytes);
+    }
+    bytes.push(u8::MAX);
+    let indices: Vec<u32> = mesh
+        .indices()
+        .map(|indices| indices.iter().map(|index| index as u32).collect())
+        .unwrap_or_default();
+    bytes.extend((indices.len() as u32).to_le_bytes());
+    bytes.extend(indices.iter().flat_map(|index| index.to_le_bytes()));
+    bytes
+}
+
+fn floats<const N: usize>(values: &[[f32; N]]) -> Vec<u8> {
+    values
+        .iter()
+        .flatten()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+struct CacheReader<'a>(&'a [u8]);
+
+impl<'a> CacheReader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], &'static str> {
+        if self.0.len() < count {
+            return Err("the file is truncated");
+        }
+        let (bytes, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, &'static str> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn f32s<const N: usize>(&mut self, count: usize) -> Result<Vec<[f32; N]>, &'static str> {
+        (0..count)
+            .map(|_| {
+                let mut value = [0.0; N];
+                for component in value.iter_mut() {
+                    *component = self.f32()?;
+                }
+                Ok(value)
+            })
+            .collect()
+    }
+}
+
+fn decode(bytes: &[u8]) -> Result<(Mesh, Option<f32>), &'static str> {
+    let mut reader = CacheReader(bytes);
+    if reader.bytes(4)? != MAGIC || reader.u32()? != FORMAT_VERSION {
+        return Err("the file isn't a cached mesh of this version");
+    }
+    let has_ior = reader.u8()? != 0;
+    let ior = reader.f32()?;
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    );
+    loop {
+        let index = reader.u8()?;
+        if index == u8::MAX {
+            break;
+        }
+        let attribute = ATTRIBUTES
+            .get(index as usize)
+            .ok_or("the file contains an unknown attribute")?
+            .clone();
+        let count = reader.u32()? as usize;
+        let values = match attribute.format {
+            VertexFormat::Float32 => VertexAttributeValues::Float32(
+                reader
+                    .f32s::<1>(count)?
+                    .into_iter()
+                    .map(|[value]| value)
+                    .collect(),
+            ),
+            VertexFormat::Float32x2 => VertexAttributeValues::Float32x2(reader.f32s(count)?),
+            VertexFormat::Float32x3 => VertexAttributeValues::Float32x3(reader.f32s(count)?),
+            VertexFormat::Float32x4 => VertexAttributeValues::Float32x4(reader.f32s(count)?),
+            VertexFormat::Uint32 => VertexAttributeValues::Uint32(
+                (0..count).map(|_| reader.u32()).collect::<Result<_, _>>()?,
+            ),
+            _ => return Err("the file contains an unsupported attribute format"),
+        };
+        mesh.insert_attribute(attribute, values);
+    }
+    let index_count = reader.u32()? as usize;
+    let indices: Vec<u32> = (0..index_count)
+        .map(|_| reader.u32())
+        .collect::<Result<_, _>>()?;
+    mesh.insert_indices(Indices::U32(indices));
+    Ok((mesh, has_ior.then_some(ior)))
+}
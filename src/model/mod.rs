@@ -10,22 +10,123 @@ use bevy::{
     render::{mesh::Mesh, texture::Image},
 };
 
-pub use self::{data::VoxelData, voxel::Voxel};
-pub(crate) use palette::MaterialProperty;
-pub(crate) use voxel::RawVoxel;
+pub use self::{
+    air::{VoxelAir, VoxelAirMap},
+    audio::VoxelAudioMaterials,
+    brick::{VoxelBrickHit, VoxelBrickMap},
+    collider::{ColliderFilter, VoxelColliderBox},
+    controller::{VoxelCharacterController, VoxelMoveResult},
+    data::VoxelData,
+    element_data::{VoxelElementData, VoxelElementDataPlugin},
+    fracture::{VoxelFracture, VoxelShard, VoxelShards},
+    grid::VoxelGrid,
+    mask::VoxelEditMask,
+    mesh::{
+        MeshAttributeConfig, ATTRIBUTE_DIRECTIONAL_OCCLUSION, ATTRIBUTE_FACE_ID,
+        ATTRIBUTE_PALETTE_INDEX,
+    },
+    occlusion::{DirectionalOcclusion, VoxelChunkOcclusion},
+    quantize::ColorMetric,
+    sample::VoxelSampler,
+    shape::{VoxelFacing, VoxelShape, VoxelShapes},
+    tint::VoxelTint,
+    voxel::Voxel,
+};
+pub(crate) use voxel::{RawVoxel, SIDES};
+mod air;
+pub(super) mod audio;
+pub(super) mod bitmask;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod blueprint;
+pub(super) mod brick;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod brush;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod clipboard;
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+pub(super) mod clipmap;
+mod collider;
+mod compress;
+mod controller;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod cursor;
 pub(super) mod data;
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+pub(super) mod debris;
+mod element_data;
+mod fracture;
+#[cfg(feature = "generate_voxels")]
+pub(super) mod generate;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod ghost;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod gravity;
+mod grid;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod harvest;
+mod hollow;
+pub(super) mod instance;
+pub(super) mod instance_material;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod integrity;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod interaction;
+mod light;
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+pub(super) mod lighting;
+pub(super) mod lod;
+mod mask;
 pub(super) mod mesh;
+mod mesh_cache;
 #[cfg(feature = "modify_voxels")]
 pub(super) mod modify;
 #[cfg(feature = "modify_voxels")]
+pub(super) mod morphology;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod neighbors;
+pub(super) mod occlusion;
+mod optimize;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod outline;
+mod quantize;
+#[cfg(feature = "modify_voxels")]
 pub(super) mod queryable;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod queue;
+#[cfg(feature = "raymarch")]
+pub(super) mod raymarch;
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+pub(super) mod region;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod resample;
+mod sample;
 #[cfg(feature = "generate_voxels")]
 pub(super) mod sdf;
+mod shape;
+mod stats;
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+pub(super) mod streaming;
+mod surface;
+pub(super) mod swap;
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+pub(super) mod tileset;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod timeline;
+mod tint;
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+pub(super) mod water;
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+pub(super) mod world;
 #[cfg(feature = "modify_voxels")]
 pub use self::queryable::VoxelQueryable;
 mod palette;
-pub use palette::{VoxelElement, VoxelPalette};
-mod voxel;
+#[cfg(feature = "modify_voxels")]
+pub(super) mod palette_texels;
+pub use palette::{
+    MaterialProperty, PaletteLayout, PalettePrecision, VoxelElement, VoxelPalette,
+    VoxelPaletteSummary,
+};
+pub(super) mod voxel;
 
 /// Contains the voxel data for a model, as well as handles to the mesh derived from that data and the material
 #[derive(Asset, TypePath, Default, Clone, Debug)]
@@ -40,6 +141,32 @@ pub struct VoxelModel {
     pub material: Handle<StandardMaterial>,
     /// True if the model contains translucent voxels.
     pub(crate) has_translucency: bool,
+    /// True if the model was loaded with [`crate::VoxLoaderSettings::lazy_meshing`] and hasn't been meshed yet.
+    pub(crate) mesh_pending: bool,
+    /// Handle to the model's brick map, if one was created with [`crate::VoxLoaderSettings::brick_maps`]
+    pub(crate) brick_map: Option<Handle<VoxelBrickMap>>,
+}
+
+impl VoxelModel {
+    /// The number of bytes of voxel data held in memory by the model. This doesn't include the model's mesh, which can
+    /// be evicted from memory with a [`crate::VoxelMemoryBudget`].
+    pub fn memory_usage(&self) -> usize {
+        self.name.capacity() + self.data.memory_usage()
+    }
+
+    /// A hash of the model's voxels, which is the same on every platform and in every release of the crate, for keying
+    /// caches, negotiating assets over the network or invalidating preprocessed assets. It covers the size of the
+    /// model, its voxel size and the palette index of every voxel, and changes as the model is modified. The name,
+    /// palette and meshing settings are not included.
+    pub fn content_hash(&self) -> u64 {
+        self.data.content_hash()
+    }
+
+    /// Handle to the model's [`VoxelBrickMap`], which is kept up to date as the model is modified. This is only
+    /// created if the model was loaded with [`crate::VoxLoaderSettings::brick_maps`] enabled.
+    pub fn brick_map(&self) -> Option<&Handle<VoxelBrickMap>> {
+        self.brick_map.as_ref()
+    }
 }
 
 #[cfg(feature = "generate_voxels")]
@@ -62,22 +189,16 @@ impl VoxelModel {
         contexts: Res<Assets<VoxelContext>>,
     ) -> Option<(Handle<VoxelModel>, VoxelModel)> {
         let context = contexts.get(&context_handle)?;
-        let (mesh, average_ior) = data.remesh(&context.palette.indices_of_refraction);
-        let material = if let Some(ior) = average_ior {
-            let mut transmissive_material =
-                materials.get(context.transmissive_material.id())?.clone();
-            transmissive_material.ior = ior;
-            transmissive_material.thickness = data.size().min_element() as f32;
-            materials.add(transmissive_material)
-        } else {
-            context.opaque_material.clone()
-        };
+        let (mesh, average_ior) = data.remesh(&context.palette);
+        let material = context.material_for(average_ior, &data, &mut materials)?;
         let model = VoxelModel {
             name: name.clone(),
             data,
             mesh: meshes.add(mesh),
             material,
             has_translucency: average_ior.is_some(),
+            mesh_pending: false,
+            brick_map: None,
         };
         let model_handle = models.add(model.clone());
         Some((model_handle, model))
@@ -89,6 +210,8 @@ impl VoxelModel {
 pub struct VoxelContext {
     /// The palette used by the models
     pub palette: VoxelPalette,
+    /// The sound tags of the voxels in the palette
+    pub audio_materials: VoxelAudioMaterials,
 
     pub(crate) opaque_material: Handle<StandardMaterial>,
     pub(crate) transmissive_material: Handle<StandardMaterial>,
@@ -96,6 +219,23 @@ pub struct VoxelContext {
 
 #[cfg(feature = "generate_voxels")]
 impl VoxelContext {
+    /// The material for a model generated from `data`, which is the opaque material unless the model has translucent
+    /// voxels, in which case a transmissive material with the model's average index of refraction is added
+    pub(crate) fn material_for(
+        &self,
+        average_ior: Option<f32>,
+        data: &VoxelData,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Option<Handle<StandardMaterial>> {
+        let Some(ior) = average_ior else {
+            return Some(self.opaque_material.clone());
+        };
+        let mut transmissive_material = materials.get(self.transmissive_material.id())?.clone();
+        transmissive_material.ior = ior;
+        transmissive_material.thickness = data.size().min_element() as f32;
+        Some(materials.add(transmissive_material))
+    }
+
     /// Create a new context with the supplied palette
     pub fn new(world: &mut World, palette: VoxelPalette) -> Handle<VoxelContext> {
         world.run_system_once_with(palette, Self::new_context)
@@ -113,6 +253,7 @@ impl VoxelContext {
         opaque_material.specular_transmission = 0.0;
         let context = VoxelContext {
             palette,
+            audio_materials: VoxelAudioMaterials::default(),
             opaque_material: materials.add(opaque_material),
             transmissive_material: materials.add(material),
         };
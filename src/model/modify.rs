@@ -1,19 +1,33 @@
 use bevy::{
-    asset::{Assets, Handle},
+    asset::{AssetEvent, AssetId, Assets, Handle},
     ecs::{
-        system::{Commands, ResMut, SystemState},
+        entity::Entity,
+        event::EventReader,
+        query::With,
+        system::{Commands, Query, ResMut, SystemState},
         world::{Command, World},
     },
-    math::{IVec3, Vec3},
+    log::info_span,
+    math::{
+        bounding::{Aabb3d, BoundingSphere, BoundingVolume},
+        BVec3, IVec3, Vec3, Vec3A,
+    },
     pbr::StandardMaterial,
     prelude::Res,
-    render::mesh::Mesh,
+    reflect::Reflect,
+    render::{mesh::Mesh, primitives::Aabb},
+    transform::components::GlobalTransform,
+    utils::HashSet,
 };
 use ndshape::Shape;
+use std::sync::Arc;
 
 use crate::VoxelModelInstance;
 
-use super::{RawVoxel, Voxel, VoxelContext, VoxelModel, VoxelQueryable};
+use super::{
+    brick::VoxelBrickMap, brush::VoxelBrush, RawVoxel, Voxel, VoxelContext, VoxelData, VoxelModel,
+    VoxelPalette, VoxelQueryable,
+};
 
 /// Command that programmatically modifies the voxels in a model.
 ///
@@ -71,6 +85,69 @@ pub trait ModifyVoxelCommandsExt {
         region: VoxelRegionMode,
         modify: F,
     ) -> &mut Self;
+
+    /// Run the `modify` closure against every voxel of the `model` that lies within a world-space `region`, converting
+    /// the region to the model's voxel space internally.
+    ///
+    /// ### Arguments
+    /// * `model` - the [`VoxelModelInstance`] to be modified.
+    /// * `global_xform` - the [`GlobalTransform`] of the entity that owns the `model`.
+    /// * `region` - the area to modify in world space, either an [`Aabb3d`] or a [`BoundingSphere`].
+    /// * `modify` - a closure that will run against every voxel whose center lies within the `region`.
+    ///
+    /// ### Arguments passed to the `modify` closure
+    /// * `position` - the position of the current voxel, in voxel space
+    /// * `voxel` - the index of the current voxel
+    /// * `model` - a reference to the model, allowing, for instance, querying neighbouring voxels via the methods in [`crate::VoxelQueryable`]
+    fn modify_voxel_model_in_world<
+        F: Fn(IVec3, &Voxel, &dyn VoxelQueryable) -> Voxel + Send + Sync + 'static,
+    >(
+        &mut self,
+        model: VoxelModelInstance,
+        global_xform: &GlobalTransform,
+        region: impl Into<VoxelWorldRegion>,
+        modify: F,
+    ) -> &mut Self;
+
+    /// Paint the `brush` onto every voxel of the `model` that lies within a world-space `region`, fading towards the
+    /// edges of the region according to the brush's falloff.
+    ///
+    /// ### Arguments
+    /// * `model` - the [`VoxelModelInstance`] to be modified.
+    /// * `global_xform` - the [`GlobalTransform`] of the entity that owns the `model`.
+    /// * `region` - the area to paint in world space, either an [`Aabb3d`] or a [`BoundingSphere`].
+    /// * `brush` - the [`VoxelBrush`] to paint with.
+    fn paint_voxels(
+        &mut self,
+        model: VoxelModelInstance,
+        global_xform: &GlobalTransform,
+        region: impl Into<VoxelWorldRegion>,
+        brush: VoxelBrush,
+    ) -> &mut Self;
+
+    /// Run the `modify` closure against every voxel of every instance that lies within a world-space `region`, for
+    /// instance to blast a crater through every model caught in an explosion.
+    ///
+    /// ### Arguments
+    /// * `instances` - the entities with a [`VoxelModelInstance`] to be modified. Entities whose bounds don't intersect the `region` are skipped.
+    /// * `region` - the area to modify in world space, either an [`Aabb3d`] or a [`BoundingSphere`].
+    /// * `modify` - a closure that will run against every voxel whose center lies within the `region`.
+    ///
+    /// ### Arguments passed to the `modify` closure
+    /// * `position` - the center of the current voxel, in world space
+    /// * `voxel` - the index of the current voxel
+    /// * `model` - a reference to the model, allowing, for instance, querying neighbouring voxels via the methods in [`crate::VoxelQueryable`]
+    ///
+    /// ### Notes
+    /// Instances of the same model share its voxel data, so an edit to one of them appears in all of them.
+    fn modify_voxel_models<
+        F: Fn(Vec3, &Voxel, &dyn VoxelQueryable) -> Voxel + Send + Sync + 'static,
+    >(
+        &mut self,
+        instances: impl IntoIterator<Item = Entity>,
+        region: impl Into<VoxelWorldRegion>,
+        modify: F,
+    ) -> &mut Self;
 }
 
 impl ModifyVoxelCommandsExt for Commands<'_, '_> {
@@ -89,12 +166,182 @@ impl ModifyVoxelCommandsExt for Commands<'_, '_> {
         });
         self
     }
+
+    fn modify_voxel_model_in_world<
+        F: Fn(IVec3, &Voxel, &dyn VoxelQueryable) -> Voxel + Send + Sync + 'static,
+    >(
+        &mut self,
+        model: VoxelModelInstance,
+        global_xform: &GlobalTransform,
+        region: impl Into<VoxelWorldRegion>,
+        modify: F,
+    ) -> &mut Self {
+        self.add(ModifyVoxelModelInWorld {
+            instance: model,
+            xform: *global_xform,
+            region: region.into(),
+            modify: Box::new(modify),
+        });
+        self
+    }
+
+    fn paint_voxels(
+        &mut self,
+        model: VoxelModelInstance,
+        global_xform: &GlobalTransform,
+        region: impl Into<VoxelWorldRegion>,
+        brush: VoxelBrush,
+    ) -> &mut Self {
+        let (xform, region) = (*global_xform, region.into());
+        self.modify_voxel_model_in_world(
+            model,
+            global_xform,
+            region,
+            move |position, voxel, model| {
+                let distance = region.normalized_distance(voxel_center(position, &xform, model));
+                brush.paint(position, voxel, distance)
+            },
+        )
+    }
+
+    fn modify_voxel_models<
+        F: Fn(Vec3, &Voxel, &dyn VoxelQueryable) -> Voxel + Send + Sync + 'static,
+    >(
+        &mut self,
+        instances: impl IntoIterator<Item = Entity>,
+        region: impl Into<VoxelWorldRegion>,
+        modify: F,
+    ) -> &mut Self {
+        self.add(ModifyVoxelModels {
+            instances: instances.into_iter().collect(),
+            region: region.into(),
+            modify: Arc::new(modify),
+        });
+        self
+    }
+}
+
+pub(super) struct ModifyVoxelModelInWorld {
+    pub(super) instance: VoxelModelInstance,
+    pub(super) xform: GlobalTransform,
+    pub(super) region: VoxelWorldRegion,
+    pub(super) modify:
+        Box<dyn Fn(IVec3, &Voxel, &dyn VoxelQueryable) -> Voxel + Send + Sync + 'static>,
+}
+
+impl Command for ModifyVoxelModelInWorld {
+    fn apply(self, world: &mut World) {
+        let Some(region) = world
+            .resource::<Assets<VoxelModel>>()
+            .get(&self.instance.model)
+            .and_then(|model| local_region(model, &self.xform, &self.region.aabb()))
+        else {
+            return;
+        };
+        let (xform, world_region, modify) = (self.xform, self.region, self.modify);
+        ModifyVoxelModel {
+            instance: self.instance,
+            region: VoxelRegionMode::Box(region),
+            modify: Box::new(move |position, voxel, model| {
+                if world_region.contains(voxel_center(position, &xform, model)) {
+                    modify(position, voxel, model)
+                } else {
+                    voxel.clone()
+                }
+            }),
+        }
+        .apply(world);
+    }
 }
 
-struct ModifyVoxelModel {
-    instance: VoxelModelInstance,
-    region: VoxelRegionMode,
-    modify: Box<dyn Fn(IVec3, &Voxel, &dyn VoxelQueryable) -> Voxel + Send + Sync + 'static>,
+struct ModifyVoxelModels {
+    instances: Vec<Entity>,
+    region: VoxelWorldRegion,
+    modify: Arc<dyn Fn(Vec3, &Voxel, &dyn VoxelQueryable) -> Voxel + Send + Sync + 'static>,
+}
+
+impl Command for ModifyVoxelModels {
+    fn apply(self, world: &mut World) {
+        for entity in self.instances {
+            let Some((instance, xform)) = world.get_entity(entity).and_then(|entity| {
+                Some((
+                    entity.get::<VoxelModelInstance>()?.clone(),
+                    *entity.get::<GlobalTransform>()?,
+                ))
+            }) else {
+                continue;
+            };
+            let Some(region) = world
+                .resource::<Assets<VoxelModel>>()
+                .get(&instance.model)
+                .and_then(|model| local_region(model, &xform, &self.region.aabb()))
+            else {
+                continue;
+            };
+            let (world_region, modify) = (self.region, self.modify.clone());
+            ModifyVoxelModel {
+                instance,
+                region: VoxelRegionMode::Box(region),
+                modify: Box::new(move |position, voxel, model| {
+                    let center = voxel_center(position, &xform, model);
+                    if world_region.contains(center) {
+                        modify(center, voxel, model)
+                    } else {
+                        voxel.clone()
+                    }
+                }),
+            }
+            .apply(world);
+        }
+    }
+}
+
+/// The center of the voxel at `position`, in world space
+fn voxel_center(position: IVec3, xform: &GlobalTransform, model: &dyn VoxelQueryable) -> Vec3 {
+    xform.transform_point(model.grid().cell_center_world(position))
+}
+
+/// The region of the `model` covered by the world-space `region`, or `None` if they don't intersect
+fn local_region(
+    model: &VoxelModel,
+    xform: &GlobalTransform,
+    region: &Aabb3d,
+) -> Option<VoxelRegion> {
+    let size = model.size();
+    if size.cmple(IVec3::ZERO).any() {
+        return None;
+    }
+    let voxel_size = model.model_size() / size.as_vec3();
+    let inverse = xform.affine().inverse();
+    let (min, max) = (Vec3::from(region.min), Vec3::from(region.max));
+    let (local_min, local_max) = (0..8)
+        .map(|corner| {
+            let world = Vec3::select(
+                BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                max,
+                min,
+            );
+            inverse.transform_point3(world) / voxel_size + size.as_vec3() * 0.5
+        })
+        .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), point| {
+            (min.min(point), max.max(point))
+        });
+    let origin = local_min.floor().as_ivec3().max(IVec3::ZERO);
+    let end = local_max.ceil().as_ivec3().min(size);
+    if end.cmple(origin).any() {
+        return None;
+    }
+    Some(VoxelRegion {
+        origin,
+        size: end - origin,
+    })
+}
+
+pub(super) struct ModifyVoxelModel {
+    pub(super) instance: VoxelModelInstance,
+    pub(super) region: VoxelRegionMode,
+    pub(super) modify:
+        Box<dyn Fn(IVec3, &Voxel, &dyn VoxelQueryable) -> Voxel + Send + Sync + 'static>,
 }
 
 impl Command for ModifyVoxelModel {
@@ -103,20 +350,22 @@ impl Command for ModifyVoxelModel {
             let mut system_state: SystemState<(
                 ResMut<Assets<Mesh>>,
                 ResMut<Assets<StandardMaterial>>,
+                ResMut<Assets<VoxelBrickMap>>,
                 ResMut<Assets<VoxelModel>>,
                 Res<Assets<VoxelContext>>,
             )> = SystemState::new(world);
-            let (mut meshes, mut materials, mut models, contexts) = system_state.get_mut(world);
+            let (mut meshes, mut materials, mut brick_maps, mut models, contexts) =
+                system_state.get_mut(world);
             let context = contexts.get(self.instance.context.id())?;
             let model = models.get_mut(self.instance.model.id())?;
-            let refraction_indices = &context.palette.indices_of_refraction;
             self.modify_model(
                 model,
                 &mut meshes,
                 &mut materials,
+                &mut brick_maps,
                 context.opaque_material.clone(),
                 context.transmissive_material.clone(),
-                refraction_indices,
+                &context.palette,
             );
             Some(())
         };
@@ -130,54 +379,160 @@ impl ModifyVoxelModel {
         model: &mut VoxelModel,
         meshes: &mut Assets<Mesh>,
         materials: &mut Assets<StandardMaterial>,
+        brick_maps: &mut Assets<VoxelBrickMap>,
         opaque_material: Handle<StandardMaterial>,
         transmissive_material: Handle<StandardMaterial>,
-        refraction_indices: &[Option<f32>],
+        palette: &VoxelPalette,
     ) {
+        self.modify_data(model);
+        update_model_mesh(
+            model,
+            meshes,
+            materials,
+            brick_maps,
+            opaque_material,
+            transmissive_material,
+            palette,
+        );
+    }
+
+    /// Runs the closure against the voxels in the region, without remeshing the model
+    pub(super) fn modify_data(&self, model: &mut VoxelModel) {
+        model.decompress();
         let leading_padding = IVec3::splat(model.data.padding() as i32 / 2);
         let model_size = model.size();
         let region = self.region.clamped(model_size);
         let start = leading_padding + region.origin;
         let end = start + region.size;
         let mut updated: Vec<RawVoxel> = model.data.voxels.clone();
+        let mut histogram = model.data.histogram.clone();
         for x in start.x..end.x {
             for y in start.y..end.y {
                 for z in start.z..end.z {
                     let index = model.data.shape.linearize([x as u32, y as u32, z as u32]) as usize;
                     let source: Voxel = model.data.voxels[index].clone().into();
-                    updated[index] = RawVoxel::from((self.modify)(
-                        IVec3::new(x, y, z) - leading_padding,
-                        &source,
-                        model,
-                    ));
+                    let position = IVec3::new(x, y, z) - leading_padding;
+                    if model.data.is_protected(position, &source) {
+                        continue;
+                    }
+                    updated[index] = RawVoxel::from((self.modify)(position, &source, model));
+                    VoxelData::record_change(
+                        &mut histogram,
+                        &model.data.voxels[index],
+                        &updated[index],
+                    );
                 }
             }
         }
         model.data.voxels = updated;
-        let (mesh, average_ior) = model.data.remesh(refraction_indices);
-        meshes.insert(&model.mesh, mesh);
-        let has_translucency_old_value = model.has_translucency;
-        model.has_translucency = average_ior.is_some();
-        match (has_translucency_old_value, average_ior) {
-            (true, Some(..)) | (false, None) => (), // no change in model's translucency
-            (true, None) => {
-                model.material = opaque_material;
-            }
-            (false, Some(ior)) => {
-                let Some(mut translucent_material) =
-                    materials.get(transmissive_material.id()).cloned()
-                else {
-                    return;
-                };
-                translucent_material.ior = ior;
-                translucent_material.thickness = model.size().min_element() as f32;
-                model.material = materials.add(translucent_material);
-            }
+        model.data.histogram = histogram;
+        model
+            .data
+            .mark_dirty(region.origin, region.origin + region.size);
+    }
+}
+
+/// Remeshes the model after its voxels have been modified, switching its material if its translucency changed, and
+/// rebuilds the bricks of its [`VoxelBrickMap`] that were written to
+pub(super) fn update_model_mesh(
+    model: &mut VoxelModel,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    brick_maps: &mut Assets<VoxelBrickMap>,
+    opaque_material: Handle<StandardMaterial>,
+    transmissive_material: Handle<StandardMaterial>,
+    palette: &VoxelPalette,
+) {
+    let _span = info_span!(
+        "voxel_remesh",
+        name = %model.name,
+        voxels = model.count_voxels()
+    )
+    .entered();
+    if let Some((min, max)) = model.data.dirty_region.take() {
+        if let Some(brick_map) = model
+            .brick_map
+            .as_ref()
+            .and_then(|brick_map| brick_maps.get_mut(brick_map))
+        {
+            brick_map.update_region(&model.data, min, max);
+        }
+    }
+    let (mesh, average_ior) = model.data.remesh(palette);
+    apply_model_mesh(
+        model,
+        mesh,
+        average_ior,
+        meshes,
+        materials,
+        opaque_material,
+        transmissive_material,
+    );
+}
+
+/// Replaces the mesh of the `model` with `mesh`, switching the model between the opaque and a transmissive material if
+/// its translucency changed, where `average_ior` is the average index of refraction of its translucent voxels
+pub(super) fn apply_model_mesh(
+    model: &mut VoxelModel,
+    mesh: Mesh,
+    average_ior: Option<f32>,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    opaque_material: Handle<StandardMaterial>,
+    transmissive_material: Handle<StandardMaterial>,
+) {
+    meshes.insert(&model.mesh, mesh);
+    model.mesh_pending = false;
+    let has_translucency_old_value = model.has_translucency;
+    model.has_translucency = average_ior.is_some();
+    match (has_translucency_old_value, average_ior) {
+        (true, Some(..)) | (false, None) => (), // no change in model's translucency
+        (true, None) => {
+            model.material = opaque_material;
+        }
+        (false, Some(ior)) => {
+            let Some(mut translucent_material) = materials.get(transmissive_material.id()).cloned()
+            else {
+                return;
+            };
+            translucent_material.ior = ior;
+            translucent_material.thickness = model.size().min_element() as f32;
+            model.material = materials.add(translucent_material);
+        }
+    }
+}
+
+/// Keeps the [`Aabb`] of voxel model instances in sync with their meshes after they have been remeshed.
+///
+/// Bevy only calculates the bounds of a mesh when it is first added to an entity, so without this, a model that grows
+/// beyond its original bounds (for instance snow piling up on top of it) would be incorrectly frustum-culled.
+pub(crate) fn update_instance_aabbs(
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    meshes: Res<Assets<Mesh>>,
+    mut instances: Query<(&Handle<Mesh>, &mut Aabb), With<VoxelModelInstance>>,
+) {
+    let modified: HashSet<AssetId<Mesh>> = mesh_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    if modified.is_empty() {
+        return;
+    }
+    for (mesh, mut aabb) in instances.iter_mut() {
+        if !modified.contains(&mesh.id()) {
+            continue;
+        }
+        if let Some(updated) = meshes.get(mesh).and_then(|mesh| mesh.compute_aabb()) {
+            *aabb = updated;
         }
     }
 }
 
 /// The region of the model to modify
+#[derive(Clone, Copy, Debug, Reflect)]
 pub enum VoxelRegionMode {
     /// The entire area of the model
     All,
@@ -186,7 +541,7 @@ pub enum VoxelRegionMode {
 }
 
 impl VoxelRegionMode {
-    fn clamped(&self, model_size: IVec3) -> VoxelRegion {
+    pub(crate) fn clamped(&self, model_size: IVec3) -> VoxelRegion {
         match self {
             VoxelRegionMode::All => VoxelRegion {
                 origin: IVec3::ZERO,
@@ -202,7 +557,68 @@ impl VoxelRegionMode {
     }
 }
 
+/// A region to modify in world space
+#[derive(Clone, Copy, Debug)]
+pub enum VoxelWorldRegion {
+    /// An axis-aligned box
+    Box(Aabb3d),
+    /// A sphere
+    Sphere(BoundingSphere),
+}
+
+impl VoxelWorldRegion {
+    /// The bounds of the region
+    pub fn aabb(&self) -> Aabb3d {
+        match self {
+            VoxelWorldRegion::Box(aabb) => *aabb,
+            VoxelWorldRegion::Sphere(sphere) => {
+                Aabb3d::new(Vec3::from(sphere.center), Vec3::splat(sphere.radius()))
+            }
+        }
+    }
+
+    /// The distance of the world-space `point` from the center of the region, normalized so that the edge of the region
+    /// is 1
+    pub fn normalized_distance(&self, point: Vec3) -> f32 {
+        let point = Vec3A::from(point);
+        match self {
+            VoxelWorldRegion::Box(aabb) => ((point - aabb.center()).abs()
+                / aabb.half_size().max(Vec3A::splat(f32::EPSILON)))
+            .max_element(),
+            VoxelWorldRegion::Sphere(sphere) => {
+                point.distance(sphere.center) / sphere.radius().max(f32::EPSILON)
+            }
+        }
+    }
+
+    /// Whether the world-space `point` lies within the region
+    pub fn contains(&self, point: Vec3) -> bool {
+        let point = Vec3A::from(point);
+        match self {
+            VoxelWorldRegion::Box(aabb) => {
+                point.cmpge(aabb.min).all() && point.cmple(aabb.max).all()
+            }
+            VoxelWorldRegion::Sphere(sphere) => {
+                point.distance_squared(sphere.center) <= sphere.radius() * sphere.radius()
+            }
+        }
+    }
+}
+
+impl From<Aabb3d> for VoxelWorldRegion {
+    fn from(aabb: Aabb3d) -> Self {
+        VoxelWorldRegion::Box(aabb)
+    }
+}
+
+impl From<BoundingSphere> for VoxelWorldRegion {
+    fn from(sphere: BoundingSphere) -> Self {
+        VoxelWorldRegion::Sphere(sphere)
+    }
+}
+
 /// A box region within a model
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
 pub struct VoxelRegion {
     /// The lower-back-left corner of the region
     pub origin: IVec3,
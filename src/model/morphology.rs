@@ -0,0 +1,290 @@
+use bevy::{
+    asset::Assets,
+    ecs::{
+        system::{Commands, Res, ResMut, SystemState},
+        world::{Command, World},
+    },
+    math::IVec3,
+    pbr::StandardMaterial,
+    render::mesh::Mesh,
+    utils::HashMap,
+};
+
+use crate::VoxelModelInstance;
+
+use super::{
+    brick::VoxelBrickMap,
+    modify::{update_model_mesh, ModifyVoxelModel, VoxelRegionMode},
+    Voxel, VoxelContext, VoxelModel, VoxelQueryable, SIDES,
+};
+
+/// Extension to [`Commands`] for growing and shrinking the solid parts of a model
+pub trait MorphologyCommandsExt {
+    /// Grow the solid voxels within the `region` of the `model` by one voxel per iteration, filling empty voxels next to
+    /// a solid voxel with `material`, for instance to build up snow on a surface.
+    ///
+    /// ### Arguments
+    /// * `model` - the [`VoxelModelInstance`] to be modified.
+    /// * `region` - a [`VoxelRegionMode`] defining the area of the voxel model that is grown.
+    /// * `iterations` - the number of voxels to grow by.
+    /// * `material` - the voxel that empty voxels are filled with.
+    fn dilate_voxel_model(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+        iterations: u32,
+        material: Voxel,
+    ) -> &mut Self;
+
+    /// Shrink the solid voxels within the `region` of the `model` by one voxel per iteration, removing solid voxels next
+    /// to an empty voxel, for instance to weather or melt a model. Voxels beyond the bounds of the model count as empty.
+    ///
+    /// ### Arguments
+    /// * `model` - the [`VoxelModelInstance`] to be modified.
+    /// * `region` - a [`VoxelRegionMode`] defining the area of the voxel model that is eroded.
+    /// * `iterations` - the number of voxels to shrink by.
+    fn erode_voxel_model(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+        iterations: u32,
+    ) -> &mut Self;
+
+    /// Smooth the surface within the `region` of the `model` by majority vote, the smooth brush of terrain editors. Each
+    /// voxel becomes solid if the fraction of solid voxels within the `kernel` around it exceeds the kernel's
+    /// threshold, and empty otherwise. Voxels that become solid take the most common voxel in the kernel.
+    ///
+    /// ### Arguments
+    /// * `model` - the [`VoxelModelInstance`] to be modified.
+    /// * `region` - a [`VoxelRegionMode`] defining the area of the voxel model that is smoothed.
+    /// * `iterations` - the number of smoothing passes.
+    /// * `kernel` - the neighbourhood sampled around each voxel.
+    fn smooth_voxel_model(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+        iterations: u32,
+        kernel: VoxelSmoothKernel,
+    ) -> &mut Self;
+}
+
+/// The neighbourhood sampled around each voxel by [`MorphologyCommandsExt::smooth_voxel_model`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoxelSmoothKernel {
+    /// The number of voxels sampled in each direction. Defaults to 1, sampling a 3x3x3 block.
+    pub radius: u32,
+    /// Whether only the voxels within a sphere of the `radius` are sampled, rather than the whole cube. Defaults to
+    /// false.
+    pub spherical: bool,
+    /// The fraction of solid voxels above which a voxel becomes solid. Defaults to 0.5. Lower values grow the model
+    /// while smoothing it, and higher values shrink it.
+    pub threshold: f32,
+}
+
+impl Default for VoxelSmoothKernel {
+    fn default() -> Self {
+        Self {
+            radius: 1,
+            spherical: false,
+            threshold: 0.5,
+        }
+    }
+}
+
+impl VoxelSmoothKernel {
+    /// Creates a cubic kernel sampling `radius` voxels in each direction
+    pub fn new(radius: u32) -> Self {
+        Self {
+            radius,
+            ..Default::default()
+        }
+    }
+
+    /// Sets whether only the voxels within a sphere are sampled
+    pub fn with_spherical(mut self, spherical: bool) -> Self {
+        self.spherical = spherical;
+        self
+    }
+
+    /// Sets the fraction of solid voxels above which a voxel becomes solid
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub(crate) fn offsets(&self) -> Vec<IVec3> {
+        let radius = self.radius as i32;
+        let mut offsets = Vec::new();
+        for z in -radius..=radius {
+            for y in -radius..=radius {
+                for x in -radius..=radius {
+                    let offset = IVec3::new(x, y, z);
+                    if !self.spherical || offset.length_squared() <= radius * radius {
+                        offsets.push(offset);
+                    }
+                }
+            }
+        }
+        offsets
+    }
+}
+
+impl MorphologyCommandsExt for Commands<'_, '_> {
+    fn dilate_voxel_model(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+        iterations: u32,
+        material: Voxel,
+    ) -> &mut Self {
+        self.add(MorphVoxelModel {
+            instance: model,
+            region,
+            iterations,
+            operation: Morphology::Dilate(material),
+        });
+        self
+    }
+
+    fn erode_voxel_model(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+        iterations: u32,
+    ) -> &mut Self {
+        self.add(MorphVoxelModel {
+            instance: model,
+            region,
+            iterations,
+            operation: Morphology::Erode,
+        });
+        self
+    }
+
+    fn smooth_voxel_model(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+        iterations: u32,
+        kernel: VoxelSmoothKernel,
+    ) -> &mut Self {
+        self.add(MorphVoxelModel {
+            instance: model,
+            region,
+            iterations,
+            operation: Morphology::Smooth {
+                offsets: kernel.offsets(),
+                threshold: kernel.threshold,
+            },
+        });
+        self
+    }
+}
+
+#[derive(Clone)]
+enum Morphology {
+    Dilate(Voxel),
+    Erode,
+    Smooth { offsets: Vec<IVec3>, threshold: f32 },
+}
+
+impl Morphology {
+    fn apply(&self, position: IVec3, voxel: &Voxel, model: &dyn VoxelQueryable) -> Voxel {
+        let is_solid = |offset: &IVec3| {
+            model
+                .get_voxel_at_point(position + *offset)
+                .is_ok_and(|neighbor| neighbor != Voxel::EMPTY)
+        };
+        match self {
+            Morphology::Dilate(material) => {
+                if *voxel == Voxel::EMPTY && SIDES.iter().any(is_solid) {
+                    material.clone()
+                } else {
+                    voxel.clone()
+                }
+            }
+            Morphology::Erode => {
+                if *voxel != Voxel::EMPTY && !SIDES.iter().all(is_solid) {
+                    Voxel::EMPTY
+                } else {
+                    voxel.clone()
+                }
+            }
+            Morphology::Smooth { offsets, threshold } => {
+                // only voxels within the model are sampled, so that the edges of the model aren't eroded
+                let mut samples = 0;
+                let mut counts: HashMap<Voxel, u32> = HashMap::new();
+                for offset in offsets.iter() {
+                    let Ok(neighbor) = model.get_voxel_at_point(position + *offset) else {
+                        continue;
+                    };
+                    samples += 1;
+                    if neighbor != Voxel::EMPTY {
+                        *counts.entry(neighbor).or_default() += 1;
+                    }
+                }
+                let solid: u32 = counts.values().sum();
+                if samples == 0 || solid as f32 / samples as f32 <= *threshold {
+                    Voxel::EMPTY
+                } else if *voxel != Voxel::EMPTY {
+                    voxel.clone()
+                } else {
+                    // ties are broken by the lowest index, so that the result is deterministic
+                    counts
+                        .into_iter()
+                        .max_by(|a, b| a.1.cmp(&b.1).then(b.0 .0.cmp(&a.0 .0)))
+                        .map(|(voxel, _)| voxel)
+                        .unwrap_or(Voxel::EMPTY)
+                }
+            }
+        }
+    }
+}
+
+struct MorphVoxelModel {
+    instance: VoxelModelInstance,
+    region: VoxelRegionMode,
+    iterations: u32,
+    operation: Morphology,
+}
+
+impl Command for MorphVoxelModel {
+    fn apply(self, world: &mut World) {
+        let mut system_state: SystemState<(
+            ResMut<Assets<Mesh>>,
+            ResMut<Assets<StandardMaterial>>,
+            ResMut<Assets<VoxelBrickMap>>,
+            ResMut<Assets<VoxelModel>>,
+            Res<Assets<VoxelContext>>,
+        )> = SystemState::new(world);
+        let (mut meshes, mut materials, mut brick_maps, mut models, contexts) =
+            system_state.get_mut(world);
+        let (Some(model), Some(context)) = (
+            models.get_mut(self.instance.model.id()),
+            contexts.get(self.instance.context.id()),
+        ) else {
+            return;
+        };
+        // each iteration reads the voxels written by the previous one
+        for _ in 0..self.iterations {
+            let operation = self.operation.clone();
+            ModifyVoxelModel {
+                instance: self.instance.clone(),
+                region: self.region,
+                modify: Box::new(move |position, voxel, model| {
+                    operation.apply(position, voxel, model)
+                }),
+            }
+            .modify_data(model);
+        }
+        update_model_mesh(
+            model,
+            &mut meshes,
+            &mut materials,
+            &mut brick_maps,
+            context.opaque_material.clone(),
+            context.transmissive_material.clone(),
+            &context.palette,
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use bevy::{
+    asset::{AssetEvent, AssetId, Assets, Handle},
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        entity::Entity,
+        event::EventReader,
+        removal_detection::RemovedComponents,
+        system::{Commands, Query, Res, ResMut},
+        world::Ref,
+    },
+    math::{IVec3, UVec3},
+    pbr::StandardMaterial,
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    render::mesh::Mesh,
+    transform::components::GlobalTransform,
+    utils::HashSet,
+};
+use block_mesh::VoxelVisibility;
+use ndshape::{RuntimeShape, Shape};
+
+use crate::VoxelModelInstance;
+
+use super::{
+    mesh::mesh_model, voxel::VisibleVoxel, RawVoxel, VoxelContext, VoxelData, VoxelModel,
+    VoxelPalette, VoxelQueryable, SIDES,
+};
+
+/// Registers the [`VoxelModelInstance`]s placed flush against the sides of an instance, such as the neighboring tiles
+/// of a tileset, so that the faces on the shared borders are culled where the neighbor has a solid voxel, and generated
+/// where it doesn't, rather than being either always or never generated.
+///
+/// Add the component to the entity holding the [`VoxelModelInstance`], and register the instance with its neighbors in
+/// turn. Models loaded without [`crate::VoxLoaderSettings::mesh_outer_faces`] are meshed as a whole, gaining the faces
+/// against empty voxels of their neighbors, while the sides without a neighbor keep their outer faces hidden. The
+/// instance is given a mesh of its own, which is rebuilt when the neighbors are changed, when the voxels of its model or
+/// of a neighbor's model are modified, and when the instance or a neighbor is moved. The voxels of the neighbors are
+/// looked up in global space, so neighbors can differ in size and offset, but should share the voxel size.
+#[derive(Component, Reflect, Clone, Debug, Default, PartialEq)]
+#[reflect(Component)]
+pub struct VoxelNeighbors {
+    /// The instance against each side of the model, in the order `-X, -Y, -Z, +X, +Y, +Z` of the model's local space
+    pub sides: [Option<Entity>; 6],
+}
+
+impl VoxelNeighbors {
+    /// Registers the `neighbor` against the `side` of the model, given as a unit axis in the model's local space such
+    /// as [`IVec3::X`]. Other values are ignored.
+    pub fn with(mut self, side: IVec3, neighbor: Entity) -> Self {
+        if let Some(index) = SIDES.iter().position(|candidate| *candidate == side) {
+            self.sides[index] = Some(neighbor);
+        }
+        self
+    }
+
+    /// The instance registered against the `side` of the model
+    pub fn get(&self, side: IVec3) -> Option<Entity> {
+        SIDES
+            .iter()
+            .position(|candidate| *candidate == side)
+            .and_then(|index| self.sides[index])
+    }
+}
+
+/// The mesh of an instance with [`VoxelNeighbors`], along with the border it was culled against
+#[derive(Component)]
+pub(crate) struct VoxelNeighborMesh {
+    border: Vec<(IVec3, RawVoxel)>,
+    /// Which sides had a neighbor
+    sides: [bool; 6],
+    mesh: Handle<Mesh>,
+}
+
+impl VoxelData {
+    /// Meshes the model like [`VoxelData::remesh`], except that the faces on the outside of the model are culled against
+    /// the `border`, the solid voxels just outside the model in voxel space, rather than against empty space. Models
+    /// without outer faces are meshed from a padded copy, with the outer faces of the sides that have no neighbor in
+    /// `sides` kept hidden. Returns the average index of refraction of the model's translucent voxels, like `remesh`.
+    pub(crate) fn remesh_with_border(
+        &self,
+        palette: &VoxelPalette,
+        border: &[(IVec3, RawVoxel)],
+        sides: [bool; 6],
+    ) -> (Mesh, Option<f32>) {
+        let padded_copy;
+        let data = if self.mesh_outer_faces {
+            self
+        } else {
+            padded_copy = self.padded_copy();
+            &padded_copy
+        };
+        let (mut visible_voxels, average_ior) = data.visible_voxels(&palette.indices_of_refraction);
+        let shapes = data.shapes.raw_shapes();
+        let mut ior_for_voxel = palette.indices_of_refraction.clone();
+        ior_for_voxel.resize(256, None);
+        let padded_size = IVec3::from_array(data.shape.as_array().map(|x| x as i32));
+        let mut set_border = |position: IVec3, voxel: VisibleVoxel| {
+            let padded = position + IVec3::ONE;
+            if padded.cmplt(IVec3::ZERO).any() || padded.cmpge(padded_size).any() {
+                return;
+            }
+            visible_voxels[data.shape.linearize(padded.as_uvec3().into()) as usize] = voxel;
+        };
+        if !self.mesh_outer_faces {
+            // an opaque voxel that is never meshed itself hides the outer faces of the sides without a neighbor
+            let hidden = VisibleVoxel {
+                index: 0,
+                visibility: VoxelVisibility::Opaque,
+            };
+            for (side, _) in SIDES.iter().zip(sides).filter(|(_, side)| !side) {
+                border_cells(self._size(), *side).for_each(|cell| set_border(cell, hidden));
+            }
+        }
+        for (position, voxel) in border.iter() {
+            set_border(
+                *position,
+                data.visible_voxel(voxel, &shapes, &ior_for_voxel),
+            );
+        }
+        (mesh_model(&visible_voxels, data, palette), average_ior)
+    }
+
+    /// A copy of the data with a layer of empty voxels around it, for meshing a model without outer faces against the
+    /// voxels of its neighbors
+    fn padded_copy(&self) -> VoxelData {
+        let size = self._size().max(IVec3::ZERO).as_uvec3();
+        let shape = RuntimeShape::<u32, 3>::new((size + UVec3::splat(2)).into());
+        let mut voxels = vec![RawVoxel::EMPTY; shape.size() as usize];
+        let leading_padding = UVec3::splat(self.padding() / 2);
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let position = UVec3::new(x, y, z);
+                    voxels[shape.linearize((position + UVec3::ONE).into()) as usize] = self
+                        .voxel_at_index(
+                            self.shape.linearize((position + leading_padding).into()) as usize
+                        )
+                        .clone();
+                }
+            }
+        }
+        VoxelData {
+            shape,
+            voxels,
+            compressed: None,
+            mesh_outer_faces: true,
+            // light levels are laid out in the padding of the original data
+            light: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// The cells just outside the `side` of a model of the supplied `size`, in voxel space
+pub(super) fn border_cells(size: IVec3, side: IVec3) -> impl Iterator<Item = IVec3> {
+    let min = IVec3::select(side.cmpgt(IVec3::ZERO), size, side.min(IVec3::ZERO));
+    let max = IVec3::select(side.cmpeq(IVec3::ZERO), size, min + IVec3::ONE);
+    (min.z..max.z).flat_map(move |z| {
+        (min.y..max.y).flat_map(move |y| (min.x..max.x).map(move |x| IVec3::new(x, y, z)))
+    })
+}
+
+pub(crate) fn update_voxel_neighbors(
+    mut commands: Commands,
+    instances: Query<(
+        Entity,
+        Ref<VoxelNeighbors>,
+        Ref<VoxelModelInstance>,
+        Ref<GlobalTransform>,
+        Option<&VoxelNeighborMesh>,
+        Option<&Handle<StandardMaterial>>,
+    )>,
+    all_instances: Query<(Ref<VoxelModelInstance>, Ref<GlobalTransform>)>,
+    mut removed_neighbors: RemovedComponents<VoxelNeighbors>,
+    mut removed_instances: RemovedComponents<VoxelModelInstance>,
+    mut model_events: EventReader<AssetEvent<VoxelModel>>,
+    models: Res<Assets<VoxelModel>>,
+    contexts: Res<Assets<VoxelContext>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for entity in removed_neighbors.read() {
+        let Ok((instance, _)) = all_instances.get(entity) else {
+            continue;
+        };
+        if let Some(model) = models.get(&instance.model) {
+            commands
+                .entity(entity)
+                .remove::<VoxelNeighborMesh>()
+                .insert(model.mesh.clone());
+        }
+    }
+    let modified_models: HashSet<AssetId<VoxelModel>> = model_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    let removed_instances: HashSet<Entity> = removed_instances.read().collect();
+    for (entity, neighbors, instance, xform, neighbor_mesh, material) in instances.iter() {
+        // moving the instance or a neighbor changes the border, but the mesh is only rebuilt if the border does
+        let neighbor_changed = xform.is_changed()
+            || neighbors.sides.iter().flatten().any(|neighbor| {
+                removed_instances.contains(neighbor)
+                    || all_instances
+                        .get(*neighbor)
+                        .is_ok_and(|(neighbor, neighbor_xform)| {
+                            neighbor.is_changed()
+                                || neighbor_xform.is_changed()
+                                || modified_models.contains(&neighbor.model.id())
+                        })
+            });
+        let own_changed = neighbor_mesh.is_none()
+            || neighbors.is_changed()
+            || instance.is_changed()
+            || modified_models.contains(&instance.model.id());
+        if !own_changed && !neighbor_changed {
+            continue;
+        }
+        let (Some(model), Some(context)) =
+            (models.get(&instance.model), contexts.get(&instance.context))
+        else {
+            continue;
+        };
+        let grid = model.grid();
+        let mut border: Vec<(IVec3, RawVoxel)> = Vec::new();
+        let mut sides = [false; 6];
+        for ((side, neighbor), has_neighbor) in SIDES
+            .iter()
+            .zip(neighbors.sides.iter())
+            .zip(sides.iter_mut())
+        {
+            let Some((neighbor, neighbor_xform)) =
+                neighbor.and_then(|neighbor| all_instances.get(neighbor).ok())
+            else {
+                continue;
+            };
+            let Some(neighbor_model) = models.get(&neighbor.model) else {
+                continue;
+            };
+            *has_neighbor = true;
+            let neighbor_grid = neighbor_model.grid();
+            let to_neighbor = neighbor_xform.affine().inverse() * xform.affine();
+            for cell in border_cells(model.size(), *side) {
+                let point = to_neighbor.transform_point3(grid.cell_center_world(cell));
+                let Ok(voxel) =
+                    neighbor_model.get_voxel_at_point(neighbor_grid.world_to_cell(point))
+                else {
+                    continue;
+                };
+                let voxel = RawVoxel::from(voxel);
+                if voxel != RawVoxel::EMPTY {
+                    border.push((cell, voxel));
+                }
+            }
+        }
+        if !own_changed
+            && neighbor_mesh.is_some_and(|mesh| mesh.border == border && mesh.sides == sides)
+        {
+            continue;
+        }
+        let (mesh, _) = model
+            .data
+            .remesh_with_border(&context.palette, &border, sides);
+        let handle = match neighbor_mesh {
+            Some(neighbor_mesh) => {
+                meshes.insert(&neighbor_mesh.mesh, mesh);
+                neighbor_mesh.mesh.clone()
+            }
+            None => meshes.add(mesh),
+        };
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert((
+            handle.clone(),
+            VoxelNeighborMesh {
+                border,
+                sides,
+                mesh: handle,
+            },
+        ));
+        // the model's material switches between opaque and transmissive as its translucent voxels are edited, which
+        // instances with a material of their own don't follow
+        if material.is_some_and(|material| *material != model.material) {
+            entity_commands.insert(model.material.clone());
+        }
+    }
+}
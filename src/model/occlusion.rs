@@ -0,0 +1,227 @@
+use bevy::math::{IVec3, UVec3, Vec3};
+use ndshape::Shape;
+use serde::{Deserialize, Serialize};
+
+use super::{RawVoxel, VoxelData, VoxelPalette, VoxelShape, SIDES};
+
+/// Solidity and visibility flags for one chunk of a [`VoxelData`], computed with [`VoxelData::chunk_occlusion`].
+///
+/// Chunked meshers can use these hints to skip meshing chunks that can never be seen, and to skip the faces between
+/// neighboring chunks that are both fully solid. [`crate::VoxelWorld`] uses them to skip meshing and drawing chunks that
+/// are solid and surrounded by solid chunks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoxelChunkOcclusion {
+    /// The position of the chunk in the grid of chunks
+    pub chunk: UVec3,
+    /// The position of the chunk's minimum corner, in voxels
+    pub min: UVec3,
+    /// The size of the chunk in voxels. Chunks on the far edges of the model may be smaller than the chunk size.
+    pub size: UVec3,
+    /// True if every voxel in the chunk is an opaque cube
+    pub solid: bool,
+    /// True if the chunk contains no voxels
+    pub empty: bool,
+    /// True if the chunk is solid and every neighboring chunk is solid, so none of its faces can be seen. Chunks on the
+    /// edge of the model are only enclosed if the outer faces of the model aren't meshed.
+    pub enclosed: bool,
+}
+
+impl VoxelChunkOcclusion {
+    /// Whether the chunk needs to be meshed at all
+    pub fn needs_mesh(&self) -> bool {
+        !self.empty && !self.enclosed
+    }
+}
+
+impl VoxelData {
+    /// Divides the model into cubic chunks with `chunk_size` voxels along each side, and returns the occlusion flags of
+    /// each chunk, ordered by x, then y, then z.
+    ///
+    /// Voxels with an index of refraction in the `palette` are translucent, and voxels drawn as water or with a
+    /// [`crate::VoxelShape`] other than a cube don't fill their cell, so neither ever make a chunk solid.
+    pub fn chunk_occlusion(
+        &self,
+        palette: &VoxelPalette,
+        chunk_size: u32,
+    ) -> Vec<VoxelChunkOcclusion> {
+        let chunk_size = chunk_size.max(1);
+        let size = self._size().as_uvec3();
+        let chunks = (size + UVec3::splat(chunk_size - 1)) / chunk_size;
+        let leading_padding = UVec3::splat(self.padding() / 2);
+        let shapes = self.shapes.raw_shapes();
+        let mut flags = Vec::with_capacity((chunks.x * chunks.y * chunks.z) as usize);
+        for z in 0..chunks.z {
+            for y in 0..chunks.y {
+                for x in 0..chunks.x {
+                    let chunk = UVec3::new(x, y, z);
+                    let min = chunk * chunk_size;
+                    let max = (min + UVec3::splat(chunk_size)).min(size);
+                    let mut solid = true;
+                    let mut empty = true;
+                    for vz in min.z..max.z {
+                        for vy in min.y..max.y {
+                            for vx in min.x..max.x {
+                                let position = UVec3::new(vx, vy, vz) + leading_padding;
+                                let raw = self
+                                    .voxel_at_index(self.shape.linearize(position.into()) as usize);
+                                if *raw == RawVoxel::EMPTY {
+                                    solid = false;
+                                } else {
+                                    empty = false;
+                                    if self.water.as_ref() == Some(raw)
+                                        || shapes[raw.0 as usize] != VoxelShape::Cube
+                                        || palette
+                                            .indices_of_refraction
+                                            .get(raw.0 as usize)
+                                            .is_some_and(|ior| ior.is_some())
+                                    {
+                                        solid = false;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    flags.push(VoxelChunkOcclusion {
+                        chunk,
+                        min,
+                        size: max - min,
+                        solid,
+                        empty,
+                        enclosed: false,
+                    });
+                }
+            }
+        }
+        let chunk_index = |chunk: IVec3| -> Option<usize> {
+            if chunk.cmplt(IVec3::ZERO).any() || chunk.cmpge(chunks.as_ivec3()).any() {
+                return None;
+            }
+            let chunk = chunk.as_uvec3();
+            Some((chunk.x + chunk.y * chunks.x + chunk.z * chunks.x * chunks.y) as usize)
+        };
+        let solid: Vec<bool> = flags.iter().map(|flags| flags.solid).collect();
+        for flags in flags.iter_mut().filter(|flags| flags.solid) {
+            flags.enclosed =
+                SIDES.iter().all(
+                    |offset| match chunk_index(flags.chunk.as_ivec3() + *offset) {
+                        Some(neighbor) => solid[neighbor],
+                        None => !self.mesh_outer_faces,
+                    },
+                );
+        }
+        flags
+    }
+}
+
+/// Directions from which a model is lit, used to bake a static, per-vertex occlusion term into the meshes generated
+/// from a [`VoxelData`] as [`crate::ATTRIBUTE_DIRECTIONAL_OCCLUSION`].
+///
+/// This gives stylized shadows to custom shaders on platforms where shadow maps are too costly. Each vertex stores the
+/// fraction of the `directions` in which a ray leaving the vertex escapes the model without hitting a voxel. As the
+/// occlusion is baked per vertex, large flat faces are only sampled at their corners.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct DirectionalOcclusion {
+    /// The directions towards each light, in bevy's Y-up space. No occlusion is baked if this is empty, which is the
+    /// default.
+    pub directions: Vec<[f32; 3]>,
+    /// The furthest distance in voxels that a voxel can cast a shadow. Defaults to 32.
+    pub max_distance: f32,
+}
+
+impl Default for DirectionalOcclusion {
+    fn default() -> Self {
+        Self {
+            directions: Vec::new(),
+            max_distance: 32.0,
+        }
+    }
+}
+
+impl DirectionalOcclusion {
+    /// Creates the bake settings for the supplied light directions
+    pub fn new(directions: impl IntoIterator<Item = Vec3>) -> Self {
+        Self {
+            directions: directions.into_iter().map(|dir| dir.to_array()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Whether any occlusion will be baked
+    pub fn is_enabled(&self) -> bool {
+        !self.directions.is_empty()
+    }
+
+    /// The fraction of the directions that are unoccluded at each vertex, for `positions` and `normals` in the space of
+    /// meshes generated from the `data`
+    pub(crate) fn bake(
+        &self,
+        data: &VoxelData,
+        positions: &[[f32; 3]],
+        normals: &[[f32; 3]],
+    ) -> Vec<f32> {
+        let directions: Vec<Vec3> = self
+            .directions
+            .iter()
+            .map(|dir| Vec3::from(*dir).normalize_or_zero())
+            .filter(|dir| *dir != Vec3::ZERO)
+            .collect();
+        if directions.is_empty() {
+            return vec![1.0; positions.len()];
+        }
+        positions
+            .chunks(4)
+            .zip(normals.chunks(4))
+            .flat_map(|(quad, quad_normals)| {
+                let quad: Vec<Vec3> = quad
+                    .iter()
+                    .map(|position| Vec3::from(*position) / data.voxel_size)
+                    .collect();
+                let center = quad.iter().sum::<Vec3>() / quad.len() as f32;
+                quad.into_iter()
+                    .zip(quad_normals)
+                    .map(|(position, normal)| {
+                        let normal = Vec3::from(*normal);
+                        // nudge the origin towards the center of the face, so that it is clear which voxels the
+                        // vertex belongs to
+                        let origin = position
+                            + (center - position).normalize_or_zero() * 0.01
+                            + normal * 0.5;
+                        let lit = directions
+                            .iter()
+                            .filter(|dir| {
+                                normal.dot(**dir) > 0.0 && !self.is_occluded(data, origin, **dir)
+                            })
+                            .count();
+                        lit as f32 / directions.len() as f32
+                    })
+                    .collect::<Vec<f32>>()
+            })
+            .collect()
+    }
+
+    fn is_occluded(&self, data: &VoxelData, origin: Vec3, direction: Vec3) -> bool {
+        const STEP: f32 = 0.5;
+        let size = data._size().as_vec3();
+        let leading_padding = UVec3::splat(data.padding() / 2);
+        let mut distance = 0.0;
+        while distance <= self.max_distance {
+            let point = origin + direction * distance;
+            let leaving = (point.cmplt(Vec3::ZERO) & direction.cmple(Vec3::ZERO))
+                | (point.cmpge(size) & direction.cmpge(Vec3::ZERO));
+            if leaving.any() {
+                return false;
+            }
+            if point.cmpge(Vec3::ZERO).all() && point.cmplt(size).all() {
+                let position = point.floor().as_uvec3() + leading_padding;
+                if *data.voxel_at_index(data.shape.linearize(position.into()) as usize)
+                    != RawVoxel::EMPTY
+                {
+                    return true;
+                }
+            }
+            distance += STEP;
+        }
+        false
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::utils::HashMap;
+
+/// The bit patterns of the attributes of each vertex of a mesh, so that vertices can be compared exactly
+pub(super) struct VertexKeys(Vec<Vec<u32>>);
+
+impl VertexKeys {
+    pub(super) fn new(vertex_count: usize) -> Self {
+        Self(vec![Vec::new(); vertex_count])
+    }
+
+    /// Adds an attribute with `N` floats per vertex
+    pub(super) fn push_floats<const N: usize>(&mut self, values: &[[f32; N]]) {
+        for (key, value) in self.0.iter_mut().zip(values) {
+            key.extend(value.iter().map(|component| component.to_bits()));
+        }
+    }
+
+    /// Adds an attribute with a single float per vertex
+    pub(super) fn push_float(&mut self, values: &[f32]) {
+        for (key, value) in self.0.iter_mut().zip(values) {
+            key.push(value.to_bits());
+        }
+    }
+
+    /// Adds an attribute with a single integer per vertex
+    pub(super) fn push_uint(&mut self, values: &[u32]) {
+        for (key, value) in self.0.iter_mut().zip(values) {
+            key.push(*value);
+        }
+    }
+}
+
+/// Merges the vertices whose attributes are identical, and reorders the triangles and vertices so that the GPU can
+/// reuse transformed vertices and fetch them in order. With the `meshopt` feature, the triangles are ordered for the
+/// post-transform vertex cache; otherwise they keep their original order.
+///
+/// Rewrites the `indices` in place, and returns the original index of each vertex of the optimized mesh. Use
+/// [`select_vertices`] to apply it to each attribute.
+pub(super) fn optimize_vertices(keys: VertexKeys, indices: &mut [u32]) -> Vec<usize> {
+    // weld identical vertices, keeping the first of each
+    let mut unique: HashMap<Vec<u32>, u32> = HashMap::new();
+    let mut welded: Vec<usize> = Vec::new();
+    let remap: Vec<u32> = keys
+        .0
+        .into_iter()
+        .enumerate()
+        .map(|(vertex, key)| {
+            *unique.entry(key).or_insert_with(|| {
+                welded.push(vertex);
+                welded.len() as u32 - 1
+            })
+        })
+        .collect();
+    for index in indices.iter_mut() {
+        *index = remap[*index as usize];
+    }
+
+    #[cfg(feature = "meshopt")]
+    {
+        let ordered = meshopt::optimize_vertex_cache(indices, welded.len());
+        indices.copy_from_slice(&ordered);
+    }
+
+    // number the vertices in the order the triangles first use them
+    let mut fetch_order: Vec<Option<u32>> = vec![None; welded.len()];
+    let mut kept: Vec<usize> = Vec::with_capacity(welded.len());
+    for index in indices.iter_mut() {
+        let vertex = *index as usize;
+        *index = *fetch_order[vertex].get_or_insert_with(|| {
+            kept.push(welded[vertex]);
+            kept.len() as u32 - 1
+        });
+    }
+    kept
+}
+
+/// The values of the vertices `kept` by [`optimize_vertices`]
+pub(super) fn select_vertices<T: Copy>(values: Vec<T>, kept: &[usize]) -> Vec<T> {
+    if values.is_empty() {
+        return values;
+    }
+    kept.iter().map(|vertex| values[*vertex]).collect()
+}
@@ -0,0 +1,153 @@
+use bevy::{
+    asset::{AssetEvent, AssetId, Assets, Handle},
+    color::{palettes::css, Color},
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        entity::Entity,
+        event::EventReader,
+        query::With,
+        system::{Commands, Query, Res, ResMut},
+        world::Ref,
+    },
+    hierarchy::{BuildChildren, DespawnRecursiveExt, Parent},
+    math::{primitives::Cuboid, Vec3},
+    pbr::StandardMaterial,
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    render::{mesh::Mesh, prelude::SpatialBundle, render_resource::Face},
+    transform::components::Transform,
+    utils::HashSet,
+};
+
+use crate::VoxelModelInstance;
+
+use super::{modify::VoxelRegion, VoxelModel, VoxelQueryable};
+
+/// Draws a selection outline around a [`VoxelModelInstance`], or around a region of its voxels, for highlighting the
+/// selection in an editor or the target of a build tool.
+///
+/// Add the component to the entity holding the [`VoxelModelInstance`]. The plugin spawns a child entity with an
+/// inverted hull: a slightly enlarged copy of the model's mesh, or of the region's bounds, with only its back faces
+/// drawn, so that it shows as a rim around the silhouette. Remove the component to remove the outline.
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VoxelOutline {
+    /// The color of the outline. Defaults to yellow.
+    pub color: Color,
+    /// The width of the outline in the local space of the model. Defaults to 0.05.
+    pub width: f32,
+    /// The region of voxels to outline, or `None` to outline the whole model
+    pub region: Option<VoxelRegion>,
+    /// The material used to draw the outline, instead of an unlit material of [`VoxelOutline::color`]. Set
+    /// [`StandardMaterial::cull_mode`] to [`Face::Front`] on a custom material so that only the rim is visible.
+    pub material: Option<Handle<StandardMaterial>>,
+}
+
+impl Default for VoxelOutline {
+    fn default() -> Self {
+        Self {
+            color: css::YELLOW.into(),
+            width: 0.05,
+            region: None,
+            material: None,
+        }
+    }
+}
+
+impl VoxelOutline {
+    /// Create an outline of the whole model in the supplied color
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            ..Default::default()
+        }
+    }
+
+    /// Outlines only the `region` of the model
+    pub fn with_region(mut self, region: VoxelRegion) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Sets the width of the outline, in the local space of the model
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+}
+
+/// Marks the child entity drawing a [`VoxelOutline`]
+#[derive(Component)]
+pub(crate) struct VoxelOutlineHull;
+
+pub(crate) fn update_voxel_outlines(
+    mut commands: Commands,
+    outlines: Query<(Entity, Ref<VoxelOutline>, &VoxelModelInstance)>,
+    hulls: Query<(Entity, &Parent), With<VoxelOutlineHull>>,
+    mut model_events: EventReader<AssetEvent<VoxelModel>>,
+    models: Res<Assets<VoxelModel>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let modified_models: HashSet<AssetId<VoxelModel>> = model_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (hull, parent) in hulls.iter() {
+        let stale = outlines
+            .get(parent.get())
+            .map_or(true, |(_, outline, instance)| {
+                outline.is_changed() || modified_models.contains(&instance.model.id())
+            });
+        if stale {
+            commands.entity(hull).despawn_recursive();
+        }
+    }
+    for (entity, outline, instance) in outlines.iter() {
+        if !outline.is_changed() && !modified_models.contains(&instance.model.id()) {
+            continue;
+        }
+        let Some(model) = models.get(&instance.model) else {
+            continue;
+        };
+        // the meshes of models and cuboids are both centered on their origin, so the hull is scaled about its center
+        let (mesh, center, extents) = match outline.region {
+            Some(region) => {
+                let grid = model.grid();
+                let min = grid.cell_min_world(region.origin);
+                let max = grid.cell_min_world(region.origin + region.size);
+                let extents = max - min;
+                (
+                    meshes.add(Cuboid::from_size(extents)),
+                    (min + max) * 0.5,
+                    extents,
+                )
+            }
+            None => (model.mesh.clone(), Vec3::ZERO, model.model_size()),
+        };
+        let scale =
+            (extents + Vec3::splat(outline.width * 2.0)) / extents.max(Vec3::splat(f32::EPSILON));
+        let material = outline.material.clone().unwrap_or_else(|| {
+            materials.add(StandardMaterial {
+                base_color: outline.color,
+                unlit: true,
+                cull_mode: Some(Face::Front),
+                ..Default::default()
+            })
+        });
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                mesh,
+                material,
+                SpatialBundle::from_transform(
+                    Transform::from_translation(center).with_scale(scale),
+                ),
+                VoxelOutlineHull,
+            ));
+        });
+    }
+}
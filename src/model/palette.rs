@@ -1,18 +1,29 @@
+use std::{
+    any::{Any, TypeId},
+    sync::Arc,
+};
+
 use bevy::{
     asset::{Assets, Handle, LoadContext},
     color::{Color, ColorToComponents, ColorToPacked, LinearRgba},
-    math::FloatExt,
+    log::info_span,
+    math::{FloatExt, UVec2},
     pbr::StandardMaterial,
+    reflect::Reflect,
     render::{
         render_asset::RenderAssetUsages,
         render_resource::{Extent3d, TextureDimension, TextureFormat},
-        texture::Image,
+        texture::{Image, ImageSampler},
     },
+    utils::HashMap,
 };
 use dot_vox::DotVoxData;
+use serde::{Deserialize, Serialize};
+
+use super::{RawVoxel, Voxel};
 
 /// Container for all of the [`VoxelElement`]s that can be used in a [`super::VoxelModel`]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Reflect)]
 pub struct VoxelPalette {
     pub(crate) elements: Vec<VoxelElement>,
     pub(crate) emission: MaterialProperty,
@@ -20,11 +31,95 @@ pub struct VoxelPalette {
     pub(crate) roughness: MaterialProperty,
     pub(crate) transmission: MaterialProperty,
     pub(crate) indices_of_refraction: Vec<Option<f32>>,
+    pub(crate) layout: PaletteLayout,
+    #[reflect(ignore)]
+    pub(crate) sampler: ImageSampler,
+    pub(crate) precision: PalettePrecision,
+    pub(crate) notes: Vec<String>,
+    pub(crate) display_order: Vec<u8>,
+    #[reflect(ignore)]
+    pub(crate) element_data: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+    /// The RON sidecar loaded alongside the palette, parsed into [`super::VoxelElementData`] by a
+    /// [`super::VoxelElementDataPlugin`]
+    #[reflect(ignore)]
+    pub(crate) element_data_source: Option<Arc<str>>,
+}
+
+/// The precision of the textures generated from a [`VoxelPalette`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Reflect)]
+pub enum PalettePrecision {
+    /// Emission is stored as `Rgba32Float`, metalness & roughness as `Rgba16Unorm`, and transmission as `R16Unorm`.
+    #[default]
+    Full,
+    /// Every texture is stored with 8 bits per channel. Emission is normalized against the brightest element in the
+    /// palette, with that maximum applied via [`StandardMaterial::emissive`]. This uses a quarter of the memory of
+    /// [`PalettePrecision::Full`], at the cost of banding between elements with very different emission strengths.
+    Reduced,
+}
+
+/// How the material properties of a [`VoxelPalette`] are laid out in the textures generated from it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Reflect)]
+pub enum PaletteLayout {
+    /// A 16x16 texture atlas, with one texel per palette index.
+    #[default]
+    Grid,
+    /// A 1024x1 strip, with each palette index repeated across 4 neighbouring texels. Meshes sample the middle of each
+    /// run of texels, so filtering the texture doesn't bleed colors between neighbouring palette indices, and colors
+    /// cannot bleed between rows of the palette as there is only one row.
+    Strip,
+}
+
+/// The number of texels each palette index is repeated across in [`PaletteLayout::Strip`]
+const STRIP_TEXELS_PER_INDEX: u32 = 4;
+
+impl PaletteLayout {
+    pub(crate) fn extent(&self) -> Extent3d {
+        let (width, height) = match self {
+            PaletteLayout::Grid => (16, 16),
+            PaletteLayout::Strip => (256 * STRIP_TEXELS_PER_INDEX, 1),
+        };
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        }
+    }
+
+    /// The position of the first texel for the palette index in the textures
+    pub(crate) fn texel(&self, palette_index: u8) -> UVec2 {
+        match self {
+            PaletteLayout::Grid => UVec2::new(palette_index as u32 % 16, palette_index as u32 / 16),
+            PaletteLayout::Strip => UVec2::new(palette_index as u32 * STRIP_TEXELS_PER_INDEX, 0),
+        }
+    }
+
+    /// The number of neighbouring texels along the x axis holding each palette index
+    pub(crate) fn texels_per_index(&self) -> u32 {
+        match self {
+            PaletteLayout::Grid => 1,
+            PaletteLayout::Strip => STRIP_TEXELS_PER_INDEX,
+        }
+    }
+
+    /// The UV coordinate of the center of the texels for the palette index
+    pub(crate) fn uv(&self, palette_index: u8) -> [f32; 2] {
+        match self {
+            PaletteLayout::Grid => [
+                ((palette_index % 16) as f32 + 0.5) / 16.0,
+                ((palette_index / 16) as f32 + 0.5) / 16.0,
+            ],
+            PaletteLayout::Strip => [(palette_index as f32 + 0.5) / 256.0, 0.5],
+        }
+    }
 }
 
-#[derive(PartialEq, Clone, Debug)]
-pub(crate) enum MaterialProperty {
+/// Whether a material property of a [`VoxelPalette`] is the same for every element, in which case no texture needs to
+/// be generated for it.
+#[derive(PartialEq, Clone, Copy, Debug, Reflect)]
+pub enum MaterialProperty {
+    /// The property differs between elements
     VariesPerElement,
+    /// Every element shares this value
     Constant(f32),
 }
 
@@ -38,8 +133,27 @@ impl MaterialProperty {
         }
     }
 }
+/// A description of the material properties of a [`VoxelPalette`], returned by [`VoxelPalette::summary`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelPaletteSummary {
+    /// Whether the emission strength varies between elements
+    pub emission: MaterialProperty,
+    /// Whether the metalness varies between elements
+    pub metalness: MaterialProperty,
+    /// Whether the roughness varies between elements
+    pub roughness: MaterialProperty,
+    /// Whether the translucency varies between elements
+    pub transmission: MaterialProperty,
+    /// The voxels whose elements have an emission strength greater than 0
+    pub emissive: Vec<Voxel>,
+    /// The voxels whose elements have a translucency greater than 0
+    pub translucent: Vec<Voxel>,
+    /// The voxels whose elements have a metalness greater than 0
+    pub metallic: Vec<Voxel>,
+}
+
 /// A material for a type of voxel brick modelled with physical properties such as color, roughness and so on.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Reflect)]
 pub struct VoxelElement {
     /// The base color of the voxel
     pub color: Color,
@@ -68,6 +182,41 @@ impl Default for VoxelElement {
     }
 }
 
+impl VoxelElement {
+    /// Creates a diffuse element with the supplied color
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the [`VoxelElement::emission`] strength
+    pub fn with_emission(mut self, emission: f32) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    /// Sets the [`VoxelElement::roughness`]
+    pub fn with_roughness(mut self, roughness: f32) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    /// Sets the [`VoxelElement::metalness`]
+    pub fn with_metalness(mut self, metalness: f32) -> Self {
+        self.metalness = metalness;
+        self
+    }
+
+    /// Makes the element fully translucent glass with the supplied index of refraction
+    pub fn with_glass(mut self, refraction_index: f32) -> Self {
+        self.translucency = 1.0;
+        self.refraction_index = refraction_index;
+        self
+    }
+}
+
 impl VoxelPalette {
     /// Create a new [`VoxelPalette`] from the supplied [`VoxelElement`]s
     pub fn new(mut elements: Vec<VoxelElement>) -> Self {
@@ -94,9 +243,120 @@ impl VoxelPalette {
             roughness: MaterialProperty::from_slice(&roughness_data),
             transmission: MaterialProperty::from_slice(&translucency_data),
             indices_of_refraction,
+            layout: PaletteLayout::default(),
+            sampler: ImageSampler::nearest(),
+            precision: PalettePrecision::default(),
+            notes: Vec::new(),
+            display_order: Vec::new(),
+            element_data: HashMap::new(),
+            element_data_source: None,
         }
     }
 
+    /// Sets how the palette is laid out in the textures generated from it.
+    pub fn with_layout(mut self, layout: PaletteLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Sets the sampler used by the textures generated from the palette. Defaults to nearest-neighbour sampling.
+    /// Pass [`ImageSampler::Descriptor`] to fully customize the [`bevy::render::texture::ImageSamplerDescriptor`].
+    pub fn with_sampler(mut self, sampler: ImageSampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Sets the precision of the textures generated from the palette. Defaults to [`PalettePrecision::Full`].
+    pub fn with_precision(mut self, precision: PalettePrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Names the rows of the palette, as authored in the palette notes of Magica Voxel. Each note names a row of 8
+    /// palette slots, so that note `n` covers slots `8 * n` to `8 * n + 7`. In the default order these hold the voxels
+    /// `Voxel(8 * n + 1)` to `Voxel(8 * n + 8)`; see [`VoxelPalette::with_display_order`]. Empty notes leave their row
+    /// unnamed.
+    pub fn with_notes(mut self, notes: Vec<String>) -> Self {
+        self.notes = notes;
+        self
+    }
+
+    /// Returns the indices of every [`Voxel`] in the rows of the palette named `name` in the palette notes, so that
+    /// tools can target groups of materials authored in Magica Voxel, such as every shade of "stone".
+    ///
+    /// The indices are in the order the voxels are displayed in Magica Voxel, and empty if no row has the name. See
+    /// [`VoxelPalette::with_notes`].
+    pub fn indices_named(&self, name: &str) -> Vec<u8> {
+        self.notes
+            .iter()
+            .enumerate()
+            .filter(|(_, note)| note.as_str() == name)
+            .flat_map(|(row, _)| (row * 8..row * 8 + 8).filter_map(|slot| self.voxel_in_slot(slot)))
+            .collect()
+    }
+
+    /// Sets the order the palette is displayed in, as saved in the `IMAP` chunk by Magica Voxel 0.99.7 and later after
+    /// rearranging colors in the editor. Entry `n` is the index of the voxel shown in slot `n`. An empty order, which
+    /// is the default, displays `Voxel(n + 1)` in slot `n`.
+    pub fn with_display_order(mut self, display_order: Vec<u8>) -> Self {
+        self.display_order = display_order;
+        self
+    }
+
+    /// The indices of the voxels in the order they are displayed in Magica Voxel, so that tools can present the palette
+    /// as it was arranged by the artist. See [`VoxelPalette::with_display_order`].
+    pub fn display_order(&self) -> Vec<u8> {
+        (0..256)
+            .filter_map(|slot| self.voxel_in_slot(slot))
+            .collect()
+    }
+
+    /// The index of the voxel displayed in `slot`, or `None` for the slot of [`Voxel::EMPTY`]
+    fn voxel_in_slot(&self, slot: usize) -> Option<u8> {
+        let index = if self.display_order.is_empty() {
+            u8::try_from(slot + 1).ok()?
+        } else {
+            *self.display_order.get(slot)?
+        };
+        (index != Voxel::EMPTY.0).then_some(index)
+    }
+
+    /// Returns the [`Voxel`] whose element in this palette most closely matches `element`, comparing color and physical properties.
+    pub fn closest_voxel(&self, element: &VoxelElement) -> Voxel {
+        let target = element.color.to_linear().to_f32_array();
+        let distance = |candidate: &VoxelElement| -> f32 {
+            let color = candidate.color.to_linear().to_f32_array();
+            let color_distance: f32 = (0..4).map(|i| (color[i] - target[i]).powi(2)).sum();
+            color_distance
+                + (candidate.emission - element.emission).powi(2)
+                + (candidate.roughness - element.roughness).powi(2)
+                + (candidate.metalness - element.metalness).powi(2)
+                + (candidate.translucency - element.translucency).powi(2)
+        };
+        let raw_index = self
+            .elements
+            .iter()
+            .take(RawVoxel::EMPTY.0 as usize)
+            .enumerate()
+            .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+            .map_or(0, |(index, _)| index);
+        RawVoxel(raw_index as u8).into()
+    }
+
+    /// Makes every element of the palette opaque, for platforms that don't support specular transmission.
+    pub(crate) fn without_transmission(self) -> Self {
+        VoxelPalette::new(
+            self.elements
+                .iter()
+                .map(|element| VoxelElement {
+                    translucency: 0.0,
+                    ..element.clone()
+                })
+                .collect(),
+        )
+        .with_settings_of(self)
+    }
+
     /// Create a new [`VoxelPalette`] from the supplied [`Color`]s
     pub fn from_colors(colors: Vec<Color>) -> Self {
         VoxelPalette::new(
@@ -110,12 +370,22 @@ impl VoxelPalette {
         )
     }
 
-    /// Create a new [`VoxelPalette`] by interpolating between the [`VoxelElement`] in the gradient stops
+    /// Create a new [`VoxelPalette`] by interpolating between the [`VoxelElement`] in the gradient stops.
+    ///
+    /// The stops are raw palette indices, so stop 0 is the element of [`Voxel`] 1. They are sorted before interpolating.
+    /// Every index from the last stop onwards, up to and including 255, has the element of the last stop, and the
+    /// indices before the first stop keep the default element.
     pub fn from_gradient(stops: &[(u8, VoxelElement)]) -> Self {
         let mut elements = vec![VoxelElement::default(); 256];
-        for (index, (stop, element)) in stops.iter().enumerate() {
-            let default = (u8::MAX - 1, element.clone());
-            let (next_stop, next_element) = stops.get(index + 1).unwrap_or(&default);
+        let mut stops = stops.to_vec();
+        stops.sort_by_key(|(stop, _)| *stop);
+        if let Some((last_stop, last_element)) = stops.last() {
+            elements[*last_stop as usize..].fill(last_element.clone());
+        }
+        for pair in stops.windows(2) {
+            let [(stop, element), (next_stop, next_element)] = pair else {
+                continue;
+            };
             let distance = (next_stop - stop) as f32;
             for i in *stop..*next_stop {
                 let fraction = (i - stop) as f32 / distance;
@@ -141,6 +411,106 @@ impl VoxelPalette {
         VoxelPalette::new(elements)
     }
 
+    /// Create a new [`VoxelPalette`] from a palette image, such as the 256x1 `.png` palettes that Magica Voxel exports.
+    ///
+    /// Any image with 256 texels is accepted, read row by row, so 16x16 palettes can be used too. The first texel is
+    /// the color of [`Voxel`] 1, matching Magica Voxel's palette indices. Returns `None` if the image doesn't have
+    /// exactly 256 texels, or isn't in one of the 8-bit RGBA or BGRA formats.
+    pub fn from_image(image: &Image) -> Option<Self> {
+        let size = image.texture_descriptor.size;
+        if size.width * size.height * size.depth_or_array_layers != 256 {
+            return None;
+        }
+        let color = |texel: &[u8]| -> Option<Color> {
+            let [a, b, c, alpha] = [texel[0], texel[1], texel[2], texel[3]];
+            match image.texture_descriptor.format {
+                TextureFormat::Rgba8UnormSrgb => Some(Color::srgba_u8(a, b, c, alpha)),
+                TextureFormat::Bgra8UnormSrgb => Some(Color::srgba_u8(c, b, a, alpha)),
+                TextureFormat::Rgba8Unorm => Some(Color::LinearRgba(LinearRgba::from_u8_array([
+                    a, b, c, alpha,
+                ]))),
+                TextureFormat::Bgra8Unorm => Some(Color::LinearRgba(LinearRgba::from_u8_array([
+                    c, b, a, alpha,
+                ]))),
+                _ => None,
+            }
+        };
+        let colors: Option<Vec<Color>> = image.data.chunks_exact(4).map(color).collect();
+        colors
+            .filter(|colors| colors.len() == 256)
+            .map(VoxelPalette::from_colors)
+    }
+
+    /// Create a new [`VoxelPalette`] by interpolating between diffuse `colors`, placed at the raw palette indices in
+    /// `stops`, so stop 0 is the color of [`Voxel`] 1.
+    ///
+    /// Each color is paired with the stop at the same position, and any colors or stops without a partner are ignored.
+    /// The stops may be in any order. See [`VoxelPalette::from_gradient`] for the indices outside of the stops.
+    pub fn gradient(colors: &[Color], stops: &[u8]) -> Self {
+        let stops: Vec<(u8, VoxelElement)> = stops
+            .iter()
+            .zip(colors)
+            .map(|(stop, color)| (*stop, VoxelElement::new(*color)))
+            .collect();
+        VoxelPalette::from_gradient(&stops)
+    }
+
+    /// Create a new [`VoxelPalette`] of `count` diffuse colors with evenly spaced hues, starting at red, with the supplied
+    /// saturation and lightness
+    pub fn hue_ramp(count: u8, saturation: f32, lightness: f32) -> Self {
+        VoxelPalette::from_colors(
+            (0..count)
+                .map(|i| Color::hsl(i as f32 * 360.0 / count as f32, saturation, lightness))
+                .collect(),
+        )
+    }
+
+    /// Describes which material properties vary across the palette, and which voxels are emissive, translucent or
+    /// metallic, so that systems spawning lights or choosing sounds don't need to inspect every element.
+    pub fn summary(&self) -> VoxelPaletteSummary {
+        let voxels_where = |predicate: fn(&VoxelElement) -> bool| -> Vec<Voxel> {
+            self.elements
+                .iter()
+                .take(RawVoxel::EMPTY.0 as usize)
+                .enumerate()
+                .filter(|(_, element)| predicate(element))
+                .map(|(index, _)| RawVoxel(index as u8).into())
+                .collect()
+        };
+        VoxelPaletteSummary {
+            emission: self.emission,
+            metalness: self.metalness,
+            roughness: self.roughness,
+            transmission: self.transmission,
+            emissive: voxels_where(|element| element.emission > 0.0),
+            translucent: voxels_where(|element| element.translucency > 0.0),
+            metallic: voxels_where(|element| element.metalness > 0.0),
+        }
+    }
+
+    /// Replaces the element used by `voxel`, keeping the rest of the palette and its settings
+    pub fn with_element(self, voxel: Voxel, element: VoxelElement) -> Self {
+        let raw: RawVoxel = voxel.into();
+        if raw == RawVoxel::EMPTY {
+            return self;
+        }
+        let mut elements = self.elements.clone();
+        elements[raw.0 as usize] = element;
+        VoxelPalette::new(elements).with_settings_of(self)
+    }
+
+    /// Keeps the settings, notes and element data of `source` in a palette rebuilt from its elements
+    fn with_settings_of(mut self, source: VoxelPalette) -> Self {
+        self.layout = source.layout;
+        self.sampler = source.sampler;
+        self.precision = source.precision;
+        self.notes = source.notes;
+        self.display_order = source.display_order;
+        self.element_data = source.element_data;
+        self.element_data_source = source.element_data_source;
+        self
+    }
+
     pub(crate) fn from_data(
         data: &DotVoxData,
         diffuse_roughness: f32,
@@ -181,7 +551,59 @@ impl VoxelPalette {
         &self,
         load_context: &mut LoadContext,
     ) -> StandardMaterial {
-        self._create_material(|name, image| load_context.add_labeled_asset(name.to_string(), image))
+        self.create_material_with_label_prefix(load_context, "")
+    }
+
+    /// Creates the material, adding its textures to the load context with labels starting with `prefix`, so that
+    /// several palettes can be loaded from one asset
+    pub(crate) fn create_material_with_label_prefix(
+        &self,
+        load_context: &mut LoadContext,
+        prefix: &str,
+    ) -> StandardMaterial {
+        self._create_material(|name, image| {
+            load_context.add_labeled_asset(format!("{prefix}{name}"), image)
+        })
+    }
+
+    fn texture_encoding(&self) -> TextureEncoding {
+        let is_reduced = self.precision == PalettePrecision::Reduced;
+        TextureEncoding {
+            has_emission: match self.emission {
+                MaterialProperty::VariesPerElement => true,
+                MaterialProperty::Constant(emission) => emission > 0.0,
+            },
+            has_roughness_metalness: self.roughness == MaterialProperty::VariesPerElement
+                || self.metalness == MaterialProperty::VariesPerElement,
+            has_translucency: self.transmission == MaterialProperty::VariesPerElement,
+            is_reduced,
+            emission_scale: if is_reduced {
+                self.elements
+                    .iter()
+                    .map(|e| e.emission)
+                    .collect::<Vec<f32>>()
+                    .max_element()
+                    .max(f32::EPSILON)
+            } else {
+                1.0
+            },
+        }
+    }
+
+    /// Whether the textures generated from `other` have the same layout, formats and encoding as those generated from
+    /// this palette, so that they can be updated in place, texel by texel, rather than being regenerated
+    pub(crate) fn textures_match(&self, other: &VoxelPalette) -> bool {
+        self.layout == other.layout
+            && self.texture_encoding() == other.texture_encoding()
+            && self.roughness == other.roughness
+            && self.metalness == other.metalness
+            && self.transmission == other.transmission
+    }
+
+    /// The bytes of the element at `palette_index` in each of the textures generated from the palette
+    pub(crate) fn texels(&self, palette_index: u8) -> PaletteTexels {
+        self.texture_encoding()
+            .encode(&self.elements[palette_index as usize])
     }
 
     pub(crate) fn create_material(&self, images: &mut Assets<Image>) -> StandardMaterial {
@@ -192,109 +614,82 @@ impl VoxelPalette {
         &self,
         mut get_handle: impl FnMut(&str, Image) -> Handle<Image>,
     ) -> StandardMaterial {
-        let image_size = Extent3d {
-            width: 16,
-            height: 16,
-            depth_or_array_layers: 1,
+        let _span = info_span!("voxel_palette_bake", elements = self.elements.len()).entered();
+        let image_size = self.layout.extent();
+        let new_image = |data: Vec<u8>, format: TextureFormat| {
+            let mut image = Image::new(
+                image_size,
+                TextureDimension::D2,
+                data,
+                format,
+                RenderAssetUsages::default(),
+            );
+            image.sampler = self.sampler.clone();
+            image
         };
-        let color_data: Vec<u8> = self
+        let encoding = self.texture_encoding();
+        let texels: Vec<PaletteTexels> = self
             .elements
             .iter()
-            .flat_map(|e| e.color.to_linear().to_u8_array())
+            .map(|element| encoding.encode(element))
             .collect();
-        let emission_data: Vec<f32> = self.elements.iter().map(|e| e.emission).collect();
-        let roughness_data: Vec<f32> = self.elements.iter().map(|e| e.roughness).collect();
-        let metalness_data: Vec<f32> = self.elements.iter().map(|e| e.metalness).collect();
-        let translucency_data: Vec<f32> = self.elements.iter().map(|e| e.translucency).collect();
-
-        let has_emission = match self.emission {
-            MaterialProperty::VariesPerElement => true,
-            MaterialProperty::Constant(emission) => emission > 0.0,
+        let texels_per_index = self.layout.texels_per_index() as usize;
+        let texture_data = |texel: fn(&PaletteTexels) -> Option<&Vec<u8>>| -> Vec<u8> {
+            texels
+                .iter()
+                .filter_map(texel)
+                .flat_map(|data| data.repeat(texels_per_index))
+                .collect()
         };
-        let has_roughness = self.roughness == MaterialProperty::VariesPerElement;
-        let has_metalness = self.metalness == MaterialProperty::VariesPerElement;
-        let has_roughness_metalness = has_roughness || has_metalness;
-        let has_translucency = self.transmission == MaterialProperty::VariesPerElement;
+        let TextureEncoding {
+            has_emission,
+            has_roughness_metalness,
+            has_translucency,
+            is_reduced,
+            emission_scale,
+        } = encoding;
 
         let base_color_texture = Some(get_handle(
             "material_color",
-            Image::new(
-                image_size,
-                TextureDimension::D2,
-                color_data,
+            new_image(
+                texture_data(|texel| Some(&texel.color)),
                 TextureFormat::Rgba8UnormSrgb,
-                RenderAssetUsages::default(),
             ),
         ));
 
         let emissive_texture = if has_emission {
-            let emission_bytes: Vec<u8> = emission_data
-                .iter()
-                .zip(self.elements.iter().map(|e| e.color))
-                .flat_map(|(emission, color)| {
-                    (color.to_linear() * *emission)
-                        .to_f32_array()
-                        .iter()
-                        .flat_map(|c| c.to_le_bytes())
-                        .collect::<Vec<u8>>()
-                })
-                .collect();
-            Some(get_handle(
-                "material_emission",
-                Image::new(
-                    image_size,
-                    TextureDimension::D2,
-                    emission_bytes,
-                    TextureFormat::Rgba32Float,
-                    RenderAssetUsages::default(),
-                ),
-            ))
+            let format = if is_reduced {
+                TextureFormat::Rgba8Unorm
+            } else {
+                TextureFormat::Rgba32Float
+            };
+            let data = texture_data(|texel| texel.emission.as_ref());
+            Some(get_handle("material_emission", new_image(data, format)))
         } else {
             None
         };
 
         let metallic_roughness_texture: Option<Handle<Image>> = if has_roughness_metalness {
-            let raw: Vec<u8> = roughness_data
-                .iter()
-                .zip(metalness_data.iter())
-                .flat_map(|(rough, metal)| {
-                    let output: Vec<u8> = [0.0, *rough, *metal, 0.0]
-                        .iter()
-                        .flat_map(|b| ((b * u16::MAX as f32) as u16).to_le_bytes())
-                        .collect();
-                    output
-                })
-                .collect();
-            let handle = get_handle(
-                "material_metallic_roughness",
-                Image::new(
-                    image_size,
-                    TextureDimension::D2,
-                    raw,
-                    TextureFormat::Rgba16Unorm,
-                    RenderAssetUsages::default(),
-                ),
-            );
+            let format = if is_reduced {
+                TextureFormat::Rgba8Unorm
+            } else {
+                TextureFormat::Rgba16Unorm
+            };
+            let data = texture_data(|texel| texel.metallic_roughness.as_ref());
+            let handle = get_handle("material_metallic_roughness", new_image(data, format));
             Some(handle)
         } else {
             None
         };
 
         let specular_transmission_texture: Option<Handle<Image>> = if has_translucency {
-            let raw: Vec<u8> = translucency_data
-                .iter()
-                .flat_map(|t| ((t * u16::MAX as f32) as u16).to_le_bytes())
-                .collect();
-            let handle = get_handle(
-                "material_specular_transmission",
-                Image::new(
-                    image_size,
-                    TextureDimension::D2,
-                    raw,
-                    TextureFormat::R16Unorm,
-                    RenderAssetUsages::default(),
-                ),
-            );
+            let format = if is_reduced {
+                TextureFormat::R8Unorm
+            } else {
+                TextureFormat::R16Unorm
+            };
+            let data = texture_data(|texel| texel.transmission.as_ref());
+            let handle = get_handle("material_specular_transmission", new_image(data, format));
             Some(handle)
         } else {
             None
@@ -303,7 +698,7 @@ impl VoxelPalette {
         StandardMaterial {
             base_color_texture,
             emissive: if has_emission {
-                LinearRgba::WHITE
+                LinearRgba::rgb(emission_scale, emission_scale, emission_scale)
             } else {
                 LinearRgba::BLACK
             },
@@ -327,6 +722,67 @@ impl VoxelPalette {
     }
 }
 
+/// Which textures are generated from a palette, and how its elements are encoded in them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct TextureEncoding {
+    has_emission: bool,
+    has_roughness_metalness: bool,
+    has_translucency: bool,
+    is_reduced: bool,
+    emission_scale: f32,
+}
+
+/// The bytes of a single element in each of the textures generated from a palette, or `None` for the textures that
+/// aren't generated
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PaletteTexels {
+    pub(crate) color: Vec<u8>,
+    pub(crate) emission: Option<Vec<u8>>,
+    pub(crate) metallic_roughness: Option<Vec<u8>>,
+    pub(crate) transmission: Option<Vec<u8>>,
+}
+
+impl TextureEncoding {
+    fn encode(&self, element: &VoxelElement) -> PaletteTexels {
+        let emission = self.has_emission.then(|| {
+            let emissive = element.color.to_linear() * (element.emission / self.emission_scale);
+            if self.is_reduced {
+                emissive.to_u8_array().to_vec()
+            } else {
+                emissive
+                    .to_f32_array()
+                    .iter()
+                    .flat_map(|c| c.to_le_bytes())
+                    .collect()
+            }
+        });
+        let metallic_roughness = self.has_roughness_metalness.then(|| {
+            [0.0, element.roughness, element.metalness, 0.0]
+                .iter()
+                .flat_map(|b| unorm_bytes(*b, self.is_reduced))
+                .collect()
+        });
+        let transmission = self
+            .has_translucency
+            .then(|| unorm_bytes(element.translucency, self.is_reduced));
+        PaletteTexels {
+            color: element.color.to_linear().to_u8_array().to_vec(),
+            emission,
+            metallic_roughness,
+            transmission,
+        }
+    }
+}
+
+/// Encodes a value in the range 0.0 to 1.0 as an 8 or 16 bit unsigned normalized integer
+fn unorm_bytes(value: f32, is_reduced: bool) -> Vec<u8> {
+    if is_reduced {
+        vec![(value * u8::MAX as f32) as u8]
+    } else {
+        ((value * u16::MAX as f32) as u16).to_le_bytes().to_vec()
+    }
+}
+
 trait VecComparable<T> {
     fn max_element(&self) -> T;
 
@@ -0,0 +1,250 @@
+use bevy::{
+    asset::{AssetId, Assets, Handle},
+    ecs::{
+        system::{Commands, Res, ResMut, Resource},
+        world::{Command, World},
+    },
+    math::UVec2,
+    pbr::StandardMaterial,
+    render::{
+        render_asset::RenderAssets,
+        render_resource::{Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d, TextureAspect},
+        renderer::RenderQueue,
+        texture::{GpuImage, Image},
+        MainWorld,
+    },
+};
+
+use super::{RawVoxel, Voxel, VoxelContext, VoxelElement};
+
+/// Extension to [`Commands`] for editing the palette of a [`VoxelContext`] at runtime
+pub trait SetVoxelElementCommandsExt {
+    /// Replaces the [`VoxelElement`] used by `voxel` in the palette of the `context`, such as to animate a color or
+    /// flash an emissive element.
+    ///
+    /// If the textures generated from the palette keep their formats and encoding, the textures are kept, and only the
+    /// texels of the element are rewritten, both in the textures' CPU data and straight into the textures on the GPU.
+    /// Otherwise, such as when the first element with a roughness of its own is added, or when the brightest emission of
+    /// a [`crate::PalettePrecision::Reduced`] palette changes, the textures are regenerated, and every material derived
+    /// from the context, including the materials of its models, is switched to the new textures.
+    ///
+    /// Models are not remeshed, so an element that becomes translucent or stops being translucent only takes effect on
+    /// the meshes generated after the edit.
+    fn set_voxel_element(
+        &mut self,
+        context: Handle<VoxelContext>,
+        voxel: Voxel,
+        element: VoxelElement,
+    ) -> &mut Self;
+}
+
+impl SetVoxelElementCommandsExt for Commands<'_, '_> {
+    fn set_voxel_element(
+        &mut self,
+        context: Handle<VoxelContext>,
+        voxel: Voxel,
+        element: VoxelElement,
+    ) -> &mut Self {
+        self.add(SetVoxelElement {
+            context,
+            voxel,
+            element,
+        });
+        self
+    }
+}
+
+struct SetVoxelElement {
+    context: Handle<VoxelContext>,
+    voxel: Voxel,
+    element: VoxelElement,
+}
+
+impl Command for SetVoxelElement {
+    fn apply(self, world: &mut World) {
+        let raw = RawVoxel::from(self.voxel.clone());
+        if raw == RawVoxel::EMPTY {
+            return;
+        }
+        let Some(context) = world
+            .resource::<Assets<VoxelContext>>()
+            .get(&self.context)
+            .cloned()
+        else {
+            return;
+        };
+        let palette = context
+            .palette
+            .clone()
+            .with_element(self.voxel, self.element);
+        let materials = world.resource::<Assets<StandardMaterial>>();
+        let textures: Option<[Option<Handle<Image>>; 4]> = materials
+            .get(&context.transmissive_material)
+            .filter(|_| context.palette.textures_match(&palette))
+            .map(|material| {
+                [
+                    material.base_color_texture.clone(),
+                    material.emissive_texture.clone(),
+                    material.metallic_roughness_texture.clone(),
+                    material.specular_transmission_texture.clone(),
+                ]
+            });
+        match textures {
+            Some(textures) => {
+                let texels = palette.texels(raw.0);
+                let texel = palette.layout.texel(raw.0);
+                let width = palette.layout.texels_per_index();
+                let data = [
+                    Some(texels.color),
+                    texels.emission,
+                    texels.metallic_roughness,
+                    texels.transmission,
+                ];
+                let writes: Vec<PaletteTexelWrite> = textures
+                    .into_iter()
+                    .zip(data)
+                    .filter_map(|(texture, data)| {
+                        Some(PaletteTexelWrite {
+                            image: texture?.id(),
+                            texel,
+                            width,
+                            data: data?.repeat(width as usize),
+                        })
+                    })
+                    .collect();
+                // keep the CPU data in sync, so that the edit survives the textures being uploaded again
+                let mut images = world.resource_mut::<Assets<Image>>();
+                for write in writes.iter() {
+                    let Some(image) = images.get_mut(write.image) else {
+                        continue;
+                    };
+                    let texel_size = write.data.len() / write.width as usize;
+                    let start =
+                        (write.texel.y * image.width() + write.texel.x) as usize * texel_size;
+                    if let Some(bytes) = image.data.get_mut(start..start + write.data.len()) {
+                        bytes.copy_from_slice(&write.data);
+                    }
+                }
+                world
+                    .resource_mut::<PendingPaletteTexelWrites>()
+                    .0
+                    .extend(writes);
+            }
+            None => {
+                let material = palette.create_material(&mut world.resource_mut::<Assets<Image>>());
+                let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+                let old_texture = materials
+                    .get(&context.opaque_material)
+                    .and_then(|material| material.base_color_texture.clone());
+                // the materials of models and their transmissive variants are copies sharing the context's textures
+                let derived: Vec<AssetId<StandardMaterial>> = materials
+                    .iter()
+                    .filter(|(id, derived)| {
+                        *id == context.opaque_material.id()
+                            || *id == context.transmissive_material.id()
+                            || (old_texture.is_some() && derived.base_color_texture == old_texture)
+                    })
+                    .map(|(id, _)| id)
+                    .collect();
+                for id in derived {
+                    let transmissive = id == context.transmissive_material.id();
+                    if let Some(derived) = materials.get_mut(id) {
+                        use_palette_textures(derived, &material, transmissive);
+                    }
+                }
+            }
+        }
+        if let Some(context) = world
+            .resource_mut::<Assets<VoxelContext>>()
+            .get_mut(&self.context)
+        {
+            context.palette = palette;
+        }
+    }
+}
+
+/// Switches a material derived from a palette to the textures and factors of `source`, the material regenerated from the
+/// palette, keeping the settings of its own such as its index of refraction and thickness. Opaque materials stay opaque.
+fn use_palette_textures(
+    material: &mut StandardMaterial,
+    source: &StandardMaterial,
+    transmissive: bool,
+) {
+    let opaque = !transmissive
+        && material.specular_transmission_texture.is_none()
+        && material.specular_transmission == 0.0;
+    material.base_color_texture = source.base_color_texture.clone();
+    material.emissive = source.emissive;
+    material.emissive_texture = source.emissive_texture.clone();
+    material.perceptual_roughness = source.perceptual_roughness;
+    material.metallic = source.metallic;
+    material.metallic_roughness_texture = source.metallic_roughness_texture.clone();
+    if !opaque {
+        material.specular_transmission = source.specular_transmission;
+        material.specular_transmission_texture = source.specular_transmission_texture.clone();
+    }
+}
+
+/// A write of the bytes of a run of texels, starting at `texel` and `width` texels wide, to a palette texture
+#[derive(Clone, Debug)]
+pub(crate) struct PaletteTexelWrite {
+    pub(crate) image: AssetId<Image>,
+    pub(crate) texel: UVec2,
+    pub(crate) width: u32,
+    pub(crate) data: Vec<u8>,
+}
+
+/// The texel writes recorded in the main world since they were last extracted, and, in the render world, those
+/// waiting for their textures to be uploaded
+#[derive(Resource, Default, Debug)]
+pub(crate) struct PendingPaletteTexelWrites(pub(crate) Vec<PaletteTexelWrite>);
+
+pub(crate) fn extract_palette_texel_writes(
+    mut main_world: ResMut<MainWorld>,
+    mut writes: ResMut<PendingPaletteTexelWrites>,
+) {
+    let Some(mut extracted) = main_world.get_resource_mut::<PendingPaletteTexelWrites>() else {
+        return;
+    };
+    writes.0.append(&mut extracted.0);
+    // writes to textures that were removed before being uploaded would otherwise wait forever
+    let images = main_world.resource::<Assets<Image>>();
+    writes.0.retain(|write| images.contains(write.image));
+}
+
+pub(crate) fn write_palette_texels(
+    mut writes: ResMut<PendingPaletteTexelWrites>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    render_queue: Res<RenderQueue>,
+) {
+    writes.0.retain(|write| {
+        let Some(gpu_image) = gpu_images.get(write.image) else {
+            // the texture hasn't been uploaded yet, so try again next frame
+            return true;
+        };
+        render_queue.write_texture(
+            ImageCopyTexture {
+                texture: &gpu_image.texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: write.texel.x,
+                    y: write.texel.y,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            &write.data,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(write.data.len() as u32),
+                rows_per_image: None,
+            },
+            Extent3d {
+                width: write.width,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        false
+    });
+}
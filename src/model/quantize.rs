@@ -0,0 +1,192 @@
+use bevy::{
+    color::{Color, ColorToComponents, LinearRgba, Oklaba},
+    math::Vec4,
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{RawVoxel, Voxel, VoxelElement, VoxelPalette};
+
+/// How the difference between two colors is measured when fitting colors to a [`VoxelPalette`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorMetric {
+    /// Euclidean distance between linear RGB colors. Fast, but spends too many palette entries on bright colors.
+    LinearRgb,
+    /// Euclidean distance between gamma-encoded sRGB colors, which roughly matches how color pickers space colors.
+    Srgb,
+    /// Euclidean distance in the Oklab color space, which is perceptually uniform, so that colors that look equally
+    /// different are equally distant. Distances range from 0 to about 1.
+    #[default]
+    Oklab,
+}
+
+impl ColorMetric {
+    /// The coordinates of `color` in the space of the metric, with the alpha channel last
+    fn coordinates(&self, color: Color) -> Vec4 {
+        match self {
+            ColorMetric::LinearRgb => Vec4::from_array(color.to_linear().to_f32_array()),
+            ColorMetric::Srgb => Vec4::from_array(color.to_srgba().to_f32_array()),
+            ColorMetric::Oklab => Vec4::from_array(Oklaba::from(color).to_f32_array()),
+        }
+    }
+
+    /// The distance between `a` and `b`, including the difference in alpha
+    pub fn distance(&self, a: Color, b: Color) -> f32 {
+        self.coordinates(a).distance(self.coordinates(b))
+    }
+}
+
+impl VoxelPalette {
+    /// Create a new [`VoxelPalette`] of at most `max_colors` diffuse colors that best represent the supplied `colors`,
+    /// such as the vertex colors of an imported mesh or the points of a scan, by splitting boxes of colors at their mean in the space of `metric`.
+    /// Map the source colors to voxels of the palette with [`VoxelPalette::fit_colors`].
+    pub fn quantized(colors: &[Color], max_colors: u8, metric: ColorMetric) -> Self {
+        let max_colors = (max_colors as usize).min(RawVoxel::EMPTY.0 as usize);
+        VoxelPalette::from_colors(mean_cut(colors, max_colors, metric))
+    }
+
+    /// Returns a copy of the palette in which the elements of the voxels in `slots` are replaced by the colors needed
+    /// to represent `colors`, for importing content into a palette shared with `.vox` assets. Only the colors further
+    /// than `tolerance` from every element outside of `slots` are added, quantized by splitting boxes of colors at their mean to fit the slots.
+    ///
+    /// ### Arguments
+    /// * `colors` - the colors of the imported content
+    /// * `slots` - the voxels whose elements are free to be replaced, such as those unused by any model
+    /// * `tolerance` - the distance under `metric` within which an existing element is close enough
+    /// * `metric` - how the distance between colors is measured
+    pub fn extended(
+        self,
+        colors: &[Color],
+        slots: &[Voxel],
+        tolerance: f32,
+        metric: ColorMetric,
+    ) -> Self {
+        let raw_slots: Vec<RawVoxel> = slots.iter().map(|slot| slot.clone().into()).collect();
+        let existing: Vec<Vec4> = self
+            .elements
+            .iter()
+            .take(RawVoxel::EMPTY.0 as usize)
+            .enumerate()
+            .filter(|(index, _)| !raw_slots.contains(&RawVoxel(*index as u8)))
+            .map(|(_, element)| metric.coordinates(element.color))
+            .collect();
+        let missing: Vec<Color> = colors
+            .iter()
+            .filter(|color| {
+                let coordinates = metric.coordinates(**color);
+                !existing
+                    .iter()
+                    .any(|element| element.distance(coordinates) <= tolerance)
+            })
+            .copied()
+            .collect();
+        let added = mean_cut(&missing, slots.len(), metric);
+        slots
+            .iter()
+            .zip(added)
+            .fold(self, |palette, (slot, color)| {
+                palette.with_element(slot.clone(), VoxelElement::new(color))
+            })
+    }
+
+    /// Returns the [`Voxel`] whose color is closest to `color` under `metric`
+    pub fn closest_voxel_by_color(&self, color: Color, metric: ColorMetric) -> Voxel {
+        let target = metric.coordinates(color);
+        let raw_index = self
+            .elements
+            .iter()
+            .take(RawVoxel::EMPTY.0 as usize)
+            .map(|element| metric.coordinates(element.color).distance_squared(target))
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(0, |(index, _)| index);
+        RawVoxel(raw_index as u8).into()
+    }
+
+    /// Maps each of the `colors` to the [`Voxel`] whose color is closest under `metric`, for writing imported colors
+    /// into [`super::VoxelData`] that uses this palette
+    pub fn fit_colors(&self, colors: &[Color], metric: ColorMetric) -> Vec<Voxel> {
+        // keyed on the exact color, as distinct dark colors round to the same 8 bit linear color
+        let mut fitted: HashMap<[u32; 4], Voxel> = HashMap::new();
+        colors
+            .iter()
+            .map(|color| {
+                fitted
+                    .entry(color.to_linear().to_f32_array().map(f32::to_bits))
+                    .or_insert_with(|| self.closest_voxel_by_color(*color, metric))
+                    .clone()
+            })
+            .collect()
+    }
+}
+
+/// Up to `max_colors` colors representing `colors`, found by repeatedly splitting the box of colors with the widest
+/// extent at the mean of its colors along its widest axis, and averaging the colors in each box. This is a variant of
+/// median cut.
+fn mean_cut(colors: &[Color], max_colors: usize, metric: ColorMetric) -> Vec<Color> {
+    if colors.is_empty() || max_colors == 0 {
+        return Vec::new();
+    }
+    let points: Vec<Vec4> = colors
+        .iter()
+        .map(|color| metric.coordinates(*color))
+        .collect();
+    let widest_axis = |indices: &[usize]| -> (usize, f32) {
+        let (min, max) = indices.iter().fold(
+            (Vec4::splat(f32::INFINITY), Vec4::splat(f32::NEG_INFINITY)),
+            |(min, max), index| (min.min(points[*index]), max.max(points[*index])),
+        );
+        let extent = (max - min).to_array();
+        (0..4).fold((0, extent[0]), |(axis, widest), candidate| {
+            if extent[candidate] > widest {
+                (candidate, extent[candidate])
+            } else {
+                (axis, widest)
+            }
+        })
+    };
+    let mut boxes: Vec<Vec<usize>> = vec![(0..colors.len()).collect()];
+    while boxes.len() < max_colors {
+        let Some((index, axis)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, indices)| indices.len() > 1)
+            .map(|(index, indices)| {
+                let (axis, extent) = widest_axis(indices);
+                (index, axis, extent)
+            })
+            .filter(|(_, _, extent)| *extent > 0.0)
+            .max_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(index, axis, _)| (index, axis))
+        else {
+            break;
+        };
+        // splitting at the mean rather than the median keeps tight clusters of colors together
+        let indices = boxes.swap_remove(index);
+        let mean = indices
+            .iter()
+            .map(|index| points[*index][axis])
+            .sum::<f32>()
+            / indices.len() as f32;
+        let (mut lower, mut upper): (Vec<usize>, Vec<usize>) = indices
+            .into_iter()
+            .partition(|index| points[*index][axis] < mean);
+        if lower.is_empty() || upper.is_empty() {
+            // rounding left the mean at the edge of the box, so fall back to the median
+            lower.append(&mut upper);
+            lower.sort_by(|a, b| points[*a][axis].total_cmp(&points[*b][axis]));
+            upper = lower.split_off(lower.len() / 2);
+        }
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+    boxes
+        .iter()
+        .map(|indices| {
+            let sum = indices.iter().fold(Vec4::ZERO, |sum, index| {
+                sum + Vec4::from_array(colors[*index].to_linear().to_f32_array())
+            });
+            Color::LinearRgba(LinearRgba::from_vec4(sum / indices.len() as f32))
+        })
+        .collect()
+}
@@ -1,6 +1,6 @@
-use super::{RawVoxel, Voxel, VoxelData, VoxelModel};
+use super::{RawVoxel, Voxel, VoxelContext, VoxelData, VoxelGrid, VoxelModel};
 use bevy::{
-    math::{BVec3, IVec3, UVec3, Vec3},
+    math::{BVec3, IVec3, Ray3d, UVec3, Vec3},
     transform::components::GlobalTransform,
 };
 use ndshape::Shape;
@@ -54,6 +54,15 @@ pub trait VoxelQueryable {
     /// the point in the local space of the entity that owns this [`crate::VoxelModelInstance`]
     fn voxel_coord_to_local_space(&self, voxel_coord: IVec3) -> Vec3;
 
+    /// The grid of voxels in the local space of the entity that owns this [`crate::VoxelModelInstance`], with cell
+    /// coordinates matching voxel coordinates
+    fn grid(&self) -> VoxelGrid {
+        VoxelGrid::new(
+            self.model_size().x / self.size().x.max(1) as f32,
+            self.voxel_coord_to_local_space(IVec3::ZERO),
+        )
+    }
+
     /// If the voxel-space `point` is within the bounds of the model, it will be returned as a [`bevy::math::UVec3`].
     fn point_in_model(&self, point: IVec3) -> Result<UVec3, OutOfBoundsError> {
         if point.greater_than_or_equal(self.size()).any() {
@@ -69,6 +78,161 @@ pub trait VoxelQueryable {
     /// ### Returns
     /// the voxel at this point. If the point lies outside the bounds of the model, it will return [`OutOfBoundsError`].
     fn get_voxel_at_point(&self, position: IVec3) -> Result<Voxel, OutOfBoundsError>;
+
+    /// Whether `voxel` stops a [`VoxelQueryable::raycast`]. Every non-empty voxel does, unless the model draws it as
+    /// water.
+    fn is_solid_voxel(&self, voxel: &Voxel) -> bool {
+        *voxel != Voxel::EMPTY
+    }
+
+    /// Estimates the normal of the surface at a voxel from the occupancy of its neighbors, which is smoother than the
+    /// normal of a single face on irregular surfaces, for instance when placing objects flush against terrain.
+    ///
+    /// ### Arguments
+    /// * `position` - the position in voxel space
+    ///
+    /// ### Returns
+    /// the normalized direction pointing away from the solid voxels surrounding `position`, in the local space of the
+    /// model. Returns `None` if the position is outside the model, or if its neighborhood is evenly filled in every
+    /// direction, for instance inside a solid region.
+    fn surface_normal_at(&self, position: IVec3) -> Option<Vec3> {
+        self.point_in_model(position).ok()?;
+        let mut gradient = Vec3::ZERO;
+        for z in -1..=1 {
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    let offset = IVec3::new(x, y, z);
+                    let is_solid = self
+                        .get_voxel_at_point(position + offset)
+                        .is_ok_and(|voxel| voxel != Voxel::EMPTY);
+                    if is_solid {
+                        gradient += offset.as_vec3();
+                    }
+                }
+            }
+        }
+        (-gradient).try_normalize()
+    }
+
+    /// Casts a ray through the model, returning the first solid voxel it hits
+    ///
+    /// ### Arguments
+    /// * `ray` - the ray in global space
+    /// * `global_xform` - the [`bevy::transform::components::GlobalTransform`] of the entity that owns this [`crate::VoxelModelInstance`]
+    /// * `max_distance` - the furthest distance along the ray to search, in global space
+    ///
+    /// ### Returns
+    /// the voxel that was hit, or `None` if the ray misses every solid voxel.
+    fn raycast(
+        &self,
+        ray: Ray3d,
+        global_xform: &GlobalTransform,
+        max_distance: f32,
+    ) -> Option<VoxelRayHit> {
+        let size = self.size();
+        if size.cmple(IVec3::ZERO).any() {
+            return None;
+        }
+        let voxel_size = self.model_size() / size.as_vec3();
+        let inverse = global_xform.affine().inverse();
+        // in voxel space each voxel is a unit cube, and the distance along the ray is unchanged
+        let origin = inverse.transform_point3(ray.origin) / voxel_size + size.as_vec3() * 0.5;
+        let direction = inverse.transform_vector3(*ray.direction) / voxel_size;
+
+        // clip the ray to the bounds of the model
+        let mut entry = 0.0_f32;
+        let mut exit = max_distance;
+        let mut normal = IVec3::ZERO;
+        for axis in 0..3 {
+            if direction[axis] == 0.0 {
+                if origin[axis] < 0.0 || origin[axis] > size[axis] as f32 {
+                    return None;
+                }
+                continue;
+            }
+            let near = (0.0 - origin[axis]) / direction[axis];
+            let far = (size[axis] as f32 - origin[axis]) / direction[axis];
+            let (near, far) = (near.min(far), near.max(far));
+            if near > entry {
+                entry = near;
+                normal = IVec3::ZERO;
+                normal[axis] = -direction[axis].signum() as i32;
+            }
+            exit = exit.min(far);
+        }
+        if entry > exit {
+            return None;
+        }
+
+        let step = direction.signum().as_ivec3();
+        let mut coord = (origin + direction * entry)
+            .floor()
+            .as_ivec3()
+            .clamp(IVec3::ZERO, size - IVec3::ONE);
+        let mut next_boundary = Vec3::ZERO;
+        let mut boundary_step = Vec3::ZERO;
+        for axis in 0..3 {
+            if direction[axis] == 0.0 {
+                next_boundary[axis] = f32::INFINITY;
+                continue;
+            }
+            let boundary = coord[axis] + if step[axis] > 0 { 1 } else { 0 };
+            next_boundary[axis] = (boundary as f32 - origin[axis]) / direction[axis];
+            boundary_step[axis] = 1.0 / direction[axis].abs();
+        }
+        let mut distance = entry;
+        while distance <= exit {
+            let voxel = self.get_voxel_at_point(coord).ok()?;
+            if self.is_solid_voxel(&voxel) {
+                return Some(VoxelRayHit {
+                    voxel_coord: coord,
+                    voxel,
+                    normal,
+                    point: ray.get_point(distance),
+                    distance,
+                });
+            }
+            let axis = if next_boundary.x < next_boundary.y && next_boundary.x < next_boundary.z {
+                0
+            } else if next_boundary.y < next_boundary.z {
+                1
+            } else {
+                2
+            };
+            distance = next_boundary[axis];
+            next_boundary[axis] += boundary_step[axis];
+            coord[axis] += step[axis];
+            normal = IVec3::ZERO;
+            normal[axis] = -step[axis];
+        }
+        None
+    }
+}
+
+/// The result of a successful [`VoxelQueryable::raycast`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelRayHit {
+    /// The coordinate of the voxel that was hit, in voxel space
+    pub voxel_coord: IVec3,
+    /// The voxel that was hit
+    pub voxel: Voxel,
+    /// The normal of the face of the voxel that the ray entered through, in voxel space. This is zero if the ray
+    /// started inside the voxel.
+    pub normal: IVec3,
+    /// The point where the ray entered the voxel, in global space
+    pub point: Vec3,
+    /// The distance along the ray to [`VoxelRayHit::point`]
+    pub distance: f32,
+}
+
+impl VoxelRayHit {
+    /// The sound tag of the voxel that was hit, for playing impact and footstep sounds
+    ///
+    /// ### Arguments
+    /// * `context` - the [`crate::VoxelContext`] of the [`crate::VoxelModelInstance`] that was hit
+    pub fn audio_tag<'a>(&self, context: &'a VoxelContext) -> Option<&'a str> {
+        context.audio_materials.tag(&self.voxel)
+    }
 }
 
 impl VoxelQueryable for VoxelModel {
@@ -89,9 +253,17 @@ impl VoxelQueryable for VoxelModel {
         self.data.voxel_coord_to_local_space(voxel_coord)
     }
 
+    fn grid(&self) -> VoxelGrid {
+        self.data.grid()
+    }
+
     fn get_voxel_at_point(&self, position: IVec3) -> Result<Voxel, OutOfBoundsError> {
         self.data.get_voxel_at_point(position)
     }
+
+    fn is_solid_voxel(&self, voxel: &Voxel) -> bool {
+        self.data.is_solid_voxel(voxel)
+    }
 }
 
 impl VoxelQueryable for VoxelData {
@@ -110,18 +282,25 @@ impl VoxelQueryable for VoxelData {
     }
 
     fn voxel_coord_to_local_space(&self, voxel_coord: IVec3) -> Vec3 {
-        let half_extents = self.size().as_vec3() * 0.5;
-        (voxel_coord.as_vec3() - half_extents) * self.voxel_size
+        self.grid().cell_min_world(voxel_coord)
+    }
+
+    fn grid(&self) -> VoxelGrid {
+        VoxelGrid::new(self.voxel_size, self.model_size() * -0.5)
     }
 
     fn get_voxel_at_point(&self, position: IVec3) -> Result<Voxel, OutOfBoundsError> {
         let position = self.point_in_model(position)?;
         let leading_padding = UVec3::splat(self.padding() / 2);
         let index = self.shape.linearize((position + leading_padding).into()) as usize;
-        let raw_voxel = self.voxels.get(index).ok_or(OutOfBoundsError)?;
+        let raw_voxel = self.raw_voxel(index).ok_or(OutOfBoundsError)?;
         let voxel: Voxel = raw_voxel.clone().into();
         Ok(voxel)
     }
+
+    fn is_solid_voxel(&self, voxel: &Voxel) -> bool {
+        *voxel != Voxel::EMPTY && self.water.as_ref() != Some(&RawVoxel::from(voxel.clone()))
+    }
 }
 
 impl VoxelData {
@@ -131,9 +310,11 @@ impl VoxelData {
     /// * `voxel` - the [`Voxel`] to be written
     /// * `point` - the position at which the voxel will be written, in voxel space
     pub fn set_voxel(&mut self, voxel: Voxel, point: UVec3) {
+        self.decompress();
         let leading_padding = UVec3::splat(self.padding() / 2);
         let index = self.shape.linearize((point + leading_padding).into()) as usize;
         let raw_voxel: RawVoxel = voxel.into();
+        VoxelData::record_change(&mut self.histogram, &self.voxels[index], &raw_voxel);
         self.voxels[index] = raw_voxel;
     }
 }
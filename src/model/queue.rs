@@ -0,0 +1,144 @@
+use bevy::{
+    asset::{AssetId, Assets},
+    ecs::{
+        system::{Commands, Res, ResMut, Resource, SystemState},
+        world::{Command, World},
+    },
+    math::IVec3,
+    pbr::StandardMaterial,
+    render::mesh::Mesh,
+};
+
+use crate::VoxelModelInstance;
+
+use super::{
+    brick::VoxelBrickMap,
+    modify::{update_model_mesh, ModifyVoxelModel, VoxelRegionMode},
+    Voxel, VoxelContext, VoxelModel, VoxelQueryable,
+};
+
+/// Resource holding voxel edits that are deferred until the end of the next fixed timestep.
+///
+/// Edits queued with [`QueueVoxelEditCommandsExt::queue_voxel_edit`] or [`VoxelEditQueue::modify_voxel_model`] are
+/// applied together in [`bevy::app::FixedPostUpdate`], in the order they were queued, and each modified model is
+/// remeshed only once per tick. As the edits are aligned to the fixed tick rather than the frame, deterministic
+/// simulations and networked games see the same sequence of edits regardless of frame rate.
+#[derive(Resource, Default)]
+pub struct VoxelEditQueue {
+    edits: Vec<ModifyVoxelModel>,
+}
+
+impl VoxelEditQueue {
+    /// Queue the `modify` closure to run against every voxel within the `region` of the `model` at the end of the next
+    /// fixed timestep. See [`crate::ModifyVoxelCommandsExt::modify_voxel_model`] for the arguments.
+    pub fn modify_voxel_model<
+        F: Fn(IVec3, &Voxel, &dyn VoxelQueryable) -> Voxel + Send + Sync + 'static,
+    >(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+        modify: F,
+    ) {
+        self.edits.push(ModifyVoxelModel {
+            instance: model,
+            region,
+            modify: Box::new(modify),
+        });
+    }
+
+    /// The number of edits waiting to be applied
+    pub fn len(&self) -> usize {
+        self.edits.len()
+    }
+
+    /// Returns true if there are no edits waiting to be applied
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+}
+
+/// Extension to [`Commands`] for queueing edits in the [`VoxelEditQueue`]
+pub trait QueueVoxelEditCommandsExt {
+    /// Queue the `modify` closure to run against every voxel within the `region` of the `model` at the end of the next
+    /// fixed timestep, instead of when the commands are applied. See
+    /// [`crate::ModifyVoxelCommandsExt::modify_voxel_model`] for the arguments.
+    fn queue_voxel_edit<
+        F: Fn(IVec3, &Voxel, &dyn VoxelQueryable) -> Voxel + Send + Sync + 'static,
+    >(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+        modify: F,
+    ) -> &mut Self;
+}
+
+impl QueueVoxelEditCommandsExt for Commands<'_, '_> {
+    fn queue_voxel_edit<
+        F: Fn(IVec3, &Voxel, &dyn VoxelQueryable) -> Voxel + Send + Sync + 'static,
+    >(
+        &mut self,
+        model: VoxelModelInstance,
+        region: VoxelRegionMode,
+        modify: F,
+    ) -> &mut Self {
+        self.add(QueueVoxelEdit(ModifyVoxelModel {
+            instance: model,
+            region,
+            modify: Box::new(modify),
+        }));
+        self
+    }
+}
+
+struct QueueVoxelEdit(ModifyVoxelModel);
+
+impl Command for QueueVoxelEdit {
+    fn apply(self, world: &mut World) {
+        world
+            .get_resource_or_insert_with(VoxelEditQueue::default)
+            .edits
+            .push(self.0);
+    }
+}
+
+/// Applies every queued edit, then remeshes each modified model once
+pub(crate) fn apply_voxel_edit_queue(world: &mut World) {
+    let edits = match world.get_resource_mut::<VoxelEditQueue>() {
+        Some(mut queue) if !queue.edits.is_empty() => std::mem::take(&mut queue.edits),
+        _ => return,
+    };
+    let mut system_state: SystemState<(
+        ResMut<Assets<Mesh>>,
+        ResMut<Assets<StandardMaterial>>,
+        ResMut<Assets<VoxelBrickMap>>,
+        ResMut<Assets<VoxelModel>>,
+        Res<Assets<VoxelContext>>,
+    )> = SystemState::new(world);
+    let (mut meshes, mut materials, mut brick_maps, mut models, contexts) =
+        system_state.get_mut(world);
+    let mut modified: Vec<(AssetId<VoxelModel>, AssetId<VoxelContext>)> = Vec::new();
+    for edit in edits.iter() {
+        let Some(model) = models.get_mut(edit.instance.model.id()) else {
+            continue;
+        };
+        edit.modify_data(model);
+        let key = (edit.instance.model.id(), edit.instance.context.id());
+        if !modified.contains(&key) {
+            modified.push(key);
+        }
+    }
+    for (model, context) in modified {
+        let (Some(model), Some(context)) = (models.get_mut(model), contexts.get(context)) else {
+            continue;
+        };
+        update_model_mesh(
+            model,
+            &mut meshes,
+            &mut materials,
+            &mut brick_maps,
+            context.opaque_material.clone(),
+            context.transmissive_material.clone(),
+            &context.palette,
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    asset::{load_internal_asset, Asset, AssetEvent, AssetId, Assets, Handle},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::EventReader,
+        query::With,
+        removal_detection::RemovedComponents,
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+        world::Ref,
+    },
+    math::{primitives::Cuboid, IVec3, UVec3, Vec4},
+    pbr::{Material, MaterialPipeline, MaterialPipelineKey, MaterialPlugin, StandardMaterial},
+    prelude::ReflectComponent,
+    reflect::{Reflect, TypePath},
+    render::{
+        mesh::{Mesh, MeshVertexBufferLayoutRef},
+        render_asset::RenderAssetUsages,
+        render_resource::{
+            AsBindGroup, Extent3d, Face, RenderPipelineDescriptor, Shader, ShaderRef,
+            SpecializedMeshPipelineError, TextureDimension, TextureFormat,
+        },
+        texture::Image,
+    },
+    utils::{HashMap, HashSet},
+};
+use ndshape::Shape;
+
+use crate::VoxelModelInstance;
+
+use super::{RawVoxel, Voxel, VoxelContext, VoxelData, VoxelModel, VoxelPalette};
+
+const RAYMARCH_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x5d3c_8b1e_2f47_4a09_9c61_7e0b_3a52_d184);
+
+/// Plugin adding the experimental ray-marched render path. Add it alongside [`crate::VoxScenePlugin`], then add a
+/// [`VoxelRaymarched`] component to any [`VoxelModelInstance`] that should be ray-marched instead of meshed.
+pub struct VoxelRaymarchPlugin;
+
+impl Plugin for VoxelRaymarchPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            RAYMARCH_SHADER_HANDLE,
+            "raymarch.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(MaterialPlugin::<VoxelRaymarchMaterial> {
+            prepass_enabled: false,
+            shadows_enabled: false,
+            ..Default::default()
+        })
+        .init_resource::<VoxelRaymarchCache>()
+        .register_type::<VoxelRaymarched>()
+        .add_systems(
+            PostUpdate,
+            update_raymarched_instances.after(crate::load::spawn::mesh_pending_models),
+        );
+    }
+}
+
+/// Marks a [`VoxelModelInstance`] to be drawn by ray-marching its voxels in a fragment shader, rather than with the
+/// model's triangle mesh. Requires the [`VoxelRaymarchPlugin`].
+///
+/// The model is uploaded as a 3D texture of palette indices and drawn as a single box, so the cost of rendering it
+/// depends on the number of pixels it covers rather than on the number of voxels. The path is experimental: ray-marched
+/// instances are lit by the scene's lights, but don't cast shadows, don't write to the prepass, don't support
+/// translucent voxels and assume a perspective camera. Removing the component restores the meshed model.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct VoxelRaymarched;
+
+/// The material used to draw [`VoxelRaymarched`] instances
+#[derive(Asset, TypePath, AsBindGroup, Clone, Debug)]
+pub struct VoxelRaymarchMaterial {
+    /// The size of the model in voxels in `xyz`, and the size of each voxel in `w`
+    #[uniform(0)]
+    pub dimensions: Vec4,
+    /// A 3D [`TextureFormat::R8Uint`] texture holding the [`Voxel`] at each position, with 0 being empty
+    #[texture(1, dimension = "3d", sample_type = "u_int")]
+    pub voxels: Handle<Image>,
+    /// A 256x2 texture holding the color and emission of each palette element in the first row, and the roughness and
+    /// metalness in the second
+    #[texture(2, sample_type = "float", filterable = false)]
+    pub elements: Handle<Image>,
+}
+
+impl Material for VoxelRaymarchMaterial {
+    fn vertex_shader() -> ShaderRef {
+        RAYMARCH_SHADER_HANDLE.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        RAYMARCH_SHADER_HANDLE.into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // draw the back faces of the box, so that the model is still visible when the camera is inside it
+        descriptor.primitive.cull_mode = Some(Face::Front);
+        Ok(())
+    }
+}
+
+/// The material and bounding box shared by every ray-marched instance of a model drawn with the same context
+#[derive(Resource, Default)]
+pub(crate) struct VoxelRaymarchCache {
+    models: HashMap<
+        (AssetId<VoxelModel>, AssetId<VoxelContext>),
+        (Handle<VoxelRaymarchMaterial>, Handle<Mesh>),
+    >,
+}
+
+impl VoxelRaymarchCache {
+    /// Drops the material and bounding box of the models, once no instance uses them
+    pub(crate) fn release(&mut self, models: &HashSet<AssetId<VoxelModel>>) {
+        self.models.retain(|(model, _), _| !models.contains(model));
+    }
+}
+
+impl VoxelData {
+    /// Packs the voxels, without padding, into a 3D texture of [`Voxel`] indices, with x varying fastest
+    pub(crate) fn raymarch_volume(&self) -> Image {
+        let size = self._size().max(IVec3::ONE).as_uvec3();
+        let leading_padding = self.padding() / 2;
+        let mut bytes = Vec::with_capacity((size.x * size.y * size.z) as usize);
+        let voxels = self.dense_voxels();
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let point = UVec3::new(x, y, z) + UVec3::splat(leading_padding);
+                    let raw = voxels
+                        .get(self.shape.linearize(point.into()) as usize)
+                        .cloned()
+                        .unwrap_or(RawVoxel::EMPTY);
+                    bytes.push(Voxel::from(raw).0);
+                }
+            }
+        }
+        Image::new(
+            Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: size.z,
+            },
+            TextureDimension::D3,
+            bytes,
+            TextureFormat::R8Uint,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+    }
+}
+
+impl VoxelPalette {
+    /// Packs the elements into a 256x2 texture, for looking up the material of each voxel in the ray-marching shader
+    pub(crate) fn raymarch_elements(&self) -> Image {
+        let mut texels = vec![[0.0_f32; 4]; 512];
+        for (index, element) in self.elements.iter().take(256).enumerate() {
+            let color = element.color.to_linear();
+            texels[index] = [color.red, color.green, color.blue, element.emission];
+            texels[256 + index] = [element.roughness, element.metalness, 0.0, 0.0];
+        }
+        Image::new(
+            Extent3d {
+                width: 256,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            texels
+                .iter()
+                .flatten()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            TextureFormat::Rgba32Float,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_raymarched_instances(
+    mut commands: Commands,
+    mut cache: ResMut<VoxelRaymarchCache>,
+    mut model_events: EventReader<AssetEvent<VoxelModel>>,
+    mut context_events: EventReader<AssetEvent<VoxelContext>>,
+    instances: Query<(Entity, Ref<VoxelModelInstance>, Ref<VoxelRaymarched>)>,
+    restored: Query<&VoxelModelInstance, With<Handle<VoxelRaymarchMaterial>>>,
+    mut removed: RemovedComponents<VoxelRaymarched>,
+    models: Res<Assets<VoxelModel>>,
+    contexts: Res<Assets<VoxelContext>>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<VoxelRaymarchMaterial>>,
+) {
+    let mut stale: HashSet<AssetId<VoxelModel>> = HashSet::new();
+    for event in model_events.read() {
+        if let AssetEvent::Modified { id } | AssetEvent::Removed { id } = event {
+            cache.models.retain(|(model, _), _| model != id);
+            stale.insert(*id);
+        }
+    }
+    // the palette of a context is packed into the materials of its models, so they are rebuilt when it changes
+    let mut stale_contexts: HashSet<AssetId<VoxelContext>> = HashSet::new();
+    for event in context_events.read() {
+        if let AssetEvent::Modified { id } | AssetEvent::Removed { id } = event {
+            cache.models.retain(|(_, context), _| context != id);
+            stale_contexts.insert(*id);
+        }
+    }
+    for (entity, instance, raymarched) in instances.iter() {
+        if !raymarched.is_added()
+            && !instance.is_changed()
+            && !stale.contains(&instance.model.id())
+            && !stale_contexts.contains(&instance.context.id())
+        {
+            continue;
+        }
+        let Some(model) = models.get(&instance.model) else {
+            continue;
+        };
+        let Some(context) = contexts.get(&instance.context) else {
+            continue;
+        };
+        let (material, mesh) = cache
+            .models
+            .entry((instance.model.id(), instance.context.id()))
+            .or_insert_with(|| {
+                let size = model.data._size().as_vec3();
+                let material = materials.add(VoxelRaymarchMaterial {
+                    dimensions: size.extend(model.data.voxel_size),
+                    voxels: images.add(model.data.raymarch_volume()),
+                    elements: images.add(context.palette.raymarch_elements()),
+                });
+                let mesh = meshes.add(Cuboid::from_size(size * model.data.voxel_size));
+                (material, mesh)
+            })
+            .clone();
+        commands
+            .entity(entity)
+            .remove::<Handle<StandardMaterial>>()
+            .insert((material, mesh));
+    }
+    for entity in removed.read() {
+        let Ok(instance) = restored.get(entity) else {
+            continue;
+        };
+        let Some(model) = models.get(&instance.model) else {
+            continue;
+        };
+        commands
+            .entity(entity)
+            .remove::<Handle<VoxelRaymarchMaterial>>()
+            .insert((model.mesh.clone(), model.material.clone()));
+    }
+}
@@ -0,0 +1,143 @@
+#import bevy_pbr::{
+    mesh_bindings::mesh,
+    mesh_functions,
+    mesh_view_bindings::view,
+    pbr_functions,
+    pbr_types,
+    view_transformations::position_world_to_clip,
+}
+#import bevy_render::maths::mat2x4_f32_to_mat3x3_unpack
+
+struct RaymarchMaterial {
+    // xyz: size of the model in voxels, w: size of each voxel
+    dimensions: vec4<f32>,
+}
+
+@group(2) @binding(0) var<uniform> material: RaymarchMaterial;
+@group(2) @binding(1) var voxels: texture_3d<u32>;
+@group(2) @binding(2) var elements: texture_2d<f32>;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) local_position: vec3<f32>,
+    @location(1) local_camera: vec3<f32>,
+    @location(2) @interpolate(flat) instance_index: u32,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    let world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
+    let world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(vertex.position, 1.0));
+    out.clip_position = position_world_to_clip(world_position.xyz);
+    out.local_position = vertex.position;
+    // the transpose of the inverse transpose is the inverse of the upper 3x3 of the model matrix
+    let local_from_world = transpose(mat2x4_f32_to_mat3x3_unpack(
+        mesh[vertex.instance_index].local_from_world_transpose_a,
+        mesh[vertex.instance_index].local_from_world_transpose_b,
+    ));
+    out.local_camera = local_from_world * (view.world_position - world_from_local[3].xyz);
+    out.instance_index = vertex.instance_index;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+    let size = material.dimensions.xyz;
+    let voxel_size = material.dimensions.w;
+    // march in voxel space, where the model spans 0 to size
+    let origin = in.local_camera / voxel_size + size * 0.5;
+    let direction = normalize(in.local_position - in.local_camera);
+    let inverse_direction = 1.0 / direction;
+
+    let t0 = -origin * inverse_direction;
+    let t1 = (size - origin) * inverse_direction;
+    let t_min = min(t0, t1);
+    let t_max = max(t0, t1);
+    let t_near = max(max(t_min.x, t_min.y), max(t_min.z, 0.0));
+    let t_far = min(t_max.x, min(t_max.y, t_max.z));
+    if t_near > t_far {
+        discard;
+    }
+
+    let start = origin + direction * t_near;
+    let size_cells = vec3<i32>(size);
+    var cell = clamp(vec3<i32>(floor(start)), vec3(0), size_cells - 1);
+    let step = vec3<i32>(sign(direction));
+    let delta = abs(inverse_direction);
+    var next = (vec3<f32>(cell) + max(sign(direction), vec3(0.0)) - start) * inverse_direction;
+    var t = t_near;
+    var normal = -sign(direction) * vec3<f32>(t_min == vec3(t_near));
+    if t_near == 0.0 {
+        normal = vec3(0.0);
+    }
+
+    let max_steps = size_cells.x + size_cells.y + size_cells.z;
+    var voxel = 0u;
+    for (var i = 0; i < max_steps; i++) {
+        if any(cell < vec3(0)) || any(cell >= size_cells) {
+            break;
+        }
+        voxel = textureLoad(voxels, cell, 0).r;
+        if voxel != 0u {
+            break;
+        }
+        if next.x < next.y && next.x < next.z {
+            t = t_near + next.x;
+            next.x += delta.x;
+            cell.x += step.x;
+            normal = vec3(-f32(step.x), 0.0, 0.0);
+        } else if next.y < next.z {
+            t = t_near + next.y;
+            next.y += delta.y;
+            cell.y += step.y;
+            normal = vec3(0.0, -f32(step.y), 0.0);
+        } else {
+            t = t_near + next.z;
+            next.z += delta.z;
+            cell.z += step.z;
+            normal = vec3(0.0, 0.0, -f32(step.z));
+        }
+    }
+    if voxel == 0u {
+        discard;
+    }
+
+    let local_hit = (origin + direction * t - size * 0.5) * voxel_size;
+    let world_from_local = mesh_functions::get_world_from_local(in.instance_index);
+    let world_hit = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(local_hit, 1.0));
+    let element = textureLoad(elements, vec2<i32>(i32(voxel) - 1, 0), 0);
+    let surface = textureLoad(elements, vec2<i32>(i32(voxel) - 1, 1), 0);
+
+    var pbr_input = pbr_types::pbr_input_new();
+    pbr_input.flags = mesh[in.instance_index].flags;
+    pbr_input.material.base_color = vec4(element.rgb, 1.0);
+    pbr_input.material.emissive = vec4(element.rgb * element.a, 1.0);
+    pbr_input.material.perceptual_roughness = surface.r;
+    pbr_input.material.metallic = surface.g;
+    pbr_input.frag_coord = in.clip_position;
+    pbr_input.world_position = world_hit;
+    pbr_input.world_normal = mesh_functions::mesh_normal_local_to_world(normal, in.instance_index);
+    pbr_input.N = normalize(pbr_input.world_normal);
+    pbr_input.is_orthographic = view.clip_from_view[3].w == 1.0;
+    pbr_input.V = pbr_functions::calculate_view(world_hit, pbr_input.is_orthographic);
+
+    var out: FragmentOutput;
+    out.color = pbr_functions::main_pass_post_lighting_processing(
+        pbr_input,
+        pbr_functions::apply_pbr_lighting(pbr_input),
+    );
+    let clip_hit = position_world_to_clip(world_hit.xyz);
+    out.depth = clip_hit.z / clip_hit.w;
+    return out;
+}
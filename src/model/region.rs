@@ -0,0 +1,540 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    ecs::system::{Local, Res, ResMut},
+    log::warn,
+    math::{IVec3, UVec3},
+    tasks::{block_on, futures_lite::future, IoTaskPool, Task},
+    time::Time,
+    utils::{HashMap, HashSet},
+};
+use ndshape::Shape;
+
+use super::{
+    compress::{decode_runs, encode_runs},
+    world::{ChunkState, VoxelWorld},
+    RawVoxel, VoxelData,
+};
+
+const REGION_MAGIC: &[u8; 4] = b"VXRG";
+const REGION_VERSION: u8 = 1;
+/// The size of the magic, version, chunk size and chunk count at the start of a region file
+const HEADER_SIZE: u32 = 21;
+/// The size of each entry of a region file's chunk table: the chunk's coordinate, offset and number of runs
+const TABLE_ENTRY_SIZE: u32 = 20;
+/// The size of each run of a chunk: its length and voxel
+const RUN_SIZE: u32 = 5;
+
+/// Settings for saving the chunks of a [`VoxelWorld`] to region files, so that edits to streamed worlds persist without
+/// keeping every chunk in memory. Add them with [`VoxelWorld::with_region_files`].
+///
+/// Each file holds the chunks in a cube of `region_size` chunks along each side, with the voxels of each chunk
+/// run-length encoded behind a table of the chunks it holds, so that a single chunk is read without reading the rest
+/// of the file. Chunks are saved when [`VoxelWorld::save`] is called, and when a chunk of a streaming world that has
+/// been edited since it was last saved is evicted. A streaming world reads each chunk from its region file before
+/// falling back to its generator, and other worlds can load chunks with [`VoxelWorld::load_chunks`]. Files are read
+/// and written on the [`IoTaskPool`], and each region file is rewritten in full when any of its chunks are saved.
+///
+/// Chunks that fail to save are kept in memory and saved again after `retry_interval`. A region file that can't be
+/// read is moved aside with a `.corrupt` extension when its region is next saved, rather than blocking every later
+/// save of the region.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelRegionFiles {
+    /// The directory that the region files are written to. It is created if it doesn't exist.
+    pub directory: PathBuf,
+    /// The number of chunks along each side of a region. Defaults to 8.
+    pub region_size: u32,
+    /// The number of seconds to wait before saving chunks again after a region file fails to be written. Defaults to 1.
+    pub retry_interval: f32,
+}
+
+impl VoxelRegionFiles {
+    /// Creates the settings for region files in `directory`
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            region_size: 8,
+            retry_interval: 1.0,
+        }
+    }
+
+    /// Sets the number of chunks along each side of a region
+    pub fn with_region_size(mut self, region_size: u32) -> Self {
+        self.region_size = region_size;
+        self
+    }
+
+    /// The coordinate of the region containing the chunk at `coord`
+    fn region(&self, coord: IVec3) -> IVec3 {
+        coord.div_euclid(IVec3::splat(self.region_size.max(1) as i32))
+    }
+
+    /// The path of the file holding the chunk at `coord`
+    pub(super) fn path(&self, coord: IVec3) -> PathBuf {
+        let region = self.region(coord);
+        self.directory
+            .join(format!("r.{}.{}.{}.vxr", region.x, region.y, region.z))
+    }
+}
+
+/// The state of a world's region files
+pub(super) struct RegionState {
+    pub(super) files: VoxelRegionFiles,
+    /// Chunks waiting to be saved, because they were evicted with unsaved edits or their region was being written
+    pub(super) evicted: HashMap<IVec3, VoxelData>,
+    /// Chunks being written to their region files
+    pub(super) saving: HashMap<IVec3, VoxelData>,
+    pub(super) save_requested: bool,
+    pub(super) load_requests: HashSet<IVec3>,
+    /// The elapsed time in seconds at which to save again, after a save failed
+    pub(super) retry_at: Option<f64>,
+}
+
+impl VoxelWorld {
+    /// Persists the chunks to region files. See [`VoxelRegionFiles`].
+    pub fn with_region_files(mut self, files: VoxelRegionFiles) -> Self {
+        self.regions = Some(RegionState {
+            files,
+            evicted: HashMap::new(),
+            saving: HashMap::new(),
+            save_requested: false,
+            load_requests: HashSet::new(),
+            retry_at: None,
+        });
+        self
+    }
+
+    /// Saves every chunk that has been edited since it was last saved to the region files, starting in the next update.
+    /// Does nothing if the world has no [`VoxelRegionFiles`].
+    pub fn save(&mut self) {
+        if let Some(regions) = self.regions.as_mut() {
+            regions.save_requested = true;
+        }
+    }
+
+    /// Loads the chunks from `min` to `max` (inclusive) that have been saved to the region files and aren't in memory
+    /// yet, starting in the next update
+    pub fn load_chunks(&mut self, min: IVec3, max: IVec3) {
+        let Some(regions) = self.regions.as_mut() else {
+            return;
+        };
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    let coord = IVec3::new(x, y, z);
+                    if !self.chunks.contains_key(&coord) {
+                        regions.load_requests.insert(coord);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The number of chunks with edits that haven't been written to the region files yet
+    pub fn unsaved_chunks(&self) -> usize {
+        let Some(regions) = self.regions.as_ref() else {
+            return 0;
+        };
+        self.chunks.values().filter(|state| state.unsaved).count()
+            + regions.evicted.len()
+            + regions.saving.len()
+    }
+
+    /// Takes the voxels of a chunk that was evicted with edits that haven't been written to its region file yet, which
+    /// are newer than the copy in the file. Chunks that are being written are copied, so that they are restored if the
+    /// write fails.
+    fn take_unsaved_chunk(&mut self, coord: IVec3) -> Option<VoxelData> {
+        let regions = self.regions.as_mut()?;
+        regions
+            .evicted
+            .remove(&coord)
+            .or_else(|| regions.saving.get(&coord).cloned())
+    }
+
+    /// Adds a chunk read from its region file, or restored from memory with its `unsaved` edits
+    fn insert_loaded_chunk(&mut self, coord: IVec3, data: VoxelData, unsaved: bool) {
+        self.chunks.insert(
+            coord,
+            ChunkState {
+                data,
+                model: None,
+                entity: None,
+                dirty: true,
+                unsaved,
+                water: None,
+                enclosed: false,
+            },
+        );
+        self.mark_neighbors_dirty(coord);
+    }
+}
+
+/// Reads the chunk at `coord` from its region file, returning `None` if it hasn't been saved. Only the header and chunk
+/// table are read besides the chunk itself.
+pub(super) fn read_chunk(
+    path: &Path,
+    coord: IVec3,
+    chunk_size: IVec3,
+) -> io::Result<Option<Vec<u8>>> {
+    let mut file = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let table = read_table(&mut file, chunk_size)?;
+    let Some(entry) = table.iter().find(|entry| entry.coord == coord) else {
+        return Ok(None);
+    };
+    file.seek(SeekFrom::Start(entry.offset as u64))?;
+    let mut bytes = vec![0; (entry.run_count * RUN_SIZE) as usize];
+    file.read_exact(&mut bytes).map_err(malformed)?;
+    Ok(Some(decode_runs(&parse_runs(&bytes))))
+}
+
+/// Creates the voxel data of a chunk from the raw voxel values read from a region file
+pub(super) fn chunk_from_raw(mut data: VoxelData, raw: &[u8]) -> VoxelData {
+    let size = data._size().as_uvec3();
+    if raw.len() != (size.x * size.y * size.z) as usize {
+        warn!("Saved chunk doesn't match the chunk size of the world");
+        return data;
+    }
+    let mut index = 0;
+    for z in 0..size.z {
+        for y in 0..size.y {
+            for x in 0..size.x {
+                if raw[index] != RawVoxel::EMPTY.0 {
+                    data.set_voxel(RawVoxel(raw[index]).into(), UVec3::new(x, y, z));
+                }
+                index += 1;
+            }
+        }
+    }
+    data
+}
+
+/// The raw voxel values of a chunk, without its padding
+fn chunk_to_raw(data: &VoxelData) -> Vec<u8> {
+    let size = data._size().as_uvec3();
+    let leading_padding = UVec3::splat(data.padding() / 2);
+    let mut raw = Vec::with_capacity((size.x * size.y * size.z) as usize);
+    for z in 0..size.z {
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let position = UVec3::new(x, y, z) + leading_padding;
+                raw.push(
+                    data.voxel_at_index(data.shape.linearize(position.into()) as usize)
+                        .0,
+                );
+            }
+        }
+    }
+    raw
+}
+
+type RegionChunks = HashMap<IVec3, Vec<(u32, u8)>>;
+
+/// An entry of the table at the start of a region file, locating the runs of a chunk
+struct TableEntry {
+    coord: IVec3,
+    /// The offset of the chunk's runs from the start of the file
+    offset: u32,
+    run_count: u32,
+}
+
+/// Treats a region file that ends early as malformed
+fn malformed(error: io::Error) -> io::Error {
+    if error.kind() == ErrorKind::UnexpectedEof {
+        io::Error::new(ErrorKind::InvalidData, "truncated voxel region file")
+    } else {
+        error
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes).map_err(malformed)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_ivec3(reader: &mut impl Read) -> io::Result<IVec3> {
+    let mut values = [0; 3];
+    for value in values.iter_mut() {
+        *value = read_u32(reader)? as i32;
+    }
+    Ok(IVec3::from_array(values))
+}
+
+/// Reads the header and chunk table of a region file
+fn read_table(reader: &mut impl Read, chunk_size: IVec3) -> io::Result<Vec<TableEntry>> {
+    let invalid = || io::Error::new(ErrorKind::InvalidData, "malformed voxel region file");
+    let mut magic = [0; 5];
+    reader.read_exact(&mut magic).map_err(malformed)?;
+    if magic[..4] != *REGION_MAGIC || magic[4] != REGION_VERSION {
+        return Err(invalid());
+    }
+    let saved_size = read_ivec3(reader)?;
+    if saved_size != chunk_size {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("voxel region file has chunks of size {saved_size}, but the world has chunks of size {chunk_size}"),
+        ));
+    }
+    let count = read_u32(reader)?;
+    (0..count)
+        .map(|_| {
+            Ok(TableEntry {
+                coord: read_ivec3(reader)?,
+                offset: read_u32(reader)?,
+                run_count: read_u32(reader)?,
+            })
+        })
+        .collect()
+}
+
+fn parse_runs(bytes: &[u8]) -> Vec<(u32, u8)> {
+    bytes
+        .chunks_exact(RUN_SIZE as usize)
+        .map(|run| (u32::from_le_bytes([run[0], run[1], run[2], run[3]]), run[4]))
+        .collect()
+}
+
+fn read_region(path: &Path, chunk_size: IVec3) -> io::Result<RegionChunks> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(RegionChunks::new()),
+        Err(error) => return Err(error),
+    };
+    let table = read_table(&mut bytes.as_slice(), chunk_size)?;
+    table
+        .iter()
+        .map(|entry| {
+            let start = entry.offset as usize;
+            let runs = start
+                .checked_add((entry.run_count * RUN_SIZE) as usize)
+                .and_then(|end| bytes.get(start..end))
+                .ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidData, "malformed voxel region file")
+                })?;
+            Ok((entry.coord, parse_runs(runs)))
+        })
+        .collect()
+}
+
+/// Merges the `chunks` into the region file at `path`, replacing the file once it has been written in full. A file
+/// that can't be read is moved aside, so that it doesn't prevent the region from being saved.
+fn write_region(path: &Path, chunk_size: IVec3, chunks: Vec<(IVec3, Vec<u8>)>) -> io::Result<()> {
+    let mut region = match read_region(path, chunk_size) {
+        Ok(region) => region,
+        Err(error) if error.kind() == ErrorKind::InvalidData => {
+            let corrupt = path.with_extension("vxr.corrupt");
+            warn!(
+                "Replacing unreadable voxel region {}, moved to {}: {error}",
+                path.display(),
+                corrupt.display()
+            );
+            fs::rename(path, corrupt)?;
+            RegionChunks::new()
+        }
+        Err(error) => return Err(error),
+    };
+    for (coord, raw) in chunks {
+        region.insert(coord, encode_runs(&raw));
+    }
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(REGION_MAGIC);
+    bytes.push(REGION_VERSION);
+    for value in chunk_size.to_array() {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&(region.len() as u32).to_le_bytes());
+    let mut offset = HEADER_SIZE + region.len() as u32 * TABLE_ENTRY_SIZE;
+    for (coord, runs) in region.iter() {
+        for value in coord.to_array() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+        offset += runs.len() as u32 * RUN_SIZE;
+    }
+    for runs in region.values() {
+        for (length, value) in runs {
+            bytes.extend_from_slice(&length.to_le_bytes());
+            bytes.push(*value);
+        }
+    }
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let temporary = path.with_extension("vxr.tmp");
+    fs::write(&temporary, bytes)?;
+    fs::rename(temporary, path)
+}
+
+/// The region files being read and written
+#[derive(Default)]
+pub(crate) struct RegionTasks {
+    saves: HashMap<PathBuf, (Task<io::Result<()>>, Vec<IVec3>)>,
+    loads: HashMap<IVec3, Task<io::Result<Option<Vec<u8>>>>>,
+}
+
+pub(crate) fn update_voxel_world_regions(
+    world: Option<ResMut<VoxelWorld>>,
+    mut tasks: Local<RegionTasks>,
+    time: Res<Time>,
+) {
+    let Some(mut world) = world else {
+        return;
+    };
+    // loaded chunks are marked dirty, so the world doesn't need to be flagged as changed
+    let world = world.bypass_change_detection();
+    if world.regions.is_none() {
+        return;
+    }
+    let chunk_size = world.chunk_size;
+    let now = time.elapsed_seconds_f64();
+
+    // finish the saves that have been written
+    let saved: Vec<(PathBuf, Vec<IVec3>, bool)> = tasks
+        .saves
+        .iter_mut()
+        .filter_map(|(path, (task, coords))| {
+            block_on(future::poll_once(task)).map(|result| {
+                if let Err(error) = &result {
+                    warn!("Failed to save voxel region {}: {error}", path.display());
+                }
+                (path.clone(), coords.clone(), result.is_ok())
+            })
+        })
+        .collect();
+    for (path, coords, succeeded) in saved {
+        tasks.saves.remove(&path);
+        let Some(regions) = world.regions.as_mut() else {
+            continue;
+        };
+        if !succeeded {
+            regions.retry_at = Some(now + regions.files.retry_interval as f64);
+        }
+        for coord in coords {
+            let Some(data) = regions.saving.remove(&coord) else {
+                continue;
+            };
+            if succeeded {
+                continue;
+            }
+            // keep the chunk until it is saved, unless a newer copy is already waiting
+            match world.chunks.get_mut(&coord) {
+                Some(state) => state.unsaved = true,
+                None => {
+                    regions.evicted.entry(coord).or_insert(data);
+                }
+            }
+        }
+    }
+
+    // add the chunks that have been read
+    let loaded: Vec<(IVec3, Option<Vec<u8>>)> = tasks
+        .loads
+        .iter_mut()
+        .filter_map(|(coord, task)| {
+            block_on(future::poll_once(task)).map(|result| {
+                let raw = result.unwrap_or_else(|error| {
+                    warn!("Failed to load voxel chunk {coord}: {error}");
+                    None
+                });
+                (*coord, raw)
+            })
+        })
+        .collect();
+    for (coord, raw) in loaded {
+        tasks.loads.remove(&coord);
+        if world.chunks.contains_key(&coord) {
+            continue;
+        }
+        // the chunk may have been evicted with edits while it was being read, making the copy read from the file stale
+        if let Some(data) = world.take_unsaved_chunk(coord) {
+            world.insert_loaded_chunk(coord, data, true);
+            continue;
+        }
+        let Some(raw) = raw else {
+            continue;
+        };
+        let data = chunk_from_raw(world.chunk_data(coord), &raw);
+        world.insert_loaded_chunk(coord, data, false);
+    }
+
+    // start reading the requested chunks, restoring those with edits that are still waiting to be saved from memory
+    let requests: Vec<IVec3> = world
+        .regions
+        .as_mut()
+        .map(|regions| regions.load_requests.drain().collect())
+        .unwrap_or_default();
+    for coord in requests {
+        if world.chunks.contains_key(&coord) || tasks.loads.contains_key(&coord) {
+            continue;
+        }
+        if let Some(data) = world.take_unsaved_chunk(coord) {
+            world.insert_loaded_chunk(coord, data, true);
+            continue;
+        }
+        let Some(regions) = world.regions.as_ref() else {
+            continue;
+        };
+        let path = regions.files.path(coord);
+        let task = IoTaskPool::get().spawn(async move { read_chunk(&path, coord, chunk_size) });
+        tasks.loads.insert(coord, task);
+    }
+
+    let Some(regions) = world.regions.as_mut() else {
+        return;
+    };
+
+    // wait before saving again after a failure, saving every unsaved chunk once the wait is over
+    match regions.retry_at {
+        Some(retry_at) if now < retry_at => return,
+        Some(_) => {
+            regions.retry_at = None;
+            regions.save_requested = true;
+        }
+        None => {}
+    }
+
+    // start writing the edited chunks, one save at a time for each region
+    let mut pending: HashMap<PathBuf, Vec<(IVec3, VoxelData)>> = HashMap::new();
+    for (coord, data) in regions.evicted.drain() {
+        pending
+            .entry(regions.files.path(coord))
+            .or_default()
+            .push((coord, data));
+    }
+    if regions.save_requested {
+        regions.save_requested = false;
+        for (coord, state) in world.chunks.iter_mut().filter(|(_, state)| state.unsaved) {
+            state.unsaved = false;
+            pending
+                .entry(regions.files.path(*coord))
+                .or_default()
+                .push((*coord, state.data.clone()));
+        }
+    }
+    for (path, chunks) in pending {
+        if tasks.saves.contains_key(&path) {
+            // wait for the previous save of this region to finish
+            regions.evicted.extend(chunks);
+            continue;
+        }
+        let raw: Vec<(IVec3, Vec<u8>)> = chunks
+            .iter()
+            .map(|(coord, data)| (*coord, chunk_to_raw(data)))
+            .collect();
+        let coords: Vec<IVec3> = chunks.iter().map(|(coord, _)| *coord).collect();
+        regions.saving.extend(chunks);
+        let task_path = path.clone();
+        let task =
+            IoTaskPool::get().spawn(async move { write_region(&task_path, chunk_size, raw) });
+        tasks.saves.insert(path, (task, coords));
+    }
+}
@@ -0,0 +1,93 @@
+use bevy::{
+    math::{IVec3, UVec3, Vec3},
+    utils::HashMap,
+};
+
+use super::{Voxel, VoxelData, VoxelModel, VoxelQueryable};
+
+/// How [`VoxelModel::resampled`] picks the voxel for each cell of the resized model
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VoxelResampleFilter {
+    /// Each cell takes the source voxel at its center. Fast, and keeps thin features sharp when upscaling, but can drop
+    /// thin features entirely when downscaling.
+    #[default]
+    Nearest,
+    /// Each cell takes the most common voxel among the source voxels it covers, counting empty voxels, so that the
+    /// overall shape and material balance of the model are kept when downscaling. Ties favor solid voxels. Behaves
+    /// like [`VoxelResampleFilter::Nearest`] when upscaling.
+    Majority,
+}
+
+impl VoxelData {
+    /// Returns a copy of the model resized to `new_size` voxels, keeping the voxel size and the rest of its settings,
+    /// so that the model stays editable at its new scale.
+    ///
+    /// ### Arguments
+    /// * `new_size` - the size of the returned model in voxels
+    /// * `filter` - how each voxel of the returned model is picked from the source voxels it covers
+    pub fn resampled(&self, new_size: UVec3, filter: VoxelResampleFilter) -> VoxelData {
+        let mut resampled =
+            VoxelData::new(new_size, self.mesh_outer_faces, self.voxel_size).with_settings_of(self);
+        let size = self.size();
+        if size.cmple(IVec3::ZERO).any() {
+            return resampled;
+        }
+        let scale = size.as_vec3() / new_size.max(UVec3::ONE).as_vec3();
+        for z in 0..new_size.z {
+            for y in 0..new_size.y {
+                for x in 0..new_size.x {
+                    let cell = UVec3::new(x, y, z);
+                    let voxel = match filter {
+                        VoxelResampleFilter::Nearest => self.nearest(cell, scale),
+                        VoxelResampleFilter::Majority => self.majority(cell, scale),
+                    };
+                    if voxel != Voxel::EMPTY {
+                        resampled.set_voxel(voxel, cell);
+                    }
+                }
+            }
+        }
+        resampled
+    }
+
+    fn nearest(&self, cell: UVec3, scale: Vec3) -> Voxel {
+        let source = ((cell.as_vec3() + 0.5) * scale).floor().as_ivec3();
+        self.get_voxel_at_point(source.min(self.size() - IVec3::ONE))
+            .unwrap_or(Voxel::EMPTY)
+    }
+
+    fn majority(&self, cell: UVec3, scale: Vec3) -> Voxel {
+        if scale.cmple(Vec3::ONE).all() {
+            return self.nearest(cell, scale);
+        }
+        let min = (cell.as_vec3() * scale).floor().as_ivec3();
+        let max = ((cell.as_vec3() + 1.0) * scale)
+            .ceil()
+            .as_ivec3()
+            .max(min + IVec3::ONE)
+            .min(self.size());
+        let mut counts: HashMap<Voxel, usize> = HashMap::new();
+        for z in min.z..max.z {
+            for y in min.y..max.y {
+                for x in min.x..max.x {
+                    if let Ok(voxel) = self.get_voxel_at_point(IVec3::new(x, y, z)) {
+                        *counts.entry(voxel).or_default() += 1;
+                    }
+                }
+            }
+        }
+        counts
+            .into_iter()
+            .max_by_key(|(voxel, count)| (*count, *voxel != Voxel::EMPTY, u8::MAX - voxel.0))
+            .map_or(Voxel::EMPTY, |(voxel, _)| voxel)
+    }
+}
+
+impl VoxelModel {
+    /// Returns a copy of the voxel data of the model resized to `new_size` voxels, for instance to shrink an item when
+    /// it is picked up while keeping it editable. Create a new model from the data with [`VoxelModel::new`]. See
+    /// [`VoxelData::resampled`].
+    pub fn resampled(&self, new_size: UVec3, filter: VoxelResampleFilter) -> VoxelData {
+        self.data.resampled(new_size, filter)
+    }
+}
@@ -0,0 +1,94 @@
+use bevy::math::{IVec3, UVec3};
+use ndshape::Shape;
+
+use crate::VoxelRng;
+
+use super::{RawVoxel, Voxel, VoxelData, VoxelModel};
+
+/// An index of the solid voxels of a model by palette index, for picking random voxels of a kind, for instance to place
+/// a chest somewhere inside the walls of a dungeon, without rejection sampling the whole model.
+///
+/// The index is a snapshot of the voxels it was built from, so it should be rebuilt after the model is modified.
+#[derive(Clone, Debug, Default)]
+pub struct VoxelSampler {
+    size: UVec3,
+    /// The cells holding each palette index, as indices into the model with x varying fastest, then y, then z
+    cells: Vec<Vec<u32>>,
+}
+
+impl VoxelSampler {
+    /// Indexes every solid voxel of the `data`
+    pub fn from_data(data: &VoxelData) -> Self {
+        let size = data._size().max(IVec3::ZERO).as_uvec3();
+        let leading_padding = UVec3::splat(data.padding() / 2);
+        let voxels = data.dense_voxels();
+        let mut cells = vec![Vec::new(); RawVoxel::EMPTY.0 as usize];
+        let mut cell = 0;
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let index = data
+                        .shape
+                        .linearize((UVec3::new(x, y, z) + leading_padding).into());
+                    if let Some(cells) = cells.get_mut(voxels[index as usize].0 as usize) {
+                        cells.push(cell);
+                    }
+                    cell += 1;
+                }
+            }
+        }
+        Self { size, cells }
+    }
+
+    /// Picks a random solid voxel for which `filter` returns true, with every matching voxel equally likely. `filter`
+    /// is called once per palette index rather than once per voxel, and the chosen voxel is looked up in its cells,
+    /// so the cost doesn't grow with the size of the model.
+    ///
+    /// ### Returns
+    /// the position of the voxel in voxel space, or `None` if no voxel matches.
+    pub fn sample_random_voxel(
+        &self,
+        filter: impl Fn(Voxel) -> bool,
+        rng: &mut VoxelRng,
+    ) -> Option<IVec3> {
+        let matching: Vec<&Vec<u32>> = self
+            .cells
+            .iter()
+            .enumerate()
+            .filter(|(index, cells)| !cells.is_empty() && filter(RawVoxel(*index as u8).into()))
+            .map(|(_, cells)| cells)
+            .collect();
+        let total: u64 = matching.iter().map(|cells| cells.len() as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut remaining = (rng.next_u64() % total) as usize;
+        for cells in matching {
+            if let Some(cell) = cells.get(remaining) {
+                let (width, height) = (self.size.x, self.size.y);
+                return Some(
+                    UVec3::new(cell % width, cell / width % height, cell / (width * height))
+                        .as_ivec3(),
+                );
+            }
+            remaining -= cells.len();
+        }
+        None
+    }
+}
+
+impl VoxelData {
+    /// Indexes the solid voxels by palette index, for picking random voxels. See [`VoxelSampler`].
+    pub fn sampler(&self) -> VoxelSampler {
+        VoxelSampler::from_data(self)
+    }
+}
+
+impl VoxelModel {
+    /// Indexes the solid voxels of the model by palette index, for picking random voxels, for instance to place a
+    /// chest somewhere inside the walls of a dungeon. Build the sampler once and reuse it for every voxel picked
+    /// until the model is modified. See [`VoxelSampler`].
+    pub fn sampler(&self) -> VoxelSampler {
+        self.data.sampler()
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+
+use bevy::math::{IVec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use super::{RawVoxel, Voxel};
+
+/// The horizontal direction that a [`VoxelShape`] rises towards
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoxelFacing {
+    /// Rises towards +X
+    PosX,
+    /// Rises towards -X
+    NegX,
+    /// Rises towards +Z
+    PosZ,
+    /// Rises towards -Z
+    NegZ,
+}
+
+impl VoxelFacing {
+    /// The number of quarter turns about +Y that take +Z to this facing
+    pub(super) fn quarter_turns(self) -> u32 {
+        match self {
+            VoxelFacing::PosZ => 0,
+            VoxelFacing::PosX => 1,
+            VoxelFacing::NegZ => 2,
+            VoxelFacing::NegX => 3,
+        }
+    }
+}
+
+/// The primitive that a voxel is drawn as. See [`VoxelShapes`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VoxelShape {
+    /// A full cube, the default
+    #[default]
+    Cube,
+    /// The lower half of a cube
+    Slab,
+    /// A wedge whose sloped top rises from the bottom of the voxel to the top of the side it faces
+    Ramp(VoxelFacing),
+    /// A lower step covering the whole voxel, with an upper step on the half of the voxel it faces
+    Stairs(VoxelFacing),
+}
+
+/// A face of a [`VoxelShape`], in the unit cube of its voxel
+pub(super) struct ShapeFace {
+    /// The corners, counter-clockwise when seen from outside. Triangles repeat their last corner.
+    pub corners: [Vec3; 4],
+    /// The side of the voxel that the face lies on, if it lies on one
+    pub side: Option<IVec3>,
+    /// Whether the face covers the whole side
+    pub full: bool,
+}
+
+impl ShapeFace {
+    fn new(corners: [[f32; 3]; 4], side: Option<IVec3>, full: bool) -> Self {
+        Self {
+            corners: corners.map(Vec3::from),
+            side,
+            full,
+        }
+    }
+
+    /// The outward normal of the face
+    pub fn normal(&self) -> Vec3 {
+        let [a, b, c, _] = self.corners;
+        (b - a).cross(c - a).normalize_or_zero()
+    }
+
+    /// Rotates the face about the vertical axis through the center of the voxel
+    fn rotated(mut self, quarter_turns: u32) -> Self {
+        for _ in 0..quarter_turns {
+            for corner in self.corners.iter_mut() {
+                *corner = Vec3::new(corner.z, corner.y, 1.0 - corner.x);
+            }
+            self.side = self.side.map(|side| IVec3::new(side.z, side.y, -side.x));
+        }
+        self
+    }
+}
+
+fn bottom() -> ShapeFace {
+    ShapeFace::new(
+        [[0., 0., 0.], [1., 0., 0.], [1., 0., 1.], [0., 0., 1.]],
+        Some(IVec3::NEG_Y),
+        true,
+    )
+}
+
+fn top(height: f32, z0: f32, z1: f32) -> ShapeFace {
+    let side = (height == 1.0).then_some(IVec3::Y);
+    let full = z0 == 0.0 && z1 == 1.0;
+    ShapeFace::new(
+        [
+            [0., height, z0],
+            [0., height, z1],
+            [1., height, z1],
+            [1., height, z0],
+        ],
+        side,
+        full,
+    )
+}
+
+/// The -X and +X sides of the box spanning `y0..y1` and `z0..z1`
+fn sides(y0: f32, y1: f32, z0: f32, z1: f32) -> [ShapeFace; 2] {
+    let full = y0 == 0.0 && y1 == 1.0 && z0 == 0.0 && z1 == 1.0;
+    [
+        ShapeFace::new(
+            [[0., y0, z0], [0., y0, z1], [0., y1, z1], [0., y1, z0]],
+            Some(IVec3::NEG_X),
+            full,
+        ),
+        ShapeFace::new(
+            [[1., y0, z0], [1., y1, z0], [1., y1, z1], [1., y0, z1]],
+            Some(IVec3::X),
+            full,
+        ),
+    ]
+}
+
+/// The face towards -Z at `z`, spanning `y0..y1`
+fn front(z: f32, y0: f32, y1: f32) -> ShapeFace {
+    let side = (z == 0.0).then_some(IVec3::NEG_Z);
+    ShapeFace::new(
+        [[0., y0, z], [0., y1, z], [1., y1, z], [1., y0, z]],
+        side,
+        y0 == 0.0 && y1 == 1.0,
+    )
+}
+
+/// The face towards +Z at `z`, spanning `y0..y1`
+fn back(z: f32, y0: f32, y1: f32) -> ShapeFace {
+    let side = (z == 1.0).then_some(IVec3::Z);
+    ShapeFace::new(
+        [[0., y0, z], [1., y0, z], [1., y1, z], [0., y1, z]],
+        side,
+        y0 == 0.0 && y1 == 1.0,
+    )
+}
+
+impl VoxelShape {
+    /// The faces of the shape, or none for a [`VoxelShape::Cube`], which is meshed with the other cubes
+    pub(super) fn faces(self) -> Vec<ShapeFace> {
+        let (faces, facing) = match self {
+            VoxelShape::Cube => return Vec::new(),
+            VoxelShape::Slab => {
+                let mut faces = vec![
+                    bottom(),
+                    top(0.5, 0.0, 1.0),
+                    front(0.0, 0.0, 0.5),
+                    back(1.0, 0.0, 0.5),
+                ];
+                faces.extend(sides(0.0, 0.5, 0.0, 1.0));
+                return faces;
+            }
+            VoxelShape::Ramp(facing) => {
+                let faces = vec![
+                    bottom(),
+                    back(1.0, 0.0, 1.0),
+                    ShapeFace::new(
+                        [[0., 0., 0.], [0., 1., 1.], [1., 1., 1.], [1., 0., 0.]],
+                        None,
+                        false,
+                    ),
+                    ShapeFace::new(
+                        [[0., 0., 0.], [0., 0., 1.], [0., 1., 1.], [0., 1., 1.]],
+                        Some(IVec3::NEG_X),
+                        false,
+                    ),
+                    ShapeFace::new(
+                        [[1., 0., 0.], [1., 1., 1.], [1., 0., 1.], [1., 0., 1.]],
+                        Some(IVec3::X),
+                        false,
+                    ),
+                ];
+                (faces, facing)
+            }
+            VoxelShape::Stairs(facing) => {
+                let mut faces = vec![
+                    bottom(),
+                    back(1.0, 0.0, 1.0),
+                    front(0.0, 0.0, 0.5),
+                    front(0.5, 0.5, 1.0),
+                    top(0.5, 0.0, 0.5),
+                    top(1.0, 0.5, 1.0),
+                ];
+                faces.extend(sides(0.0, 0.5, 0.0, 1.0));
+                faces.extend(sides(0.5, 1.0, 0.5, 1.0));
+                (faces, facing)
+            }
+        };
+        faces
+            .into_iter()
+            .map(|face| face.rotated(facing.quarter_turns()))
+            .collect()
+    }
+
+    /// Whether the shape covers the whole `side` of its voxel
+    pub(super) fn covers(self, side: IVec3) -> bool {
+        self == VoxelShape::Cube
+            || self
+                .faces()
+                .iter()
+                .any(|face| face.full && face.side == Some(side))
+    }
+}
+
+/// Maps palette indices to the [`VoxelShape`] that their voxels are drawn as, so that terrain can have ramps, slabs and
+/// stairs while the voxel data stays a grid of palette indices. Voxels of unmapped indices are cubes.
+///
+/// Shaped voxels only affect meshing: they are still whole voxels to queries, raycasts and
+/// [`crate::VoxelCharacterController`]s. Faces are culled against the neighbors that cover them, whether cubes or
+/// shapes with a full face on that side, such as the bottom of a slab or the back of a ramp.
+/// ```
+/// # use bevy_vox_scene::{Voxel, VoxelFacing, VoxelShape, VoxelShapes};
+/// let shapes = VoxelShapes::default()
+///     .with_shape(Voxel(7), VoxelShape::Slab)
+///     .with_shape(Voxel(8), VoxelShape::Ramp(VoxelFacing::PosX));
+/// assert_eq!(shapes.shape(&Voxel(8)), VoxelShape::Ramp(VoxelFacing::PosX));
+/// assert_eq!(shapes.shape(&Voxel(1)), VoxelShape::Cube);
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct VoxelShapes(BTreeMap<u8, VoxelShape>);
+
+impl VoxelShapes {
+    /// Draws the voxels of the palette index of `voxel` as `shape`
+    pub fn with_shape(mut self, voxel: Voxel, shape: VoxelShape) -> Self {
+        if voxel != Voxel::EMPTY {
+            self.0.insert(voxel.0, shape);
+        }
+        self
+    }
+
+    /// The shape that `voxel` is drawn as
+    pub fn shape(&self, voxel: &Voxel) -> VoxelShape {
+        self.0.get(&voxel.0).copied().unwrap_or_default()
+    }
+
+    /// Returns true if every voxel is drawn as a cube
+    pub fn is_empty(&self) -> bool {
+        self.0.values().all(|shape| *shape == VoxelShape::Cube)
+    }
+
+    /// The shape of each raw palette index, with empty voxels as cubes
+    pub(crate) fn raw_shapes(&self) -> Vec<VoxelShape> {
+        let mut shapes = vec![VoxelShape::Cube; 256];
+        for (index, shape) in self.0.iter() {
+            shapes[RawVoxel::from(Voxel(*index)).0 as usize] = *shape;
+        }
+        shapes
+    }
+}
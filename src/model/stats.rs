@@ -0,0 +1,71 @@
+use super::{RawVoxel, Voxel, VoxelData, VoxelModel};
+
+impl VoxelData {
+    /// The number of solid voxels in the model
+    pub fn count_voxels(&self) -> usize {
+        self.histogram.iter().map(|count| *count as usize).sum()
+    }
+
+    /// The number of solid voxels for which `predicate` returns true
+    pub fn count_matching(&self, predicate: impl Fn(Voxel) -> bool) -> usize {
+        self.histogram()
+            .filter(|(voxel, _)| predicate(voxel.clone()))
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// The number of voxels in the model with the palette index of `voxel`. Always 0 for [`Voxel::EMPTY`].
+    pub fn count_of(&self, voxel: Voxel) -> usize {
+        let raw = RawVoxel::from(voxel);
+        self.histogram
+            .get(raw.0 as usize)
+            .map_or(0, |count| *count as usize)
+    }
+
+    /// The number of voxels of each palette index present in the model, in ascending order of index. Indices with no
+    /// voxels are skipped.
+    pub fn histogram(&self) -> impl Iterator<Item = (Voxel, usize)> + '_ {
+        self.histogram
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(index, count)| (RawVoxel(index as u8).into(), *count as usize))
+    }
+
+    /// Updates the histogram for a voxel changing from `old` to `new`. Counts never drop below zero, even if the
+    /// histogram is out of step with the voxels.
+    pub(crate) fn record_change(histogram: &mut [u32], old: &RawVoxel, new: &RawVoxel) {
+        if old == new {
+            return;
+        }
+        if let Some(count) = histogram.get_mut(old.0 as usize) {
+            *count = count.saturating_sub(1);
+        }
+        if let Some(count) = histogram.get_mut(new.0 as usize) {
+            *count += 1;
+        }
+    }
+}
+
+impl VoxelModel {
+    /// The number of solid voxels in the model. This is tracked as the model is edited, so is cheap to call every frame.
+    pub fn count_voxels(&self) -> usize {
+        self.data.count_voxels()
+    }
+
+    /// The number of solid voxels for which `predicate` returns true, for instance to find how much of a resource
+    /// remains in a model
+    pub fn count_matching(&self, predicate: impl Fn(Voxel) -> bool) -> usize {
+        self.data.count_matching(predicate)
+    }
+
+    /// The number of voxels in the model with the palette index of `voxel`
+    pub fn count_of(&self, voxel: Voxel) -> usize {
+        self.data.count_of(voxel)
+    }
+
+    /// The number of voxels of each palette index present in the model, in ascending order of index
+    pub fn histogram(&self) -> impl Iterator<Item = (Voxel, usize)> + '_ {
+        self.data.histogram()
+    }
+}
@@ -0,0 +1,315 @@
+use std::sync::Arc;
+
+use bevy::{
+    asset::Assets,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventWriter},
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    hierarchy::DespawnRecursiveExt,
+    log::warn,
+    math::{IVec3, UVec3, Vec3},
+    pbr::StandardMaterial,
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    render::mesh::Mesh,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+    transform::components::GlobalTransform,
+    utils::{HashMap, HashSet},
+};
+
+use super::{
+    region::{chunk_from_raw, read_chunk},
+    world::{spawn_chunk, ChunkState, VoxelWorld},
+    Voxel, VoxelContext, VoxelData, VoxelModel,
+};
+
+/// Settings for generating the chunks of a [`VoxelWorld`] around the player as they move through it, for infinite
+/// procedural worlds. Add them with [`VoxelWorld::with_streaming`], and add a [`VoxelWorldViewer`] to the player or
+/// camera.
+///
+/// Each update, the missing chunks within `view_distance` of a viewer are generated and meshed on the
+/// [`AsyncComputeTaskPool`], nearest first, favoring the chunks ahead of the viewer's direction of travel. A
+/// [`VoxelChunkLoaded`] event is sent as each chunk is ready. Chunks further than `view_distance + evict_margin` from
+/// every viewer are despawned, and a [`VoxelChunkUnloaded`] event is sent with their voxels, so that edits can be
+/// saved. Evicted chunks are generated afresh if a viewer returns to them, unless the world has
+/// [`crate::VoxelRegionFiles`], in which case edited chunks are saved as they are evicted and read back instead.
+#[derive(Clone)]
+pub struct VoxelWorldStreaming {
+    /// The distance in chunks around each viewer within which chunks are generated. Defaults to 6.
+    pub view_distance: u32,
+    /// The additional distance in chunks before chunks are evicted, so that chunks at the edge of the view aren't
+    /// repeatedly generated and evicted as the viewer moves back and forth. Defaults to 2.
+    pub evict_margin: u32,
+    /// The maximum number of chunks being generated at once. Defaults to 8.
+    pub max_tasks: usize,
+    /// How strongly chunks ahead of the viewer are favored over chunks behind it, in chunks. Defaults to 2.
+    pub travel_bias: f32,
+    generator: Arc<dyn Fn(IVec3) -> Voxel + Send + Sync>,
+}
+
+impl VoxelWorldStreaming {
+    /// Creates the settings with a `generator` returning the voxel at each global position
+    pub fn new<F: Fn(IVec3) -> Voxel + Send + Sync + 'static>(generator: F) -> Self {
+        Self {
+            view_distance: 6,
+            evict_margin: 2,
+            max_tasks: 8,
+            travel_bias: 2.0,
+            generator: Arc::new(generator),
+        }
+    }
+
+    /// Sets the distance in chunks around each viewer within which chunks are generated
+    pub fn with_view_distance(mut self, view_distance: u32) -> Self {
+        self.view_distance = view_distance;
+        self
+    }
+}
+
+/// Marks an entity, such as the player or camera, around which the chunks of a streaming [`VoxelWorld`] are generated
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct VoxelWorldViewer {
+    last_position: Option<Vec3>,
+    direction: Vec3,
+}
+
+/// Sent when a chunk of a streaming [`VoxelWorld`] has been generated
+#[derive(Event, Clone, Debug)]
+pub struct VoxelChunkLoaded {
+    /// The coordinate of the chunk
+    pub coord: IVec3,
+    /// The entity displaying the chunk, or `None` if the chunk is empty
+    pub entity: Option<Entity>,
+}
+
+/// Sent when a chunk of a streaming [`VoxelWorld`] has been evicted
+#[derive(Event, Clone, Debug)]
+pub struct VoxelChunkUnloaded {
+    /// The coordinate of the chunk
+    pub coord: IVec3,
+    /// The voxels of the chunk when it was evicted
+    pub data: VoxelData,
+}
+
+/// The voxels and mesh of a chunk, and whether it has edits that haven't been saved
+type ChunkTask = Task<(VoxelData, Mesh, Option<f32>, bool)>;
+
+/// The chunks of the streaming world that are being generated
+#[derive(Default)]
+pub(crate) struct StreamingTasks {
+    tasks: HashMap<IVec3, ChunkTask>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn stream_voxel_world(
+    mut commands: Commands,
+    world: Option<ResMut<VoxelWorld>>,
+    mut tasks: Local<StreamingTasks>,
+    mut viewers: Query<(&GlobalTransform, &mut VoxelWorldViewer)>,
+    mut loaded: EventWriter<VoxelChunkLoaded>,
+    mut unloaded: EventWriter<VoxelChunkUnloaded>,
+    mut models: ResMut<Assets<VoxelModel>>,
+    contexts: Res<Assets<VoxelContext>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Some(mut world) = world else {
+        return;
+    };
+    let Some(streaming) = world.streaming.clone() else {
+        return;
+    };
+    let Some(context) = contexts.get(&world.context) else {
+        return;
+    };
+    let context_handle = world.context.clone();
+    let chunk_size = world.chunk_size;
+    let voxel_size = world.voxel_size;
+
+    // finish the chunks that have been generated
+    let finished: Vec<IVec3> = tasks
+        .tasks
+        .iter_mut()
+        .filter_map(|(coord, task)| {
+            block_on(future::poll_once(task)).map(|result| {
+                // a chunk that was edited while it was being generated is already resident, and keeps its edits
+                // rather than being replaced, which would also leave its entity behind
+                if world.chunks.contains_key(coord) {
+                    return *coord;
+                }
+                let (data, mesh, average_ior, unsaved) = result;
+                let mut state = ChunkState {
+                    data,
+                    model: None,
+                    entity: None,
+                    dirty: false,
+                    unsaved,
+                    water: None,
+                    enclosed: false,
+                };
+                // light and water surfaces are added once the chunk's neighbors are known
+                let finish_later = world.lighting.is_some() || world.water.is_some();
+                let entity = (state.data.count_voxels() > 0)
+                    .then(|| {
+                        spawn_chunk(
+                            &mut commands,
+                            *coord,
+                            &mut state,
+                            mesh,
+                            average_ior,
+                            (context, &context_handle),
+                            chunk_size,
+                            voxel_size,
+                            (&mut models, &mut meshes, &mut materials),
+                        )
+                    })
+                    .flatten();
+                // the faces on the borders with resident chunks are culled once the chunk is remeshed with them
+                state.dirty = finish_later || world.has_neighbors(*coord);
+                world.chunks.insert(*coord, state);
+                world.mark_neighbors_dirty(*coord);
+                loaded.send(VoxelChunkLoaded {
+                    coord: *coord,
+                    entity,
+                });
+                *coord
+            })
+        })
+        .collect();
+    for coord in finished {
+        tasks.tasks.remove(&coord);
+    }
+
+    // find the chunks around each viewer
+    let view_distance = streaming.view_distance as i32;
+    let keep_distance = (streaming.view_distance + streaming.evict_margin) as i32;
+    let mut wanted: Vec<(f32, IVec3)> = Vec::new();
+    let mut kept: HashSet<IVec3> = HashSet::new();
+    let chunk_extent = chunk_size.as_vec3() * voxel_size;
+    for (transform, mut viewer) in viewers.iter_mut() {
+        let position = transform.translation();
+        if let Some(last_position) = viewer.last_position {
+            if let Some(moved) = (position - last_position).try_normalize() {
+                viewer.direction = (viewer.direction * 0.8 + moved * 0.2).normalize_or_zero();
+            }
+        }
+        viewer.last_position = Some(position);
+        let center = (position / chunk_extent).floor().as_ivec3();
+        for z in -keep_distance..=keep_distance {
+            for y in -keep_distance..=keep_distance {
+                for x in -keep_distance..=keep_distance {
+                    let offset = IVec3::new(x, y, z);
+                    let distance = offset.as_vec3().length();
+                    if distance > keep_distance as f32 {
+                        continue;
+                    }
+                    kept.insert(center + offset);
+                    if distance > view_distance as f32
+                        || world.chunks.contains_key(&(center + offset))
+                        || tasks.tasks.contains_key(&(center + offset))
+                    {
+                        continue;
+                    }
+                    let ahead = offset
+                        .as_vec3()
+                        .try_normalize()
+                        .map_or(0.0, |offset| offset.dot(viewer.direction));
+                    wanted.push((distance - ahead * streaming.travel_bias, center + offset));
+                }
+            }
+        }
+    }
+
+    // evict the chunks left behind
+    if !viewers.is_empty() {
+        let evicted: Vec<IVec3> = world
+            .chunks
+            .keys()
+            .filter(|coord| !kept.contains(*coord))
+            .copied()
+            .collect();
+        for coord in evicted {
+            let Some(state) = world.chunks.remove(&coord) else {
+                continue;
+            };
+            world.mark_neighbors_dirty(coord);
+            if let Some(entity) = state.entity {
+                commands.entity(entity).despawn_recursive();
+            }
+            if let Some(model) = state.model {
+                models.remove(&model);
+            }
+            if let Some(regions) = world.regions.as_mut().filter(|_| state.unsaved) {
+                regions.evicted.insert(coord, state.data.clone());
+            }
+            unloaded.send(VoxelChunkUnloaded {
+                coord,
+                data: state.data,
+            });
+        }
+        tasks.tasks.retain(|coord, _| kept.contains(coord));
+    }
+
+    // start generating the nearest chunks
+    wanted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    for (_, coord) in wanted {
+        if tasks.tasks.len() >= streaming.max_tasks {
+            break;
+        }
+        if tasks.tasks.contains_key(&coord) {
+            continue;
+        }
+        let generator = streaming.generator.clone();
+        let palette = context.palette.clone();
+        let origin = coord * chunk_size;
+        let size = chunk_size.as_uvec3();
+        let mut data = world.chunk_data(coord);
+        // chunks with edits that are still waiting to be saved are restored from memory
+        let restored = world.regions.as_ref().and_then(|regions| {
+            regions
+                .evicted
+                .get(&coord)
+                .or_else(|| regions.saving.get(&coord))
+                .cloned()
+        });
+        let path = world
+            .regions
+            .as_ref()
+            .map(|regions| regions.files.path(coord));
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            if let Some(data) = restored {
+                let (mesh, average_ior) = data.remesh(&palette);
+                return (data, mesh, average_ior, true);
+            }
+            let saved = path.and_then(|path| {
+                read_chunk(&path, coord, chunk_size).unwrap_or_else(|error| {
+                    warn!("Failed to load voxel chunk {coord}: {error}");
+                    None
+                })
+            });
+            if let Some(raw) = saved {
+                let data = chunk_from_raw(data, &raw);
+                let (mesh, average_ior) = data.remesh(&palette);
+                return (data, mesh, average_ior, false);
+            }
+            for z in 0..size.z {
+                for y in 0..size.y {
+                    for x in 0..size.x {
+                        let position = UVec3::new(x, y, z);
+                        let voxel = generator(origin + position.as_ivec3());
+                        if voxel != Voxel::EMPTY {
+                            data.set_voxel(voxel, position);
+                        }
+                    }
+                }
+            }
+            let (mesh, average_ior) = data.remesh(&palette);
+            (data, mesh, average_ior, false)
+        });
+        tasks.tasks.insert(coord, task);
+    }
+}
@@ -0,0 +1,47 @@
+use bevy::math::IVec3;
+use ndshape::Shape;
+
+use super::{RawVoxel, Voxel, VoxelData, VoxelModel, SIDES};
+
+impl VoxelData {
+    /// Iterates over the solid voxels with at least one empty neighbor, yielding the position of each voxel in voxel
+    /// space along with the voxel.
+    ///
+    /// Voxels on the edge of the model are on the surface if the outer faces of the model are meshed.
+    pub fn iter_surface(&self) -> impl Iterator<Item = (IVec3, Voxel)> + '_ {
+        let size = self._size();
+        let leading_padding = IVec3::splat(self.padding() as i32 / 2);
+        let raw_size = IVec3::from(self.shape.as_array().map(|axis| axis as i32));
+        let is_empty = move |position: IVec3| -> bool {
+            if position.cmplt(IVec3::ZERO).any() || position.cmpge(raw_size).any() {
+                return false;
+            }
+            *self.voxel_at_index(self.shape.linearize(position.as_uvec3().into()) as usize)
+                == RawVoxel::EMPTY
+        };
+        (0..size.z).flat_map(move |z| {
+            (0..size.y).flat_map(move |y| {
+                (0..size.x).filter_map(move |x| {
+                    let position = IVec3::new(x, y, z);
+                    let padded = position + leading_padding;
+                    let raw = self
+                        .voxel_at_index(self.shape.linearize(padded.as_uvec3().into()) as usize);
+                    if *raw == RawVoxel::EMPTY
+                        || !SIDES.iter().any(|offset| is_empty(padded + *offset))
+                    {
+                        return None;
+                    }
+                    Some((position, raw.clone().into()))
+                })
+            })
+        })
+    }
+}
+
+impl VoxelModel {
+    /// Iterates over the solid voxels with at least one empty neighbor, for effects that only affect the shell of a
+    /// model, such as freezing its surface. See [`VoxelData::iter_surface`].
+    pub fn iter_surface(&self) -> impl Iterator<Item = (IVec3, Voxel)> + '_ {
+        self.data.iter_surface()
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{
+        entity::Entity,
+        system::Commands,
+        world::{Command, World},
+    },
+    log::warn,
+    render::primitives::Aabb,
+};
+
+use crate::VoxelModelInstance;
+
+use super::{lod::VoxelLod, VoxelModel};
+
+/// Extension to [`Commands`] for swapping the model displayed by an instance
+pub trait SwapVoxelModelCommandsExt {
+    /// Replace the model displayed by a [`VoxelModelInstance`], swapping its mesh, material and voxel data in one step,
+    /// for instance to switch between damage states of a building authored as separate models.
+    ///
+    /// ### Arguments
+    /// * `instance` - the entity with the [`VoxelModelInstance`] to update
+    /// * `model` - the handle to the new model, which must already be loaded. Models from the same `.vox` file share a
+    ///   [`crate::VoxelContext`], so the instance's context is kept.
+    fn swap_voxel_model(&mut self, instance: Entity, model: Handle<VoxelModel>) -> &mut Self;
+}
+
+impl SwapVoxelModelCommandsExt for Commands<'_, '_> {
+    fn swap_voxel_model(&mut self, instance: Entity, model: Handle<VoxelModel>) -> &mut Self {
+        self.add(SwapVoxelModel { instance, model });
+        self
+    }
+}
+
+struct SwapVoxelModel {
+    instance: Entity,
+    model: Handle<VoxelModel>,
+}
+
+impl Command for SwapVoxelModel {
+    fn apply(self, world: &mut World) {
+        let Some((mesh, material)) = world
+            .resource::<Assets<VoxelModel>>()
+            .get(&self.model)
+            .map(|model| (model.mesh.clone(), model.material.clone()))
+        else {
+            warn!("Can't swap to a voxel model that hasn't loaded");
+            return;
+        };
+        let Some(mut entity) = world.get_entity_mut(self.instance) else {
+            return;
+        };
+        let Some(mut instance) = entity.get_mut::<VoxelModelInstance>() else {
+            warn!("Can't swap the model of an entity without a VoxelModelInstance");
+            return;
+        };
+        instance.model = self.model;
+        if let Some(mut lod) = entity.get_mut::<VoxelLod>() {
+            lod.invalidate();
+        }
+        // the bounds are recalculated for the new mesh
+        entity.insert((mesh, material)).remove::<Aabb>();
+    }
+}
@@ -0,0 +1,156 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{
+    ecs::system::Resource,
+    math::{IVec3, Quat, UVec3},
+    utils::HashSet,
+};
+
+use super::{clipboard::VoxelClipboard, world::VoxelWorld, Voxel, SIDES};
+
+/// A tile of a [`VoxelTileset`], along with the sides on which it connects to the neighboring tiles
+#[derive(Clone, Debug)]
+pub struct VoxelTile {
+    /// The name of the tile, eg `corner` or `t-junction`
+    pub name: String,
+    /// The voxels of the tile, as authored
+    pub voxels: VoxelClipboard,
+    /// Whether the tile connects to a neighbor on each side, in the order `-X, -Y, -Z, +X, +Y, +Z`, as authored
+    pub connections: [bool; 6],
+}
+
+impl VoxelTile {
+    /// Creates a tile that connects to its neighbors on the supplied `sides`, given as unit axes such as [`IVec3::X`]
+    pub fn new(name: impl Into<String>, voxels: VoxelClipboard, sides: &[IVec3]) -> Self {
+        Self {
+            name: name.into(),
+            voxels,
+            connections: SIDES.map(|side| sides.contains(&side)),
+        }
+    }
+
+    /// The connections of the tile once it has been rotated by `quarter_turns` about +Y
+    fn rotated_connections(&self, quarter_turns: u32) -> [bool; 6] {
+        let rotation = quarter_turn_rotation(quarter_turns);
+        let mut rotated = [false; 6];
+        for (side, connects) in SIDES.iter().zip(self.connections) {
+            let side = (rotation * side.as_vec3()).round().as_ivec3();
+            if let Some(index) = SIDES.iter().position(|candidate| *candidate == side) {
+                rotated[index] = connects;
+            }
+        }
+        rotated
+    }
+}
+
+/// Picks and rotates tiles to match their neighbors as they are placed on a grid in a [`VoxelWorld`], in the manner of
+/// marching squares, so that paths, walls and fences get the right corners, edges and junctions without each variant
+/// being placed by hand.
+///
+/// Author one tile for each shape of junction, such as an end, a straight, a corner, a T-junction and a crossing, and
+/// tag each with the sides it connects on. Each time a cell is placed or removed, the cell and its neighbors are
+/// stamped with the tile whose connections, rotated by a multiple of 90 degrees about +Y, match the occupied
+/// neighbors. Tiles should have the same size on the X and Z axes so that they can be rotated in place, and should use
+/// the world's palette, for instance by being copied from models of the same `.vox` file.
+#[derive(Resource, Clone, Debug)]
+pub struct VoxelTileset {
+    tile_size: IVec3,
+    tiles: Vec<VoxelTile>,
+    cells: HashSet<IVec3>,
+}
+
+impl VoxelTileset {
+    /// Creates an empty tileset for tiles of `tile_size` voxels
+    pub fn new(tile_size: UVec3) -> Self {
+        Self {
+            tile_size: tile_size.max(UVec3::ONE).as_ivec3(),
+            tiles: Vec::new(),
+            cells: HashSet::new(),
+        }
+    }
+
+    /// Adds a tile to the set. Earlier tiles are preferred when several match a cell equally well.
+    pub fn with_tile(mut self, tile: VoxelTile) -> Self {
+        self.tiles.push(tile);
+        self
+    }
+
+    /// The tile with the supplied name
+    pub fn tile(&self, name: &str) -> Option<&VoxelTile> {
+        self.tiles.iter().find(|tile| tile.name == name)
+    }
+
+    /// Whether the cell at `cell`, in units of the tile size, has been placed
+    pub fn contains(&self, cell: IVec3) -> bool {
+        self.cells.contains(&cell)
+    }
+
+    /// Picks the tile for a cell whose neighbors are occupied on the supplied sides, in the order `-X, -Y, -Z, +X, +Y,
+    /// +Z`, and the number of quarter turns about +Y it is rotated by. A tile whose rotated connections match the
+    /// neighbors exactly is preferred, otherwise the tile matching on the most sides is picked. Returns `None` if the
+    /// set has no tiles.
+    pub fn select(&self, neighbors: [bool; 6]) -> Option<(&VoxelTile, u32)> {
+        let mut best: Option<(&VoxelTile, u32, usize)> = None;
+        for tile in self.tiles.iter() {
+            for quarter_turns in 0..4 {
+                let matches = tile
+                    .rotated_connections(quarter_turns)
+                    .iter()
+                    .zip(neighbors)
+                    .filter(|(connects, occupied)| **connects == *occupied)
+                    .count();
+                if best.map_or(true, |(_, _, best_matches)| matches > best_matches) {
+                    best = Some((tile, quarter_turns, matches));
+                }
+            }
+        }
+        best.map(|(tile, quarter_turns, _)| (tile, quarter_turns))
+    }
+
+    /// Places a tile at `cell`, in units of the tile size, and restamps the neighboring tiles to connect to it
+    pub fn place(&mut self, world: &mut VoxelWorld, cell: IVec3) {
+        self.cells.insert(cell);
+        self.restamp_around(world, cell);
+    }
+
+    /// Removes the tile at `cell`, in units of the tile size, emptying its voxels, and restamps the neighboring tiles
+    pub fn remove(&mut self, world: &mut VoxelWorld, cell: IVec3) {
+        if !self.cells.remove(&cell) {
+            return;
+        }
+        let origin = cell * self.tile_size;
+        world.fill(origin, origin + self.tile_size, Voxel::EMPTY);
+        self.restamp_around(world, cell);
+    }
+
+    fn restamp_around(&self, world: &mut VoxelWorld, cell: IVec3) {
+        for neighbor in std::iter::once(cell).chain(SIDES.iter().map(|side| cell + *side)) {
+            if self.cells.contains(&neighbor) {
+                self.stamp(world, neighbor);
+            }
+        }
+    }
+
+    /// Writes the tile selected for `cell` into the world, overwriting the variant that was there before
+    fn stamp(&self, world: &mut VoxelWorld, cell: IVec3) {
+        let neighbors = SIDES.map(|side| self.cells.contains(&(cell + side)));
+        let Some((tile, quarter_turns)) = self.select(neighbors) else {
+            return;
+        };
+        let voxels = tile.voxels.rotated(quarter_turn_rotation(quarter_turns));
+        let origin = cell * self.tile_size;
+        for z in 0..self.tile_size.z {
+            for y in 0..self.tile_size.y {
+                for x in 0..self.tile_size.x {
+                    let position = IVec3::new(x, y, z);
+                    let voxel = voxels.get_voxel(position).cloned().unwrap_or(Voxel::EMPTY);
+                    world.set_voxel(origin + position, voxel);
+                }
+            }
+        }
+    }
+}
+
+fn quarter_turn_rotation(quarter_turns: u32) -> Quat {
+    Quat::from_rotation_y(FRAC_PI_2 * quarter_turns as f32)
+}
@@ -0,0 +1,248 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    asset::Assets,
+    ecs::{
+        component::Component,
+        system::{Query, Res, ResMut},
+    },
+    math::IVec3,
+    pbr::StandardMaterial,
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    render::mesh::Mesh,
+    time::Time,
+};
+
+use crate::VoxelModelInstance;
+
+use super::{
+    brick::VoxelBrickMap,
+    compress::{decode_runs, encode_runs},
+    modify::update_model_mesh,
+    RawVoxel, VoxelContext, VoxelData, VoxelModel,
+};
+
+/// Records the voxels of a [`VoxelModelInstance`] over time, so that destruction can be rewound, for instance for
+/// time-rewind gameplay.
+///
+/// Add it to an entity holding a [`VoxelModelInstance`]. Every `interval` seconds the voxels of the model are compared
+/// with the previous recording, and only the voxels that changed are stored. The oldest state is kept run-length
+/// encoded, and recordings older than `max_duration` are folded into it, so the memory used grows with the amount of
+/// destruction rather than with the size of the model. Times are measured with [`Time::elapsed_seconds`].
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct VoxelTimeline {
+    /// The number of seconds between recordings. Defaults to 0.1.
+    pub interval: f32,
+    /// The number of seconds of history to keep. Defaults to 10.
+    pub max_duration: f32,
+    #[reflect(ignore)]
+    history: TimelineHistory,
+    #[reflect(ignore)]
+    rewind: Option<f32>,
+}
+
+impl Default for VoxelTimeline {
+    fn default() -> Self {
+        Self {
+            interval: 0.1,
+            max_duration: 10.0,
+            history: TimelineHistory::default(),
+            rewind: None,
+        }
+    }
+}
+
+impl VoxelTimeline {
+    /// Creates a timeline that records every `interval` seconds and keeps `max_duration` seconds of history
+    pub fn new(interval: f32, max_duration: f32) -> Self {
+        Self {
+            interval,
+            max_duration,
+            ..Default::default()
+        }
+    }
+
+    /// Restores the model to the state it was recorded in at `time`, measured with [`Time::elapsed_seconds`], on the
+    /// next update. Times before the start of the recording restore the oldest state. The history after `time` is
+    /// discarded, and recording continues from the restored state.
+    pub fn rewind_to(&mut self, time: f32) {
+        self.rewind = Some(time);
+    }
+
+    /// Restores the model to the state it was recorded in `seconds` before the latest recording
+    pub fn rewind_by(&mut self, seconds: f32) {
+        if let Some((_, latest)) = self.recorded_range() {
+            self.rewind_to(latest - seconds);
+        }
+    }
+
+    /// The times of the oldest and latest recordings, or `None` if nothing has been recorded yet
+    pub fn recorded_range(&self) -> Option<(f32, f32)> {
+        self.history
+            .base_time
+            .map(|base_time| (base_time, self.history.latest_time))
+    }
+
+    /// Discards the recorded history, so that recording starts again from the current state of the model
+    pub fn clear(&mut self) {
+        self.history = TimelineHistory::default();
+        self.rewind = None;
+    }
+
+    /// The number of bytes used to store the recorded history
+    pub fn memory_usage(&self) -> usize {
+        self.history.base.capacity() * std::mem::size_of::<(u32, u8)>()
+            + self.history.latest.capacity()
+            + self
+                .history
+                .frames
+                .iter()
+                .map(|frame| frame.changes.capacity() * std::mem::size_of::<(u32, u8)>())
+                .sum::<usize>()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct TimelineHistory {
+    /// The time of the oldest state, or `None` if nothing has been recorded
+    base_time: Option<f32>,
+    /// The oldest state, as runs of raw voxel values
+    base: Vec<(u32, u8)>,
+    /// The voxels that changed at each recording after the oldest state, by index
+    frames: VecDeque<TimelineFrame>,
+    /// The latest recorded state, uncompressed so that it can be compared against cheaply
+    latest: Vec<u8>,
+    latest_time: f32,
+}
+
+#[derive(Clone, Debug)]
+struct TimelineFrame {
+    time: f32,
+    changes: Vec<(u32, u8)>,
+}
+
+impl TimelineHistory {
+    /// Records the `voxels` at `time`, forgetting recordings from before `time - max_duration`
+    fn record(&mut self, time: f32, voxels: &[RawVoxel], max_duration: f32) {
+        if self.base_time.is_none() || voxels.len() != self.latest.len() {
+            self.latest = voxels.iter().map(|voxel| voxel.0).collect();
+            self.base = encode_runs(&self.latest);
+            self.base_time = Some(time);
+            self.latest_time = time;
+            self.frames.clear();
+            return;
+        }
+        let changes: Vec<(u32, u8)> = voxels
+            .iter()
+            .zip(self.latest.iter_mut())
+            .enumerate()
+            .filter(|(_, (voxel, latest))| voxel.0 != **latest)
+            .map(|(index, (voxel, latest))| {
+                *latest = voxel.0;
+                (index as u32, voxel.0)
+            })
+            .collect();
+        self.latest_time = time;
+        if !changes.is_empty() {
+            self.frames.push_back(TimelineFrame { time, changes });
+        }
+        let oldest = time - max_duration;
+        if self.frames.front().is_some_and(|frame| frame.time < oldest) {
+            let mut base = decode_runs(&self.base);
+            while let Some(frame) = self.frames.front() {
+                if frame.time >= oldest {
+                    break;
+                }
+                for (index, value) in frame.changes.iter() {
+                    base[*index as usize] = *value;
+                }
+                self.base_time = Some(frame.time);
+                self.frames.pop_front();
+            }
+            self.base = encode_runs(&base);
+        }
+    }
+
+    /// Returns the state recorded at `time`, discarding the recordings after it
+    fn rewind(&mut self, time: f32) -> Option<Vec<u8>> {
+        self.base_time?;
+        while self.frames.back().is_some_and(|frame| frame.time > time) {
+            self.frames.pop_back();
+        }
+        let mut state = decode_runs(&self.base);
+        for frame in self.frames.iter() {
+            for (index, value) in frame.changes.iter() {
+                state[*index as usize] = *value;
+            }
+        }
+        self.latest = state.clone();
+        Some(state)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_voxel_timelines(
+    time: Res<Time>,
+    mut instances: Query<(&VoxelModelInstance, &mut VoxelTimeline)>,
+    mut models: ResMut<Assets<VoxelModel>>,
+    contexts: Res<Assets<VoxelContext>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut brick_maps: ResMut<Assets<VoxelBrickMap>>,
+) {
+    let now = time.elapsed_seconds();
+    for (instance, mut timeline) in instances.iter_mut() {
+        if let Some(target) = timeline.rewind.take() {
+            let Some(state) = timeline.history.rewind(target) else {
+                continue;
+            };
+            timeline.history.latest_time = now;
+            let (Some(model), Some(context)) = (
+                models.get_mut(&instance.model),
+                contexts.get(&instance.context),
+            ) else {
+                continue;
+            };
+            model.data.restore(&state);
+            update_model_mesh(
+                model,
+                &mut meshes,
+                &mut materials,
+                &mut brick_maps,
+                context.opaque_material.clone(),
+                context.transmissive_material.clone(),
+                &context.palette,
+            );
+            continue;
+        }
+        if timeline.history.base_time.is_some()
+            && now - timeline.history.latest_time < timeline.interval
+        {
+            continue;
+        }
+        let Some(model) = models.get(&instance.model) else {
+            continue;
+        };
+        let max_duration = timeline.max_duration;
+        timeline
+            .history
+            .record(now, &model.data.dense_voxels(), max_duration);
+    }
+}
+
+impl VoxelData {
+    /// Overwrites every voxel with the raw values in `state`, which must match the padded size of the model
+    fn restore(&mut self, state: &[u8]) {
+        self.decompress();
+        if state.len() != self.voxels.len() {
+            return;
+        }
+        for (voxel, value) in self.voxels.iter_mut().zip(state.iter()) {
+            Self::record_change(&mut self.histogram, voxel, &RawVoxel(*value));
+            voxel.0 = *value;
+        }
+        self.mark_dirty(IVec3::ZERO, self._size());
+    }
+}
@@ -0,0 +1,54 @@
+use std::{fmt::Debug, sync::Arc};
+
+use bevy::{
+    color::{Color, ColorToComponents},
+    math::Vec3,
+};
+
+use super::Voxel;
+
+/// A hook run for every vertex as a model is meshed, returning a color that the voxel's color is multiplied by, so that
+/// the same voxel data can be tinted by its position in the world, for instance to shade grass by biome, without
+/// duplicating palette entries.
+///
+/// The tint is written to [`bevy::render::mesh::Mesh::ATTRIBUTE_COLOR`], which [`bevy::pbr::StandardMaterial`]
+/// multiplies its base color by. Greedy meshing merges the faces of neighboring voxels of the same type, so the tint
+/// is interpolated across merged faces, and is best suited to gradual gradients. Add it with
+/// [`crate::VoxelData::with_tint`] or [`crate::VoxelWorld::with_tint`].
+#[derive(Clone)]
+pub struct VoxelTint {
+    origin: Vec3,
+    tint: Arc<dyn Fn(Vec3, Voxel) -> Color + Send + Sync>,
+}
+
+impl Debug for VoxelTint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VoxelTint")
+            .field("origin", &self.origin)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VoxelTint {
+    /// Creates a tint from a function of the global position of each vertex and the voxel it belongs to
+    pub fn new<F: Fn(Vec3, Voxel) -> Color + Send + Sync + 'static>(tint: F) -> Self {
+        Self {
+            origin: Vec3::ZERO,
+            tint: Arc::new(tint),
+        }
+    }
+
+    /// Sets the global position of the model's origin, which is added to the local position of each vertex before the
+    /// tint is evaluated. Defaults to zero.
+    pub fn with_origin(mut self, origin: Vec3) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// The linear color multiplier for a vertex at the `local` position of a `voxel`
+    pub(crate) fn evaluate(&self, local: Vec3, voxel: Voxel) -> [f32; 4] {
+        (self.tint)(self.origin + local, voxel)
+            .to_linear()
+            .to_f32_array()
+    }
+}
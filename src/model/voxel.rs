@@ -1,7 +1,19 @@
+use bevy::{math::IVec3, reflect::Reflect};
 use block_mesh::{MergeVoxel, Voxel as BlockyVoxel, VoxelVisibility};
 
+/// The steps from a voxel to its six neighbors across each of its faces, in the order of the faces of a mesh: -X, -Y,
+/// -Z, +X, +Y, +Z
+pub(crate) const SIDES: [IVec3; 6] = [
+    IVec3::NEG_X,
+    IVec3::NEG_Y,
+    IVec3::NEG_Z,
+    IVec3::X,
+    IVec3::Y,
+    IVec3::Z,
+];
+
 /// A Voxel. The value is its index in the Magica Voxel palette (1-255), with 0 reserved for [`Voxel::EMPTY`].
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Reflect)]
 pub struct Voxel(pub u8);
 
 impl Voxel {
@@ -0,0 +1,269 @@
+use bevy::{
+    asset::{Assets, Handle},
+    core::Name,
+    ecs::{
+        component::Component,
+        system::{Commands, Query, Res},
+    },
+    hierarchy::{BuildChildren, DespawnRecursiveExt},
+    math::{bounding::Aabb3d, IVec3, UVec3, Vec3},
+    pbr::{PbrBundle, StandardMaterial},
+    prelude::ReflectComponent,
+    reflect::Reflect,
+    render::{
+        mesh::{Indices, Mesh, VertexAttributeValues},
+        render_asset::RenderAssetUsages,
+        render_resource::PrimitiveTopology,
+    },
+    transform::components::GlobalTransform,
+};
+use ndshape::Shape;
+
+use super::{
+    world::{ChunkState, VoxelWorld},
+    RawVoxel, Voxel, VoxelData,
+};
+
+/// Designates a voxel of a [`VoxelWorld`]'s palette as water. Add it with [`VoxelWorld::with_water`].
+///
+/// Water voxels are left out of the chunk meshes. Instead, each chunk containing water gets a child entity with a
+/// [`VoxelWaterSurface`] component, displaying a mesh of the top faces of the water that are open to the air, drawn
+/// slightly below the top of the voxels with the surface `material`. Water isn't solid, so it is ignored by
+/// [`VoxelWorld::move_and_slide`] and [`VoxelWorld::is_solid`]. Add a [`VoxelSubmersion`] to entities that need to know
+/// how deep they are in the water, for instance to apply buoyancy.
+#[derive(Clone, Debug)]
+pub struct VoxelWater {
+    /// The voxel that is water
+    pub voxel: Voxel,
+    /// The material of the water surface, which should usually be translucent
+    pub material: Handle<StandardMaterial>,
+    /// How far below the top of the water voxels the surface is drawn, in voxels. Defaults to 0.1.
+    pub surface_offset: f32,
+}
+
+impl VoxelWater {
+    /// Designates `voxel` as water, with a surface drawn with `material`
+    pub fn new(voxel: Voxel, material: Handle<StandardMaterial>) -> Self {
+        Self {
+            voxel,
+            material,
+            surface_offset: 0.1,
+        }
+    }
+}
+
+/// Marks the entity displaying the water surface of a chunk of a [`VoxelWorld`]
+#[derive(Component, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct VoxelWaterSurface;
+
+/// Measures how much of an entity is under the water of the [`VoxelWorld`]. See [`VoxelWater`].
+///
+/// The entity is treated as a box of `half_extents` around its global translation, and `submerged` is updated every
+/// frame in [`bevy::app::PostUpdate`]. A simple buoyancy force is `submerged * volume * density * gravity`, pointing
+/// up.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq)]
+#[reflect(Component)]
+pub struct VoxelSubmersion {
+    /// Half the size of the entity's box in global space
+    pub half_extents: Vec3,
+    /// The fraction of the box's volume that is under water, from 0 to 1
+    pub submerged: f32,
+}
+
+impl VoxelSubmersion {
+    /// Measures the submersion of a box of `half_extents` around the entity
+    pub fn new(half_extents: Vec3) -> Self {
+        Self {
+            half_extents,
+            submerged: 0.0,
+        }
+    }
+
+    /// Whether any part of the entity is under water
+    pub fn is_submerged(&self) -> bool {
+        self.submerged > 0.0
+    }
+}
+
+impl VoxelWorld {
+    /// Designates a voxel as water. See [`VoxelWater`].
+    pub fn with_water(mut self, water: VoxelWater) -> Self {
+        self.water = Some(water);
+        self
+    }
+
+    /// Whether the voxel at the global `position` is water
+    pub fn is_water(&self, position: IVec3) -> bool {
+        self.water.as_ref().is_some_and(|water| {
+            water.voxel != Voxel::EMPTY && self.get_voxel(position) == water.voxel
+        })
+    }
+
+    /// Whether the voxel at the global `position` is solid, meaning neither empty nor water
+    pub fn is_solid(&self, position: IVec3) -> bool {
+        self.get_voxel(position) != Voxel::EMPTY && !self.is_water(position)
+    }
+
+    /// The fraction of the volume of the box `aabb`, in global space, that is under water, from 0 to 1
+    pub fn submersion(&self, aabb: Aabb3d) -> f32 {
+        if self.water.is_none() {
+            return 0.0;
+        }
+        let min = Vec3::from(aabb.min) / self.voxel_size;
+        let max = Vec3::from(aabb.max) / self.voxel_size;
+        let volume = (max - min).max(Vec3::ZERO).element_product();
+        if volume <= 0.0 {
+            return 0.0;
+        }
+        let from = min.floor().as_ivec3();
+        let to = max.ceil().as_ivec3();
+        let mut submerged = 0.0;
+        for z in from.z..to.z {
+            for y in from.y..to.y {
+                for x in from.x..to.x {
+                    let cell = IVec3::new(x, y, z);
+                    if !self.is_water(cell) {
+                        continue;
+                    }
+                    let overlap = (max.min(cell.as_vec3() + Vec3::ONE) - min.max(cell.as_vec3()))
+                        .max(Vec3::ZERO);
+                    submerged += overlap.element_product();
+                }
+            }
+        }
+        (submerged / volume).clamp(0.0, 1.0)
+    }
+
+    /// The surface mesh of the water in the chunk at `coord` with the voxels `data`, or `None` if no water is open to
+    /// the air
+    pub(super) fn water_surface(&self, coord: IVec3, data: &VoxelData) -> Option<Mesh> {
+        let water = self.water.as_ref()?;
+        let raw = RawVoxel::from(water.voxel.clone());
+        if raw == RawVoxel::EMPTY {
+            return None;
+        }
+        let size = self.chunk_size.as_uvec3();
+        let leading_padding = UVec3::splat(data.padding() / 2);
+        let voxel_at = |position: UVec3| {
+            data.voxel_at_index(data.shape.linearize((position + leading_padding).into()) as usize)
+        };
+        // chunk meshes are centered on the chunk entity, so the surface is too
+        let half = self.chunk_size.as_vec3() * self.voxel_size * 0.5;
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let position = UVec3::new(x, y, z);
+                    if *voxel_at(position) != raw {
+                        continue;
+                    }
+                    let open = if y + 1 < size.y {
+                        *voxel_at(position + UVec3::Y) == RawVoxel::EMPTY
+                    } else {
+                        let global = coord * self.chunk_size + position.as_ivec3() + IVec3::Y;
+                        self.get_voxel(global) == Voxel::EMPTY
+                    };
+                    if !open {
+                        continue;
+                    }
+                    let height = (y as f32 + 1.0 - water.surface_offset) * self.voxel_size - half.y;
+                    let (x0, z0) = (
+                        x as f32 * self.voxel_size - half.x,
+                        z as f32 * self.voxel_size - half.z,
+                    );
+                    let (x1, z1) = (x0 + self.voxel_size, z0 + self.voxel_size);
+                    let start = positions.len() as u32;
+                    positions.extend_from_slice(&[
+                        [x0, height, z0],
+                        [x0, height, z1],
+                        [x1, height, z1],
+                        [x1, height, z0],
+                    ]);
+                    indices.extend_from_slice(&[
+                        start,
+                        start + 1,
+                        start + 2,
+                        start,
+                        start + 2,
+                        start + 3,
+                    ]);
+                }
+            }
+        }
+        if positions.is_empty() {
+            return None;
+        }
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        );
+        let vertex_count = positions.len();
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            VertexAttributeValues::Float32x3(vec![[0.0, 1.0, 0.0]; vertex_count]),
+        );
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            VertexAttributeValues::Float32x3(positions),
+        );
+        mesh.insert_indices(Indices::U32(indices));
+        Some(mesh)
+    }
+}
+
+/// Replaces the water surface of the chunk with `surface`, spawning or despawning its entity as needed
+pub(super) fn update_water_surface(
+    commands: &mut Commands,
+    state: &mut ChunkState,
+    surface: Option<Mesh>,
+    material: &Handle<StandardMaterial>,
+    meshes: &mut Assets<Mesh>,
+) {
+    match (surface, state.water.take(), state.entity) {
+        (Some(surface), Some((entity, mesh)), _) => {
+            meshes.insert(&mesh, surface);
+            state.water = Some((entity, mesh));
+        }
+        (Some(surface), None, Some(chunk)) => {
+            let mesh = meshes.add(surface);
+            let entity = commands
+                .spawn((
+                    PbrBundle {
+                        mesh: mesh.clone(),
+                        material: material.clone(),
+                        ..Default::default()
+                    },
+                    VoxelWaterSurface,
+                    Name::new("water surface"),
+                ))
+                .id();
+            commands.entity(chunk).add_child(entity);
+            state.water = Some((entity, mesh));
+        }
+        (None, Some((entity, mesh)), _) => {
+            commands.entity(entity).despawn_recursive();
+            meshes.remove(&mesh);
+        }
+        _ => (),
+    }
+}
+
+pub(crate) fn update_voxel_submersion(
+    world: Option<Res<VoxelWorld>>,
+    mut query: Query<(&GlobalTransform, &mut VoxelSubmersion)>,
+) {
+    let Some(world) = world else {
+        return;
+    };
+    for (transform, mut submersion) in query.iter_mut() {
+        let submerged = world.submersion(Aabb3d::new(
+            transform.translation(),
+            submersion.half_extents,
+        ));
+        if submersion.submerged != submerged {
+            submersion.submerged = submerged;
+        }
+    }
+}
@@ -0,0 +1,475 @@
+use bevy::{
+    asset::{Assets, Handle},
+    core::Name,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        system::{Commands, Res, ResMut, Resource},
+    },
+    math::{IVec3, UVec3, Vec3},
+    pbr::StandardMaterial,
+    prelude::{ReflectComponent, SpatialBundle},
+    reflect::Reflect,
+    render::mesh::Mesh,
+    render::view::Visibility,
+    transform::components::Transform,
+    utils::HashMap,
+};
+
+use crate::VoxelModelInstance;
+
+use super::{
+    collider::ColliderFilter,
+    lighting::VoxelWorldLighting,
+    modify::apply_model_mesh,
+    neighbors::border_cells,
+    region::RegionState,
+    shape::VoxelShapes,
+    streaming::VoxelWorldStreaming,
+    tint::VoxelTint,
+    water::{update_water_surface, VoxelWater},
+    RawVoxel, Voxel, VoxelContext, VoxelData, VoxelModel, VoxelPalette, VoxelQueryable, SIDES,
+};
+
+/// An unbounded world of voxels, split into chunk models that are created as voxels are written to them.
+///
+/// Voxels are addressed by global integer coordinates, so that block games can read and write voxels without keeping
+/// track of which chunk they belong to. Insert the world as a resource, and every chunk that has been written to since
+/// the last update is remeshed in [`bevy::app::PostUpdate`], with a new [`VoxelModel`] and an entity holding a
+/// [`VoxelModelInstance`] and a [`VoxelWorldChunk`] spawned for each new chunk. Voxel `(0, 0, 0)` spans from the
+/// origin to `(voxel_size, voxel_size, voxel_size)` in global space.
+///
+/// The world holds the only uncompressed copy of the voxels of each chunk. The [`VoxelModel`] of a chunk holds a
+/// [compressed](VoxelModel::compress) copy, for queries such as raycasts, which is replaced whenever the chunk is
+/// remeshed. The faces on the border between two chunks are culled against the voxels of the neighboring chunk, so they
+/// are only generated where the neighbor is empty, and chunks that are solid and surrounded by solid chunks, as found
+/// with [`VoxelData::chunk_occlusion`], are neither meshed nor drawn.
+#[derive(Resource)]
+pub struct VoxelWorld {
+    pub(super) context: Handle<VoxelContext>,
+    pub(super) chunk_size: IVec3,
+    pub(super) voxel_size: f32,
+    pub(super) chunks: HashMap<IVec3, ChunkState>,
+    pub(super) streaming: Option<VoxelWorldStreaming>,
+    pub(super) tint: Option<VoxelTint>,
+    pub(super) lighting: Option<VoxelWorldLighting>,
+    pub(super) regions: Option<RegionState>,
+    pub(super) water: Option<VoxelWater>,
+    pub(super) shapes: VoxelShapes,
+    pub(super) collider_filter: ColliderFilter,
+}
+
+pub(super) struct ChunkState {
+    pub(super) data: VoxelData,
+    pub(super) model: Option<Handle<VoxelModel>>,
+    pub(super) entity: Option<Entity>,
+    pub(super) dirty: bool,
+    /// True if the chunk has been edited since it was last saved to its region file
+    pub(super) unsaved: bool,
+    /// The entity displaying the chunk's water surface, and its mesh
+    pub(super) water: Option<(Entity, Handle<Mesh>)>,
+    /// True if the chunk was solid and surrounded by solid chunks when it was last updated, so that it has no visible
+    /// faces and is neither meshed nor drawn
+    pub(super) enclosed: bool,
+}
+
+/// Marks an entity spawned by the [`VoxelWorld`] to display one of its chunks
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct VoxelWorldChunk {
+    /// The coordinate of the chunk, in units of the world's chunk size
+    pub coord: IVec3,
+}
+
+impl VoxelWorld {
+    /// Creates an empty world
+    ///
+    /// ### Arguments
+    /// * `context` - the [`VoxelContext`] whose palette the chunks use. This must be loaded before the first update.
+    /// * `chunk_size` - the size of each chunk in voxels
+    /// * `voxel_size` - the size of each voxel in global space
+    pub fn new(context: Handle<VoxelContext>, chunk_size: UVec3, voxel_size: f32) -> Self {
+        Self {
+            context,
+            chunk_size: chunk_size.max(UVec3::ONE).as_ivec3(),
+            voxel_size,
+            chunks: HashMap::new(),
+            streaming: None,
+            tint: None,
+            lighting: None,
+            regions: None,
+            water: None,
+            shapes: VoxelShapes::default(),
+            collider_filter: ColliderFilter::default(),
+        }
+    }
+
+    /// Generates the chunks around each [`crate::VoxelWorldViewer`] as it moves, and evicts the chunks that are left
+    /// behind. See [`VoxelWorldStreaming`].
+    pub fn with_streaming(mut self, streaming: VoxelWorldStreaming) -> Self {
+        self.streaming = Some(streaming);
+        self
+    }
+
+    /// Tints the chunks by global position, for instance to shade terrain by biome. The tint is evaluated at the
+    /// global position of each vertex. See [`VoxelTint`].
+    pub fn with_tint(mut self, tint: VoxelTint) -> Self {
+        self.tint = Some(tint);
+        self
+    }
+
+    /// Draws the voxels of some palette indices as ramps, slabs or stairs, for smoother walkable terrain. See
+    /// [`VoxelShapes`].
+    pub fn with_shapes(mut self, shapes: VoxelShapes) -> Self {
+        self.shapes = shapes;
+        self
+    }
+
+    /// Sets which voxels block movement with [`VoxelWorld::move_and_slide`] and generate colliders, and their collision
+    /// groups. See [`ColliderFilter`].
+    pub fn with_collider_filter(mut self, collider_filter: ColliderFilter) -> Self {
+        self.collider_filter = collider_filter;
+        self
+    }
+
+    /// The size of each chunk in voxels
+    pub fn chunk_size(&self) -> UVec3 {
+        self.chunk_size.as_uvec3()
+    }
+
+    /// The size of each voxel in global space
+    pub fn voxel_size(&self) -> f32 {
+        self.voxel_size
+    }
+
+    /// Returns the voxel at the global `position`, which is [`Voxel::EMPTY`] in chunks that haven't been written to
+    pub fn get_voxel(&self, position: IVec3) -> Voxel {
+        let (chunk, local) = self.split(position);
+        self.chunks
+            .get(&chunk)
+            .and_then(|state| state.data.get_voxel_at_point(local).ok())
+            .unwrap_or(Voxel::EMPTY)
+    }
+
+    /// Writes the `voxel` at the global `position`, creating the chunk that contains it if it doesn't exist yet
+    pub fn set_voxel(&mut self, position: IVec3, voxel: Voxel) {
+        let (chunk, local) = self.split(position);
+        if voxel == Voxel::EMPTY && !self.chunks.contains_key(&chunk) {
+            return;
+        }
+        if !self.chunks.contains_key(&chunk) {
+            let data = self.chunk_data(chunk);
+            self.chunks.insert(
+                chunk,
+                ChunkState {
+                    data,
+                    model: None,
+                    entity: None,
+                    dirty: false,
+                    unsaved: false,
+                    water: None,
+                    enclosed: false,
+                },
+            );
+        }
+        let Some(state) = self.chunks.get_mut(&chunk) else {
+            return;
+        };
+        if state.data.get_voxel_at_point(local).as_ref() == Ok(&voxel) {
+            return;
+        }
+        state.data.set_voxel(voxel, local.as_uvec3());
+        state.dirty = true;
+        state.unsaved = true;
+        // the faces on the borders of the neighboring chunks, and the water surface of the chunk below, depend on the
+        // voxels on the sides of this chunk
+        for side in SIDES {
+            let on_side = (side.cmplt(IVec3::ZERO) & local.cmpeq(IVec3::ZERO))
+                | (side.cmpgt(IVec3::ZERO) & local.cmpeq(self.chunk_size - IVec3::ONE));
+            if on_side.any() {
+                if let Some(neighbor) = self.chunks.get_mut(&(chunk + side)) {
+                    neighbor.dirty = true;
+                }
+            }
+        }
+    }
+
+    /// Writes `voxel` to every position in the box from `min` to `max` (exclusive), for instance to fill terrain
+    pub fn fill(&mut self, min: IVec3, max: IVec3, voxel: Voxel) {
+        for z in min.z..max.z {
+            for y in min.y..max.y {
+                for x in min.x..max.x {
+                    self.set_voxel(IVec3::new(x, y, z), voxel.clone());
+                }
+            }
+        }
+    }
+
+    /// The coordinate of the chunk containing the global voxel `position`
+    pub fn chunk_coord(&self, position: IVec3) -> IVec3 {
+        self.split(position).0
+    }
+
+    /// The coordinates of every chunk that has been created
+    pub fn chunks(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    /// The entity displaying the chunk at `coord`, once it has been spawned
+    pub fn chunk_entity(&self, coord: IVec3) -> Option<Entity> {
+        self.chunks.get(&coord).and_then(|state| state.entity)
+    }
+
+    /// The model of the chunk at `coord`, once it has been created
+    pub fn chunk_model(&self, coord: IVec3) -> Option<&Handle<VoxelModel>> {
+        self.chunks
+            .get(&coord)
+            .and_then(|state| state.model.as_ref())
+    }
+
+    /// The global voxel position containing the global `point`
+    pub fn point_to_voxel(&self, point: Vec3) -> IVec3 {
+        (point / self.voxel_size).floor().as_ivec3()
+    }
+
+    /// The global point at the center of the voxel at `position`
+    pub fn voxel_to_point(&self, position: IVec3) -> Vec3 {
+        (position.as_vec3() + Vec3::splat(0.5)) * self.voxel_size
+    }
+
+    /// Creates the empty voxel data of the chunk at `coord`
+    pub(super) fn chunk_data(&self, coord: IVec3) -> VoxelData {
+        let mut data = VoxelData::new(self.chunk_size.as_uvec3(), true, self.voxel_size)
+            .with_shapes(self.shapes.clone())
+            .with_collider_filter(self.collider_filter.clone());
+        data.water = self
+            .water
+            .as_ref()
+            .map(|water| water.voxel.clone().into())
+            .filter(|water| *water != RawVoxel::EMPTY);
+        match self.tint.as_ref() {
+            Some(tint) => data.with_tint(tint.clone().with_origin(chunk_translation(
+                coord,
+                self.chunk_size,
+                self.voxel_size,
+            ))),
+            None => data,
+        }
+    }
+
+    /// Marks the chunks next to the chunk at `coord` to be remeshed, after it has been added or removed, as the faces on
+    /// their borders are culled against its voxels
+    pub(super) fn mark_neighbors_dirty(&mut self, coord: IVec3) {
+        for side in SIDES {
+            if let Some(neighbor) = self.chunks.get_mut(&(coord + side)) {
+                neighbor.dirty = true;
+            }
+        }
+    }
+
+    /// Whether the chunk at `coord` exists and every voxel in it is an opaque cube
+    fn is_chunk_solid(&self, coord: IVec3, palette: &VoxelPalette) -> bool {
+        self.chunks.get(&coord).is_some_and(|state| {
+            let flags = state
+                .data
+                .chunk_occlusion(palette, self.chunk_size.max_element() as u32);
+            !flags.is_empty() && flags.iter().all(|flags| flags.solid)
+        })
+    }
+
+    /// Whether any of the chunks next to the chunk at `coord` exist
+    pub(super) fn has_neighbors(&self, coord: IVec3) -> bool {
+        SIDES
+            .iter()
+            .any(|side| self.chunks.contains_key(&(coord + *side)))
+    }
+
+    /// Meshes the chunk at `coord` with the voxels `data`, culling the faces on its borders against the voxels of the
+    /// neighboring chunks
+    pub(super) fn mesh_chunk(
+        &self,
+        coord: IVec3,
+        data: &VoxelData,
+        palette: &VoxelPalette,
+    ) -> (Mesh, Option<f32>) {
+        let mut border: Vec<(IVec3, RawVoxel)> = Vec::new();
+        for side in SIDES {
+            let Some(neighbor) = self.chunks.get(&(coord + side)) else {
+                continue;
+            };
+            for cell in border_cells(self.chunk_size, side) {
+                let Ok(voxel) = neighbor
+                    .data
+                    .get_voxel_at_point(cell.rem_euclid(self.chunk_size))
+                else {
+                    continue;
+                };
+                let voxel = RawVoxel::from(voxel);
+                if voxel != RawVoxel::EMPTY {
+                    border.push((cell, voxel));
+                }
+            }
+        }
+        data.remesh_with_border(palette, &border, [true; 6])
+    }
+
+    fn split(&self, position: IVec3) -> (IVec3, IVec3) {
+        (
+            position.div_euclid(self.chunk_size),
+            position.rem_euclid(self.chunk_size),
+        )
+    }
+}
+
+pub(crate) fn update_voxel_world(
+    mut commands: Commands,
+    world: Option<ResMut<VoxelWorld>>,
+    mut models: ResMut<Assets<VoxelModel>>,
+    contexts: Res<Assets<VoxelContext>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Some(mut world) = world else {
+        return;
+    };
+    if !world.is_changed() && !world.chunks.values().any(|state| state.dirty) {
+        return;
+    }
+    let Some(context) = contexts.get(&world.context) else {
+        return;
+    };
+    let context_handle = world.context.clone();
+    let chunk_size = world.chunk_size;
+    let voxel_size = world.voxel_size;
+    world.bypass_change_detection().relight(&context.palette);
+    let mut water_surfaces: HashMap<IVec3, Option<Mesh>> = HashMap::new();
+    if world.water.is_some() {
+        for (coord, state) in world.chunks.iter().filter(|(_, state)| state.dirty) {
+            water_surfaces.insert(*coord, world.water_surface(*coord, &state.data));
+        }
+    }
+    let water_material = world
+        .water
+        .as_ref()
+        .map(|water| water.material.clone())
+        .unwrap_or_default();
+    // a chunk is enclosed if it and its neighbors are solid, in which case its faces are all culled against its
+    // neighbors, so it isn't meshed
+    let mut solid: HashMap<IVec3, bool> = HashMap::new();
+    let mut chunk_meshes: HashMap<IVec3, Option<(Mesh, Option<f32>)>> = HashMap::new();
+    for (coord, state) in world.chunks.iter().filter(|(_, state)| state.dirty) {
+        let enclosed = std::iter::once(IVec3::ZERO).chain(SIDES).all(|offset| {
+            *solid
+                .entry(*coord + offset)
+                .or_insert_with(|| world.is_chunk_solid(*coord + offset, &context.palette))
+        });
+        let mesh = (!enclosed).then(|| world.mesh_chunk(*coord, &state.data, &context.palette));
+        chunk_meshes.insert(*coord, mesh);
+    }
+    for ((coord, state), mesh) in world
+        .bypass_change_detection()
+        .chunks
+        .iter_mut()
+        .filter_map(|(coord, state)| {
+            chunk_meshes
+                .remove(coord)
+                .map(|mesh| ((coord, state), mesh))
+        })
+    {
+        state.dirty = false;
+        let enclosed = mesh.is_none();
+        if enclosed != state.enclosed {
+            if let Some(entity) = state.entity {
+                commands.entity(entity).insert(if enclosed {
+                    Visibility::Hidden
+                } else {
+                    Visibility::Inherited
+                });
+            }
+            state.enclosed = enclosed;
+        }
+        if let Some((mesh, average_ior)) = mesh {
+            if let Some(model) = state.model.as_ref().and_then(|model| models.get_mut(model)) {
+                model.data = state.data.compressed_copy();
+                apply_model_mesh(
+                    model,
+                    mesh,
+                    average_ior,
+                    &mut meshes,
+                    &mut materials,
+                    context.opaque_material.clone(),
+                    context.transmissive_material.clone(),
+                );
+            } else {
+                spawn_chunk(
+                    &mut commands,
+                    *coord,
+                    state,
+                    mesh,
+                    average_ior,
+                    (context, &context_handle),
+                    chunk_size,
+                    voxel_size,
+                    (&mut models, &mut meshes, &mut materials),
+                );
+            }
+        }
+        if let Some(surface) = water_surfaces.remove(coord) {
+            update_water_surface(&mut commands, state, surface, &water_material, &mut meshes);
+        }
+    }
+}
+
+/// Creates the model of a chunk from its mesh, and spawns an entity to display it
+#[allow(clippy::too_many_arguments)]
+pub(super) fn spawn_chunk(
+    commands: &mut Commands,
+    coord: IVec3,
+    state: &mut ChunkState,
+    mesh: Mesh,
+    average_ior: Option<f32>,
+    (context, context_handle): (&VoxelContext, &Handle<VoxelContext>),
+    chunk_size: IVec3,
+    voxel_size: f32,
+    (models, meshes, materials): (
+        &mut Assets<VoxelModel>,
+        &mut Assets<Mesh>,
+        &mut Assets<StandardMaterial>,
+    ),
+) -> Option<Entity> {
+    let material = context.material_for(average_ior, &state.data, materials)?;
+    let name = format!("chunk {} {} {}", coord.x, coord.y, coord.z);
+    let mesh = meshes.add(mesh);
+    let model = models.add(VoxelModel {
+        name: name.clone(),
+        data: state.data.compressed_copy(),
+        mesh: mesh.clone(),
+        material: material.clone(),
+        has_translucency: average_ior.is_some(),
+        mesh_pending: false,
+        brick_map: None,
+    });
+    let entity = commands
+        .spawn((
+            VoxelModelInstance {
+                model: model.clone(),
+                context: context_handle.clone(),
+            },
+            VoxelWorldChunk { coord },
+            Name::new(name),
+            mesh,
+            material,
+            SpatialBundle::from_transform(Transform::from_translation(chunk_translation(
+                coord, chunk_size, voxel_size,
+            ))),
+        ))
+        .id();
+    state.model = Some(model);
+    state.entity = Some(entity);
+    Some(entity)
+}
+
+/// The global translation of the entity displaying the chunk at `coord`
+fn chunk_translation(coord: IVec3, chunk_size: IVec3, voxel_size: f32) -> Vec3 {
+    ((coord * chunk_size).as_vec3() + chunk_size.as_vec3() * 0.5) * voxel_size
+}
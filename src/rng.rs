@@ -0,0 +1,64 @@
+use std::ops::Range;
+
+use bevy::ecs::system::Resource;
+
+/// A small, seedable pseudo-random number generator used by the crate's procedural helpers.
+///
+/// The sequence produced for a given seed is stable across platforms and crate versions, so replays and lockstep
+/// multiplayer remain deterministic. Helpers that need randomness accept a `&mut VoxelRng`; the plugin inserts one as a
+/// resource that can be used as the default source, and reseeded with [`VoxelRng::from_seed`].
+#[derive(Resource, Clone, Debug, PartialEq, Eq)]
+pub struct VoxelRng {
+    state: u64,
+}
+
+impl Default for VoxelRng {
+    fn default() -> Self {
+        Self::from_seed(0x5EED)
+    }
+}
+
+impl VoxelRng {
+    /// Create a new generator from the supplied seed
+    pub fn from_seed(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns the next 64 bits in the sequence (SplitMix64)
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns the next 32 bits in the sequence
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns a float in the range 0.0 (inclusive) to 1.0 (exclusive)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Returns an integer in the range 0 (inclusive) to `bound` (exclusive). Returns 0 if `bound` is 0.
+    pub fn below(&mut self, bound: u32) -> u32 {
+        if bound == 0 {
+            return 0;
+        }
+        ((self.next_u32() as u64 * bound as u64) >> 32) as u32
+    }
+
+    /// Returns a float within the `range`
+    pub fn range_f32(&mut self, range: Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next_f32()
+    }
+
+    /// Returns a new generator seeded from this one, so that an independent stream can be handed to a helper without
+    /// the helper's consumption affecting the rest of this sequence.
+    pub fn fork(&mut self) -> Self {
+        Self::from_seed(self.next_u64())
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::anyhow;
+use bevy::{ecs::system::Resource, math::IVec3, utils::HashMap};
+
+use crate::{
+    load::{
+        chunks::{palette_index_map, palette_notes},
+        model_names,
+        validate::validate_file,
+        VoxLoaderError,
+    },
+    VoxLoaderSettings, Voxel, VoxelData, VoxelPalette, VoxelQueryable, VoxelRegionMode,
+};
+
+/// Identifies a model held by a [`VoxelWorldServer`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VoxelModelId(pub u32);
+
+/// A single voxel that was changed on a [`VoxelWorldServer`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelChange {
+    /// The model that was changed
+    pub model: VoxelModelId,
+    /// The position of the voxel, in the model's voxel space
+    pub position: IVec3,
+    /// The new value of the voxel
+    pub voxel: Voxel,
+}
+
+struct ServerModel {
+    name: String,
+    data: VoxelData,
+    palette: VoxelPalette,
+}
+
+/// An authoritative store of voxel models, for running on a headless server.
+///
+/// Unlike the [`crate::VoxScenePlugin`], which stores models as assets and remeshes them whenever they change, the
+/// server only holds the voxel data, and refers to models by plain [`VoxelModelId`]s rather than asset handles. Every
+/// edit made to the server is recorded as a list of [`VoxelChange`]s, which can be drained with
+/// [`VoxelWorldServer::drain_changes`] and sent to clients over a network layer. Clients apply them to their own copy of
+/// the models with [`crate::ModifyVoxelCommandsExt::modify_voxel_model`], or to another server with
+/// [`VoxelWorldServer::apply_changes`].
+#[derive(Resource, Default)]
+pub struct VoxelWorldServer {
+    models: HashMap<VoxelModelId, ServerModel>,
+    next_id: u32,
+    changes: Vec<VoxelChange>,
+}
+
+impl VoxelWorldServer {
+    /// Loads every model in the `.vox` file `bytes`, returning the ids of the new models in the order they appear in
+    /// the file
+    pub fn load_vox_bytes(
+        &mut self,
+        bytes: &[u8],
+        settings: &VoxLoaderSettings,
+    ) -> Result<Vec<VoxelModelId>, VoxLoaderError> {
+        let file = dot_vox::load_bytes(bytes).map_err(|error| anyhow!(error))?;
+        validate_file(&file, "<bytes>")?;
+        let palette = settings
+            .create_palette(&file)
+            .with_notes(palette_notes(bytes))
+            .with_display_order(palette_index_map(bytes));
+        Ok(model_names(&file, settings.duplicate_names)
+            .into_iter()
+            .zip(file.models.iter())
+            .map(|(name, model)| {
+                let data = settings.create_data(model);
+                self.insert_model(name, data, palette.clone())
+            })
+            .collect())
+    }
+
+    /// Adds a model to the server
+    pub fn insert_model(
+        &mut self,
+        name: impl Into<String>,
+        data: VoxelData,
+        palette: VoxelPalette,
+    ) -> VoxelModelId {
+        let id = VoxelModelId(self.next_id);
+        self.next_id += 1;
+        self.models.insert(
+            id,
+            ServerModel {
+                name: name.into(),
+                data,
+                palette,
+            },
+        );
+        id
+    }
+
+    /// Removes a model from the server, returning its data if it existed
+    pub fn remove_model(&mut self, id: VoxelModelId) -> Option<VoxelData> {
+        self.models.remove(&id).map(|model| model.data)
+    }
+
+    /// Returns the id of the first model with the supplied name
+    pub fn find_model(&self, name: &str) -> Option<VoxelModelId> {
+        self.models
+            .iter()
+            .filter(|(_, model)| model.name == name)
+            .map(|(id, _)| *id)
+            .min()
+    }
+
+    /// Iterates over the id and name of every model on the server
+    pub fn models(&self) -> impl Iterator<Item = (VoxelModelId, &str)> {
+        self.models
+            .iter()
+            .map(|(id, model)| (*id, model.name.as_str()))
+    }
+
+    /// Returns the voxel data of the model, which can be queried with the methods of [`VoxelQueryable`]
+    pub fn model(&self, id: VoxelModelId) -> Option<&VoxelData> {
+        self.models.get(&id).map(|model| &model.data)
+    }
+
+    /// Returns the palette of the model
+    pub fn palette(&self, id: VoxelModelId) -> Option<&VoxelPalette> {
+        self.models.get(&id).map(|model| &model.palette)
+    }
+
+    /// Returns the voxel at the `position` in the model, or `None` if the model doesn't exist or the position is out
+    /// of bounds
+    pub fn get_voxel(&self, id: VoxelModelId, position: IVec3) -> Option<Voxel> {
+        self.model(id)?.get_voxel_at_point(position).ok()
+    }
+
+    /// Writes the `voxel` at the `position` in the model. Returns false if the model doesn't exist or the position is
+    /// out of bounds.
+    pub fn set_voxel(&mut self, id: VoxelModelId, position: IVec3, voxel: Voxel) -> bool {
+        let Some(model) = self.models.get_mut(&id) else {
+            return false;
+        };
+        let Ok(point) = model.data.point_in_model(position) else {
+            return false;
+        };
+        if model.data.get_voxel_at_point(position).as_ref() != Ok(&voxel) {
+            model.data.set_voxel(voxel.clone(), point);
+            self.changes.push(VoxelChange {
+                model: id,
+                position,
+                voxel,
+            });
+        }
+        true
+    }
+
+    /// Run the `modify` closure against every voxel within the `region` of the model. See
+    /// [`crate::ModifyVoxelCommandsExt::modify_voxel_model`] for a description of the closure's arguments.
+    pub fn modify_voxels<F: Fn(IVec3, &Voxel, &dyn VoxelQueryable) -> Voxel>(
+        &mut self,
+        id: VoxelModelId,
+        region: VoxelRegionMode,
+        modify: F,
+    ) {
+        let Some(model) = self.models.get_mut(&id) else {
+            return;
+        };
+        let region = region.clamped(model.data.size());
+        let mut updates: Vec<(IVec3, Voxel)> = Vec::new();
+        for x in 0..region.size.x {
+            for y in 0..region.size.y {
+                for z in 0..region.size.z {
+                    let position = region.origin + IVec3::new(x, y, z);
+                    let Ok(source) = model.data.get_voxel_at_point(position) else {
+                        continue;
+                    };
+                    if model.data.is_protected(position, &source) {
+                        continue;
+                    }
+                    let voxel = modify(position, &source, &model.data);
+                    if voxel != source {
+                        updates.push((position, voxel));
+                    }
+                }
+            }
+        }
+        for (position, voxel) in updates {
+            model.data.set_voxel(voxel.clone(), position.as_uvec3());
+            self.changes.push(VoxelChange {
+                model: id,
+                position,
+                voxel,
+            });
+        }
+    }
+
+    /// Applies changes drained from another server
+    pub fn apply_changes(&mut self, changes: impl IntoIterator<Item = VoxelChange>) {
+        for change in changes {
+            self.set_voxel(change.model, change.position, change.voxel);
+        }
+    }
+
+    /// Returns true if there are changes that haven't been drained yet
+    pub fn has_changes(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    /// Removes and returns every change made since the last time the changes were drained, in the order they were made
+    pub fn drain_changes(&mut self) -> Vec<VoxelChange> {
+        std::mem::take(&mut self.changes)
+    }
+}
@@ -0,0 +1,212 @@
+//! Utilities for writing regression tests against `.vox` assets.
+//!
+//! The functions in this module mesh `.vox` files headlessly, without an [`bevy::app::App`], asset server or render
+//! device, and expose the resulting buffers as plain data that can be compared against previously recorded "golden"
+//! snapshots.
+//!
+//! ```no_run
+//! use bevy_vox_scene::{test_utils::snapshot_vox_bytes, VoxLoaderSettings};
+//!
+//! let bytes = std::fs::read("assets/study.vox").unwrap();
+//! let golden = snapshot_vox_bytes(&bytes, &VoxLoaderSettings::default()).unwrap();
+//! let settings = VoxLoaderSettings {
+//!     voxel_size: 0.5,
+//!     ..Default::default()
+//! };
+//! let snapshot = snapshot_vox_bytes(&bytes, &settings).unwrap();
+//! let desk = snapshot.model("workstation/desk").unwrap();
+//! assert_eq!(desk.indices, golden.model("workstation/desk").unwrap().indices);
+//! ```
+
+use anyhow::anyhow;
+use bevy::{
+    asset::Assets,
+    math::IVec3,
+    pbr::StandardMaterial,
+    render::{
+        mesh::{Indices, Mesh, VertexAttributeValues},
+        texture::Image,
+    },
+};
+
+use block_mesh::{
+    greedy_quads, visible_block_faces, GreedyQuadsBuffer, UnitQuadBuffer, RIGHT_HANDED_Y_UP_CONFIG,
+};
+use ndshape::Shape;
+
+use crate::{
+    load::{model_names, validate::validate_file, VoxLoaderError},
+    model::{bitmask::VoxelOccupancy, mesh::mesh_model},
+    VoxLoaderSettings, VoxelData, VoxelPalette,
+};
+
+/// The meshes generated for every model in a `.vox` file
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxSnapshot {
+    /// The snapshot of each model, in the order the models appear in the file
+    pub models: Vec<ModelSnapshot>,
+}
+
+impl VoxSnapshot {
+    /// Returns the snapshot of the model with the supplied name, as it would be labeled by the asset loader
+    pub fn model(&self, name: &str) -> Option<&ModelSnapshot> {
+        self.models.iter().find(|model| model.name == name)
+    }
+}
+
+/// The vertex and index buffers of the mesh generated for a single model
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelSnapshot {
+    /// The name of the model
+    pub name: String,
+    /// The size of the model, in voxels
+    pub size: IVec3,
+    /// Vertex positions
+    pub positions: Vec<[f32; 3]>,
+    /// Vertex normals
+    pub normals: Vec<[f32; 3]>,
+    /// Vertex UVs into the palette textures
+    pub uvs: Vec<[f32; 2]>,
+    /// Triangle list indices
+    pub indices: Vec<u32>,
+}
+
+impl ModelSnapshot {
+    /// Captures the buffers of a mesh generated by this crate
+    pub fn from_mesh(name: impl Into<String>, size: IVec3, mesh: &Mesh) -> Self {
+        Self {
+            name: name.into(),
+            size,
+            positions: match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+                Some(VertexAttributeValues::Float32x3(values)) => values.clone(),
+                _ => Vec::new(),
+            },
+            normals: match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+                Some(VertexAttributeValues::Float32x3(values)) => values.clone(),
+                _ => Vec::new(),
+            },
+            uvs: match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+                Some(VertexAttributeValues::Float32x2(values)) => values.clone(),
+                _ => Vec::new(),
+            },
+            indices: match mesh.indices() {
+                Some(Indices::U32(indices)) => indices.clone(),
+                Some(Indices::U16(indices)) => indices.iter().map(|i| *i as u32).collect(),
+                None => Vec::new(),
+            },
+        }
+    }
+
+    /// The number of vertices in the mesh
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// The number of triangles in the mesh
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Returns true if the two snapshots have identical topology, and all of their vertex attributes are within
+    /// `epsilon` of each other
+    pub fn approx_eq(&self, other: &ModelSnapshot, epsilon: f32) -> bool {
+        fn close<const N: usize>(a: &[[f32; N]], b: &[[f32; N]], epsilon: f32) -> bool {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|(a, b)| a.iter().zip(b).all(|(a, b)| (a - b).abs() <= epsilon))
+        }
+        self.name == other.name
+            && self.size == other.size
+            && self.indices == other.indices
+            && close(&self.positions, &other.positions, epsilon)
+            && close(&self.normals, &other.normals, epsilon)
+            && close(&self.uvs, &other.uvs, epsilon)
+    }
+}
+
+/// Parses and meshes every model in the `.vox` file `bytes` with the supplied `settings`, producing the same meshes as
+/// the asset loader. Unlike the loader, it parses every model of the file at once, and adds none of the materials,
+/// colliders, LODs or other assets that the loader adds alongside the meshes.
+pub fn snapshot_vox_bytes(
+    bytes: &[u8],
+    settings: &VoxLoaderSettings,
+) -> Result<VoxSnapshot, VoxLoaderError> {
+    let file = dot_vox::load_bytes(bytes).map_err(|error| anyhow!(error))?;
+    validate_file(&file, "<bytes>")?;
+    let palette = settings.create_palette(&file);
+    let models = model_names(&file, settings.duplicate_names)
+        .into_iter()
+        .zip(file.models.iter())
+        .map(|(name, model)| {
+            let data = settings.create_data(model);
+            let (visible_voxels, _) = data.visible_voxels(&palette.indices_of_refraction);
+            let mesh = mesh_model(&visible_voxels, &data, &palette);
+            ModelSnapshot::from_mesh(name, data._size(), &mesh)
+        })
+        .collect();
+    Ok(VoxSnapshot { models })
+}
+
+/// Meshes `data` with the colors of `palette` exactly as the asset loader and voxel edits would, for benchmarking the
+/// mesher against a model that has already been built.
+pub fn mesh_voxel_data(data: &VoxelData, palette: &VoxelPalette) -> Mesh {
+    data.remesh(palette).0
+}
+
+/// The number of faces of the cube voxels of `data` that aren't covered by their neighbors, found with the bitmask
+/// visibility pass used by the mesher.
+pub fn count_visible_faces(data: &VoxelData, palette: &VoxelPalette) -> usize {
+    let (visible_voxels, _) = data.visible_voxels(&palette.indices_of_refraction);
+    let mut buffer = UnitQuadBuffer::new();
+    VoxelOccupancy::new(&visible_voxels, &data.shape).visible_faces(&mut buffer);
+    buffer.groups.iter().map(Vec::len).sum()
+}
+
+/// The same as [`count_visible_faces`], but testing the neighbors of one voxel at a time, as a baseline for the
+/// bitmask pass.
+pub fn count_visible_faces_scalar(data: &VoxelData, palette: &VoxelPalette) -> usize {
+    let (visible_voxels, _) = data.visible_voxels(&palette.indices_of_refraction);
+    let mut buffer = UnitQuadBuffer::new();
+    visible_block_faces(
+        &visible_voxels,
+        &data.shape,
+        [0; 3],
+        data.shape.as_array().map(|x| x - 1),
+        &RIGHT_HANDED_Y_UP_CONFIG.faces,
+        &mut buffer,
+    );
+    buffer.groups.iter().map(Vec::len).sum()
+}
+
+/// The number of quads that the greedy mesher merges the faces of the cube voxels of `data` into, found with the
+/// bitmask visibility pass used by the mesher.
+pub fn count_greedy_quads(data: &VoxelData, palette: &VoxelPalette) -> usize {
+    let (visible_voxels, _) = data.visible_voxels(&palette.indices_of_refraction);
+    VoxelOccupancy::new(&visible_voxels, &data.shape)
+        .greedy_quads(&visible_voxels, &data.shape)
+        .iter()
+        .map(Vec::len)
+        .sum()
+}
+
+/// The same as [`count_greedy_quads`], but with [`block_mesh::greedy_quads`], which tests the neighbors of one voxel at
+/// a time, as a baseline for the bitmask mesher.
+pub fn count_greedy_quads_scalar(data: &VoxelData, palette: &VoxelPalette) -> usize {
+    let (visible_voxels, _) = data.visible_voxels(&palette.indices_of_refraction);
+    let mut buffer = GreedyQuadsBuffer::new(visible_voxels.len());
+    greedy_quads(
+        &visible_voxels,
+        &data.shape,
+        [0; 3],
+        data.shape.as_array().map(|x| x - 1),
+        &RIGHT_HANDED_Y_UP_CONFIG.faces,
+        &mut buffer,
+    );
+    buffer.quads.groups.iter().map(Vec::len).sum()
+}
+
+/// Bakes `palette` into the textures of its material, adding them to `images`, exactly as the asset loader would
+pub fn bake_palette(palette: &VoxelPalette, images: &mut Assets<Image>) -> StandardMaterial {
+    palette.create_material(images)
+}
@@ -1,5 +1,7 @@
 use std::f32::consts::FRAC_PI_2;
 
+use ndshape::Shape;
+
 use super::*;
 
 #[cfg(feature = "modify_voxels")]
@@ -9,15 +11,20 @@ use crate::{model::RawVoxel, VoxScenePlugin, VoxelModelInstance};
 use bevy::{
     app::App,
     asset::{AssetApp, AssetPlugin, AssetServer, Assets, Handle, LoadState},
+    color::{Color, ColorToComponents},
     core::Name,
     hierarchy::Children,
-    math::{IVec3, Quat, UVec3, Vec3, Vec3A},
+    math::{bounding::Aabb3d, IVec3, Quat, UVec3, Vec2, Vec3, Vec3A, Vec4},
     pbr::StandardMaterial,
     prelude::{
-        GlobalTransform, HierarchyPlugin, InheritedVisibility, OnAdd, Query, Transform, Trigger,
-        ViewVisibility, Visibility,
+        AlphaMode, GlobalTransform, HierarchyPlugin, InheritedVisibility, OnAdd, Query, Transform,
+        Trigger, ViewVisibility, Visibility,
+    },
+    render::{
+        mesh::{Mesh, VertexAttributeValues},
+        primitives::Aabb,
+        texture::ImagePlugin,
     },
-    render::{mesh::Mesh, texture::ImagePlugin},
     scene::{Scene, SceneBundle, ScenePlugin},
     utils::hashbrown::HashSet,
     MinimalPlugins,
@@ -40,6 +47,22 @@ fn test_raw_voxel_conversion() {
     );
 }
 
+#[test]
+fn test_rng_is_deterministic() {
+    let mut a = VoxelRng::from_seed(42);
+    let mut b = VoxelRng::from_seed(42);
+    let sequence_a: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+    let sequence_b: Vec<u64> = (0..8).map(|_| b.next_u64()).collect();
+    assert_eq!(sequence_a, sequence_b, "Same seed produces same sequence");
+    let mut c = VoxelRng::from_seed(43);
+    assert_ne!(c.next_u64(), sequence_a[0], "Different seeds diverge");
+    for _ in 0..100 {
+        let value = a.next_f32();
+        assert!((0.0..1.0).contains(&value));
+        assert!(a.below(5) < 5);
+    }
+}
+
 #[async_std::test]
 async fn test_load_scene() {
     let mut app = App::new();
@@ -58,6 +81,50 @@ async fn test_load_scene() {
     );
 }
 
+#[async_std::test]
+async fn test_load_progress() {
+    use crate::VoxelLoadProgress;
+    let mut app = App::new();
+    let handle = setup_and_load_voxel_scene(&mut app, "test.vox#outer-group").await;
+    app.update();
+    let progress = app.world().resource::<VoxelLoadProgress>();
+    let file = progress.get_for(&handle).expect("progress of test.vox");
+    assert_eq!(Some(file), progress.get("test.vox"));
+    assert!(file.finished);
+    assert_eq!(file.fraction(), 1.0);
+    assert_eq!(file.bytes_read, include_bytes!("../assets/test.vox").len());
+    assert_eq!(file.total_bytes, Some(file.bytes_read));
+    assert_eq!(file.total_models, 3);
+    assert_eq!(file.models_meshed, 3);
+    assert!(progress.get("study.vox").is_none());
+}
+
+#[test]
+fn test_load_progress_forgets_old_files() {
+    use crate::VoxelLoadProgress;
+    use std::path::PathBuf;
+    let progress = VoxelLoadProgress::default();
+    let path = |index: usize| PathBuf::from(format!("level-{index}.vox"));
+    for index in 0..100 {
+        progress.start(&path(index));
+        progress.finish(&path(index));
+    }
+    assert!(
+        progress.get(path(0)).is_none(),
+        "the oldest files are forgotten"
+    );
+    assert!(progress.get(path(99)).is_some_and(|file| file.finished));
+    // reloading a file moves it to the back of the queue
+    progress.start(&path(40));
+    progress.finish(&path(40));
+    for index in 100..163 {
+        progress.start(&path(index));
+        progress.finish(&path(index));
+    }
+    assert!(progress.get(path(41)).is_none());
+    assert!(progress.get(path(40)).is_some());
+}
+
 #[async_std::test]
 async fn test_transmissive_mat() {
     let mut app = App::new();
@@ -225,13 +292,391 @@ async fn test_spawn_system() {
     app.update(); // fire the hooks
 }
 
-#[cfg(feature = "modify_voxels")]
 #[async_std::test]
-async fn test_modify_voxels() {
+async fn test_scene_instance_node_map() {
+    let mut app = App::new();
+    let handle = setup_and_load_voxel_scene(&mut app, "test.vox#outer-group/inner-group").await;
+    app.update();
+    let scene_root = app
+        .world_mut()
+        .spawn(SceneBundle {
+            scene: handle,
+            ..Default::default()
+        })
+        .id();
+    app.update();
+    let scene_instance = app
+        .world()
+        .get::<VoxelSceneInstance>(scene_root)
+        .expect("VoxelSceneInstance on scene root");
+    assert_eq!(scene_instance.nodes().count(), 3);
+    let dice = scene_instance
+        .get_node("outer-group/inner-group/dice")
+        .expect("dice node");
+    assert_eq!(
+        app.world()
+            .get::<Name>(dice)
+            .expect("Name component")
+            .as_str(),
+        "outer-group/inner-group/dice"
+    );
+    assert!(scene_instance
+        .get_node("outer-group/inner-group/nope")
+        .is_none());
+}
+
+#[test]
+fn test_unsupported_chunks() {
+    use crate::load::validate::unsupported_chunks;
+    let bytes = include_bytes!("../assets/test.vox");
+    assert!(unsupported_chunks(bytes).is_empty());
+
+    // append two chunks from a hypothetical newer version of the format to the children of the MAIN chunk
+    let mut newer = bytes.to_vec();
+    for id in [b"zNEW", b"zNEW", b"zOLD"] {
+        newer.extend_from_slice(id);
+        newer.extend_from_slice(&4u32.to_le_bytes());
+        newer.extend_from_slice(&0u32.to_le_bytes());
+        newer.extend_from_slice(&[1, 2, 3, 4]);
+    }
+    let children = u32::from_le_bytes(newer[16..20].try_into().expect("MAIN chunk"));
+    newer[16..20].copy_from_slice(&(children + 3 * 16).to_le_bytes());
+    assert_eq!(unsupported_chunks(&newer), vec!["zNEW", "zOLD"]);
+    let file = dot_vox::load_bytes(&newer).expect("newer files still parse");
+    assert_eq!(
+        crate::VoxelFileIndex::from_file(&file, DuplicateNamePolicy::default())
+            .models
+            .len(),
+        dot_vox::load_bytes(bytes).expect("parse").models.len()
+    );
+}
+
+#[test]
+fn test_element_data() {
+    use crate::VoxelElementData;
+    #[derive(bevy::reflect::Reflect, serde::Deserialize, Debug, PartialEq)]
+    struct Mining {
+        hardness: f32,
+    }
+    let data = VoxelElementData::<Mining>::from_ron("{ 0: (hardness: 9.0), 1: (hardness: 3.0) }")
+        .expect("parse element data");
+    assert_eq!(data.get(&Voxel::EMPTY), None, "empty voxels have no data");
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GRAY.into()])
+        .with_element_data(data.with(Voxel(2), Mining { hardness: 0.5 }))
+        .with_element(
+            Voxel(3),
+            VoxelElement::new(bevy::color::palettes::css::RED.into()),
+        );
+    assert_eq!(
+        palette.element_data::<Mining>(&Voxel(1)),
+        Some(&Mining { hardness: 3.0 }),
+        "element data survives editing the palette"
+    );
+    assert_eq!(
+        palette.element_data::<Mining>(&Voxel(2)),
+        Some(&Mining { hardness: 0.5 })
+    );
+    assert_eq!(palette.element_data::<Mining>(&Voxel(3)), None);
+    assert!(palette.element_data_table::<f32>().is_none());
+    assert!(VoxelElementData::<Mining>::from_ron("{ 1: (softness: 1.0) }").is_err());
+}
+
+#[test]
+fn test_palette_notes() {
+    use crate::load::chunks::palette_notes;
+    let notes = palette_notes(include_bytes!("../assets/test.vox"));
+    assert_eq!(notes.len(), 32, "one note per row of the palette");
+    assert_eq!(notes[0], "NOTE");
+    assert!(notes[1..].iter().all(|note| note.is_empty()));
+    assert!(palette_notes(&include_bytes!("../assets/test.vox")[..8]).is_empty());
+
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GRAY.into()])
+        .with_notes(vec![
+            "stone".to_string(),
+            String::new(),
+            "stone".to_string(),
+        ])
+        .with_element(
+            Voxel(1),
+            VoxelElement::new(bevy::color::palettes::css::RED.into()),
+        );
+    assert_eq!(
+        palette.indices_named("stone"),
+        vec![1, 2, 3, 4, 5, 6, 7, 8, 17, 18, 19, 20, 21, 22, 23, 24],
+        "notes survive editing the palette"
+    );
+    assert!(palette.indices_named("grass").is_empty());
+}
+
+#[test]
+fn test_palette_display_order_and_render_objects() {
+    use crate::load::chunks::{palette_index_map, render_objects};
+    let bytes = include_bytes!("../assets/study.vox");
+    assert!(
+        palette_index_map(bytes).is_empty(),
+        "study.vox keeps the default order"
+    );
+    let objects = render_objects(bytes);
+    assert_eq!(objects.len(), 15);
+    assert!(objects.iter().all(|object| object.contains_key("_type")));
+
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GRAY.into()]);
+    assert_eq!(palette.display_order(), (1..=255).collect::<Vec<u8>>());
+    // the editor shows the palette back to front, with the empty voxel in the last slot
+    let reversed: Vec<u8> = (0..=255).rev().collect();
+    let palette = palette
+        .with_display_order(reversed)
+        .with_notes(vec!["lava".to_string()]);
+    assert_eq!(
+        palette.display_order(),
+        (1..=255).rev().collect::<Vec<u8>>()
+    );
+    assert_eq!(
+        palette.indices_named("lava"),
+        vec![255, 254, 253, 252, 251, 250, 249, 248],
+        "notes name rows of the displayed palette"
+    );
+}
+
+#[async_std::test]
+async fn test_render_objects_on_scene_root() {
+    use crate::{load::chunks::render_objects, VoxelRenderObjects};
+    use bevy::{ecs::query::Without, hierarchy::Parent};
+    let mut app = App::new();
+    setup_app(&mut app);
+    let handle = app
+        .world()
+        .resource::<AssetServer>()
+        .load_untyped_async("study.vox")
+        .await
+        .expect("Loaded study")
+        .typed::<Scene>();
+    let mut scenes = app.world_mut().resource_mut::<Assets<Scene>>();
+    let scene = scenes.get_mut(&handle).expect("study scene");
+    let objects: Vec<VoxelRenderObjects> = scene
+        .world
+        .query_filtered::<&VoxelRenderObjects, Without<Parent>>()
+        .iter(&scene.world)
+        .cloned()
+        .collect();
+    assert_eq!(objects.len(), 1, "only the root holds the render objects");
+    let expected = render_objects(include_bytes!("../assets/study.vox"));
+    assert_eq!(objects[0].0, expected);
+    let kind = expected[0].get("_type").expect("type");
+    assert_eq!(objects[0].get(kind), Some(&expected[0]));
+    assert_eq!(objects[0].get("_missing"), None);
+}
+
+#[test]
+fn test_validate_vox_bytes() {
+    use crate::load::validate::lint_file;
+    use crate::{validate_vox_bytes, VoxelLintIssue};
+    use dot_vox::SceneNode;
+    let bytes = include_bytes!("../assets/test.vox");
+    let issues = validate_vox_bytes(bytes).expect("test.vox is valid");
+    assert!(!issues.iter().any(|issue| matches!(
+        issue,
+        VoxelLintIssue::ModelTooLarge { .. } | VoxelLintIssue::UnsupportedChunk { .. }
+    )));
+    assert!(validate_vox_bytes(&bytes[..8]).is_err());
+
+    let mut file = dot_vox::load_bytes(bytes).expect("parse test.vox");
+    file.models[0].voxels.clear();
+    file.models[0].size.x = 300;
+    for node in file.scenes.iter_mut() {
+        if let SceneNode::Transform { attributes, .. } = node {
+            if attributes.contains_key("_name") {
+                attributes.insert("_name".to_string(), "same".to_string());
+            }
+        }
+    }
+    let issues = lint_file(&file, bytes);
+    let name = crate::load::model_names(&file, DuplicateNamePolicy::default())[0].clone();
+    assert!(issues.contains(&VoxelLintIssue::EmptyModel {
+        model: name.clone()
+    }));
+    assert!(issues.iter().any(
+        |issue| matches!(issue, VoxelLintIssue::ModelTooLarge { model, .. } if *model == name)
+    ));
+    assert!(issues
+        .iter()
+        .any(|issue| matches!(issue, VoxelLintIssue::DuplicateNodeName { .. })));
+    assert!(issues
+        .iter()
+        .any(|issue| matches!(issue, VoxelLintIssue::DuplicateModelName { .. })));
+}
+
+#[test]
+fn test_duplicate_name_policy() {
+    use crate::load::{names::resolve_duplicate_names, validate::lint_file};
+    use crate::{DuplicateNamePolicy, VoxelFileIndex, VoxelLintIssue};
+    use dot_vox::SceneNode;
+    let bytes = include_bytes!("../assets/test.vox");
+    let parse = || {
+        let mut file = dot_vox::load_bytes(bytes).expect("parse test.vox");
+        for node in file.scenes.iter_mut() {
+            if let SceneNode::Transform { attributes, .. } = node {
+                if attributes.contains_key("_name") {
+                    attributes.insert("_name".to_string(), "same".to_string());
+                }
+            }
+        }
+        file
+    };
+
+    let mut file = parse();
+    let renamed = resolve_duplicate_names(&mut file, DuplicateNamePolicy::default(), "test.vox")
+        .expect("shared");
+    assert!(renamed.is_empty());
+    assert_eq!(file, parse());
+
+    let mut file = parse();
+    let renamed = resolve_duplicate_names(&mut file, DuplicateNamePolicy::KeepFirst, "test.vox")
+        .expect("keep first");
+    assert!(renamed.is_empty());
+
+    let mut file = parse();
+    assert!(matches!(
+        resolve_duplicate_names(&mut file, DuplicateNamePolicy::Error, "test.vox"),
+        Err(VoxLoaderError::DuplicateName { .. })
+    ));
+
+    let mut file = parse();
+    let renamed = resolve_duplicate_names(&mut file, DuplicateNamePolicy::Suffix, "test.vox")
+        .expect("suffix");
+    assert!(!renamed.is_empty());
+    let index = VoxelFileIndex::from_file(&file, DuplicateNamePolicy::Suffix);
+    for (original, label) in renamed.iter() {
+        assert_ne!(original, label);
+        assert!(index.nodes.contains(label));
+    }
+    assert!(!lint_file(&file, bytes).iter().any(|issue| matches!(
+        issue,
+        VoxelLintIssue::DuplicateNodeName { .. } | VoxelLintIssue::DuplicateModelName { .. }
+    )));
+}
+
+#[test]
+fn test_dedupe_model_names() {
+    use crate::load::names::dedupe_model_names;
+    let mut names = vec![
+        Some("a".to_string()),
+        None,
+        Some("b".to_string()),
+        Some("a".to_string()),
+    ];
+    let original = names.clone();
+    dedupe_model_names(&mut names, DuplicateNamePolicy::default());
+    assert_eq!(names, original);
+    dedupe_model_names(&mut names, DuplicateNamePolicy::KeepFirst);
+    assert_eq!(
+        names,
+        vec![Some("a".to_string()), None, Some("b".to_string()), None]
+    );
+}
+
+#[test]
+fn test_streamed_models_match_parsed_models() {
+    use crate::hash::StableHasher;
+    use crate::load::stream::read_streamed;
+    use bevy::tasks::{block_on, futures_lite::io::Cursor};
+    for bytes in [
+        &include_bytes!("../assets/test.vox")[..],
+        &include_bytes!("../assets/study.vox")[..],
+    ] {
+        let parsed = dot_vox::load_bytes(bytes).expect("parse file");
+        let mut reader = Cursor::new(bytes);
+        let mut bytes_read = 0;
+        let streamed = block_on(read_streamed(&mut reader, |read| bytes_read = read))
+            .expect("read streamed file");
+        assert_eq!(bytes_read, bytes.len());
+        let mut hasher = StableHasher::default();
+        hasher.write(bytes);
+        assert_eq!(streamed.content_hash, hasher.finish());
+        assert!(streamed.skeleton.len() < bytes.len());
+        let file = streamed.file;
+        assert_eq!(file.palette, parsed.palette);
+        assert_eq!(file.materials, parsed.materials);
+        assert_eq!(file.scenes, parsed.scenes);
+        assert!(file.models.iter().all(|model| model.voxels.is_empty()));
+        assert_eq!(
+            streamed.models.voxel_counts(),
+            parsed
+                .models
+                .iter()
+                .map(|model| model.voxels.len())
+                .collect::<Vec<usize>>()
+        );
+        // models are read back in any order
+        for (index, model) in parsed.models.iter().enumerate().rev() {
+            assert_eq!(
+                &block_on(streamed.models.model(&mut reader, &file, index, "file.vox"))
+                    .expect("model"),
+                model
+            );
+        }
+    }
+    let truncated = &include_bytes!("../assets/test.vox")[..8];
+    assert!(block_on(read_streamed(&mut Cursor::new(truncated), |_| {})).is_err());
+}
+
+#[test]
+fn test_validate_file() {
+    use crate::load::validate::validate_file;
+    use dot_vox::SceneNode;
+    let parse =
+        || dot_vox::load_bytes(include_bytes!("../assets/test.vox")).expect("parse test.vox");
+    assert!(validate_file(&parse(), "test.vox").is_ok());
+
+    let mut missing_model = parse();
+    for node in missing_model.scenes.iter_mut() {
+        if let SceneNode::Shape { models, .. } = node {
+            models[0].model_id = 99;
+        }
+    }
+    assert!(matches!(
+        validate_file(&missing_model, "test.vox"),
+        Err(VoxLoaderError::MissingModel { model_id: 99, .. })
+    ));
+
+    let mut cycle = parse();
+    if let SceneNode::Transform { child, .. } = &mut cycle.scenes[0] {
+        *child = 0;
+    }
+    assert!(matches!(
+        validate_file(&cycle, "test.vox"),
+        Err(VoxLoaderError::SceneGraphCycle { .. })
+    ));
+
+    let mut out_of_range = parse();
+    out_of_range.models[0].voxels[0].i = 255;
+    let error = validate_file(&out_of_range, "test.vox").expect_err("out of range");
+    assert!(matches!(
+        error,
+        VoxLoaderError::PaletteOutOfRange {
+            model: 0,
+            index: 255,
+            ..
+        }
+    ));
+    assert!(error.to_string().starts_with("test.vox: "));
+}
+
+#[async_std::test]
+async fn test_snapshot_matches_loaded_mesh() {
+    use crate::test_utils::{snapshot_vox_bytes, ModelSnapshot};
+    let bytes = include_bytes!("../assets/test.vox");
+    let snapshot =
+        snapshot_vox_bytes(bytes, &VoxLoaderSettings::default()).expect("snapshot test.vox");
+    let dice = snapshot
+        .model("outer-group/inner-group/dice")
+        .expect("dice snapshot");
+    assert!(dice.triangle_count() > 0);
+    assert_eq!(dice.vertex_count(), dice.normals.len());
+
     let mut app = App::new();
     let handle =
         setup_and_load_voxel_scene(&mut app, "test.vox#outer-group/inner-group/dice").await;
-    app.update();
     let scene_root = app
         .world_mut()
         .spawn(SceneBundle {
@@ -246,135 +691,5278 @@ async fn test_modify_voxels() {
         .expect("children")
         .first()
         .expect("scene root");
-    let model_instance = app
+    let model_id = &app
         .world()
         .get::<VoxelModelInstance>(*entity)
-        .expect("voxel model instance")
-        .clone();
-    let region = VoxelRegion {
-        origin: IVec3::splat(2),
-        size: IVec3::ONE,
-    };
-    app.world_mut().commands().modify_voxel_model(
-        model_instance.clone(),
-        VoxelRegionMode::Box(region),
-        |_pos, _voxel, _model| Voxel(7),
-    );
-    app.update();
+        .expect("Voxel model instance")
+        .model;
     let model = app
         .world()
         .resource::<Assets<VoxelModel>>()
-        .get(model_instance.model.id())
-        .expect("retrieve model from Res<Assets>");
+        .get(model_id)
+        .expect("voxel model");
+    let mesh = app
+        .world()
+        .resource::<Assets<Mesh>>()
+        .get(&model.mesh)
+        .expect("dice mesh");
+    let loaded = ModelSnapshot::from_mesh(dice.name.clone(), dice.size, mesh);
+    assert!(dice.approx_eq(&loaded, f32::EPSILON));
+}
 
-    assert_eq!(
-        model.get_voxel_at_point(IVec3::splat(4)),
-        Err(OutOfBoundsError),
-        "Max coordinate should be 3,3,3"
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_voxel_world_server() {
+    let mut server = VoxelWorldServer::default();
+    let ids = server
+        .load_vox_bytes(
+            include_bytes!("../assets/test.vox"),
+            &VoxLoaderSettings::default(),
+        )
+        .expect("load test.vox");
+    let dice = server
+        .find_model("outer-group/inner-group/dice")
+        .expect("dice model");
+    assert!(ids.contains(&dice));
+    assert!(!server.has_changes());
+
+    let block = server.insert_model(
+        "block",
+        VoxelData::new(UVec3::splat(4), true, 1.0),
+        VoxelPalette::from_colors(vec![bevy::color::palettes::css::RED.into()]),
     );
-    assert_eq!(
-        model.get_voxel_at_point(IVec3::splat(-1)),
-        Err(OutOfBoundsError),
-        "Min coordinate should be 0,0,0"
+    assert!(server.set_voxel(block, IVec3::ZERO, Voxel(7)));
+    assert!(!server.set_voxel(block, IVec3::splat(-1), Voxel(7)));
+    server.modify_voxels(
+        block,
+        VoxelRegionMode::Box(VoxelRegion {
+            origin: IVec3::ZERO,
+            size: IVec3::new(2, 1, 1),
+        }),
+        |_, _, _| Voxel(7),
     );
-    let voxel = model
-        .get_voxel_at_point(IVec3::splat(2))
-        .expect("Retrieve voxel");
-    assert_eq!(voxel.0, 7, "Voxel material should've been changed to 7");
+    let changes = server.drain_changes();
+    assert_eq!(changes.len(), 2, "unchanged voxels aren't recorded");
+    assert_eq!(server.get_voxel(block, IVec3::new(1, 0, 0)), Some(Voxel(7)));
+    assert!(!server.has_changes());
+
+    let mut client = VoxelWorldServer::default();
+    client
+        .load_vox_bytes(
+            include_bytes!("../assets/test.vox"),
+            &VoxLoaderSettings::default(),
+        )
+        .expect("load test.vox");
+    let client_block = client.insert_model(
+        "block",
+        VoxelData::new(UVec3::splat(4), true, 1.0),
+        VoxelPalette::from_colors(vec![bevy::color::palettes::css::RED.into()]),
+    );
+    assert_eq!(client_block, block);
+    client.apply_changes(changes);
+    assert_eq!(client.get_voxel(block, IVec3::ZERO), Some(Voxel(7)));
 }
 
-#[cfg(feature = "generate_voxels")]
 #[test]
-fn test_generate_voxels() {
-    let mut app = App::new();
-    setup_app(&mut app);
+fn test_generate_tangents() {
     let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
-    let tall_box = SDF::cuboid(Vec3::new(0.5, 2.5, 0.5)).voxelize(UVec3::splat(6), 1.0, Voxel(1));
-    let world = app.world_mut();
-    let context = VoxelContext::new(world, palette);
-    let (_, tall_box_model) =
-        VoxelModel::new(world, tall_box, "tall box".to_string(), context).expect("Add box model");
-    assert_eq!(tall_box_model.name, "tall box");
-    assert_eq!(tall_box_model.has_translucency, false);
-    let mesh = app
-        .world()
-        .resource::<Assets<Mesh>>()
-        .get(tall_box_model.mesh.id())
-        .expect("mesh generated");
-    assert_eq!(
-        mesh.compute_aabb().expect("aabb").half_extents,
-        Vec3A::new(0.5, 2.5, 0.5)
-    );
+    let mut data = VoxelData::new(UVec3::splat(2), true, 1.0);
+    data.set_voxel(Voxel(1), UVec3::ZERO);
+    let (mesh, _) = data.remesh(&palette);
+    assert!(mesh.attribute(Mesh::ATTRIBUTE_TANGENT).is_none());
+
+    let (mesh, _) = data.with_tangents(true).remesh(&palette);
+    let Some(VertexAttributeValues::Float32x4(tangents)) = mesh.attribute(Mesh::ATTRIBUTE_TANGENT)
+    else {
+        panic!("Mesh has tangents");
+    };
+    let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+    else {
+        panic!("Mesh has normals");
+    };
+    assert_eq!(tangents.len(), normals.len());
+    for (tangent, normal) in tangents.iter().zip(normals) {
+        let tangent = Vec3::new(tangent[0], tangent[1], tangent[2]);
+        assert_eq!(tangent.length(), 1.0);
+        assert_eq!(tangent.dot(Vec3::from(*normal)), 0.0);
+    }
+}
+
+#[test]
+fn test_mesh_attribute_config() {
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let mut data =
+        VoxelData::new(UVec3::splat(2), true, 1.0).with_attributes(MeshAttributeConfig {
+            uv0: false,
+            uv1: true,
+            color: true,
+            face_id: true,
+            palette_index: true,
+            ..Default::default()
+        });
+    data.set_voxel(Voxel(1), UVec3::ZERO);
+    let (mesh, _) = data.remesh(&palette);
+    assert!(mesh.attribute(Mesh::ATTRIBUTE_UV_0).is_none());
+    let vertex_count = mesh.count_vertices();
+    assert_eq!(vertex_count, 24);
+    let Some(VertexAttributeValues::Float32x2(uv1)) = mesh.attribute(Mesh::ATTRIBUTE_UV_1) else {
+        panic!("Mesh has UV1");
+    };
+    assert_eq!(uv1.len(), vertex_count);
+    let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute(Mesh::ATTRIBUTE_COLOR)
+    else {
+        panic!("Mesh has vertex colors");
+    };
     assert_eq!(
-        mesh.count_vertices(),
-        6 * 4,
-        "resulting mesh should have 6 quads"
+        colors[0],
+        palette.elements[0].color.to_linear().to_f32_array()
     );
+    let Some(VertexAttributeValues::Uint32(face_ids)) = mesh.attribute(ATTRIBUTE_FACE_ID) else {
+        panic!("Mesh has face ids");
+    };
+    let distinct: HashSet<u32> = face_ids.iter().copied().collect();
+    assert_eq!(distinct, (0..6).collect());
+    let Some(VertexAttributeValues::Uint32(palette_indices)) =
+        mesh.attribute(ATTRIBUTE_PALETTE_INDEX)
+    else {
+        panic!("Mesh has palette indices");
+    };
+    assert!(palette_indices.iter().all(|index| *index == 1));
 }
 
-#[cfg(feature = "generate_voxels")]
 #[test]
-fn test_sdf_intersect() {
-    let box_sphere = SDF::cuboid(Vec3::splat(2.0))
-        .intersect(SDF::sphere(2.5))
-        .voxelize(UVec3::splat(7), 1.0, Voxel(1));
-    let sphere_box = SDF::sphere(2.5)
-        .intersect(SDF::cuboid(Vec3::splat(2.0)))
-        .voxelize(UVec3::splat(7), 1.0, Voxel(1));
-    assert_eq!(box_sphere.voxels, sphere_box.voxels);
+fn test_voxel_tint() {
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::WHITE.into()]);
+    let tint = VoxelTint::new(|position, voxel| {
+        assert_eq!(voxel, Voxel(1));
+        if position.x > 10.5 {
+            Color::srgb(1.0, 0.0, 0.0)
+        } else {
+            Color::WHITE
+        }
+    })
+    .with_origin(Vec3::new(10.0, 0.0, 0.0));
+    let mut data = VoxelData::new(UVec3::splat(2), true, 1.0).with_tint(tint);
+    data.set_voxel(Voxel(1), UVec3::ZERO);
+    let (mesh, _) = data.remesh(&palette);
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("Mesh has positions");
+    };
+    let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute(Mesh::ATTRIBUTE_COLOR)
+    else {
+        panic!("Tinted mesh has vertex colors");
+    };
+    assert_eq!(colors.len(), positions.len());
+    for (position, color) in positions.iter().zip(colors) {
+        let expected = if position[0] + 10.0 > 10.5 {
+            [1.0, 0.0, 0.0, 1.0]
+        } else {
+            [1.0; 4]
+        };
+        assert_eq!(*color, expected);
+    }
 }
 
-#[cfg(feature = "generate_voxels")]
 #[test]
-fn test_sdf_subtract() {
-    let thin_box = SDF::cuboid(Vec3::new(1.0, 2.0, 2.0)).voxelize(UVec3::splat(6), 1.0, Voxel(1));
-    let halved_cube = SDF::cuboid(Vec3::new(2.0, 2.0, 2.0))
-        .subtract(SDF::cuboid(Vec3::new(1.0, 2.0, 2.0)).translate(Vec3::X))
-        .translate(Vec3::X)
-        .voxelize(UVec3::splat(6), 1.0, Voxel(1));
-    assert_eq!(thin_box.voxels, halved_cube.voxels);
+fn test_voxel_shapes() {
+    use crate::{VoxelFacing, VoxelShape, VoxelShapes};
+    let palette = VoxelPalette::from_colors(vec![
+        bevy::color::palettes::css::GREEN.into(),
+        bevy::color::palettes::css::RED.into(),
+    ]);
+    let mut data = VoxelData::new(UVec3::new(2, 1, 1), true, 1.0)
+        .with_tangents(true)
+        .with_attributes(MeshAttributeConfig {
+            face_id: true,
+            ..Default::default()
+        })
+        .with_shapes(
+            VoxelShapes::default().with_shape(Voxel(2), VoxelShape::Ramp(VoxelFacing::NegX)),
+        );
+    data.set_voxel(Voxel(1), UVec3::ZERO);
+    data.set_voxel(Voxel(2), UVec3::new(1, 0, 0));
+    let (mesh, _) = data.remesh(&palette);
+    // 5 faces of the cube, and the bottom, slope and sides of the ramp, whose full back hides and is hidden by the cube
+    assert_eq!(mesh.count_vertices(), 9 * 4);
+    let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+    else {
+        panic!("Mesh has normals");
+    };
+    let Some(VertexAttributeValues::Uint32(face_ids)) = mesh.attribute(ATTRIBUTE_FACE_ID) else {
+        panic!("Mesh has face ids");
+    };
+    let slope = Vec3::new(1.0, 1.0, 0.0).normalize();
+    let sloped: Vec<usize> = normals
+        .iter()
+        .enumerate()
+        .filter(|(_, normal)| Vec3::from(**normal).distance(slope) < 1e-5)
+        .map(|(index, _)| index)
+        .collect();
+    assert_eq!(sloped.len(), 4, "the ramp slopes down towards +X");
+    assert!(sloped.iter().all(|index| face_ids[*index] == 4));
+    let Some(VertexAttributeValues::Float32x4(tangents)) = mesh.attribute(Mesh::ATTRIBUTE_TANGENT)
+    else {
+        panic!("Mesh has tangents");
+    };
+    for (tangent, normal) in tangents.iter().zip(normals) {
+        let tangent = Vec3::new(tangent[0], tangent[1], tangent[2]);
+        assert!((tangent.length() - 1.0).abs() < 1e-5);
+        assert!(tangent.dot(Vec3::from(*normal)).abs() < 1e-5);
+    }
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("Mesh has positions");
+    };
+    assert!(
+        !positions
+            .iter()
+            .zip(normals)
+            .any(|(position, normal)| position[0] == 1.0 && normal[0] == -1.0),
+        "the back of the ramp is hidden by the cube"
+    );
+    assert!(
+        !positions
+            .iter()
+            .zip(normals)
+            .any(|(position, normal)| position[0] == 1.0 && normal[0] == 1.0),
+        "the side of the cube is hidden by the back of the ramp"
+    );
+
+    // a slab between two cubes: its full bottom hides the top of the cube below, but its top is below the cube above
+    let mut data = VoxelData::new(UVec3::new(1, 3, 1), true, 1.0)
+        .with_shapes(VoxelShapes::default().with_shape(Voxel(2), VoxelShape::Slab));
+    data.set_voxel(Voxel(1), UVec3::ZERO);
+    data.set_voxel(Voxel(2), UVec3::new(0, 1, 0));
+    data.set_voxel(Voxel(1), UVec3::new(0, 2, 0));
+    let (mesh, _) = data.remesh(&palette);
+    // 5 faces of the lower cube, the top and sides of the slab, and 6 faces of the upper cube
+    assert_eq!(mesh.count_vertices(), 16 * 4);
 }
 
-#[cfg(feature = "generate_voxels")]
 #[test]
-fn test_sdf_rotate() {
-    let tall_box = SDF::cuboid(Vec3::new(0.5, 2.5, 0.5)).voxelize(UVec3::splat(6), 1.0, Voxel(1));
-    let deep_box_rotated = SDF::cuboid(Vec3::new(0.5, 0.5, 2.5))
-        .rotate(Quat::from_axis_angle(Vec3::X, FRAC_PI_2))
-        .voxelize(UVec3::splat(6), 1.0, Voxel(1));
-    assert_eq!(tall_box.voxels, deep_box_rotated.voxels);
+fn test_lightmap_uvs_do_not_overlap() {
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let mut data =
+        VoxelData::new(UVec3::new(3, 2, 1), true, 1.0).with_attributes(MeshAttributeConfig {
+            lightmap_resolution: Some(64),
+            ..Default::default()
+        });
+    data.set_voxel(Voxel(1), UVec3::ZERO);
+    data.set_voxel(Voxel(1), UVec3::new(1, 0, 0));
+    data.set_voxel(Voxel(1), UVec3::new(2, 1, 0));
+    let (mesh, _) = data.remesh(&palette);
+    let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_1) else {
+        panic!("Mesh has lightmap UVs");
+    };
+    let rects: Vec<(Vec2, Vec2)> = uvs
+        .chunks(4)
+        .map(|quad| {
+            quad.iter().fold(
+                (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+                |(min, max), uv| (min.min(Vec2::from(*uv)), max.max(Vec2::from(*uv))),
+            )
+        })
+        .collect();
+    for (index, (min, max)) in rects.iter().enumerate() {
+        assert!(min.cmpge(Vec2::ZERO).all() && max.cmple(Vec2::ONE).all());
+        for (other_min, other_max) in rects.iter().skip(index + 1) {
+            let overlaps = min.cmplt(*other_max).all() && other_min.cmplt(*max).all();
+            assert!(!overlaps, "lightmap faces overlap");
+        }
+    }
 }
 
-#[cfg(feature = "generate_voxels")]
-#[test]
-fn test_voxel_queryable() {
-    let data = SDF::cuboid(Vec3::splat(2.0)).voxelize(UVec3::splat(4), 1.0, Voxel(1));
-    assert!(data.point_in_model(IVec3::new(3, 0, 0)).is_ok());
-    assert!(data.point_in_model(IVec3::new(4, 0, 0)).is_err());
+#[async_std::test]
+async fn test_file_index() {
+    let mut app = App::new();
+    setup_app(&mut app);
+    let handle = app
+        .world()
+        .resource::<AssetServer>()
+        .load_untyped_async("test.vox#index")
+        .await
+        .expect("Loaded index")
+        .typed::<VoxelFileIndex>();
+    let index = app
+        .world()
+        .resource::<Assets<VoxelFileIndex>>()
+        .get(&handle)
+        .expect("index");
+    let dice = index
+        .model("outer-group/inner-group/dice")
+        .expect("dice entry");
+    assert!(dice.voxel_count > 0);
     assert_eq!(
-        data.local_point_to_voxel_space(Vec3::ZERO),
-        IVec3::new(2, 2, 2)
+        dice.node_paths,
+        vec!["outer-group/inner-group/dice".to_string()]
     );
+    assert!(index.nodes.contains(&"outer-group/inner-group".to_string()));
+
+    // the index loader streams the file, so counts the voxels without parsing them
+    let scene_index = index.clone();
+    let handle = app
+        .world()
+        .resource::<AssetServer>()
+        .load::<VoxelFileIndex>("test.vox");
+    for _ in 0..1000 {
+        app.update();
+        if app
+            .world()
+            .resource::<AssetServer>()
+            .is_loaded_with_dependencies(&handle)
+        {
+            break;
+        }
+        async_std::task::sleep(std::time::Duration::from_millis(1)).await;
+    }
+    let index = app
+        .world()
+        .resource::<Assets<VoxelFileIndex>>()
+        .get(&handle)
+        .expect("standalone index");
+    assert_eq!(index.models, scene_index.models);
+    assert_eq!(index.nodes, scene_index.nodes);
+    assert_eq!(index.content_hash, scene_index.content_hash);
 }
 
-async fn setup_and_load_voxel_scene(app: &mut App, filename: &'static str) -> Handle<Scene> {
-    setup_app(app);
-    let assets = app.world().resource::<AssetServer>();
-    assets
-        .load_untyped_async(filename)
+#[async_std::test]
+async fn test_voxel_catalog() {
+    let mut app = App::new();
+    setup_app(&mut app);
+    let handle = app
+        .world()
+        .resource::<AssetServer>()
+        .load_untyped_async("test.voxcat.ron")
         .await
-        .expect(format!("Loaded {filename}").as_str())
-        .typed::<Scene>()
+        .expect("Loaded catalog")
+        .typed::<VoxelCatalog>();
+    let catalog = app
+        .world()
+        .resource::<Assets<VoxelCatalog>>()
+        .get(&handle)
+        .expect("catalog");
+    assert_eq!(catalog.contexts().len(), 1);
+    let dice = catalog
+        .get("test/outer-group/inner-group/dice")
+        .expect("dice entry");
+    assert_eq!(dice.file, "test.vox");
+    assert_eq!(dice.context, catalog.contexts()[0]);
+    assert_eq!(
+        catalog.get_by_id(dice.id).map(|entry| entry.name.as_str()),
+        Some("test/outer-group/inner-group/dice")
+    );
+    assert!(catalog
+        .entries()
+        .iter()
+        .all(|entry| entry.context == dice.context));
+    let context = app
+        .world()
+        .resource::<Assets<VoxelContext>>()
+        .get(&dice.context)
+        .expect("catalog context");
+    let models = app.world().resource::<Assets<VoxelModel>>();
+    let (translucent, opaque): (Vec<&VoxelModel>, Vec<&VoxelModel>) = catalog
+        .entries()
+        .iter()
+        .map(|entry| models.get(&entry.model).expect("catalog model"))
+        .partition(|model| model.has_translucency);
+    assert!(!opaque.is_empty());
+    assert!(
+        opaque
+            .iter()
+            .all(|model| model.material == context.opaque_material),
+        "opaque models share the context's material"
+    );
+    assert!(translucent
+        .iter()
+        .all(|model| model.material != context.opaque_material));
 }
 
-fn setup_app(app: &mut App) {
-    app.add_plugins((
+#[async_std::test]
+async fn test_lazy_meshing_keeps_shared_catalog_material() {
+    let mut app = App::new();
+    setup_app_with_settings(
+        &mut app,
+        Some(VoxLoaderSettings {
+            lazy_meshing: true,
+            ..Default::default()
+        }),
+    );
+    let handle = app
+        .world()
+        .resource::<AssetServer>()
+        .load_untyped_async("test.voxcat.ron")
+        .await
+        .expect("Loaded catalog")
+        .typed::<VoxelCatalog>();
+    let catalog = app
+        .world()
+        .resource::<Assets<VoxelCatalog>>()
+        .get(&handle)
+        .expect("catalog")
+        .clone();
+    let walls = catalog
+        .get("test/outer-group/inner-group/walls")
+        .expect("walls entry");
+    let opaque_material = app
+        .world()
+        .resource::<Assets<VoxelContext>>()
+        .get(&walls.context)
+        .expect("catalog context")
+        .opaque_material
+        .clone();
+    let (mesh, material) = {
+        let model = app
+            .world()
+            .resource::<Assets<VoxelModel>>()
+            .get(&walls.model)
+            .expect("walls model");
+        (model.mesh.clone(), model.material.clone())
+    };
+    assert_ne!(
+        material, opaque_material,
+        "Models whose translucency isn't known yet don't share the opaque material"
+    );
+    let instance = app
+        .world_mut()
+        .spawn((walls.instance(), mesh, material))
+        .id();
+    app.update();
+
+    let materials = app.world().resource::<Assets<StandardMaterial>>();
+    let opaque = materials.get(&opaque_material).expect("opaque material");
+    assert_eq!(
+        opaque.specular_transmission, 0.0,
+        "Meshing a glass model shouldn't make the shared opaque material transmissive"
+    );
+    assert!(opaque.specular_transmission_texture.is_none());
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&walls.model)
+        .expect("walls model");
+    assert!(model.has_translucency);
+    assert_ne!(model.material, opaque_material);
+    let glass = materials.get(&model.material).expect("glass material");
+    assert!((glass.ior - 1.3).abs() / 1.3 <= 0.0001);
+    assert_eq!(
+        app.world().get::<Handle<StandardMaterial>>(instance),
+        Some(&model.material),
+        "The instance should follow the model to its new material"
+    );
+}
+
+#[test]
+fn test_voxel_catalog_ids_are_stable() {
+    assert_eq!(
+        VoxelCatalogId::from_name(""),
+        VoxelCatalogId(0xcbf2_9ce4_8422_2325)
+    );
+    assert_eq!(
+        VoxelCatalogId::from_name("a"),
+        VoxelCatalogId(0xaf63_dc4c_8601_ec8c)
+    );
+    assert_ne!(
+        VoxelCatalogId::from_name("crate"),
+        VoxelCatalogId::from_name("barrel")
+    );
+}
+
+#[async_std::test]
+async fn test_lazy_meshing() {
+    let mut app = App::new();
+    setup_app_with_settings(
+        &mut app,
+        Some(VoxLoaderSettings {
+            lazy_meshing: true,
+            ..Default::default()
+        }),
+    );
+    let handle = app
+        .world()
+        .resource::<AssetServer>()
+        .load_untyped_async("test.vox#outer-group/inner-group/dice")
+        .await
+        .expect("Loaded dice")
+        .typed::<Scene>();
+    let mesh_handle: Handle<Mesh> = app
+        .world()
+        .resource::<AssetServer>()
+        .load("test.vox#outer-group/inner-group/dice@mesh");
+    let vertex_count = |app: &App| {
+        app.world()
+            .resource::<Assets<Mesh>>()
+            .get(&mesh_handle)
+            .expect("dice mesh")
+            .count_vertices()
+    };
+    assert_eq!(vertex_count(&app), 0, "mesh is deferred until first spawn");
+    app.world_mut().spawn(SceneBundle {
+        scene: handle,
+        ..Default::default()
+    });
+    app.update();
+    assert!(vertex_count(&app) > 0);
+}
+
+#[async_std::test]
+async fn test_global_settings() {
+    let mut app = App::new();
+    setup_app_with_settings(
+        &mut app,
+        Some(VoxLoaderSettings {
+            voxel_size: 0.5,
+            ..Default::default()
+        }),
+    );
+    assert_eq!(
+        app.world()
+            .resource::<VoxSceneGlobalSettings>()
+            .get()
+            .voxel_size,
+        0.5
+    );
+    let handle: Handle<VoxelModel> = app
+        .world()
+        .resource::<AssetServer>()
+        .load_untyped_async("test.vox#outer-group/inner-group/dice@model")
+        .await
+        .expect("Loaded dice")
+        .typed::<VoxelModel>();
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&handle)
+        .expect("dice model");
+    assert_eq!(model.data.voxel_size, 0.5);
+
+    // per-load settings override the global settings field by field, even when they are set to the defaults
+    let global = app.world().resource::<VoxSceneGlobalSettings>();
+    let resolved = global.resolve(&VoxLoaderOverrides {
+        voxel_size: Some(1.0),
+        brick_maps: Some(true),
+        ..Default::default()
+    });
+    assert_eq!(resolved.voxel_size, 1.0);
+    assert!(resolved.brick_maps);
+    assert_eq!(
+        global.resolve(&VoxLoaderOverrides::default()).voxel_size,
+        0.5
+    );
+}
+
+/// Loads the dice model with the `settings` as the global settings, waiting for the load to finish
+fn load_dice_with_settings(settings: VoxLoaderSettings) -> (App, Handle<VoxelModel>) {
+    let mut app = App::new();
+    setup_app_with_settings(&mut app, Some(settings));
+    let handle = bevy::tasks::block_on(
+        app.world()
+            .resource::<AssetServer>()
+            .load_untyped_async("test.vox#outer-group/inner-group/dice@model"),
+    )
+    .expect("Loaded dice")
+    .typed::<VoxelModel>();
+    app.update();
+    (app, handle)
+}
+
+#[test]
+fn test_probe_names() {
+    use crate::load::parse_scene::probe_name;
+    assert_eq!(
+        probe_name(Some(&"probe:hall".to_string())),
+        Some("hall".to_string())
+    );
+    assert_eq!(probe_name(Some(&"hall".to_string())), None);
+    assert_eq!(probe_name(None), None);
+}
+
+#[test]
+fn test_socket_names() {
+    use crate::load::parse_scene::socket_name;
+    use dot_vox::SceneNode;
+    let group = |children: Vec<u32>| SceneNode::Group {
+        attributes: Default::default(),
+        children,
+    };
+    let shape = SceneNode::Shape {
+        attributes: Default::default(),
+        models: Vec::new(),
+    };
+    assert_eq!(
+        socket_name(Some(&"socket:hand_r".to_string()), &shape),
+        Some("hand_r".to_string()),
+        "The prefix should be stripped from the socket name"
+    );
+    assert_eq!(
+        socket_name(Some(&"hand_r".to_string()), &group(Vec::new())),
+        Some("hand_r".to_string()),
+        "Named nodes containing an empty group are sockets"
+    );
+    assert_eq!(
+        socket_name(Some(&"hand_r".to_string()), &group(vec![1])),
+        None
+    );
+    assert_eq!(socket_name(Some(&"hand_r".to_string()), &shape), None);
+    assert_eq!(socket_name(None, &group(Vec::new())), None);
+}
+
+#[test]
+fn test_spawn_reflection_probes() {
+    let (mut app, handle) = load_dice_with_settings(VoxLoaderSettings::default());
+    let size = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&handle)
+        .expect("dice model")
+        .data
+        ._size()
+        .as_vec3();
+    let probe = app
+        .world_mut()
+        .spawn((
+            Transform::default(),
+            Handle::<Mesh>::default(),
+            VoxelModelInstance {
+                model: handle,
+                context: Handle::default(),
+            },
+            crate::VoxelReflectionProbe("dice".to_string()),
+        ))
+        .id();
+    app.update();
+    let entity = app.world().entity(probe);
+    assert!(entity.contains::<bevy::pbr::LightProbe>());
+    assert!(!entity.contains::<Handle<Mesh>>());
+    assert!(!entity.contains::<VoxelModelInstance>());
+    assert_eq!(
+        entity.get::<Transform>().expect("transform").scale,
+        size,
+        "probe covers the model"
+    );
+}
+
+#[test]
+fn test_spawn_voxel_model_instance() {
+    #[derive(bevy::ecs::component::Component)]
+    struct Collider;
+    let (mut app, handle) = load_dice_with_settings(VoxLoaderSettings::default());
+    let mesh = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&handle)
+        .expect("dice model")
+        .mesh
+        .clone();
+    let mut commands = app.world_mut().commands();
+    let entity = VoxelModelInstance {
+        model: handle,
+        context: Handle::default(),
+    }
+    .spawn(&mut commands)
+    .with_name("dice")
+    .with_layer(VoxelLayer {
+        id: 2,
+        name: Some("props".to_string()),
+    })
+    .with_collider(Collider)
+    .at(Transform::from_xyz(1.0, 2.0, 3.0))
+    .id();
+    app.world_mut().flush();
+    let entity = app.world().entity(entity);
+    assert_eq!(*entity.get::<Handle<Mesh>>().expect("mesh"), mesh);
+    assert!(entity.contains::<Handle<StandardMaterial>>());
+    assert!(entity.contains::<InheritedVisibility>());
+    assert_eq!(
+        entity.get::<Transform>().expect("transform").translation,
+        Vec3::new(1.0, 2.0, 3.0)
+    );
+    assert_eq!(entity.get::<Name>().expect("name").as_str(), "dice");
+    assert_eq!(entity.get::<VoxelLayer>().expect("layer").id, 2);
+    assert!(entity.contains::<Collider>());
+}
+
+#[test]
+fn test_swap_voxel_model() {
+    use crate::SwapVoxelModelCommandsExt;
+    let (mut app, handle) = load_dice_with_settings(VoxLoaderSettings::default());
+    let damaged = {
+        let mut models = app.world_mut().resource_mut::<Assets<VoxelModel>>();
+        let mut damaged = models.get(&handle).expect("dice model").clone();
+        damaged.name = "dice-damaged".to_string();
+        damaged.mesh = Handle::weak_from_u128(1);
+        damaged.material = Handle::weak_from_u128(2);
+        models.add(damaged)
+    };
+    let instance = app
+        .world_mut()
+        .spawn((
+            Handle::<Mesh>::default(),
+            Handle::<StandardMaterial>::default(),
+            Aabb::default(),
+            VoxelModelInstance {
+                model: handle,
+                context: Handle::default(),
+            },
+        ))
+        .id();
+    app.world_mut()
+        .commands()
+        .swap_voxel_model(instance, damaged.clone());
+    app.world_mut().flush();
+    let entity = app.world().entity(instance);
+    assert_eq!(
+        entity.get::<VoxelModelInstance>().expect("instance").model,
+        damaged
+    );
+    assert_eq!(
+        *entity.get::<Handle<Mesh>>().expect("mesh"),
+        Handle::weak_from_u128(1)
+    );
+    assert_eq!(
+        *entity.get::<Handle<StandardMaterial>>().expect("material"),
+        Handle::weak_from_u128(2)
+    );
+    assert!(!entity.contains::<Aabb>(), "bounds are recalculated");
+}
+
+#[test]
+fn test_voxel_instance_material_params() {
+    use crate::{VoxelInstanceMaterial, VoxelInstanceMaterialParams};
+    let (mut app, handle) = load_dice_with_settings(VoxLoaderSettings::default());
+    app.init_asset::<VoxelInstanceMaterial>().add_systems(
+        bevy::app::PostUpdate,
+        crate::model::instance_material::update_instance_materials,
+    );
+    let material = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&handle)
+        .expect("dice model")
+        .material
+        .clone();
+    let instance = app
+        .world_mut()
+        .spawn((
+            material.clone(),
+            VoxelModelInstance {
+                model: handle,
+                context: Handle::default(),
+            },
+            VoxelInstanceMaterialParams {
+                wetness: 0.5,
+                ..Default::default()
+            },
+        ))
+        .id();
+    app.update();
+    let extended = app
+        .world()
+        .get::<Handle<VoxelInstanceMaterial>>(instance)
+        .expect("instance material")
+        .clone();
+    assert!(!app
+        .world()
+        .entity(instance)
+        .contains::<Handle<StandardMaterial>>());
+    let params = |app: &App| {
+        app.world()
+            .resource::<Assets<VoxelInstanceMaterial>>()
+            .get(&extended)
+            .expect("material")
+            .extension
+            .params
+    };
+    assert_eq!(params(&app), Vec4::new(0.0, 0.5, 0.0, 4.0));
+
+    app.world_mut()
+        .get_mut::<VoxelInstanceMaterialParams>(instance)
+        .expect("params")
+        .damage = 2.0;
+    app.update();
+    assert_eq!(
+        app.world().get::<Handle<VoxelInstanceMaterial>>(instance),
+        Some(&extended),
+        "the material is updated in place"
+    );
+    assert_eq!(params(&app), Vec4::new(1.0, 0.5, 0.0, 4.0));
+
+    app.world_mut()
+        .resource_mut::<Assets<StandardMaterial>>()
+        .get_mut(&material)
+        .expect("model material")
+        .base_color = Color::srgb(1.0, 0.0, 0.0);
+    app.update();
+    let base = |app: &App| {
+        app.world()
+            .resource::<Assets<VoxelInstanceMaterial>>()
+            .get(&extended)
+            .expect("material")
+            .base
+            .clone()
+    };
+    assert_eq!(
+        base(&app).base_color,
+        Color::srgb(1.0, 0.0, 0.0),
+        "the instance follows the model's material"
+    );
+    assert_eq!(base(&app).alpha_mode, AlphaMode::Opaque);
+
+    app.world_mut()
+        .get_mut::<VoxelInstanceMaterialParams>(instance)
+        .expect("params")
+        .dissolve = 0.5;
+    app.update();
+    assert_eq!(
+        base(&app).alpha_mode,
+        AlphaMode::Mask(0.5),
+        "dissolving instances are masked so the prepasses discard too"
+    );
+
+    app.world_mut()
+        .entity_mut(instance)
+        .remove::<VoxelInstanceMaterialParams>();
+    app.update();
+    let entity = app.world().entity(instance);
+    assert!(!entity.contains::<Handle<VoxelInstanceMaterial>>());
+    assert_eq!(entity.get::<Handle<StandardMaterial>>(), Some(&material));
+}
+
+#[test]
+fn test_explode_voxel_scene() {
+    use crate::{ExplodeVoxelSceneCommandsExt, VoxelExplodedView};
+    use bevy::hierarchy::BuildWorldChildren;
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let mut children = Vec::new();
+    let root = app
+        .world_mut()
+        .spawn(Transform::default())
+        .with_children(|parent| {
+            let mut group = parent.spawn(Transform::from_xyz(2.0, 0.0, 0.0));
+            group.with_children(|group| {
+                children.push(group.spawn(Transform::from_xyz(0.0, 0.0, -3.0)).id());
+            });
+            children.push(group.id());
+            children.push(parent.spawn(Transform::default()).id());
+        })
+        .id();
+    app.world_mut()
+        .commands()
+        .explode_voxel_scene(root, 1.0, 0.05);
+    app.world_mut().flush();
+    assert!(app.world().entity(root).contains::<VoxelExplodedView>());
+
+    let mut furthest = 0.0_f32;
+    for _ in 0..1000 {
+        app.update();
+        furthest = furthest.max(
+            app.world()
+                .get::<Transform>(children[0])
+                .expect("transform")
+                .translation
+                .z
+                .abs(),
+        );
+        let centered = app
+            .world()
+            .get::<Transform>(children[2])
+            .expect("transform");
+        assert_eq!(
+            centered.translation,
+            Vec3::ZERO,
+            "children at the origin stay"
+        );
+        if !app.world().entity(root).contains::<VoxelExplodedView>() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert!(
+        !app.world().entity(root).contains::<VoxelExplodedView>(),
+        "the animation ends"
+    );
+    assert!(furthest > 3.0, "nested children move apart");
+    assert_eq!(
+        app.world()
+            .get::<Transform>(children[0])
+            .expect("transform")
+            .translation,
+        Vec3::new(0.0, 0.0, -3.0)
+    );
+    assert_eq!(
+        app.world()
+            .get::<Transform>(children[1])
+            .expect("transform")
+            .translation,
+        Vec3::new(2.0, 0.0, 0.0),
+        "the scene is back in its original pose"
+    );
+}
+
+#[test]
+fn test_voxel_model_info() {
+    // `VoxelModelInfo` is named through the crate root, like the example on it does from outside the crate
+    use bevy::ecs::reflect::AppTypeRegistry;
+    let (mut app, handle) = load_dice_with_settings(VoxLoaderSettings::default());
+    let instance = app
+        .world_mut()
+        .spawn(VoxelModelInstance {
+            model: handle.clone(),
+            context: Handle::default(),
+        })
+        .id();
+    app.update();
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&handle)
+        .expect("dice model");
+    let info = app
+        .world()
+        .get::<VoxelModelInfo>(instance)
+        .expect("info is added to instances");
+    assert_eq!(info.name, model.name);
+    assert_eq!(info.size, model.data._size().as_uvec3());
+    assert_eq!(info.voxel_count, model.count_voxels());
+
+    let registry = app.world().resource::<AppTypeRegistry>().read();
+    for type_path in [
+        "bevy_vox_scene::load::components::VoxelModelInfo",
+        "bevy_vox_scene::load::components::VoxelSceneInstance",
+        "bevy_vox_scene::model::palette::VoxelPalette",
+        "bevy_vox_scene::model::palette::VoxelElement",
+        "bevy_vox_scene::model::lod::VoxelLod",
+    ] {
+        assert!(
+            registry.get_with_type_path(type_path).is_some(),
+            "{type_path} is registered"
+        );
+    }
+}
+
+#[test]
+fn test_shape_frames() {
+    use crate::{VoxelShapeFrame, VoxelShapeFrames};
+    let keyframe = |frame_index: u32, id: u128| VoxelShapeFrame {
+        frame_index,
+        model: Handle::weak_from_u128(id),
+    };
+    let frames = VoxelShapeFrames {
+        frames: vec![keyframe(2, 1), keyframe(5, 2), keyframe(9, 3)],
+    };
+    assert_eq!(frames.model_at(0), Some(&Handle::weak_from_u128(1)));
+    assert_eq!(frames.model_at(4), Some(&Handle::weak_from_u128(1)));
+    assert_eq!(frames.model_at(5), Some(&Handle::weak_from_u128(2)));
+    assert_eq!(frames.model_at(20), Some(&Handle::weak_from_u128(3)));
+    assert_eq!(frames.last_frame_index(), 9);
+    assert_eq!(VoxelShapeFrames { frames: vec![] }.model_at(0), None);
+}
+
+#[test]
+fn test_joint_kinds() {
+    use crate::load::parse_scene::joint_kind;
+    use crate::VoxelJointKind;
+    let kind = |name: &str| joint_kind(Some(&name.to_string()));
+    assert_eq!(kind("joint:hinge"), Some(VoxelJointKind::Hinge));
+    assert_eq!(kind("joint:slider:drawer"), Some(VoxelJointKind::Prismatic));
+    assert_eq!(
+        kind("joint:rope"),
+        Some(VoxelJointKind::Other("rope".to_string()))
+    );
+    assert_eq!(kind("door"), None);
+}
+
+#[test]
+fn test_connect_voxel_joints() {
+    use crate::{VoxelJoint, VoxelJointKind};
+    use bevy::hierarchy::BuildWorldChildren;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let instance = || VoxelModelInstance {
+        model: Handle::default(),
+        context: Handle::default(),
+    };
+    let mut bodies = Vec::new();
+    let mut joint = None;
+    app.world_mut()
+        .spawn(Transform::default())
+        .with_children(|parent| {
+            for x in [-1.0, 1.0, 10.0] {
+                bodies.push(
+                    parent
+                        .spawn((Transform::from_xyz(x, 0.0, 0.0), instance()))
+                        .id(),
+                );
+            }
+            joint = Some(
+                parent
+                    .spawn((Transform::default(), VoxelJoint::new(VoxelJointKind::Hinge)))
+                    .id(),
+            );
+        });
+    app.update();
+    let joint = app
+        .world()
+        .get::<VoxelJoint>(joint.expect("joint"))
+        .expect("joint component");
+    let mut connected = joint.bodies().expect("connected bodies");
+    connected.sort();
+    let mut expected = [bodies[0], bodies[1]];
+    expected.sort();
+    assert_eq!(connected, expected);
+}
+
+#[test]
+fn test_voxel_joints_as_physics_data() {
+    use crate::{VoxelJoint, VoxelJointKind};
+    use bevy::hierarchy::BuildWorldChildren;
+    use bevy::prelude::{Changed, Entity, Last, ResMut, Resource};
+    /// The joints a physics engine would create: the kind, the bodies, and the anchor and axis of the joint
+    #[derive(Resource, Default)]
+    struct CreatedJoints(Vec<(VoxelJointKind, [Entity; 2], Vec3, Vec3)>);
+
+    fn create_joints(
+        joints: Query<(&VoxelJoint, &Transform), Changed<VoxelJoint>>,
+        mut created: ResMut<CreatedJoints>,
+    ) {
+        for (joint, transform) in joints.iter() {
+            if let Some(bodies) = joint.bodies() {
+                created.0.push((
+                    joint.kind.clone(),
+                    bodies,
+                    transform.translation,
+                    *transform.right(),
+                ));
+            }
+        }
+    }
+
+    let mut app = App::new();
+    setup_app(&mut app);
+    app.init_resource::<CreatedJoints>()
+        .add_systems(Last, create_joints);
+    let instance = || VoxelModelInstance {
+        model: Handle::default(),
+        context: Handle::default(),
+    };
+    app.world_mut()
+        .spawn(Transform::from_xyz(0.0, 5.0, 0.0))
+        .with_children(|parent| {
+            parent.spawn((Transform::from_xyz(-1.0, 0.0, 0.0), instance()));
+            parent.spawn((Transform::from_xyz(1.0, 0.0, 0.0), instance()));
+            parent.spawn((
+                Transform::from_xyz(0.0, 0.5, 0.0).with_rotation(Quat::from_rotation_z(FRAC_PI_2)),
+                VoxelJoint::new(VoxelJointKind::Hinge),
+            ));
+        });
+    // a joint without two sibling models is left unconnected
+    let lonely = app
+        .world_mut()
+        .spawn(Transform::default())
+        .with_children(|parent| {
+            parent.spawn((Transform::default(), instance()));
+            parent.spawn((Transform::default(), VoxelJoint::new(VoxelJointKind::Fixed)));
+        })
+        .id();
+    app.update();
+
+    let created = &app.world().resource::<CreatedJoints>().0;
+    assert_eq!(
+        created.len(),
+        1,
+        "only the connected joint is created, once"
+    );
+    let (kind, _, anchor, axis) = &created[0];
+    assert_eq!(*kind, VoxelJointKind::Hinge);
+    assert!(anchor.distance(Vec3::new(0.0, 0.5, 0.0)) < 1e-5);
+    assert!(
+        axis.distance(Vec3::Y) < 1e-5,
+        "the hinge turns around the node's X axis"
+    );
+    let lonely_joint = app.world().get::<Children>(lonely).expect("children")[1];
+    assert_eq!(
+        app.world()
+            .get::<VoxelJoint>(lonely_joint)
+            .expect("joint")
+            .bodies(),
+        None
+    );
+
+    app.update();
+    assert_eq!(
+        app.world().resource::<CreatedJoints>().0.len(),
+        1,
+        "the joint is unchanged after it is connected"
+    );
+}
+
+#[test]
+fn test_settings_mesh_outer_faces() {
+    let (app, handle) = load_dice_with_settings(VoxLoaderSettings {
+        mesh_outer_faces: false,
+        ..Default::default()
+    });
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&handle)
+        .expect("dice model");
+    assert!(!model.data.mesh_outer_faces);
+    assert_eq!(model.data.padding(), 0);
+}
+
+#[test]
+fn test_model_content_hash() {
+    let hash = |settings: VoxLoaderSettings| {
+        let (app, handle) = load_dice_with_settings(settings);
+        app.world()
+            .resource::<Assets<VoxelModel>>()
+            .get(&handle)
+            .expect("dice model")
+            .content_hash()
+    };
+    let default = hash(VoxLoaderSettings::default());
+    assert_eq!(
+        default,
+        hash(VoxLoaderSettings {
+            mesh_outer_faces: false,
+            optimize_meshes: true,
+            ..Default::default()
+        }),
+        "Meshing settings don't change the hash"
+    );
+    assert_ne!(
+        default,
+        hash(VoxLoaderSettings {
+            voxel_size: 0.5,
+            ..Default::default()
+        }),
+        "The voxel size changes the hash"
+    );
+}
+
+#[async_std::test]
+async fn test_file_index_content_hash() {
+    let mut app = App::new();
+    setup_app(&mut app);
+    let handle = app
+        .world()
+        .resource::<AssetServer>()
+        .load_untyped_async("test.vox#index")
+        .await
+        .expect("Loaded index")
+        .typed::<VoxelFileIndex>();
+    let index = app
+        .world()
+        .resource::<Assets<VoxelFileIndex>>()
+        .get(&handle)
+        .expect("index");
+    // pinned, so that the hash is known to be stable across platforms and releases
+    assert_eq!(index.content_hash, 0xbf23_9564_ef4e_6b99);
+}
+
+#[test]
+fn test_mesh_cache() {
+    let cache = std::env::temp_dir().join(format!("vox_mesh_cache_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&cache);
+    let load = || {
+        let (app, handle) = load_dice_with_settings(VoxLoaderSettings {
+            mesh_cache: Some(cache.clone()),
+            mesh_attributes: MeshAttributeConfig {
+                palette_index: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let model = app
+            .world()
+            .resource::<Assets<VoxelModel>>()
+            .get(&handle)
+            .expect("dice model");
+        let mesh = app
+            .world()
+            .resource::<Assets<Mesh>>()
+            .get(&model.mesh)
+            .expect("mesh");
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("Mesh has positions");
+        };
+        let Some(VertexAttributeValues::Uint32(palette_indices)) =
+            mesh.attribute(ATTRIBUTE_PALETTE_INDEX)
+        else {
+            panic!("Mesh has palette indices");
+        };
+        let indices: Vec<usize> = mesh.indices().expect("indices").iter().collect();
+        (positions.clone(), palette_indices.clone(), indices)
+    };
+    let meshed = load();
+    let cached_files = std::fs::read_dir(&cache).expect("cache directory").count();
+    assert_eq!(cached_files, 1, "The mesh is written to the cache");
+    assert_eq!(load(), meshed, "The cached mesh matches the meshed one");
+    let _ = std::fs::remove_dir_all(&cache);
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_hollow_and_solidify() {
+    let mut data = VoxelData::new(UVec3::splat(7), true, 1.0);
+    for x in 0..7 {
+        for y in 0..7 {
+            for z in 0..7 {
+                data.set_voxel(Voxel(if x == 3 { 2 } else { 1 }), UVec3::new(x, y, z));
+            }
+        }
+    }
+    let shell = data.hollow(1);
+    assert_eq!(shell.count_voxels(), 7 * 7 * 7 - 5 * 5 * 5);
+    assert_eq!(shell.get_voxel_at_point(IVec3::splat(3)), Ok(Voxel::EMPTY));
+    assert_eq!(shell.get_voxel_at_point(IVec3::new(3, 0, 3)), Ok(Voxel(2)));
+    let thick = data.hollow(2);
+    assert_eq!(thick.count_voxels(), 7 * 7 * 7 - 3 * 3 * 3);
+
+    let solid = shell.solidify();
+    assert_eq!(solid.count_voxels(), 7 * 7 * 7, "the cavity is filled");
+    assert_eq!(
+        solid.get_voxel_at_point(IVec3::new(3, 1, 3)),
+        Ok(Voxel(2)),
+        "cavities take the nearest voxel of the shell"
+    );
+
+    // a cavity open to the outside is left alone
+    let mut open = shell.clone();
+    open.set_voxel(Voxel::EMPTY, UVec3::new(3, 3, 0));
+    assert_eq!(open.solidify().count_voxels(), open.count_voxels());
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_fracture() {
+    use crate::{VoxelFracture, VoxelShards};
+    let (app, handle) = load_dice_with_settings(VoxLoaderSettings {
+        fracture_shards: 4,
+        ..Default::default()
+    });
+    let models = app.world().resource::<Assets<VoxelModel>>();
+    let dice = models.get(&handle).expect("dice model");
+    let shards = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelShards>("test.vox#outer-group/inner-group/dice@shards")
+        .expect("shards handle");
+    let shards = app
+        .world()
+        .resource::<Assets<VoxelShards>>()
+        .get(&shards)
+        .expect("shards");
+    assert_eq!(shards.shards.len(), 4);
+    let total: usize = shards
+        .shards
+        .iter()
+        .map(|(model, _)| models.get(model).expect("shard model").count_voxels())
+        .sum();
+    assert_eq!(total, dice.count_voxels(), "every voxel lands in one shard");
+    for (model, offset) in shards.shards.iter() {
+        let shard = models.get(model).expect("shard model");
+        assert!(shard.size().cmple(dice.size()).all());
+        assert!(offset.abs().cmple(dice.model_size() * 0.5).all());
+    }
+
+    let halves = dice.fracture(&VoxelFracture::Points(vec![
+        IVec3::new(0, 0, 0),
+        dice.size() - IVec3::ONE,
+    ]));
+    assert_eq!(halves.len(), 2);
+    assert_eq!(
+        halves[0].data.count_voxels() + halves[1].data.count_voxels(),
+        dice.count_voxels()
+    );
+    assert!(halves[0].offset.x < halves[1].offset.x);
+    assert_eq!(
+        dice.fracture(&VoxelFracture::Count { count: 3, seed: 7 })
+            .iter()
+            .map(|shard| shard.data.count_voxels())
+            .collect::<Vec<usize>>(),
+        dice.fracture(&VoxelFracture::Count { count: 3, seed: 7 })
+            .iter()
+            .map(|shard| shard.data.count_voxels())
+            .collect::<Vec<usize>>(),
+        "fracturing is deterministic"
+    );
+}
+
+#[test]
+fn test_settings_optimize_meshes() {
+    let triangles = |optimize_meshes: bool| {
+        let (app, handle) = load_dice_with_settings(VoxLoaderSettings {
+            optimize_meshes,
+            ..Default::default()
+        });
+        let mesh = app
+            .world()
+            .resource::<Assets<VoxelModel>>()
+            .get(&handle)
+            .map(|model| model.mesh.clone())
+            .expect("dice mesh");
+        let mesh = app
+            .world()
+            .resource::<Assets<Mesh>>()
+            .get(&mesh)
+            .expect("mesh")
+            .clone();
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("Mesh has positions");
+        };
+        let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0)
+        else {
+            panic!("Mesh has uvs");
+        };
+        let indices: Vec<usize> = mesh.indices().expect("indices").iter().collect();
+        assert!(indices.iter().all(|index| *index < positions.len()));
+        let mut triangles: Vec<String> = indices
+            .chunks(3)
+            .map(|triangle| {
+                let mut corners: Vec<String> = triangle
+                    .iter()
+                    .map(|index| format!("{:?} {:?}", positions[*index], uvs[*index]))
+                    .collect();
+                // triangles may start from any corner, but keep their winding
+                let first = (0..3)
+                    .min_by_key(|corner| corners[*corner].clone())
+                    .unwrap();
+                corners.rotate_left(first);
+                corners.join(", ")
+            })
+            .collect();
+        triangles.sort();
+        (positions.len(), triangles)
+    };
+    let (vertices, expected) = triangles(false);
+    let (welded_vertices, optimized) = triangles(true);
+    assert!(welded_vertices < vertices, "identical vertices are welded");
+    assert_eq!(optimized, expected, "the same triangles are drawn");
+}
+
+#[test]
+fn test_settings_emission_strength() {
+    let emission = |strength: f32| {
+        let (app, _) = load_dice_with_settings(VoxLoaderSettings {
+            emission_strength: strength,
+            ..Default::default()
+        });
+        let context = app
+            .world()
+            .resource::<AssetServer>()
+            .get_handle::<VoxelContext>("test.vox#voxel-context")
+            .expect("voxel context");
+        app.world()
+            .resource::<Assets<VoxelContext>>()
+            .get(&context)
+            .expect("context")
+            .palette
+            .elements
+            .iter()
+            .map(|element| element.emission)
+            .sum::<f32>()
+    };
+    let weak = emission(1.0);
+    let strong = emission(4.0);
+    assert!(weak > 0.0, "test.vox contains emissive voxels");
+    assert!((strong - weak * 4.0).abs() <= weak * 0.0001);
+}
+
+#[test]
+fn test_downsampled() {
+    let mut data = VoxelData::new(UVec3::new(9, 8, 8), true, 0.5);
+    for x in 0..8 {
+        for y in 0..8 {
+            for z in 0..8 {
+                data.set_voxel(Voxel(3), UVec3::new(x, y, z));
+            }
+        }
+    }
+    data.set_voxel(Voxel(5), UVec3::ZERO);
+    data.set_voxel(Voxel(7), UVec3::new(8, 0, 0));
+    let downsampled = data.downsampled(8);
+    assert_eq!(downsampled._size(), IVec3::new(2, 1, 1));
+    assert_eq!(downsampled.voxel_size, 4.0);
+    let voxel_at = |position: UVec3| -> Voxel {
+        let index = downsampled.shape.linearize((position + UVec3::ONE).into()) as usize;
+        downsampled.voxels[index].clone().into()
+    };
+    assert_eq!(voxel_at(UVec3::ZERO), Voxel(3));
+    assert_eq!(voxel_at(UVec3::X), Voxel(7));
+
+    // the impostor overhangs the model by 7 voxels along x, so is shifted back by half of that
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into(); 7]);
+    let center = |mesh: &Mesh| Vec3::from(mesh.compute_aabb().expect("aabb").center);
+    let (mesh, _) = data.remesh(&palette);
+    let impostor = crate::model::lod::impostor_mesh(&data, 8, &palette);
+    assert_eq!(center(&impostor), center(&mesh));
+}
+
+#[test]
+fn test_chunk_occlusion() {
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let mut data = VoxelData::new(UVec3::splat(6), true, 1.0);
+    for x in 0..6 {
+        for y in 0..6 {
+            for z in 0..6 {
+                data.set_voxel(Voxel(1), UVec3::new(x, y, z));
+            }
+        }
+    }
+    let flags = data.chunk_occlusion(&palette, 2);
+    assert_eq!(flags.len(), 27);
+    assert!(flags.iter().all(|chunk| chunk.solid && !chunk.empty));
+    let enclosed: Vec<UVec3> = flags
+        .iter()
+        .filter(|chunk| chunk.enclosed)
+        .map(|chunk| chunk.chunk)
+        .collect();
+    assert_eq!(enclosed, vec![UVec3::ONE]);
+
+    data.set_voxel(Voxel::EMPTY, UVec3::new(2, 4, 2));
+    let flags = data.chunk_occlusion(&palette, 2);
+    assert!(!flags[13].enclosed, "neighbor above is no longer solid");
+    assert!(flags[13].needs_mesh());
+
+    let flags = VoxelData::new(UVec3::new(3, 2, 2), false, 1.0).chunk_occlusion(&palette, 2);
+    assert_eq!(flags.len(), 2);
+    assert!(flags.iter().all(|chunk| chunk.empty && !chunk.needs_mesh()));
+    assert_eq!(flags[1].size, UVec3::new(1, 2, 2));
+}
+
+#[test]
+fn test_directional_occlusion() {
+    use crate::model::DirectionalOcclusion;
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let mut data = VoxelData::new(UVec3::new(3, 3, 3), true, 1.0)
+        .with_directional_occlusion(DirectionalOcclusion::new([Vec3::Y]));
+    for x in 0..3 {
+        for z in 0..3 {
+            data.set_voxel(Voxel(1), UVec3::new(x, 0, z));
+            data.set_voxel(Voxel(1), UVec3::new(x, 2, z));
+        }
+    }
+    let (mesh, _) = data.remesh(&palette);
+    let Some(VertexAttributeValues::Float32(occlusion)) =
+        mesh.attribute(crate::ATTRIBUTE_DIRECTIONAL_OCCLUSION)
+    else {
+        panic!("No directional occlusion attribute");
+    };
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("No positions");
+    };
+    let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+    else {
+        panic!("No normals");
+    };
+    assert_eq!(occlusion.len(), positions.len());
+    for ((position, normal), lit) in positions.iter().zip(normals).zip(occlusion) {
+        if normal[1] > 0.0 {
+            // the top of the roof is lit, while the floor is in its shadow
+            let expected = if position[1] > 2.0 { 1.0 } else { 0.0 };
+            assert_eq!(*lit, expected);
+        } else if normal[1] < 0.0 {
+            assert_eq!(*lit, 0.0);
+        }
+    }
+}
+
+#[test]
+fn test_palette_from_image() {
+    use bevy::{
+        color::Color,
+        render::{
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+            texture::Image,
+        },
+    };
+    let image = |width: u32, height: u32, format: TextureFormat| {
+        let data = (0..width * height)
+            .flat_map(|i| [i as u8, 0, 255 - i as u8, 255])
+            .collect();
+        Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            format,
+            RenderAssetUsages::default(),
+        )
+    };
+    let palette = VoxelPalette::from_image(&image(256, 1, TextureFormat::Rgba8UnormSrgb))
+        .expect("palette from strip");
+    assert_eq!(palette.elements[0].color, Color::srgba_u8(0, 0, 255, 255));
+    assert_eq!(palette.elements[17].color, Color::srgba_u8(17, 0, 238, 255));
+    let palette = VoxelPalette::from_image(&image(16, 16, TextureFormat::Bgra8UnormSrgb))
+        .expect("palette from grid");
+    assert_eq!(palette.elements[1].color, Color::srgba_u8(254, 0, 1, 255));
+    assert!(VoxelPalette::from_image(&image(8, 8, TextureFormat::Rgba8UnormSrgb)).is_none());
+}
+
+#[test]
+fn test_palette_quantization() {
+    use crate::ColorMetric;
+    use bevy::color::{palettes::css, Color};
+    // clusters of slightly varying reds, greens and blues
+    let colors: Vec<Color> = (0..30)
+        .map(|i| {
+            let shade = (i % 10) as f32 * 0.01;
+            match i / 10 {
+                0 => Color::srgb(0.9 + shade, shade, shade),
+                1 => Color::srgb(shade, 0.9 + shade, shade),
+                _ => Color::srgb(shade, shade, 0.9 + shade),
+            }
+        })
+        .collect();
+    for metric in [
+        ColorMetric::LinearRgb,
+        ColorMetric::Srgb,
+        ColorMetric::Oklab,
+    ] {
+        let palette = VoxelPalette::quantized(&colors, 3, metric);
+        let voxels = palette.fit_colors(&colors, metric);
+        assert_eq!(voxels.len(), colors.len());
+        assert!(voxels.iter().all(|voxel| voxel.0 >= 1 && voxel.0 <= 3));
+        assert!(voxels[0..10].iter().all(|voxel| *voxel == voxels[0]));
+        assert!(voxels[10..20].iter().all(|voxel| *voxel == voxels[10]));
+        assert_ne!(voxels[0], voxels[10], "each cluster gets its own entry");
+        assert_ne!(voxels[10], voxels[20]);
+    }
+
+    // dark colors that round to the same 8 bit linear color are still fitted to their own entries
+    let darks = vec![Color::srgb_u8(1, 1, 1), Color::srgb_u8(3, 3, 3)];
+    assert_eq!(
+        VoxelPalette::from_colors(darks.clone()).fit_colors(&darks, ColorMetric::Srgb),
+        vec![Voxel(1), Voxel(2)]
+    );
+
+    let blues = VoxelPalette::from_colors(vec![css::BLUE.into(), css::NAVY.into()]);
+    let extended = blues.extended(
+        &[css::BLUE.into(), css::LIME.into()],
+        &[Voxel(3), Voxel(4)],
+        0.05,
+        ColorMetric::Oklab,
+    );
+    assert_eq!(
+        extended.closest_voxel_by_color(css::LIME.into(), ColorMetric::Oklab),
+        Voxel(3),
+        "only the missing color is added"
+    );
+    assert_eq!(
+        extended.closest_voxel_by_color(css::BLUE.into(), ColorMetric::Oklab),
+        Voxel(1)
+    );
+    assert_eq!(extended.elements[3].color, VoxelElement::default().color);
+}
+
+#[cfg(feature = "point_cloud")]
+#[test]
+fn test_point_cloud_voxelization() {
+    use crate::{load::point_cloud::PointCloud, ColorMetric, VoxPointCloudSettings};
+    let ascii = b"ply\nformat ascii 1.0\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\nelement face 0\nproperty list uchar int vertex_indices\nend_header\n0.1 0.1 0.1 255 0 0\n0.2 0.3 0.4 255 0 0\n2.5 0.5 0.5 0 0 255\n0.5 2.5 0.5 0 0 255\n";
+    let cloud = PointCloud::from_ply(ascii).expect("parsed ascii ply");
+    assert_eq!(cloud.positions.len(), 4);
+    assert_eq!(cloud.positions[2], Vec3::new(2.5, 0.5, 0.5));
+    assert_eq!(cloud.colors[0], Color::srgb(1.0, 0.0, 0.0));
+
+    let mut binary = b"ply\nformat binary_little_endian 1.0\nelement vertex 2\nproperty float x\nproperty float y\nproperty float z\nend_header\n".to_vec();
+    for value in [1.0f32, 2.0, 3.0, -1.0, 0.0, 0.5] {
+        binary.extend_from_slice(&value.to_le_bytes());
+    }
+    let cloud = PointCloud::from_ply(&binary).expect("parsed binary ply");
+    assert_eq!(
+        cloud.positions,
+        vec![Vec3::new(1.0, 2.0, 3.0), Vec3::new(-1.0, 0.0, 0.5)]
+    );
+    assert!(PointCloud::from_ply(&binary[..binary.len() - 1]).is_err());
+
+    let cloud = PointCloud::from_xyz(
+        "# scan\n0.1 0.1 0.1 255 0 0\n0.2 0.3 0.4 255 0 0\n2.5 0.5 0.5 0 0 255\n",
+    )
+    .expect("parsed xyz");
+    let settings = VoxPointCloudSettings::default();
+    let (data, _) = settings
+        .voxelize(&cloud, &VoxLoaderSettings::default())
+        .expect("voxelized");
+    assert_eq!(data._size(), IVec3::new(3, 1, 1));
+    let solid = |data: &VoxelData| {
+        data.voxels
+            .iter()
+            .filter(|voxel| **voxel != RawVoxel::EMPTY)
+            .cloned()
+            .collect::<Vec<RawVoxel>>()
+    };
+    let voxels = solid(&data);
+    assert_eq!(voxels.len(), 2);
+    assert_ne!(voxels[0], voxels[1], "red and blue voxels are kept apart");
+
+    let dense = VoxPointCloudSettings {
+        density_threshold: 2,
+        ..Default::default()
+    };
+    let (data, palette) = dense
+        .voxelize(&cloud, &VoxLoaderSettings::default())
+        .expect("voxelized");
+    assert_eq!(data._size(), IVec3::ONE, "the lone blue point is dropped");
+    let voxels = solid(&data);
+    assert_eq!(voxels.len(), 1);
+    let red = palette.closest_voxel_by_color(Color::srgb(1.0, 0.0, 0.0), ColorMetric::Oklab);
+    assert_eq!(Voxel::from(voxels[0].clone()), red);
+    assert!(VoxPointCloudSettings {
+        density_threshold: 3,
+        ..Default::default()
+    }
+    .voxelize(&cloud, &VoxLoaderSettings::default())
+    .is_none());
+}
+
+#[test]
+fn test_procedural_palettes() {
+    use bevy::color::{palettes::css, Color, Hsla};
+    let palette = VoxelPalette::gradient(&[css::RED.into(), css::BLUE.into()], &[0, 10]);
+    assert_eq!(palette.elements[0].color.to_linear(), css::RED.into());
+    assert_eq!(palette.elements[10].color.to_linear(), css::BLUE.into());
+    assert_eq!(
+        palette.elements[255].color.to_linear(),
+        css::BLUE.into(),
+        "The last stop's color continues to the end of the palette"
+    );
+    let palette = VoxelPalette::gradient(&[css::BLUE.into(), css::RED.into()], &[255, 0]);
+    assert_eq!(
+        palette.elements[0].color.to_linear(),
+        css::RED.into(),
+        "Unsorted stops are sorted"
+    );
+    assert_eq!(palette.elements[255].color.to_linear(), css::BLUE.into());
+    let middle = palette.elements[127].color.to_linear();
+    assert!(middle.red > 0.0 && middle.blue > 0.0);
+    let palette = VoxelPalette::gradient(&[css::GREEN.into()], &[4]);
+    assert_eq!(palette.elements[3].color, VoxelElement::default().color);
+    assert!(palette.elements[4..]
+        .iter()
+        .all(|element| element.color.to_linear() == css::GREEN.into()));
+    let ramp = VoxelPalette::hue_ramp(4, 1.0, 0.5);
+    assert!((Hsla::from(ramp.elements[1].color).hue - 90.0).abs() < 0.01);
+    let glass = VoxelElement::new(Color::WHITE)
+        .with_glass(1.3)
+        .with_emission(2.0);
+    assert_eq!(glass.translucency, 1.0);
+    let palette = ramp.with_element(Voxel(3), glass);
+    assert_eq!(palette.elements[2].emission, 2.0);
+    assert_eq!(palette.indices_of_refraction[2], Some(1.3));
+}
+
+#[test]
+fn test_palette_summary() {
+    use crate::MaterialProperty;
+    use bevy::color::Color;
+    let palette = VoxelPalette::new(vec![
+        VoxelElement::new(Color::WHITE),
+        VoxelElement::new(Color::WHITE).with_emission(4.0),
+        VoxelElement::new(Color::WHITE).with_glass(1.5),
+        VoxelElement::new(Color::WHITE).with_metalness(1.0),
+    ]);
+    let summary = palette.summary();
+    assert_eq!(summary.emission, MaterialProperty::VariesPerElement);
+    assert_eq!(summary.roughness, MaterialProperty::Constant(0.5));
+    assert_eq!(summary.emissive, vec![Voxel(2)]);
+    assert_eq!(summary.translucent, vec![Voxel(3)]);
+    assert_eq!(summary.metallic, vec![Voxel(4)]);
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_set_voxel_element() {
+    use crate::{model::palette_texels::PendingPaletteTexelWrites, SetVoxelElementCommandsExt};
+    use bevy::{ecs::world::World, math::UVec2, render::texture::Image};
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::new(vec![
+        VoxelElement::new(Color::WHITE),
+        VoxelElement::new(Color::WHITE).with_emission(2.0),
+    ]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let material = |world: &World| {
+        let context = world
+            .resource::<Assets<VoxelContext>>()
+            .get(&context)
+            .expect("context");
+        world
+            .resource::<Assets<StandardMaterial>>()
+            .get(&context.transmissive_material)
+            .expect("material")
+            .clone()
+    };
+    let before = material(world);
+    // a model's own material, derived from the context's
+    let mut model_material = before.clone();
+    model_material.ior = 1.3;
+    let model_material = world
+        .resource_mut::<Assets<StandardMaterial>>()
+        .add(model_material);
+
+    world.commands().set_voxel_element(
+        context.clone(),
+        Voxel(2),
+        VoxelElement::new(bevy::color::palettes::css::RED.into()).with_emission(2.0),
+    );
+    world.flush();
+    let writes = world.resource::<PendingPaletteTexelWrites>().0.clone();
+    assert_eq!(
+        writes.len(),
+        2,
+        "one write for the color and one for the emission"
+    );
+    assert_eq!(
+        writes[0].image,
+        before.base_color_texture.clone().expect("color").id()
+    );
+    assert_eq!(writes[0].texel, UVec2::new(1, 0));
+    assert_eq!(writes[0].data, vec![255, 0, 0, 255]);
+    let color = world
+        .resource::<Assets<Image>>()
+        .get(&before.base_color_texture.clone().expect("color"))
+        .expect("color image");
+    assert_eq!(
+        color.data[4..8],
+        [255, 0, 0, 255],
+        "the CPU copy of the texture is updated too"
+    );
+    assert_eq!(
+        material(world).base_color_texture,
+        before.base_color_texture,
+        "the textures are kept"
+    );
+    let palette = world
+        .resource::<Assets<VoxelContext>>()
+        .get(&context)
+        .expect("context")
+        .palette
+        .clone();
+    let regenerated = palette.create_material(&mut world.resource_mut::<Assets<Image>>());
+    let emission = world
+        .resource::<Assets<Image>>()
+        .get(&regenerated.emissive_texture.expect("emission"))
+        .expect("emission image");
+    let size = writes[1].data.len();
+    assert_eq!(
+        emission.data[size..size * 2],
+        writes[1].data,
+        "texels are encoded as in the regenerated textures"
+    );
+
+    world.commands().set_voxel_element(
+        context.clone(),
+        Voxel(1),
+        VoxelElement::new(Color::WHITE).with_metalness(1.0),
+    );
+    world.flush();
+    assert_eq!(world.resource::<PendingPaletteTexelWrites>().0.len(), 2);
+    let after = material(world);
+    assert!(
+        before.metallic_roughness_texture.is_none() && after.metallic_roughness_texture.is_some(),
+        "the textures are regenerated when their formats change"
+    );
+    let model_material = world
+        .resource::<Assets<StandardMaterial>>()
+        .get(&model_material)
+        .expect("model material");
+    assert_eq!(
+        model_material.metallic_roughness_texture, after.metallic_roughness_texture,
+        "materials derived from the context switch to the new textures"
+    );
+    assert_eq!(model_material.ior, 1.3, "and keep their own settings");
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_voxel_cursor() {
+    use crate::VoxelCursor;
+    use bevy::{ecs::entity::Entity, math::Ray3d};
+    let (mut app, handle) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    let instance = VoxelModelInstance {
+        model: handle,
+        context,
+    };
+    let near = app
+        .world_mut()
+        .spawn((instance.clone(), GlobalTransform::IDENTITY))
+        .id();
+    let far = app
+        .world_mut()
+        .spawn((
+            instance,
+            GlobalTransform::from_translation(Vec3::new(0.0, 0.0, -40.0)),
+        ))
+        .id();
+    let camera = app.world_mut().spawn(GlobalTransform::IDENTITY).id();
+    let cursor = app.world_mut().spawn(VoxelCursor::new(camera)).id();
+    // without a camera to cast from, nothing is targeted
+    app.update();
+    assert_eq!(
+        app.world()
+            .get::<VoxelCursor>(cursor)
+            .expect("cursor")
+            .target,
+        None
+    );
+
+    let ray = Ray3d::new(Vec3::new(0.0, 0.0, 20.0), Vec3::NEG_Z);
+    let mut instances = app
+        .world_mut()
+        .query::<(Entity, &VoxelModelInstance, &GlobalTransform)>();
+    let world = app.world();
+    let models = world.resource::<Assets<VoxelModel>>();
+    let mut cursor = VoxelCursor::new(camera);
+    cursor.retarget(Some(ray), instances.iter(world), models);
+    let target = cursor.target.clone().expect("the cursor targets the dice");
+    assert_eq!(target.entity, near, "the nearest instance is targeted");
+    assert_eq!(target.normal, IVec3::Z);
+    assert_ne!(target.voxel, Voxel::EMPTY);
+
+    let mut cursor = VoxelCursor::new(camera).with_instances([far]);
+    cursor.retarget(Some(ray), instances.iter(world), models);
+    assert_eq!(
+        cursor.target.map(|target| target.entity),
+        Some(far),
+        "instances outside of the set are ignored"
+    );
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_voxel_interactor() {
+    use crate::{VoxelInteraction, VoxelInteractionKind, VoxelInteractionPlugin, VoxelInteractor};
+    use bevy::{
+        ecs::event::Events,
+        input::{mouse::MouseButton, ButtonInput},
+    };
+    let (mut app, handle) = load_dice_with_settings(VoxLoaderSettings::default());
+    app.add_plugins(VoxelInteractionPlugin)
+        .init_resource::<ButtonInput<MouseButton>>();
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    let dice = app
+        .world_mut()
+        .spawn((
+            VoxelModelInstance {
+                model: handle.clone(),
+                context,
+            },
+            GlobalTransform::IDENTITY,
+        ))
+        .id();
+    let interactor = app
+        .world_mut()
+        .spawn((
+            VoxelInteractor::new(100.0).with_palette(vec![Voxel(2)]),
+            GlobalTransform::from(
+                Transform::from_xyz(0.0, 0.0, 20.0).looking_at(Vec3::ZERO, Vec3::Y),
+            ),
+        ))
+        .id();
+    app.update();
+    let target = app
+        .world()
+        .get::<VoxelInteractor>(interactor)
+        .and_then(|interactor| interactor.target.clone())
+        .expect("the interactor targets the dice");
+    assert_eq!(target.entity, dice);
+    assert_eq!(target.normal, IVec3::Z);
+
+    app.world_mut()
+        .resource_mut::<ButtonInput<MouseButton>>()
+        .press(MouseButton::Left);
+    app.update();
+    app.world_mut()
+        .resource_mut::<ButtonInput<MouseButton>>()
+        .clear();
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&handle)
+        .expect("dice model");
+    assert_eq!(
+        model.get_voxel_at_point(target.voxel_coord),
+        Ok(Voxel::EMPTY)
+    );
+    let events: Vec<VoxelInteraction> = app
+        .world_mut()
+        .resource_mut::<Events<VoxelInteraction>>()
+        .drain()
+        .collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, VoxelInteractionKind::Remove);
+    assert_eq!(events[0].voxel, target.voxel);
+
+    app.update();
+    let target = app
+        .world()
+        .get::<VoxelInteractor>(interactor)
+        .and_then(|interactor| interactor.target.clone())
+        .expect("the interactor targets the dice");
+    app.world_mut()
+        .resource_mut::<ButtonInput<MouseButton>>()
+        .press(MouseButton::Right);
+    app.update();
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&handle)
+        .expect("dice model");
+    assert_eq!(
+        model.get_voxel_at_point(target.adjacent_coord()),
+        Ok(Voxel(2)),
+        "the selected voxel is placed in front of the targeted face"
+    );
+}
+
+#[test]
+fn test_voxel_character_controller() {
+    use crate::{VoxelCharacterController, VoxelGrid};
+    // a floor below y = 0, with a one voxel ledge from x = 3
+    let is_solid = |cell: IVec3| cell.y < 0 || (cell.x >= 3 && cell.y == 0);
+    let grid = VoxelGrid::default();
+    let aabb = |min: Vec3| Aabb3d::new(min + Vec3::new(0.4, 0.9, 0.4), Vec3::new(0.4, 0.9, 0.4));
+
+    let falling = VoxelCharacterController::default().move_and_slide(
+        &grid,
+        is_solid,
+        aabb(Vec3::new(1.1, 0.5, 0.1)),
+        Vec3::new(0.0, -1.0, 0.0),
+    );
+    assert!(falling.grounded);
+    assert!((falling.motion.y + 0.5).abs() < 1e-4, "lands on the floor");
+
+    let walking = VoxelCharacterController::default().move_and_slide(
+        &grid,
+        is_solid,
+        aabb(Vec3::new(1.1, 0.0, 0.1)),
+        Vec3::new(2.0, -0.1, 0.5),
+    );
+    assert!(walking.grounded);
+    assert!(walking.hit_wall);
+    assert!(!walking.stepped);
+    assert!((walking.motion.x - 1.1).abs() < 1e-4, "stops at the ledge");
+    assert!(
+        (walking.motion.z - 0.5).abs() < 1e-4,
+        "slides along the ledge"
+    );
+
+    let stepping = VoxelCharacterController::new(1.1).move_and_slide(
+        &grid,
+        is_solid,
+        aabb(Vec3::new(1.1, 0.0, 0.1)),
+        Vec3::new(2.0, -0.1, 0.0),
+    );
+    assert!(stepping.stepped);
+    assert!(!stepping.hit_wall);
+    assert!((stepping.motion.x - 2.0).abs() < 1e-4);
+    assert!(
+        (stepping.motion.y - 1.0).abs() < 1e-4,
+        "stands on the ledge"
+    );
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_collider_filter() {
+    use crate::{ColliderFilter, VoxelData};
+    let filter = ColliderFilter::default()
+        .with_excluded(Voxel(2))
+        .with_groups(Voxel(3), 0b10);
+    let mut data = VoxelData::new(UVec3::new(5, 1, 1), true, 1.0).with_collider_filter(filter);
+    for (x, voxel) in [Voxel(1), Voxel(1), Voxel(2), Voxel(3)]
+        .into_iter()
+        .enumerate()
+    {
+        data.set_voxel(voxel, UVec3::new(x as u32, 0, 0));
+    }
+    assert!(data.collides_at(IVec3::ZERO));
+    assert!(
+        !data.collides_at(IVec3::new(2, 0, 0)),
+        "grass doesn't collide"
+    );
+    assert!(!data.collides_at(IVec3::new(4, 0, 0)));
+    let boxes = data.collider_boxes();
+    assert_eq!(
+        boxes.len(),
+        2,
+        "neighboring voxels of the same groups are merged"
+    );
+    assert_eq!(boxes[0].groups, 1);
+    assert_eq!(Vec3::from(boxes[0].aabb.min), Vec3::new(-2.5, -0.5, -0.5));
+    assert_eq!(Vec3::from(boxes[0].aabb.max), Vec3::new(-0.5, 0.5, 0.5));
+    assert_eq!(boxes[1].groups, 0b10);
+    assert_eq!(Vec3::from(boxes[1].aabb.min), Vec3::new(0.5, -0.5, -0.5));
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_world_collider_filter() {
+    use crate::{ColliderFilter, VoxelCharacterController, VoxelWorld};
+    let mut world = VoxelWorld::new(Handle::default(), UVec3::splat(8), 1.0)
+        .with_collider_filter(ColliderFilter::default().with_excluded(Voxel(7)));
+    world.fill(IVec3::new(0, -1, 0), IVec3::new(8, 0, 8), Voxel(1));
+    // a wall of grass
+    world.fill(IVec3::new(3, 0, 0), IVec3::new(4, 2, 8), Voxel(7));
+    assert!(world.is_solid(IVec3::new(3, 0, 0)));
+    assert!(!world.collides(IVec3::new(3, 0, 0)));
+    let aabb = Aabb3d::new(Vec3::new(1.5, 0.9, 1.5), Vec3::new(0.4, 0.9, 0.4));
+    let result = world.move_and_slide(
+        &VoxelCharacterController::default(),
+        aabb,
+        Vec3::new(3.0, 0.0, 0.0),
+    );
+    assert!(!result.hit_wall, "the character walks through the grass");
+    assert!((result.motion.x - 3.0).abs() < 1e-4);
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_raycast_audio_tag() {
+    use crate::{VoxelAudioMaterials, VoxelContext};
+    use bevy::math::{Dir3, Ray3d};
+    let mut data = VoxelData::new(UVec3::splat(4), true, 1.0);
+    data.set_voxel(Voxel(7), UVec3::new(2, 1, 1));
+    let xform = GlobalTransform::default();
+    let hit = data
+        .raycast(
+            Ray3d::new(Vec3::new(-10.0, -0.5, -0.5), Dir3::X),
+            &xform,
+            100.0,
+        )
+        .expect("ray hits the voxel");
+    assert_eq!(hit.voxel_coord, IVec3::new(2, 1, 1));
+    assert_eq!(hit.voxel, Voxel(7));
+    assert_eq!(hit.normal, IVec3::NEG_X);
+    assert!((hit.distance - 10.0).abs() < 0.001);
+    assert!(data
+        .raycast(
+            Ray3d::new(Vec3::new(-10.0, -0.5, -0.5), Dir3::X),
+            &xform,
+            5.0
+        )
+        .is_none());
+    assert!(data
+        .raycast(
+            Ray3d::new(Vec3::new(-10.0, 1.5, -0.5), Dir3::X),
+            &xform,
+            100.0
+        )
+        .is_none());
+
+    let context = VoxelContext {
+        palette: VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]),
+        audio_materials: VoxelAudioMaterials::default().with_tag(Voxel(7), "metal"),
+        opaque_material: Handle::default(),
+        transmissive_material: Handle::default(),
+    };
+    assert_eq!(hit.audio_tag(&context), Some("metal"));
+}
+
+#[test]
+fn test_loaded_audio_materials() {
+    let (app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    let context = app
+        .world()
+        .resource::<Assets<VoxelContext>>()
+        .get(&context)
+        .expect("context");
+    assert_eq!(context.audio_materials.tag(&Voxel(1)), Some("diffuse"));
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_surface_normal_at() {
+    let mut data = VoxelData::new(UVec3::splat(5), true, 1.0);
+    for x in 0..5 {
+        for z in 0..5 {
+            data.set_voxel(Voxel(1), UVec3::new(x, 0, z));
+            data.set_voxel(Voxel(1), UVec3::new(x, 1, z));
+        }
+    }
+    let normal = data
+        .surface_normal_at(IVec3::new(2, 1, 2))
+        .expect("floor normal");
+    assert!(normal.abs_diff_eq(Vec3::Y, 0.001));
+    // a step up along +X tilts the normal away from it
+    data.set_voxel(Voxel(1), UVec3::new(3, 2, 2));
+    let normal = data
+        .surface_normal_at(IVec3::new(2, 1, 2))
+        .expect("step normal");
+    assert!(normal.x < 0.0 && normal.y > 0.0);
+    assert!(data.surface_normal_at(IVec3::new(9, 0, 0)).is_none());
+}
+
+#[test]
+fn test_node_tag_patterns() {
+    use crate::load::tags::matches_pattern;
+    assert!(matches_pattern("*door*", "house/front-door-left"));
+    assert!(matches_pattern("house/*", "house/door"));
+    assert!(matches_pattern("*-tile", "wall-tile"));
+    assert!(matches_pattern("floor", "floor"));
+    assert!(!matches_pattern("floor", "floor-tile"));
+    assert!(!matches_pattern("*door", "door-frame"));
+    assert!(!matches_pattern("a*a", "a"));
+}
+
+#[async_std::test]
+async fn test_tag_scene_nodes() {
+    #[derive(bevy::prelude::Component, Clone)]
+    struct Dice;
+
+    let mut app = App::new();
+    let handle = setup_and_load_voxel_scene(&mut app, "test.vox#outer-group/inner-group").await;
+    app.insert_resource(VoxelNodeTags::default().with("*/dice", Dice));
+    app.update();
+    let scene_root = app
+        .world_mut()
+        .spawn(SceneBundle {
+            scene: handle,
+            ..Default::default()
+        })
+        .id();
+    app.update();
+    let scene_instance = app
+        .world()
+        .get::<VoxelSceneInstance>(scene_root)
+        .expect("VoxelSceneInstance on scene root");
+    let dice = scene_instance
+        .get_node("outer-group/inner-group/dice")
+        .expect("dice node");
+    let inner_group = scene_instance
+        .get_node("outer-group/inner-group")
+        .expect("inner-group node");
+    assert!(app.world().get::<Dice>(dice).is_some());
+    assert!(app.world().get::<Dice>(inner_group).is_none());
+}
+
+#[cfg(feature = "modify_voxels")]
+#[async_std::test]
+async fn test_modify_voxels() {
+    let mut app = App::new();
+    let handle =
+        setup_and_load_voxel_scene(&mut app, "test.vox#outer-group/inner-group/dice").await;
+    app.update();
+    let scene_root = app
+        .world_mut()
+        .spawn(SceneBundle {
+            scene: handle,
+            ..Default::default()
+        })
+        .id();
+    app.update();
+    let entity = app
+        .world()
+        .get::<Children>(scene_root)
+        .expect("children")
+        .first()
+        .expect("scene root");
+    let model_instance = app
+        .world()
+        .get::<VoxelModelInstance>(*entity)
+        .expect("voxel model instance")
+        .clone();
+    let region = VoxelRegion {
+        origin: IVec3::splat(2),
+        size: IVec3::ONE,
+    };
+    app.world_mut().commands().modify_voxel_model(
+        model_instance.clone(),
+        VoxelRegionMode::Box(region),
+        |_pos, _voxel, _model| Voxel(7),
+    );
+    app.update();
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(model_instance.model.id())
+        .expect("retrieve model from Res<Assets>");
+
+    assert_eq!(
+        model.get_voxel_at_point(IVec3::splat(4)),
+        Err(OutOfBoundsError),
+        "Max coordinate should be 3,3,3"
+    );
+    assert_eq!(
+        model.get_voxel_at_point(IVec3::splat(-1)),
+        Err(OutOfBoundsError),
+        "Min coordinate should be 0,0,0"
+    );
+    let voxel = model
+        .get_voxel_at_point(IVec3::splat(2))
+        .expect("Retrieve voxel");
+    assert_eq!(voxel.0, 7, "Voxel material should've been changed to 7");
+}
+
+#[test]
+fn test_voxel_grid() {
+    use bevy::math::bounding::BoundingVolume;
+    let grid = crate::VoxelGrid::new(0.5, Vec3::new(1.0, 0.0, -1.0));
+    assert_eq!(grid.world_to_cell(Vec3::new(1.0, 0.0, -1.0)), IVec3::ZERO);
+    assert_eq!(
+        grid.world_to_cell(Vec3::new(0.9, 0.74, -1.26)),
+        IVec3::new(-1, 1, -1)
+    );
+    assert_eq!(
+        grid.cell_center_world(IVec3::new(2, 0, 0)),
+        Vec3::new(2.25, 0.25, -0.75)
+    );
+    assert_eq!(
+        grid.snap_world_point(Vec3::new(1.3, 0.2, -1.1)),
+        Vec3::new(1.5, 0.0, -1.0)
+    );
+    assert_eq!(
+        grid.snap_to_cell_center(Vec3::new(1.3, 0.2, -1.1)),
+        Vec3::new(1.25, 0.25, -1.25)
+    );
+    for cell in [IVec3::ZERO, IVec3::new(-3, 4, 7)] {
+        assert_eq!(grid.world_to_cell(grid.cell_center_world(cell)), cell);
+        assert!(grid
+            .cell_aabb(cell)
+            .contains(&Aabb3d::new(grid.cell_center_world(cell), Vec3::splat(0.1))));
+    }
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_model_grid_matches_voxel_space() {
+    let data = VoxelData::new(UVec3::new(4, 2, 6), true, 0.5);
+    let grid = data.grid();
+    assert_eq!(grid.origin, Vec3::new(-1.0, -0.5, -1.5));
+    for cell in [IVec3::ZERO, IVec3::new(3, 1, 5), IVec3::new(1, 0, 2)] {
+        assert_eq!(
+            grid.cell_min_world(cell),
+            data.voxel_coord_to_local_space(cell)
+        );
+        assert_eq!(
+            data.local_point_to_voxel_space(grid.cell_center_world(cell)),
+            cell
+        );
+    }
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_iter_surface() {
+    let solid_cube = |mesh_outer_faces: bool| {
+        let mut data = VoxelData::new(UVec3::splat(3), mesh_outer_faces, 1.0);
+        for z in 0..3 {
+            for y in 0..3 {
+                for x in 0..3 {
+                    data.set_voxel(Voxel(1), UVec3::new(x, y, z));
+                }
+            }
+        }
+        data
+    };
+    let data = solid_cube(true);
+    let surface: Vec<(IVec3, Voxel)> = data.iter_surface().collect();
+    assert_eq!(surface.len(), 26);
+    assert!(!surface.iter().any(|(position, _)| *position == IVec3::ONE));
+    assert!(surface.iter().all(|(_, voxel)| *voxel == Voxel(1)));
+
+    let mut data = solid_cube(false);
+    assert_eq!(data.iter_surface().count(), 0, "outer faces aren't meshed");
+    data.set_voxel(Voxel::EMPTY, UVec3::ONE);
+    let mut surface: Vec<IVec3> = data.iter_surface().map(|(position, _)| position).collect();
+    surface.sort_by_key(|position| (position.z, position.y, position.x));
+    assert_eq!(
+        surface,
+        vec![
+            IVec3::new(1, 1, 0),
+            IVec3::new(1, 0, 1),
+            IVec3::new(0, 1, 1),
+            IVec3::new(2, 1, 1),
+            IVec3::new(1, 2, 1),
+            IVec3::new(1, 1, 2),
+        ]
+    );
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_resampled() {
+    use crate::VoxelResampleFilter;
+    // a 4x4x4 model whose bottom half is solid, with a single stray voxel in the top half
+    let mut data = VoxelData::new(UVec3::splat(4), true, 0.5);
+    for z in 0..4 {
+        for y in 0..2 {
+            for x in 0..4 {
+                data.set_voxel(Voxel(1), UVec3::new(x, y, z));
+            }
+        }
+    }
+    data.set_voxel(Voxel(2), UVec3::new(3, 3, 3));
+
+    let half = data.resampled(UVec3::splat(2), VoxelResampleFilter::Majority);
+    assert_eq!(half.size(), IVec3::splat(2));
+    assert_eq!(half.model_size(), Vec3::ONE, "the voxel size is kept");
+    assert_eq!(half.count_of(Voxel(1)), 4);
+    assert_eq!(half.count_voxels(), 4, "the stray voxel is outvoted");
+
+    let nearest = data.resampled(UVec3::splat(2), VoxelResampleFilter::Nearest);
+    assert_eq!(
+        nearest.get_voxel_at_point(IVec3::new(0, 0, 0)),
+        Ok(Voxel(1))
+    );
+    assert_eq!(
+        nearest.get_voxel_at_point(IVec3::new(1, 1, 1)),
+        Ok(Voxel(2)),
+        "nearest sampling picks the voxel at the center of the cell"
+    );
+    assert_eq!(
+        nearest.get_voxel_at_point(IVec3::new(1, 1, 0)),
+        Ok(Voxel::EMPTY)
+    );
+
+    let double = data.resampled(UVec3::splat(8), VoxelResampleFilter::Majority);
+    assert_eq!(double.count_of(Voxel(1)), 8 * data.count_of(Voxel(1)));
+    assert_eq!(double.count_of(Voxel(2)), 8);
+    assert_eq!(double.get_voxel_at_point(IVec3::splat(7)), Ok(Voxel(2)));
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_classify_air() {
+    use crate::VoxelAir;
+    // a hollow 3x3x3 box with a notch cut in one wall, next to a sealed 1 voxel room
+    let mut data = VoxelData::new(UVec3::new(7, 5, 5), true, 1.0);
+    for z in 1..4 {
+        for y in 1..4 {
+            for x in 1..6 {
+                data.set_voxel(Voxel(1), UVec3::new(x, y, z));
+            }
+        }
+    }
+    data.set_voxel(Voxel::EMPTY, UVec3::new(2, 2, 2));
+    data.set_voxel(Voxel::EMPTY, UVec3::new(4, 2, 2));
+    data.set_voxel(Voxel::EMPTY, UVec3::new(5, 2, 2));
+    let air = data.classify_air();
+    assert_eq!(air.get(IVec3::ZERO), Some(VoxelAir::Exterior));
+    assert_eq!(air.get(IVec3::new(1, 1, 1)), Some(VoxelAir::Solid));
+    assert_eq!(air.get(IVec3::new(2, 2, 2)), Some(VoxelAir::Cavity(0)));
+    assert_eq!(
+        air.get(IVec3::new(4, 2, 2)),
+        Some(VoxelAir::Exterior),
+        "the notch is open to the outside"
+    );
+    assert_eq!(air.get(IVec3::new(7, 0, 0)), None);
+    assert_eq!(air.cavity_count(), 1);
+    assert_eq!(air.cavity_size(0), 1);
+    assert_eq!(
+        air.cavity_voxels(0).collect::<Vec<_>>(),
+        vec![IVec3::new(2, 2, 2)]
+    );
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_sample_random_voxel() {
+    let mut data = VoxelData::new(UVec3::splat(4), true, 1.0);
+    let mut rng = VoxelRng::from_seed(7);
+    assert_eq!(data.sampler().sample_random_voxel(|_| true, &mut rng), None);
+    for x in 0..4 {
+        data.set_voxel(Voxel(1), UVec3::new(x, 0, 0));
+    }
+    data.set_voxel(Voxel(2), UVec3::new(3, 2, 1));
+    let sampler = data.sampler();
+    assert_eq!(
+        sampler.sample_random_voxel(|voxel| voxel == Voxel(2), &mut rng),
+        Some(IVec3::new(3, 2, 1))
+    );
+    assert_eq!(
+        sampler.sample_random_voxel(|voxel| voxel == Voxel(3), &mut rng),
+        None
+    );
+    let mut sampled = std::collections::HashSet::new();
+    for _ in 0..64 {
+        let position = sampler
+            .sample_random_voxel(|voxel| voxel == Voxel(1), &mut rng)
+            .expect("floor voxel");
+        assert_eq!(data.get_voxel_at_point(position), Ok(Voxel(1)));
+        sampled.insert(position);
+    }
+    assert_eq!(sampled.len(), 4, "every matching voxel can be chosen");
+
+    // the filter is asked about each palette index once, not about every voxel
+    let calls = std::cell::Cell::new(0);
+    sampler.sample_random_voxel(
+        |_| {
+            calls.set(calls.get() + 1);
+            true
+        },
+        &mut rng,
+    );
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn test_voxel_counts() {
+    let (app, handle) = load_dice_with_settings(VoxLoaderSettings::default());
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&handle)
+        .expect("dice model");
+    let total = model.count_voxels();
+    assert!(total > 0);
+    assert_eq!(
+        model.histogram().map(|(_, count)| count).sum::<usize>(),
+        total
+    );
+    for (voxel, count) in model.histogram() {
+        assert_eq!(model.count_of(voxel), count);
+    }
+    assert_eq!(model.count_of(Voxel::EMPTY), 0);
+    assert_eq!(model.count_matching(|_| true), total);
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_voxel_counts_track_edits() {
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![
+        bevy::color::palettes::css::GRAY.into(),
+        bevy::color::palettes::css::GOLD.into(),
+    ]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let mut data = VoxelData::new(UVec3::splat(4), true, 1.0);
+    data.set_voxel(Voxel(2), UVec3::ZERO);
+    data.set_voxel(Voxel(2), UVec3::ZERO);
+    assert_eq!(
+        data.count_of(Voxel(2)),
+        1,
+        "overwrites aren't double counted"
+    );
+    let mut histogram = vec![0; 4];
+    VoxelData::record_change(&mut histogram, &RawVoxel(1), &RawVoxel(2));
+    assert_eq!(
+        histogram,
+        vec![0, 0, 1, 0],
+        "counts that are out of step don't underflow"
+    );
+    let (model, _) =
+        VoxelModel::new(world, data, "rock".to_string(), context.clone()).expect("Add model");
+    world.commands().modify_voxel_model(
+        VoxelModelInstance {
+            model: model.clone(),
+            context,
+        },
+        VoxelRegionMode::All,
+        |position, voxel, _| {
+            if position.y == 0 && *voxel == Voxel::EMPTY {
+                Voxel(1)
+            } else if position == IVec3::ONE {
+                Voxel(2)
+            } else {
+                voxel.clone()
+            }
+        },
+    );
+    world.flush();
+    let model = world
+        .resource::<Assets<VoxelModel>>()
+        .get(&model)
+        .expect("model");
+    assert_eq!(model.count_voxels(), 17);
+    assert_eq!(model.count_of(Voxel(1)), 15);
+    assert_eq!(model.count_matching(|voxel| voxel == Voxel(2)), 2);
+    assert_eq!(
+        model.histogram().collect::<Vec<_>>(),
+        vec![(Voxel(1), 15), (Voxel(2), 2)]
+    );
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_harvest_voxels() {
+    use crate::{HarvestVoxelCommandsExt, VoxelsHarvested};
+    use bevy::ecs::event::Events;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![
+        bevy::color::palettes::css::GRAY.into(),
+        bevy::color::palettes::css::GOLD.into(),
+    ]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let mut data = VoxelData::new(UVec3::splat(4), true, 1.0);
+    for x in 0..4 {
+        data.set_voxel(Voxel(1), UVec3::new(x, 0, 0));
+    }
+    data.set_voxel(Voxel(2), UVec3::new(0, 1, 0));
+    data.set_voxel(Voxel(2), UVec3::new(1, 1, 0));
+    let (model, _) =
+        VoxelModel::new(world, data, "rock".to_string(), context.clone()).expect("Add model");
+    let instance = VoxelModelInstance {
+        model: model.clone(),
+        context,
+    };
+    world.commands().harvest_voxels(
+        instance.clone(),
+        VoxelRegionMode::Box(VoxelRegion {
+            origin: IVec3::ZERO,
+            size: IVec3::new(1, 2, 1),
+        }),
+        |_| true,
+    );
+    world
+        .commands()
+        .harvest_voxels(instance, VoxelRegionMode::All, |voxel| *voxel == Voxel(2));
+    world.flush();
+    let events = world.resource::<Events<VoxelsHarvested>>();
+    let harvested: Vec<VoxelsHarvested> = events.get_reader().read(events).cloned().collect();
+    assert_eq!(harvested.len(), 2);
+    assert_eq!(harvested[0].total(), 2);
+    assert_eq!(harvested[0].counts.get(&Voxel(1)), Some(&1));
+    assert_eq!(harvested[0].counts.get(&Voxel(2)), Some(&1));
+    assert_eq!(harvested[1].total(), 1, "only the remaining gold is mined");
+    assert_eq!(harvested[1].counts.get(&Voxel(1)), None);
+    let model = world
+        .resource::<Assets<VoxelModel>>()
+        .get(&model)
+        .expect("model");
+    assert_eq!(model.count_voxels(), 3);
+    assert_eq!(model.count_of(Voxel(2)), 0);
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_dilate_and_erode() {
+    use crate::MorphologyCommandsExt;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![
+        bevy::color::palettes::css::GRAY.into(),
+        bevy::color::palettes::css::WHITE.into(),
+    ]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let mut data = VoxelData::new(UVec3::splat(5), true, 1.0);
+    data.set_voxel(Voxel(1), UVec3::splat(2));
+    let (model, _) =
+        VoxelModel::new(world, data, "snowball".to_string(), context.clone()).expect("Add model");
+    let instance = VoxelModelInstance {
+        model: model.clone(),
+        context,
+    };
+    let count = |world: &bevy::ecs::world::World, voxel: Voxel| {
+        world
+            .resource::<Assets<VoxelModel>>()
+            .get(&model)
+            .expect("model")
+            .count_of(voxel)
+    };
+    world
+        .commands()
+        .dilate_voxel_model(instance.clone(), VoxelRegionMode::All, 2, Voxel(2));
+    world.flush();
+    assert_eq!(count(world, Voxel(1)), 1);
+    assert_eq!(count(world, Voxel(2)), 24, "grows into an octahedron");
+
+    world
+        .commands()
+        .erode_voxel_model(instance, VoxelRegionMode::All, 1);
+    world.flush();
+    assert_eq!(count(world, Voxel(1)), 1);
+    assert_eq!(
+        count(world, Voxel(2)),
+        6,
+        "the tips of the octahedron are removed"
+    );
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_smooth_voxel_model() {
+    use crate::{MorphologyCommandsExt, VoxelSmoothKernel};
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let mut data = VoxelData::new(UVec3::splat(5), true, 1.0);
+    for z in 0..5 {
+        for y in 0..2 {
+            for x in 0..5 {
+                data.set_voxel(Voxel(1), UVec3::new(x, y, z));
+            }
+        }
+    }
+    // a pit in the terrain, and a spike sticking out of it
+    data.set_voxel(Voxel::EMPTY, UVec3::new(2, 1, 2));
+    data.set_voxel(Voxel(1), UVec3::new(2, 2, 2));
+    let (model, _) =
+        VoxelModel::new(world, data, "terrain".to_string(), context.clone()).expect("Add model");
+    world.commands().smooth_voxel_model(
+        VoxelModelInstance {
+            model: model.clone(),
+            context,
+        },
+        VoxelRegionMode::All,
+        1,
+        VoxelSmoothKernel::default(),
+    );
+    world.flush();
+    let model = world
+        .resource::<Assets<VoxelModel>>()
+        .get(&model)
+        .expect("model");
+    assert_eq!(model.get_voxel_at_point(IVec3::new(2, 1, 2)), Ok(Voxel(1)));
+    assert_eq!(
+        model.get_voxel_at_point(IVec3::new(2, 2, 2)),
+        Ok(Voxel::EMPTY)
+    );
+    assert_eq!(model.count_voxels(), 50, "the edges of the slab are kept");
+    assert_eq!(
+        VoxelSmoothKernel::new(2)
+            .with_spherical(true)
+            .offsets()
+            .len(),
+        33
+    );
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_voxel_gravity() {
+    use crate::VoxelGravity;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![
+        bevy::color::palettes::css::YELLOW.into(),
+        bevy::color::palettes::css::GRAY.into(),
+    ]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let mut data = VoxelData::new(UVec3::new(2, 5, 1), true, 1.0);
+    data.set_voxel(Voxel(1), UVec3::new(0, 3, 0));
+    data.set_voxel(Voxel(1), UVec3::new(0, 4, 0));
+    data.set_voxel(Voxel(2), UVec3::new(1, 4, 0));
+    let (model, _) =
+        VoxelModel::new(world, data, "sand".to_string(), context.clone()).expect("Add model");
+    let entity = world
+        .spawn((
+            VoxelModelInstance {
+                model: model.clone(),
+                context,
+            },
+            VoxelGravity::new([Voxel(1)]).with_cells_per_tick(2),
+        ))
+        .id();
+    let voxel_at = |world: &bevy::ecs::world::World, position: IVec3| {
+        world
+            .resource::<Assets<VoxelModel>>()
+            .get(&model)
+            .expect("model")
+            .get_voxel_at_point(position)
+            .expect("voxel")
+    };
+    world.run_schedule(bevy::app::FixedPostUpdate);
+    assert_eq!(voxel_at(world, IVec3::new(0, 1, 0)), Voxel(1));
+    assert_eq!(voxel_at(world, IVec3::new(0, 2, 0)), Voxel(1));
+    assert_eq!(voxel_at(world, IVec3::new(0, 4, 0)), Voxel::EMPTY);
+    assert_eq!(
+        voxel_at(world, IVec3::new(1, 4, 0)),
+        Voxel(2),
+        "only the listed materials fall"
+    );
+    world.run_schedule(bevy::app::FixedPostUpdate);
+    assert_eq!(voxel_at(world, IVec3::new(0, 0, 0)), Voxel(1));
+    assert_eq!(voxel_at(world, IVec3::new(0, 1, 0)), Voxel(1));
+    assert!(!world
+        .get::<VoxelGravity>(entity)
+        .expect("gravity")
+        .is_settled());
+    world.run_schedule(bevy::app::FixedPostUpdate);
+    assert!(world
+        .get::<VoxelGravity>(entity)
+        .expect("gravity")
+        .is_settled());
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_voxel_outline() {
+    use crate::VoxelOutline;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let data = VoxelData::new(UVec3::splat(4), true, 1.0);
+    let (model, _) =
+        VoxelModel::new(world, data, "selected".to_string(), context.clone()).expect("Add model");
+    let entity = world
+        .spawn((
+            VoxelModelInstance { model, context },
+            VoxelOutline::default().with_width(0.5),
+        ))
+        .id();
+    app.update();
+    let hull = |app: &App| {
+        app.world()
+            .get::<Children>(entity)
+            .and_then(|children| children.first().copied())
+    };
+    let transform = *app
+        .world()
+        .get::<Transform>(hull(&app).expect("hull"))
+        .expect("transform");
+    assert_eq!(transform.scale, Vec3::splat(1.25));
+    assert_eq!(transform.translation, Vec3::ZERO);
+
+    app.world_mut()
+        .get_mut::<VoxelOutline>(entity)
+        .expect("outline")
+        .region = Some(VoxelRegion {
+        origin: IVec3::ZERO,
+        size: IVec3::ONE,
+    });
+    app.update();
+    let children = app.world().get::<Children>(entity).expect("children");
+    assert_eq!(children.len(), 1, "the old hull is replaced");
+    let transform = app
+        .world()
+        .get::<Transform>(children[0])
+        .expect("transform");
+    assert_eq!(transform.translation, Vec3::splat(-1.5));
+    assert_eq!(transform.scale, Vec3::splat(2.0));
+
+    app.world_mut().entity_mut(entity).remove::<VoxelOutline>();
+    app.update();
+    assert_eq!(hull(&app), None, "removing the outline removes the hull");
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_voxel_neighbors() {
+    use crate::VoxelNeighbors;
+    use bevy::ecs::entity::Entity;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let mut data = VoxelData::new(UVec3::ONE, true, 1.0);
+    data.set_voxel(Voxel(1), UVec3::ZERO);
+    let (solid, _) =
+        VoxelModel::new(world, data, "solid".to_string(), context.clone()).expect("Add model");
+    let (empty, _) = VoxelModel::new(
+        world,
+        VoxelData::new(UVec3::ONE, true, 1.0),
+        "empty".to_string(),
+        context.clone(),
+    )
+    .expect("Add model");
+    let tile = world
+        .spawn((
+            VoxelModelInstance {
+                model: solid.clone(),
+                context: context.clone(),
+            },
+            GlobalTransform::default(),
+        ))
+        .id();
+    let neighbor = world
+        .spawn((
+            VoxelModelInstance {
+                model: solid.clone(),
+                context: context.clone(),
+            },
+            GlobalTransform::from_translation(Vec3::X),
+        ))
+        .id();
+    world
+        .entity_mut(tile)
+        .insert(VoxelNeighbors::default().with(IVec3::X, neighbor));
+    app.update();
+    let vertex_count = |app: &App, entity: Entity| {
+        let handle = app.world().get::<Handle<Mesh>>(entity).expect("mesh");
+        app.world()
+            .resource::<Assets<Mesh>>()
+            .get(handle)
+            .expect("mesh")
+            .count_vertices()
+    };
+    assert_eq!(
+        vertex_count(&app, tile),
+        20,
+        "the face against the neighbor is culled"
+    );
+    assert_eq!(
+        VoxelNeighbors::default()
+            .with(IVec3::X, neighbor)
+            .get(IVec3::X),
+        Some(neighbor)
+    );
+
+    app.world_mut()
+        .get_mut::<VoxelModelInstance>(neighbor)
+        .expect("instance")
+        .model = empty;
+    app.update();
+    assert_eq!(
+        vertex_count(&app, tile),
+        24,
+        "the face is restored when the neighbor is empty"
+    );
+
+    // a model without outer faces only gains the faces against the empty voxels of its neighbors
+    let mut data = VoxelData::new(UVec3::ONE, false, 1.0);
+    data.set_voxel(Voxel(1), UVec3::ZERO);
+    let (unpadded, _) = VoxelModel::new(
+        app.world_mut(),
+        data,
+        "unpadded".to_string(),
+        context.clone(),
+    )
+    .expect("Add model");
+    let edge = app
+        .world_mut()
+        .spawn((
+            VoxelModelInstance {
+                model: unpadded,
+                context: context.clone(),
+            },
+            GlobalTransform::default(),
+            VoxelNeighbors::default().with(IVec3::X, neighbor),
+        ))
+        .id();
+    app.update();
+    assert_eq!(vertex_count(&app, edge), 4);
+
+    app.world_mut()
+        .get_mut::<VoxelModelInstance>(neighbor)
+        .expect("instance")
+        .model = solid.clone();
+    app.update();
+    assert_eq!(vertex_count(&app, tile), 20);
+    assert_eq!(vertex_count(&app, edge), 0);
+
+    // moving the neighbor away restores the face
+    *app.world_mut()
+        .get_mut::<GlobalTransform>(neighbor)
+        .expect("transform") = GlobalTransform::from_translation(Vec3::X * 5.0);
+    app.update();
+    assert_eq!(
+        vertex_count(&app, tile),
+        24,
+        "the mesh follows the neighbor as it moves"
+    );
+    let model_mesh = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&solid)
+        .expect("model")
+        .mesh
+        .clone();
+    assert_ne!(app.world().get::<Handle<Mesh>>(tile), Some(&model_mesh));
+
+    app.world_mut().entity_mut(tile).remove::<VoxelNeighbors>();
+    app.update();
+    assert_eq!(
+        app.world().get::<Handle<Mesh>>(tile),
+        Some(&model_mesh),
+        "removing the neighbors restores the model's mesh"
+    );
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_voxel_edit_mask() {
+    use crate::VoxelEditMask;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![
+        bevy::color::palettes::css::GRAY.into(),
+        bevy::color::palettes::css::BLACK.into(),
+    ]);
+    let mut data = VoxelData::new(UVec3::splat(4), true, 1.0);
+    for x in 0..4 {
+        for z in 0..4 {
+            data.set_voxel(Voxel(2), UVec3::new(x, 0, z));
+        }
+    }
+    let mask = VoxelEditMask::new(IVec3::splat(4))
+        .with_protected_voxel(Voxel(2))
+        .with_protected_region(IVec3::new(0, 3, 0), IVec3::new(1, 1, 1));
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let (model, _) = VoxelModel::new(
+        world,
+        data.with_edit_mask(mask),
+        "shrine".to_string(),
+        context.clone(),
+    )
+    .expect("Add model");
+    world.commands().modify_voxel_model(
+        VoxelModelInstance {
+            model: model.clone(),
+            context,
+        },
+        VoxelRegionMode::All,
+        |_, _, _| Voxel(1),
+    );
+    world.flush();
+    let model = world
+        .resource::<Assets<VoxelModel>>()
+        .get(&model)
+        .expect("model");
+    assert!(model.edit_mask().is_some());
+    assert_eq!(
+        model.get_voxel_at_point(IVec3::new(2, 0, 2)),
+        Ok(Voxel(2)),
+        "bedrock is protected"
+    );
+    assert_eq!(
+        model.get_voxel_at_point(IVec3::new(0, 3, 0)),
+        Ok(Voxel::EMPTY),
+        "protected cells are left as they are"
+    );
+    assert_eq!(model.get_voxel_at_point(IVec3::new(1, 3, 0)), Ok(Voxel(1)));
+    assert_eq!(model.count_of(Voxel(1)), 64 - 16 - 1);
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_voxel_integrity() {
+    use crate::{VoxelIntegrity, VoxelIntegrityThresholdCrossed};
+    use bevy::ecs::event::Events;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let data = VoxelData::new(UVec3::splat(4), true, 1.0);
+    let (model, _) =
+        VoxelModel::new(world, data, "building".to_string(), context.clone()).expect("Add model");
+    let instance = VoxelModelInstance { model, context };
+    world
+        .commands()
+        .modify_voxel_model(instance.clone(), VoxelRegionMode::All, |_, _, _| Voxel(1));
+    world.flush();
+    let entity = world
+        .spawn((instance.clone(), VoxelIntegrity::new([0.25, 0.5, 0.9])))
+        .id();
+    app.update();
+    let integrity = app
+        .world()
+        .get::<VoxelIntegrity>(entity)
+        .expect("integrity");
+    assert_eq!(integrity.original_voxel_count(), Some(64));
+    assert_eq!(integrity.destroyed_fraction, 0.0);
+
+    let world = app.world_mut();
+    world.commands().modify_voxel_model(
+        instance,
+        VoxelRegionMode::Box(VoxelRegion {
+            origin: IVec3::ZERO,
+            size: IVec3::new(4, 2, 4),
+        }),
+        |_, _, _| Voxel::EMPTY,
+    );
+    world.flush();
+    app.update();
+    let integrity = app
+        .world()
+        .get::<VoxelIntegrity>(entity)
+        .expect("integrity");
+    assert_eq!(integrity.destroyed_fraction, 0.5);
+    let events = app
+        .world()
+        .resource::<Events<VoxelIntegrityThresholdCrossed>>();
+    let thresholds: Vec<f32> = events
+        .get_reader()
+        .read(events)
+        .map(|event| {
+            assert_eq!(event.entity, entity);
+            event.threshold
+        })
+        .collect();
+    assert_eq!(thresholds, vec![0.25, 0.5]);
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_aabb_updated_after_modify() {
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let data = SDF::cuboid(Vec3::splat(1.0)).voxelize(UVec3::splat(6), 1.0, Voxel(1));
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let (model_handle, model) =
+        VoxelModel::new(world, data, "growing".to_string(), context.clone()).expect("Add model");
+    let original_aabb = world
+        .resource::<Assets<Mesh>>()
+        .get(model.mesh.id())
+        .and_then(|mesh| mesh.compute_aabb())
+        .expect("aabb");
+    let instance = VoxelModelInstance {
+        model: model_handle,
+        context,
+    };
+    let entity = world
+        .spawn((instance.clone(), model.mesh.clone(), original_aabb))
+        .id();
+    world
+        .commands()
+        .modify_voxel_model(instance, VoxelRegionMode::All, |_, _, _| Voxel(1));
+    app.update();
+    app.update();
+    let aabb = app.world().get::<Aabb>(entity).expect("aabb");
+    assert_eq!(
+        aabb.half_extents,
+        Vec3A::splat(3.0),
+        "Aabb should have grown to the full size of the model"
+    );
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_modify_voxel_models_in_world_region() {
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let entities: Vec<_> = [0.0, 4.0, 100.0]
+        .into_iter()
+        .enumerate()
+        .map(|(index, x)| {
+            let data = VoxelData::new(UVec3::splat(4), true, 1.0);
+            let (model, _) =
+                VoxelModel::new(world, data, format!("model-{index}"), context.clone())
+                    .expect("Add model");
+            let instance = VoxelModelInstance {
+                model,
+                context: context.clone(),
+            };
+            world
+                .spawn((instance, GlobalTransform::from_xyz(x, 0.0, 0.0)))
+                .id()
+        })
+        .collect();
+    let region = Aabb3d::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 2.0));
+    world
+        .commands()
+        .modify_voxel_models(entities.clone(), region, move |position, _, _| {
+            assert!(position.x > 1.0 && position.x < 3.0);
+            Voxel(1)
+        });
+    app.update();
+    let count_solid = |entity| {
+        let instance = app
+            .world()
+            .get::<VoxelModelInstance>(entity)
+            .expect("instance");
+        let model = app
+            .world()
+            .resource::<Assets<VoxelModel>>()
+            .get(&instance.model)
+            .expect("model");
+        let mut solid = Vec::new();
+        for x in 0..4 {
+            if model
+                .get_voxel_at_point(IVec3::new(x, 1, 1))
+                .is_ok_and(|voxel| voxel != Voxel::EMPTY)
+            {
+                solid.push(x);
+            }
+        }
+        solid
+    };
+    assert_eq!(count_solid(entities[0]), vec![3]);
+    assert_eq!(count_solid(entities[1]), vec![0]);
+    assert!(count_solid(entities[2]).is_empty());
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_modify_voxel_model_in_world_sphere() {
+    use bevy::math::bounding::BoundingSphere;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let data = VoxelData::new(UVec3::splat(8), true, 0.5);
+    let (model, _) =
+        VoxelModel::new(world, data, "sphere".to_string(), context.clone()).expect("Add model");
+    let instance = VoxelModelInstance {
+        model: model.clone(),
+        context,
+    };
+    let xform = GlobalTransform::from_xyz(10.0, 0.0, 0.0);
+    world.commands().modify_voxel_model_in_world(
+        instance,
+        &xform,
+        BoundingSphere::new(Vec3::new(10.0, 0.0, 0.0), 0.8),
+        |_, _, _| Voxel(1),
+    );
+    app.update();
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&model)
+        .expect("model");
+    let solid = |x, y, z| model.get_voxel_at_point(IVec3::new(x, y, z)) == Ok(Voxel(1));
+    // voxels around the center of the model, whose centers lie 0.25 from each axis
+    assert!(solid(3, 3, 3) && solid(4, 4, 4));
+    assert!(!solid(0, 0, 0), "corner is outside the sphere");
+    assert!(!solid(6, 4, 4), "voxel center 1.25 from the sphere center");
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_voxel_brush() {
+    use crate::{VoxelBrush, VoxelBrushBlend, VoxelBrushFalloff};
+    let fill = VoxelBrush::new(Voxel(3)).with_blend(VoxelBrushBlend::FillEmpty);
+    assert_eq!(fill.paint(IVec3::ZERO, &Voxel::EMPTY, 0.5), Voxel(3));
+    assert_eq!(fill.paint(IVec3::ZERO, &Voxel(1), 0.5), Voxel(1));
+    let eraser = VoxelBrush::eraser();
+    assert_eq!(eraser.paint(IVec3::ZERO, &Voxel(1), 0.5), Voxel::EMPTY);
+    assert_eq!(VoxelBrushFalloff::Smooth.weight(0.0), 1.0);
+    assert_eq!(VoxelBrushFalloff::Linear.weight(1.0), 0.0);
+    assert_eq!(VoxelBrushFalloff::Hardness(0.5).weight(0.25), 1.0);
+    assert_eq!(VoxelBrushFalloff::Hardness(0.5).weight(0.75), 0.5);
+    // at half strength roughly half of the voxels are painted, and the pattern is stable
+    let feathered = VoxelBrush::new(Voxel(2))
+        .with_falloff(VoxelBrushFalloff::Linear)
+        .with_seed(7);
+    let painted = |brush: &VoxelBrush| {
+        (0..1000)
+            .filter(|x| brush.paint(IVec3::new(*x, 0, 0), &Voxel::EMPTY, 0.5) == Voxel(2))
+            .count()
+    };
+    let count = painted(&feathered);
+    assert!((400..600).contains(&count), "{count}");
+    assert_eq!(painted(&feathered), count);
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_queued_voxel_edits_apply_on_fixed_tick() {
+    use crate::{QueueVoxelEditCommandsExt, VoxelEditQueue};
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let data = VoxelData::new(UVec3::splat(4), true, 1.0);
+    let (model, _) =
+        VoxelModel::new(world, data, "queued".to_string(), context.clone()).expect("Add model");
+    let instance = VoxelModelInstance {
+        model: model.clone(),
+        context,
+    };
+    world
+        .commands()
+        .queue_voxel_edit(instance.clone(), VoxelRegionMode::All, |_, _, _| Voxel(1));
+    world.commands().queue_voxel_edit(
+        instance,
+        VoxelRegionMode::Box(VoxelRegion {
+            origin: IVec3::ZERO,
+            size: IVec3::ONE,
+        }),
+        |_, _, _| Voxel(2),
+    );
+    world.flush();
+    assert_eq!(world.resource::<VoxelEditQueue>().len(), 2);
+    let voxel_at = |world: &bevy::ecs::world::World, position: IVec3| {
+        world
+            .resource::<Assets<VoxelModel>>()
+            .get(&model)
+            .expect("model")
+            .get_voxel_at_point(position)
+            .expect("voxel")
+    };
+    assert_eq!(
+        voxel_at(world, IVec3::ONE),
+        Voxel::EMPTY,
+        "edits are deferred"
+    );
+    world.run_schedule(bevy::app::FixedPostUpdate);
+    assert!(world.resource::<VoxelEditQueue>().is_empty());
+    assert_eq!(voxel_at(world, IVec3::ONE), Voxel(1));
+    assert_eq!(
+        voxel_at(world, IVec3::ZERO),
+        Voxel(2),
+        "edits apply in order"
+    );
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_paste_voxels_remaps_palette() {
+    let mut app = App::new();
+    setup_app(&mut app);
+    let source_palette = VoxelPalette::from_colors(vec![
+        bevy::color::palettes::css::RED.into(),
+        bevy::color::palettes::css::GREEN.into(),
+    ]);
+    let destination_palette = VoxelPalette::from_colors(vec![
+        bevy::color::palettes::css::BLUE.into(),
+        bevy::color::palettes::css::GREEN.into(),
+    ]);
+    let mut tall_bar = VoxelData::new(UVec3::new(1, 3, 1), true, 1.0);
+    for y in 0..3 {
+        tall_bar.set_voxel(Voxel(2), UVec3::new(0, y, 0));
+    }
+    let world = app.world_mut();
+    let source_context = VoxelContext::new(world, source_palette);
+    let destination_context = VoxelContext::new(world, destination_palette);
+    let (source_handle, _) =
+        VoxelModel::new(world, tall_bar, "bar".to_string(), source_context.clone())
+            .expect("Add source model");
+    let (destination_handle, _) = VoxelModel::new(
+        world,
+        VoxelData::new(UVec3::splat(4), true, 1.0),
+        "empty".to_string(),
+        destination_context.clone(),
+    )
+    .expect("Add destination model");
+    world.commands().copy_voxel_region(
+        VoxelModelInstance {
+            model: source_handle,
+            context: source_context,
+        },
+        VoxelRegionMode::All,
+    );
+    world.commands().paste_voxel_clipboard(
+        VoxelModelInstance {
+            model: destination_handle.clone(),
+            context: destination_context,
+        },
+        IVec3::new(0, 1, 0),
+        Quat::from_rotation_z(FRAC_PI_2),
+    );
+    app.update();
+    let clipboard = app.world().resource::<VoxelClipboard>();
+    assert_eq!(clipboard.size(), IVec3::new(1, 3, 1));
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(destination_handle.id())
+        .expect("destination model");
+    for x in 0..3 {
+        assert_eq!(
+            model.get_voxel_at_point(IVec3::new(x, 1, 0)),
+            Ok(Voxel(2)),
+            "Rotated bar should lie along the x axis, with green mapped to green"
+        );
+    }
+    assert_eq!(
+        model.get_voxel_at_point(IVec3::new(0, 2, 0)),
+        Ok(Voxel::EMPTY)
+    );
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_stamp_blueprint() {
+    use crate::StampBlueprintCommandsExt;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let pillar = SDF::cuboid(Vec3::new(0.5, 1.0, 0.5)).voxelize(UVec3::new(1, 2, 1), 1.0, Voxel(1));
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette.clone());
+    let blueprint = VoxelBlueprint::from_clipboard(VoxelClipboard::copy(
+        &pillar,
+        context.id(),
+        &palette,
+        VoxelRegionMode::All,
+    ));
+    assert_eq!(blueprint.size(), IVec3::new(1, 2, 1));
+    assert_eq!(
+        blueprint.anchor,
+        IVec3::ZERO,
+        "Anchored at the center of its bottom face"
+    );
+    let blueprint = blueprint.with_anchor(IVec3::new(0, 1, 0));
+    let (model, _) = VoxelModel::new(
+        world,
+        VoxelData::new(UVec3::splat(4), true, 1.0),
+        "ground".to_string(),
+        context.clone(),
+    )
+    .expect("Add model");
+    let blueprint = world
+        .resource_mut::<Assets<VoxelBlueprint>>()
+        .add(blueprint);
+    world.commands().stamp_blueprint(
+        VoxelModelInstance {
+            model: model.clone(),
+            context,
+        },
+        blueprint,
+        IVec3::new(2, 2, 2),
+        Quat::IDENTITY,
+    );
+    app.update();
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(model.id())
+        .expect("model");
+    for y in 1..=2 {
+        assert_eq!(
+            model.get_voxel_at_point(IVec3::new(2, y, 2)),
+            Ok(Voxel(1)),
+            "The blueprint's anchor should be placed at the stamped position"
+        );
+    }
+    for y in [0, 3] {
+        assert_eq!(
+            model.get_voxel_at_point(IVec3::new(2, y, 2)),
+            Ok(Voxel::EMPTY)
+        );
+    }
+}
+
+#[cfg(feature = "modify_voxels")]
+#[async_std::test]
+async fn test_load_blueprint() {
+    let mut app = App::new();
+    setup_app_with_settings(
+        &mut app,
+        Some(VoxLoaderSettings {
+            create_blueprints: true,
+            ..Default::default()
+        }),
+    );
+    let asset_server = app.world().resource::<AssetServer>().clone();
+    let model = asset_server
+        .load_untyped_async("test.vox#outer-group/inner-group/dice@model")
+        .await
+        .expect("Loaded dice")
+        .typed::<VoxelModel>();
+    let blueprint = asset_server
+        .load_untyped_async("test.vox#outer-group/inner-group/dice@blueprint")
+        .await
+        .expect("Loaded dice blueprint")
+        .typed::<VoxelBlueprint>();
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&model)
+        .expect("dice model");
+    let blueprint = app
+        .world()
+        .resource::<Assets<VoxelBlueprint>>()
+        .get(&blueprint)
+        .expect("dice blueprint");
+    assert_eq!(blueprint.size(), model.size());
+
+    let mut app = App::new();
+    setup_app(&mut app);
+    assert!(
+        app.world()
+            .resource::<AssetServer>()
+            .load_untyped_async("test.vox#outer-group/inner-group/dice@blueprint")
+            .await
+            .is_err(),
+        "Blueprints are only created when enabled in the settings"
+    );
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_blueprint_fits_at() {
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let pillar = SDF::cuboid(Vec3::new(0.5, 1.0, 0.5)).voxelize(UVec3::new(1, 2, 1), 1.0, Voxel(1));
+    let blueprint = VoxelBlueprint::from_clipboard(VoxelClipboard::copy(
+        &pillar,
+        Handle::<VoxelContext>::default().id(),
+        &palette,
+        VoxelRegionMode::All,
+    ));
+    let mut ground = VoxelData::new(UVec3::splat(4), true, 1.0);
+    for x in 0..4 {
+        for z in 0..4 {
+            ground.set_voxel(Voxel(1), UVec3::new(x, 0, z));
+        }
+    }
+    assert!(blueprint.fits_at(&ground, IVec3::new(1, 1, 1), Quat::IDENTITY));
+    assert!(
+        !blueprint.fits_at(&ground, IVec3::new(1, 0, 1), Quat::IDENTITY),
+        "Overlaps the ground"
+    );
+    assert!(
+        !blueprint.fits_at(&ground, IVec3::new(1, 3, 1), Quat::IDENTITY),
+        "Extends beyond the top of the model"
+    );
+    assert!(
+        blueprint.fits_at(
+            &ground,
+            IVec3::new(1, 1, 1),
+            Quat::from_rotation_z(FRAC_PI_2)
+        ),
+        "Lying on its side"
+    );
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_ghost_reuses_assets() {
+    use crate::VoxelGhost;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let pillar = SDF::cuboid(Vec3::new(0.5, 1.0, 0.5)).voxelize(UVec3::new(1, 2, 1), 1.0, Voxel(1));
+    let blueprint = VoxelBlueprint::from_clipboard(VoxelClipboard::copy(
+        &pillar,
+        Handle::<VoxelContext>::default().id(),
+        &palette,
+        VoxelRegionMode::All,
+    ));
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let ground = VoxelData::new(UVec3::splat(4), true, 1.0);
+    let (model, _) =
+        VoxelModel::new(world, ground, "ground".to_string(), context.clone()).expect("Add model");
+    let blueprint = world
+        .resource_mut::<Assets<VoxelBlueprint>>()
+        .add(blueprint);
+    let ghost = world
+        .spawn(VoxelGhost::new(
+            blueprint,
+            VoxelModelInstance { model, context },
+            IVec3::ONE,
+            Quat::IDENTITY,
+        ))
+        .id();
+    app.update();
+    let handles = |app: &App| {
+        let entity = app.world().entity(ghost);
+        (
+            entity.get::<Handle<Mesh>>().expect("ghost mesh").clone(),
+            entity
+                .get::<Handle<StandardMaterial>>()
+                .expect("ghost material")
+                .clone(),
+        )
+    };
+    let first = handles(&app);
+    let counts = |app: &App| {
+        (
+            app.world().resource::<Assets<Mesh>>().len(),
+            app.world().resource::<Assets<StandardMaterial>>().len(),
+        )
+    };
+    let first_counts = counts(&app);
+    for x in 0..3 {
+        app.world_mut()
+            .get_mut::<VoxelGhost>(ghost)
+            .expect("ghost")
+            .position = IVec3::new(x, 3, 1);
+        app.update();
+    }
+    assert!(!app.world().get::<VoxelGhost>(ghost).expect("ghost").fits());
+    assert_eq!(
+        handles(&app),
+        first,
+        "the ghost keeps its mesh and material"
+    );
+    assert_eq!(counts(&app), first_counts, "no assets are left behind");
+}
+
+#[cfg(feature = "generate_voxels")]
+#[test]
+fn test_generate_voxels() {
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let tall_box = SDF::cuboid(Vec3::new(0.5, 2.5, 0.5)).voxelize(UVec3::splat(6), 1.0, Voxel(1));
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let (_, tall_box_model) =
+        VoxelModel::new(world, tall_box, "tall box".to_string(), context).expect("Add box model");
+    assert_eq!(tall_box_model.name, "tall box");
+    assert_eq!(tall_box_model.has_translucency, false);
+    let mesh = app
+        .world()
+        .resource::<Assets<Mesh>>()
+        .get(tall_box_model.mesh.id())
+        .expect("mesh generated");
+    assert_eq!(
+        mesh.compute_aabb().expect("aabb").half_extents,
+        Vec3A::new(0.5, 2.5, 0.5)
+    );
+    assert_eq!(
+        mesh.count_vertices(),
+        6 * 4,
+        "resulting mesh should have 6 quads"
+    );
+}
+
+#[cfg(feature = "generate_voxels")]
+#[test]
+fn test_strip_palette_layout() {
+    use bevy::render::texture::Image;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()])
+        .with_layout(PaletteLayout::Strip);
+    let data = SDF::cuboid(Vec3::splat(1.0)).voxelize(UVec3::splat(2), 1.0, Voxel(1));
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let (_, model) =
+        VoxelModel::new(world, data, "strip".to_string(), context.clone()).expect("Add model");
+    let mesh = app
+        .world()
+        .resource::<Assets<Mesh>>()
+        .get(model.mesh.id())
+        .expect("mesh generated");
+    let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0) else {
+        panic!("mesh should have uvs");
+    };
+    assert!(
+        uvs.iter().all(|uv| *uv == [0.5 / 256.0, 0.5]),
+        "Palette index 0 should be sampled from the middle of the first run of texels"
+    );
+    let opaque_material = app
+        .world()
+        .resource::<Assets<VoxelContext>>()
+        .get(&context)
+        .expect("context")
+        .opaque_material
+        .clone();
+    let color = app
+        .world()
+        .resource::<Assets<StandardMaterial>>()
+        .get(&opaque_material)
+        .and_then(|material| material.base_color_texture.clone())
+        .expect("color texture");
+    let image = app
+        .world()
+        .resource::<Assets<Image>>()
+        .get(&color)
+        .expect("color image");
+    assert_eq!(image.width(), 1024);
+    let texels: Vec<&[u8]> = image.data.chunks_exact(4).collect();
+    assert!(
+        texels[..4].iter().all(|texel| *texel == texels[0]),
+        "Palette index 0 should be repeated across its run of texels, so filtering doesn't bleed into index 1"
+    );
+    assert_ne!(texels[3], texels[4]);
+}
+
+#[test]
+fn test_reduced_palette_precision() {
+    use bevy::{
+        color::LinearRgba,
+        render::{render_resource::TextureFormat, texture::Image},
+    };
+    let palette = VoxelPalette::new(vec![
+        VoxelElement::new(Color::WHITE)
+            .with_emission(2.0)
+            .with_roughness(0.25),
+        VoxelElement::new(Color::WHITE).with_emission(4.0),
+    ]);
+    let mut images = Assets::<Image>::default();
+    let full = palette.clone().create_material(&mut images);
+    let reduced = palette
+        .with_precision(PalettePrecision::Reduced)
+        .create_material(&mut images);
+    let image = |texture: &Option<Handle<Image>>| {
+        images
+            .get(texture.as_ref().expect("texture generated"))
+            .expect("image added")
+    };
+    for (full_texture, reduced_texture, full_format, reduced_format) in [
+        (
+            &full.emissive_texture,
+            &reduced.emissive_texture,
+            TextureFormat::Rgba32Float,
+            TextureFormat::Rgba8Unorm,
+        ),
+        (
+            &full.metallic_roughness_texture,
+            &reduced.metallic_roughness_texture,
+            TextureFormat::Rgba16Unorm,
+            TextureFormat::Rgba8Unorm,
+        ),
+    ] {
+        let full_image = image(full_texture);
+        let reduced_image = image(reduced_texture);
+        assert_eq!(full_image.texture_descriptor.format, full_format);
+        assert_eq!(reduced_image.texture_descriptor.format, reduced_format);
+        assert!(reduced_image.data.len() < full_image.data.len());
+    }
+    assert_eq!(full.emissive, LinearRgba::rgb(1.0, 1.0, 1.0));
+    assert_eq!(
+        reduced.emissive,
+        LinearRgba::rgb(4.0, 4.0, 4.0),
+        "Reduced emission should be scaled by the brightest element"
+    );
+    let emission = &image(&reduced.emissive_texture).data;
+    assert_eq!(
+        emission[4..7],
+        [255, 255, 255],
+        "The brightest element should use the full range of the texture"
+    );
+    assert!(
+        (127..=128).contains(&emission[0]),
+        "An element half as bright should be encoded at half the range"
+    );
+}
+
+#[test]
+fn test_platform_profile() {
+    let palette = VoxelPalette::new(vec![
+        VoxelElement::new(Color::WHITE),
+        VoxelElement::new(Color::WHITE).with_glass(1.3),
+    ]);
+    let configure = |platform_profile: PlatformProfile| {
+        VoxLoaderSettings {
+            platform_profile,
+            palette_precision: PalettePrecision::Full,
+            ..Default::default()
+        }
+        .configure_palette(palette.clone())
+    };
+
+    let desktop = configure(PlatformProfile::Desktop);
+    assert_eq!(desktop.precision, PalettePrecision::Full);
+    assert_eq!(desktop.transmission, MaterialProperty::VariesPerElement);
+    assert_eq!(desktop.indices_of_refraction[1], Some(1.3));
+
+    let mobile = configure(PlatformProfile::Mobile);
+    assert_eq!(
+        mobile.precision,
+        PalettePrecision::Reduced,
+        "Mobile should force reduced precision"
+    );
+    assert_eq!(mobile.transmission, MaterialProperty::VariesPerElement);
+
+    let webgl = configure(PlatformProfile::WebGL2);
+    assert_eq!(webgl.precision, PalettePrecision::Reduced);
+    assert_eq!(
+        webgl.transmission,
+        MaterialProperty::Constant(0.0),
+        "WebGL2 should render glass as opaque"
+    );
+    assert!(webgl.indices_of_refraction.iter().all(Option::is_none));
+}
+
+#[cfg(feature = "generate_voxels")]
+#[test]
+fn test_voxel_world_index() {
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let data = SDF::cuboid(Vec3::splat(2.0)).voxelize(UVec3::splat(4), 1.0, Voxel(1));
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let (model_handle, _) =
+        VoxelModel::new(world, data, "box".to_string(), context.clone()).expect("Add model");
+    let entity = world
+        .spawn((
+            VoxelModelInstance {
+                model: model_handle.clone(),
+                context,
+            },
+            GlobalTransform::from_translation(Vec3::new(10.0, 0.0, 0.0)),
+        ))
+        .id();
+    app.update();
+    let index = app.world().resource::<VoxelWorldIndex>();
+    assert_eq!(index.len(), 1);
+    let hit = Aabb3d::new(Vec3::new(11.5, 0.0, 0.0), Vec3::splat(0.1));
+    assert_eq!(index.intersecting(&hit).collect::<Vec<_>>(), vec![entity]);
+    let miss = Aabb3d::new(Vec3::ZERO, Vec3::splat(1.0));
+    assert_eq!(index.intersecting(&miss).count(), 0);
+    assert_eq!(index.instances_of(model_handle.id()).count(), 1);
+
+    app.world_mut().despawn(entity);
+    app.update();
+    assert!(app.world().resource::<VoxelWorldIndex>().is_empty());
+}
+
+#[test]
+fn test_despawn_voxel_scene() {
+    use crate::DespawnVoxelSceneCommandsExt;
+    use bevy::{ecs::world::World, hierarchy::BuildWorldChildren};
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let mut data = VoxelData::new(UVec3::splat(2), true, 1.0);
+    data.set_voxel(Voxel(1), UVec3::ZERO);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let (model, _) =
+        VoxelModel::new(world, data, "box".to_string(), context.clone()).expect("Add model");
+    let model_id = model.id();
+    let instance = VoxelModelInstance { model, context };
+    let spawn_scene = |world: &mut World| {
+        world
+            .spawn(GlobalTransform::IDENTITY)
+            .with_children(|parent| {
+                parent.spawn((instance.clone(), GlobalTransform::IDENTITY));
+            })
+            .id()
+    };
+    let first = spawn_scene(world);
+    let second = spawn_scene(world);
+    drop(instance);
+    app.update();
+    assert_eq!(app.world().resource::<VoxelWorldIndex>().len(), 2);
+
+    let world = app.world_mut();
+    world.commands().despawn_voxel_scene(first, true);
+    world.flush();
+    assert!(world.get_entity(first).is_none());
+    assert_eq!(
+        world.resource::<VoxelWorldIndex>().len(),
+        1,
+        "the index forgets the despawned instances straight away"
+    );
+    assert!(world.resource::<Assets<VoxelModel>>().contains(model_id));
+
+    world.commands().despawn_voxel_scene(second, true);
+    world.flush();
+    app.update();
+    assert!(
+        !app.world()
+            .resource::<Assets<VoxelModel>>()
+            .contains(model_id),
+        "the plugin keeps no handle to the assets of a released scene"
+    );
+}
+
+#[test]
+fn test_voxel_level_manager() {
+    use crate::{VoxelLevelManager, VoxelLevelProgress, VoxelLevelStage};
+    use bevy::ecs::event::Events;
+    let mut app = App::new();
+    setup_app(&mut app);
+    let load_level = |app: &mut App, path: &str| {
+        app.world_mut()
+            .resource_mut::<VoxelLevelManager>()
+            .load_level(path);
+        let mut events = Vec::new();
+        for _ in 0..1000 {
+            app.update();
+            events.extend(
+                app.world_mut()
+                    .resource_mut::<Events<VoxelLevelProgress>>()
+                    .drain(),
+            );
+            if events.last().map(|event| event.stage) == Some(VoxelLevelStage::Ready) {
+                assert!(
+                    events
+                        .windows(2)
+                        .all(|pair| pair[0].progress <= pair[1].progress),
+                    "the progress only moves forwards"
+                );
+                assert!(events
+                    .iter()
+                    .filter(|event| event.stage == VoxelLevelStage::Loading)
+                    .all(|event| event.progress <= 0.5));
+                return events
+                    .into_iter()
+                    .map(|event| event.stage)
+                    .collect::<Vec<_>>();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("Timed out loading {path}");
+    };
+
+    let stages = load_level(&mut app, "test.vox#outer-group/inner-group");
+    assert_eq!(stages.first(), Some(&VoxelLevelStage::Loading));
+    let manager = app.world().resource::<VoxelLevelManager>();
+    assert!(!manager.is_transitioning());
+    assert_eq!(
+        manager.current_path(),
+        Some("test.vox#outer-group/inner-group")
+    );
+    let first = manager.current().expect("first level");
+    app.update();
+    assert_eq!(
+        app.world().get::<Visibility>(first),
+        Some(&Visibility::Inherited),
+        "the level is shown once it has spawned"
+    );
+
+    let stages = load_level(&mut app, "test.vox#outer-group");
+    assert!(!stages.contains(&VoxelLevelStage::Fading));
+    let second = app
+        .world()
+        .resource::<VoxelLevelManager>()
+        .current()
+        .expect("second level");
+    app.update();
+    assert_ne!(first, second);
+    assert!(
+        app.world().get_entity(first).is_none(),
+        "the previous level is despawned"
+    );
+}
+
+#[async_std::test]
+async fn test_voxel_level_manager_crossfade() {
+    use crate::{
+        VoxelInstanceMaterialParams, VoxelLevelManager, VoxelLevelProgress, VoxelLevelStage,
+    };
+    use bevy::{ecs::event::Events, time::TimeUpdateStrategy};
+    let mut app = App::new();
+    setup_app(&mut app);
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(
+        std::time::Duration::from_millis(10),
+    ))
+    .insert_resource(VoxelLevelManager::with_fade(0.1));
+    // the levels are loaded up front, so that the transitions only wait for spawning and fading
+    let _levels = [
+        app.world()
+            .resource::<AssetServer>()
+            .load_untyped_async("test.vox#outer-group/inner-group")
+            .await
+            .expect("Loaded first level"),
+        app.world()
+            .resource::<AssetServer>()
+            .load_untyped_async("test.vox#outer-group")
+            .await
+            .expect("Loaded second level"),
+    ];
+    let dissolves = |app: &mut App| -> Vec<f32> {
+        app.world_mut()
+            .query::<&VoxelInstanceMaterialParams>()
+            .iter(app.world())
+            .map(|params| params.dissolve)
+            .collect()
+    };
+    // runs the transition to `path`, checking the dissolve of every instance while the levels crossfade
+    let transition = |app: &mut App, path: &str| {
+        app.world_mut()
+            .resource_mut::<VoxelLevelManager>()
+            .load_level(path);
+        let mut events = Vec::new();
+        for _ in 0..100 {
+            app.update();
+            events.extend(
+                app.world_mut()
+                    .resource_mut::<Events<VoxelLevelProgress>>()
+                    .drain(),
+            );
+            match events.last().map(|event| event.stage) {
+                Some(VoxelLevelStage::Ready) => return events,
+                Some(VoxelLevelStage::Fading) => {
+                    let dissolves = dissolves(app);
+                    assert!(!dissolves.is_empty(), "the fading instances are dissolved");
+                    assert!(dissolves
+                        .iter()
+                        .all(|dissolve| (0.0..=1.0).contains(dissolve)));
+                }
+                _ => {}
+            }
+        }
+        panic!("Timed out switching to {path}");
+    };
+
+    let events = transition(&mut app, "test.vox#outer-group/inner-group");
+    let fading = events
+        .iter()
+        .filter(|event| event.stage == VoxelLevelStage::Fading)
+        .count();
+    assert!(fading >= 5, "the level fades in over several frames");
+    assert!(events
+        .windows(2)
+        .all(|pair| pair[0].progress <= pair[1].progress));
+    let first = app
+        .world()
+        .resource::<VoxelLevelManager>()
+        .current()
+        .expect("first level");
+    app.update();
+    assert!(
+        dissolves(&mut app).is_empty(),
+        "the dissolve is removed from the instances once they have faded in"
+    );
+
+    let events = transition(&mut app, "test.vox#outer-group");
+    assert!(events
+        .iter()
+        .any(|event| event.stage == VoxelLevelStage::Fading));
+    app.update();
+    assert!(
+        app.world().get_entity(first).is_none(),
+        "the previous level is despawned after fading out"
+    );
+    assert!(dissolves(&mut app).is_empty());
+}
+
+#[cfg(feature = "generate_voxels")]
+#[test]
+fn test_memory_budget_evicts_distant_meshes() {
+    let mut app = App::new();
+    setup_app(&mut app);
+    app.insert_resource(VoxelMemoryBudget::with_eviction_distance(20.0));
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GREEN.into()]);
+    let data = SDF::cuboid(Vec3::splat(2.0)).voxelize(UVec3::splat(4), 1.0, Voxel(1));
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let (model_handle, model) =
+        VoxelModel::new(world, data, "box".to_string(), context.clone()).expect("Add model");
+    assert!(model.memory_usage() >= 6 * 6 * 6);
+    world.spawn((
+        VoxelModelInstance {
+            model: model_handle.clone(),
+            context,
+        },
+        GlobalTransform::IDENTITY,
+    ));
+    let camera = world
+        .spawn((
+            bevy::render::camera::Camera::default(),
+            GlobalTransform::from_translation(Vec3::new(100.0, 0.0, 0.0)),
+        ))
+        .id();
+    app.update();
+    let budget = app.world().resource::<VoxelMemoryBudget>();
+    assert!(budget.is_evicted(model_handle.id()));
+    assert_eq!(budget.mesh_bytes(), 0);
+    assert!(budget.voxel_bytes() > 0);
+    assert!(app
+        .world()
+        .resource::<Assets<Mesh>>()
+        .get(&model.mesh)
+        .is_none());
+
+    *app.world_mut()
+        .get_mut::<GlobalTransform>(camera)
+        .expect("camera transform") = GlobalTransform::from_translation(Vec3::new(10.0, 0.0, 0.0));
+    app.update();
+    let budget = app.world().resource::<VoxelMemoryBudget>();
+    assert!(!budget.is_evicted(model_handle.id()));
+    assert!(budget.mesh_bytes() > 0);
+    assert!(app
+        .world()
+        .resource::<Assets<Mesh>>()
+        .get(&model.mesh)
+        .is_some());
+
+    let uncompressed = budget.voxel_bytes();
+    app.world_mut()
+        .resource_mut::<Assets<VoxelModel>>()
+        .get_mut(&model_handle)
+        .expect("model")
+        .compress();
+    app.update();
+    assert_ne!(
+        app.world().resource::<VoxelMemoryBudget>().voxel_bytes(),
+        uncompressed,
+        "the budget is measured again when a model changes"
+    );
+    app.world_mut()
+        .resource_mut::<Assets<VoxelModel>>()
+        .remove(&model_handle);
+    app.update();
+    let budget = app.world().resource::<VoxelMemoryBudget>();
+    assert_eq!(budget.voxel_bytes(), 0);
+    assert_eq!(budget.mesh_bytes(), 0);
+}
+
+#[cfg(feature = "generate_voxels")]
+#[test]
+fn test_sdf_intersect() {
+    let box_sphere = SDF::cuboid(Vec3::splat(2.0))
+        .intersect(SDF::sphere(2.5))
+        .voxelize(UVec3::splat(7), 1.0, Voxel(1));
+    let sphere_box = SDF::sphere(2.5)
+        .intersect(SDF::cuboid(Vec3::splat(2.0)))
+        .voxelize(UVec3::splat(7), 1.0, Voxel(1));
+    assert_eq!(box_sphere.voxels, sphere_box.voxels);
+}
+
+#[cfg(feature = "generate_voxels")]
+#[test]
+fn test_sdf_subtract() {
+    let thin_box = SDF::cuboid(Vec3::new(1.0, 2.0, 2.0)).voxelize(UVec3::splat(6), 1.0, Voxel(1));
+    let halved_cube = SDF::cuboid(Vec3::new(2.0, 2.0, 2.0))
+        .subtract(SDF::cuboid(Vec3::new(1.0, 2.0, 2.0)).translate(Vec3::X))
+        .translate(Vec3::X)
+        .voxelize(UVec3::splat(6), 1.0, Voxel(1));
+    assert_eq!(thin_box.voxels, halved_cube.voxels);
+}
+
+#[cfg(feature = "generate_voxels")]
+#[test]
+fn test_sdf_rotate() {
+    let tall_box = SDF::cuboid(Vec3::new(0.5, 2.5, 0.5)).voxelize(UVec3::splat(6), 1.0, Voxel(1));
+    let deep_box_rotated = SDF::cuboid(Vec3::new(0.5, 0.5, 2.5))
+        .rotate(Quat::from_axis_angle(Vec3::X, FRAC_PI_2))
+        .voxelize(UVec3::splat(6), 1.0, Voxel(1));
+    assert_eq!(tall_box.voxels, deep_box_rotated.voxels);
+}
+
+#[cfg(feature = "generate_voxels")]
+#[test]
+fn test_voxel_queryable() {
+    let data = SDF::cuboid(Vec3::splat(2.0)).voxelize(UVec3::splat(4), 1.0, Voxel(1));
+    assert!(data.point_in_model(IVec3::new(3, 0, 0)).is_ok());
+    assert!(data.point_in_model(IVec3::new(4, 0, 0)).is_err());
+    assert_eq!(
+        data.local_point_to_voxel_space(Vec3::ZERO),
+        IVec3::new(2, 2, 2)
+    );
+}
+
+#[cfg(feature = "generate_voxels")]
+#[test]
+fn test_generate_voxel_model_async() {
+    use crate::{GenerateVoxelModelCommandsExt, VoxelModelGenerated};
+    use bevy::ecs::event::Events;
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    app.world_mut().commands().generate_voxel_model_async(
+        "island",
+        context,
+        UVec3::splat(8),
+        1.0,
+        |position| {
+            if position.y < 4 {
+                Voxel(1)
+            } else {
+                Voxel::EMPTY
+            }
+        },
+    );
+    app.world_mut().flush();
+    for _ in 0..1000 {
+        app.update();
+        let generated: Vec<VoxelModelGenerated> = app
+            .world_mut()
+            .resource_mut::<Events<VoxelModelGenerated>>()
+            .drain()
+            .collect();
+        if let Some(generated) = generated.first() {
+            assert_eq!(generated.name, "island");
+            let model = app
+                .world()
+                .resource::<Assets<VoxelModel>>()
+                .get(&generated.model)
+                .expect("generated model");
+            assert_eq!(model.data.size(), UVec3::splat(8));
+            assert_eq!(
+                model.data.get_voxel_at_point(IVec3::new(2, 3, 2)),
+                Ok(Voxel(1))
+            );
+            assert_eq!(
+                model.data.get_voxel_at_point(IVec3::new(2, 4, 2)),
+                Ok(Voxel::EMPTY)
+            );
+            assert!(app.world().resource::<Assets<Mesh>>().contains(&model.mesh));
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    panic!("Timed out generating model");
+}
+
+#[cfg(all(feature = "raymarch", feature = "modify_voxels"))]
+#[test]
+fn test_raymarch_volume() {
+    let mut data = VoxelData::new(UVec3::new(3, 2, 1), true, 1.0);
+    data.set_voxel(Voxel(7), UVec3::new(2, 1, 0));
+    let image = data.raymarch_volume();
+    assert_eq!(image.texture_descriptor.size.width, 3);
+    assert_eq!(image.texture_descriptor.size.height, 2);
+    assert_eq!(image.texture_descriptor.size.depth_or_array_layers, 1);
+    assert_eq!(image.data, vec![0, 0, 0, 0, 0, 7]);
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_brick_map() {
+    use crate::VoxelBrickMap;
+    use bevy::math::{Dir3, Ray3d};
+    let mut data = VoxelData::new(UVec3::new(150, 2, 2), true, 1.0);
+    data.set_voxel(Voxel(3), UVec3::new(140, 1, 1));
+    data.set_voxel(Voxel(3), UVec3::new(141, 1, 1));
+    let brick_map = VoxelBrickMap::from_data(&data);
+    assert_eq!(brick_map.bricks_size(), IVec3::new(3, 1, 1));
+    assert_eq!(brick_map.occupied_bricks(), 1);
+    assert!(brick_map.is_brick_empty(IVec3::ZERO));
+    assert!(brick_map.is_solid(IVec3::new(140, 1, 1)));
+    assert!(!brick_map.is_solid(IVec3::new(140, 0, 1)));
+    assert!(!brick_map.any_solid(IVec3::ZERO, IVec3::new(140, 2, 2)));
+    assert_eq!(brick_map.count_solid(IVec3::ZERO, IVec3::new(150, 2, 2)), 2);
+    assert_eq!(
+        brick_map.count_solid(IVec3::new(141, 0, 0), IVec3::new(150, 2, 2)),
+        1
+    );
+
+    // the model spans -75 to 75 along x, so voxel 140 starts at 65
+    let hit = brick_map
+        .raycast(
+            Ray3d::new(Vec3::new(-100.0, 0.5, 0.5), Dir3::X),
+            &GlobalTransform::IDENTITY,
+            1000.0,
+        )
+        .expect("hit");
+    assert_eq!(hit.voxel_coord, IVec3::new(140, 1, 1));
+    assert_eq!(hit.normal, IDir3::NEG_X);
+    assert!((hit.distance - 165.0).abs() < 0.001);
+    let hit = brick_map
+        .raycast(
+            Ray3d::new(Vec3::new(100.0, 0.5, 0.5), Dir3::NEG_X),
+            &GlobalTransform::IDENTITY,
+            1000.0,
+        )
+        .expect("hit");
+    assert_eq!(hit.voxel_coord, IVec3::new(141, 1, 1));
+    assert_eq!(hit.normal, IDir3::X);
+    assert!(brick_map
+        .raycast(
+            Ray3d::new(Vec3::new(-100.0, -0.5, 0.5), Dir3::X),
+            &GlobalTransform::IDENTITY,
+            1000.0,
+        )
+        .is_none());
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_brick_map_updates_on_edit() {
+    use crate::VoxelBrickMap;
+    let (mut app, handle) = load_dice_with_settings(VoxLoaderSettings {
+        brick_maps: true,
+        ..Default::default()
+    });
+    let brick_map = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&handle)
+        .expect("dice model")
+        .brick_map()
+        .expect("brick map")
+        .clone();
+    let solid_before = app
+        .world()
+        .resource::<Assets<VoxelBrickMap>>()
+        .get(&brick_map)
+        .expect("brick map")
+        .count_solid(IVec3::ZERO, IVec3::splat(64));
+    assert!(solid_before > 0);
+    app.world_mut().commands().modify_voxel_model(
+        VoxelModelInstance {
+            model: handle,
+            context: app
+                .world()
+                .resource::<AssetServer>()
+                .get_handle::<VoxelContext>("test.vox#voxel-context")
+                .expect("voxel context"),
+        },
+        VoxelRegionMode::All,
+        |_, _, _| Voxel::EMPTY,
+    );
+    app.update();
+    let brick_map = app
+        .world()
+        .resource::<Assets<VoxelBrickMap>>()
+        .get(&brick_map)
+        .expect("brick map");
+    assert_eq!(brick_map.occupied_bricks(), 0);
+}
+
+#[cfg(feature = "modify_voxels")]
+#[test]
+fn test_voxel_timeline() {
+    use crate::VoxelTimeline;
+    let (mut app, handle) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    let instance = VoxelModelInstance {
+        model: handle.clone(),
+        context,
+    };
+    let count = |app: &App| {
+        app.world()
+            .resource::<Assets<VoxelModel>>()
+            .get(&handle)
+            .expect("dice model")
+            .count_voxels()
+    };
+    let intact = count(&app);
+    let entity = app
+        .world_mut()
+        .spawn((instance.clone(), VoxelTimeline::new(0.0, 10.0)))
+        .id();
+    app.update();
+    let (start, _) = app
+        .world()
+        .get::<VoxelTimeline>(entity)
+        .expect("timeline")
+        .recorded_range()
+        .expect("initial recording");
+    app.world_mut().commands().modify_voxel_model(
+        instance,
+        VoxelRegionMode::All,
+        |position, voxel, _| {
+            if position.y > 2 {
+                Voxel::EMPTY
+            } else {
+                voxel.clone()
+            }
+        },
+    );
+    app.update();
+    app.update();
+    let damaged = count(&app);
+    assert!(damaged < intact);
+    let timeline = app.world().get::<VoxelTimeline>(entity).expect("timeline");
+    assert!(timeline.memory_usage() > 0);
+    app.world_mut()
+        .get_mut::<VoxelTimeline>(entity)
+        .expect("timeline")
+        .rewind_to(start);
+    app.update();
+    assert_eq!(count(&app), intact);
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_world() {
+    use crate::{VoxelWorld, VoxelWorldChunk};
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    let mut world = VoxelWorld::new(context, UVec3::splat(16), 0.5);
+    world.set_voxel(IVec3::new(-1, 0, 20), Voxel(3));
+    world.set_voxel(IVec3::new(100, 0, 0), Voxel::EMPTY);
+    assert_eq!(world.get_voxel(IVec3::new(-1, 0, 20)), Voxel(3));
+    assert_eq!(world.get_voxel(IVec3::new(0, 0, 20)), Voxel::EMPTY);
+    assert_eq!(
+        world.chunk_coord(IVec3::new(-1, 0, 20)),
+        IVec3::new(-1, 0, 1)
+    );
+    assert_eq!(
+        world.chunks().count(),
+        1,
+        "writing empty voxels doesn't create chunks"
+    );
+    assert_eq!(
+        world.point_to_voxel(Vec3::new(-0.1, 0.2, 10.3)),
+        IVec3::new(-1, 0, 20)
+    );
+    app.insert_resource(world);
+    app.update();
+    let entity = app
+        .world()
+        .resource::<VoxelWorld>()
+        .chunk_entity(IVec3::new(-1, 0, 1))
+        .expect("chunk entity");
+    let chunk = app.world().entity(entity);
+    assert_eq!(
+        chunk.get::<VoxelWorldChunk>(),
+        Some(&VoxelWorldChunk {
+            coord: IVec3::new(-1, 0, 1)
+        })
+    );
+    assert_eq!(
+        chunk.get::<Transform>().expect("transform").translation,
+        Vec3::new(-4.0, 4.0, 12.0)
+    );
+    let model = chunk
+        .get::<VoxelModelInstance>()
+        .expect("instance")
+        .model
+        .clone();
+    app.world_mut()
+        .resource_mut::<VoxelWorld>()
+        .set_voxel(IVec3::new(-2, 0, 20), Voxel(3));
+    app.update();
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&model)
+        .expect("chunk model");
+    assert_eq!(model.count_voxels(), 2);
+    assert_eq!(model.get_voxel_at_point(IVec3::new(14, 0, 4)), Ok(Voxel(3)));
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_world_culls_chunk_borders() {
+    use crate::VoxelWorld;
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    let mut world = VoxelWorld::new(context, UVec3::splat(4), 1.0);
+    // a slab spanning two chunks along x
+    world.fill(IVec3::ZERO, IVec3::new(8, 1, 4), Voxel(3));
+    app.insert_resource(world);
+    app.update();
+    let vertex_count = |app: &App| {
+        let model = app
+            .world()
+            .resource::<VoxelWorld>()
+            .chunk_model(IVec3::ZERO)
+            .expect("chunk model")
+            .clone();
+        let model = app
+            .world()
+            .resource::<Assets<VoxelModel>>()
+            .get(&model)
+            .expect("chunk model");
+        assert!(model.is_compressed(), "the world holds the dense voxels");
+        app.world()
+            .resource::<Assets<Mesh>>()
+            .get(&model.mesh)
+            .expect("chunk mesh")
+            .count_vertices()
+    };
+    assert_eq!(
+        vertex_count(&app),
+        5 * 4,
+        "the face against the neighboring chunk is culled"
+    );
+
+    app.world_mut().resource_mut::<VoxelWorld>().fill(
+        IVec3::new(4, 0, 0),
+        IVec3::new(8, 1, 4),
+        Voxel::EMPTY,
+    );
+    app.update();
+    assert_eq!(
+        vertex_count(&app),
+        6 * 4,
+        "emptying the neighbor's border uncovers the face"
+    );
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_world_skips_enclosed_chunks() {
+    use crate::VoxelWorld;
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    let mut world = VoxelWorld::new(context, UVec3::splat(2), 1.0);
+    // a solid block of 3x3x3 chunks, so that the center chunk is enclosed
+    world.fill(IVec3::ZERO, IVec3::splat(6), Voxel(3));
+    app.insert_resource(world);
+    app.update();
+    let center = IVec3::ONE;
+    assert!(
+        app.world()
+            .resource::<VoxelWorld>()
+            .chunk_model(center)
+            .is_none(),
+        "the enclosed chunk is not meshed"
+    );
+    assert!(app
+        .world()
+        .resource::<VoxelWorld>()
+        .chunk_model(IVec3::ZERO)
+        .is_some());
+
+    // opens a hole in the neighbor's border next to the center chunk
+    app.world_mut()
+        .resource_mut::<VoxelWorld>()
+        .set_voxel(IVec3::new(4, 2, 2), Voxel::EMPTY);
+    app.update();
+    let model = app
+        .world()
+        .resource::<VoxelWorld>()
+        .chunk_model(center)
+        .expect("the uncovered chunk is meshed")
+        .clone();
+    let model = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&model)
+        .expect("chunk model");
+    assert_eq!(
+        app.world()
+            .resource::<Assets<Mesh>>()
+            .get(&model.mesh)
+            .expect("chunk mesh")
+            .count_vertices(),
+        4,
+        "only the face next to the hole is visible"
+    );
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_tileset() {
+    use crate::{VoxelClipboard, VoxelTile, VoxelTileset, VoxelWorld};
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GRAY.into()]);
+    let tile = |positions: &[IVec3]| {
+        let mut data = VoxelData::new(UVec3::new(3, 1, 3), true, 1.0);
+        for position in positions {
+            data.set_voxel(Voxel(1), position.as_uvec3());
+        }
+        VoxelClipboard::copy(
+            &data,
+            bevy::asset::AssetId::default(),
+            &palette,
+            VoxelRegionMode::All,
+        )
+    };
+    let tileset = VoxelTileset::new(UVec3::new(3, 1, 3))
+        .with_tile(VoxelTile::new(
+            "end",
+            tile(&[IVec3::new(1, 0, 1), IVec3::new(2, 0, 1)]),
+            &[IVec3::X],
+        ))
+        .with_tile(VoxelTile::new(
+            "straight",
+            tile(&[
+                IVec3::new(0, 0, 1),
+                IVec3::new(1, 0, 1),
+                IVec3::new(2, 0, 1),
+            ]),
+            &[IVec3::NEG_X, IVec3::X],
+        ))
+        .with_tile(VoxelTile::new(
+            "corner",
+            tile(&[
+                IVec3::new(1, 0, 1),
+                IVec3::new(2, 0, 1),
+                IVec3::new(1, 0, 2),
+            ]),
+            &[IVec3::X, IVec3::Z],
+        ));
+    let (corner, quarter_turns) = tileset
+        .select([true, false, false, false, false, true])
+        .expect("a tile");
+    assert_eq!(corner.name, "corner");
+    assert_eq!(quarter_turns, 3, "+X turns to -X and +Z stays");
+
+    let mut tileset = tileset;
+    let mut world = VoxelWorld::new(Handle::default(), UVec3::splat(8), 1.0);
+    tileset.place(&mut world, IVec3::ZERO);
+    tileset.place(&mut world, IVec3::X);
+    let row = |world: &VoxelWorld| {
+        (0..9)
+            .map(|x| world.get_voxel(IVec3::new(x, 0, 1)) != Voxel::EMPTY)
+            .collect::<Vec<bool>>()
+    };
+    assert_eq!(
+        row(&world),
+        vec![false, true, true, true, true, false, false, false, false],
+        "two ends face each other"
+    );
+    tileset.place(&mut world, IVec3::new(2, 0, 0));
+    assert_eq!(
+        row(&world),
+        vec![false, true, true, true, true, true, true, true, false],
+        "the middle tile becomes a straight"
+    );
+    tileset.remove(&mut world, IVec3::new(2, 0, 0));
+    assert!(!tileset.contains(IVec3::new(2, 0, 0)));
+    assert_eq!(
+        row(&world),
+        vec![false, true, true, true, true, false, false, false, false]
+    );
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_world_region_files() {
+    use crate::{VoxelRegionFiles, VoxelWorld};
+    let directory =
+        std::env::temp_dir().join(format!("bevy_vox_scene_regions_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let new_world = |app: &App| {
+        let context = app
+            .world()
+            .resource::<AssetServer>()
+            .get_handle::<VoxelContext>("test.vox#voxel-context")
+            .expect("voxel context");
+        VoxelWorld::new(context, UVec3::splat(8), 1.0)
+            .with_region_files(VoxelRegionFiles::new(&directory).with_region_size(2))
+    };
+    // an unreadable region file doesn't prevent the region from being saved
+    std::fs::create_dir_all(&directory).expect("region directory");
+    std::fs::write(directory.join("r.0.0.0.vxr"), b"not a region").expect("corrupt region");
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let mut world = new_world(&app);
+    world.set_voxel(IVec3::new(1, 2, 3), Voxel(3));
+    world.set_voxel(IVec3::new(-20, 0, 0), Voxel(5));
+    assert_eq!(world.unsaved_chunks(), 2);
+    world.save();
+    app.insert_resource(world);
+    for _ in 0..1000 {
+        app.update();
+        if app.world().resource::<VoxelWorld>().unsaved_chunks() == 0 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(app.world().resource::<VoxelWorld>().unsaved_chunks(), 0);
+    let region_files = |extension: &str| {
+        std::fs::read_dir(&directory)
+            .expect("region directory")
+            .filter(|entry| {
+                entry.as_ref().is_ok_and(|entry| {
+                    entry
+                        .path()
+                        .extension()
+                        .is_some_and(|found| found == extension)
+                })
+            })
+            .count()
+    };
+    assert_eq!(
+        region_files("vxr"),
+        2,
+        "the chunks are in different regions"
+    );
+    assert_eq!(
+        region_files("corrupt"),
+        1,
+        "the unreadable region is kept aside"
+    );
+
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let mut world = new_world(&app);
+    world.load_chunks(IVec3::splat(-3), IVec3::splat(3));
+    app.insert_resource(world);
+    for _ in 0..1000 {
+        app.update();
+        if app.world().resource::<VoxelWorld>().chunks().count() == 2 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    let world = app.world().resource::<VoxelWorld>();
+    assert_eq!(world.get_voxel(IVec3::new(1, 2, 3)), Voxel(3));
+    assert_eq!(world.get_voxel(IVec3::new(-20, 0, 0)), Voxel(5));
+    assert_eq!(world.get_voxel(IVec3::new(0, 0, 0)), Voxel::EMPTY);
+    assert_eq!(world.unsaved_chunks(), 0, "loaded chunks are already saved");
+    let _ = std::fs::remove_dir_all(&directory);
+
+    // chunks that fail to save are kept until they are saved
+    std::fs::write(&directory, b"not a directory").expect("blocking file");
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    let mut files = VoxelRegionFiles::new(&directory);
+    files.retry_interval = 0.0;
+    let mut world = VoxelWorld::new(context, UVec3::splat(8), 1.0).with_region_files(files);
+    world.set_voxel(IVec3::new(1, 2, 3), Voxel(3));
+    world.save();
+    app.insert_resource(world);
+    for _ in 0..20 {
+        app.update();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(app.world().resource::<VoxelWorld>().unsaved_chunks(), 1);
+    std::fs::remove_file(&directory).expect("blocking file");
+    for _ in 0..1000 {
+        app.update();
+        if app.world().resource::<VoxelWorld>().unsaved_chunks() == 0 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(
+        app.world().resource::<VoxelWorld>().unsaved_chunks(),
+        0,
+        "the failed save is retried"
+    );
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_load_chunks_restores_unsaved_evicted_chunk() {
+    use crate::{VoxelRegionFiles, VoxelWorld, VoxelWorldStreaming, VoxelWorldViewer};
+    let directory =
+        std::env::temp_dir().join(format!("bevy_vox_scene_reload_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    let streaming = VoxelWorldStreaming::new(|_| Voxel::EMPTY).with_view_distance(0);
+    app.insert_resource(
+        VoxelWorld::new(context, UVec3::splat(8), 1.0)
+            .with_streaming(streaming)
+            .with_region_files(VoxelRegionFiles::new(&directory)),
+    );
+    let viewer = app
+        .world_mut()
+        .spawn((
+            VoxelWorldViewer::default(),
+            Transform::default(),
+            GlobalTransform::default(),
+        ))
+        .id();
+    let has_chunk = |app: &App| {
+        app.world()
+            .resource::<VoxelWorld>()
+            .chunks()
+            .any(|coord| coord == IVec3::ZERO)
+    };
+    for _ in 0..1000 {
+        app.update();
+        if has_chunk(&app) {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert!(has_chunk(&app));
+
+    // save an edit, so that the region file holds an older copy of the chunk
+    let position = IVec3::new(1, 2, 3);
+    let mut world = app.world_mut().resource_mut::<VoxelWorld>();
+    world.set_voxel(position, Voxel(3));
+    world.save();
+    for _ in 0..1000 {
+        app.update();
+        if app.world().resource::<VoxelWorld>().unsaved_chunks() == 0 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(app.world().resource::<VoxelWorld>().unsaved_chunks(), 0);
+
+    // edit the chunk again, then evict it before the edit is saved
+    app.world_mut()
+        .resource_mut::<VoxelWorld>()
+        .set_voxel(position, Voxel(5));
+    *app.world_mut()
+        .get_mut::<GlobalTransform>(viewer)
+        .expect("transform") = GlobalTransform::from_xyz(1000.0, 0.0, 0.0);
+    *app.world_mut()
+        .get_mut::<Transform>(viewer)
+        .expect("transform") = Transform::from_xyz(1000.0, 0.0, 0.0);
+    app.update();
+    app.world_mut().despawn(viewer);
+    assert!(!has_chunk(&app), "the chunk is evicted");
+
+    app.world_mut()
+        .resource_mut::<VoxelWorld>()
+        .load_chunks(IVec3::ZERO, IVec3::ZERO);
+    for _ in 0..1000 {
+        app.update();
+        if has_chunk(&app) {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(
+        app.world().resource::<VoxelWorld>().get_voxel(position),
+        Voxel(5),
+        "the latest edit is restored rather than the copy in the region file"
+    );
+    for _ in 0..1000 {
+        app.update();
+        if app.world().resource::<VoxelWorld>().unsaved_chunks() == 0 {
+            break;
+        }
+        app.world_mut().resource_mut::<VoxelWorld>().save();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(app.world().resource::<VoxelWorld>().unsaved_chunks(), 0);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_world_lighting() {
+    use crate::{VoxelWorld, VoxelWorldLighting};
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    let mut world = VoxelWorld::new(context, UVec3::splat(16), 1.0)
+        .with_lighting(VoxelWorldLighting::default());
+    world.fill(IVec3::ZERO, IVec3::new(16, 1, 16), Voxel(3));
+    world.fill(IVec3::new(0, 20, 0), IVec3::new(8, 21, 16), Voxel(3));
+    app.insert_resource(world);
+    app.update();
+    let floor_brightness = |app: &App| -> Vec<f32> {
+        let model = app
+            .world()
+            .resource::<VoxelWorld>()
+            .chunk_model(IVec3::ZERO)
+            .expect("floor model")
+            .clone();
+        let mesh = app
+            .world()
+            .resource::<Assets<VoxelModel>>()
+            .get(&model)
+            .map(|model| model.mesh.clone())
+            .expect("floor mesh");
+        let mesh = app
+            .world()
+            .resource::<Assets<Mesh>>()
+            .get(&mesh)
+            .expect("mesh");
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            panic!("Mesh has normals");
+        };
+        let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute(Mesh::ATTRIBUTE_COLOR)
+        else {
+            panic!("Lit mesh has vertex colors");
+        };
+        normals
+            .iter()
+            .zip(colors)
+            .filter(|(normal, _)| **normal == [0.0, 1.0, 0.0])
+            .map(|(_, color)| color[0])
+            .collect()
+    };
+    let brightness = floor_brightness(&app);
+    assert_eq!(brightness.len(), 16 * 16 * 4, "lit faces aren't merged");
+    assert!(brightness.iter().any(|brightness| *brightness == 1.0));
+    assert!(
+        brightness.iter().any(|brightness| *brightness < 0.5),
+        "the roof shades the floor"
+    );
+    app.world_mut().resource_mut::<VoxelWorld>().fill(
+        IVec3::new(0, 20, 0),
+        IVec3::new(8, 21, 16),
+        Voxel::EMPTY,
+    );
+    app.update();
+    assert!(
+        floor_brightness(&app)
+            .iter()
+            .all(|brightness| *brightness == 1.0),
+        "removing the roof relights the floor in the chunk below"
+    );
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_world_water() {
+    use crate::{VoxelSubmersion, VoxelWater, VoxelWaterSurface, VoxelWorld};
+    use bevy::{
+        math::{Dir3, Ray3d},
+        prelude::With,
+    };
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    let mut world = VoxelWorld::new(context, UVec3::splat(16), 1.0)
+        .with_water(VoxelWater::new(Voxel(5), Handle::default()));
+    world.fill(IVec3::ZERO, IVec3::new(16, 1, 16), Voxel(3));
+    world.fill(IVec3::new(0, 1, 0), IVec3::new(4, 3, 4), Voxel(5));
+    app.insert_resource(world);
+    let floater = app
+        .world_mut()
+        .spawn((
+            VoxelSubmersion::new(Vec3::new(0.5, 1.5, 0.5)),
+            GlobalTransform::from_translation(Vec3::new(2.0, 2.0, 2.0)),
+        ))
+        .id();
+    app.update();
+
+    let world = app.world().resource::<VoxelWorld>();
+    assert!(world.is_water(IVec3::new(1, 2, 1)));
+    assert!(!world.is_solid(IVec3::new(1, 2, 1)), "water isn't solid");
+    assert!(world.is_solid(IVec3::new(1, 0, 1)));
+    let submersion = world.submersion(Aabb3d::new(
+        Vec3::new(2.0, 2.0, 2.0),
+        Vec3::new(0.5, 1.5, 0.5),
+    ));
+    assert!((submersion - 2.0 / 3.0).abs() < 1e-5);
+    assert_eq!(
+        world.submersion(Aabb3d::new(Vec3::new(2.0, 5.0, 2.0), Vec3::splat(0.5))),
+        0.0
+    );
+
+    let model = world.chunk_model(IVec3::ZERO).expect("chunk model").clone();
+    let chunk = app
+        .world()
+        .resource::<Assets<VoxelModel>>()
+        .get(&model)
+        .expect("chunk model");
+    assert!(
+        chunk.data.collides_at(IVec3::new(1, 0, 1)) && !chunk.data.collides_at(IVec3::new(1, 2, 1)),
+        "water doesn't collide"
+    );
+    let hit = chunk
+        .raycast(
+            Ray3d::new(Vec3::new(-6.5, 7.0, -6.5), Dir3::NEG_Y),
+            &GlobalTransform::IDENTITY,
+            100.0,
+        )
+        .expect("ray hits the floor");
+    assert_eq!(
+        hit.voxel_coord,
+        IVec3::new(1, 0, 1),
+        "rays pass through water"
+    );
+    assert_eq!(hit.voxel, Voxel(3));
+    let mesh = chunk.mesh.clone();
+    let Some(VertexAttributeValues::Float32x3(positions)) = app
+        .world()
+        .resource::<Assets<Mesh>>()
+        .get(&mesh)
+        .expect("mesh")
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("Mesh has positions");
+    };
+    assert!(
+        positions.iter().all(|position| position[1] <= -7.0 + 1e-5),
+        "water is left out of the chunk mesh, which is centered on the chunk"
+    );
+
+    let surface = app
+        .world_mut()
+        .query_filtered::<&Handle<Mesh>, With<VoxelWaterSurface>>()
+        .get_single(app.world())
+        .expect("one water surface")
+        .clone();
+    let Some(VertexAttributeValues::Float32x3(positions)) = app
+        .world()
+        .resource::<Assets<Mesh>>()
+        .get(&surface)
+        .expect("surface mesh")
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("Surface has positions");
+    };
+    assert_eq!(positions.len(), 4 * 4 * 4, "only the top faces are meshed");
+    assert!(
+        positions
+            .iter()
+            .all(|position| (position[1] + 5.1).abs() < 1e-5
+                && (-8.0..=-4.0).contains(&position[0])
+                && (-8.0..=-4.0).contains(&position[2])),
+        "the surface is centered on the chunk like its mesh"
+    );
+
+    let submerged = app
+        .world()
+        .get::<VoxelSubmersion>(floater)
+        .expect("submersion")
+        .submerged;
+    assert!((submerged - 2.0 / 3.0).abs() < 1e-5);
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_world_streaming() {
+    use crate::{
+        VoxelChunkLoaded, VoxelChunkUnloaded, VoxelWorld, VoxelWorldStreaming, VoxelWorldViewer,
+    };
+    use bevy::ecs::event::Events;
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    // a flat floor one voxel below the origin
+    let streaming = VoxelWorldStreaming::new(|position| {
+        if position.y == -1 {
+            Voxel(1)
+        } else {
+            Voxel::EMPTY
+        }
+    })
+    .with_view_distance(1);
+    app.insert_resource(VoxelWorld::new(context, UVec3::splat(8), 1.0).with_streaming(streaming));
+    let viewer = app
+        .world_mut()
+        .spawn((
+            VoxelWorldViewer::default(),
+            Transform::default(),
+            GlobalTransform::default(),
+        ))
+        .id();
+    let mut loaded = 0;
+    for _ in 0..1000 {
+        app.update();
+        loaded += app
+            .world_mut()
+            .resource_mut::<Events<VoxelChunkLoaded>>()
+            .drain()
+            .count();
+        if loaded == 7 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(loaded, 7, "the chunk under the viewer and its 6 neighbours");
+    let world = app.world().resource::<VoxelWorld>();
+    assert_eq!(world.get_voxel(IVec3::new(3, -1, 3)), Voxel(1));
+    assert!(world.chunk_entity(IVec3::new(0, -1, 0)).is_some());
+    assert!(
+        world.chunk_entity(IVec3::new(0, 1, 0)).is_none(),
+        "empty chunks aren't spawned"
+    );
+
+    // move far away, evicting every chunk
+    *app.world_mut()
+        .get_mut::<GlobalTransform>(viewer)
+        .expect("transform") = GlobalTransform::from_xyz(1000.0, 0.0, 0.0);
+    *app.world_mut()
+        .get_mut::<Transform>(viewer)
+        .expect("transform") = Transform::from_xyz(1000.0, 0.0, 0.0);
+    app.update();
+    let unloaded = app
+        .world_mut()
+        .resource_mut::<Events<VoxelChunkUnloaded>>()
+        .drain()
+        .count();
+    assert_eq!(unloaded, 7);
+    assert_eq!(
+        app.world()
+            .resource::<VoxelWorld>()
+            .get_voxel(IVec3::new(3, -1, 3)),
+        Voxel::EMPTY
+    );
+
+    // return, and edit a chunk while it is being generated
+    *app.world_mut()
+        .get_mut::<GlobalTransform>(viewer)
+        .expect("transform") = GlobalTransform::default();
+    *app.world_mut()
+        .get_mut::<Transform>(viewer)
+        .expect("transform") = Transform::default();
+    app.update();
+    app.world_mut()
+        .resource_mut::<VoxelWorld>()
+        .set_voxel(IVec3::new(3, -2, 3), Voxel(2));
+    let edited = app
+        .world()
+        .resource::<VoxelWorld>()
+        .chunk_coord(IVec3::new(3, -2, 3));
+    let mut loaded = Vec::new();
+    for _ in 0..1000 {
+        app.update();
+        loaded.extend(
+            app.world_mut()
+                .resource_mut::<Events<VoxelChunkLoaded>>()
+                .drain()
+                .map(|event| event.coord),
+        );
+        if loaded.len() == 6 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    // give the edited chunk's generation time to finish too
+    for _ in 0..20 {
+        app.update();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert!(
+        !loaded.contains(&edited),
+        "the edited chunk isn't replaced by the generated one"
+    );
+    assert_eq!(
+        app.world()
+            .resource::<VoxelWorld>()
+            .get_voxel(IVec3::new(3, -2, 3)),
+        Voxel(2),
+        "edits made while a chunk is generated are kept"
+    );
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_clipmap() {
+    use crate::{VoxelClipmap, VoxelClipmapTile};
+    use bevy::math::IVec2;
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    // a flat terrain four voxels high
+    let clipmap = VoxelClipmap::new(context, 1.0, |_| (4, Voxel(3)))
+        .with_levels(2)
+        .with_tile_size(4)
+        .with_rings(1)
+        .with_detail(1.0);
+    app.world_mut()
+        .spawn((clipmap, Transform::default(), GlobalTransform::default()));
+    app.world_mut().spawn((
+        bevy::render::camera::Camera::default(),
+        GlobalTransform::from_translation(Vec3::new(0.0, 10.0, 0.0)),
+    ));
+    let tiles = |app: &mut App| {
+        app.world_mut()
+            .query::<(&VoxelClipmapTile, &Transform)>()
+            .iter(app.world())
+            .map(|(tile, transform)| (*tile, transform.translation))
+            .collect::<Vec<(VoxelClipmapTile, Vec3)>>()
+    };
+    for _ in 0..1000 {
+        app.update();
+        if tiles(&mut app).len() == 21 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    let spawned = tiles(&mut app);
+    // the four coarse tiles around the camera are split into sixteen fine tiles, and the other five stay coarse
+    assert_eq!(spawned.len(), 21);
+    assert_eq!(
+        spawned.iter().filter(|(tile, _)| tile.level == 0).count(),
+        16
+    );
+    let (_, translation) = spawned
+        .iter()
+        .find(|(tile, _)| tile.level == 0 && tile.coord == IVec2::ZERO)
+        .expect("fine tile at the origin");
+    assert_eq!(translation.x, 2.0, "the tile is centered on its columns");
+    assert_eq!(translation.z, 2.0);
+
+    // moving the camera swaps the tiles area by area, without ever leaving a hole or overlapping tiles
+    let mut camera = app
+        .world_mut()
+        .query_filtered::<&mut GlobalTransform, bevy::prelude::With<bevy::render::camera::Camera>>(
+        );
+    *camera.single_mut(app.world_mut()) =
+        GlobalTransform::from_translation(Vec3::new(8.0, 10.0, 0.0));
+    let bounds = |tile: &VoxelClipmapTile| {
+        let width = 4 << tile.level;
+        (tile.coord * width, tile.coord * width + IVec2::splat(width))
+    };
+    for _ in 0..1000 {
+        app.update();
+        let spawned = tiles(&mut app);
+        for column in [IVec2::new(0, 0), IVec2::new(8, 0), IVec2::new(12, -4)] {
+            assert_eq!(
+                spawned
+                    .iter()
+                    .filter(|(tile, _)| {
+                        let (min, max) = bounds(tile);
+                        column.cmpge(min).all() && column.cmplt(max).all()
+                    })
+                    .count(),
+                1,
+                "column {column} is covered by one tile"
+            );
+        }
+        if spawned
+            .iter()
+            .any(|(tile, _)| tile.level == 0 && tile.coord == IVec2::new(3, 0))
+        {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    panic!("Timed out splitting the tiles around the moved camera");
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_voxel_clipmap_skirts() {
+    use crate::{VoxelClipmap, VoxelClipmapTile};
+    use bevy::math::IVec2;
+    let (mut app, _) = load_dice_with_settings(VoxLoaderSettings::default());
+    let context = app
+        .world()
+        .resource::<AssetServer>()
+        .get_handle::<VoxelContext>("test.vox#voxel-context")
+        .expect("voxel context");
+    // a steep slope, falling four voxels with every column along x
+    let clipmap = VoxelClipmap::new(context, 1.0, |column| (-4 * column.x, Voxel(3)))
+        .with_levels(2)
+        .with_tile_size(4)
+        .with_rings(1)
+        .with_detail(1.0);
+    app.world_mut()
+        .spawn((clipmap, Transform::default(), GlobalTransform::default()));
+    app.world_mut().spawn((
+        bevy::render::camera::Camera::default(),
+        GlobalTransform::from_translation(Vec3::new(0.0, 10.0, 0.0)),
+    ));
+    // the fine tile covering columns 4 to 7 along x, next to the coarse tile covering columns 8 to 15
+    let fine_tile = |app: &mut App| {
+        app.world_mut()
+            .query::<(&VoxelClipmapTile, &Transform, &VoxelModelInstance)>()
+            .iter(app.world())
+            .find(|(tile, _, _)| tile.level == 0 && tile.coord == IVec2::new(1, 0))
+            .map(|(_, transform, instance)| (transform.translation, instance.model.clone()))
+    };
+    for _ in 0..1000 {
+        app.update();
+        if let Some((translation, model)) = fine_tile(&mut app) {
+            let height = app
+                .world()
+                .resource::<Assets<VoxelModel>>()
+                .get(&model)
+                .expect("tile model")
+                .size()
+                .y as f32;
+            // the coarse tile's edge column is sampled at x = 9, and is 36 voxels deep
+            assert!(
+                translation.y - height * 0.5 < -36.0,
+                "the wall reaches below the surface of the coarser neighbor"
+            );
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    panic!("Timed out meshing the clipmap");
+}
+
+#[cfg(all(feature = "modify_voxels", feature = "generate_voxels"))]
+#[test]
+fn test_shatter_voxels() {
+    use crate::{
+        ShatterVoxelCommandsExt, VoxelDebris, VoxelDebrisBudget, VoxelDebrisParticle,
+        VoxelDebrisSettings,
+    };
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![bevy::color::palettes::css::GRAY.into()]);
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let mut data = VoxelData::new(UVec3::splat(4), true, 1.0);
+    for x in 0..4 {
+        for y in 0..4 {
+            for z in 0..4 {
+                data.set_voxel(Voxel(1), UVec3::new(x, y, z));
+            }
+        }
+    }
+    let (model, _) =
+        VoxelModel::new(world, data, "wall".to_string(), context.clone()).expect("Add model");
+    let instance = VoxelModelInstance {
+        model: model.clone(),
+        context,
+    };
+    // eight fragments of eight voxels, of which two become particles straight away
+    world.commands().shatter_voxels(
+        instance,
+        &GlobalTransform::default(),
+        Aabb3d::new(Vec3::ZERO, Vec3::splat(2.5)),
+        VoxelDebrisSettings::default()
+            .with_fragment_size(2)
+            .with_max_fragments(6),
+    );
+    world.flush();
+    let count = |app: &mut App| {
+        let fragments = app
+            .world_mut()
+            .query::<&VoxelDebris>()
+            .iter(app.world())
+            .count();
+        let particles = app
+            .world_mut()
+            .query::<&VoxelDebrisParticle>()
+            .iter(app.world())
+            .count();
+        (fragments, particles)
+    };
+    assert_eq!(count(&mut app), (6, 2));
+    assert_eq!(
+        app.world()
+            .resource::<Assets<VoxelModel>>()
+            .get(&model)
+            .expect("wall")
+            .count_voxels(),
+        0,
+        "the shattered voxels are removed"
+    );
+    for debris in app.world_mut().query::<&VoxelDebris>().iter(app.world()) {
+        assert_eq!(debris.voxel_count, 8);
+        assert!(debris.velocity.length() > 0.0, "fragments fly apart");
+    }
+    app.world_mut()
+        .resource_mut::<VoxelDebrisBudget>()
+        .max_fragments = 4;
+    app.update();
+    assert_eq!(
+        count(&mut app),
+        (4, 4),
+        "the fragments over budget are merged"
+    );
+}
+
+async fn setup_and_load_voxel_scene(app: &mut App, filename: &'static str) -> Handle<Scene> {
+    setup_app(app);
+    let assets = app.world().resource::<AssetServer>();
+    assets
+        .load_untyped_async(filename)
+        .await
+        .expect(format!("Loaded {filename}").as_str())
+        .typed::<Scene>()
+}
+
+fn setup_app(app: &mut App) {
+    setup_app_with_settings(app, None);
+}
+
+fn setup_app_with_settings(app: &mut App, global_settings: Option<VoxLoaderSettings>) {
+    app.add_plugins((
         MinimalPlugins,
         AssetPlugin::default(),
         ImagePlugin::default(),
         ScenePlugin,
         HierarchyPlugin,
-        VoxScenePlugin::default(),
+        VoxScenePlugin { global_settings },
     ))
     .init_asset::<StandardMaterial>()
     .init_asset::<Mesh>()
@@ -385,3 +5973,161 @@ fn setup_app(app: &mut App) {
     .register_type::<Transform>()
     .register_type::<GlobalTransform>();
 }
+
+#[cfg(feature = "generate_voxels")]
+#[test]
+fn test_compress_voxel_model() {
+    let mut app = App::new();
+    setup_app(&mut app);
+    let palette = VoxelPalette::from_colors(vec![
+        bevy::color::palettes::css::GREEN.into(),
+        bevy::color::palettes::css::RED.into(),
+    ]);
+    let palette_for_occlusion = palette.clone();
+    let mut data = SDF::cuboid(Vec3::splat(6.0)).voxelize(UVec3::splat(16), 1.0, Voxel(1));
+    data.set_voxel(Voxel(2), UVec3::new(8, 8, 8));
+    let world = app.world_mut();
+    let context = VoxelContext::new(world, palette);
+    let (_, model) = VoxelModel::new(world, data, "box".to_string(), context).expect("Add model");
+    let mut compressed = model.clone();
+    compressed.compress();
+    assert!(compressed.is_compressed());
+    assert!(compressed.memory_usage() < model.memory_usage());
+    assert_eq!(compressed.content_hash(), model.content_hash());
+    assert_eq!(compressed.count_voxels(), model.count_voxels());
+    for position in [
+        IVec3::ZERO,
+        IVec3::splat(8),
+        IVec3::new(7, 8, 8),
+        IVec3::splat(15),
+    ] {
+        assert_eq!(
+            compressed.get_voxel_at_point(position),
+            model.get_voxel_at_point(position)
+        );
+    }
+    assert!(compressed.get_voxel_at_point(IVec3::splat(16)).is_err());
+    // reads that walk the whole model see the same voxels as the uncompressed model
+    assert_eq!(
+        compressed.data.downsampled(2).content_hash(),
+        model.data.downsampled(2).content_hash()
+    );
+    assert_eq!(compressed.collider_boxes(), model.collider_boxes());
+    assert_eq!(
+        compressed.data.chunk_occlusion(&palette_for_occlusion, 4),
+        model.data.chunk_occlusion(&palette_for_occlusion, 4)
+    );
+    assert_eq!(
+        compressed.data.iter_surface().count(),
+        model.data.iter_surface().count()
+    );
+    assert!(compressed.is_compressed(), "reading doesn't decompress");
+
+    let mut buffer = vec![Voxel(3)];
+    compressed.decompress_into(&mut buffer);
+    assert_eq!(buffer.len(), 16 * 16 * 16);
+    assert_eq!(buffer[8 + 16 * (8 + 16 * 8)], Voxel(2));
+    assert_eq!(buffer[0], Voxel::EMPTY);
+    assert_eq!(
+        buffer
+            .iter()
+            .filter(|voxel| **voxel != Voxel::EMPTY)
+            .count(),
+        model.count_voxels()
+    );
+
+    compressed.decompress();
+    assert!(!compressed.is_compressed());
+    assert_eq!(compressed.data.voxels, model.data.voxels);
+}
+
+#[test]
+fn test_bitmask_visible_faces_match_block_mesh() {
+    use crate::model::{bitmask::VoxelOccupancy, voxel::VisibleVoxel};
+    use block_mesh::{
+        greedy_quads, visible_block_faces, GreedyQuadsBuffer, UnitQuadBuffer, VoxelVisibility,
+        RIGHT_HANDED_Y_UP_CONFIG,
+    };
+    use ndshape::RuntimeShape;
+    let mut rng = VoxelRng::from_seed(7);
+    // wide enough for rows to span several words, with empty space around the voxels
+    let shape = RuntimeShape::<u32, 3>::new([140, 7, 6]);
+    let voxels: Vec<VisibleVoxel> = (0..shape.size())
+        .map(|index| {
+            let [x, _, _] = shape.delinearize(index);
+            let (index, visibility) = if !(20..130).contains(&x) {
+                (255, VoxelVisibility::Empty)
+            } else {
+                match rng.below(4) {
+                    0 => (255, VoxelVisibility::Empty),
+                    1 => (1, VoxelVisibility::Translucent),
+                    value => (value as u8, VoxelVisibility::Opaque),
+                }
+            };
+            VisibleVoxel { index, visibility }
+        })
+        .collect();
+    let max = shape.as_array().map(|x| x - 1);
+    let faces = &RIGHT_HANDED_Y_UP_CONFIG.faces;
+    let occupancy = VoxelOccupancy::new(&voxels, &shape);
+
+    let mut expected = UnitQuadBuffer::new();
+    visible_block_faces(&voxels, &shape, [0; 3], max, faces, &mut expected);
+    let mut actual = UnitQuadBuffer::new();
+    occupancy.visible_faces(&mut actual);
+    assert!(expected.groups.iter().all(|group| !group.is_empty()));
+    for (expected, actual) in expected.groups.iter().zip(actual.groups.iter()) {
+        let minimum = |group: &Vec<block_mesh::UnorientedUnitQuad>| {
+            group.iter().map(|quad| quad.minimum).collect::<Vec<_>>()
+        };
+        assert_eq!(minimum(actual), minimum(expected));
+    }
+
+    assert_eq!(occupancy.bounds(), Some(([20, 0, 0], [129, 6, 5])));
+    let assert_greedy_quads_match = |voxels: &[VisibleVoxel]| {
+        let mut expected = GreedyQuadsBuffer::new(shape.size() as usize);
+        greedy_quads(voxels, &shape, [0; 3], max, faces, &mut expected);
+        let actual = VoxelOccupancy::new(voxels, &shape).greedy_quads(voxels, &shape);
+        assert!(expected.quads.groups.iter().all(|group| !group.is_empty()));
+        for (expected, actual) in expected.quads.groups.iter().zip(actual.iter()) {
+            let quads = |group: &Vec<block_mesh::UnorientedQuad>| {
+                group
+                    .iter()
+                    .map(|quad| (quad.minimum, quad.width, quad.height))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(quads(actual), quads(expected));
+        }
+    };
+    assert_greedy_quads_match(&voxels);
+    // layers of identical voxels with holes punched through them, so that quads grow in both directions
+    let layered: Vec<VisibleVoxel> = (0..shape.size())
+        .map(|index| {
+            let [x, y, z] = shape.delinearize(index);
+            let visibility = if (20..130).contains(&x) && (x + z) % 37 != 0 {
+                VoxelVisibility::Opaque
+            } else {
+                VoxelVisibility::Empty
+            };
+            VisibleVoxel {
+                index: (y / 3) as u8,
+                visibility,
+            }
+        })
+        .collect();
+    assert_greedy_quads_match(&layered);
+
+    let empty = vec![
+        VisibleVoxel {
+            index: 255,
+            visibility: VoxelVisibility::Empty
+        };
+        shape.size() as usize
+    ];
+    let occupancy = VoxelOccupancy::new(&empty, &shape);
+    assert!(occupancy.bounds().is_none());
+    assert!(occupancy
+        .greedy_quads(&empty, &shape)
+        .iter()
+        .all(Vec::is_empty));
+}
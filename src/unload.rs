@@ -0,0 +1,96 @@
+use bevy::{
+    asset::AssetId,
+    ecs::{
+        entity::Entity,
+        system::Commands,
+        world::{Command, World},
+    },
+    hierarchy::{Children, DespawnRecursiveExt},
+    scene::{SceneInstance, SceneSpawner},
+    utils::HashSet,
+};
+
+use crate::{model::lod::VoxelLodMeshes, VoxelModel, VoxelModelInstance, VoxelWorldIndex};
+
+/// Extension to [`Commands`] for unloading spawned `.vox` scenes, such as when switching levels
+pub trait DespawnVoxelSceneCommandsExt {
+    /// Despawns `root` and its descendants, and forgets the scene instance spawned on it by the
+    /// [`SceneSpawner`].
+    ///
+    /// With `release_assets`, the strong handles that the plugin created while the scene was spawned are dropped
+    /// straight away, rather than being kept for later instances: the entries of the [`VoxelWorldIndex`], and the
+    /// impostor meshes of [`crate::VoxelLod`] and ray-marched instances of every model that no instance outside the
+    /// scene still uses. Models shared with other spawned scenes keep their cached meshes.
+    ///
+    /// After that, the plugin holds no handle to the scene's assets, so they stay resident exactly as long as handles
+    /// held elsewhere, such as the [`bevy::asset::Handle`] returned by [`bevy::asset::AssetServer::load`], and are
+    /// unloaded once those are dropped.
+    ///
+    /// ### Arguments
+    /// * `root` - the entity at the top of the hierarchy, such as the entity holding a [`bevy::scene::SceneBundle`]
+    /// * `release_assets` - whether to drop the handles that the plugin keeps for the scene's models
+    fn despawn_voxel_scene(&mut self, root: Entity, release_assets: bool) -> &mut Self;
+}
+
+impl DespawnVoxelSceneCommandsExt for Commands<'_, '_> {
+    fn despawn_voxel_scene(&mut self, root: Entity, release_assets: bool) -> &mut Self {
+        self.add(DespawnVoxelScene {
+            root,
+            release_assets,
+        });
+        self
+    }
+}
+
+struct DespawnVoxelScene {
+    root: Entity,
+    release_assets: bool,
+}
+
+impl Command for DespawnVoxelScene {
+    fn apply(self, world: &mut World) {
+        let Some(root) = world.get_entity(self.root) else {
+            return;
+        };
+        let instance = root.get::<SceneInstance>().map(|instance| **instance);
+        let mut entities = Vec::new();
+        let mut models: HashSet<AssetId<VoxelModel>> = HashSet::new();
+        let mut stack = vec![self.root];
+        while let Some(entity) = stack.pop() {
+            let Some(entity_ref) = world.get_entity(entity) else {
+                continue;
+            };
+            if let Some(instance) = entity_ref.get::<VoxelModelInstance>() {
+                entities.push(entity);
+                models.insert(instance.model.id());
+            }
+            if let Some(children) = entity_ref.get::<Children>() {
+                stack.extend(children.iter().copied());
+            }
+        }
+        if let Some(instance) = instance {
+            world
+                .resource_mut::<SceneSpawner>()
+                .despawn_instance(instance);
+        }
+        world.entity_mut(self.root).despawn_recursive();
+        if !self.release_assets {
+            return;
+        }
+        let mut index = world.resource_mut::<VoxelWorldIndex>();
+        for entity in entities {
+            index.remove(entity);
+        }
+        // models that are still instanced elsewhere keep their caches
+        for instance in world.query::<&VoxelModelInstance>().iter(world) {
+            models.remove(&instance.model.id());
+        }
+        world.resource_mut::<VoxelLodMeshes>().release(&models);
+        #[cfg(feature = "raymarch")]
+        if let Some(mut cache) =
+            world.get_resource_mut::<crate::model::raymarch::VoxelRaymarchCache>()
+        {
+            cache.release(&models);
+        }
+    }
+}
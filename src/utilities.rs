@@ -1,3 +1,8 @@
+//! Helpers for prototyping voxel scenes, as used by the examples: a [`PanOrbitCamera`] for inspecting models with the
+//! mouse, and a [`VoxelSceneSwitcher`] for cycling through several scenes with the keyboard.
+//!
+//! Enable the `utilities` feature to use them.
+
 use bevy::{
     input::mouse::{MouseMotion, MouseWheel},
     prelude::*,
@@ -9,7 +14,10 @@ use bevy::{
 pub struct PanOrbitCamera {
     /// The "focus point" to orbit around. It is automatically updated when panning the camera
     pub focus: Vec3,
+    /// The distance from the focus point. It is set from the camera's initial translation when the camera is spawned,
+    /// and updated when zooming
     pub radius: f32,
+    /// Whether the camera is upside down, in which case horizontal orbiting is inverted
     pub upside_down: bool,
 }
 
@@ -23,6 +31,8 @@ impl Default for PanOrbitCamera {
     }
 }
 
+/// Adds the systems that move every [`PanOrbitCamera`]: pan with the right mouse button, orbit with the left mouse
+/// button, and zoom with the scroll wheel.
 pub struct PanOrbitCameraPlugin;
 
 impl Plugin for PanOrbitCameraPlugin {
@@ -133,6 +143,98 @@ fn pan_orbit_camera(
 
 fn get_primary_window_size(window_query: &Query<&Window, With<PrimaryWindow>>) -> Vec2 {
     let window = window_query.get_single().expect("no window found");
-    let window = Vec2::new(window.width() as f32, window.height() as f32);
-    window
+    Vec2::new(window.width(), window.height())
+}
+
+/// A list of scenes to cycle through with the keyboard, for comparing several `.vox` files or settings without
+/// restarting. Add the resource along with the [`VoxelSceneSwitcherPlugin`].
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_vox_scene::{utilities::*, VoxScenePlugin};
+/// App::new()
+///     .add_plugins((DefaultPlugins, VoxScenePlugin::default(), VoxelSceneSwitcherPlugin))
+///     .insert_resource(VoxelSceneSwitcher::new(["study.vox", "study.vox#tank"]))
+///     .run();
+/// ```
+#[derive(Resource, Clone, Debug)]
+pub struct VoxelSceneSwitcher {
+    /// The asset paths of the scenes
+    pub scenes: Vec<String>,
+    /// The key that switches to the next scene. Defaults to [`KeyCode::Tab`]
+    pub next: KeyCode,
+    /// The key that switches to the previous scene. Defaults to [`KeyCode::Backquote`]
+    pub previous: KeyCode,
+    current: usize,
+    spawned: bool,
+}
+
+impl VoxelSceneSwitcher {
+    /// Creates a switcher that starts with the first of the supplied scenes
+    pub fn new(scenes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            scenes: scenes.into_iter().map(Into::into).collect(),
+            next: KeyCode::Tab,
+            previous: KeyCode::Backquote,
+            current: 0,
+            spawned: false,
+        }
+    }
+
+    /// The asset path of the scene being shown
+    pub fn current(&self) -> Option<&str> {
+        self.scenes.get(self.current).map(|scene| scene.as_str())
+    }
+}
+
+/// Marks the scene spawned by the [`VoxelSceneSwitcher`]
+#[derive(Component)]
+pub struct SwitchedVoxelScene;
+
+/// Spawns the current scene of the [`VoxelSceneSwitcher`] resource, and switches scenes when its keys are pressed.
+pub struct VoxelSceneSwitcherPlugin;
+
+impl Plugin for VoxelSceneSwitcherPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            switch_scenes.run_if(resource_exists::<VoxelSceneSwitcher>),
+        );
+    }
+}
+
+fn switch_scenes(
+    mut commands: Commands,
+    mut switcher: ResMut<VoxelSceneSwitcher>,
+    keys: Res<ButtonInput<KeyCode>>,
+    assets: Res<AssetServer>,
+    spawned: Query<Entity, With<SwitchedVoxelScene>>,
+) {
+    let count = switcher.scenes.len();
+    if count == 0 {
+        return;
+    }
+    let current = if keys.just_pressed(switcher.next) {
+        (switcher.current + 1) % count
+    } else if keys.just_pressed(switcher.previous) {
+        (switcher.current + count - 1) % count
+    } else if switcher.spawned {
+        return;
+    } else {
+        switcher.current
+    };
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    switcher.current = current;
+    switcher.spawned = true;
+    let path = switcher.scenes[current].clone();
+    info!("Showing {path}");
+    commands.spawn((
+        SceneBundle {
+            scene: assets.load(path),
+            ..default()
+        },
+        SwitchedVoxelScene,
+    ));
 }